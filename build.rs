@@ -0,0 +1,27 @@
+//! Bakes build information into the binary for `solscrape --version --verbose`.
+//!
+//! Every value is optional at runtime (read with `option_env!`), so a failure
+//! to determine one here only leaves it out of the output.
+
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+
+    if let Ok(target) = env::var("TARGET") {
+        println!("cargo:rustc-env=SOLSCRAPE_BUILD_TARGET={}", target);
+    }
+    if let Ok(profile) = env::var("PROFILE") {
+        println!("cargo:rustc-env=SOLSCRAPE_BUILD_PROFILE={}", profile);
+    }
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Ok(output) = Command::new(rustc).arg("--version").output() {
+        let version = String::from_utf8_lossy(&output.stdout);
+        if output.status.success() && !version.trim().is_empty() {
+            println!("cargo:rustc-env=SOLSCRAPE_BUILD_RUSTC={}", version.trim());
+        }
+    }
+}
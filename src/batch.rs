@@ -0,0 +1,385 @@
+//! The source list of `--from-file`.
+//!
+//! Each non-blank line that does not start with `#` names one source and,
+//! optionally, options for it alone, in the syntax of the command line:
+//!
+//! ```text
+//! # core at the develop branch, with its libraries
+//! https://github.com/x/core.git --branch develop -o core --include-lib
+//! './vendored/periphery' --local -o "periphery v2"
+//! ```
+//!
+//! A line is split into words the way a POSIX shell would split it, without
+//! expanding anything: single quotes keep everything up to the next single
+//! quote, double quotes keep everything but a backslash escaping `"` or
+//! `\`, and outside quotes a backslash keeps the next character. The words
+//! are then parsed with the command-line option table, so per-line options
+//! are exactly the command-line ones, less [`GLOBAL_FLAGS`].
+//!
+//! Each source scraped is recorded in a [`Resume`] file as soon as it is
+//! done, so a batch that is stopped, fails, or is killed can be continued
+//! with `--resume`. There is no signal handling (the crate forbids `unsafe`
+//! and has no dependencies): Ctrl-C kills the run at once, and a stop is
+//! asked for instead by creating [`STOP_FILE`], which is checked between
+//! sources.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::checksum;
+
+/// The file, in the destination, that records the sources already scraped.
+pub const RESUME_FILE: &str = ".solscrape-resume";
+
+/// The file that, created in the destination, stops a batch before its next
+/// source.
+pub const STOP_FILE: &str = ".solscrape-stop";
+
+/// Options that apply to the whole run and so cannot be given per line.
+pub const GLOBAL_FLAGS: [&str; 20] = [
+    "-h",
+    "--help",
+    "-v",
+    "--version",
+    "--version-full",
+    "-q",
+    "--quiet",
+    "--verbose",
+    "--ascii",
+    "--log-file",
+    "--output-mode",
+    "--metrics-file",
+    "--metrics-stdout",
+    "--no-subprocess",
+    "--deadline",
+    "--list-files",
+    "-0",
+    "--from-file",
+    "--resume",
+    "--run-dir",
+];
+
+/// The [`GLOBAL_FLAGS`] that take a value.
+const GLOBAL_VALUES: [&str; 4] = [
+    "--log-file",
+    "--output-mode",
+    "--metrics-file",
+    "--deadline",
+];
+
+/// One source of the list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The 1-based line number, for messages.
+    pub line: usize,
+    /// The words of the line: the source and its options, in any order.
+    pub words: Vec<String>,
+}
+
+/// Splits `line` into words with shell-like quoting.
+///
+/// # Errors
+///
+/// | Condition | Message |
+/// |-----------|---------|
+/// | A quote is not closed | `"unterminated {'|"} quote"` |
+/// | The line ends in a lone backslash | `"trailing backslash"` |
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(tokenize(r#"./a -o "my name""#)?, ["./a", "-o", "my name"]);
+/// ```
+pub fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err("unterminated ' quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let quoted = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => quoted.push(c),
+                            Some(c) => quoted.extend(['\\', c]),
+                            None => return Err("unterminated \" quote".to_string()),
+                        },
+                        Some(c) => quoted.push(c),
+                        None => return Err("unterminated \" quote".to_string()),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err("trailing backslash".to_string()),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Parses a source list into its entries, skipping blank lines and
+/// comments.
+///
+/// # Errors
+///
+/// Returns the line number and the [`tokenize`] error of the first
+/// malformed line.
+pub fn parse_list(text: &str) -> Result<Vec<Entry>, (usize, String)> {
+    let mut entries = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let words = tokenize(trimmed).map_err(|e| (index + 1, e))?;
+        entries.push(Entry {
+            line: index + 1,
+            words,
+        });
+    }
+    Ok(entries)
+}
+
+/// The first word of `words` that is a [`GLOBAL_FLAGS`] option.
+pub fn global_flag(words: &[String]) -> Option<&str> {
+    words
+        .iter()
+        .map(String::as_str)
+        .find(|word| GLOBAL_FLAGS.contains(word))
+}
+
+/// The record of a batch's finished sources, kept in [`RESUME_FILE`].
+///
+/// The file starts with a fingerprint of the command-line options that
+/// change what is scraped, and lists one key per finished source, from the
+/// fingerprint and the line's words. A source whose line or whose
+/// command-line options changed therefore has another key and is scraped
+/// again; options that only change what is reported, such as `--verbose`,
+/// do not count.
+#[derive(Debug)]
+pub struct Resume {
+    /// Where the record is kept.
+    path: PathBuf,
+    /// The fingerprint of the run's options.
+    run: String,
+    /// The keys of the sources already scraped.
+    done: BTreeSet<String>,
+}
+
+impl Resume {
+    /// Starts the record in `dir` for a run with the command-line `options`.
+    ///
+    /// With `resume`, the sources an earlier run with the same options
+    /// finished are kept; otherwise, or when the options differ, the record
+    /// starts empty.
+    ///
+    /// # Returns
+    ///
+    /// The record, and a note when an existing file was not used.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to write {path}: ..."` when the file cannot be
+    /// written.
+    pub fn open(
+        dir: &Path,
+        options: &[String],
+        resume: bool,
+    ) -> Result<(Self, Option<String>), String> {
+        let path = dir.join(RESUME_FILE);
+        let run = fingerprint(options);
+        let mut note = None;
+        let mut done = BTreeSet::new();
+        if resume {
+            match fs::read_to_string(&path) {
+                Ok(text) if text.lines().nth(1) == Some(&format!("run {}", run)) => {
+                    done = text
+                        .lines()
+                        .filter_map(|line| line.strip_prefix("done "))
+                        .filter_map(|line| line.split(' ').next())
+                        .map(str::to_string)
+                        .collect();
+                }
+                Ok(_) => {
+                    note = Some(format!(
+                        "Ignoring {}: it was written with other options; scraping every source",
+                        path.display()
+                    ));
+                }
+                Err(_) => {
+                    note = Some(format!(
+                        "No {} to resume from; scraping every source",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        let record = Self { path, run, done };
+        let mut text = format!(
+            "# solscrape --from-file progress; delete to start over\nrun {}\n",
+            record.run
+        );
+        for key in &record.done {
+            text.push_str(&format!("done {}\n", key));
+        }
+        fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&record.path, text))
+            .map_err(|e| format!("Failed to write {}: {}", record.path.display(), e))?;
+        Ok((record, note))
+    }
+
+    /// The key of `entry` under this run.
+    fn key(&self, entry: &Entry) -> String {
+        let mut words = vec![self.run.as_str()];
+        words.extend(entry.words.iter().map(String::as_str));
+        checksum::sha256(words.join("\0").as_bytes())
+    }
+
+    /// Whether an earlier run finished `entry`.
+    pub fn is_done(&self, entry: &Entry) -> bool {
+        self.done.contains(&self.key(entry))
+    }
+
+    /// Records that `entry`, scraping `source`, is finished.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to write {path}: ..."`.
+    pub fn record(&mut self, entry: &Entry, source: &str) -> Result<(), String> {
+        let key = self.key(entry);
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&self.path)
+            .and_then(|mut file| writeln!(file, "done {} {}", key, source))
+            .map_err(|e| format!("Failed to write {}: {}", self.path.display(), e))?;
+        self.done.insert(key);
+        Ok(())
+    }
+
+    /// Removes the record once every source is done.
+    pub fn finish(self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The fingerprint of the command-line `options`, without the
+/// [`GLOBAL_FLAGS`] and their values.
+fn fingerprint(options: &[String]) -> String {
+    let mut kept = Vec::new();
+    let mut words = options.iter();
+    while let Some(word) = words.next() {
+        if GLOBAL_VALUES.contains(&word.as_str()) {
+            words.next();
+        } else if !GLOBAL_FLAGS.contains(&word.as_str()) {
+            kept.push(word.as_str());
+        }
+    }
+    checksum::sha256(kept.join("\0").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies the quoting rules and their errors.
+    #[test]
+    fn test_tokenize_quoting() {
+        let cases: [(&str, &[&str]); 6] = [
+            ("a  b\tc", &["a", "b", "c"]),
+            (r#"-o "my name""#, &["-o", "my name"]),
+            (r"-o 'it''s'", &["-o", "its"]),
+            (r#"'a "b" \c'"#, &[r#"a "b" \c"#]),
+            (r#""say \"hi\" \n""#, &[r#"say "hi" \n"#]),
+            (r"a\ b '' x", &["a b", "", "x"]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(tokenize(line).unwrap(), expected, "{}", line);
+        }
+        assert_eq!(tokenize("-o 'core").unwrap_err(), "unterminated ' quote");
+        assert_eq!(
+            tokenize(r#"-o "core\""#).unwrap_err(),
+            "unterminated \" quote"
+        );
+        assert_eq!(tokenize(r"core\").unwrap_err(), "trailing backslash");
+    }
+
+    /// Verifies that comments and blank lines are skipped with line numbers
+    /// kept, and that a malformed line reports its number.
+    #[test]
+    fn test_parse_list() {
+        let text = "# sources\n\nhttps://x/core.git --branch develop\n  ./b --local -o 'b v2'\n";
+        let entries = parse_list(text).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].line, 3);
+        assert_eq!(
+            entries[0].words,
+            ["https://x/core.git", "--branch", "develop"]
+        );
+        assert_eq!(entries[1].line, 4);
+        assert_eq!(entries[1].words, ["./b", "--local", "-o", "b v2"]);
+
+        let error = parse_list("./a\n./b -o \"b\n").unwrap_err();
+        assert_eq!(error, (2, "unterminated \" quote".to_string()));
+        assert_eq!(global_flag(&entries[1].words), None);
+        let words = tokenize("./c --quiet -q").unwrap();
+        assert_eq!(global_flag(&words), Some("--quiet"));
+    }
+
+    /// Verifies that finished sources survive a resume with the same
+    /// options only, that reporting flags do not count, and that a changed
+    /// line is scraped again.
+    #[test]
+    fn test_resume() {
+        let dir = std::env::temp_dir().join(format!("solscrape_resume_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let options = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let entry = |line: &str| Entry {
+            line: 1,
+            words: tokenize(line).unwrap(),
+        };
+        let (core, oracle) = (entry("core --include-lib"), entry("oracle"));
+
+        let run = options(&["--include-test"]);
+        let (mut resume, note) = Resume::open(&dir, &run, true).unwrap();
+        assert!(note.unwrap().starts_with("No "));
+        resume.record(&core, "core").unwrap();
+        assert!(resume.is_done(&core) && !resume.is_done(&oracle));
+
+        let verbose = options(&["--verbose", "--include-test", "--log-file", "x.log"]);
+        let (resume, note) = Resume::open(&dir, &verbose, true).unwrap();
+        assert_eq!(note, None);
+        assert!(resume.is_done(&core) && !resume.is_done(&oracle));
+        assert!(!resume.is_done(&entry("core")));
+
+        let (resume, note) = Resume::open(&dir, &options(&["--include-lib"]), true).unwrap();
+        assert!(note.unwrap().starts_with("Ignoring "));
+        assert!(!resume.is_done(&core));
+
+        let (mut resume, _) = Resume::open(&dir, &run, false).unwrap();
+        assert!(!resume.is_done(&core));
+        resume.record(&oracle, "oracle").unwrap();
+        let (resume, _) = Resume::open(&dir, &run, true).unwrap();
+        assert!(resume.is_done(&oracle) && !resume.is_done(&core));
+        resume.finish();
+        assert!(!dir.join(RESUME_FILE).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
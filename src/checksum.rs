@@ -0,0 +1,294 @@
+//! SHA-256 checksums of consolidated output (`--checksum-file`, `verify`).
+//!
+//! A consolidated file handed to audit participants can be checked against
+//! a sidecar next to it, `{output}.sha256`, holding one line in the format
+//! `sha256sum` writes and reads: the hex digest, two spaces, and the file
+//! name. `solscrape verify` (see [`verify`]) and `sha256sum -c` both accept
+//! it. The digest is computed by [`sha256`], a plain implementation of FIPS
+//! 180-4, since solscrape has no dependencies.
+//!
+//! The same digest, taken over the cleaned code with whitespace differences
+//! ignored ([`content_digest`]), tells copies of one file apart from
+//! near-copies when reporting duplicates.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The extension appended to the output's file name for its sidecar.
+pub const SIDECAR_EXTENSION: &str = "sha256";
+
+/// The first 32 bits of the fractional parts of the cube roots of the first
+/// 64 primes.
+const ROUND_CONSTANTS: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+/// The first 32 bits of the fractional parts of the square roots of the
+/// first 8 primes.
+const INITIAL_STATE: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// Returns the SHA-256 digest of `bytes` as lowercase hex.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     sha256(b"abc"),
+///     "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+/// );
+/// ```
+pub fn sha256(bytes: &[u8]) -> String {
+    let mut padded = bytes.to_vec();
+    padded.push(0x80);
+    while padded.len() % 64 != 56 {
+        padded.push(0);
+    }
+    padded.extend_from_slice(&((bytes.len() as u64).wrapping_mul(8)).to_be_bytes());
+
+    let mut state = INITIAL_STATE;
+    for block in padded.chunks_exact(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16]
+                .wrapping_add(s0)
+                .wrapping_add(w[i - 7])
+                .wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h
+                .wrapping_add(s1)
+                .wrapping_add(choice)
+                .wrapping_add(ROUND_CONSTANTS[i])
+                .wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            (h, g, f, e, d, c, b, a) = (g, f, e, d.wrapping_add(t1), c, b, a, t1.wrapping_add(t2));
+        }
+        for (word, add) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(add);
+        }
+    }
+    state.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+/// Returns the SHA-256 digest of `code` with whitespace that does not
+/// separate two words removed and every other run of whitespace made one
+/// space, so re-indented or re-wrapped copies of a file share a digest.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     content_digest("uint a = 1;\n"),
+///     content_digest("\tuint  a=1 ;")
+/// );
+/// ```
+pub fn content_digest(code: &str) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let mut normalized = String::with_capacity(code.len());
+    let mut gap = false;
+    for c in code.chars() {
+        if c.is_whitespace() {
+            gap = true;
+            continue;
+        }
+        if gap && is_word(c) && normalized.ends_with(is_word) {
+            normalized.push(' ');
+        }
+        gap = false;
+        normalized.push(c);
+    }
+    sha256(normalized.as_bytes())
+}
+
+/// Returns the sidecar path for `output`: its full name plus
+/// `.`[`SIDECAR_EXTENSION`].
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     sidecar_path(Path::new("out/repo_scraped.sol")),
+///     PathBuf::from("out/repo_scraped.sol.sha256")
+/// );
+/// ```
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Writes the sidecar of `output`, returning its path and the digest.
+///
+/// # Errors
+///
+/// Returns `"Failed to read {output}: ..."` or
+/// `"Failed to write {sidecar}: ..."`.
+pub fn write_sidecar(output: &Path) -> Result<(PathBuf, String), String> {
+    let bytes =
+        fs::read(output).map_err(|e| format!("Failed to read {}: {}", output.display(), e))?;
+    let digest = sha256(&bytes);
+    let name = output
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let sidecar = sidecar_path(output);
+    fs::write(&sidecar, format!("{}  {}\n", digest, name))
+        .map_err(|e| format!("Failed to write {}: {}", sidecar.display(), e))?;
+    Ok((sidecar, digest))
+}
+
+/// Reads the digest from a sidecar's text: the first field of its first
+/// line, which must be 64 hex digits.
+fn parse_sidecar(text: &str) -> Option<String> {
+    let digest = text.lines().next()?.split_whitespace().next()?;
+    (digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit()))
+        .then(|| digest.to_ascii_lowercase())
+}
+
+/// Checks `file` against its sidecar, returning the digest when they match.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"No checksum file at {sidecar} ..."` | The sidecar is missing |
+/// | `"{sidecar} does not hold a SHA-256 digest"` | The sidecar is malformed |
+/// | `"Checksum mismatch for {file}: ..."` | The digests differ; the message holds both |
+/// | `"Failed to read {path}: ..."` | Either file cannot be read |
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let digest = verify(Path::new("out/repo_scraped.sol"))?;
+/// ```
+pub fn verify(file: &Path) -> Result<String, String> {
+    let sidecar = sidecar_path(file);
+    let text = match fs::read_to_string(&sidecar) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(format!(
+                "No checksum file at {}; scrape with --checksum-file to write one",
+                sidecar.display()
+            ));
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", sidecar.display(), e)),
+    };
+    let expected = parse_sidecar(&text)
+        .ok_or_else(|| format!("{} does not hold a SHA-256 digest", sidecar.display()))?;
+    let bytes = fs::read(file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+    let actual = sha256(&bytes);
+    if actual == expected {
+        Ok(actual)
+    } else {
+        Err(format!(
+            "Checksum mismatch for {} ({} bytes):\n  expected {} (from {})\n  actual   {}",
+            file.display(),
+            bytes.len(),
+            expected,
+            sidecar.display(),
+            actual
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies the digest against the FIPS 180-4 examples and block edges.
+    #[test]
+    fn test_sha256() {
+        assert_eq!(
+            sha256(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            sha256(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1"
+        );
+        assert_eq!(
+            sha256(&[b'a'; 1000]),
+            "41edece42d63e8d9bf515a9ba6932e1c20cbc9f5a5d134645adb5db1b9737ea3"
+        );
+    }
+
+    /// Verifies that only whitespace between words survives normalization.
+    #[test]
+    fn test_content_digest() {
+        let digest = content_digest("contract A {\n    uint a = 1;\n}\n");
+        assert_eq!(content_digest("contract A{\n\tuint  a=1 ;}"), digest);
+        assert_eq!(content_digest("\r\ncontract   A {uint a = 1;}"), digest);
+        assert_ne!(content_digest("contractA {\n    uint a = 1;\n}\n"), digest);
+        assert_ne!(content_digest("contract A {\n    uint a = 2;\n}\n"), digest);
+    }
+
+    /// Verifies writing and checking a sidecar, and that one changed byte
+    /// is caught.
+    #[test]
+    fn test_verify() {
+        let dir = std::env::temp_dir().join(format!("solscrape_checksum_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("repo_scraped.sol");
+        fs::write(&output, "contract A {}\n").unwrap();
+
+        assert!(
+            verify(&output)
+                .unwrap_err()
+                .starts_with("No checksum file at ")
+        );
+        let (sidecar, digest) = write_sidecar(&output).unwrap();
+        assert_eq!(sidecar, dir.join("repo_scraped.sol.sha256"));
+        assert_eq!(
+            fs::read_to_string(&sidecar).unwrap(),
+            format!("{}  repo_scraped.sol\n", digest)
+        );
+        assert_eq!(verify(&output).unwrap(), digest);
+
+        fs::write(&output, "contract B {}\n").unwrap();
+        let error = verify(&output).unwrap_err();
+        assert!(
+            error.starts_with(&format!(
+                "Checksum mismatch for {} (14 bytes):\n  expected {}",
+                output.display(),
+                digest
+            )),
+            "{error}"
+        );
+        assert!(error.ends_with(&format!("actual   {}", sha256(b"contract B {}\n"))));
+
+        fs::write(&sidecar, "not a digest\n").unwrap();
+        assert!(
+            verify(&output)
+                .unwrap_err()
+                .ends_with("does not hold a SHA-256 digest")
+        );
+        assert_eq!(parse_sidecar(&digest.to_uppercase()), Some(digest));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
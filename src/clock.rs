@@ -0,0 +1,147 @@
+//! Minimal wall-clock formatting helpers.
+//!
+//! The standard library exposes [`SystemTime`] but no calendar conversion, so
+//! this module implements the small amount of civil-date arithmetic solscrape
+//! needs for timestamps in logs and generated paths. All times are UTC.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Parses a duration such as `90s`, `30m`, `2h`, or `7d`; a bare number is seconds.
+///
+/// # Errors
+///
+/// Returns `"Invalid duration: {text} ..."` for anything else.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7_200)));
+/// ```
+pub fn parse_duration(text: &str) -> Result<Duration, String> {
+    let invalid = || format!("Invalid duration: {} (expected e.g. 30m, 2h or 7d)", text);
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let multiplier: u64 = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        _ => return Err(invalid()),
+    };
+    let value: u64 = number.parse().map_err(|_| invalid())?;
+    value
+        .checked_mul(multiplier)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// A broken-down UTC date and time with second precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl DateTime {
+    /// Converts a [`SystemTime`] to a UTC [`DateTime`].
+    ///
+    /// Times before the Unix epoch are clamped to the epoch.
+    pub fn from_system_time(time: SystemTime) -> Self {
+        let secs = time
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self::from_unix(secs)
+    }
+
+    /// The current UTC date and time.
+    pub fn now() -> Self {
+        Self::from_system_time(SystemTime::now())
+    }
+
+    /// Converts seconds since the Unix epoch to a UTC [`DateTime`].
+    ///
+    /// Uses Howard Hinnant's `civil_from_days` algorithm.
+    pub fn from_unix(secs: u64) -> Self {
+        let days = (secs / 86_400) as i64;
+        let rem = secs % 86_400;
+
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year,
+            month,
+            day,
+            hour: (rem / 3_600) as u32,
+            minute: ((rem % 3_600) / 60) as u32,
+            second: (rem % 60) as u32,
+        }
+    }
+
+    /// Formats as a filesystem-safe sortable stamp (`2024-01-31_130509`).
+    pub fn compact(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}_{:02}{:02}{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+
+    /// Formats as RFC 3339 (`2024-01-31T13:05:09Z`).
+    pub fn rfc3339(&self) -> String {
+        format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies duration parsing across units and rejection of malformed input.
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(1_800)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7_200)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(604_800)));
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("h").is_err());
+        assert!(parse_duration("3w").is_err());
+    }
+
+    /// Verifies conversion of known Unix timestamps, including a leap day.
+    #[test]
+    fn test_from_unix() {
+        assert_eq!(DateTime::from_unix(0).rfc3339(), "1970-01-01T00:00:00Z");
+        assert_eq!(
+            DateTime::from_unix(951_782_400).rfc3339(),
+            "2000-02-29T00:00:00Z"
+        );
+        assert_eq!(
+            DateTime::from_unix(1_706_706_309).rfc3339(),
+            "2024-01-31T13:05:09Z"
+        );
+        assert_eq!(
+            DateTime::from_unix(1_706_706_309).compact(),
+            "2024-01-31_130509"
+        );
+    }
+}
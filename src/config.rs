@@ -0,0 +1,539 @@
+//! Defaults read from configuration files and the environment.
+//!
+//! Options a user gives on every run can be set once instead. Four layers
+//! are read, each overriding the one before it, and the command line
+//! overrides them all:
+//!
+//! | Layer | Where |
+//! |-------|-------|
+//! | User | `config.toml` in the user's [`config_dir`]`/solscrape` |
+//! | Project | [`PROJECT_FILE`] in the working directory |
+//! | Environment | `SOLSCRAPE_` and the key in capitals, e.g. `SOLSCRAPE_QUIET` |
+//! | Command line | The options themselves |
+//!
+//! The files are a small subset of TOML: `key = value` lines, `#`
+//! comments, strings in double or single quotes, and `true` or `false`.
+//! Only the [`KEYS`] are known; anything else is an error naming the file
+//! and line, so a typo never goes unnoticed. Settings become command-line
+//! options placed before the real ones (see [`Settings::options`]), which
+//! is what makes the command line win.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The name of the project configuration file.
+pub const PROJECT_FILE: &str = "solscrape.toml";
+
+/// The name of the user configuration file, in [`config_dir`]`/solscrape`.
+pub const USER_FILE: &str = "config.toml";
+
+/// The prefix of the environment variable of each key.
+pub const ENV_PREFIX: &str = "SOLSCRAPE_";
+
+/// What a key holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Type {
+    /// A string.
+    Text,
+    /// `true` or `false`.
+    Bool,
+    /// One of the strings listed.
+    OneOf(&'static [&'static str]),
+}
+
+/// The keys a layer may set, and what each holds.
+///
+/// | Key | Value | Same as |
+/// |-----|-------|---------|
+/// | `destination` | A directory or `.sol` path | The `[destination]` argument |
+/// | `quiet` | `true` or `false` | `--quiet` |
+/// | `headers` | `"full"` or `"none"` | `--no-headers` for `"none"` |
+/// | `profile` | `"audit"`, `"llm"`, or `"verify"` | `--profile` |
+const KEYS: [(&str, Type); 4] = [
+    ("destination", Type::Text),
+    ("quiet", Type::Bool),
+    ("headers", Type::OneOf(&["full", "none"])),
+    ("profile", Type::Text),
+];
+
+/// Where a setting came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    /// A configuration file.
+    File(PathBuf),
+    /// An environment variable.
+    Env(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::File(path) => write!(f, "{}", path.display()),
+            Origin::Env(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+/// A value of a key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    Bool(bool),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Text(text) => write!(f, "{:?}", text),
+            Value::Bool(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+/// The value a key ended up with, and the layer that set it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Setting {
+    pub value: Value,
+    pub origin: Origin,
+}
+
+/// The configuration files looked for, and the settings of every layer
+/// merged.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// Each file looked for, lowest precedence first, and whether it was
+    /// there.
+    pub files: Vec<(PathBuf, bool)>,
+    /// The final setting of each key that any layer set.
+    pub values: BTreeMap<&'static str, Setting>,
+}
+
+impl Settings {
+    /// The setting of `key`, if a layer set it.
+    pub fn get(&self, key: &str) -> Option<&Setting> {
+        self.values.get(key)
+    }
+
+    /// The text of `key`, if a layer set it.
+    pub fn text(&self, key: &str) -> Option<&str> {
+        match self.get(key).map(|setting| &setting.value) {
+            Some(Value::Text(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// The command-line options equivalent to the settings, other than
+    /// `destination`, which is an argument rather than an option.
+    ///
+    /// Settings the command line `args` overrides are left out: `profile`
+    /// when it gives `--profile`, and `quiet` when it gives `--verbose`,
+    /// which could not otherwise be used with it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// // quiet = true, headers = "none", profile = "llm"
+    /// assert_eq!(settings.options(&[]), ["--profile", "llm", "--quiet", "--no-headers"]);
+    /// ```
+    pub fn options(&self, args: &[String]) -> Vec<String> {
+        let given = |flag: &str| args.iter().any(|arg| arg == flag);
+        let mut options = Vec::new();
+        if let Some(profile) = self.text("profile").filter(|_| !given("--profile")) {
+            options.extend(["--profile".to_string(), profile.to_string()]);
+        }
+        if !given("--verbose")
+            && matches!(
+                self.get("quiet"),
+                Some(Setting {
+                    value: Value::Bool(true),
+                    ..
+                })
+            )
+        {
+            options.push("--quiet".to_string());
+        }
+        if self.text("headers") == Some("none") {
+            options.push("--no-headers".to_string());
+        }
+        options
+    }
+
+    /// The report of `solscrape config path`: each file looked for, the
+    /// environment variables read, and each setting with where it came
+    /// from.
+    pub fn report(&self) -> String {
+        let mut out = String::from("Configuration, lowest precedence first:\n");
+        for (path, found) in &self.files {
+            out.push_str(&format!(
+                "  {}{}\n",
+                path.display(),
+                if *found { "" } else { " (not found)" }
+            ));
+        }
+        let vars: Vec<String> = KEYS.iter().map(|(key, _)| env_var(key)).collect();
+        out.push_str(&format!("  ${}\n", vars.join(", $")));
+        out.push_str("  command-line options\n\nSettings:\n");
+        if self.values.is_empty() {
+            out.push_str("  none; the built-in defaults apply\n");
+        }
+        for (key, setting) in &self.values {
+            out.push_str(&format!(
+                "  {} = {}  (from {})\n",
+                key, setting.value, setting.origin
+            ));
+        }
+        out
+    }
+}
+
+/// The directory user configuration goes in on `os` (as in
+/// [`std::env::consts::OS`]), with `var` reading the environment.
+///
+/// | OS | Directory |
+/// |----|-----------|
+/// | Windows | `%APPDATA%` |
+/// | macOS | `$HOME/Library/Application Support` |
+/// | Others | `$XDG_CONFIG_HOME` if absolute, else `$HOME/.config` |
+///
+/// # Returns
+///
+/// `None` when the variables it needs are unset or empty.
+pub fn config_dir(os: &str, var: &impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let path = |name: &str| var(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+    match os {
+        "windows" => path("APPDATA"),
+        "macos" => path("HOME").map(|home| home.join("Library").join("Application Support")),
+        _ => path("XDG_CONFIG_HOME")
+            .filter(|dir| dir.is_absolute())
+            .or_else(|| path("HOME").map(|home| home.join(".config"))),
+    }
+}
+
+/// The environment variable of `key`.
+fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.to_uppercase())
+}
+
+/// Reads every layer but the command line: the user file for `os`, the
+/// project file in `cwd`, then the environment, read with `var`.
+///
+/// A missing file is skipped.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Failed to read {path}: {e}"` | A file exists but cannot be read |
+/// | `"{path}:{line}: ..."` | A file has a malformed line, an unknown key, or a wrong value |
+/// | `"${var}: ..."` | A variable has a wrong value |
+pub fn load(
+    os: &str,
+    cwd: &Path,
+    var: impl Fn(&str) -> Option<OsString>,
+) -> Result<Settings, String> {
+    let mut settings = Settings::default();
+    let user = config_dir(os, &var).map(|dir| dir.join("solscrape").join(USER_FILE));
+    for path in user.into_iter().chain([cwd.join(PROJECT_FILE)]) {
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                settings.files.push((path, false));
+                continue;
+            }
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        let values =
+            parse(&text).map_err(|(line, e)| format!("{}:{}: {}", path.display(), line, e))?;
+        for (key, value) in values {
+            settings.values.insert(
+                key,
+                Setting {
+                    value,
+                    origin: Origin::File(path.clone()),
+                },
+            );
+        }
+        settings.files.push((path, true));
+    }
+
+    for (key, kind) in KEYS {
+        let name = env_var(key);
+        let Some(raw) = var(&name).filter(|raw| !raw.is_empty()) else {
+            continue;
+        };
+        let raw = raw.to_string_lossy();
+        let value = match kind {
+            Type::Bool => match raw.trim() {
+                "1" | "true" | "yes" => Value::Bool(true),
+                "0" | "false" | "no" => Value::Bool(false),
+                _ => {
+                    return Err(format!(
+                        "${}: expected true or false, found {:?}",
+                        name, raw
+                    ));
+                }
+            },
+            _ => checked(key, kind, Value::Text(raw.to_string()))
+                .map_err(|e| format!("${}: {}", name, e))?,
+        };
+        settings.values.insert(
+            key,
+            Setting {
+                value,
+                origin: Origin::Env(name),
+            },
+        );
+    }
+    Ok(settings)
+}
+
+/// The settings of a configuration file, in order.
+///
+/// # Errors
+///
+/// The 1-based line number and what is wrong with it.
+fn parse(text: &str) -> Result<Vec<(&'static str, Value)>, (usize, String)> {
+    let mut values: Vec<(&'static str, Value)> = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            return Err((number, "tables are not supported".to_string()));
+        }
+        let Some((key, raw)) = line.split_once('=') else {
+            return Err((number, "expected `key = value`".to_string()));
+        };
+        let key = key.trim();
+        let Some(&(key, kind)) = KEYS.iter().find(|(known, _)| *known == key) else {
+            let known: Vec<&str> = KEYS.iter().map(|(known, _)| *known).collect();
+            return Err((
+                number,
+                format!("unknown key {:?} (expected {})", key, known.join(", ")),
+            ));
+        };
+        if values.iter().any(|(seen, _)| *seen == key) {
+            return Err((number, format!("{} is set twice", key)));
+        }
+        let value = parse_value(raw.trim()).ok_or_else(|| {
+            (
+                number,
+                format!(
+                    "{} has no valid value (expected a quoted string, true, or false)",
+                    key
+                ),
+            )
+        })?;
+        values.push((key, checked(key, kind, value).map_err(|e| (number, e))?));
+    }
+    Ok(values)
+}
+
+/// A quoted string (with `\"`, `\\`, `\n`, and `\t` escapes in double
+/// quotes) or `true` or `false`, optionally followed by a `#` comment.
+fn parse_value(raw: &str) -> Option<Value> {
+    let (value, rest) = if let Some(body) = raw.strip_prefix('\'') {
+        let end = body.find('\'')?;
+        (Value::Text(body[..end].to_string()), &body[end + 1..])
+    } else if let Some(body) = raw.strip_prefix('"') {
+        let mut text = String::new();
+        let mut chars = body.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (at, '"') => break at,
+                (_, '\\') => text.push(match chars.next()?.1 {
+                    'n' => '\n',
+                    't' => '\t',
+                    c @ ('"' | '\\') => c,
+                    _ => return None,
+                }),
+                (_, c) => text.push(c),
+            }
+        };
+        (Value::Text(text), &body[end + 1..])
+    } else {
+        let end = raw.find(['#', ' ', '\t']).unwrap_or(raw.len());
+        let value = match &raw[..end] {
+            "true" => Value::Bool(true),
+            "false" => Value::Bool(false),
+            _ => return None,
+        };
+        (value, &raw[end..])
+    };
+    let rest = rest.trim_start();
+    (rest.is_empty() || rest.starts_with('#')).then_some(value)
+}
+
+/// `value`, if it is what `key` holds.
+fn checked(key: &str, kind: Type, value: Value) -> Result<Value, String> {
+    match (kind, &value) {
+        (Type::Text, Value::Text(_)) | (Type::Bool, Value::Bool(_)) => Ok(value),
+        (Type::OneOf(allowed), Value::Text(text)) if allowed.contains(&text.as_str()) => Ok(value),
+        (Type::OneOf(allowed), _) => {
+            Err(format!("{} must be \"{}\"", key, allowed.join("\" or \"")))
+        }
+        (Type::Bool, _) => Err(format!("{} must be true or false", key)),
+        (Type::Text, _) => Err(format!("{} must be a quoted string", key)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// An environment of `pairs`.
+    fn env(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<OsString> {
+        let vars: HashMap<String, OsString> = pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), OsString::from(value)))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    /// Verifies the user directory of each platform.
+    #[test]
+    fn test_config_dir() {
+        let home = env(&[
+            ("HOME", "/home/a"),
+            ("APPDATA", "C:\\Users\\a\\AppData\\Roaming"),
+        ]);
+        assert_eq!(
+            config_dir("linux", &home),
+            Some(PathBuf::from("/home/a/.config"))
+        );
+        assert_eq!(
+            config_dir("macos", &home),
+            Some(PathBuf::from("/home/a/Library/Application Support"))
+        );
+        assert_eq!(
+            config_dir("windows", &home),
+            Some(PathBuf::from("C:\\Users\\a\\AppData\\Roaming"))
+        );
+        let xdg = env(&[("HOME", "/home/a"), ("XDG_CONFIG_HOME", "/xdg")]);
+        assert_eq!(config_dir("freebsd", &xdg), Some(PathBuf::from("/xdg")));
+        let relative = env(&[("HOME", "/home/a"), ("XDG_CONFIG_HOME", "xdg")]);
+        assert_eq!(
+            config_dir("linux", &relative),
+            Some(PathBuf::from("/home/a/.config"))
+        );
+        assert_eq!(config_dir("linux", &env(&[("HOME", "")])), None);
+    }
+
+    /// Verifies values, comments, and each kind of malformed line.
+    #[test]
+    fn test_parse() {
+        let text = "# defaults\n\ndestination = \"./out \\\"a\\\"\"  # here\n\
+                    quiet = true\nheaders = 'none'\n";
+        assert_eq!(
+            parse(text),
+            Ok(vec![
+                ("destination", Value::Text("./out \"a\"".to_string())),
+                ("quiet", Value::Bool(true)),
+                ("headers", Value::Text("none".to_string())),
+            ])
+        );
+        let cases = [
+            ("[scrape]", "tables are not supported"),
+            ("quiet", "expected `key = value`"),
+            ("quite = true", "unknown key \"quite\""),
+            ("quiet = yes", "quiet has no valid value"),
+            ("quiet = \"true\"", "quiet must be true or false"),
+            ("profile = true", "profile must be a quoted string"),
+            ("headers = \"some\"", "headers must be \"full\" or \"none\""),
+            ("destination = \"open", "destination has no valid value"),
+            ("quiet = true false", "quiet has no valid value"),
+        ];
+        for (line, expected) in cases {
+            let (number, error) = parse(&format!("\n{}", line)).unwrap_err();
+            assert_eq!(number, 2, "{line}");
+            assert!(error.starts_with(expected), "{line}: {error}");
+        }
+        assert_eq!(
+            parse("quiet = true\nquiet = false").unwrap_err(),
+            (2, "quiet is set twice".to_string())
+        );
+    }
+
+    /// Verifies that each layer overrides the one before it, key by key,
+    /// and that the report says where each setting came from.
+    #[test]
+    fn test_load_layers() {
+        let root = std::env::temp_dir().join(format!("solscrape_config_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let user_dir = root.join("home/.config/solscrape");
+        let project = root.join("project");
+        fs::create_dir_all(&user_dir).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        fs::write(
+            user_dir.join(USER_FILE),
+            "destination = \"~/scrapes\"\nquiet = true\nprofile = \"audit\"\n",
+        )
+        .unwrap();
+        fs::write(
+            project.join(PROJECT_FILE),
+            "profile = \"llm\"\nheaders = \"none\"\n",
+        )
+        .unwrap();
+        let home = root.join("home");
+        let home = home.to_str().unwrap();
+
+        let settings = load(
+            "linux",
+            &project,
+            env(&[("HOME", home), ("SOLSCRAPE_QUIET", "false")]),
+        )
+        .unwrap();
+        let origin = |key: &str| settings.get(key).unwrap().origin.to_string();
+        assert_eq!(settings.text("destination"), Some("~/scrapes"));
+        assert_eq!(
+            origin("destination"),
+            user_dir.join(USER_FILE).display().to_string()
+        );
+        assert_eq!(settings.text("profile"), Some("llm"));
+        assert_eq!(
+            origin("profile"),
+            project.join(PROJECT_FILE).display().to_string()
+        );
+        assert_eq!(origin("quiet"), "$SOLSCRAPE_QUIET");
+        assert_eq!(settings.options(&[]), ["--profile", "llm", "--no-headers"]);
+        let cli = ["--profile".to_string(), "verify".to_string()];
+        assert_eq!(settings.options(&cli), ["--no-headers"]);
+        assert!(
+            settings
+                .report()
+                .contains("  quiet = false  (from $SOLSCRAPE_QUIET)\n")
+        );
+
+        // Missing files are reported and skipped
+        let settings = load("linux", &root, env(&[("HOME", "/nowhere")])).unwrap();
+        assert_eq!(settings.files.iter().filter(|(_, found)| !found).count(), 2);
+        assert!(settings.report().contains(" (not found)\n"));
+        assert!(
+            settings
+                .report()
+                .contains("  none; the built-in defaults apply\n")
+        );
+
+        let err = load(
+            "linux",
+            &project,
+            env(&[("HOME", home), ("SOLSCRAPE_HEADERS", "half")]),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "$SOLSCRAPE_HEADERS: headers must be \"full\" or \"none\""
+        );
+        fs::write(project.join(PROJECT_FILE), "quiet = 1\n").unwrap();
+        let err = load("linux", &project, env(&[])).unwrap_err();
+        assert!(err.ends_with("solscrape.toml:1: quiet has no valid value (expected a quoted string, true, or false)"), "{err}");
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,361 @@
+//! Non-Solidity project context appended with `--with-context`.
+//!
+//! A reader of the consolidated output (often a language model) gets much
+//! further with the project's README, compiler settings, and remappings at
+//! hand. [`collect`] reads the [`CONTEXT_FILES`] allowlist from the
+//! repository root, and [`render`] turns them into a section after the
+//! Solidity code, opened by [`BANNER`]. Every line of that section is a `//`
+//! comment, so the output still compiles; `split` stops reading at the
+//! banner.
+
+use std::fs;
+use std::path::Path;
+
+/// The line opening the context section.
+pub const BANNER: &str = "// ===== PROJECT CONTEXT =====";
+
+/// The files read from the repository root, in output order.
+pub const CONTEXT_FILES: &[&str] = &[
+    "README.md",
+    "foundry.toml",
+    "remappings.txt",
+    "package.json",
+];
+
+/// The top-level `package.json` fields kept; scripts, dev dependencies, and
+/// the like say little about the contracts.
+pub const PACKAGE_FIELDS: &[&str] = &["name", "version", "dependencies"];
+
+/// How much of the README is kept by default, in bytes
+/// (`--context-readme-limit`).
+pub const DEFAULT_README_LIMIT: usize = 8 * 1024;
+
+/// The comment marker starting every line of the context section.
+const LINE_PREFIX: &str = "//";
+
+/// One context file as it goes into the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextFile {
+    /// The file's name at the repository root.
+    pub path: &'static str,
+    /// The kept text, before comment markers are added.
+    pub text: String,
+    /// The file's full size in bytes, when only part of it was kept.
+    pub truncated_from: Option<usize>,
+}
+
+impl ContextFile {
+    /// The number of lines the file takes in the output, its heading included.
+    pub fn line_count(&self) -> usize {
+        self.text.lines().count() + 1
+    }
+}
+
+/// Reads the allowlisted context files present at `root`.
+///
+/// Unreadable files are left out, as is a `package.json` with none of the
+/// [`PACKAGE_FIELDS`]. The README is cut to `readme_limit` bytes, at a line
+/// break when there is one.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let files = collect(Path::new("/tmp/repo"), DEFAULT_README_LIMIT);
+/// // [ContextFile { path: "README.md", .. }, ContextFile { path: "foundry.toml", .. }]
+/// ```
+pub fn collect(root: &Path, readme_limit: usize) -> Vec<ContextFile> {
+    CONTEXT_FILES
+        .iter()
+        .filter_map(|&path| {
+            let content = fs::read_to_string(root.join(path)).ok()?;
+            let (text, truncated_from) = match path {
+                "README.md" => match truncate(&content, readme_limit) {
+                    Some(kept) => (kept.to_string(), Some(content.len())),
+                    None => (content, None),
+                },
+                "package.json" => (package_summary(&content)?, None),
+                _ => (content, None),
+            };
+            Some(ContextFile {
+                path,
+                text,
+                truncated_from,
+            })
+        })
+        .collect()
+}
+
+/// Renders the context section, starting with [`BANNER`] and without a
+/// trailing newline.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let section = render(&collect(root, DEFAULT_README_LIMIT));
+/// // "// ===== PROJECT CONTEXT =====\n// Not Solidity: ...\n// ----- foundry.toml -----\n// [profile.default]..."
+/// ```
+pub fn render(files: &[ContextFile]) -> String {
+    let mut lines = vec![
+        BANNER.to_string(),
+        format!(
+            "{} Not Solidity: project files from the repository root, for reference.",
+            LINE_PREFIX
+        ),
+    ];
+    for file in files {
+        let heading = match file.truncated_from {
+            Some(size) => format!(
+                "{} ----- {} (first {} of {} bytes) -----",
+                LINE_PREFIX,
+                file.path,
+                file.text.len(),
+                size
+            ),
+            None => format!("{} ----- {} -----", LINE_PREFIX, file.path),
+        };
+        lines.push(heading);
+        lines.extend(file.text.lines().map(|line| {
+            if line.trim().is_empty() {
+                LINE_PREFIX.to_string()
+            } else {
+                format!("{} {}", LINE_PREFIX, line.trim_end())
+            }
+        }));
+    }
+    lines.join("\n")
+}
+
+/// Returns the first `limit` bytes of `text` when it is longer, cut after
+/// the last line break within them or, failing that, at a char boundary.
+fn truncate(text: &str, limit: usize) -> Option<&str> {
+    if text.len() <= limit {
+        return None;
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let kept = &text[..end];
+    Some(match kept.rfind('\n') {
+        Some(newline) if newline > 0 => &kept[..newline],
+        _ => kept,
+    })
+}
+
+/// Renders the [`PACKAGE_FIELDS`] of a `package.json` as `"key": value`
+/// lines, or `None` when it has none of them.
+fn package_summary(manifest: &str) -> Option<String> {
+    let fields = top_level_fields(manifest)?;
+    let kept: Vec<String> = PACKAGE_FIELDS
+        .iter()
+        .filter_map(|&key| {
+            let (_, value) = fields.iter().find(|(name, _)| name == key)?;
+            Some(format!("\"{}\": {}", key, value))
+        })
+        .collect();
+    (!kept.is_empty()).then(|| kept.join("\n"))
+}
+
+/// Splits a JSON object into its top-level keys and raw value texts, or
+/// returns `None` when `json` is not an object.
+///
+/// Keys are taken as written, without unescaping; values are not checked
+/// beyond matching brackets and quotes.
+fn top_level_fields(json: &str) -> Option<Vec<(String, &str)>> {
+    let bytes = json.as_bytes();
+    let skip_space = |mut i: usize| {
+        while bytes.get(i).is_some_and(u8::is_ascii_whitespace) {
+            i += 1;
+        }
+        i
+    };
+
+    let mut i = skip_space(0);
+    if bytes.get(i) != Some(&b'{') {
+        return None;
+    }
+    i += 1;
+    let mut fields = Vec::new();
+    loop {
+        i = skip_space(i);
+        match bytes.get(i)? {
+            b'}' => return Some(fields),
+            b',' => {
+                i += 1;
+                continue;
+            }
+            b'"' => {}
+            _ => return None,
+        }
+        let key_end = string_end(bytes, i)?;
+        let key = json[i + 1..key_end - 1].to_string();
+        i = skip_space(key_end);
+        if bytes.get(i) != Some(&b':') {
+            return None;
+        }
+        let start = skip_space(i + 1);
+        let end = value_end(bytes, start)?;
+        fields.push((key, json[start..end].trim_end()));
+        i = end;
+    }
+}
+
+/// Returns the index just past the string starting at `start`.
+fn string_end(bytes: &[u8], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    loop {
+        match bytes.get(i)? {
+            b'\\' => i += 2,
+            b'"' => return Some(i + 1),
+            _ => i += 1,
+        }
+    }
+}
+
+/// Returns the index just past the value starting at `start`: a string, a
+/// bracketed object or array, or a bare literal up to the next `,` or `}`.
+fn value_end(bytes: &[u8], start: usize) -> Option<usize> {
+    match bytes.get(start)? {
+        b'"' => string_end(bytes, start),
+        b'{' | b'[' => {
+            let mut depth = 0usize;
+            let mut i = start;
+            loop {
+                match bytes.get(i)? {
+                    b'"' => {
+                        i = string_end(bytes, i)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(i + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                i += 1;
+            }
+        }
+        _ => {
+            let mut i = start;
+            while !matches!(bytes.get(i)?, b',' | b'}') {
+                i += 1;
+            }
+            Some(i)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("solscrape_context_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for (relative, content) in files {
+            fs::write(root.join(relative), content).unwrap();
+        }
+        root
+    }
+
+    /// Verifies the allowlist and limits, which the output format relies on.
+    #[test]
+    fn test_constants() {
+        assert_eq!(
+            CONTEXT_FILES,
+            [
+                "README.md",
+                "foundry.toml",
+                "remappings.txt",
+                "package.json"
+            ]
+        );
+        assert_eq!(PACKAGE_FIELDS, ["name", "version", "dependencies"]);
+        assert_eq!(DEFAULT_README_LIMIT, 8192);
+        assert!(BANNER.starts_with(LINE_PREFIX));
+    }
+
+    /// Verifies cutting at line breaks and char boundaries.
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("short", 10), None);
+        assert_eq!(truncate("one\ntwo\nthree", 9), Some("one\ntwo"));
+        assert_eq!(truncate("no breaks here", 5), Some("no br"));
+        assert_eq!(truncate("ééé", 3), Some("é"));
+    }
+
+    /// Verifies which package.json fields are kept.
+    #[test]
+    fn test_package_summary() {
+        let manifest = r#"{
+  "name": "vault",
+  "scripts": {"test": "forge test, \"quoted\""},
+  "version": "1.2.0",
+  "private": true,
+  "dependencies": {
+    "@openzeppelin/contracts": "^5.0.0"
+  }
+}"#;
+        assert_eq!(
+            package_summary(manifest).unwrap(),
+            "\"name\": \"vault\"\n\"version\": \"1.2.0\"\n\"dependencies\": {\n    \"@openzeppelin/contracts\": \"^5.0.0\"\n  }"
+        );
+        assert_eq!(
+            top_level_fields(r#"{"a": 1, "b": [1, {"c": "}"}], "d": null}"#).unwrap(),
+            [
+                ("a".to_string(), "1"),
+                ("b".to_string(), r#"[1, {"c": "}"}]"#),
+                ("d".to_string(), "null")
+            ]
+        );
+        assert_eq!(package_summary(r#"{"private": true}"#), None);
+        assert_eq!(package_summary("not json"), None);
+        assert_eq!(package_summary(r#"{"name": "x""#), None);
+    }
+
+    /// Verifies collection from a repository root and the rendered section.
+    #[test]
+    fn test_collect_and_render() {
+        let root = tree(
+            "collect",
+            &[
+                (
+                    "README.md",
+                    "# Vault\n\nDeposits */ and withdrawals.\nMore text\n",
+                ),
+                ("remappings.txt", "@oz/=lib/openzeppelin-contracts/\n"),
+                ("package.json", r#"{"name": "vault", "private": true}"#),
+                ("hardhat.config.js", "module.exports = {}"),
+            ],
+        );
+        let files = collect(&root, 40);
+        let paths: Vec<&str> = files.iter().map(|f| f.path).collect();
+        assert_eq!(paths, ["README.md", "remappings.txt", "package.json"]);
+        assert_eq!(files[0].truncated_from, Some(48));
+        assert_eq!(files[0].line_count(), 4);
+
+        let section = render(&files);
+        assert_eq!(
+            section,
+            "// ===== PROJECT CONTEXT =====\n\
+             // Not Solidity: project files from the repository root, for reference.\n\
+             // ----- README.md (first 37 of 48 bytes) -----\n\
+             // # Vault\n\
+             //\n\
+             // Deposits */ and withdrawals.\n\
+             // ----- remappings.txt -----\n\
+             // @oz/=lib/openzeppelin-contracts/\n\
+             // ----- package.json -----\n\
+             // \"name\": \"vault\""
+        );
+        assert!(section.lines().all(|line| line.starts_with(LINE_PREFIX)));
+        assert!(collect(&root.join("missing"), 40).is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+}
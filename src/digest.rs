@@ -0,0 +1,200 @@
+//! The one-line-per-file overview printed by `--digest`.
+//!
+//! A digest is a quick look at a repository before a full scrape: each
+//! included file is processed as for a scrape, but instead of its code one
+//! [`Row`] is printed with its cleaned line count, top-level declarations,
+//! pragma, and flags. The table is aligned for reading, or tab-separated
+//! with `--quiet` for `awk` and `cut`; either way no field is ever empty.
+
+use crate::naming;
+use crate::structure::{self, Kind, KindCounts};
+use crate::todo;
+
+/// What stands in for an empty pragma or flag field.
+const NONE: &str = "-";
+
+/// The column names of the aligned table.
+const HEADINGS: [&str; 7] = [
+    "PATH",
+    "LINES",
+    "CONTRACTS",
+    "INTERFACES",
+    "LIBRARIES",
+    "PRAGMA",
+    "FLAGS",
+];
+
+/// The digest of one included file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    /// The file's path relative to the source root, escaped for display.
+    pub path: String,
+    /// The lines of its cleaned code.
+    pub lines: usize,
+    /// Its top-level contracts, abstract ones included.
+    pub contracts: usize,
+    /// Its top-level interfaces.
+    pub interfaces: usize,
+    /// Its top-level libraries.
+    pub libraries: usize,
+    /// Its `pragma solidity` constraints, space-separated, if any.
+    pub pragma: Option<String>,
+    /// Whether its code has an `assembly` block.
+    pub assembly: bool,
+    /// The [`todo::MARKERS`] in the original file, comments included.
+    pub todos: usize,
+}
+
+impl Row {
+    /// The row of the file at `relative`, whose cleaned code is `text` and
+    /// whose content on disk is `original`.
+    ///
+    /// Markers are counted in `original`, since cleaning removes the
+    /// comments most of them are in.
+    pub fn of(relative: &str, text: &str, original: &str) -> Self {
+        let counts = KindCounts::of(text);
+        let pragmas = structure::solidity_pragmas(text);
+        Self {
+            path: naming::display_path(relative),
+            lines: text.lines().count(),
+            contracts: counts.get(Kind::Contract) + counts.get(Kind::Abstract),
+            interfaces: counts.get(Kind::Interface),
+            libraries: counts.get(Kind::Library),
+            pragma: (!pragmas.is_empty()).then(|| pragmas.join(" ")),
+            assembly: structure::uses_assembly(text),
+            todos: todo::scan(original, original).len(),
+        }
+    }
+
+    /// The flags column: `assembly` and `todo`, comma-separated.
+    fn flags(&self) -> String {
+        let flags: Vec<&str> = [("assembly", self.assembly), ("todo", self.todos > 0)]
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(flag, _)| flag)
+            .collect();
+        if flags.is_empty() {
+            NONE.to_string()
+        } else {
+            flags.join(",")
+        }
+    }
+
+    /// The row's fields, in [`HEADINGS`] order.
+    fn fields(&self) -> [String; 7] {
+        [
+            self.path.clone(),
+            self.lines.to_string(),
+            self.contracts.to_string(),
+            self.interfaces.to_string(),
+            self.libraries.to_string(),
+            self.pragma.clone().unwrap_or_else(|| NONE.to_string()),
+            self.flags(),
+        ]
+    }
+}
+
+/// Renders `rows` in order, each line ending in a newline: tab-separated
+/// without headings, or as a table under [`HEADINGS`] with the counts
+/// right-aligned.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let text = render(&[Row::of("src/A.sol", "contract A {}", "")], true);
+/// assert_eq!(text, "src/A.sol\t1\t1\t0\t0\t-\t-\n");
+/// ```
+pub fn render(rows: &[Row], tab_separated: bool) -> String {
+    let lines: Vec<[String; 7]> = rows.iter().map(Row::fields).collect();
+    if tab_separated {
+        return lines
+            .iter()
+            .map(|fields| format!("{}\n", fields.join("\t")))
+            .collect();
+    }
+    let mut widths = HEADINGS.map(|heading| heading.len());
+    for fields in &lines {
+        for (width, field) in widths.iter_mut().zip(fields) {
+            *width = (*width).max(field.chars().count());
+        }
+    }
+    let headings = HEADINGS.map(str::to_string);
+    std::iter::once(&headings)
+        .chain(&lines)
+        .map(|fields| {
+            let cells: Vec<String> = fields
+                .iter()
+                .zip(widths)
+                .enumerate()
+                .map(|(column, (field, width))| match column {
+                    1..=4 => format!("{:>width$}", field),
+                    _ => format!("{:<width$}", field),
+                })
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two rows covering every column: a file with each kind of
+    /// declaration, two pragmas, assembly, and a marker, and one with
+    /// nothing to flag and no pragma.
+    fn rows() -> Vec<Row> {
+        let vault = "pragma solidity ^0.8.20;\npragma solidity <0.9.0;\n\
+                     interface IVault {}\nlibrary Math {}\n\
+                     abstract contract Base {}\ncontract Vault is Base {\n    \
+                     function f() external { assembly { let x := 1 } }\n}";
+        let original = format!("// TODO: fees\n{}", vault);
+        vec![
+            Row::of("src/Vault.sol", vault, &original),
+            Row::of("src/lib/Pad ded.sol", "contract P {}", "contract P {}"),
+        ]
+    }
+
+    /// Verifies each field of a row.
+    #[test]
+    fn test_row_of() {
+        let rows = rows();
+        assert_eq!(
+            rows[0],
+            Row {
+                path: "src/Vault.sol".to_string(),
+                lines: 8,
+                contracts: 2,
+                interfaces: 1,
+                libraries: 1,
+                pragma: Some("^0.8.20 <0.9.0".to_string()),
+                assembly: true,
+                todos: 1,
+            }
+        );
+        assert_eq!(
+            (rows[1].pragma.as_ref(), rows[1].flags()),
+            (None, "-".to_string())
+        );
+    }
+
+    /// Verifies the aligned table and the tab-separated lines exactly.
+    #[test]
+    fn test_render_golden() {
+        let rows = rows();
+        assert_eq!(
+            render(&rows, false),
+            "\
+PATH                 LINES  CONTRACTS  INTERFACES  LIBRARIES  PRAGMA          FLAGS
+src/Vault.sol            8          2           1          1  ^0.8.20 <0.9.0  assembly,todo
+src/lib/Pad ded.sol      1          1           0          0  -               -
+"
+        );
+        assert_eq!(
+            render(&rows, true),
+            "src/Vault.sol\t8\t2\t1\t1\t^0.8.20 <0.9.0\tassembly,todo\n\
+             src/lib/Pad ded.sol\t1\t1\t0\t0\t-\t-\n"
+        );
+        assert_eq!(render(&[], true), "");
+    }
+}
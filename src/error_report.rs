@@ -0,0 +1,423 @@
+//! The custom errors and revert reasons a scrape can throw
+//! (`--errors-report`).
+//!
+//! [`scan`] takes a file's cleaned code: every `error Name(...)` definition
+//! found by [`structure::scan_errors`], and every reason literal of a
+//! `require(...)` or `revert(...)` call found by [`revert::revert_reasons`],
+//! each placed in its top-level contract with [`structure::scopes`].
+//! [`Inventory`] collects the files of a scrape, counting each distinct
+//! reason once per occurrence, and renders as text or JSON for the
+//! `{output}.errors.txt` or `{output}.errors.json` sidecar.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::json::Object;
+use crate::revert;
+use crate::structure::{self, ErrorDeclaration};
+
+/// The format of the sidecar, as given to `--errors-report`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    /// Parses a format name.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Invalid --errors-report: ... (expected text or json)"`.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            _ => Err(format!(
+                "Invalid --errors-report: {} (expected text or json)",
+                name
+            )),
+        }
+    }
+
+    /// The extension appended to the output's file name for the sidecar.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Text => "errors.txt",
+            Format::Json => "errors.json",
+        }
+    }
+
+    /// Returns the sidecar path for `output`: its full name plus `.` and
+    /// the [`extension`](Self::extension).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// assert_eq!(
+    ///     Format::Json.sidecar_path(Path::new("out/repo_scraped.sol")),
+    ///     PathBuf::from("out/repo_scraped.sol.errors.json")
+    /// );
+    /// ```
+    pub fn sidecar_path(self, output: &Path) -> PathBuf {
+        let mut name = output.as_os_str().to_os_string();
+        name.push(".");
+        name.push(self.extension());
+        PathBuf::from(name)
+    }
+}
+
+/// What [`scan`] finds in one file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileErrors {
+    /// The custom errors defined, in source order.
+    pub errors: Vec<ErrorDeclaration>,
+    /// Each reason literal as written, with the top-level definition it is
+    /// in, in source order.
+    pub reasons: Vec<(String, Option<String>)>,
+}
+
+/// Finds the custom errors and revert reasons of `code`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let found = scan("contract A { function f() { require(ok, \"no\"); } }");
+/// assert_eq!(found.reasons, [("\"no\"".to_string(), Some("A".to_string()))]);
+/// ```
+pub fn scan(code: &str) -> FileErrors {
+    let scopes = structure::scopes(code);
+    FileErrors {
+        errors: structure::scan_errors(code),
+        reasons: revert::revert_reasons(code)
+            .into_iter()
+            .map(|(at, reason)| (reason, scopes.at(at).map(str::to_string)))
+            .collect(),
+    }
+}
+
+/// Where a custom error or reason appears: a displayed path and the
+/// top-level definition, `None` at file scope.
+pub type Location = (String, Option<String>);
+
+/// One distinct reason literal across a scrape.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Reason {
+    /// The number of `require`/`revert` calls using it.
+    pub count: usize,
+    /// Where it appears, each once, in the order first seen.
+    pub locations: Vec<Location>,
+}
+
+/// The custom errors and reasons of every file of a scrape.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Inventory {
+    /// Each custom error with where it is defined, in output order.
+    pub errors: Vec<(ErrorDeclaration, String)>,
+    /// Each distinct reason literal, as written.
+    pub reasons: BTreeMap<String, Reason>,
+}
+
+impl Inventory {
+    /// Adds what [`scan`] found in the file shown as `path`.
+    pub fn add(&mut self, path: &str, found: FileErrors) {
+        for error in found.errors {
+            self.errors.push((error, path.to_string()));
+        }
+        for (text, contract) in found.reasons {
+            let reason = self.reasons.entry(text).or_default();
+            reason.count += 1;
+            let location = (path.to_string(), contract);
+            if !reason.locations.contains(&location) {
+                reason.locations.push(location);
+            }
+        }
+    }
+
+    /// Puts every path under the directory `name`, as several `--source`
+    /// put each source's files under its name.
+    pub fn put_under(&mut self, name: &str) {
+        let paths = self.errors.iter_mut().map(|(_, path)| path).chain(
+            self.reasons
+                .values_mut()
+                .flat_map(|reason| reason.locations.iter_mut().map(|(path, _)| path)),
+        );
+        for path in paths {
+            *path = format!("{}/{}", name, path);
+        }
+    }
+
+    /// Adds the errors and reasons of `other`, whose paths are all different
+    /// from this inventory's.
+    pub fn merge(&mut self, other: Inventory) {
+        self.errors.extend(other.errors);
+        for (text, found) in other.reasons {
+            let reason = self.reasons.entry(text).or_default();
+            reason.count += found.count;
+            reason.locations.extend(found.locations);
+        }
+    }
+
+    /// The summary line, e.g. `"3 custom errors, 2 revert strings (5 uses)"`.
+    pub fn summary(&self) -> String {
+        let uses: usize = self.reasons.values().map(|reason| reason.count).sum();
+        format!(
+            "{} custom error{}, {} revert string{} ({} use{})",
+            self.errors.len(),
+            plural(self.errors.len()),
+            self.reasons.len(),
+            plural(self.reasons.len()),
+            uses,
+            plural(uses)
+        )
+    }
+
+    /// The distinct reasons, most used first, then alphabetically.
+    fn ranked_reasons(&self) -> Vec<(&String, &Reason)> {
+        let mut ranked: Vec<(&String, &Reason)> = self.reasons.iter().collect();
+        ranked.sort_by_key(|(_, reason)| std::cmp::Reverse(reason.count));
+        ranked
+    }
+
+    /// Renders the inventory in `format`, ending with a newline.
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Text => self.render_text(),
+            Format::Json => format!("{}\n", self.render_json()),
+        }
+    }
+
+    /// Renders the two lists with aligned columns:
+    ///
+    /// ```text
+    /// Custom errors (2):
+    ///   Unauthorized(address caller)  Vault  src/Vault.sol
+    ///   Zero()                        -      src/Errors.sol
+    ///
+    /// Revert strings (1, 3 uses):
+    ///   3  "Vault: paused"  src/Vault.sol (Vault), src/Router.sol (Router)
+    /// ```
+    ///
+    /// `-` stands for file scope; reasons are listed most used first.
+    fn render_text(&self) -> String {
+        let mut out = format!("Custom errors ({}):\n", self.errors.len());
+        let signatures: Vec<String> = self
+            .errors
+            .iter()
+            .map(|(error, _)| format!("{}({})", error.name, error.parameters))
+            .collect();
+        let width = signatures.iter().map(|s| s.chars().count()).max();
+        let contract_width = self
+            .errors
+            .iter()
+            .map(|(error, _)| error.contract.as_deref().unwrap_or("-").chars().count())
+            .max();
+        for ((error, path), signature) in self.errors.iter().zip(&signatures) {
+            out.push_str(&format!(
+                "  {:<w$}  {:<cw$}  {}\n",
+                signature,
+                error.contract.as_deref().unwrap_or("-"),
+                path,
+                w = width.unwrap_or(0),
+                cw = contract_width.unwrap_or(0)
+            ));
+        }
+
+        let uses: usize = self.reasons.values().map(|reason| reason.count).sum();
+        out.push_str(&format!(
+            "\nRevert strings ({}, {} use{}):\n",
+            self.reasons.len(),
+            uses,
+            plural(uses)
+        ));
+        let count_width = self
+            .reasons
+            .values()
+            .map(|r| r.count.to_string().len())
+            .max();
+        let text_width = self.reasons.keys().map(|text| text.chars().count()).max();
+        for (text, reason) in self.ranked_reasons() {
+            let locations: Vec<String> = reason
+                .locations
+                .iter()
+                .map(|(path, contract)| match contract {
+                    Some(contract) => format!("{} ({})", path, contract),
+                    None => path.clone(),
+                })
+                .collect();
+            out.push_str(&format!(
+                "  {:>cw$}  {:<tw$}  {}\n",
+                reason.count,
+                text,
+                locations.join(", "),
+                cw = count_width.unwrap_or(0),
+                tw = text_width.unwrap_or(0)
+            ));
+        }
+        out
+    }
+
+    /// Renders `{"errors":[...],"reasons":[...]}` on one line: each error
+    /// as `{"name","parameters","contract","path"}` and each reason as
+    /// `{"text","count","locations":[{"path","contract"}]}`, `contract`
+    /// being `null` at file scope.
+    fn render_json(&self) -> String {
+        let errors: Vec<Object> = self
+            .errors
+            .iter()
+            .map(|(error, path)| {
+                Object::new()
+                    .string("name", &error.name)
+                    .string("parameters", &error.parameters)
+                    .optional_string("contract", error.contract.as_deref())
+                    .string("path", path)
+            })
+            .collect();
+        let reasons: Vec<Object> = self
+            .ranked_reasons()
+            .into_iter()
+            .map(|(text, reason)| {
+                let locations: Vec<Object> = reason
+                    .locations
+                    .iter()
+                    .map(|(path, contract)| {
+                        Object::new()
+                            .string("path", path)
+                            .optional_string("contract", contract.as_deref())
+                    })
+                    .collect();
+                Object::new()
+                    .string("text", text)
+                    .number("count", reason.count)
+                    .objects("locations", &locations)
+            })
+            .collect();
+        Object::new()
+            .objects("errors", &errors)
+            .objects("reasons", &reasons)
+            .render()
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two files sharing a reason, with errors at file scope and inside a
+    /// contract.
+    fn inventory() -> Inventory {
+        let mut inventory = Inventory::default();
+        inventory.add(
+            "src/Errors.sol",
+            scan("error Zero();\nerror Late(uint256 at);\n"),
+        );
+        inventory.add(
+            "src/Vault.sol",
+            scan(
+                "contract Vault {\n    error Unauthorized(address caller);\n    \
+                 function f() external {\n        require(ok, \"Vault: paused\");\n        \
+                 require(other, \"Vault: paused\");\n        revert(\"Access\");\n    }\n}\n",
+            ),
+        );
+        inventory.add(
+            "src/Router.sol",
+            scan("function g() { require(ok, \"Vault: paused\"); }\ncontract Router {}\n"),
+        );
+        inventory
+    }
+
+    /// Verifies counts and locations of a reason used in several files, and
+    /// the scope of each error.
+    #[test]
+    fn test_inventory() {
+        let inventory = inventory();
+        let scopes: Vec<(&str, Option<&str>)> = inventory
+            .errors
+            .iter()
+            .map(|(error, _)| (error.name.as_str(), error.contract.as_deref()))
+            .collect();
+        assert_eq!(
+            scopes,
+            [
+                ("Zero", None),
+                ("Late", None),
+                ("Unauthorized", Some("Vault"))
+            ]
+        );
+        let paused = &inventory.reasons["\"Vault: paused\""];
+        assert_eq!(paused.count, 3);
+        assert_eq!(
+            paused.locations,
+            [
+                ("src/Vault.sol".to_string(), Some("Vault".to_string())),
+                ("src/Router.sol".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            inventory.summary(),
+            "3 custom errors, 2 revert strings (4 uses)"
+        );
+    }
+
+    /// Verifies the exact text and JSON renderings.
+    #[test]
+    fn test_render() {
+        let inventory = inventory();
+        assert_eq!(
+            inventory.render(Format::Text),
+            "Custom errors (3):\n\
+             \x20 Zero()                        -      src/Errors.sol\n\
+             \x20 Late(uint256 at)              -      src/Errors.sol\n\
+             \x20 Unauthorized(address caller)  Vault  src/Vault.sol\n\
+             \n\
+             Revert strings (2, 4 uses):\n\
+             \x20 3  \"Vault: paused\"  src/Vault.sol (Vault), src/Router.sol\n\
+             \x20 1  \"Access\"         src/Vault.sol (Vault)\n"
+        );
+        assert_eq!(
+            inventory.render(Format::Json),
+            r#"{"errors":[{"name":"Zero","parameters":"","contract":null,"path":"src/Errors.sol"},{"name":"Late","parameters":"uint256 at","contract":null,"path":"src/Errors.sol"},{"name":"Unauthorized","parameters":"address caller","contract":"Vault","path":"src/Vault.sol"}],"reasons":[{"text":"\"Vault: paused\"","count":3,"locations":[{"path":"src/Vault.sol","contract":"Vault"},{"path":"src/Router.sol","contract":null}]},{"text":"\"Access\"","count":1,"locations":[{"path":"src/Vault.sol","contract":"Vault"}]}]}"#
+                .to_string()
+                + "\n"
+        );
+        assert_eq!(
+            Inventory::default().render(Format::Text),
+            "Custom errors (0):\n\nRevert strings (0, 0 uses):\n"
+        );
+        assert!(Format::parse("yaml").is_err());
+    }
+
+    /// Verifies that merging two sources' inventories, each under its name,
+    /// adds up shared reasons and keeps their paths apart.
+    #[test]
+    fn test_merge() {
+        let mut merged = inventory();
+        merged.put_under("vault");
+        let mut other = Inventory::default();
+        other.add(
+            "src/Vault.sol",
+            scan("error Zero();\nfunction f() { revert(\"Access\"); }\n"),
+        );
+        other.put_under("fork");
+        merged.merge(other);
+        assert_eq!(merged.errors[0].1, "vault/src/Errors.sol");
+        assert_eq!(merged.errors[3].1, "fork/src/Vault.sol");
+        let access = &merged.reasons["\"Access\""];
+        assert_eq!(access.count, 2);
+        assert_eq!(
+            access.locations,
+            [
+                ("vault/src/Vault.sol".to_string(), Some("Vault".to_string())),
+                ("fork/src/Vault.sol".to_string(), None),
+            ]
+        );
+        assert_eq!(
+            merged.summary(),
+            "4 custom errors, 2 revert strings (5 uses)"
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Which paths of a source tree are scraped, and why the others are not.
+//!
+//! Every inclusion rule lives in an [`ExclusionPolicy`], whose single
+//! [`decide`](ExclusionPolicy::decide) answers for any path relative to the
+//! scan root. Discovery asks it about each directory before entering it and
+//! about each file it finds, so directory walks and post-filters cannot
+//! disagree.
+//!
+//! # Precedence
+//!
+//! Rules are applied in this order, and the first that excludes wins:
+//!
+//! 1. **Excluded directories.** A path is excluded if any of its directory
+//!    components (the path itself, for a directory) is an excluded name, at
+//!    any depth. Build output and package folders (`.git`, `node_modules`,
+//!    `out`, `cache`, `artifacts`, `build`, `coverage`, `.deps`) are always
+//!    excluded. `lib`/`dependencies`, `test`/`tests`, and `script`/`scripts`
+//!    are excluded by default, and the matching `--include-*` flag removes
+//!    them from the set: an explicit include beats a default exclude, but
+//!    never an always-excluded name, so `--include-lib` still skips
+//!    `lib/x/node_modules/`.
+//! 2. **Non-Solidity files.** Files without a `.sol` extension are excluded.
+//! 3. **Excluded suffixes.** In Foundry projects, `*.t.sol` and `*.s.sol`
+//!    files follow `--include-test` and `--include-script` wherever they
+//!    are, so they are excluded by default even outside `test/` and
+//!    `script/`. Including the directory does not include the suffix, and
+//!    vice versa.
+//!
+//! The scan root itself is never checked: scraping `./test --local`
+//! includes its files.
+//!
+//! # Per-file markers
+//!
+//! A file whose first line is [`INCLUDE_MARKER`] (`// solscrape: include`)
+//! is scraped even if rule 1 or 3 excludes it, so one important file inside
+//! `test/` can be kept without `--include-test`. Like the `--include-*`
+//! flags, the marker never beats an always-excluded name. Discovery therefore
+//! walks every directory but the always-excluded ones, and reads the first
+//! line only of files the rules exclude (see
+//! [`decide_file`](ExclusionPolicy::decide_file)). The opposite marker,
+//! [`IGNORE_MARKER`], applies to files the rules include; it is honored when
+//! the file is processed, so it shows up in the skip report.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Component, Path};
+
+use crate::naming::SOLDEER_DIR;
+use crate::project::ProjectKind;
+
+/// Directory names excluded regardless of flags.
+const ALWAYS_EXCLUDED: [&str; 8] = [
+    ".git",
+    "node_modules",
+    "out",
+    "cache",
+    "artifacts",
+    "build",
+    "coverage",
+    ".deps",
+];
+
+/// The first line that opts a file out of scraping.
+pub const IGNORE_MARKER: &str = "// solscrape: ignore";
+
+/// The first line that opts a file in despite directory or suffix rules.
+pub const INCLUDE_MARKER: &str = "// solscrape: include";
+
+/// Bytes read from the start of a file when looking for a marker.
+const MARKER_SCAN_BYTES: u64 = 256;
+
+/// A per-file override written as the file's first line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Marker {
+    /// [`IGNORE_MARKER`]: never scrape this file.
+    Ignore,
+    /// [`INCLUDE_MARKER`]: scrape this file wherever it is.
+    Include,
+}
+
+/// Returns the marker on `first_line`, if any.
+///
+/// Leading whitespace and a byte-order mark are ignored, and the marker may
+/// be followed by an explanation: `// solscrape: ignore (deprecated)`.
+pub fn marker(first_line: &str) -> Option<Marker> {
+    let line = first_line.trim_start_matches('\u{feff}').trim();
+    [
+        (IGNORE_MARKER, Marker::Ignore),
+        (INCLUDE_MARKER, Marker::Include),
+    ]
+    .into_iter()
+    .find_map(|(text, marker)| {
+        let rest = line.strip_prefix(text)?;
+        (rest.is_empty() || rest.starts_with(char::is_whitespace)).then_some(marker)
+    })
+}
+
+/// Reads the marker on the first line of the file at `path`, if any.
+///
+/// Unreadable files have no marker.
+pub fn read_marker(path: &Path) -> Option<Marker> {
+    let file = File::open(path).ok()?;
+    let mut first_line = String::new();
+    BufReader::new(file.take(MARKER_SCAN_BYTES))
+        .read_line(&mut first_line)
+        .ok()?;
+    marker(&first_line)
+}
+
+/// Which default exclusions the `--include-*` flags lift.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Inclusions {
+    /// `--include-lib`: `lib/` and Soldeer's `dependencies/`.
+    pub lib: bool,
+    /// `--include-test`: `test/`, `tests/`, and Foundry `*.t.sol` files.
+    pub test: bool,
+    /// `--include-script`: `script/`, `scripts/`, and Foundry `*.s.sol` files.
+    pub script: bool,
+}
+
+/// Why [`ExclusionPolicy::decide`] left a path out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// A directory component has an excluded name.
+    Directory(String),
+    /// Not a `.sol` file.
+    NotSolidity,
+    /// The file name ends with an excluded suffix such as `.t.sol`.
+    Suffix(&'static str),
+}
+
+impl Reason {
+    /// The stable rule name used by `--explain`: `default-dir:lib`,
+    /// `always-dir:node_modules`, `suffix:*.t.sol`, or `not-solidity`.
+    pub fn rule(&self) -> String {
+        match self {
+            Reason::Directory(name) if ALWAYS_EXCLUDED.contains(&name.as_str()) => {
+                format!("always-dir:{}", name)
+            }
+            Reason::Directory(name) => format!("default-dir:{}", name),
+            Reason::NotSolidity => "not-solidity".to_string(),
+            Reason::Suffix(suffix) => format!("suffix:*{}", suffix),
+        }
+    }
+
+    /// The `--include-*` flag that lifts this exclusion, if one does.
+    pub fn lifted_by(&self) -> Option<&'static str> {
+        match self {
+            Reason::Directory(name) => match name.as_str() {
+                "lib" | SOLDEER_DIR => Some("include-lib"),
+                "test" | "tests" | "Test" | "Tests" => Some("include-test"),
+                "script" | "scripts" | "Script" | "Scripts" => Some("include-script"),
+                _ => None,
+            },
+            Reason::Suffix(".t.sol") => Some("include-test"),
+            Reason::Suffix(".s.sol") => Some("include-script"),
+            Reason::NotSolidity | Reason::Suffix(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::Directory(name) => write!(f, "inside excluded directory {}/", name),
+            Reason::NotSolidity => write!(f, "not a .sol file"),
+            Reason::Suffix(suffix) => write!(f, "*{} file", suffix),
+        }
+    }
+}
+
+/// The outcome of [`ExclusionPolicy::decide`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision {
+    /// The path is scraped (or, for a directory, entered).
+    Include,
+    /// The path is left out.
+    Exclude(Reason),
+}
+
+/// The complete set of inclusion rules for one scrape.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let policy = ExclusionPolicy::new(&project, Inclusions { lib: true, ..Inclusions::default() });
+/// assert_eq!(policy.decide(Path::new("lib/oz/Token.sol"), false), Decision::Include);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ExclusionPolicy {
+    /// Directory names never entered.
+    excluded_dirs: BTreeSet<&'static str>,
+    /// File name suffixes left out.
+    excluded_suffixes: Vec<&'static str>,
+}
+
+impl ExclusionPolicy {
+    /// Builds the policy for a project of kind `project` under `include`.
+    pub fn new(project: &ProjectKind, include: Inclusions) -> Self {
+        let mut excluded_dirs: BTreeSet<&'static str> = ALWAYS_EXCLUDED.into_iter().collect();
+        if !include.lib {
+            excluded_dirs.extend(["lib", SOLDEER_DIR]);
+        }
+        if !include.test {
+            excluded_dirs.extend(["test", "tests", "Test", "Tests"]);
+        }
+        if !include.script {
+            excluded_dirs.extend(["script", "scripts", "Script", "Scripts"]);
+        }
+
+        let mut excluded_suffixes = Vec::new();
+        if project.is_foundry() {
+            if !include.test {
+                excluded_suffixes.push(".t.sol");
+            }
+            if !include.script {
+                excluded_suffixes.push(".s.sol");
+            }
+        }
+
+        Self {
+            excluded_dirs,
+            excluded_suffixes,
+        }
+    }
+
+    /// The policy discovery walks with: only always-excluded directories are
+    /// skipped, so [`decide_file`](Self::decide_file) can see marked files.
+    pub fn always() -> Self {
+        Self {
+            excluded_dirs: ALWAYS_EXCLUDED.into_iter().collect(),
+            excluded_suffixes: Vec::new(),
+        }
+    }
+
+    /// A policy that excludes nothing, for walks that must see every file.
+    pub fn unfiltered() -> Self {
+        Self {
+            excluded_dirs: BTreeSet::new(),
+            excluded_suffixes: Vec::new(),
+        }
+    }
+
+    /// Decides whether the file at `rel_path` is scraped, letting an
+    /// [`INCLUDE_MARKER`] override directory and suffix rules except
+    /// always-excluded names.
+    ///
+    /// `marker` is only called for files the rules exclude.
+    pub fn decide_file(
+        &self,
+        rel_path: &Path,
+        marker: impl FnOnce() -> Option<Marker>,
+    ) -> Decision {
+        let decision = self.decide(rel_path, false);
+        let overridable = matches!(
+            decision,
+            Decision::Exclude(Reason::Directory(_) | Reason::Suffix(_))
+        ) && Self::always().decide(rel_path, false) == Decision::Include;
+        if overridable && marker() == Some(Marker::Include) {
+            Decision::Include
+        } else {
+            decision
+        }
+    }
+
+    /// Decides whether `rel_path`, relative to the scan root, is scraped.
+    ///
+    /// For a directory, [`Decision::Include`] means it is entered. The rules
+    /// and their precedence are described in the [module docs](self).
+    pub fn decide(&self, rel_path: &Path, is_dir: bool) -> Decision {
+        let directories = if is_dir {
+            Some(rel_path)
+        } else {
+            rel_path.parent()
+        };
+        let excluded_dir =
+            directories
+                .into_iter()
+                .flat_map(Path::components)
+                .find_map(|c| match c {
+                    Component::Normal(name) => {
+                        let name = name.to_string_lossy();
+                        self.excluded_dirs
+                            .contains(name.as_ref())
+                            .then(|| name.to_string())
+                    }
+                    _ => None,
+                });
+        if let Some(name) = excluded_dir {
+            return Decision::Exclude(Reason::Directory(name));
+        }
+        if is_dir {
+            return Decision::Include;
+        }
+
+        if rel_path.extension().is_none_or(|ext| ext != "sol") {
+            return Decision::Exclude(Reason::NotSolidity);
+        }
+        let name = rel_path
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+        match self.excluded_suffixes.iter().find(|s| name.ends_with(*s)) {
+            Some(suffix) => Decision::Exclude(Reason::Suffix(suffix)),
+            None => Decision::Include,
+        }
+    }
+
+    /// Returns true if directories named `name` are never entered.
+    pub fn excludes_dir_name(&self, name: &str) -> bool {
+        self.excluded_dirs.contains(name)
+    }
+
+    /// The excluded directory names, sorted.
+    pub fn excluded_dir_names(&self) -> Vec<&'static str> {
+        self.excluded_dirs.iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Framework;
+
+    fn include(lib: bool, test: bool, script: bool) -> Inclusions {
+        Inclusions { lib, test, script }
+    }
+
+    /// Encodes the precedence rules as a decision matrix.
+    #[test]
+    fn test_decision_matrix() {
+        let foundry = ProjectKind {
+            frameworks: vec![Framework::Foundry],
+        };
+        let plain = ProjectKind::default();
+        let none = include(false, false, false);
+        let all = include(true, true, true);
+        let dir = |name: &str| Decision::Exclude(Reason::Directory(name.to_string()));
+        use Decision::Include;
+
+        let cases: Vec<(&ProjectKind, Inclusions, &str, bool, Decision)> = vec![
+            // Defaults
+            (&plain, none, "src/Token.sol", false, Include),
+            (&plain, none, "Root.sol", false, Include),
+            (&plain, none, "src", true, Include),
+            (&plain, none, "lib", true, dir("lib")),
+            (&plain, none, "lib/oz/Token.sol", false, dir("lib")),
+            (
+                &plain,
+                none,
+                "dependencies/oz-5.0.2/T.sol",
+                false,
+                dir("dependencies"),
+            ),
+            (&plain, none, "src/test/Mock.sol", false, dir("test")),
+            (&plain, none, "Tests/A.sol", false, dir("Tests")),
+            (&plain, none, "scripts/Deploy.sol", false, dir("scripts")),
+            (
+                &plain,
+                none,
+                "src/notes.txt",
+                false,
+                Decision::Exclude(Reason::NotSolidity),
+            ),
+            // The outermost excluded component is reported
+            (&plain, none, "lib/x/node_modules/A.sol", false, dir("lib")),
+            // Explicit includes beat default excludes...
+            (&plain, all, "lib/oz/Token.sol", false, Include),
+            (&plain, all, "dependencies/oz-5.0.2/T.sol", false, Include),
+            (&plain, all, "test/A.sol", false, Include),
+            (&plain, all, "script/Deploy.sol", false, Include),
+            // ...but never always-excluded names
+            (
+                &plain,
+                all,
+                "lib/x/node_modules/A.sol",
+                false,
+                dir("node_modules"),
+            ),
+            (&plain, all, "out/A.sol", false, dir("out")),
+            (&plain, all, "src/.git", true, dir(".git")),
+            // Each flag lifts only its own directories
+            (
+                &plain,
+                include(true, false, false),
+                "test/A.sol",
+                false,
+                dir("test"),
+            ),
+            (
+                &plain,
+                include(false, true, false),
+                "lib/A.sol",
+                false,
+                dir("lib"),
+            ),
+            // Suffixes only in Foundry projects, independent of directories
+            (&plain, none, "src/A.t.sol", false, Include),
+            (
+                &foundry,
+                none,
+                "src/A.t.sol",
+                false,
+                Decision::Exclude(Reason::Suffix(".t.sol")),
+            ),
+            (
+                &foundry,
+                none,
+                "src/Deploy.s.sol",
+                false,
+                Decision::Exclude(Reason::Suffix(".s.sol")),
+            ),
+            (
+                &foundry,
+                include(false, true, false),
+                "src/A.t.sol",
+                false,
+                Include,
+            ),
+            (
+                &foundry,
+                include(false, true, false),
+                "src/Deploy.s.sol",
+                false,
+                Decision::Exclude(Reason::Suffix(".s.sol")),
+            ),
+            (
+                &foundry,
+                include(false, true, false),
+                "test/A.t.sol",
+                false,
+                Include,
+            ),
+            (
+                &foundry,
+                include(false, false, true),
+                "test/A.t.sol",
+                false,
+                dir("test"),
+            ),
+            // A directory rule outranks a suffix rule
+            (&foundry, none, "test/A.t.sol", false, dir("test")),
+        ];
+
+        for (project, inclusions, path, is_dir, expected) in cases {
+            let policy = ExclusionPolicy::new(project, inclusions);
+            assert_eq!(
+                policy.decide(Path::new(path), is_dir),
+                expected,
+                "{} with {:?} in {}",
+                path,
+                inclusions,
+                project
+            );
+        }
+    }
+
+    /// Verifies the `--explain` rule names and the flags lifting each reason.
+    #[test]
+    fn test_reason_rules() {
+        let dir = |name: &str| Reason::Directory(name.to_string());
+        for (reason, rule, flag) in [
+            (dir("lib"), "default-dir:lib", Some("include-lib")),
+            (
+                dir("dependencies"),
+                "default-dir:dependencies",
+                Some("include-lib"),
+            ),
+            (dir("Tests"), "default-dir:Tests", Some("include-test")),
+            (
+                dir("scripts"),
+                "default-dir:scripts",
+                Some("include-script"),
+            ),
+            (dir("node_modules"), "always-dir:node_modules", None),
+            (
+                Reason::Suffix(".t.sol"),
+                "suffix:*.t.sol",
+                Some("include-test"),
+            ),
+            (
+                Reason::Suffix(".s.sol"),
+                "suffix:*.s.sol",
+                Some("include-script"),
+            ),
+            (Reason::NotSolidity, "not-solidity", None),
+        ] {
+            assert_eq!(reason.rule(), rule);
+            assert_eq!(reason.lifted_by(), flag, "{}", rule);
+        }
+        assert_eq!(
+            ExclusionPolicy::unfiltered().decide(Path::new("node_modules/x/A.sol"), false),
+            Decision::Include
+        );
+    }
+
+    /// Verifies that the include marker beats default rules but not
+    /// always-excluded names or the extension rule.
+    #[test]
+    fn test_marker_precedence() {
+        let foundry = ProjectKind {
+            frameworks: vec![Framework::Foundry],
+        };
+        let policy = ExclusionPolicy::new(&foundry, Inclusions::default());
+        let include = Some(Marker::Include);
+        let ignore = Some(Marker::Ignore);
+
+        let cases = [
+            ("test/Important.sol", include, true),
+            ("script/Deploy.s.sol", include, true),
+            ("src/Helper.t.sol", include, true),
+            ("lib/oz/token/ERC20.sol", include, true),
+            ("test/Important.sol", None, false),
+            ("test/Important.sol", ignore, false),
+            ("lib/x/node_modules/A.sol", include, false),
+            ("out/A.sol", include, false),
+            ("test/notes.txt", include, false),
+            ("src/Vault.sol", None, true),
+        ];
+        for (path, marker, included) in cases {
+            let decision = policy.decide_file(Path::new(path), || marker);
+            assert_eq!(decision == Decision::Include, included, "{}", path);
+        }
+
+        // Included files are decided without reading them
+        let decision = policy.decide_file(Path::new("src/Vault.sol"), || -> Option<Marker> {
+            panic!("marker read for an included file")
+        });
+        assert_eq!(decision, Decision::Include);
+        assert!(ExclusionPolicy::always().excludes_dir_name("node_modules"));
+        assert!(!ExclusionPolicy::always().excludes_dir_name("test"));
+    }
+
+    /// Verifies marker recognition on first lines.
+    #[test]
+    fn test_marker() {
+        assert_eq!(marker("// solscrape: ignore"), Some(Marker::Ignore));
+        assert_eq!(marker("  // solscrape: include\n"), Some(Marker::Include));
+        assert_eq!(
+            marker("\u{feff}// solscrape: ignore (deprecated)"),
+            Some(Marker::Ignore)
+        );
+        assert_eq!(marker("// solscrape: ignored"), None);
+        assert_eq!(marker("// SPDX-License-Identifier: MIT"), None);
+        assert_eq!(marker("pragma solidity ^0.8.0; // solscrape: ignore"), None);
+    }
+
+    /// Verifies the reported names and reasons.
+    #[test]
+    fn test_excluded_names_and_reasons() {
+        let policy = ExclusionPolicy::new(&ProjectKind::default(), Inclusions::default());
+        assert!(policy.excludes_dir_name("lib"));
+        assert!(!policy.excludes_dir_name("src"));
+        assert_eq!(policy.excluded_dir_names().len(), 8 + 2 + 4 + 4);
+        assert_eq!(
+            Reason::Directory("lib".to_string()).to_string(),
+            "inside excluded directory lib/"
+        );
+        assert_eq!(Reason::Suffix(".t.sol").to_string(), "*.t.sol file");
+    }
+}
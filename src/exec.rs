@@ -0,0 +1,282 @@
+//! The single place solscrape spawns external programs from.
+//!
+//! Every subprocess goes through an [`Exec`], which logs the invocation at
+//! detail level (visible with `--verbose`), records it for auditing (both
+//! with any password in a URL argument redacted, see
+//! [`forge_host::redact`]), and,
+//! under `--no-subprocess`, refuses any program not on an allow-list derived
+//! from the flags in use.
+
+use std::ffi::{OsStr, OsString};
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::forge_host;
+use crate::logger::Logger;
+
+/// One spawned (or refused) subprocess.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invocation {
+    /// The program name, e.g. `"git"`.
+    pub program: &'static str,
+    /// The arguments, lossily converted for display, with URL passwords
+    /// redacted.
+    pub args: Vec<String>,
+    /// Whether the policy allowed it to run.
+    pub allowed: bool,
+}
+
+impl Invocation {
+    /// The invocation as a shell-like command line.
+    pub fn command_line(&self) -> String {
+        std::iter::once(self.program.to_string())
+            .chain(self.args.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+/// Runs external programs under a policy, keeping an audit trail.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let exec = Exec::restricted(&["git"]);
+/// let output = exec.output("git", ["--version"], None, &log)?;
+/// assert!(exec.output("curl", ["https://x"], None, &log).is_err());
+/// ```
+pub struct Exec {
+    /// Programs that may run, or `None` for no restriction.
+    allowed: Option<Vec<&'static str>>,
+    /// Every invocation requested so far, in order.
+    audit: Mutex<Vec<Invocation>>,
+}
+
+impl Exec {
+    /// An executor that runs any program.
+    pub fn unrestricted() -> Self {
+        Self {
+            allowed: None,
+            audit: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// An executor that only runs the listed programs.
+    pub fn restricted(allowed: &[&'static str]) -> Self {
+        Self {
+            allowed: Some(allowed.to_vec()),
+            audit: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Returns true if `program` may run under this policy.
+    pub fn allows(&self, program: &str) -> bool {
+        self.allowed
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&program))
+    }
+
+    /// Runs `program` with `args` (in `dir`, if given) and captures its output.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`io::ErrorKind::PermissionDenied`] error naming the allowed
+    /// programs if the policy forbids `program`, or the spawn error.
+    pub fn output<I, S>(
+        &self,
+        program: &'static str,
+        args: I,
+        dir: Option<&Path>,
+        log: &Logger,
+    ) -> io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.output_within(program, args, dir, None, log)
+    }
+
+    /// Like [`output`](Self::output), but kills `program` once `timeout`
+    /// has elapsed.
+    ///
+    /// # Errors
+    ///
+    /// Additionally returns an [`io::ErrorKind::TimedOut`] error when the
+    /// program was killed.
+    pub fn output_within<I, S>(
+        &self,
+        program: &'static str,
+        args: I,
+        dir: Option<&Path>,
+        timeout: Option<Duration>,
+        log: &Logger,
+    ) -> io::Result<Output>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args: Vec<OsString> = args.into_iter().map(|a| a.as_ref().to_owned()).collect();
+        let invocation = Invocation {
+            program,
+            args: args
+                .iter()
+                .map(|a| forge_host::redact(&a.to_string_lossy()))
+                .collect(),
+            allowed: self.allows(program),
+        };
+        let command_line = invocation.command_line();
+        if let Ok(mut audit) = self.audit.lock() {
+            audit.push(invocation.clone());
+        }
+
+        if !invocation.allowed {
+            log.detail(&format!("exec refused: {}", command_line));
+            let allowed = self.allowed.as_deref().unwrap_or_default();
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "--no-subprocess forbids running {} (allowed: {})",
+                    program,
+                    if allowed.is_empty() {
+                        "none".to_string()
+                    } else {
+                        allowed.join(", ")
+                    }
+                ),
+            ));
+        }
+
+        log.detail(&format!("exec: {}", command_line));
+        let mut command = Command::new(program);
+        command.args(&args);
+        if let Some(dir) = dir {
+            command.current_dir(dir);
+        }
+        match timeout {
+            None => command.output(),
+            Some(timeout) => output_within(command, program, timeout, log),
+        }
+    }
+
+    /// Every invocation requested so far, including refused ones.
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.audit.lock().map(|a| a.clone()).unwrap_or_default()
+    }
+}
+
+/// Runs `command`, polling for its exit and killing it after `timeout`.
+///
+/// Output pipes are drained on their own threads so a chatty program cannot
+/// block on a full pipe while being waited for.
+fn output_within(
+    mut command: Command,
+    program: &str,
+    timeout: Duration,
+    log: &Logger,
+) -> io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().map(drain);
+    let stderr = child.stderr.take().map(drain);
+
+    let end = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= end {
+            // Grandchildren may keep the pipes open, so the readers are not joined
+            let _ = child.kill();
+            let _ = child.wait();
+            log.detail(&format!("exec timed out after {:?}: {}", timeout, program));
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!("{} did not finish within {}s", program, timeout.as_secs()),
+            ));
+        }
+        thread::sleep(Duration::from_millis(20));
+    };
+    let collect = |reader: Option<thread::JoinHandle<Vec<u8>>>| {
+        reader
+            .and_then(|reader| reader.join().ok())
+            .unwrap_or_default()
+    };
+    Ok(Output {
+        status,
+        stdout: collect(stdout),
+        stderr: collect(stderr),
+    })
+}
+
+/// Reads `pipe` to its end on a new thread.
+fn drain(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies that refused programs are audited, without URL passwords,
+    /// but never spawned.
+    #[test]
+    fn test_restricted_refuses_and_audits() {
+        let exec = Exec::restricted(&["git"]);
+        let log = Logger::new();
+
+        let err = exec
+            .output(
+                "solscrape-no-such-program",
+                ["--flag", "https://u:secret@h/r"],
+                None,
+                &log,
+            )
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.to_string().contains("(allowed: git)"), "{}", err);
+        assert_eq!(
+            exec.invocations(),
+            vec![Invocation {
+                program: "solscrape-no-such-program",
+                args: vec!["--flag".to_string(), "https://u:***@h/r".to_string()],
+                allowed: false,
+            }]
+        );
+        assert!(exec.allows("git"));
+        assert!(Exec::unrestricted().allows("anything"));
+        assert!(!Exec::restricted(&[]).allows("git"));
+    }
+
+    /// Verifies that a timeout kills a slow program and keeps fast ones' output.
+    #[cfg(unix)]
+    #[test]
+    fn test_output_within_timeout() {
+        let exec = Exec::unrestricted();
+        let log = Logger::new();
+
+        let started = Instant::now();
+        let err = exec
+            .output_within("sleep", ["5"], None, Some(Duration::from_millis(100)), &log)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let output = exec
+            .output_within("echo", ["done"], None, Some(Duration::from_secs(5)), &log)
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"done\n");
+    }
+}
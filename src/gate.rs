@@ -0,0 +1,452 @@
+//! Soft checks on a finished scrape for CI (`--gate`).
+//!
+//! A scrape can succeed and still leave something a pipeline wants to hear
+//! about: files skipped, a license it cannot ship, imports the output does
+//! not contain, or an output too large for the model it is meant for. Each
+//! `--gate` names one such condition; [`evaluate`] checks them all against
+//! what the scrape [`Observed`], after the output is written, and the run
+//! exits with its own code when any fails, so CI can tell "scraped, but
+//! look at this" from both success and failure.
+//!
+//! | Gate | Fails when |
+//! |------|------------|
+//! | `no-skips` | Any candidate file was skipped |
+//! | `no-license:<ID>` | An included file's SPDX expression names `ID` (or `ID-only`, `ID-or-later`, `ID+`) |
+//! | `no-missing-imports` | An import of an included file resolves to no included file |
+//! | `max-lines:<N>` | The output has more than `N` lines |
+//!
+//! The facts a gate needs from each file are taken by [`FileFacts::scan`]
+//! while the file is processed.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::graph::{self, Remapping};
+use crate::imports;
+
+/// The marker of an SPDX license line.
+const SPDX_MARKER: &str = "SPDX-License-Identifier:";
+
+/// The most files or imports a failure lists before `and N more`.
+const MAX_LISTED: usize = 5;
+
+/// One `--gate` condition.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Gate {
+    /// No candidate file was skipped.
+    NoSkips,
+    /// No included file is under the SPDX license with this identifier.
+    NoLicense(String),
+    /// Every import of an included file is in the output.
+    NoMissingImports,
+    /// The output has at most this many lines.
+    MaxLines(usize),
+}
+
+impl Gate {
+    /// Parses one condition, as given to `--gate`.
+    ///
+    /// # Errors
+    ///
+    /// | Error | Condition |
+    /// |-------|-----------|
+    /// | `"Invalid --gate: {text} (expected no-skips, ...)"` | `text` names no condition |
+    /// | `"Invalid --gate no-license: ..."` | The identifier is empty or has spaces |
+    /// | `"Invalid --gate max-lines: ..."` | The limit is not a whole number |
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// assert_eq!(Gate::parse("max-lines:200000"), Ok(Gate::MaxLines(200_000)));
+    /// ```
+    pub fn parse(text: &str) -> Result<Self, String> {
+        match text.split_once(':') {
+            None if text == "no-skips" => Ok(Gate::NoSkips),
+            None if text == "no-missing-imports" => Ok(Gate::NoMissingImports),
+            Some(("no-license", id)) => {
+                if id.is_empty() || id.contains(char::is_whitespace) {
+                    return Err(format!(
+                        "Invalid --gate no-license: {:?} (expected an SPDX identifier such as GPL-3.0)",
+                        id
+                    ));
+                }
+                Ok(Gate::NoLicense(id.to_string()))
+            }
+            Some(("max-lines", limit)) => limit.parse().map(Gate::MaxLines).map_err(|_| {
+                format!(
+                    "Invalid --gate max-lines: {} (expected a whole number of lines)",
+                    limit
+                )
+            }),
+            _ => Err(format!(
+                "Invalid --gate: {} (expected no-skips, no-license:<ID>, \
+                 no-missing-imports, or max-lines:<N>)",
+                text
+            )),
+        }
+    }
+
+    /// Returns true if the gate needs the [`FileFacts`] of included files.
+    pub fn needs_facts(&self) -> bool {
+        matches!(self, Gate::NoLicense(_) | Gate::NoMissingImports)
+    }
+}
+
+impl fmt::Display for Gate {
+    /// Writes the gate as it is given to `--gate`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Gate::NoSkips => write!(f, "no-skips"),
+            Gate::NoLicense(id) => write!(f, "no-license:{}", id),
+            Gate::NoMissingImports => write!(f, "no-missing-imports"),
+            Gate::MaxLines(limit) => write!(f, "max-lines:{}", limit),
+        }
+    }
+}
+
+/// What the gates need to know about one included file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileFacts {
+    /// The SPDX license expression, when the file has one.
+    pub license: Option<String>,
+    /// The path of each `import`, as written, in source order.
+    pub imports: Vec<String>,
+}
+
+impl FileFacts {
+    /// Reads the facts of a file from its original `code`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let facts = FileFacts::scan("// SPDX-License-Identifier: MIT\nimport \"./A.sol\";");
+    /// assert_eq!(facts.license.as_deref(), Some("MIT"));
+    /// assert_eq!(facts.imports, ["./A.sol"]);
+    /// ```
+    pub fn scan(code: &str) -> Self {
+        Self {
+            license: spdx_expression(code),
+            imports: imports::parse_imports(code)
+                .into_iter()
+                .map(|import| import.path)
+                .collect(),
+        }
+    }
+}
+
+/// The expression of the first `SPDX-License-Identifier:` line of `code`,
+/// without a closing `*/`.
+fn spdx_expression(code: &str) -> Option<String> {
+    code.lines().find_map(|line| {
+        let (_, rest) = line.split_once(SPDX_MARKER)?;
+        let expression = rest.trim().trim_end_matches("*/").trim();
+        (!expression.is_empty()).then(|| expression.to_string())
+    })
+}
+
+/// Returns true if the SPDX `expression` names the license `id`, in any
+/// letter case, alone or as its `-only`, `-or-later`, or `+` form.
+fn names_license(expression: &str, id: &str) -> bool {
+    expression
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .any(|word| {
+            let word = word.to_ascii_lowercase();
+            let id = id.to_ascii_lowercase();
+            [
+                id.clone(),
+                format!("{}-only", id),
+                format!("{}-or-later", id),
+                format!("{}+", id),
+            ]
+            .contains(&word)
+        })
+}
+
+/// The imports of `imports`, given as `(relative path, import paths)` for
+/// every included file, that resolve to none of the included files, as
+/// `(relative path, import path)`.
+///
+/// Imports resolve as for `--related` (see [`graph::resolve`]), with the
+/// source's `remappings`.
+pub fn missing_imports(
+    imports: &[(String, Vec<String>)],
+    remappings: &[Remapping],
+) -> Vec<(String, String)> {
+    let included: BTreeSet<&str> = imports.iter().map(|(path, _)| path.as_str()).collect();
+    let mut missing = Vec::new();
+    for (importer, paths) in imports {
+        for import in paths {
+            let found = graph::resolve(importer, import, remappings)
+                .iter()
+                .any(|candidate| included.contains(candidate.as_str()));
+            if !found {
+                missing.push((importer.clone(), import.clone()));
+            }
+        }
+    }
+    missing
+}
+
+/// What a finished scrape observed, for [`evaluate`]; paths are as
+/// displayed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Observed {
+    /// Each skipped candidate with the name of its reason.
+    pub skipped: Vec<(String, &'static str)>,
+    /// Each included file with an SPDX expression, and the expression.
+    pub licenses: Vec<(String, String)>,
+    /// Each import not in the output, with the file making it.
+    pub missing_imports: Vec<(String, String)>,
+    /// The lines of the output.
+    pub line_count: usize,
+}
+
+/// A gate that failed, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    /// The gate.
+    pub gate: Gate,
+    /// What failed it, e.g. `"2 files skipped: ..."`.
+    pub reason: String,
+}
+
+/// Checks every gate of `gates` against `observed`, returning those that
+/// fail, in the order given.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let observed = Observed { line_count: 250_000, ..Observed::default() };
+/// let failed = evaluate(&[Gate::NoSkips, Gate::MaxLines(200_000)], &observed);
+/// assert_eq!(failed[0].reason, "the output has 250000 lines, over 200000");
+/// ```
+pub fn evaluate(gates: &[Gate], observed: &Observed) -> Vec<Failure> {
+    gates
+        .iter()
+        .filter_map(|gate| {
+            let reason = match gate {
+                Gate::NoSkips if !observed.skipped.is_empty() => format!(
+                    "{} skipped: {}",
+                    count(observed.skipped.len(), "file"),
+                    list(
+                        observed
+                            .skipped
+                            .iter()
+                            .map(|(path, reason)| format!("{} ({})", path, reason))
+                    )
+                ),
+                Gate::NoLicense(id) => {
+                    let under: Vec<String> = observed
+                        .licenses
+                        .iter()
+                        .filter(|(_, expression)| names_license(expression, id))
+                        .map(|(path, expression)| format!("{} ({})", path, expression))
+                        .collect();
+                    if under.is_empty() {
+                        return None;
+                    }
+                    format!(
+                        "{} under {}: {}",
+                        count(under.len(), "file"),
+                        id,
+                        list(under.into_iter())
+                    )
+                }
+                Gate::NoMissingImports if !observed.missing_imports.is_empty() => format!(
+                    "{} not in the output: {}",
+                    count(observed.missing_imports.len(), "import"),
+                    list(
+                        observed
+                            .missing_imports
+                            .iter()
+                            .map(|(path, import)| format!("{} imports {}", path, import))
+                    )
+                ),
+                Gate::MaxLines(limit) if observed.line_count > *limit => format!(
+                    "the output has {} lines, over {}",
+                    observed.line_count, limit
+                ),
+                _ => return None,
+            };
+            Some(Failure {
+                gate: gate.clone(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// `"1 file"`, `"3 files"`.
+fn count(n: usize, noun: &str) -> String {
+    format!("{} {}{}", n, noun, if n == 1 { "" } else { "s" })
+}
+
+/// Joins the first [`MAX_LISTED`] of `items` with commas, noting how many
+/// more there are.
+fn list(items: impl ExactSizeIterator<Item = String>) -> String {
+    let total = items.len();
+    let shown: Vec<String> = items.take(MAX_LISTED).collect();
+    match total - shown.len() {
+        0 => shown.join(", "),
+        more => format!("{}, and {} more", shown.join(", "), more),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies parsing of every condition, its display, and the errors.
+    #[test]
+    fn test_parse() {
+        for text in [
+            "no-skips",
+            "no-license:GPL-3.0",
+            "no-missing-imports",
+            "max-lines:200000",
+        ] {
+            assert_eq!(Gate::parse(text).unwrap().to_string(), text);
+        }
+        assert_eq!(Gate::parse("max-lines:10"), Ok(Gate::MaxLines(10)));
+        assert!(
+            Gate::parse("max-lines:lots")
+                .unwrap_err()
+                .starts_with("Invalid --gate max-lines: lots")
+        );
+        assert!(
+            Gate::parse("no-license:")
+                .unwrap_err()
+                .starts_with("Invalid --gate no-license")
+        );
+        assert!(
+            Gate::parse("no-skip")
+                .unwrap_err()
+                .starts_with("Invalid --gate: no-skip (expected")
+        );
+        assert!(Gate::NoMissingImports.needs_facts());
+        assert!(!Gate::MaxLines(1).needs_facts());
+    }
+
+    /// Verifies `no-skips`, listing at most five files.
+    #[test]
+    fn test_no_skips() {
+        let mut observed = Observed::default();
+        assert!(evaluate(&[Gate::NoSkips], &observed).is_empty());
+        observed.skipped = (0..7).map(|i| (format!("{}.sol", i), "binary")).collect();
+        assert_eq!(
+            evaluate(&[Gate::NoSkips], &observed)[0].reason,
+            "7 files skipped: 0.sol (binary), 1.sol (binary), 2.sol (binary), \
+             3.sol (binary), 4.sol (binary), and 2 more"
+        );
+    }
+
+    /// Verifies `no-license` against SPDX expressions, including the
+    /// `-only`/`-or-later` forms and compound expressions.
+    #[test]
+    fn test_no_license() {
+        let observed = Observed {
+            licenses: vec![
+                ("src/A.sol".to_string(), "MIT".to_string()),
+                ("src/B.sol".to_string(), "GPL-3.0-or-later".to_string()),
+                (
+                    "src/C.sol".to_string(),
+                    "Apache-2.0 AND (MIT OR gpl-3.0)".to_string(),
+                ),
+                ("src/D.sol".to_string(), "AGPL-3.0-only".to_string()),
+            ],
+            ..Observed::default()
+        };
+        let gate = Gate::NoLicense("GPL-3.0".to_string());
+        assert_eq!(
+            evaluate(&[gate], &observed)[0].reason,
+            "2 files under GPL-3.0: src/B.sol (GPL-3.0-or-later), src/C.sol \
+             (Apache-2.0 AND (MIT OR gpl-3.0))"
+        );
+        assert!(evaluate(&[Gate::NoLicense("BUSL-1.1".to_string())], &observed).is_empty());
+        assert_eq!(
+            spdx_expression("/* SPDX-License-Identifier: BUSL-1.1 */\ncontract A {}"),
+            Some("BUSL-1.1".to_string())
+        );
+        assert_eq!(spdx_expression("contract A {}"), None);
+    }
+
+    /// Verifies `no-missing-imports` with relative and remapped imports.
+    #[test]
+    fn test_no_missing_imports() {
+        let imports = vec![
+            (
+                "src/Vault.sol".to_string(),
+                vec![
+                    "./Token.sol".to_string(),
+                    "@oz/token/ERC20.sol".to_string(),
+                    "forge-std/Test.sol".to_string(),
+                ],
+            ),
+            ("src/Token.sol".to_string(), Vec::new()),
+            ("lib/oz/contracts/token/ERC20.sol".to_string(), Vec::new()),
+        ];
+        let remappings = graph::parse_remappings("@oz/=lib/oz/contracts/\n");
+        let missing = missing_imports(&imports, &remappings);
+        assert_eq!(
+            missing,
+            [(
+                "src/Vault.sol".to_string(),
+                "forge-std/Test.sol".to_string()
+            )]
+        );
+        let observed = Observed {
+            missing_imports: missing,
+            ..Observed::default()
+        };
+        assert_eq!(
+            evaluate(&[Gate::NoMissingImports], &observed)[0].reason,
+            "1 import not in the output: src/Vault.sol imports forge-std/Test.sol"
+        );
+        assert_eq!(
+            FileFacts::scan(
+                "// SPDX-License-Identifier: MIT\n// import \"./B.sol\";\nimport \"./A.sol\";\n"
+            ),
+            FileFacts {
+                license: Some("MIT".to_string()),
+                imports: vec!["./A.sol".to_string()],
+            }
+        );
+    }
+
+    /// Verifies `max-lines` at and over the limit.
+    #[test]
+    fn test_max_lines() {
+        let observed = Observed {
+            line_count: 200,
+            ..Observed::default()
+        };
+        assert!(evaluate(&[Gate::MaxLines(200)], &observed).is_empty());
+        assert_eq!(
+            evaluate(&[Gate::MaxLines(199)], &observed)[0].reason,
+            "the output has 200 lines, over 199"
+        );
+    }
+
+    /// Verifies that several gates are all checked, failures kept in the
+    /// order given, and passing ones left out.
+    #[test]
+    fn test_several_gates() {
+        let observed = Observed {
+            skipped: vec![("src/Big.sol".to_string(), "long-lines")],
+            licenses: vec![("src/A.sol".to_string(), "MIT".to_string())],
+            line_count: 500,
+            ..Observed::default()
+        };
+        let gates = [
+            Gate::MaxLines(100),
+            Gate::NoLicense("GPL-3.0".to_string()),
+            Gate::NoMissingImports,
+            Gate::NoSkips,
+        ];
+        let failed: Vec<String> = evaluate(&gates, &observed)
+            .iter()
+            .map(|failure| failure.gate.to_string())
+            .collect();
+        assert_eq!(failed, ["max-lines:100", "no-skips"]);
+    }
+}
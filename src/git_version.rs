@@ -0,0 +1,182 @@
+//! The installed git's version, and the flags that need a newer one.
+//!
+//! Some flags rely on git features that old distributions lack: the
+//! pre-check's blob-less clone needs partial clone support, and `--since`
+//! and `--reuse-clone` ask git whether a clone is shallow. Without a check,
+//! an old git fails those commands with errors that do not name the cause.
+//! [`GitVersion::parse`] reads `git --version` output and [`check`] turns a
+//! [`Feature`] the installed git lacks into a clear error before any git
+//! command runs.
+
+use std::fmt;
+
+/// A git release, as `major.minor.patch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GitVersion {
+    /// The major release, 2 for any git since 2014.
+    pub major: u32,
+    /// The minor release, where features arrive.
+    pub minor: u32,
+    /// The maintenance release.
+    pub patch: u32,
+}
+
+impl GitVersion {
+    /// Creates a version.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Reads the version from `git --version` output.
+    ///
+    /// Vendor suffixes after the numbers (`(Apple Git-146)`, `.windows.1`,
+    /// `.vfs.0.0`, `-rc1`) are ignored, and a missing patch level is 0.
+    /// Returns `None` when no version follows `git version`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let version = GitVersion::parse("git version 2.39.3 (Apple Git-146)\n");
+    /// assert_eq!(version, Some(GitVersion::new(2, 39, 3)));
+    /// ```
+    pub fn parse(output: &str) -> Option<Self> {
+        let rest = output.trim().strip_prefix("git version ")?;
+        let numbers = rest.split_whitespace().next()?;
+        let mut parts = numbers.split('.').map(|part| {
+            let digits = part.len() - part.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            part[..digits].parse::<u32>().ok()
+        });
+        let major = parts.next()??;
+        let minor = parts.next()??;
+        let patch = parts.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for GitVersion {
+    /// Writes the version as `2.39.3`, or `2.27` for a `.0` release.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.patch == 0 {
+            write!(f, "{}.{}", self.major, self.minor)
+        } else {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+        }
+    }
+}
+
+/// A flag that needs at least some git release.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Feature {
+    /// The flag, as the user wrote it.
+    pub flag: &'static str,
+    /// The oldest git release the flag works with.
+    pub minimum: GitVersion,
+}
+
+/// The pre-check's shallow, blob-less clone (`--filter=blob:none`).
+pub const PRECHECK: Feature = Feature {
+    flag: "--precheck",
+    minimum: GitVersion::new(2, 19, 0),
+};
+
+/// Deepening a shallow clone to the `--since` ref, which needs
+/// `git rev-parse --is-shallow-repository`.
+pub const SINCE: Feature = Feature {
+    flag: "--since",
+    minimum: GitVersion::new(2, 15, 0),
+};
+
+/// Refreshing a `--reuse-clone` checkout, which needs
+/// `git rev-parse --is-shallow-repository`.
+pub const REUSE_CLONE: Feature = Feature {
+    flag: "--reuse-clone",
+    minimum: GitVersion::new(2, 15, 0),
+};
+
+/// Checking out a `--rev`, which needs
+/// `git rev-parse --is-shallow-repository`.
+pub const REV: Feature = Feature {
+    flag: "--rev",
+    minimum: GitVersion::new(2, 15, 0),
+};
+
+/// Checks that the git found supports `feature`.
+///
+/// An unknown version passes, leaving git to report any problem itself.
+///
+/// # Errors
+///
+/// Returns `"requires git >= {minimum} for {flag}, found {found}"`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let found = GitVersion::parse("git version 2.17.1");
+/// assert_eq!(
+///     check(found, &PRECHECK).unwrap_err(),
+///     "requires git >= 2.19 for --precheck, found 2.17.1"
+/// );
+/// ```
+pub fn check(found: Option<GitVersion>, feature: &Feature) -> Result<(), String> {
+    match found {
+        Some(found) if found < feature.minimum => Err(format!(
+            "requires git >= {} for {}, found {}",
+            feature.minimum, feature.flag, found
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies parsing against version strings seen in the wild.
+    #[test]
+    fn test_parse() {
+        for (output, expected) in [
+            ("git version 2.43.0\n", Some((2, 43, 0))),
+            ("git version 2.17.1", Some((2, 17, 1))),
+            ("git version 1.8.3.1", Some((1, 8, 3))),
+            ("git version 2.39.3 (Apple Git-146)", Some((2, 39, 3))),
+            ("git version 2.24.3 (Apple Git-128)\n", Some((2, 24, 3))),
+            ("git version 2.45.2.windows.1", Some((2, 45, 2))),
+            ("git version 2.40.1.vfs.0.0", Some((2, 40, 1))),
+            ("git version 2.34.1.574.g1e8e3a7", Some((2, 34, 1))),
+            ("git version 2.47.0-rc1", Some((2, 47, 0))),
+            ("git version 2.30", Some((2, 30, 0))),
+            ("  git version 2.20.1 \n", Some((2, 20, 1))),
+            ("git version", None),
+            ("git version unknown", None),
+            ("hub version 2.14.2", None),
+            ("", None),
+        ] {
+            let expected =
+                expected.map(|(major, minor, patch)| GitVersion::new(major, minor, patch));
+            assert_eq!(GitVersion::parse(output), expected, "{:?}", output);
+        }
+        assert!(GitVersion::new(2, 9, 5) < GitVersion::new(2, 19, 0));
+        assert_eq!(GitVersion::new(2, 27, 0).to_string(), "2.27");
+        assert_eq!(GitVersion::new(2, 39, 3).to_string(), "2.39.3");
+    }
+
+    /// Verifies the error and that an unknown or newer version passes.
+    #[test]
+    fn test_check() {
+        assert_eq!(
+            check(Some(GitVersion::new(2, 17, 1)), &PRECHECK).unwrap_err(),
+            "requires git >= 2.19 for --precheck, found 2.17.1"
+        );
+        assert_eq!(
+            check(Some(GitVersion::new(2, 11, 0)), &SINCE).unwrap_err(),
+            "requires git >= 2.15 for --since, found 2.11"
+        );
+        assert!(check(Some(GitVersion::new(2, 19, 0)), &PRECHECK).is_ok());
+        assert!(check(Some(GitVersion::new(3, 0, 0)), &REUSE_CLONE).is_ok());
+        assert!(check(None, &PRECHECK).is_ok());
+    }
+}
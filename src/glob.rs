@@ -0,0 +1,107 @@
+//! Shell-style path patterns, for `--no-headers-for`.
+//!
+//! A pattern is matched against a `/`-separated path relative to the source
+//! root, one component at a time: `*` matches any run of characters within
+//! a component, `?` any one character, and a `**` component any number of
+//! components, none included. A pattern matches a path when it matches the
+//! path itself or one of the directories above it, so `lib` matches every
+//! file under `lib/`.
+
+/// The components of `path`, without empty and `.` ones.
+fn components(path: &str) -> Vec<&str> {
+    path.split('/')
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+/// Whether `pattern` matches `path` or one of the directories above it.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert!(matches("lib", "lib/oz/token/ERC20.sol"));
+/// assert!(matches("**/mocks", "src/test/mocks/Mock.sol"));
+/// assert!(!matches("src/*.sol", "src/vault/Vault.sol"));
+/// ```
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let pattern = components(pattern);
+    let path = components(path);
+    !pattern.is_empty() && (1..=path.len()).any(|n| matches_components(&pattern, &path[..n]))
+}
+
+/// Whether the pattern components match the path components exactly.
+fn matches_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_components(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((name, tail)) => matches_name(first, name) && matches_components(rest, tail),
+            None => false,
+        },
+    }
+}
+
+/// Whether one pattern component matches one path component.
+///
+/// On a mismatch, the last `*` is retried one character further on, which
+/// is enough since `*` cannot cross a component.
+fn matches_name(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            p = star_p + 1;
+            n = star_n + 1;
+            star = Some((star_p, star_n + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies wildcards within a component, `**` across components, and
+    /// that a directory pattern matches the files under it.
+    #[test]
+    fn test_matches() {
+        let cases = [
+            ("lib", "lib/oz/token/ERC20.sol", true),
+            ("lib/", "lib/A.sol", true),
+            ("./lib/oz", "lib/oz/A.sol", true),
+            ("lib", "src/lib/A.sol", false),
+            ("lib", "library/A.sol", false),
+            ("*/mocks", "src/mocks/Mock.sol", true),
+            ("**/mocks", "src/test/mocks/Mock.sol", true),
+            ("**/mocks", "mocks/Mock.sol", true),
+            ("src/*.sol", "src/Vault.sol", true),
+            ("src/*.sol", "src/vault/Vault.sol", false),
+            ("src/**/*.t.sol", "src/a/b/Vault.t.sol", true),
+            ("src/**/*.t.sol", "src/Vault.sol", false),
+            (
+                "dependencies/forge-std-*",
+                "dependencies/forge-std-1.9.1/src/Test.sol",
+                true,
+            ),
+            ("I?????.sol", "IERC20.sol", true),
+            ("I?????.sol", "IERC721.sol", false),
+            ("*a*b", "xaxxbxb", true),
+            ("*a*b", "xaxxbx", false),
+            ("", "lib/A.sol", false),
+        ];
+        for (pattern, path, expected) in cases {
+            assert_eq!(matches(pattern, path), expected, "{} {}", pattern, path);
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! The import graph of a source tree, for `--related`.
+//!
+//! Every `.sol` file under the source directory is a node, whatever the
+//! inclusion rules say, so a file linked to the target only through an
+//! excluded one (a test importing a helper, say) is still found. An edge
+//! runs from a file to each file of the tree its imports resolve to (see
+//! [`resolve`]); an import of a file that is not in the tree, such as an
+//! uninstalled library, has no edge.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::imports;
+
+/// The file `--related` reads remappings from, at the source root.
+pub const REMAPPINGS_FILE: &str = "remappings.txt";
+
+/// The directory tried for a bare import no remapping matches, as Hardhat
+/// resolves packages.
+const PACKAGE_DIR: &str = "node_modules";
+
+/// How a file relates to the `--related` target, as its header notes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Relation {
+    /// The named file itself.
+    Target,
+    /// Imported by the target, directly or not.
+    DependsOn,
+    /// Imports the target, directly or not.
+    Dependent,
+}
+
+impl Relation {
+    /// Every relation, in the order summaries list them.
+    pub const ALL: [Relation; 3] = [Relation::Target, Relation::DependsOn, Relation::Dependent];
+
+    /// The name shown in headers and the summary.
+    pub fn name(self) -> &'static str {
+        match self {
+            Relation::Target => "target",
+            Relation::DependsOn => "depends-on",
+            Relation::Dependent => "dependent",
+        }
+    }
+}
+
+impl fmt::Display for Relation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// One line of a remappings file: imports starting with `prefix` are
+/// looked up under `target` instead.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remapping {
+    /// The directory the remapping is limited to, from a `context:` prefix.
+    pub context: Option<String>,
+    /// The import path prefix replaced.
+    pub prefix: String,
+    /// What replaces it, relative to the source root.
+    pub target: String,
+}
+
+/// Parses remappings in the `remappings.txt` format, one
+/// `[context:]prefix=target` per line.
+///
+/// Blank lines and lines without `=` are ignored.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let remappings = parse_remappings("@oz/=lib/openzeppelin-contracts/contracts/\n");
+/// assert_eq!(remappings[0].target, "lib/openzeppelin-contracts/contracts/");
+/// ```
+pub fn parse_remappings(text: &str) -> Vec<Remapping> {
+    text.lines()
+        .filter_map(|line| {
+            let (prefix, target) = line.trim().split_once('=')?;
+            let (context, prefix) = match prefix.split_once(':') {
+                Some((context, prefix)) if !context.is_empty() => {
+                    (Some(context.to_string()), prefix)
+                }
+                Some((_, prefix)) => (None, prefix),
+                None => (None, prefix),
+            };
+            (!prefix.is_empty()).then(|| Remapping {
+                context,
+                prefix: prefix.to_string(),
+                target: target.trim().to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Joins `/`-separated `path` onto `base`, resolving `.` and `..`.
+///
+/// Returns `None` for a path that climbs above the root.
+fn join(base: &str, path: &str) -> Option<String> {
+    let mut parts: Vec<&str> = base.split('/').filter(|p| !p.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// The paths, relative to the source root, that the import of `import` by
+/// the file at `importer` may name, in the order they are tried.
+///
+/// An import starting with `./` or `../` is relative to the importer's
+/// directory. Any other is remapped by the longest matching prefix of the
+/// remappings that apply to the importer; without one, it is tried as a
+/// path from the source root and then under `node_modules/`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(resolve("src/vault/Vault.sol", "../Token.sol", &[]), ["src/Token.sol"]);
+/// ```
+pub fn resolve(importer: &str, import: &str, remappings: &[Remapping]) -> Vec<String> {
+    if import.starts_with("./") || import.starts_with("../") {
+        let dir = importer.rsplit_once('/').map_or("", |(dir, _)| dir);
+        return join(dir, import).into_iter().collect();
+    }
+    let remapped = remappings
+        .iter()
+        .filter(|r| import.starts_with(&r.prefix))
+        .filter(|r| r.context.as_ref().is_none_or(|c| importer.starts_with(c)))
+        .max_by_key(|r| r.prefix.len());
+    match remapped {
+        Some(r) => join(&r.target, &import[r.prefix.len()..])
+            .into_iter()
+            .collect(),
+        None => [join("", import), join(PACKAGE_DIR, import)]
+            .into_iter()
+            .flatten()
+            .collect(),
+    }
+}
+
+/// The imports between the `.sol` files of one tree.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportGraph {
+    /// Each file's relative path and the files it imports.
+    imports: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl ImportGraph {
+    /// Builds the graph of `files`, given as `(relative path, source)`.
+    pub fn build(files: &[(String, String)], remappings: &[Remapping]) -> Self {
+        let known: BTreeSet<&str> = files.iter().map(|(path, _)| path.as_str()).collect();
+        let imports = files
+            .iter()
+            .map(|(path, code)| {
+                let targets = imports::parse_imports(code)
+                    .iter()
+                    .filter_map(|import| {
+                        resolve(path, &import.path, remappings)
+                            .into_iter()
+                            .find(|candidate| known.contains(candidate.as_str()))
+                    })
+                    .filter(|target| target != path)
+                    .collect();
+                (path.clone(), targets)
+            })
+            .collect();
+        Self { imports }
+    }
+
+    /// Whether `path` is a file of the graph.
+    pub fn contains(&self, path: &str) -> bool {
+        self.imports.contains_key(path)
+    }
+
+    /// Every file reachable from `start` by following `next`, without
+    /// `start` itself.
+    fn reach<'a>(
+        &'a self,
+        start: &'a str,
+        next: impl Fn(&'a str) -> Vec<&'a str>,
+    ) -> BTreeSet<&'a str> {
+        let mut seen = BTreeSet::new();
+        let mut pending = vec![start];
+        while let Some(path) = pending.pop() {
+            for found in next(path) {
+                if found != start && seen.insert(found) {
+                    pending.push(found);
+                }
+            }
+        }
+        seen
+    }
+
+    /// The files related to `target`: itself, its transitive imports, and
+    /// the files that transitively import it.
+    ///
+    /// A file in an import cycle with the target is both; it is reported as
+    /// [`Relation::DependsOn`].
+    pub fn related(&self, target: &str) -> BTreeMap<String, Relation> {
+        let dependents = self.reach(target, |path| {
+            self.imports
+                .iter()
+                .filter(|(_, imported)| imported.contains(path))
+                .map(|(importer, _)| importer.as_str())
+                .collect()
+        });
+        let dependencies = self.reach(target, |path| {
+            self.imports.get(path).map_or_else(Vec::new, |imported| {
+                imported.iter().map(String::as_str).collect()
+            })
+        });
+        let mut related: BTreeMap<String, Relation> = dependents
+            .into_iter()
+            .map(|path| (path.to_string(), Relation::Dependent))
+            .collect();
+        for path in dependencies {
+            related.insert(path.to_string(), Relation::DependsOn);
+        }
+        if self.contains(target) {
+            related.insert(target.to_string(), Relation::Target);
+        }
+        related
+    }
+}
+
+/// Counts `related` by relation, in [`Relation::ALL`] order.
+pub fn count(related: &BTreeMap<String, Relation>) -> [usize; 3] {
+    Relation::ALL.map(|relation| related.values().filter(|r| **r == relation).count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The diamond `Vault -> {Left, Right} -> Base`, imported by
+    /// `test/Vault.t.sol`, with an unrelated `Other.sol` and an import of a
+    /// library that is not in the tree.
+    fn diamond() -> ImportGraph {
+        let files = [
+            (
+                "src/Vault.sol",
+                "import \"./Left.sol\";\nimport {R} from \"./Right.sol\";\n",
+            ),
+            ("src/Left.sol", "import \"./base/Base.sol\";\n"),
+            (
+                "src/Right.sol",
+                "import \"@base/Base.sol\";\nimport \"forge-std/Test.sol\";\n",
+            ),
+            ("src/base/Base.sol", "contract Base {}\n"),
+            ("src/Other.sol", "import \"./base/Base.sol\";\n"),
+            ("test/Vault.t.sol", "import \"src/Vault.sol\";\n"),
+        ];
+        let files: Vec<(String, String)> = files
+            .iter()
+            .map(|(path, code)| (path.to_string(), code.to_string()))
+            .collect();
+        ImportGraph::build(&files, &parse_remappings("@base/=src/base/\n"))
+    }
+
+    /// Verifies relative, remapped, and root-relative imports, and that
+    /// paths cannot climb out of the root.
+    #[test]
+    fn test_resolve() {
+        let remappings = parse_remappings(
+            "@oz/=lib/oz/contracts/\n@oz/token/=lib/token/\nlib/a:dep/=lib/a/dep/\nno equals\n",
+        );
+        assert_eq!(remappings.len(), 3);
+        let cases = [
+            ("src/v/Vault.sol", "../Token.sol", vec!["src/Token.sol"]),
+            ("src/Vault.sol", "./lib/./A.sol", vec!["src/lib/A.sol"]),
+            ("src/Vault.sol", "../../A.sol", vec![]),
+            (
+                "src/Vault.sol",
+                "@oz/Ownable.sol",
+                vec!["lib/oz/contracts/Ownable.sol"],
+            ),
+            (
+                "src/Vault.sol",
+                "@oz/token/ERC20.sol",
+                vec!["lib/token/ERC20.sol"],
+            ),
+            ("lib/a/B.sol", "dep/C.sol", vec!["lib/a/dep/C.sol"]),
+            (
+                "src/Vault.sol",
+                "dep/C.sol",
+                vec!["dep/C.sol", "node_modules/dep/C.sol"],
+            ),
+        ];
+        for (importer, import, expected) in cases {
+            assert_eq!(
+                resolve(importer, import, &remappings),
+                expected,
+                "{}",
+                import
+            );
+        }
+    }
+
+    /// Verifies the diamond: both sides count once, the shared base is a
+    /// dependency, and the unrelated file is left out.
+    #[test]
+    fn test_related_diamond() {
+        let graph = diamond();
+        let related = graph.related("src/Vault.sol");
+        let expected = [
+            ("src/Left.sol", Relation::DependsOn),
+            ("src/Right.sol", Relation::DependsOn),
+            ("src/Vault.sol", Relation::Target),
+            ("src/base/Base.sol", Relation::DependsOn),
+            ("test/Vault.t.sol", Relation::Dependent),
+        ];
+        let expected: BTreeMap<String, Relation> = expected
+            .iter()
+            .map(|(path, relation)| (path.to_string(), *relation))
+            .collect();
+        assert_eq!(related, expected);
+        assert_eq!(count(&related), [1, 3, 1]);
+
+        let related = graph.related("src/base/Base.sol");
+        assert_eq!(count(&related), [1, 0, 5]);
+        assert_eq!(related["src/Other.sol"], Relation::Dependent);
+        assert!(graph.related("src/Missing.sol").is_empty());
+    }
+}
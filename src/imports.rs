@@ -0,0 +1,424 @@
+//! Parsing of Solidity `import` directives.
+//!
+//! Solidity has four import forms, and the two that bind aliases are easy
+//! to miss with a pattern match on `import "..."`:
+//!
+//! | Form          | Example                                     |
+//! |---------------|---------------------------------------------|
+//! | plain         | `import "./A.sol";`                         |
+//! | aliased unit  | `import "./A.sol" as A;`                    |
+//! | aliased unit  | `import * as A from "./A.sol";`             |
+//! | symbol list   | `import {A, B as C} from "./A.sol";`        |
+//!
+//! [`parse_imports`] tokenizes the source, skipping comments and reading
+//! string literals whole, so symbol lists may span lines and the word
+//! `import` inside a string or comment is never taken for a directive. Each
+//! directive becomes an [`ImportStatement`]; anything that does not parse
+//! as one of the forms is left out rather than reported.
+
+/// One imported symbol of a `{...}` list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportedSymbol {
+    /// The name declared in the imported file.
+    pub name: String,
+    /// The local name after `as`, if any.
+    pub alias: Option<String>,
+}
+
+impl ImportedSymbol {
+    /// The name the symbol is known by in the importing file.
+    pub fn local_name(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// A parsed `import` directive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportStatement {
+    /// The import path as written, without quotes.
+    pub path: String,
+    /// The unit alias of `import "p" as X` or `import * as X from "p"`.
+    pub unit_alias: Option<String>,
+    /// The symbols of `import {A, B as C} from "p"`; empty for other forms.
+    pub symbols: Vec<ImportedSymbol>,
+    /// The 1-based line of the `import` keyword.
+    pub line: usize,
+}
+
+impl ImportStatement {
+    /// The names this directive binds in the importing file.
+    ///
+    /// A plain import binds names that can only be known by reading the
+    /// imported file, so it contributes none here.
+    pub fn bound_names(&self) -> Vec<&str> {
+        match &self.unit_alias {
+            Some(alias) => vec![alias.as_str()],
+            None => self
+                .symbols
+                .iter()
+                .map(ImportedSymbol::local_name)
+                .collect(),
+        }
+    }
+}
+
+/// A token of the import grammar.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Str(String),
+    Punct(char),
+}
+
+/// Splits `code` into words, string literals, and punctuation, dropping
+/// comments and whitespace.
+///
+/// A string literal keeps its escapes as written, since import paths do not
+/// use them in practice; an unterminated literal ends at the line break.
+fn tokens(code: &str) -> Vec<(Token, usize)> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => line += 1,
+            _ if c.is_whitespace() => {}
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '"' | '\'' => {
+                let start = i + 1;
+                i += 1;
+                while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+                let end = i.min(chars.len());
+                tokens.push((Token::Str(chars[start..end].iter().collect()), line));
+            }
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                tokens.push((Token::Word(chars[start..i].iter().collect()), line));
+                continue;
+            }
+            _ => tokens.push((Token::Punct(c), line)),
+        }
+        i += 1;
+    }
+
+    tokens
+}
+
+/// Parses every `import` directive in `code`, in source order.
+///
+/// `import` only starts a directive at the beginning of a statement, so a
+/// member or variable named `import` elsewhere is not mistaken for one.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let imports = parse_imports("import {IERC20 as IToken} from \"./IERC20.sol\";");
+/// assert_eq!(imports[0].path, "./IERC20.sol");
+/// assert_eq!(imports[0].bound_names(), vec!["IToken"]);
+/// ```
+pub fn parse_imports(code: &str) -> Vec<ImportStatement> {
+    let tokens = tokens(code);
+    let mut imports = Vec::new();
+
+    for (i, (token, line)) in tokens.iter().enumerate() {
+        if *token != Token::Word("import".to_string()) {
+            continue;
+        }
+        let starts_statement = i == 0
+            || matches!(
+                tokens[i - 1].0,
+                Token::Punct(';') | Token::Punct('}') | Token::Punct('{')
+            );
+        if !starts_statement {
+            continue;
+        }
+        if let Some(mut statement) = parse_directive(&tokens[i + 1..]) {
+            statement.line = *line;
+            imports.push(statement);
+        }
+    }
+
+    imports
+}
+
+/// Parses the tokens after `import` up to the closing `;`.
+fn parse_directive(tokens: &[(Token, usize)]) -> Option<ImportStatement> {
+    let mut rest = tokens.iter().map(|(token, _)| token);
+    let word = |token: Option<&Token>| match token {
+        Some(Token::Word(w)) => Some(w.clone()),
+        _ => None,
+    };
+    let keyword = |token: Option<&Token>, expected: &str| matches!(token, Some(Token::Word(w)) if w == expected);
+
+    let (path, unit_alias, symbols) = match rest.next()? {
+        Token::Str(path) => match rest.next()? {
+            Token::Punct(';') => return Some(statement(path.clone(), None, Vec::new())),
+            Token::Word(w) if w == "as" => (path.clone(), Some(word(rest.next())?), Vec::new()),
+            _ => return None,
+        },
+        Token::Punct('*') => {
+            if !keyword(rest.next(), "as") {
+                return None;
+            }
+            let alias = word(rest.next())?;
+            if !keyword(rest.next(), "from") {
+                return None;
+            }
+            let Token::Str(path) = rest.next()? else {
+                return None;
+            };
+            (path.clone(), Some(alias), Vec::new())
+        }
+        Token::Punct('{') => {
+            let mut symbols = Vec::new();
+            loop {
+                match rest.next()? {
+                    Token::Punct('}') => break,
+                    Token::Punct(',') => continue,
+                    Token::Word(name) => {
+                        let mut lookahead = rest.clone();
+                        let alias = if keyword(lookahead.next(), "as") {
+                            rest.next();
+                            Some(word(rest.next())?)
+                        } else {
+                            None
+                        };
+                        symbols.push(ImportedSymbol {
+                            name: name.clone(),
+                            alias,
+                        });
+                    }
+                    _ => return None,
+                }
+            }
+            if !keyword(rest.next(), "from") {
+                return None;
+            }
+            let Token::Str(path) = rest.next()? else {
+                return None;
+            };
+            (path.clone(), None, symbols)
+        }
+        _ => return None,
+    };
+
+    (rest.next()? == &Token::Punct(';')).then(|| statement(path, unit_alias, symbols))
+}
+
+fn statement(
+    path: String,
+    unit_alias: Option<String>,
+    symbols: Vec<ImportedSymbol>,
+) -> ImportStatement {
+    ImportStatement {
+        path,
+        unit_alias,
+        symbols,
+        line: 0,
+    }
+}
+
+/// Returns the local names bound by more than one import in `imports`,
+/// each with the lines binding it.
+///
+/// solc rejects such a file with "Identifier already declared"; in scraped
+/// output it usually means two copies of a dependency were imported under
+/// one name.
+pub fn conflicting_names(imports: &[ImportStatement]) -> Vec<(String, Vec<usize>)> {
+    let mut bindings: Vec<(String, Vec<usize>)> = Vec::new();
+    for import in imports {
+        for name in import.bound_names() {
+            match bindings.iter_mut().find(|(bound, _)| bound == name) {
+                Some((_, lines)) => lines.push(import.line),
+                None => bindings.push((name.to_string(), vec![import.line])),
+            }
+        }
+    }
+    bindings.retain(|(_, lines)| lines.len() > 1);
+    bindings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, alias: Option<&str>) -> ImportedSymbol {
+        ImportedSymbol {
+            name: name.to_string(),
+            alias: alias.map(str::to_string),
+        }
+    }
+
+    fn only(code: &str) -> ImportStatement {
+        let mut imports = parse_imports(code);
+        assert_eq!(imports.len(), 1, "{}", code);
+        imports.remove(0)
+    }
+
+    /// Verifies the plain form with either quote style.
+    #[test]
+    fn test_plain_import() {
+        let import = only("import \"./A.sol\";");
+        assert_eq!(import.path, "./A.sol");
+        assert!(import.bound_names().is_empty());
+        assert_eq!((import.unit_alias, import.symbols), (None, Vec::new()));
+        assert_eq!(
+            only("import '@oz/token/ERC20.sol' ;").path,
+            "@oz/token/ERC20.sol"
+        );
+    }
+
+    /// Verifies both spellings of the aliased unit form.
+    #[test]
+    fn test_aliased_unit_import() {
+        let import = only("import \"./X.sol\" as X;");
+        assert_eq!(
+            (import.path.as_str(), import.unit_alias.as_deref()),
+            ("./X.sol", Some("X"))
+        );
+        assert_eq!(import.bound_names(), vec!["X"]);
+
+        let star = only("import * as Lib from \"lib/Lib.sol\";");
+        assert_eq!(
+            (star.path.as_str(), star.unit_alias.as_deref()),
+            ("lib/Lib.sol", Some("Lib"))
+        );
+        assert!(star.symbols.is_empty());
+    }
+
+    /// Verifies symbol lists with and without aliases.
+    #[test]
+    fn test_symbol_list_import() {
+        let import = only("import {IERC20, SafeERC20 as Safe} from \"./Token.sol\";");
+        assert_eq!(import.path, "./Token.sol");
+        assert_eq!(
+            import.symbols,
+            vec![symbol("IERC20", None), symbol("SafeERC20", Some("Safe"))]
+        );
+        assert_eq!(import.bound_names(), vec!["IERC20", "Safe"]);
+
+        let aliased = only("import {IERC20 as IToken} from \"./IERC20.sol\";");
+        assert_eq!(aliased.symbols, vec![symbol("IERC20", Some("IToken"))]);
+        assert_eq!(aliased.bound_names(), vec!["IToken"]);
+    }
+
+    /// Verifies multi-line symbol lists, trailing commas, and comments inside.
+    #[test]
+    fn test_multi_line_symbol_list() {
+        let code = "pragma solidity ^0.8.0;\n\nimport {\n    A,\n    // the old name\n    \
+                    B as C, /* kept */\n    D,\n} from\n    \"./All.sol\";\n";
+        let import = only(code);
+        assert_eq!(import.line, 3);
+        assert_eq!(
+            import.symbols,
+            vec![symbol("A", None), symbol("B", Some("C")), symbol("D", None)]
+        );
+    }
+
+    /// Verifies `import` in strings, comments, and non-statement positions.
+    #[test]
+    fn test_import_word_elsewhere() {
+        let code = "// import \"./Commented.sol\";\n\
+                    /* import {A} from \"./Block.sol\"; */\n\
+                    import \"./Real.sol\";\n\
+                    contract C {\n    \
+                    string s = \"import {B} from './InString.sol';\";\n    \
+                    string t = 'import \"./Single.sol\";';\n    \
+                    function f() public { x.import; }\n}\n";
+        let imports = parse_imports(code);
+        assert_eq!(imports.len(), 1);
+        assert_eq!(
+            (imports[0].path.as_str(), imports[0].line),
+            ("./Real.sol", 3)
+        );
+    }
+
+    /// Verifies malformed directives are left out without stopping the scan.
+    #[test]
+    fn test_malformed_imports() {
+        for code in [
+            "import;",
+            "import \"./A.sol\"",
+            "import \"./A.sol\" as;",
+            "import * from \"./A.sol\";",
+            "import {A} \"./A.sol\";",
+            "import {A as} from \"./A.sol\";",
+            "import {A from \"./A.sol\";",
+        ] {
+            assert!(parse_imports(code).is_empty(), "{}", code);
+        }
+        let imports = parse_imports("import {A} \"./Bad.sol\";\nimport \"./Good.sol\";");
+        assert_eq!(imports.len(), 1);
+        assert_eq!(imports[0].path, "./Good.sol");
+    }
+
+    /// Verifies the order and lines of several directives.
+    #[test]
+    fn test_several_imports() {
+        let code = "import \"./A.sol\";\nimport \"./B.sol\" as B;\n\
+                    import * as C from \"./C.sol\";\nimport {D} from \"./D.sol\";";
+        let imports = parse_imports(code);
+        let summary: Vec<(&str, usize)> = imports
+            .iter()
+            .map(|import| (import.path.as_str(), import.line))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("./A.sol", 1),
+                ("./B.sol", 2),
+                ("./C.sol", 3),
+                ("./D.sol", 4)
+            ]
+        );
+    }
+
+    /// Verifies detection of one local name bound by two imports.
+    #[test]
+    fn test_conflicting_names() {
+        let imports = parse_imports(
+            "import {IERC20} from \"lib/a/IERC20.sol\";\n\
+             import {IERC20 as IToken} from \"lib/b/IERC20.sol\";\n\
+             import {Other as IERC20} from \"./Other.sol\";\n\
+             import \"./Plain.sol\";\nimport \"./Plain2.sol\";\n\
+             import * as IToken from \"./T.sol\";",
+        );
+        assert_eq!(
+            conflicting_names(&imports),
+            vec![
+                ("IERC20".to_string(), vec![1, 3]),
+                ("IToken".to_string(), vec![2, 6]),
+            ]
+        );
+        assert!(conflicting_names(&parse_imports("import {A} from \"a\";")).is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+//! Minimal JSON output for machine-readable results.
+//!
+//! solscrape has no dependencies, so the few JSON documents it prints are
+//! built with [`Object`], which escapes strings as RFC 8259 requires and
+//! keeps fields in insertion order. Documents render on a single line, so
+//! a script can read exactly one line from stdout.
+
+/// Quotes and escapes `text` as a JSON string.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(quote("a \"b\"\n"), r#""a \"b\"\n""#);
+/// ```
+pub fn quote(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// A JSON object under construction.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let json = Object::new().string("output", "out/a.sol").number("files", 3).render();
+/// assert_eq!(json, r#"{"output":"out/a.sol","files":3}"#);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Object {
+    /// Quoted keys with their rendered values, in insertion order.
+    fields: Vec<(String, String)>,
+}
+
+impl Object {
+    /// Creates an empty object.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a string field.
+    pub fn string(self, key: &str, value: &str) -> Self {
+        self.raw(key, quote(value))
+    }
+
+    /// Adds a string field, or `null` when `value` is `None`.
+    pub fn optional_string(self, key: &str, value: Option<&str>) -> Self {
+        self.raw(key, value.map_or_else(|| "null".to_string(), quote))
+    }
+
+    /// Adds a number field.
+    pub fn number(self, key: &str, value: usize) -> Self {
+        self.raw(key, value.to_string())
+    }
+
+    /// Adds a number field, or `null` when `value` is `None`.
+    pub fn optional_number(self, key: &str, value: Option<usize>) -> Self {
+        self.raw(
+            key,
+            value.map_or_else(|| "null".to_string(), |n| n.to_string()),
+        )
+    }
+
+    /// Adds a number field with `places` decimal places; `value` must be
+    /// finite.
+    pub fn decimal(self, key: &str, value: f64, places: usize) -> Self {
+        self.raw(key, format!("{:.*}", places, value))
+    }
+
+    /// Adds a boolean field.
+    pub fn boolean(self, key: &str, value: bool) -> Self {
+        self.raw(key, value.to_string())
+    }
+
+    /// Adds an array of strings.
+    pub fn strings<S: AsRef<str>>(self, key: &str, values: &[S]) -> Self {
+        let items: Vec<String> = values.iter().map(|v| quote(v.as_ref())).collect();
+        self.raw(key, format!("[{}]", items.join(",")))
+    }
+
+    /// Adds an array of objects.
+    pub fn objects(self, key: &str, values: &[Object]) -> Self {
+        let items: Vec<String> = values.iter().map(Object::render).collect();
+        self.raw(key, format!("[{}]", items.join(",")))
+    }
+
+    fn raw(mut self, key: &str, value: String) -> Self {
+        self.fields.push((quote(key), value));
+        self
+    }
+
+    /// Renders the object on one line, without a trailing newline.
+    pub fn render(&self) -> String {
+        let fields: Vec<String> = self
+            .fields
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies escaping of quotes, backslashes, and control characters.
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("plain"), "\"plain\"");
+        assert_eq!(quote("a \"b\" \\ c"), r#""a \"b\" \\ c""#);
+        assert_eq!(quote("x\ny\tz\r"), r#""x\ny\tz\r""#);
+        assert_eq!(quote("\u{1}é"), "\"\\u0001é\"");
+    }
+
+    /// Verifies field order and value rendering.
+    #[test]
+    fn test_object_render() {
+        let json = Object::new()
+            .string("output", "out/a.sol")
+            .number("files", 2)
+            .strings("paths", &["src/A.sol", "src/\"B\".sol"])
+            .optional_string("run_dir", None)
+            .boolean("partial", false)
+            .optional_number("line", Some(7))
+            .optional_number("gone", None)
+            .decimal("share", 2.0 / 3.0 * 100.0, 1)
+            .render();
+        assert_eq!(
+            json,
+            r#"{"output":"out/a.sol","files":2,"paths":["src/A.sol","src/\"B\".sol"],"run_dir":null,"partial":false,"line":7,"gone":null,"share":66.7}"#
+        );
+        assert_eq!(
+            Object::new().strings::<&str>("none", &[]).render(),
+            r#"{"none":[]}"#
+        );
+        let nested = [Object::new().number("a", 1), Object::new()];
+        assert_eq!(
+            Object::new().objects("items", &nested).render(),
+            r#"{"items":[{"a":1},{}]}"#
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Per-library grouping of vendored dependencies.
+//!
+//! With `--include-lib`, files of several vendored libraries end up side by
+//! side, sometimes two copies of one library at different versions. Each
+//! top-level directory under `lib/` or `dependencies/` is a library, and the
+//! output gets a [`render_banner`] wherever the library changes, naming it
+//! and, when it can be found, its version. Versions come from the library's
+//! `package.json`, from the commit a git submodule is pinned to, or from a
+//! Soldeer package directory name, and are looked up once per library by
+//! [`Libraries`]. Missing or unreadable metadata just leaves the version out.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::naming::{self, SOLDEER_DIR};
+use crate::symbols::{self, Symbols};
+
+/// The prefix of the banner line naming a library.
+pub const BANNER_PREFIX: &str = "// Library: ";
+
+/// The banner line for project files following a library.
+pub const PROJECT_LABEL: &str = "// Project sources";
+
+/// The prefix of the banner line naming one source of several (`--source`).
+pub const SOURCE_PREFIX: &str = "// Source: ";
+
+/// Returns the library a file belongs to, as `lib/<name>` or
+/// `dependencies/<name>`, or `None` for project files.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(library_dir("lib/forge-std/src/Test.sol"), Some("lib/forge-std"));
+/// assert_eq!(library_dir("src/Vault.sol"), None);
+/// ```
+pub fn library_dir(relative: &str) -> Option<&str> {
+    let mut parts = relative.splitn(3, '/');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(top @ ("lib" | SOLDEER_DIR)), Some(name), Some(_)) if !name.is_empty() => {
+            Some(&relative[..top.len() + 1 + name.len()])
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if `line` belongs to a banner written by [`render_banner`]
+/// or [`render_source_banner`], with either charset's rule.
+pub fn is_banner_line(line: &str) -> bool {
+    symbols::ALL.iter().any(|s| line == s.library_rule)
+        || line == PROJECT_LABEL
+        || line.starts_with(BANNER_PREFIX)
+        || line.starts_with(SOURCE_PREFIX)
+}
+
+/// Renders the banner starting the files of `library`, or of the project
+/// when `library` is `None`, framed by the `symbols` library rule.
+///
+/// A Soldeer package is named without the version in its directory name,
+/// which the banner shows as its version instead.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let banner = render_banner(Some("lib/openzeppelin-contracts"), Some("4.9.3"), &symbols::UNICODE);
+/// // "// ───…\n// Library: lib/openzeppelin-contracts (version 4.9.3)\n// ───…"
+/// ```
+pub fn render_banner(library: Option<&str>, version: Option<&str>, symbols: &Symbols) -> String {
+    let unversioned = library.map(|dir| {
+        match dir
+            .strip_prefix(SOLDEER_DIR)
+            .and_then(|rest| rest.strip_prefix('/'))
+            .and_then(naming::split_package_version)
+        {
+            Some((name, _)) => format!("{}/{}", SOLDEER_DIR, name),
+            None => dir.to_string(),
+        }
+    });
+    let label = match (unversioned.as_deref(), version) {
+        (Some(dir), Some(version)) => format!(
+            "{}{} (version {})",
+            BANNER_PREFIX,
+            naming::display_path(dir),
+            naming::display_path(version)
+        ),
+        (Some(dir), None) => format!("{}{}", BANNER_PREFIX, naming::display_path(dir)),
+        (None, _) => PROJECT_LABEL.to_string(),
+    };
+    let rule = symbols.library_rule;
+    format!("{}\n{}\n{}", rule, label, rule)
+}
+
+/// Renders the banner starting the files of the source named `name`, when a
+/// scrape combines several, framed like a library's.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let banner = render_source_banner("solmate", &symbols::UNICODE);
+/// assert_eq!(banner.lines().nth(1), Some("// Source: solmate"));
+/// ```
+pub fn render_source_banner(name: &str, symbols: &Symbols) -> String {
+    let rule = symbols.library_rule;
+    format!("{}\n{}{}\n{}", rule, SOURCE_PREFIX, name, rule)
+}
+
+/// Caches the version of each library under a source root.
+#[derive(Debug, Default)]
+pub struct Libraries {
+    /// Looked-up versions by library directory, including failed lookups.
+    versions: HashMap<String, Option<String>>,
+}
+
+impl Libraries {
+    /// The version of the library at `dir` under `root`, looked up on first use.
+    ///
+    /// Tries, in order: the version in a Soldeer directory name, the
+    /// `"version"` of `package.json`, and the commit checked out in the
+    /// library's git directory (a submodule's pinned commit).
+    pub fn version(&mut self, root: &Path, dir: &str) -> Option<&str> {
+        self.versions
+            .entry(dir.to_string())
+            .or_insert_with(|| {
+                let package = dir.rsplit('/').next().unwrap_or(dir);
+                let soldeer = dir
+                    .starts_with(SOLDEER_DIR)
+                    .then(|| naming::split_package_version(package))
+                    .flatten()
+                    .map(|(_, version)| version.to_string());
+                let path = root.join(dir);
+                soldeer
+                    .or_else(|| package_version(&path))
+                    .or_else(|| pinned_commit(&path).map(|sha| format!("commit {}", &sha[..7])))
+            })
+            .as_deref()
+    }
+}
+
+/// Reads the first `"version"` string of `dir/package.json`.
+fn package_version(dir: &Path) -> Option<String> {
+    let manifest = fs::read_to_string(dir.join("package.json")).ok()?;
+    let at = manifest.find("\"version\"")? + "\"version\"".len();
+    let rest = manifest[at..].trim_start().strip_prefix(':')?.trim_start();
+    let value = rest.strip_prefix('"')?;
+    let version = value[..value.find('"')?].trim();
+    (!version.is_empty()).then(|| version.to_string())
+}
+
+/// Reads the commit checked out in `dir`'s git directory.
+///
+/// A submodule's `.git` is a file pointing at its git directory; a nested
+/// clone has a `.git` directory. A branch `HEAD` is resolved through its
+/// loose ref.
+fn pinned_commit(dir: &Path) -> Option<String> {
+    let dot_git = dir.join(".git");
+    let git_dir = if dot_git.is_file() {
+        let pointer = fs::read_to_string(&dot_git).ok()?;
+        let target = PathBuf::from(pointer.strip_prefix("gitdir:")?.trim());
+        dir.join(target)
+    } else {
+        dot_git
+    };
+
+    let head = fs::read_to_string(git_dir.join("HEAD")).ok()?;
+    let head = head.trim();
+    let sha = match head.strip_prefix("ref:") {
+        Some(reference) => fs::read_to_string(git_dir.join(reference.trim())).ok()?,
+        None => head.to_string(),
+    };
+    let sha = sha.trim();
+    (sha.len() == 40 && sha.chars().all(|c| c.is_ascii_hexdigit())).then(|| sha.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root =
+            std::env::temp_dir().join(format!("solscrape_library_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (relative, content) in files {
+            let path = root.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    /// Verifies which paths belong to a library.
+    #[test]
+    fn test_library_dir() {
+        assert_eq!(
+            library_dir("lib/forge-std/src/Test.sol"),
+            Some("lib/forge-std")
+        );
+        assert_eq!(
+            library_dir("dependencies/oz-5.0.2/token/ERC20.sol"),
+            Some("dependencies/oz-5.0.2")
+        );
+        assert_eq!(library_dir("lib/oz/lib/nested/A.sol"), Some("lib/oz"));
+        assert_eq!(library_dir("lib/Loose.sol"), None);
+        assert_eq!(library_dir("src/lib/A.sol"), None);
+        assert_eq!(library_dir("src/Vault.sol"), None);
+    }
+
+    /// Verifies each version source, the fallback order, and silent failure.
+    #[test]
+    fn test_library_versions() {
+        let sha = "0123456789abcdef0123456789abcdef01234567";
+        let root = tree(
+            "versions",
+            &[
+                (
+                    "lib/oz/package.json",
+                    "{\n  \"name\": \"@openzeppelin/contracts\",\n  \"version\" : \"4.9.3\"\n}",
+                ),
+                (
+                    "lib/forge-std/.git",
+                    "gitdir: ../../.git/modules/lib/forge-std\n",
+                ),
+                (".git/modules/lib/forge-std/HEAD", &format!("{}\n", sha)),
+                ("lib/nested/.git/HEAD", "ref: refs/heads/main\n"),
+                ("lib/nested/.git/refs/heads/main", sha),
+                ("lib/broken/.git", "not a pointer"),
+                ("lib/bad-json/package.json", "{\"version\": 4}"),
+                ("dependencies/solady-0.0.245/src/A.sol", ""),
+            ],
+        );
+
+        let mut libraries = Libraries::default();
+        assert_eq!(libraries.version(&root, "lib/oz"), Some("4.9.3"));
+        assert_eq!(
+            libraries.version(&root, "lib/forge-std"),
+            Some("commit 0123456")
+        );
+        assert_eq!(
+            libraries.version(&root, "lib/nested"),
+            Some("commit 0123456")
+        );
+        assert_eq!(libraries.version(&root, "lib/broken"), None);
+        assert_eq!(libraries.version(&root, "lib/bad-json"), None);
+        assert_eq!(libraries.version(&root, "lib/missing"), None);
+        assert_eq!(
+            libraries.version(&root, "dependencies/solady-0.0.245"),
+            Some("0.0.245")
+        );
+
+        // Answers are cached, so later changes on disk are not seen
+        fs::remove_file(root.join("lib/oz/package.json")).unwrap();
+        assert_eq!(libraries.version(&root, "lib/oz"), Some("4.9.3"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies banner rendering and recognition.
+    #[test]
+    fn test_render_banner() {
+        let rule = symbols::UNICODE.library_rule;
+        let banner = render_banner(Some("lib/oz"), Some("4.9.3"), &symbols::UNICODE);
+        let lines: Vec<&str> = banner.lines().collect();
+        assert_eq!(lines, [rule, "// Library: lib/oz (version 4.9.3)", rule]);
+        assert!(lines.iter().all(|l| is_banner_line(l)));
+        assert_eq!(
+            render_banner(None, None, &symbols::UNICODE),
+            format!("{}\n{}\n{}", rule, PROJECT_LABEL, rule)
+        );
+        let ascii = render_banner(None, None, &symbols::ASCII);
+        assert!(ascii.starts_with("// ----"));
+        assert!(ascii.lines().all(is_banner_line));
+        assert!(!is_banner_line("// File: lib/oz/A.sol"));
+        let source = render_source_banner("solmate", &symbols::ASCII);
+        assert_eq!(source.lines().nth(1), Some("// Source: solmate"));
+        assert!(source.lines().all(is_banner_line));
+        assert_eq!(
+            render_banner(
+                Some("dependencies/forge-std-1.9.1"),
+                Some("1.9.1"),
+                &symbols::UNICODE
+            )
+            .lines()
+            .nth(1),
+            Some("// Library: dependencies/forge-std (version 1.9.1)")
+        );
+    }
+}
@@ -0,0 +1,287 @@
+//! Logging abstraction routing progress and diagnostics to one or more sinks.
+//!
+//! A [`Logger`] owns a list of [`Sink`]s and forwards every message to each of
+//! them; sinks decide individually what they display. The console sink keeps
+//! the familiar terminal output (and honors `--quiet`), while the file sink
+//! records everything with timestamps for post-mortem debugging. Structured
+//! [`ProgressEvent`]s travel alongside messages to registered observers.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::clock::DateTime;
+use crate::progress::ProgressEvent;
+
+/// The severity or kind of a log message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Regular progress and summary output.
+    Info,
+    /// A recoverable problem the user should know about.
+    Warn,
+    /// Diagnostic detail (timings, full paths, resolved options) that is too
+    /// noisy for the terminal but valuable in a log file.
+    Detail,
+    /// A report about the run that always goes to stderr, never mixed into
+    /// stdout.
+    Report,
+}
+
+impl Level {
+    /// The fixed-width label used in log file records.
+    fn label(self) -> &'static str {
+        match self {
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+            Level::Detail => "DETAIL",
+            Level::Report => "REPORT",
+        }
+    }
+}
+
+/// A destination for log messages.
+pub trait Sink {
+    /// Records a single message at the given level.
+    fn log(&self, level: Level, message: &str);
+}
+
+/// Writes progress to stdout and warnings to stderr, as solscrape always has.
+///
+/// In quiet mode nothing is written; [`Level::Detail`] messages are only
+/// shown on stderr in verbose mode. When stdout is reserved for a result
+/// (`--output-mode`), progress goes to stderr as well.
+pub struct ConsoleSink {
+    quiet: bool,
+    verbose: bool,
+    stderr_only: bool,
+}
+
+impl ConsoleSink {
+    /// Creates a console sink, suppressing all output when `quiet` is set.
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            quiet,
+            verbose: false,
+            stderr_only: false,
+        }
+    }
+
+    /// Also shows [`Level::Detail`] messages when `verbose` is set.
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Writes [`Level::Info`] messages to stderr too when `stderr_only` is set.
+    pub fn stderr_only(mut self, stderr_only: bool) -> Self {
+        self.stderr_only = stderr_only;
+        self
+    }
+}
+
+impl Sink for ConsoleSink {
+    fn log(&self, level: Level, message: &str) {
+        if self.quiet {
+            return;
+        }
+        match level {
+            Level::Info if self.stderr_only => eprintln!("{}", message),
+            Level::Info => println!("{}", message),
+            Level::Warn => eprintln!("Warning: {}", message),
+            Level::Detail if self.verbose => eprintln!("{}", message),
+            Level::Detail => {}
+            Level::Report => eprintln!("{}", message),
+        }
+    }
+}
+
+/// Appends timestamped records of every message to a log file.
+///
+/// Messages are recorded regardless of `--quiet`. Multi-line messages are
+/// split so that every line in the file carries its own timestamp.
+pub struct FileSink {
+    file: Mutex<File>,
+}
+
+impl FileSink {
+    /// Opens `path` for appending, creating it and any parent directories.
+    ///
+    /// # Errors
+    ///
+    /// Returns a descriptive message if the directories cannot be created or
+    /// the file cannot be opened for writing.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Cannot create log directory {}: {}", parent.display(), e))?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Cannot write log file {}: {}", path.display(), e))?;
+
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn log(&self, level: Level, message: &str) {
+        let timestamp = DateTime::now().rfc3339();
+        let Ok(mut file) = self.file.lock() else {
+            return;
+        };
+        for line in message.lines().filter(|l| !l.trim().is_empty()) {
+            let _ = writeln!(file, "[{}] {:<6} {}", timestamp, level.label(), line);
+        }
+    }
+}
+
+/// Fans messages out to every registered [`Sink`], and progress events to
+/// every registered observer.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let log = Logger::new()
+///     .with_sink(ConsoleSink::new(false))
+///     .with_progress(|event| eprintln!("{:?}", event));
+/// log.info("Cloning repository...");
+/// log.warn("Could not read src/Broken.sol");
+/// ```
+#[derive(Default)]
+pub struct Logger {
+    sinks: Vec<Box<dyn Sink>>,
+    observers: Vec<Observer>,
+}
+
+/// A registered progress observer.
+type Observer = Box<dyn Fn(&ProgressEvent)>;
+
+impl Logger {
+    /// Creates a logger with no sinks; messages are discarded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a sink, returning the logger for chaining.
+    pub fn with_sink(mut self, sink: impl Sink + 'static) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Adds a progress observer, returning the logger for chaining.
+    pub fn with_progress(mut self, observer: impl Fn(&ProgressEvent) + 'static) -> Self {
+        self.observers.push(Box::new(observer));
+        self
+    }
+
+    /// Delivers `event` to every observer and records it as detail.
+    pub fn progress(&self, event: ProgressEvent) {
+        for observer in &self.observers {
+            observer(&event);
+        }
+        self.detail(&format!("Progress: {:?}", event));
+    }
+
+    /// Records a message at `level` in every sink.
+    pub fn log(&self, level: Level, message: &str) {
+        for sink in &self.sinks {
+            sink.log(level, message);
+        }
+    }
+
+    /// Records regular progress output.
+    pub fn info(&self, message: &str) {
+        self.log(Level::Info, message);
+    }
+
+    /// Records a recoverable problem.
+    pub fn warn(&self, message: &str) {
+        self.log(Level::Warn, message);
+    }
+
+    /// Records diagnostic detail that only verbose sinks keep.
+    pub fn detail(&self, message: &str) {
+        self.log(Level::Detail, message);
+    }
+
+    /// Records a line of a report that belongs on stderr.
+    pub fn report(&self, message: &str) {
+        self.log(Level::Report, message);
+    }
+
+    /// Runs `f` as a named phase, recording its duration as detail.
+    pub fn phase<T>(&self, name: &str, f: impl FnOnce() -> T) -> T {
+        self.detail(&format!("Phase started: {}", name));
+        let start = Instant::now();
+        let value = f();
+        self.detail(&format!(
+            "Phase finished: {} ({:.3}s)",
+            name,
+            start.elapsed().as_secs_f64()
+        ));
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A sink recording messages in memory for assertions.
+    struct RecordingSink(Arc<Mutex<Vec<(Level, String)>>>);
+
+    impl Sink for RecordingSink {
+        fn log(&self, level: Level, message: &str) {
+            self.0.lock().unwrap().push((level, message.to_string()));
+        }
+    }
+
+    /// Verifies that messages reach every sink and phases record their timings.
+    #[test]
+    fn test_logger_fans_out_to_sinks() {
+        let first = Arc::new(Mutex::new(Vec::new()));
+        let second = Arc::new(Mutex::new(Vec::new()));
+        let log = Logger::new()
+            .with_sink(RecordingSink(first.clone()))
+            .with_sink(RecordingSink(second.clone()));
+
+        log.info("hello");
+        let value = log.phase("clone", || 42);
+
+        assert_eq!(value, 42);
+        for records in [first, second] {
+            let records = records.lock().unwrap();
+            assert_eq!(records[0], (Level::Info, "hello".to_string()));
+            assert!(records[2].1.starts_with("Phase finished: clone"));
+        }
+    }
+
+    /// Verifies the file sink creates parent directories and appends across opens.
+    #[test]
+    fn test_file_sink_appends() {
+        let dir = std::env::temp_dir().join(format!("solscrape_logtest_{}", std::process::id()));
+        let path = dir.join("nested").join("run.log");
+
+        FileSink::open(&path).unwrap().log(Level::Info, "first");
+        FileSink::open(&path)
+            .unwrap()
+            .log(Level::Warn, "second\nthird");
+
+        let content = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("INFO   first"));
+        assert!(lines[2].ends_with("WARN   third"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -33,19 +33,84 @@
 
 #![forbid(unsafe_code)]
 
-use std::collections::HashSet;
+mod batch;
+mod checksum;
+mod clock;
+mod config;
+mod context;
+mod digest;
+mod error_report;
+mod exclusion;
+mod exec;
+mod forge_host;
+mod gate;
+mod git_version;
+mod glob;
+mod graph;
+mod imports;
+mod json;
+mod library;
+mod logger;
+mod metrics;
+mod naming;
+mod output;
+mod parts;
+mod pragma;
+mod precheck;
+mod progress;
+mod project;
+mod revert;
+mod rundir;
+mod skip;
+mod space;
+mod split;
+mod structure;
+mod submodules;
+mod symbols;
+mod tarball;
+mod todo;
+mod vcs;
+mod wrap;
+
+use std::borrow::Cow;
 use std::env;
-use std::fs::{self, File};
-use std::io::{self, Write};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::ops::ControlFlow;
 use std::path::{Path, PathBuf};
-use std::process::{Command, ExitCode};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use exclusion::{Decision, ExclusionPolicy};
+use exec::Exec;
+use git_version::GitVersion;
+use logger::{ConsoleSink, FileSink, Logger};
+use parts::PartSpan;
+use progress::ProgressEvent;
+use skip::{Skip, SkipReason};
+use symbols::{Charset, Symbols};
 
 // ============================================================================
 // Configuration
 // ============================================================================
 
 /// The current version of solscrape, following semantic versioning.
-const VERSION: &str = "1.0.0";
+///
+/// Derived from `Cargo.toml` at compile time so the two can never diverge.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The marker comment appended to pragmas added by `--inject-pragma`.
+const INJECTED_PRAGMA_MARKER: &str = "// injected by solscrape";
+
+/// The prefix of the header line naming a file's relative path.
+const HEADER_FILE_PREFIX: &str = "// File: ";
+
+/// The prefix of the trailer ending output that `--deadline` cut short.
+const TRUNCATED_PREFIX: &str = "// TRUNCATED: ";
+
+/// Minimum inner width (in chars) of the boxed banner.
+const BANNER_MIN_WIDTH: usize = 63;
 
 // ============================================================================
 // CLI Argument Parsing
@@ -68,12 +133,14 @@ const VERSION: &str = "1.0.0";
 /// ```rust,ignore
 /// let args = parse_args()?;
 /// if args.show_help {
-///     print_help();
+///     print_help(args.help_page, args.charset.symbols());
 ///     return Ok(());
 /// }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 struct Args {
+    /// A subcommand other than scraping, selected by the first positional argument.
+    subcommand: Option<Subcommand>,
     /// Git repository URL or local directory path to scrape.
     source: String,
     /// Output directory for the consolidated Solidity file.
@@ -82,36 +149,440 @@ struct Args {
     output_name: Option<String>,
     /// When `true`, treat `source` as a local filesystem path instead of a git URL.
     is_local: bool,
+    /// Never switch to `--local` because the source exists on disk.
+    no_autodetect: bool,
     /// Include `lib/` directory contents in output.
     include_lib: bool,
+    /// Fill a clone's submodules from the cache only, without fetching them
+    /// (`--offline`).
+    offline: bool,
+    /// Download a GitHub source as a tarball even when git is installed
+    /// (`--no-git`; see [`tarball`]).
+    no_git: bool,
+    /// The cache root submodules are filled from (`--foundry-cache`).
+    foundry_cache: Option<String>,
     /// Include `test/` and `tests/` directory contents in output.
     include_test: bool,
     /// Include `script/` and `scripts/` directory contents in output.
     include_script: bool,
+    /// Files to scrape even inside excluded directories, relative to the
+    /// source root (`--force-include`, repeatable).
+    force_include: Vec<String>,
     /// Omit file separator headers from the consolidated output.
     no_headers: bool,
+    /// Patterns of files given a one-line header instead of the framed one
+    /// (`--no-headers-for`; see [`glob::matches`]).
+    no_headers_for: Vec<String>,
+    /// Print per-file cleaning statistics after the summary.
+    stats: bool,
+    /// How many directory levels the `--stats` rollup lists
+    /// (`--stats-depth`); [`DEFAULT_STATS_DEPTH`] when `None`.
+    stats_depth: Option<usize>,
+    /// Write outputs into a fresh timestamped directory under `destination`.
+    run_dir: bool,
+    /// Write files in discovery order as they are found instead of sorting first.
+    stream: bool,
+    /// Keep only the first path of each group of files with the same
+    /// cleaned code (`--dedupe-content`).
+    dedupe_content: bool,
+    /// Leave out interface files whose interfaces included contracts
+    /// implement (`--prune-implemented-interfaces`).
+    prune_implemented_interfaces: bool,
+    /// Write one output per top-level source directory (`--split-by-dir`).
+    split_by_dir: bool,
+    /// Normalize whitespace and report changes versus the previous output.
+    vcs_friendly: bool,
+    /// Add the include/exclude decision for every `.sol` file to the JSON result.
+    explain: bool,
+    /// When `--deadline` runs out, counted from argument parsing.
+    deadline: Option<Instant>,
+    /// Print the relative paths that would be included instead of scraping.
+    list_files: bool,
+    /// Print one line per included file instead of scraping (`--digest`;
+    /// see [`digest`]).
+    digest: bool,
+    /// The stdout contract selected with `--output-mode`.
+    output_mode: Option<OutputMode>,
+    /// NUL-terminate `--list-files` entries instead of using newlines.
+    null_terminated: bool,
+    /// Treat recoverable problems (such as case-only path collisions) as errors.
+    strict: bool,
+    /// The conditions checked against the finished scrape (`--gate`).
+    gates: Vec<gate::Gate>,
+    /// Refuse to spawn any program the other flags do not require.
+    no_subprocess: bool,
+    /// Look at the remote tree before cloning; `None` means only for GitHub
+    /// HTTPS URLs when not quiet.
+    precheck: Option<bool>,
+    /// Answer yes to the pre-check's confirmation prompt.
+    assume_yes: bool,
+    /// Version constraint for `pragma solidity` lines added to files lacking one.
+    inject_pragma: Option<String>,
+    /// Include only files whose `pragma solidity` constraint intersects this one.
+    filter_pragma: Option<pragma::Constraint>,
+    /// Exclude files whose pragma only allows compilers older than this version.
+    min_solidity: Option<String>,
+    /// What `--filter-pragma` and `--min-solidity` do with files that have
+    /// no `pragma solidity`.
+    pragma_unknown: Option<PragmaUnknown>,
+    /// Old and new refs for `--compare <old>..<new>`.
+    compare: Option<(String, String)>,
+    /// Only scrape files changed since this git ref (`--since`).
+    since: Option<String>,
+    /// Only scrape this file and the files it imports or that import it,
+    /// relative to the source root (`--related`).
+    related: Option<String>,
+    /// Refresh and scrape this existing clone of the source URL instead of
+    /// cloning it (`--reuse-clone`).
+    reuse_clone: Option<String>,
+    /// Skip checking that the `--reuse-clone` origin matches the source URL.
+    trust_clone: bool,
+    /// Let `--reuse-clone` use a working tree with uncommitted changes.
+    allow_dirty: bool,
+    /// The branch or tag named by a web URL source, cloned instead of the
+    /// default branch (see [`resolve_web_url`]).
+    url_ref: Option<String>,
+    /// The commit, tag, or branch checked out instead of the default branch
+    /// (`--rev`); unlike `--branch` it may be any commit.
+    rev: Option<String>,
+    /// The access token HTTPS clones of GitHub and GitLab use (`--token`),
+    /// over the environment's (see [`git_token`]).
+    token: Option<forge_host::Token>,
+    /// How many times a clone that failed for a transient reason is retried
+    /// (`--retries`); [`DEFAULT_RETRIES`] when not given.
+    retries: Option<usize>,
+    /// How long one clone attempt may take before git is killed
+    /// (`--timeout`); no limit when not given.
+    clone_timeout: Option<Duration>,
+    /// The directory named by a web URL source, scraped instead of the root.
+    url_subdir: Option<String>,
+    /// The only directory of the source files are discovered in
+    /// (`--subdir`); paths stay relative to the source root.
+    subdir: Option<String>,
+    /// Append the README and project configuration files after the Solidity code.
+    with_context: bool,
+    /// How many bytes of the README `--with-context` keeps.
+    context_readme_limit: Option<usize>,
+    /// Preserve a file's leading copyright/license comment block.
+    keep_license_headers: bool,
+    /// Only scrape files declaring one of these kinds (`--kinds`).
+    kinds: Option<Vec<structure::Kind>>,
+    /// Report TODO/FIXME/XXX/HACK markers left in the output (`--flag-todos`).
+    flag_todos: bool,
+    /// Write a `.sha256` sidecar next to the output (`--checksum-file`).
+    checksum_file: bool,
+    /// Write a `.parts` sidecar next to the output, for `extract`
+    /// (`--parts-file`).
+    parts_file: bool,
+    /// Write the custom errors and revert reasons found next to the output,
+    /// in this format (`--errors-report`).
+    errors_report: Option<error_report::Format>,
+    /// Append the run's metrics to this file (`--metrics-file`).
+    metrics_file: Option<String>,
+    /// Print the run's metrics on stdout at the end (`--metrics-stdout`).
+    metrics_stdout: bool,
+    /// The decorative characters of the messages printed, ASCII with
+    /// `--ascii` or a non-UTF-8 locale (see [`Charset::detect`]).
+    charset: Charset,
+    /// The separators written into the output, ASCII only with `--ascii`,
+    /// so the output is the same under any locale.
+    output_charset: Charset,
+    /// Copy files verbatim instead of removing comments and blank lines.
+    no_clean: bool,
+    /// Re-wrap extremely long lines at statement boundaries instead of skipping the file.
+    rewrap_long_lines: bool,
+    /// Skip files whose braces nest deeper than this (`--max-brace-depth`);
+    /// [`structure::MAX_BRACE_DEPTH`] when not given.
+    max_brace_depth: Option<usize>,
+    /// Replace `require`/`revert` reason strings with a short placeholder.
+    strip_revert_strings: bool,
+    /// Re-wrap lines wider than this many chars (`--max-line-width`; see
+    /// [`wrap`]).
+    max_line_width: Option<usize>,
     /// Suppress progress output; only print the final output path.
     quiet: bool,
+    /// Also print detailed diagnostics (such as raw git output) to stderr.
+    verbose: bool,
+    /// Append a timestamped, detailed record of the run to this file.
+    log_file: Option<String>,
+    /// Directory to clone into instead of `SOLSCRAPE_TMPDIR` or the system default.
+    temp_dir: Option<String>,
+    /// Free bytes required in the temp directory before cloning.
+    min_temp_space: Option<u64>,
+    /// The largest repository size, as its host reports it, cloned without
+    /// asking (`--max-clone-size`).
+    max_clone_size: Option<u64>,
+    /// For `cleanup-temp`: only remove directories at least this old.
+    older_than: Option<Duration>,
+    /// For `cleanup-temp`: remove leftovers of any age.
+    clean_all: bool,
+    /// For `split`: the scraped tree to copy modification times from.
+    mtime_from: Option<String>,
+    /// For `check`: the committed consolidated file to compare with.
+    against: Option<String>,
+    /// Scrape every source listed in this file (`--from-file`).
+    from_file: Option<String>,
+    /// The sources given with `--source`, when there are several; a single
+    /// one is moved to `source`. See [`scrape_sources`].
+    sources: Vec<String>,
+    /// With several `--source`: write one output per source
+    /// (`--split-by-source`).
+    split_by_source: bool,
+    /// With `--from-file`: skip the sources an interrupted run finished.
+    resume: bool,
+    /// With `--from-file`: the command-line options, parsed again under
+    /// each line's own (see [`batch_line_args`]).
+    batch_options: Vec<String>,
+    /// Whether these are the options of one `--from-file` line, whose
+    /// `--quiet` line [`run_batch`] prints in place of the output paths.
+    batch_line: bool,
     /// Display help message and exit.
     show_help: bool,
+    /// The page `--help` prints, from the topic after it.
+    help_page: HelpPage,
     /// Display version information and exit.
     show_version: bool,
+    /// Include build information with the version (`--version-full`, or with `--verbose`).
+    version_full: bool,
 }
 
 impl Default for Args {
     fn default() -> Self {
         Self {
+            subcommand: None,
             source: String::new(),
             destination: ".".to_string(),
             output_name: None,
             is_local: false,
+            no_autodetect: false,
             include_lib: false,
+            offline: false,
+            no_git: false,
+            foundry_cache: None,
             include_test: false,
             include_script: false,
+            force_include: Vec::new(),
             no_headers: false,
+            no_headers_for: Vec::new(),
+            stats: false,
+            stats_depth: None,
+            run_dir: false,
+            split_by_dir: false,
+            stream: false,
+            dedupe_content: false,
+            prune_implemented_interfaces: false,
+            vcs_friendly: false,
+            explain: false,
+            deadline: None,
+            list_files: false,
+            digest: false,
+            output_mode: None,
+            null_terminated: false,
+            strict: false,
+            gates: Vec::new(),
+            no_subprocess: false,
+            precheck: None,
+            assume_yes: false,
+            inject_pragma: None,
+            filter_pragma: None,
+            min_solidity: None,
+            pragma_unknown: None,
+            compare: None,
+            since: None,
+            related: None,
+            reuse_clone: None,
+            trust_clone: false,
+            allow_dirty: false,
+            url_ref: None,
+            rev: None,
+            token: None,
+            retries: None,
+            clone_timeout: None,
+            url_subdir: None,
+            subdir: None,
+            with_context: false,
+            context_readme_limit: None,
+            keep_license_headers: false,
+            kinds: None,
+            flag_todos: false,
+            checksum_file: false,
+            parts_file: false,
+            errors_report: None,
+            metrics_file: None,
+            metrics_stdout: false,
+            charset: Charset::Unicode,
+            output_charset: Charset::Unicode,
+            no_clean: false,
+            rewrap_long_lines: false,
+            max_brace_depth: None,
+            max_line_width: None,
+            strip_revert_strings: false,
             quiet: false,
+            verbose: false,
+            log_file: None,
+            temp_dir: None,
+            min_temp_space: None,
+            max_clone_size: None,
+            older_than: None,
+            clean_all: false,
+            mtime_from: None,
+            against: None,
+            from_file: None,
+            sources: Vec::new(),
+            split_by_source: false,
+            resume: false,
+            batch_options: Vec::new(),
+            batch_line: false,
             show_help: false,
+            help_page: HelpPage::Overview,
             show_version: false,
+            version_full: false,
+        }
+    }
+}
+
+/// An operation other than scraping, selected by the first positional argument.
+#[derive(Debug, Clone, PartialEq)]
+enum Subcommand {
+    /// `split <scraped.sol> <out-dir>`: recreate a source tree from a consolidated file.
+    Split {
+        /// The consolidated file to split.
+        input: String,
+        /// The directory under which the tree is recreated.
+        out_dir: String,
+        /// The scraped tree whose modification times the files take
+        /// (`--mtime-from`).
+        mtime_from: Option<String>,
+    },
+    /// `extract <scraped.sol> <path>`: print one file's section of a consolidated file.
+    Extract {
+        /// The consolidated file to read.
+        input: String,
+        /// The relative path of the file whose section is printed.
+        path: String,
+    },
+    /// `verify <scraped.sol>`: check a consolidated file against its checksum sidecar.
+    Verify {
+        /// The consolidated file to check.
+        input: String,
+    },
+    /// `check <source> --against <scraped.sol>`: fail when a committed
+    /// consolidated file is not what scraping the source produces now. The
+    /// source is in [`Args::source`].
+    Check {
+        /// The committed consolidated file.
+        against: String,
+    },
+    /// `cleanup-temp`: remove temporary clone directories left by killed runs.
+    CleanupTemp {
+        /// Only directories at least this old are removed; zero for `--all`.
+        min_age: Duration,
+    },
+    /// `config path`: print the configuration files consulted and the
+    /// value each setting came from.
+    ConfigPath,
+}
+
+/// Minimum age of leftovers removed by `cleanup-temp` without `--older-than`.
+const DEFAULT_CLEANUP_AGE: Duration = Duration::from_secs(3_600);
+
+/// How `--filter-pragma` and `--min-solidity` treat files without a
+/// `pragma solidity` directive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PragmaUnknown {
+    /// Keep them (the default): nothing says they are incompatible.
+    Include,
+    /// Leave them out, reported as skipped.
+    Exclude,
+}
+
+/// What `--output-mode` guarantees to print on stdout.
+///
+/// In every mode stdout carries nothing else; progress, the summary, and
+/// warnings go to stderr.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum OutputMode {
+    /// Exactly one line: the output file path.
+    Path,
+    /// The consolidated content, byte for byte as written to the output file.
+    Content,
+    /// Exactly one line holding a JSON object describing the result.
+    Json,
+}
+
+impl OutputMode {
+    /// Parses an `--output-mode` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Invalid --output-mode: {name} ..."` for anything but `path`,
+    /// `content`, or `json`.
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "path" => Ok(OutputMode::Path),
+            "content" => Ok(OutputMode::Content),
+            "json" => Ok(OutputMode::Json),
+            _ => Err(format!(
+                "Invalid --output-mode: {} (expected path, content, or json)",
+                name
+            )),
+        }
+    }
+}
+
+/// A named bundle of options selected with `--profile`.
+///
+/// Profiles are applied before any explicit flag, whatever their position on
+/// the command line, so flags add to or override what a profile sets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Profile {
+    /// For human review: file headers and license/SPDX blocks are kept.
+    Audit,
+    /// For LLM context: comments, license blocks, and revert reasons are
+    /// stripped, and minified files are re-wrapped rather than skipped.
+    Llm,
+    /// For source verification: files are copied verbatim, license blocks,
+    /// revert reasons and all, and no pragma is injected.
+    Verify,
+}
+
+impl Profile {
+    /// Parses a `--profile` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Unknown profile: {name} ..."` for anything but `audit`,
+    /// `llm`, or `verify`.
+    fn parse(name: &str) -> Result<Self, String> {
+        match name {
+            "audit" => Ok(Profile::Audit),
+            "llm" => Ok(Profile::Llm),
+            "verify" => Ok(Profile::Verify),
+            _ => Err(format!(
+                "Unknown profile: {} (expected audit, llm, or verify)",
+                name
+            )),
+        }
+    }
+
+    /// Sets the options this profile bundles on `args`.
+    fn apply(self, args: &mut Args) {
+        match self {
+            Profile::Audit => {
+                args.no_headers = false;
+                args.keep_license_headers = true;
+            }
+            Profile::Llm => {
+                args.keep_license_headers = false;
+                args.rewrap_long_lines = true;
+                args.strip_revert_strings = true;
+            }
+            Profile::Verify => {
+                args.no_clean = true;
+                args.keep_license_headers = false;
+                args.strip_revert_strings = false;
+                args.inject_pragma = None;
+            }
         }
     }
 }
@@ -127,12 +598,52 @@ impl Default for Args {
 ///
 /// # Errors
 ///
+/// Flags that conflict with other flags are not rejected here, but checked
+/// afterwards against [`FLAG_COMBINATIONS`]; only `--source` with
+/// `--from-file` is, as it decides how the arguments are read.
+///
 /// | Error | Condition |
 /// |-------|-----------|
 /// | `"--output requires a value"` | `-o`/`--output` flag provided without argument |
+/// | `"--log-file requires a value"` | `--log-file` flag provided without argument |
+/// | `"--temp-dir requires a value"` | `--temp-dir` flag provided without argument |
+/// | `"Invalid --inject-pragma version: ..."` | Empty version, or one containing `;` or a newline |
+/// | `"Invalid --force-include path: ..."` | Empty, absolute, or containing `..` |
+/// | `"Invalid size: {value} ..."` | `--min-temp-space` or `--max-clone-size` value is not a size |
 /// | `"Unknown option: {arg}"` | Unrecognized flag starting with `-` |
 /// | `"Missing required argument: <source>"` | No source path/URL provided |
 /// | `"Too many positional arguments"` | More than two positional arguments |
+/// | `"With --source, the only argument is the destination"` | `--source` and more than one positional argument |
+/// | `"--source cannot be used with ..."` | `--source` with a subcommand or `--from-file` |
+/// | `"--split-by-source requires more than one --source"` | `--split-by-source` without several sources |
+/// | `"Usage: solscrape split <scraped.sol> <out-dir>"` | `split` without exactly two arguments |
+/// | `"--mtime-from can only be used with split"` | `--mtime-from` without `split` |
+/// | `"Usage: solscrape verify <scraped.sol>"` | `verify` without exactly one argument |
+/// | `"Usage: solscrape check ..."` | `check` without one source and `--against` |
+/// | `"Usage: solscrape config path"` | `config` without `path` after it |
+/// | `"{path}:{line}: ..."`, `"${var}: ..."` | A configuration file or variable is malformed; see [`config::load`] |
+/// | `"check cannot be used with ..."` | `check` with `--compare`, `--since`, `--related`, `--split-by-dir`, or `--list-files` |
+/// | `"--against can only be used with check"` | `--against` without `check` |
+/// | `"Usage: solscrape cleanup-temp ..."` | `cleanup-temp` with arguments, or both `--older-than` and `--all` |
+/// | `"Invalid duration: {value} ..."` | `--older-than` value is not a duration |
+/// | `"Invalid --max-brace-depth: ..."` | `--max-brace-depth` is not a positive whole number |
+/// | `"--output cannot be used ..."` | `--output` combined with a `.sol` destination |
+/// | `"--output ... is a path, so it cannot be used with the destination ..."` | An `--output` path and a destination argument (see [`resolve_output`]) |
+/// | `"--output ... names a directory ..."` | An `--output` path with no file name |
+/// | `"With --from-file, --output is a name ..."` | `--from-file` with an `--output` path or `.sol` name |
+/// | `"-0 can only be used with --list-files"` | `-0` without `--list-files` |
+/// | `"Invalid version constraint: ..."` | Malformed `--filter-pragma` constraint |
+/// | `"Invalid version: ..."` | `--min-solidity` is not a plain version |
+/// | `"--pragma-unknown can only be used ..."` | `--pragma-unknown` without `--filter-pragma` or `--min-solidity` |
+/// | `"Invalid --compare range: ..."` | `--compare` value is not `<old>..<new>` |
+/// | `"Invalid --since ref: ..."` | Empty `--since` ref, or one starting with `-` or containing `..` |
+/// | `"Invalid --related path: ..."` | Empty, absolute, or containing `..` |
+/// | `"Unknown profile: {name} ..."` | `--profile` is not `audit`, `llm`, or `verify` |
+/// | `"Invalid --gate: ..."` | The condition is not one of [`gate::Gate`] (see [`gate::Gate::parse`]) |
+/// | `"Invalid --errors-report: ..."` | The format is not `text` or `json` |
+/// | `"--explain can only be used with --output-mode json"` | `--explain` without `--output-mode json` |
+/// | `"Invalid --output-mode: ..."` | `--output-mode` is not `path`, `content`, or `json` |
+/// | `"--output-mode cannot be used with {subcommand}"` | `--output-mode` with a subcommand |
 ///
 /// # Examples
 ///
@@ -141,221 +652,2491 @@ impl Default for Args {
 /// println!("Scraping from: {}", args.source);
 /// ```
 fn parse_args() -> Result<Args, String> {
-    let args: Vec<String> = env::args().collect();
-    let mut parsed = Args::default();
+    parse_args_with(&load_settings()?, env::args().collect())
+}
+
+/// Reads the configuration files and environment variables for this run;
+/// see [`config`].
+fn load_settings() -> Result<config::Settings, String> {
+    let cwd =
+        env::current_dir().map_err(|e| format!("Failed to read the current directory: {}", e))?;
+    config::load(env::consts::OS, &cwd, |name| env::var_os(name))
+}
+
+/// Parses `args` on top of `settings`: a configured destination becomes the
+/// default one, and the other settings become options placed before those
+/// given, which therefore take precedence.
+///
+/// # Errors
+///
+/// As [`parse_args_from`], and `"Unknown profile: ... (from {origin})"`
+/// for a configured profile that does not exist and is not overridden.
+fn parse_args_with(settings: &config::Settings, args: Vec<String>) -> Result<Args, String> {
+    let given = args.get(1..).unwrap_or_default();
+    let options = settings.options(given);
+    if let Some((setting, name)) = settings
+        .get("profile")
+        .zip(settings.text("profile"))
+        .filter(|(_, name)| options.iter().any(|option| option == name))
+    {
+        Profile::parse(name).map_err(|e| format!("{} (from {})", e, setting.origin))?;
+    }
+    let mut defaults = Args::default();
+    if let Some(destination) = settings.text("destination") {
+        defaults.destination = destination.to_string();
+    }
+    let mut argv: Vec<String> = args.iter().take(1).cloned().collect();
+    argv.extend(options);
+    argv.extend(given.iter().cloned());
+    parse_args_onto(defaults, argv)
+}
+
+/// Normalizes the path given to `flag` (`--force-include`, `--related`,
+/// `--subdir`) to `/`-separated components.
+///
+/// `.` components and repeated separators are dropped. The path must stay
+/// inside the source root, so absolute paths and `..` are rejected.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(source_relative_path("--force-include", "./lib//oz/A.sol")?, "lib/oz/A.sol");
+/// ```
+fn source_relative_path(flag: &str, value: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "Invalid {} path: {:?} (expected a path relative to the source root)",
+            flag, value
+        )
+    };
+    if value.starts_with(['/', '\\']) || Path::new(value).is_absolute() {
+        return Err(invalid());
+    }
+    let components: Vec<&str> = value
+        .split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect();
+    if components.is_empty() || components.contains(&"..") {
+        return Err(invalid());
+    }
+    Ok(components.join("/"))
+}
+
+/// Parses an explicit argument vector (including the program name at index 0).
+///
+/// This is the testable core of [`parse_args`], without configuration.
+fn parse_args_from(args: Vec<String>) -> Result<Args, String> {
+    parse_args_onto(Args::default(), args)
+}
+
+/// Parses `args` as [`parse_args_from`] does, starting from `parsed`
+/// rather than the built-in defaults.
+fn parse_args_onto(mut parsed: Args, args: Vec<String>) -> Result<Args, String> {
     let mut positional: Vec<String> = Vec::new();
+
+    // Profiles go first so that explicit flags can override them
+    for pair in args.get(1..).unwrap_or_default().windows(2) {
+        if pair[0] == "--profile" {
+            Profile::parse(&pair[1])?.apply(&mut parsed);
+        }
+    }
+
     let mut i = 1;
 
     while i < args.len() {
         let arg = &args[i];
+        if arg.starts_with('-') && !FLAGS.contains(&arg.as_str()) {
+            return Err(format!("Unknown option: {}", arg));
+        }
+        let start = i;
+        let mut is_option = true;
         match arg.as_str() {
-            "-h" | "--help" => parsed.show_help = true,
+            "-h" | "--help" => {
+                parsed.show_help = true;
+                if let Some(topic) = args.get(i + 1).filter(|next| !next.starts_with('-')) {
+                    parsed.help_page = HelpPage::parse(topic)?;
+                    i += 1;
+                }
+            }
             "-v" | "--version" => parsed.show_version = true,
+            "--version-full" => {
+                parsed.show_version = true;
+                parsed.version_full = true;
+            }
             "-l" | "--local" => parsed.is_local = true,
+            "--no-autodetect" => parsed.no_autodetect = true,
             "-q" | "--quiet" => parsed.quiet = true,
+            "--verbose" => parsed.verbose = true,
+            "--ascii" => {
+                parsed.charset = Charset::Ascii;
+                parsed.output_charset = Charset::Ascii;
+            }
             "--include-lib" => parsed.include_lib = true,
+            "--offline" => parsed.offline = true,
+            "--no-git" => parsed.no_git = true,
+            "--foundry-cache" => {
+                parsed.foundry_cache = Some(take_value(&args, &mut i, "--foundry-cache")?);
+            }
             "--include-test" => parsed.include_test = true,
             "--include-script" => parsed.include_script = true,
+            "--force-include" => {
+                let value = take_value(&args, &mut i, "--force-include")?;
+                parsed
+                    .force_include
+                    .push(source_relative_path("--force-include", &value)?);
+            }
             "--no-headers" => parsed.no_headers = true,
-            "-o" | "--output" => {
-                i += 1;
-                if i >= args.len() {
-                    return Err("--output requires a value".to_string());
+            "--no-headers-for" => {
+                let pattern = take_value(&args, &mut i, "--no-headers-for")?;
+                if pattern.trim_matches(['/', '.', ' ']).is_empty() {
+                    return Err(format!(
+                        "Invalid --no-headers-for pattern: {:?} (expected a path or glob \
+                         relative to the source root)",
+                        pattern
+                    ));
+                }
+                parsed.no_headers_for.push(pattern);
+            }
+            "--stats" => parsed.stats = true,
+            "--stats-depth" => {
+                let value = take_value(&args, &mut i, "--stats-depth")?;
+                match value.parse::<usize>() {
+                    Ok(depth) if depth > 0 => parsed.stats_depth = Some(depth),
+                    _ => {
+                        return Err(format!(
+                            "Invalid --stats-depth: {:?} (expected a positive whole number)",
+                            value
+                        ));
+                    }
+                }
+            }
+            "--run-dir" => parsed.run_dir = true,
+            "--split-by-dir" => parsed.split_by_dir = true,
+            "--stream" => parsed.stream = true,
+            "--dedupe-content" => parsed.dedupe_content = true,
+            "--prune-implemented-interfaces" => parsed.prune_implemented_interfaces = true,
+            "--vcs-friendly" => parsed.vcs_friendly = true,
+            "--explain" => parsed.explain = true,
+            "--list-files" => parsed.list_files = true,
+            "--digest" => parsed.digest = true,
+            "--output-mode" => {
+                parsed.output_mode = Some(OutputMode::parse(&take_value(
+                    &args,
+                    &mut i,
+                    "--output-mode",
+                )?)?);
+            }
+            "-0" | "--null" => parsed.null_terminated = true,
+            "--strict" => parsed.strict = true,
+            "--gate" => parsed
+                .gates
+                .push(gate::Gate::parse(&take_value(&args, &mut i, "--gate")?)?),
+            "--no-subprocess" => parsed.no_subprocess = true,
+            "--precheck" => parsed.precheck = Some(true),
+            "--no-precheck" => parsed.precheck = Some(false),
+            "-y" | "--yes" => parsed.assume_yes = true,
+            "--inject-pragma" => {
+                let version = take_value(&args, &mut i, "--inject-pragma")?;
+                if version.trim().is_empty() || version.contains([';', '\n', '\r']) {
+                    return Err(format!(
+                        "Invalid --inject-pragma version: {:?} (expected e.g. ^0.8.0)",
+                        version
+                    ));
+                }
+                parsed.inject_pragma = Some(version.trim().to_string());
+            }
+            "--min-solidity" => {
+                let version = take_value(&args, &mut i, "--min-solidity")?;
+                pragma::Constraint::at_least(&version)?;
+                parsed.min_solidity = Some(version.trim().to_string());
+            }
+            "--filter-pragma" => {
+                let constraint = take_value(&args, &mut i, "--filter-pragma")?;
+                parsed.filter_pragma = Some(pragma::Constraint::parse(&constraint)?);
+            }
+            "--pragma-unknown" => {
+                parsed.pragma_unknown = Some(
+                    match take_value(&args, &mut i, "--pragma-unknown")?.as_str() {
+                        "include" => PragmaUnknown::Include,
+                        "exclude" => PragmaUnknown::Exclude,
+                        other => {
+                            return Err(format!(
+                                "Invalid --pragma-unknown policy: {} (expected include or exclude)",
+                                other
+                            ));
+                        }
+                    },
+                );
+            }
+            "--compare" => {
+                let range = take_value(&args, &mut i, "--compare")?;
+                parsed.compare = Some(parse_compare_range(&range)?);
+            }
+            "--since" => {
+                let git_ref = take_value(&args, &mut i, "--since")?;
+                let git_ref = git_ref.trim();
+                if git_ref.is_empty() || git_ref.starts_with('-') || git_ref.contains("..") {
+                    return Err(format!(
+                        "Invalid --since ref: {:?} (expected a tag, branch, or commit)",
+                        git_ref
+                    ));
+                }
+                parsed.since = Some(git_ref.to_string());
+            }
+            "--related" => {
+                let value = take_value(&args, &mut i, "--related")?;
+                parsed.related = Some(source_relative_path("--related", &value)?);
+            }
+            "--reuse-clone" => {
+                parsed.reuse_clone = Some(take_value(&args, &mut i, "--reuse-clone")?);
+            }
+            "--trust-clone" => parsed.trust_clone = true,
+            "--allow-dirty" => parsed.allow_dirty = true,
+            "--profile" => {
+                // Already applied above; validate that a value is present
+                take_value(&args, &mut i, "--profile")?;
+            }
+            "--kinds" => {
+                let list = take_value(&args, &mut i, "--kinds")?;
+                parsed.kinds = Some(parse_kinds(&list)?);
+            }
+            "--flag-todos" => parsed.flag_todos = true,
+            "--checksum-file" => parsed.checksum_file = true,
+            "--parts-file" => parsed.parts_file = true,
+            "--errors-report" => {
+                let format = take_value(&args, &mut i, "--errors-report")?;
+                parsed.errors_report = Some(error_report::Format::parse(&format)?);
+            }
+            "--metrics-file" => {
+                parsed.metrics_file = Some(take_value(&args, &mut i, "--metrics-file")?);
+            }
+            "--metrics-stdout" => parsed.metrics_stdout = true,
+            "--with-context" => parsed.with_context = true,
+            "--context-readme-limit" => {
+                let value = take_value(&args, &mut i, "--context-readme-limit")?;
+                let limit = space::parse_size(&value)?;
+                parsed.context_readme_limit = Some(usize::try_from(limit).unwrap_or(usize::MAX));
+            }
+            "--keep-license-headers" => parsed.keep_license_headers = true,
+            "--no-clean" => parsed.no_clean = true,
+            "--rewrap-long-lines" => parsed.rewrap_long_lines = true,
+            "--max-brace-depth" => {
+                let value = take_value(&args, &mut i, "--max-brace-depth")?;
+                match value.parse::<usize>() {
+                    Ok(limit) if limit > 0 => parsed.max_brace_depth = Some(limit),
+                    _ => {
+                        return Err(format!(
+                            "Invalid --max-brace-depth: {:?} (expected a positive whole number)",
+                            value
+                        ));
+                    }
+                }
+            }
+            "--strip-revert-strings" => parsed.strip_revert_strings = true,
+            "--max-line-width" => {
+                let value = take_value(&args, &mut i, "--max-line-width")?;
+                match value.parse::<usize>() {
+                    Ok(width) if width > 0 => parsed.max_line_width = Some(width),
+                    _ => {
+                        return Err(format!(
+                            "Invalid --max-line-width: {:?} (expected a positive whole number)",
+                            value
+                        ));
+                    }
+                }
+            }
+            "-o" | "--output" => parsed.output_name = Some(take_value(&args, &mut i, "--output")?),
+            "--log-file" => parsed.log_file = Some(take_value(&args, &mut i, "--log-file")?),
+            "--temp-dir" => parsed.temp_dir = Some(take_value(&args, &mut i, "--temp-dir")?),
+            "--min-temp-space" => {
+                let value = take_value(&args, &mut i, "--min-temp-space")?;
+                parsed.min_temp_space = Some(space::parse_size(&value)?);
+            }
+            "--max-clone-size" => {
+                let value = take_value(&args, &mut i, "--max-clone-size")?;
+                parsed.max_clone_size = Some(space::parse_size(&value)?);
+            }
+            "--deadline" => {
+                let value = take_value(&args, &mut i, "--deadline")?;
+                parsed.deadline = Some(Instant::now() + clock::parse_duration(&value)?);
+            }
+            "--older-than" => {
+                let value = take_value(&args, &mut i, "--older-than")?;
+                parsed.older_than = Some(clock::parse_duration(&value)?);
+            }
+            "--all" => parsed.clean_all = true,
+            "--mtime-from" => parsed.mtime_from = Some(take_value(&args, &mut i, "--mtime-from")?),
+            "--against" => parsed.against = Some(take_value(&args, &mut i, "--against")?),
+            "-b" | "--branch" => {
+                let git_ref = take_value(&args, &mut i, "--branch")?;
+                if git_ref.trim().is_empty() || git_ref.starts_with('-') {
+                    return Err(format!(
+                        "Invalid --branch: {:?} (expected a branch or tag)",
+                        git_ref
+                    ));
+                }
+                parsed.url_ref = Some(git_ref.trim().to_string());
+            }
+            "--token" => {
+                let token = take_value(&args, &mut i, "--token")?;
+                if token.trim().is_empty() {
+                    return Err("--token cannot be empty".to_string());
+                }
+                parsed.token = Some(forge_host::Token(token));
+            }
+            "--retries" => {
+                let value = take_value(&args, &mut i, "--retries")?;
+                parsed.retries = Some(value.parse::<usize>().map_err(|_| {
+                    format!(
+                        "Invalid --retries: {:?} (expected a whole number, 0 for none)",
+                        value
+                    )
+                })?);
+            }
+            "--timeout" => {
+                let value = take_value(&args, &mut i, "--timeout")?;
+                let timeout = clock::parse_duration(&value)?;
+                if timeout.is_zero() {
+                    return Err("--timeout must be at least 1s".to_string());
+                }
+                parsed.clone_timeout = Some(timeout);
+            }
+            "--rev" => {
+                let rev = take_value(&args, &mut i, "--rev")?;
+                if rev.trim().is_empty() || rev.starts_with('-') {
+                    return Err(format!(
+                        "Invalid --rev: {:?} (expected a commit, tag, or branch)",
+                        rev
+                    ));
                 }
-                parsed.output_name = Some(args[i].clone());
+                parsed.rev = Some(rev.trim().to_string());
+            }
+            "--subdir" => {
+                let subdir = take_value(&args, &mut i, "--subdir")?;
+                parsed.subdir = Some(source_relative_path("--subdir", &subdir)?);
+            }
+            "--resume" => {
+                parsed.resume = true;
+                is_option = false;
+            }
+            "--from-file" => {
+                parsed.from_file = Some(take_value(&args, &mut i, "--from-file")?);
+                is_option = false;
+            }
+            "--source" => {
+                parsed.sources.push(take_value(&args, &mut i, "--source")?);
+                is_option = false;
             }
+            "--split-by-source" => parsed.split_by_source = true,
             _ if arg.starts_with('-') => {
                 return Err(format!("Unknown option: {}", arg));
             }
-            _ => positional.push(arg.clone()),
+            _ => {
+                positional.push(arg.clone());
+                is_option = false;
+            }
+        }
+        if is_option {
+            parsed.batch_options.extend_from_slice(&args[start..=i]);
         }
         i += 1;
     }
+    if parsed.from_file.is_none() {
+        parsed.batch_options.clear();
+    }
 
     if parsed.show_help || parsed.show_version {
         return Ok(parsed);
     }
 
-    match positional.len() {
-        0 => return Err("Missing required argument: <source>".to_string()),
-        1 => parsed.source = positional[0].clone(),
-        2 => {
-            parsed.source = positional[0].clone();
-            parsed.destination = positional[1].clone();
+    // `check` reads a source like a scrape does, so `--local` is one of its
+    // options; a directory named `check` can still be written `./check`
+    let subcommand = positional.first().filter(|p| {
+        (!parsed.is_local || p.as_str() == "check")
+            && matches!(
+                p.as_str(),
+                "split" | "extract" | "verify" | "check" | "cleanup-temp" | "config"
+            )
+    });
+    if let (Some(name), Some(_)) = (subcommand, parsed.output_mode) {
+        return Err(format!("--output-mode cannot be used with {}", name));
+    }
+    if let (Some(name), Some(_)) = (subcommand, &parsed.from_file) {
+        return Err(format!("--from-file cannot be used with {}", name));
+    }
+    if let (Some(name), false) = (subcommand, parsed.sources.is_empty()) {
+        return Err(format!("--source cannot be used with {}", name));
+    }
+
+    if !parsed.is_local && positional.first().map(String::as_str) == Some("split") {
+        let [_, input, out_dir] = positional.as_slice() else {
+            return Err("Usage: solscrape split <scraped.sol> <out-dir>".to_string());
+        };
+        parsed.subcommand = Some(Subcommand::Split {
+            input: input.clone(),
+            out_dir: out_dir.clone(),
+            mtime_from: parsed.mtime_from.clone(),
+        });
+        return Ok(parsed);
+    }
+
+    if parsed.mtime_from.is_some() {
+        return Err("--mtime-from can only be used with split".to_string());
+    }
+
+    if !parsed.is_local && positional.first().map(String::as_str) == Some("extract") {
+        let [_, input, path] = positional.as_slice() else {
+            return Err("Usage: solscrape extract <scraped.sol> <relative/path>".to_string());
+        };
+        parsed.subcommand = Some(Subcommand::Extract {
+            input: input.clone(),
+            path: path.clone(),
+        });
+        return Ok(parsed);
+    }
+
+    if !parsed.is_local && positional.first().map(String::as_str) == Some("verify") {
+        let [_, input] = positional.as_slice() else {
+            return Err("Usage: solscrape verify <scraped.sol>".to_string());
+        };
+        parsed.subcommand = Some(Subcommand::Verify {
+            input: input.clone(),
+        });
+        return Ok(parsed);
+    }
+
+    if positional.first().map(String::as_str) == Some("check") {
+        let ([_, source], Some(against)) = (positional.as_slice(), &parsed.against) else {
+            return Err("Usage: solscrape check <source> --against <scraped.sol>".to_string());
+        };
+        if parsed.compare.is_some()
+            || parsed.since.is_some()
+            || parsed.related.is_some()
+            || parsed.split_by_dir
+            || parsed.list_files
+        {
+            return Err(
+                "check cannot be used with --compare, --since, --related, --split-by-dir, \
+                 or --list-files"
+                    .to_string(),
+            );
+        }
+        parsed.source = source.clone();
+        parsed.subcommand = Some(Subcommand::Check {
+            against: against.clone(),
+        });
+        return Ok(parsed);
+    }
+
+    if parsed.against.is_some() {
+        return Err("--against can only be used with check".to_string());
+    }
+
+    if !parsed.is_local && positional.first().map(String::as_str) == Some("cleanup-temp") {
+        if positional.len() != 1 || (parsed.clean_all && parsed.older_than.is_some()) {
+            return Err("Usage: solscrape cleanup-temp [--older-than <AGE> | --all]".to_string());
+        }
+        let min_age = if parsed.clean_all {
+            Duration::ZERO
+        } else {
+            parsed.older_than.unwrap_or(DEFAULT_CLEANUP_AGE)
+        };
+        parsed.subcommand = Some(Subcommand::CleanupTemp { min_age });
+        return Ok(parsed);
+    }
+
+    if !parsed.is_local && positional.first().map(String::as_str) == Some("config") {
+        if positional.len() != 2 || positional[1] != "path" {
+            return Err("Usage: solscrape config path".to_string());
+        }
+        parsed.subcommand = Some(Subcommand::ConfigPath);
+        return Ok(parsed);
+    }
+
+    if parsed.older_than.is_some() || parsed.clean_all {
+        return Err("--older-than and --all can only be used with cleanup-temp".to_string());
+    }
+
+    if parsed.resume && parsed.from_file.is_none() {
+        return Err("--resume can only be used with --from-file".to_string());
+    }
+
+    let mut destination_given = false;
+    if !parsed.sources.is_empty() {
+        if parsed.from_file.is_some() {
+            return Err("--source cannot be used with --from-file".to_string());
+        }
+        match positional.as_slice() {
+            [] => {}
+            [destination] => {
+                parsed.destination = destination.clone();
+                destination_given = true;
+            }
+            _ => {
+                return Err("With --source, the only argument is the destination".to_string());
+            }
         }
-        _ => return Err("Too many positional arguments".to_string()),
+        if parsed.sources.len() == 1 {
+            parsed.source = parsed.sources.remove(0);
+        }
+    } else if parsed.from_file.is_some() {
+        match positional.as_slice() {
+            [] => {}
+            [destination] => {
+                parsed.destination = destination.clone();
+                destination_given = true;
+            }
+            _ => {
+                return Err(
+                    "With --from-file, sources come from the file; the only argument is \
+                     the destination"
+                        .to_string(),
+                );
+            }
+        }
+        if parsed
+            .output_name
+            .as_deref()
+            .is_some_and(|output| is_output_path(output) || output.ends_with(".sol"))
+        {
+            return Err(
+                "With --from-file, --output is a name for the outputs in the destination, \
+                 not a path or a .sol file"
+                    .to_string(),
+            );
+        }
+    } else {
+        match positional.len() {
+            0 => return Err("Missing required argument: <source>".to_string()),
+            1 => parsed.source = positional[0].clone(),
+            2 => {
+                parsed.source = positional[0].clone();
+                parsed.destination = positional[1].clone();
+                destination_given = true;
+            }
+            _ => return Err("Too many positional arguments".to_string()),
+        }
+    }
+
+    if let Some(output) = parsed.output_name.take() {
+        let home = env::var("HOME").ok();
+        let given = destination_given.then_some(parsed.destination.as_str());
+        let (destination, name) = resolve_output(&parsed.destination, given, &output, home)?;
+        parsed.destination = destination;
+        parsed.output_name = name;
+    }
+
+    if parsed.null_terminated && !parsed.list_files {
+        return Err("-0 can only be used with --list-files".to_string());
+    }
+
+    if parsed.explain && parsed.output_mode != Some(OutputMode::Json) {
+        return Err("--explain can only be used with --output-mode json".to_string());
+    }
+
+    if parsed.split_by_source && parsed.sources.is_empty() {
+        return Err("--split-by-source requires more than one --source".to_string());
+    }
+
+    if parsed.pragma_unknown.is_some()
+        && parsed.filter_pragma.is_none()
+        && parsed.min_solidity.is_none()
+    {
+        return Err(
+            "--pragma-unknown can only be used with --filter-pragma or --min-solidity".to_string(),
+        );
     }
 
     Ok(parsed)
 }
 
-/// Prints the help message with usage instructions and available options.
-///
-/// Displays comprehensive CLI documentation including argument descriptions,
-/// all available flags, and practical usage examples.
-fn print_help() {
-    println!(
-        r#"
-Solscrape v{} - Solidity Source Code Scraper
+/// Returns true if the `--output` value `value` is a path rather than a
+/// bare name: it has a path separator, or is `~`.
+fn is_output_path(value: &str) -> bool {
+    value == "~" || value.contains('/') || value.contains(std::path::MAIN_SEPARATOR)
+}
 
-USAGE:
-    solscrape [OPTIONS] <source> [destination]
+/// Resolves `-o`/`--output` against the destination, returning the
+/// destination and output name to scrape with.
+///
+/// `destination` is the destination in effect, and `given` the one given as
+/// an argument, if any. A bare name is joined to the destination as
+/// `{name}_scraped.sol`, or used as it is when it ends in `.sol`, which
+/// becomes the exact output file (and the name `None`). A path, one
+/// [`is_output_path`] accepts, is the whole output: its directory replaces
+/// the destination and its last component is the name, as for a bare name.
+/// `~` and a leading `~/` are replaced by `home`.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"--output cannot be used when the destination is a .sol file ({destination})"` | A bare name with a `.sol` destination |
+/// | `"--output {output} is a path, so it cannot be used with the destination {given} ..."` | A path with a destination argument |
+/// | `"--output {output} names a directory ..."` | A path with no file name, e.g. ending in a separator, or `~` |
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let resolve = |given, output| resolve_output("./out", given, output, None);
+/// assert_eq!(resolve(None, "vault")?, ("./out".into(), Some("vault".into())));
+/// assert_eq!(resolve(None, "vault.sol")?, ("./out/vault.sol".into(), None));
+/// assert_eq!(resolve(None, "/abs/vault")?, ("/abs".into(), Some("vault".into())));
+/// assert!(resolve(Some("./out"), "/abs/vault").is_err());
+/// ```
+fn resolve_output(
+    destination: &str,
+    given: Option<&str>,
+    output: &str,
+    home: Option<String>,
+) -> Result<(String, Option<String>), String> {
+    if !is_output_path(output) {
+        if explicit_output_file(destination).is_some() {
+            return Err(format!(
+                "--output cannot be used when the destination is a .sol file ({})",
+                destination
+            ));
+        }
+        if output.len() > ".sol".len() && output.ends_with(".sol") {
+            let file = Path::new(destination).join(output);
+            return Ok((file.to_string_lossy().to_string(), None));
+        }
+        return Ok((destination.to_string(), Some(output.to_string())));
+    }
+    if let Some(given) = given {
+        return Err(format!(
+            "--output {} is a path, so it cannot be used with the destination {}; \
+             give a destination and a bare --output name, or --output alone",
+            output, given
+        ));
+    }
 
-ARGUMENTS:
-    <source>        Git repository URL or local directory path (with --local)
-    [destination]   Output directory (default: current directory)
-
-OPTIONS:
-    -o, --output <NAME>    Custom output filename (without _scraped.sol suffix)
-    -l, --local            Treat source as a local directory path
-    --include-lib          Include lib/ dependencies
-    --include-test         Include test/ files
-    --include-script       Include script/ files
-    --no-headers           Omit file separator headers in output
-    -q, --quiet            Suppress progress output (only print result path)
-    -h, --help             Show this help message
-    -v, --version          Show version
-
-EXAMPLES:
-    solscrape https://github.com/clober-dex/v2-core.git
-    solscrape https://github.com/OpenZeppelin/openzeppelin-contracts.git ./output
-    solscrape https://github.com/uniswap/v3-core.git -o uniswap_v3
-    solscrape ./my-local-project --local -o my_contracts
-    solscrape https://github.com/example/repo.git --include-lib --include-test
-"#,
-        VERSION
-    );
+    let expanded = match (output.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            format!("{}{}", home.trim_end_matches('/'), rest)
+        }
+        _ => output.to_string(),
+    };
+    let path = Path::new(&expanded);
+    let name = match path.file_name() {
+        Some(name) if output != "~" && !output.ends_with(['/', std::path::MAIN_SEPARATOR]) => {
+            name.to_string_lossy().to_string()
+        }
+        _ => {
+            return Err(format!(
+                "--output {} names a directory; give it as the destination instead",
+                output
+            ));
+        }
+    };
+    if name.len() > ".sol".len() && name.ends_with(".sol") {
+        return Ok((expanded, None));
+    }
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_string_lossy().to_string(),
+        _ => ".".to_string(),
+    };
+    Ok((dir, Some(name)))
 }
 
-/// Prints the version string in `solscrape {VERSION}` format.
-fn print_version() {
-    println!("solscrape {}", VERSION);
+/// Splits a `--compare` value such as `v1.0.0..v2.0.0` into its two refs.
+///
+/// # Errors
+///
+/// Returns `"Invalid --compare range: ..."` unless the value is two non-empty
+/// refs joined by `..`.
+fn parse_compare_range(range: &str) -> Result<(String, String), String> {
+    match range.split_once("..") {
+        Some((old, new))
+            if !old.trim().is_empty() && !new.trim().is_empty() && !new.contains("..") =>
+        {
+            Ok((old.trim().to_string(), new.trim().to_string()))
+        }
+        _ => Err(format!(
+            "Invalid --compare range: {} (expected <old>..<new>, e.g. v1.0.0..v2.0.0)",
+            range
+        )),
+    }
 }
 
-/// Prints the decorative ASCII banner with version information.
+/// Consumes the value following the option at `args[*i]`, advancing `i` past it.
 ///
-/// Displayed at startup in non-quiet mode to provide visual context.
-fn print_banner() {
-    println!(
-        r#"
-╔═══════════════════════════════════════════════════════════════╗
-║              SOLSCRAPE v{}  -  Solidity Scraper              ║
-╚═══════════════════════════════════════════════════════════════╝
-"#,
-        VERSION
-    );
+/// Returns `"{name} requires a value"` when the option is the last argument.
+fn take_value(args: &[String], i: &mut usize, name: &str) -> Result<String, String> {
+    *i += 1;
+    args.get(*i)
+        .cloned()
+        .ok_or_else(|| format!("{} requires a value", name))
 }
 
-// ============================================================================
-// Solidity Parser - Comment Removal
-// ============================================================================
+/// Whether a flag combination stops the run or only produces a warning.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    /// The flags contradict each other; the run stops.
+    Error,
+    /// One flag makes the other ineffective; the run continues.
+    Warning,
+}
 
-/// The current parsing context within Solidity source code.
-///
-/// Used by [`remove_comments`] to track whether the parser is inside a string
-/// literal, comment block, or normal code context. This enables correct handling
-/// of comment-like syntax within strings (e.g., `"// not a comment"`).
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum ParserState {
-    /// Normal code context; comments and strings may begin.
-    Normal,
-    /// Inside a double-quoted string literal (`"..."`).
-    InStringDouble,
-    /// Inside a single-quoted string literal (`'...'`).
-    InStringSingle,
-    /// Inside a single-line comment (`// ...`).
-    InSingleComment,
-    /// Inside a multi-line comment (`/* ... */`).
-    InMultiComment,
+/// A combination of flags that conflict, or where one has no effect.
+struct FlagCombination {
+    /// Stops the run or warns.
+    severity: Severity,
+    /// Returns true if `args` uses the combination.
+    applies: fn(&Args) -> bool,
+    /// What happens; warnings name the flag that wins.
+    message: &'static str,
 }
 
-/// Removes all comments from Solidity source code while preserving string literals.
+/// Flag combinations checked by [`check_flag_combinations`].
 ///
-/// Use this function to strip both single-line (`//`) and multi-line (`/* */`)
-/// comments from Solidity code. String literals containing comment-like syntax
-/// are correctly preserved.
+/// Every combination of flags that conflict, or where one overrides or
+/// ignores another, is listed here rather than checked in
+/// [`parse_args_from`], which keeps to the positional arguments and
+/// subcommands. Add an entry, and a case to `test_flag_combinations`,
+/// whenever a new flag conflicts with, overrides, or ignores another.
 ///
-/// # Behavioral Contract
+/// | Severity | Flags | Outcome |
+/// |----------|-------|---------|
+/// | error | `--quiet` with `--verbose` | Contradictory |
+/// | warning | `--local` with a URL source | `--local` wins; the source is read as a path |
+/// | warning | `--keep-license-headers` with `--no-clean` | `--no-clean` wins; headers are kept verbatim anyway |
+/// | warning | `--stats` with `--quiet` | `--quiet` wins; no report is printed |
+/// | warning | `--verbose` with `--list-files` | `--list-files` wins; only paths are printed |
+/// | warning | `--precheck` with a local directory | `--local` wins; nothing is pre-checked |
+/// | warning | `--yes` with `--no-precheck` and no `--max-clone-size` | `--no-precheck` wins; nothing is asked |
+/// | warning | `--offline` or `--foundry-cache` without `--include-lib` on a cloned repository | No dependencies are installed anyway |
+/// | warning | `--context-readme-limit` without `--with-context` | No context is appended |
+/// | warning | `--trust-clone` without `--reuse-clone` | Nothing is checked anyway |
+/// | warning | `--allow-dirty` without `--reuse-clone` | Fresh clones are never dirty |
+/// | warning | `--max-clone-size` with a local directory | `--local` wins; nothing is probed |
+/// | warning | `--branch` with a local directory | `--local` wins; no branch is checked out |
+/// | warning | `--no-headers-for` with `--no-headers` | `--no-headers` wins; every header is omitted |
+/// | warning | `--stats-depth` without `--stats` | No report is printed |
+/// | warning | `--rev` with a local directory | `--local` wins; no revision is checked out |
+/// | warning | `--no-git` with a local directory | Nothing is cloned anyway |
+/// | error | `--compare` with `--list-files`, `--stream`, or `--no-headers` | Contradictory |
+/// | error | `--strip-revert-strings` with `--no-clean` | Contradictory |
+/// | error | `--output-mode` with `--list-files` | Contradictory |
+/// | error | `--output-mode json` with `--compare` | Contradictory |
+/// | error | `--vcs-friendly` with `--stream`, `--run-dir`, or `--compare` | Contradictory |
+/// | error | `--since` with `--compare` | Contradictory |
+/// | error | `--reuse-clone` with `--local` or `--compare` | Contradictory |
+/// | error | `--checksum-file` with `--list-files` or `--compare` | Contradictory |
+/// | error | `--parts-file` with `--no-headers`, `--list-files`, or `--compare` | `extract` checks the header at each range |
+/// | error | `--metrics-file` with `--metrics-stdout` | Contradictory |
+/// | error | `--metrics-file` or `--metrics-stdout` with `--list-files` or `--compare` | Contradictory |
+/// | error | `--metrics-stdout` with `--output-mode` | Both write to stdout |
+/// | error | `--split-by-dir` with `--stream`, `--list-files`, or `--compare` | Contradictory |
+/// | error | `--split-by-dir` with `--output-mode path` or `content` | Those print a single output |
+/// | error | `--split-by-dir` with a `.sol` destination | There is one output file |
+/// | error | `--from-file` with `--list-files` or `--output-mode` | Those print a single output; `--quiet` prints a line per source |
+/// | error | `--dedupe-content` with `--stream`, `--list-files`, or `--compare` | Contradictory |
+/// | error | `--related` with `--list-files` or `--compare` | Contradictory |
+/// | error | `--rev` with `--branch` | Contradictory |
+/// | error | `--rev` with `--compare` | `--compare` names its own refs |
+/// | error | `--no-git` with `--reuse-clone`, `--compare`, `--since`, or `--precheck` | They need git |
+/// | error | `--prune-implemented-interfaces` with `--stream`, `--list-files`, or `--compare` | Contradictory |
+/// | error | `--digest` with `--list-files`, `--output-mode`, `--compare`, or `--from-file` | Contradictory |
+/// | error | `--max-line-width` with `--no-clean` | Contradictory |
+/// | error | `--errors-report` with `--list-files`, `--compare`, `--digest`, or `--split-by-dir` | Contradictory |
+/// | error | Several `--source` with `--list-files`, `--digest`, `--compare`, `--since`, `--related`, `--reuse-clone`, `--stream`, `--split-by-dir`, `--with-context`, `--deadline`, or `--output-mode json` | Contradictory |
+/// | error | `--split-by-source` with `--errors-report`, `--output-mode path` or `content`, or a `.sol` destination | Contradictory |
+/// | error | `--gate` with `--list-files`, `--compare`, or `--digest` | Contradictory |
+const FLAG_COMBINATIONS: &[FlagCombination] = &[
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.quiet && a.verbose,
+        message: "--quiet cannot be used with --verbose",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.is_local && looks_like_remote_url(&a.source),
+        message: "the source looks like a URL, but --local reads it as a directory path",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.keep_license_headers && a.no_clean,
+        message: "--keep-license-headers has no effect with --no-clean, \
+                  which copies files verbatim, license headers included",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.stats && a.quiet,
+        message: "--stats has no effect with --quiet, which prints no report",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.verbose && a.list_files,
+        message: "--verbose has no effect with --list-files, which prints only paths",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.precheck == Some(true) && !clones_source(a),
+        message: "--precheck has no effect on a local directory, which is not cloned",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.assume_yes && a.precheck == Some(false) && a.max_clone_size.is_none(),
+        message: "--yes has no effect with --no-precheck, which asks nothing",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| {
+            (a.offline || a.foundry_cache.is_some()) && !(a.include_lib && clones_source(a))
+        },
+        message: "--offline and --foundry-cache have no effect without --include-lib \
+                  on a repository that is cloned",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.context_readme_limit.is_some() && !a.with_context,
+        message: "--context-readme-limit has no effect without --with-context",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.trust_clone && a.reuse_clone.is_none(),
+        message: "--trust-clone has no effect without --reuse-clone",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.allow_dirty && a.reuse_clone.is_none(),
+        message: "--allow-dirty has no effect without --reuse-clone",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.max_clone_size.is_some() && !clones_source(a),
+        message: "--max-clone-size has no effect on a local directory, which is not cloned",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| {
+            a.url_ref.is_some()
+                && !clones_source(a)
+                && a.from_file.is_none()
+                && a.reuse_clone.is_none()
+        },
+        message: "--branch has no effect on a local directory, which is not cloned",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| !a.no_headers_for.is_empty() && a.no_headers,
+        message: "--no-headers-for has no effect with --no-headers, which omits every header",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.stats_depth.is_some() && !a.stats,
+        message: "--stats-depth has no effect without --stats",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| {
+            a.rev.is_some() && !clones_source(a) && a.from_file.is_none() && a.reuse_clone.is_none()
+        },
+        message: "--rev has no effect on a local directory, which is not cloned",
+    },
+    FlagCombination {
+        severity: Severity::Warning,
+        applies: |a| a.no_git && !clones_source(a) && a.from_file.is_none(),
+        message: "--no-git has no effect on a local directory, which is not cloned",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.compare.is_some() && (a.list_files || a.stream || a.no_headers),
+        message: "--compare cannot be used with --list-files, --stream, or --no-headers",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.strip_revert_strings && a.no_clean,
+        message: "--strip-revert-strings cannot be used with --no-clean",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.output_mode.is_some() && a.list_files,
+        message: "--output-mode cannot be used with --list-files",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.output_mode == Some(OutputMode::Json) && a.compare.is_some(),
+        message: "--output-mode json cannot be used with --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.vcs_friendly && (a.stream || a.run_dir || a.compare.is_some()),
+        message: "--vcs-friendly cannot be used with --stream, --run-dir, or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.since.is_some() && a.compare.is_some(),
+        message: "--since cannot be used with --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.reuse_clone.is_some() && (a.is_local || a.compare.is_some()),
+        message: "--reuse-clone cannot be used with --local or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.checksum_file && (a.list_files || a.compare.is_some()),
+        message: "--checksum-file cannot be used with --list-files or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.parts_file && (a.no_headers || a.list_files || a.compare.is_some()),
+        message: "--parts-file cannot be used with --no-headers, --list-files, or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.metrics_file.is_some() && a.metrics_stdout,
+        message: "--metrics-file cannot be used with --metrics-stdout",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            (a.metrics_file.is_some() || a.metrics_stdout) && (a.list_files || a.compare.is_some())
+        },
+        message: "--metrics-file and --metrics-stdout cannot be used with --list-files \
+                  or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.metrics_stdout && a.output_mode.is_some(),
+        message: "--metrics-stdout cannot be used with --output-mode",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.split_by_dir && (a.stream || a.list_files || a.compare.is_some()),
+        message: "--split-by-dir cannot be used with --stream, --list-files, or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.split_by_dir && matches!(a.output_mode, Some(OutputMode::Path | OutputMode::Content))
+        },
+        message: "--split-by-dir cannot be used with --output-mode path or content, \
+                  which print a single output",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.split_by_dir && explicit_output_file(&a.destination).is_some(),
+        message: "--split-by-dir cannot be used when the destination is a .sol file",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.from_file.is_some() && (a.list_files || a.output_mode.is_some()),
+        message: "--from-file cannot be used with --list-files or --output-mode",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.dedupe_content && (a.stream || a.list_files || a.compare.is_some()),
+        message: "--dedupe-content cannot be used with --stream, --list-files, or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.related.is_some() && (a.list_files || a.compare.is_some()),
+        message: "--related cannot be used with --list-files or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.rev.is_some() && a.url_ref.is_some(),
+        message: "--rev cannot be used with --branch; give one or the other",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.rev.is_some() && a.compare.is_some(),
+        message: "--rev cannot be used with --compare, which names its own refs",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.no_git
+                && (a.reuse_clone.is_some()
+                    || a.compare.is_some()
+                    || a.since.is_some()
+                    || a.precheck == Some(true))
+        },
+        message: "--no-git cannot be used with --reuse-clone, --compare, --since, \
+                  or --precheck, which need git",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.prune_implemented_interfaces && (a.stream || a.list_files || a.compare.is_some())
+        },
+        message: "--prune-implemented-interfaces cannot be used with --stream, --list-files, \
+                  or --compare",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.digest
+                && (a.list_files
+                    || a.output_mode.is_some()
+                    || a.compare.is_some()
+                    || a.from_file.is_some())
+        },
+        message: "--digest cannot be used with --list-files, --output-mode, --compare, \
+                  or --from-file",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| a.max_line_width.is_some() && a.no_clean,
+        message: "--max-line-width cannot be used with --no-clean",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.errors_report.is_some()
+                && (a.list_files || a.compare.is_some() || a.digest || a.split_by_dir)
+        },
+        message: "--errors-report cannot be used with --list-files, --compare, --digest, \
+                  or --split-by-dir",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.sources.len() > 1
+                && (a.list_files
+                    || a.digest
+                    || a.compare.is_some()
+                    || a.since.is_some()
+                    || a.related.is_some()
+                    || a.reuse_clone.is_some()
+                    || a.stream
+                    || a.split_by_dir
+                    || a.with_context
+                    || a.deadline.is_some()
+                    || a.output_mode == Some(OutputMode::Json))
+        },
+        message: "Several --source cannot be used with --list-files, --digest, --compare, \
+                  --since, --related, --reuse-clone, --stream, --split-by-dir, \
+                  --with-context, --deadline, or --output-mode json",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| {
+            a.split_by_source
+                && (a.errors_report.is_some()
+                    || matches!(a.output_mode, Some(OutputMode::Path | OutputMode::Content))
+                    || explicit_output_file(&a.destination).is_some())
+        },
+        message: "--split-by-source cannot be used with --errors-report, --output-mode path \
+                  or content, or a .sol destination",
+    },
+    FlagCombination {
+        severity: Severity::Error,
+        applies: |a| !a.gates.is_empty() && (a.list_files || a.compare.is_some() || a.digest),
+        message: "--gate cannot be used with --list-files, --compare, or --digest",
+    },
+];
+
+/// Checks `args` against [`FLAG_COMBINATIONS`].
 ///
-/// - Single-line comments are removed up to (but not including) the newline
-/// - Multi-line comments are removed entirely, including their delimiters
-/// - Escape sequences within strings (e.g., `\"`) are handled correctly
-/// - The output length is always less than or equal to the input length
+/// Run after parsing, once help and version requests are handled.
 ///
-/// # Examples
+/// # Returns
 ///
-/// ```rust,ignore
-/// let code = "uint256 x; // comment\nuint256 y;";
-/// let cleaned = remove_comments(code);
-/// assert_eq!(cleaned, "uint256 x; \nuint256 y;");
-/// ```
+/// The warnings of the soft combinations in use, in table order.
+///
+/// # Errors
+///
+/// Returns the message of the first hard conflict in use.
+fn check_flag_combinations(args: &Args) -> Result<Vec<String>, String> {
+    let mut warnings = Vec::new();
+    for combination in FLAG_COMBINATIONS.iter().filter(|c| (c.applies)(args)) {
+        match combination.severity {
+            Severity::Error => return Err(combination.message.to_string()),
+            Severity::Warning => warnings.push(combination.message.to_string()),
+        }
+    }
+    Ok(warnings)
+}
+
+/// Parses the comma-separated `--kinds` list, sorted and without repeats.
+///
+/// # Errors
+///
+/// Returns `"Invalid --kinds entry ..."` for an empty list or an unknown name.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(parse_kinds("libraries,contract")?, vec![Kind::Contract, Kind::Library]);
+/// ```
+fn parse_kinds(list: &str) -> Result<Vec<structure::Kind>, String> {
+    let mut kinds = list
+        .split(',')
+        .map(|name| {
+            structure::Kind::parse(name).ok_or_else(|| {
+                format!(
+                    "Invalid --kinds entry {:?} (expected contract, abstract, interface, or library)",
+                    name.trim()
+                )
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    kinds.sort();
+    kinds.dedup();
+    Ok(kinds)
+}
+
+/// Returns true if `source` is written like a remote git URL.
+///
+/// `file://` URLs are left out, since `--local` accepts them.
+fn looks_like_remote_url(source: &str) -> bool {
+    ["http://", "https://", "ssh://", "git://"]
+        .iter()
+        .any(|scheme| source.starts_with(scheme))
+        || source
+            .strip_prefix("git@")
+            .is_some_and(|rest| rest.contains(':'))
+}
+
+/// Switches `args` to `--local` when the source is an existing path rather
+/// than a URL, returning the note to log.
+///
+/// Without this, `solscrape ./contracts` would hand the path to `git clone`
+/// and fail with "repository does not exist". A source written like a URL
+/// (a scheme or `git@host:`) is never switched, even if a file of that name
+/// exists; `--no-autodetect` turns the switch off.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let mut args = parse_args_from(vec!["solscrape".into(), "./contracts".into()])?;
+/// let note = autodetect_local(&mut args);
+/// assert!(args.is_local && note.is_some());
+/// ```
+fn autodetect_local(args: &mut Args) -> Option<String> {
+    let candidate = !args.is_local
+        && !args.no_autodetect
+        && matches!(args.subcommand, None | Some(Subcommand::Check { .. }))
+        && args.reuse_clone.is_none()
+        && !args.source.is_empty()
+        && !args.source.starts_with("file://")
+        && !looks_like_remote_url(&args.source);
+    if !candidate || !Path::new(&args.source).exists() {
+        return None;
+    }
+    args.is_local = true;
+    Some(format!(
+        "{} exists on disk; treating it as a local path (--local). \
+         Use --no-autodetect to clone it as a git URL instead",
+        args.source
+    ))
+}
+
+/// Replaces a web URL source (a branch or directory page on GitHub, GitLab,
+/// or Bitbucket) with its clone URL, keeping the ref and directory it names
+/// in `args`, and returns the note to log.
+///
+/// `--branch` wins over the URL's ref, and so does `--rev`, which leaves no
+/// ref to clone.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// // solscrape https://gitlab.com/acme/sub/vault/-/tree/v2/src
+/// let note = resolve_web_url(&mut args);
+/// assert_eq!(args.source, "https://gitlab.com/acme/sub/vault");
+/// assert_eq!((args.url_ref.as_deref(), args.url_subdir.as_deref()), (Some("v2"), Some("src")));
+/// ```
+fn resolve_web_url(args: &mut Args) -> Option<String> {
+    if args.is_local || !matches!(args.subcommand, None | Some(Subcommand::Check { .. })) {
+        return None;
+    }
+    let url = forge_host::parse(&args.source);
+    url.git_ref.as_ref()?;
+    let git_ref = match &args.rev {
+        Some(_) => None,
+        None => args.url_ref.take().or(url.git_ref),
+    };
+    let mut note = format!("Reading {} as {}", args.source, url.clone_url);
+    if let Some(git_ref) = git_ref.as_ref().or(args.rev.as_ref()) {
+        note.push_str(&format!(", ref {}", git_ref));
+    }
+    if let Some(subdir) = &url.subdir {
+        note.push_str(&format!(", directory {}", subdir));
+    }
+    args.source = url.clone_url;
+    args.url_ref = git_ref;
+    args.url_subdir = url.subdir;
+    Some(note)
+}
+
+/// A group of options with its own `solscrape --help <topic>` page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HelpTopic {
+    /// Help, version, profiles, batch lists, and run-wide limits.
+    General,
+    /// Which files are scraped.
+    Filters,
+    /// What each file looks like in the output.
+    Formats,
+    /// Where the output goes and what is reported.
+    Output,
+    /// Cloning and local sources.
+    Git,
+}
+
+impl HelpTopic {
+    /// Every topic, in the order the overview lists them.
+    const ALL: [HelpTopic; 5] = [
+        HelpTopic::General,
+        HelpTopic::Filters,
+        HelpTopic::Formats,
+        HelpTopic::Output,
+        HelpTopic::Git,
+    ];
+
+    /// The name given to `--help`.
+    fn name(self) -> &'static str {
+        match self {
+            HelpTopic::General => "general",
+            HelpTopic::Filters => "filters",
+            HelpTopic::Formats => "formats",
+            HelpTopic::Output => "output",
+            HelpTopic::Git => "git",
+        }
+    }
+
+    /// The heading of the topic's page.
+    fn title(self) -> &'static str {
+        match self {
+            HelpTopic::General => "GENERAL OPTIONS",
+            HelpTopic::Filters => "FILTER OPTIONS",
+            HelpTopic::Formats => "FORMAT OPTIONS",
+            HelpTopic::Output => "OUTPUT OPTIONS",
+            HelpTopic::Git => "GIT OPTIONS",
+        }
+    }
+
+    /// The line the overview describes the topic with.
+    fn summary(self) -> &'static str {
+        match self {
+            HelpTopic::General => "Help, version, profiles, --from-file, and run-wide limits",
+            HelpTopic::Filters => "Which files are scraped: libraries, tests, pragmas, kinds",
+            HelpTopic::Formats => "How files are cleaned, annotated, and rewritten",
+            HelpTopic::Output => "Where output goes, and what is printed or logged",
+            HelpTopic::Git => "Cloning, local sources, and the checks before a clone",
+        }
+    }
+}
+
+/// The page `--help` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum HelpPage {
+    /// Usage, subcommands, and the list of topics.
+    #[default]
+    Overview,
+    /// Everything, as one page (`--help all`).
+    All,
+    /// The options of one topic.
+    Topic(HelpTopic),
+}
+
+impl HelpPage {
+    /// Parses the topic following `--help`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Unknown help topic: {name} (expected one of: ...)"`, listing
+    /// every topic and `all`.
+    fn parse(name: &str) -> Result<Self, String> {
+        if name == "all" {
+            return Ok(HelpPage::All);
+        }
+        HelpTopic::ALL
+            .into_iter()
+            .find(|topic| topic.name() == name)
+            .map(HelpPage::Topic)
+            .ok_or_else(|| {
+                let names: Vec<&str> = HelpTopic::ALL.iter().map(|t| t.name()).collect();
+                format!(
+                    "Unknown help topic: {} (expected one of: {}, all)",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// One option's entry in the help text.
+struct OptionHelp {
+    /// The flags and value, as in `-o, --output <NAME>`.
+    usage: &'static str,
+    /// The `--help` page the option is listed on.
+    topic: HelpTopic,
+    /// The description, one string per line, already wrapped.
+    text: &'static [&'static str],
+}
+
+/// The column option descriptions start at; a longer usage gets a line of
+/// its own.
+const HELP_TEXT_COLUMN: usize = 27;
+
+/// Every flag [`parse_args_onto`] accepts, in the order it matches them;
+/// anything else starting with `-` is an unknown option. `test_parse_flags`
+/// checks that each is parsed and `test_help_topics` that each is
+/// documented.
+const FLAGS: [&str; 90] = [
+    "-h",
+    "--help",
+    "-v",
+    "--version",
+    "--version-full",
+    "-l",
+    "--local",
+    "--no-autodetect",
+    "-q",
+    "--quiet",
+    "--verbose",
+    "--ascii",
+    "--include-lib",
+    "--offline",
+    "--no-git",
+    "--foundry-cache",
+    "--include-test",
+    "--include-script",
+    "--force-include",
+    "--no-headers",
+    "--no-headers-for",
+    "--stats",
+    "--stats-depth",
+    "--run-dir",
+    "--split-by-dir",
+    "--stream",
+    "--dedupe-content",
+    "--prune-implemented-interfaces",
+    "--vcs-friendly",
+    "--explain",
+    "--list-files",
+    "--digest",
+    "--output-mode",
+    "-0",
+    "--null",
+    "--strict",
+    "--gate",
+    "--no-subprocess",
+    "--precheck",
+    "--no-precheck",
+    "-y",
+    "--yes",
+    "--inject-pragma",
+    "--min-solidity",
+    "--filter-pragma",
+    "--pragma-unknown",
+    "--compare",
+    "--since",
+    "--related",
+    "--reuse-clone",
+    "--trust-clone",
+    "--allow-dirty",
+    "--profile",
+    "--kinds",
+    "--flag-todos",
+    "--checksum-file",
+    "--parts-file",
+    "--errors-report",
+    "--metrics-file",
+    "--metrics-stdout",
+    "--with-context",
+    "--context-readme-limit",
+    "--keep-license-headers",
+    "--no-clean",
+    "--rewrap-long-lines",
+    "--max-brace-depth",
+    "--strip-revert-strings",
+    "--max-line-width",
+    "-o",
+    "--output",
+    "--log-file",
+    "--temp-dir",
+    "--min-temp-space",
+    "--max-clone-size",
+    "--deadline",
+    "--older-than",
+    "--all",
+    "--mtime-from",
+    "--against",
+    "-b",
+    "--branch",
+    "--token",
+    "--retries",
+    "--timeout",
+    "--rev",
+    "--subdir",
+    "--resume",
+    "--from-file",
+    "--source",
+    "--split-by-source",
+];
+
+/// Every option, in the order `--help all` lists them. Each is on exactly
+/// one topic's page; `test_help_topics` checks that every flag in [`FLAGS`]
+/// is here or in the subcommand usage.
+const OPTIONS_HELP: &[OptionHelp] = &[
+    OptionHelp {
+        usage: "-o, --output <NAME>",
+        topic: HelpTopic::Output,
+        text: &[
+            "Custom output filename (without _scraped.sol suffix;",
+            "a name ending in .sol is used as it is); a path, such as",
+            "/abs/dir/NAME or ~/dir/NAME, replaces the destination",
+        ],
+    },
+    OptionHelp {
+        usage: "-b, --branch <NAME>",
+        topic: HelpTopic::Git,
+        text: &["Clone this branch or tag instead of the default branch"],
+    },
+    OptionHelp {
+        usage: "--rev <REV>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Check out this commit, tag, or branch instead of the",
+            "default branch; the summary shows its full hash",
+        ],
+    },
+    OptionHelp {
+        usage: "--token <TOKEN>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Clone private HTTPS GitHub and GitLab repositories with",
+            "this access token, over $SOLSCRAPE_GIT_TOKEN and",
+            "$GITHUB_TOKEN; it is never printed",
+        ],
+    },
+    OptionHelp {
+        usage: "--timeout <DURATION>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Kill a clone attempt still running after DURATION",
+            "(e.g. 60 or 5m); no limit by default",
+        ],
+    },
+    OptionHelp {
+        usage: "--retries <N>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Retry a clone that failed for a network reason up to N",
+            "times, waiting 1s, 2s, 4s, ... in between (default 2)",
+        ],
+    },
+    OptionHelp {
+        usage: "--source <SOURCE>",
+        topic: HelpTopic::General,
+        text: &[
+            "Scrape SOURCE; repeat to scrape several into one output,",
+            "each under its name, and then the only argument is the",
+            "destination. A source that fails is reported and the rest",
+            "still scraped, unless --strict",
+        ],
+    },
+    OptionHelp {
+        usage: "--from-file <LIST>",
+        topic: HelpTopic::General,
+        text: &[
+            "Scrape every source listed in LIST, one per line, each",
+            "optionally followed by its own options (which win over",
+            "the command line's); # starts a comment",
+        ],
+    },
+    OptionHelp {
+        usage: "--resume",
+        topic: HelpTopic::General,
+        text: &[
+            "With --from-file, skip the sources an interrupted or",
+            "partly failed run already scraped",
+        ],
+    },
+    OptionHelp {
+        usage: "-l, --local",
+        topic: HelpTopic::Git,
+        text: &[
+            "Treat source as a local directory path (implied when",
+            "the source exists on disk and is not a URL)",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-autodetect",
+        topic: HelpTopic::Git,
+        text: &[
+            "Treat a source that exists on disk as a git URL",
+            "unless --local is given",
+        ],
+    },
+    OptionHelp {
+        usage: "--include-lib",
+        topic: HelpTopic::Filters,
+        text: &["Include lib/ and Soldeer dependencies/ packages"],
+    },
+    OptionHelp {
+        usage: "--offline",
+        topic: HelpTopic::Git,
+        text: &[
+            "With --include-lib, fill a clone's submodules from",
+            "the --foundry-cache checkouts without fetching them",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-git",
+        topic: HelpTopic::Git,
+        text: &[
+            "Download a GitHub source as a tarball with curl or",
+            "wget and tar instead of cloning it (done anyway",
+            "when git is not installed)",
+        ],
+    },
+    OptionHelp {
+        usage: "--foundry-cache <PATH>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Where to look for checkouts of submodules that",
+            "cannot be fetched (default: ~/.foundry)",
+        ],
+    },
+    OptionHelp {
+        usage: "--include-test",
+        topic: HelpTopic::Filters,
+        text: &["Include test/ files"],
+    },
+    OptionHelp {
+        usage: "--include-script",
+        topic: HelpTopic::Filters,
+        text: &["Include script/ files"],
+    },
+    OptionHelp {
+        usage: "--force-include <PATH>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Scrape this file (relative to the source root) even",
+            "inside an excluded directory; repeatable",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-headers",
+        topic: HelpTopic::Formats,
+        text: &["Omit file separator headers in output"],
+    },
+    OptionHelp {
+        usage: "--no-headers-for <GLOB>",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Give files matching GLOB (e.g. lib, **/mocks) a one-line",
+            "header instead of the framed one; repeatable",
+        ],
+    },
+    OptionHelp {
+        usage: "--stats",
+        topic: HelpTopic::Output,
+        text: &["Print per-file comment/blank-line statistics"],
+    },
+    OptionHelp {
+        usage: "--stats-depth <N>",
+        topic: HelpTopic::Output,
+        text: &["Directory levels of the --stats rollup (default: 2)"],
+    },
+    OptionHelp {
+        usage: "--profile <NAME>",
+        topic: HelpTopic::General,
+        text: &[
+            "Apply a bundle of options before any explicit flag:",
+            "  audit   keep file headers and license/SPDX blocks",
+            "  llm     strip comments, license blocks, and revert",
+            "          reasons, and re-wrap minified files",
+            "          instead of skipping",
+            "  verify  copy files verbatim (--no-clean), no",
+            "          license or pragma additions",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-clean",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Copy files verbatim instead of removing comments",
+            "and empty lines",
+        ],
+    },
+    OptionHelp {
+        usage: "--keep-license-headers",
+        topic: HelpTopic::Formats,
+        text: &["Keep a file's leading copyright/license comment block"],
+    },
+    OptionHelp {
+        usage: "--with-context",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Append README.md, foundry.toml, remappings.txt, and",
+            "package.json's name/version/dependencies from the",
+            "repository root, as comments after the Solidity code",
+        ],
+    },
+    OptionHelp {
+        usage: "--context-readme-limit",
+        topic: HelpTopic::Formats,
+        text: &[
+            "<SIZE>",
+            "Bytes of README.md kept by --with-context, e.g. 16K",
+            "(default: 8K)",
+        ],
+    },
+    OptionHelp {
+        usage: "--rewrap-long-lines",
+        topic: HelpTopic::Formats,
+        text: &["Re-wrap minified files at statement boundaries instead of skipping them"],
+    },
+    OptionHelp {
+        usage: "--max-line-width <N>",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Re-wrap lines wider than N chars after commas inside",
+            "parentheses, before `returns`, and after `{`",
+        ],
+    },
+    OptionHelp {
+        usage: "--max-brace-depth <N>",
+        topic: HelpTopic::Filters,
+        text: &["Skip files whose braces nest deeper than N (default 1000)"],
+    },
+    OptionHelp {
+        usage: "--strip-revert-strings",
+        topic: HelpTopic::Formats,
+        text: &["Replace require/revert reason strings with \"e\""],
+    },
+    OptionHelp {
+        usage: "--split-by-dir",
+        topic: HelpTopic::Output,
+        text: &[
+            "Write one file per top-level source directory:",
+            "<DIR>_scraped.sol, or <NAME>_<DIR>_scraped.sol with",
+            "-o (files at the root: root_scraped.sol)",
+        ],
+    },
+    OptionHelp {
+        usage: "--split-by-source",
+        topic: HelpTopic::Output,
+        text: &[
+            "With several --source, write one file per source:",
+            "<NAME>_scraped.sol, or <OUT>_<NAME>_scraped.sol with -o",
+        ],
+    },
+    OptionHelp {
+        usage: "--run-dir",
+        topic: HelpTopic::Output,
+        text: &[
+            "Write into destination/<YYYY-MM-DD_HHMMSS>/ and update",
+            "a `latest` pointer in the destination; with --from-file,",
+            "one run directory holds every source",
+        ],
+    },
+    OptionHelp {
+        usage: "--dedupe-content",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Keep only the first path of each group of files whose",
+            "cleaned code is the same (whitespace aside)",
+        ],
+    },
+    OptionHelp {
+        usage: "--prune-implemented-interfaces",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Leave out files declaring only interfaces that an",
+            "included contract implements (names its `is` clause)",
+        ],
+    },
+    OptionHelp {
+        usage: "--stream",
+        topic: HelpTopic::Output,
+        text: &[
+            "Write files in discovery order as they are found",
+            "(lower memory, unsorted output)",
+        ],
+    },
+    OptionHelp {
+        usage: "--vcs-friendly",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Normalize whitespace (LF, spaces for leading tabs, no",
+            "trailing blanks) and report per-file line changes",
+            "versus the previous output on stderr",
+        ],
+    },
+    OptionHelp {
+        usage: "--checksum-file",
+        topic: HelpTopic::Output,
+        text: &[
+            "Also write <output>.sha256 with the output's SHA-256",
+            "digest, in sha256sum format (see verify)",
+        ],
+    },
+    OptionHelp {
+        usage: "--parts-file",
+        topic: HelpTopic::Output,
+        text: &[
+            "Also write <output>.parts with the byte range of each",
+            "file's part, which extract seeks to",
+        ],
+    },
+    OptionHelp {
+        usage: "--errors-report <FORMAT>",
+        topic: HelpTopic::Output,
+        text: &[
+            "Also write <output>.errors.txt (text) or .errors.json",
+            "(json) listing every custom error and every distinct",
+            "require/revert reason, with counts and where they appear",
+        ],
+    },
+    OptionHelp {
+        usage: "--metrics-file <PATH>",
+        topic: HelpTopic::Output,
+        text: &[
+            "Append this run's metrics (clone_ms, files_processed,",
+            "files_skipped, bytes_written, total_ms, success) as",
+            "`solscrape.<key> <value> <unix time>` lines",
+        ],
+    },
+    OptionHelp {
+        usage: "--metrics-stdout",
+        topic: HelpTopic::Output,
+        text: &["Print the same metrics lines on stdout at the end"],
+    },
+    OptionHelp {
+        usage: "--list-files",
+        topic: HelpTopic::Output,
+        text: &[
+            "Print the relative paths that would be included, one",
+            "per line, and write nothing (exit 4 if none match)",
+        ],
+    },
+    OptionHelp {
+        usage: "--digest",
+        topic: HelpTopic::Output,
+        text: &[
+            "Print one line per included file (lines, declarations,",
+            "pragma, assembly and TODO flags) and write nothing;",
+            "tab-separated with --quiet",
+        ],
+    },
+    OptionHelp {
+        usage: "-0, --null",
+        topic: HelpTopic::Output,
+        text: &["With --list-files, end entries with NUL instead of newline"],
+    },
+    OptionHelp {
+        usage: "--compare <OLD>..<NEW>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Clone once and write both versions of only the files",
+            "whose cleaned code differs between two refs",
+        ],
+    },
+    OptionHelp {
+        usage: "--since <REF>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Only scrape files changed since REF (git diff",
+            "REF...HEAD), deepening a shallow clone as needed",
+        ],
+    },
+    OptionHelp {
+        usage: "--subdir <PATH>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Only scrape files under this directory of the source;",
+            "headers stay relative to the source root",
+        ],
+    },
+    OptionHelp {
+        usage: "--related <PATH>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Only scrape this file (relative to the source root),",
+            "the files it imports, and the files importing it,",
+            "transitively; headers note which each file is",
+        ],
+    },
+    OptionHelp {
+        usage: "--reuse-clone <PATH>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Fetch the source URL into this existing clone and",
+            "scrape it in place instead of cloning to a temp dir",
+        ],
+    },
+    OptionHelp {
+        usage: "--trust-clone",
+        topic: HelpTopic::Git,
+        text: &[
+            "Skip checking that the --reuse-clone origin is the",
+            "source URL",
+        ],
+    },
+    OptionHelp {
+        usage: "--allow-dirty",
+        topic: HelpTopic::Git,
+        text: &["Let --reuse-clone use a clone with uncommitted changes"],
+    },
+    OptionHelp {
+        usage: "--inject-pragma <VERSION>",
+        topic: HelpTopic::Formats,
+        text: &["Add `pragma solidity <VERSION>;` to files that have none"],
+    },
+    OptionHelp {
+        usage: "--filter-pragma <CONSTRAINT>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Include only files whose pragma allows a version in",
+            "CONSTRAINT, e.g. 0.8, ^0.8.0, \">=0.7 <0.9\"",
+        ],
+    },
+    OptionHelp {
+        usage: "--min-solidity <VERSION>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Skip files whose pragma only allows compilers older",
+            "than VERSION, e.g. 0.6",
+        ],
+    },
+    OptionHelp {
+        usage: "--kinds <LIST>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "Include only files declaring one of these kinds:",
+            "contract, abstract, interface, library (comma-",
+            "separated); mixed files are kept with a note",
+        ],
+    },
+    OptionHelp {
+        usage: "--pragma-unknown <include|exclude>",
+        topic: HelpTopic::Filters,
+        text: &[
+            "With --filter-pragma or --min-solidity, keep or skip",
+            "files that have no pragma solidity (default: include)",
+        ],
+    },
+    OptionHelp {
+        usage: "--flag-todos",
+        topic: HelpTopic::Formats,
+        text: &[
+            "Report TODO, FIXME, XXX, and HACK markers left in the",
+            "output (in strings, kept headers, or with --no-clean)",
+        ],
+    },
+    OptionHelp {
+        usage: "--gate <CONDITION>",
+        topic: HelpTopic::General,
+        text: &[
+            "After a successful scrape, exit with code 8 if CONDITION",
+            "fails: no-skips, no-license:<SPDX-ID>, no-missing-imports,",
+            "or max-lines:<N>; repeat for several",
+        ],
+    },
+    OptionHelp {
+        usage: "--strict",
+        topic: HelpTopic::General,
+        text: &[
+            "Fail instead of working around recoverable problems",
+            "(e.g. split paths that differ only by case), when a",
+            "file is skipped with its code lost (binary, an LFS",
+            "pointer, not UTF-8, long lines, unreadable), on any",
+            "marker --flag-todos finds, and at the first --source",
+            "that fails",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-subprocess",
+        topic: HelpTopic::General,
+        text: &[
+            "Refuse to run external programs other than those the",
+            "flags require (only git, and only to clone)",
+        ],
+    },
+    OptionHelp {
+        usage: "--precheck",
+        topic: HelpTopic::Git,
+        text: &[
+            "List the remote tree before cloning and ask before",
+            "scraping a repository with no .sol files (default",
+            "for GitHub HTTPS URLs unless --quiet)",
+        ],
+    },
+    OptionHelp {
+        usage: "--no-precheck",
+        topic: HelpTopic::Git,
+        text: &["Clone without looking at the remote tree first"],
+    },
+    OptionHelp {
+        usage: "-y, --yes",
+        topic: HelpTopic::Git,
+        text: &[
+            "Clone without asking when the pre-check finds no",
+            ".sol files",
+        ],
+    },
+    OptionHelp {
+        usage: "-q, --quiet",
+        topic: HelpTopic::Output,
+        text: &[
+            "Suppress progress output (only print result path; with",
+            "--from-file, one SOURCE<TAB>ok|failed|skipped<TAB>PATH",
+            "or error line per source, in list order)",
+        ],
+    },
+    OptionHelp {
+        usage: "--output-mode <path|content|json>",
+        topic: HelpTopic::Output,
+        text: &[
+            "Print only the output path, the consolidated content,",
+            "or a one-line JSON summary on stdout; everything",
+            "else goes to stderr",
+        ],
+    },
+    OptionHelp {
+        usage: "--explain",
+        topic: HelpTopic::Output,
+        text: &[
+            "With --output-mode json, list every .sol file in the",
+            "tree with the rule that included or excluded it",
+        ],
+    },
+    OptionHelp {
+        usage: "--verbose",
+        topic: HelpTopic::Output,
+        text: &["Print detailed diagnostics (e.g. raw git output)"],
+    },
+    OptionHelp {
+        usage: "--ascii",
+        topic: HelpTopic::Output,
+        text: &[
+            "Use ASCII for the banner, summary, and file header",
+            "separators (the banner and summary default to ASCII",
+            "when the locale is not UTF-8)",
+        ],
+    },
+    OptionHelp {
+        usage: "--log-file <PATH>",
+        topic: HelpTopic::Output,
+        text: &["Append a detailed, timestamped run log to PATH"],
+    },
+    OptionHelp {
+        usage: "--deadline <DURATION>",
+        topic: HelpTopic::General,
+        text: &[
+            "Stop processing after DURATION (e.g. 300 or 5m) and",
+            "write a partial output, exiting with code 3; the",
+            "clone is killed if it runs past the deadline",
+        ],
+    },
+    OptionHelp {
+        usage: "--temp-dir <DIR>",
+        topic: HelpTopic::Git,
+        text: &["Clone into DIR (default: $SOLSCRAPE_TMPDIR or system temp)"],
+    },
+    OptionHelp {
+        usage: "--min-temp-space <SIZE>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Free space required in the temp directory before",
+            "cloning, e.g. 2G (default: 256M; 0 disables)",
+        ],
+    },
+    OptionHelp {
+        usage: "--max-clone-size <SIZE>",
+        topic: HelpTopic::Git,
+        text: &[
+            "Ask before cloning a GitHub or GitLab repository",
+            "whose host reports it larger than SIZE, e.g. 1G;",
+            "without a terminal, refuse (default: no limit, with",
+            "a warning above 1G)",
+        ],
+    },
+    OptionHelp {
+        usage: "-h, --help [TOPIC]",
+        topic: HelpTopic::General,
+        text: &[
+            "Show this overview, or the options of one topic:",
+            "general, filters, formats, output, git, or all",
+        ],
+    },
+    OptionHelp {
+        usage: "-v, --version",
+        topic: HelpTopic::General,
+        text: &["Show version (with --verbose: build information)"],
+    },
+    OptionHelp {
+        usage: "--version-full",
+        topic: HelpTopic::General,
+        text: &["Show version and build information"],
+    },
+];
+
+/// Usage, arguments, and subcommands, shared by the overview and `--help all`.
+const HELP_USAGE: &str = "USAGE:
+    solscrape [OPTIONS] <source> [destination]
+    solscrape [OPTIONS] --from-file <LIST> [destination]
+    solscrape [OPTIONS] --source <SOURCE>... [destination]
+    solscrape split <scraped.sol> <out-dir> [--mtime-from <DIR>]
+    solscrape extract <scraped.sol> <relative/path>
+    solscrape verify <scraped.sol>
+    solscrape check [OPTIONS] <source> --against <scraped.sol>
+    solscrape cleanup-temp [--older-than <AGE> | --all]
+    solscrape config path
+
+ARGUMENTS:
+    <source>        Git repository URL or local directory path (with --local)
+    [destination]   Output directory (default: current directory), or an
+                    output file path ending in .sol
+
+SUBCOMMANDS:
+    split <scraped.sol> <out-dir>
+                           Recreate the source tree from a consolidated file
+                           produced with headers; --mtime-from <DIR> gives each
+                           file the modification time of its source under DIR
+    extract <scraped.sol> <relative/path>
+                           Print one file's section of a consolidated file
+    verify <scraped.sol>   Check a consolidated file against the .sha256 file
+                           --checksum-file wrote next to it
+    check <source> --against <scraped.sol>
+                           Scrape the source in memory with the given options
+                           and exit 7, naming the files that differ, unless
+                           it matches the committed consolidated file
+    cleanup-temp           Remove temporary clones left behind by killed runs
+                           (older than 1h by default; --older-than 30m, --all),
+                           skipping those of runs still in progress
+    config path            Print the configuration files consulted, user
+                           then project, and where each setting came from
+
+";
+
+/// The closing examples of the overview and `--help all`.
+const HELP_EXAMPLES: &str = "EXAMPLES:
+    solscrape https://github.com/clober-dex/v2-core.git
+    solscrape https://github.com/OpenZeppelin/openzeppelin-contracts.git ./output
+    solscrape https://github.com/uniswap/v3-core.git -o uniswap_v3
+    solscrape ./my-local-project --local -o my_contracts
+    solscrape https://github.com/example/repo.git --include-lib --include-test
+";
+
+/// Renders the options of `topic`, or all of them, in [`OPTIONS_HELP`] order.
+fn render_options(topic: Option<HelpTopic>) -> String {
+    let indent = " ".repeat(HELP_TEXT_COLUMN);
+    let mut out = String::new();
+    for option in OPTIONS_HELP {
+        if topic.is_some_and(|topic| topic != option.topic) {
+            continue;
+        }
+        let mut text = option.text.iter();
+        let usage = format!("    {}", option.usage);
+        match text.next() {
+            Some(first) if usage.len() < HELP_TEXT_COLUMN => {
+                out.push_str(&format!(
+                    "{:<width$}{}\n",
+                    usage,
+                    first,
+                    width = HELP_TEXT_COLUMN
+                ));
+            }
+            first => {
+                out.push_str(&format!("{}\n", usage));
+                out.extend(first.map(|line| format!("{}{}\n", indent, line)));
+            }
+        }
+        for line in text {
+            out.push_str(&format!("{}{}\n", indent, line));
+        }
+    }
+    out
+}
+
+/// Renders the help `page`.
+///
+/// The overview lists the usage, the subcommands, and the topics; a topic
+/// page lists its options; `--help all` is everything, as one page.
+fn render_help(page: HelpPage, symbols: &Symbols) -> String {
+    let mut out = format!("\n{}\n", render_banner(VERSION, symbols));
+    match page {
+        HelpPage::Overview => {
+            out.push_str(HELP_USAGE);
+            out.push_str("HELP TOPICS:\n");
+            for topic in HelpTopic::ALL {
+                out.push_str(&format!(
+                    "    solscrape --help {:<9}{}\n",
+                    topic.name(),
+                    topic.summary()
+                ));
+            }
+            out.push_str("    solscrape --help all      Every option on one page\n\n");
+            out.push_str(HELP_EXAMPLES);
+        }
+        HelpPage::All => {
+            out.push_str(HELP_USAGE);
+            out.push_str("OPTIONS:\n");
+            out.push_str(&render_options(None));
+            out.push('\n');
+            out.push_str(HELP_EXAMPLES);
+        }
+        HelpPage::Topic(topic) => {
+            out.push_str(&format!(
+                "{}:\n{}\nSee solscrape --help for usage and the other topics.\n",
+                topic.title(),
+                render_options(Some(topic))
+            ));
+        }
+    }
+    out
+}
+
+/// Prints the help `page` (see [`render_help`]).
+fn print_help(page: HelpPage, symbols: &Symbols) {
+    println!("{}", render_help(page, symbols));
+}
+
+/// Prints the version string, with build information when `full` is set.
+fn print_version(full: bool) {
+    print!("{}", render_version(full));
+}
+
+/// Renders the version output.
+///
+/// Plain output is exactly `solscrape {VERSION}\n` so scripts parsing it keep
+/// working. Full output adds the target triple, rustc version, and build
+/// profile recorded by `build.rs`, plus the default scraping settings.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(render_version(false), "solscrape 1.0.0\n");
+/// ```
+fn render_version(full: bool) -> String {
+    let mut out = format!("solscrape {}\n", VERSION);
+    if !full {
+        return out;
+    }
+
+    let unknown = "unknown";
+    let excluded =
+        exclusion_policy(&project::ProjectKind::default(), &Args::default()).excluded_dir_names();
+
+    let rows = [
+        (
+            "target",
+            option_env!("SOLSCRAPE_BUILD_TARGET")
+                .unwrap_or(unknown)
+                .to_string(),
+        ),
+        (
+            "rustc",
+            option_env!("SOLSCRAPE_BUILD_RUSTC")
+                .unwrap_or(unknown)
+                .to_string(),
+        ),
+        (
+            "profile",
+            option_env!("SOLSCRAPE_BUILD_PROFILE")
+                .unwrap_or(unknown)
+                .to_string(),
+        ),
+        ("excluded dirs", excluded.join(", ")),
+        ("headers", "on (disable with --no-headers)".to_string()),
+        (
+            "long lines",
+            format!(
+                "files over {} chars per line are skipped",
+                LONG_LINE_THRESHOLD
+            ),
+        ),
+        (
+            "min temp space",
+            space::format_size(space::DEFAULT_MIN_TEMP_SPACE),
+        ),
+    ];
+    for (label, value) in rows {
+        out.push_str(&format!("{:<15} {}\n", format!("{}:", label), value));
+    }
+    out
+}
+
+/// Prints the decorative ASCII banner with version information.
+///
+/// Displayed at startup in non-quiet mode to provide visual context, on
+/// stderr when stdout is reserved by `--output-mode`.
+fn print_banner(stderr_only: bool, symbols: &Symbols) {
+    let banner = format!("\n{}\n", render_banner(VERSION, symbols));
+    if stderr_only {
+        eprint!("{}", banner);
+    } else {
+        print!("{}", banner);
+    }
+}
+
+/// Renders the boxed `SOLSCRAPE v{version}` banner.
+///
+/// The title is centered inside a box whose inner width is at least
+/// [`BANNER_MIN_WIDTH`] chars and grows to fit longer version strings, so
+/// every line has the same display width regardless of `version`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let banner = render_banner("2.0.0-rc.1", &symbols::UNICODE);
+/// assert!(banner.contains("SOLSCRAPE v2.0.0-rc.1"));
+/// ```
+fn render_banner(version: &str, symbols: &Symbols) -> String {
+    render_box(
+        &format!("SOLSCRAPE v{}  -  Solidity Scraper", version),
+        symbols,
+    )
+}
+
+/// Draws a single-line box around `content`, centering it within the box.
+///
+/// Widths are measured in chars rather than bytes so that multi-byte
+/// box-drawing characters and non-ASCII content line up correctly.
+fn render_box(content: &str, symbols: &Symbols) -> String {
+    let content_width = content.chars().count();
+    let inner_width = BANNER_MIN_WIDTH.max(content_width + 4);
+    let left = (inner_width - content_width) / 2;
+    let right = inner_width - content_width - left;
+    let rule = symbols.box_horizontal.to_string().repeat(inner_width);
+    let [top_left, top_right, bottom_left, bottom_right] = symbols.box_corners;
+    let edge = symbols.box_vertical;
+
+    format!(
+        "{top_left}{rule}{top_right}\n{edge}{}{content}{}{edge}\n{bottom_left}{rule}{bottom_right}\n",
+        " ".repeat(left),
+        " ".repeat(right),
+    )
+}
+
+// ============================================================================
+// Solidity Parser - Comment Removal
+// ============================================================================
+
+/// The current parsing context within Solidity source code.
+///
+/// Used by [`remove_comments`] to track whether the parser is inside a string
+/// literal, comment block, or normal code context. This enables correct handling
+/// of comment-like syntax within strings (e.g., `"// not a comment"`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ParserState {
+    /// Normal code context; comments and strings may begin.
+    Normal,
+    /// Inside a double-quoted string literal (`"..."`).
+    InStringDouble,
+    /// Inside a single-quoted string literal (`'...'`).
+    InStringSingle,
+    /// Inside a single-line comment (`// ...`).
+    InSingleComment,
+    /// Inside a multi-line comment (`/* ... */`).
+    InMultiComment,
+}
+
+/// Counters describing what cleaning removed from a single file.
+///
+/// Produced by [`remove_comments`] and [`remove_empty_lines`] and combined by
+/// [`clean_solidity`]. Comment-like text inside string literals is
+/// code, never comment, so it is not counted here.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct CleanStats {
+    /// Lines in the original source.
+    original_lines: usize,
+    /// Size of the original source in bytes.
+    original_bytes: usize,
+    /// Original lines containing only comment text (and whitespace).
+    comment_lines: usize,
+    /// Original lines that were empty or whitespace-only.
+    blank_lines: usize,
+    /// Bytes of comment text removed, including delimiters.
+    comment_bytes: usize,
+    /// Lines dropped by [`remove_empty_lines`] after comment removal.
+    empty_lines_removed: usize,
+    /// Lines remaining after cleaning.
+    cleaned_lines: usize,
+    /// Chars saved by `--strip-revert-strings`.
+    revert_chars_saved: usize,
+    /// Top-level declarations of the kept code, by kind.
+    declarations: structure::KindCounts,
+}
+
+impl CleanStats {
+    /// Adds every counter of `other` to `self`.
+    fn add(&mut self, other: &CleanStats) {
+        self.original_lines += other.original_lines;
+        self.original_bytes += other.original_bytes;
+        self.comment_lines += other.comment_lines;
+        self.blank_lines += other.blank_lines;
+        self.comment_bytes += other.comment_bytes;
+        self.empty_lines_removed += other.empty_lines_removed;
+        self.cleaned_lines += other.cleaned_lines;
+        self.revert_chars_saved += other.revert_chars_saved;
+        self.declarations.add(&other.declarations);
+    }
+
+    /// The share of the original bytes that were comments, from `0.0` to `1.0`.
+    fn documentation_ratio(&self) -> f64 {
+        if self.original_bytes == 0 {
+            0.0
+        } else {
+            self.comment_bytes as f64 / self.original_bytes as f64
+        }
+    }
+}
+
+/// Classifies original source lines while [`remove_comments`] scans them.
+#[derive(Default)]
+struct LineTally {
+    has_code: bool,
+    has_comment: bool,
+    open: bool,
+}
+
+impl LineTally {
+    /// Records a char of the current line that belongs to code or a string.
+    fn code(&mut self, c: char) {
+        self.open = true;
+        if !c.is_whitespace() {
+            self.has_code = true;
+        }
+    }
+
+    /// Records a char of the current line that belongs to a comment.
+    fn comment(&mut self, stats: &mut CleanStats, c: char) {
+        self.open = true;
+        self.has_comment = true;
+        stats.comment_bytes += c.len_utf8();
+    }
+
+    /// Closes the current line, classifying it into `stats`.
+    fn end_line(&mut self, stats: &mut CleanStats) {
+        stats.original_lines += 1;
+        if !self.has_code {
+            if self.has_comment {
+                stats.comment_lines += 1;
+            } else {
+                stats.blank_lines += 1;
+            }
+        }
+        *self = Self::default();
+    }
+}
+
+/// Removes all comments from Solidity source code while preserving string literals.
+///
+/// Use this function to strip both single-line (`//`) and multi-line (`/* */`)
+/// comments from Solidity code. String literals containing comment-like syntax
+/// are correctly preserved. Alongside the cleaned code, returns [`CleanStats`]
+/// with the original line classification and the number of comment bytes removed.
+///
+/// # Behavioral Contract
+///
+/// - Single-line comments are removed up to (but not including) the newline
+/// - `\n`, `\r\n`, and a bare `\r` (old Mac line endings) all end a line; a
+///   bare `\r` is emitted as `\n`
+/// - Multi-line comments are removed entirely, including their delimiters
+/// - Escape sequences within strings (e.g., `\"`) are handled correctly
+/// - The output length is always less than or equal to the input length
+/// - `comment_bytes` equals the input length minus the output length
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let code = "uint256 x; // comment\nuint256 y;";
+/// let (cleaned, stats) = remove_comments(code);
+/// assert_eq!(cleaned, "uint256 x; \nuint256 y;");
+/// assert_eq!(stats.comment_bytes, 10);
+/// ```
 ///
 /// String literals are preserved:
 ///
 /// ```rust,ignore
 /// let code = r#"string s = "// not a comment";"#;
-/// let cleaned = remove_comments(code);
+/// let (cleaned, _) = remove_comments(code);
 /// assert!(cleaned.contains("// not a comment"));
 /// ```
-fn remove_comments(code: &str) -> String {
+fn remove_comments(code: &str) -> (String, CleanStats) {
     let chars: Vec<char> = code.chars().collect();
     let mut result = String::with_capacity(code.len());
+    let mut stats = CleanStats {
+        original_bytes: code.len(),
+        ..CleanStats::default()
+    };
+    let mut line = LineTally::default();
     let mut state = ParserState::Normal;
     let mut i = 0;
 
     while i < chars.len() {
+        if is_line_break(&chars, i) {
+            if state == ParserState::InMultiComment {
+                stats.comment_bytes += 1;
+            } else {
+                result.push('\n');
+            }
+            if state == ParserState::InSingleComment {
+                // Keep the newline, exit comment
+                state = ParserState::Normal;
+            }
+            line.end_line(&mut stats);
+            i += 1;
+            continue;
+        }
+
         match state {
             ParserState::Normal => {
                 // Check for string start
                 if chars[i] == '"' {
                     state = ParserState::InStringDouble;
+                    line.code(chars[i]);
                     result.push(chars[i]);
                     i += 1;
                 } else if chars[i] == '\'' {
                     state = ParserState::InStringSingle;
+                    line.code(chars[i]);
                     result.push(chars[i]);
                     i += 1;
                 }
                 // Check for single-line comment
                 else if i + 1 < chars.len() && chars[i] == '/' && chars[i + 1] == '/' {
                     state = ParserState::InSingleComment;
+                    line.comment(&mut stats, '/');
+                    line.comment(&mut stats, '/');
                     i += 2;
                 }
                 // Check for multi-line comment
                 else if i + 1 < chars.len() && chars[i] == '/' && chars[i + 1] == '*' {
                     state = ParserState::InMultiComment;
+                    line.comment(&mut stats, '/');
+                    line.comment(&mut stats, '*');
                     i += 2;
                 }
                 // Normal character
                 else {
+                    line.code(chars[i]);
                     result.push(chars[i]);
                     i += 1;
                 }
             }
 
-            ParserState::InStringDouble => {
-                result.push(chars[i]);
-                // Handle escape sequences
-                if chars[i] == '\\' && i + 1 < chars.len() {
-                    i += 1;
-                    result.push(chars[i]);
-                    i += 1;
-                } else if chars[i] == '"' {
-                    state = ParserState::Normal;
-                    i += 1;
+            ParserState::InStringDouble | ParserState::InStringSingle => {
+                let quote = if state == ParserState::InStringDouble {
+                    '"'
                 } else {
-                    i += 1;
-                }
-            }
-
-            ParserState::InStringSingle => {
+                    '\''
+                };
+                line.code(chars[i]);
                 result.push(chars[i]);
                 // Handle escape sequences
                 if chars[i] == '\\' && i + 1 < chars.len() {
                     i += 1;
+                    if is_line_break(&chars, i) {
+                        // An escaped line continuation still ends the source line
+                        continue;
+                    }
+                    line.code(chars[i]);
                     result.push(chars[i]);
                     i += 1;
-                } else if chars[i] == '\'' {
+                } else if chars[i] == quote {
                     state = ParserState::Normal;
                     i += 1;
                 } else {
@@ -364,54 +3145,80 @@ fn remove_comments(code: &str) -> String {
             }
 
             ParserState::InSingleComment => {
-                if chars[i] == '\n' {
-                    // Keep the newline, exit comment
-                    result.push('\n');
-                    state = ParserState::Normal;
-                }
+                line.comment(&mut stats, chars[i]);
                 i += 1;
             }
 
             ParserState::InMultiComment => {
                 if i + 1 < chars.len() && chars[i] == '*' && chars[i + 1] == '/' {
                     state = ParserState::Normal;
+                    line.comment(&mut stats, '*');
+                    line.comment(&mut stats, '/');
                     i += 2;
                 } else {
+                    line.comment(&mut stats, chars[i]);
                     i += 1;
                 }
             }
         }
     }
 
-    result
+    if line.open {
+        line.end_line(&mut stats);
+    }
+
+    (result, stats)
+}
+
+/// Returns true if `chars[i]` ends a line: a `\n`, or a `\r` not followed by
+/// one (the `\r` of a `\r\n` pair is part of the line).
+fn is_line_break(chars: &[char], i: usize) -> bool {
+    chars[i] == '\n' || (chars[i] == '\r' && chars.get(i + 1) != Some(&'\n'))
 }
 
 /// Removes empty lines and trailing whitespace from source code.
 ///
 /// Use this function to normalize whitespace after comment removal. Each line
 /// is trimmed of trailing whitespace, and lines that are empty or contain only
-/// whitespace are removed entirely.
+/// whitespace are removed entirely. Lines may end in `\n`, `\r\n`, or a bare
+/// `\r`; the output always uses `\n`. The returned [`CleanStats`] only
+/// carries `empty_lines_removed` and `cleaned_lines`.
 ///
 /// # Examples
 ///
 /// ```rust,ignore
 /// let code = "line1\n\n\nline2\n  \nline3";
-/// let cleaned = remove_empty_lines(code);
+/// let (cleaned, stats) = remove_empty_lines(code);
 /// assert_eq!(cleaned, "line1\nline2\nline3");
+/// assert_eq!(stats.empty_lines_removed, 3);
 /// ```
-fn remove_empty_lines(code: &str) -> String {
-    code.lines()
+fn remove_empty_lines(code: &str) -> (String, CleanStats) {
+    let mut stats = CleanStats::default();
+    let normalized = if code.contains('\r') {
+        Cow::Owned(code.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(code)
+    };
+    let kept: Vec<&str> = normalized
+        .lines()
         .map(|line| line.trim_end())
-        .filter(|line| !line.trim().is_empty())
-        .collect::<Vec<&str>>()
-        .join("\n")
+        .filter(|line| {
+            let empty = line.trim().is_empty();
+            if empty {
+                stats.empty_lines_removed += 1;
+            }
+            !empty
+        })
+        .collect();
+    stats.cleaned_lines = kept.len();
+    (kept.join("\n"), stats)
 }
 
 /// Cleans Solidity source code by removing comments and empty lines.
 ///
 /// This is the primary cleaning pipeline that combines [`remove_comments`] and
-/// [`remove_empty_lines`] into a single operation. Use this for preparing
-/// Solidity files for consolidation.
+/// [`remove_empty_lines`] into a single operation, merging their [`CleanStats`].
+/// Use this for preparing Solidity files for consolidation.
 ///
 /// # Examples
 ///
@@ -425,787 +3232,12772 @@ fn remove_empty_lines(code: &str) -> String {
 ///     uint256 value;
 /// }
 /// "#;
-/// let cleaned = clean_solidity(code);
+/// let (cleaned, stats) = clean_solidity(code);
 /// assert!(!cleaned.contains("SPDX"));
 /// assert!(cleaned.contains("pragma solidity"));
+/// assert_eq!(stats.comment_lines, 2);
 /// ```
-fn clean_solidity(code: &str) -> String {
-    let without_comments = remove_comments(code);
-    remove_empty_lines(&without_comments)
+fn clean_solidity(code: &str) -> (String, CleanStats) {
+    let (without_comments, mut stats) = remove_comments(code);
+    let (cleaned, empty_stats) = remove_empty_lines(&without_comments);
+    stats.add(&empty_stats);
+    (cleaned, stats)
 }
 
-// ============================================================================
-// Git Operations
-// ============================================================================
+/// Number of leading lines in which a license header block must appear.
+const LICENSE_HEADER_MAX_LINE: usize = 20;
 
-/// Clones a git repository to the specified directory using shallow clone.
-///
-/// Uses `git clone --depth 1` for minimal bandwidth and disk usage. The target
-/// directory is created if it doesn't exist.
-///
-/// # Arguments
+/// Keywords (matched case-insensitively) identifying a legal header block.
+const LICENSE_HEADER_KEYWORDS: [&str; 3] = ["copyright", "license", "(c)"];
+
+/// Extracts a file's leading legal header comment block, if it has one.
 ///
-/// * `url` — The git repository URL (HTTPS or SSH format)
-/// * `target_dir` — The filesystem path where the repository will be cloned
+/// Use this with `--keep-license-headers` to retain copyright notices that
+/// must survive redistribution even though comments are otherwise stripped.
 ///
-/// # Errors
+/// # Behavioral Contract
 ///
-/// | Error | Condition |
-/// |-------|-----------|
-/// | `"Git is not installed..."` | `git` command not found in PATH |
-/// | `"Failed to execute git: {e}"` | System error spawning the git process |
-/// | `"Git clone failed: {stderr}"` | Git returned non-zero exit code |
+/// - Only the *first* comment block is considered: blank lines and
+///   `// SPDX-License-Identifier` lines are skipped, and if the next line is
+///   code rather than a comment, there is no header
+/// - A block is either a `/* ... */` comment or a run of consecutive `//` lines
+/// - The block must start within the first [`LICENSE_HEADER_MAX_LINE`] lines
+///   and contain one of [`LICENSE_HEADER_KEYWORDS`] within those lines
+/// - SPDX lines alone never count as a license header; they are handled
+///   like any other comment
 ///
 /// # Examples
 ///
 /// ```rust,ignore
-/// let temp = tempfile::tempdir()?;
-/// clone_repository("https://github.com/user/repo.git", temp.path())?;
+/// let code = "/* Copyright 2024 Acme. All rights reserved. */\ncontract A {}";
+/// assert_eq!(
+///     extract_license_header(code).as_deref(),
+///     Some("/* Copyright 2024 Acme. All rights reserved. */")
+/// );
+/// assert_eq!(extract_license_header("// SPDX-License-Identifier: MIT\ncontract A {}"), None);
 /// ```
-fn clone_repository(url: &str, target_dir: &Path) -> Result<(), String> {
-    let output = Command::new("git")
-        .args(["clone", "--depth", "1", url])
-        .arg(target_dir)
-        .output()
-        .map_err(|e| {
-            if e.kind() == io::ErrorKind::NotFound {
-                "Git is not installed or not in PATH. Please install Git first.".to_string()
-            } else {
-                format!("Failed to execute git: {}", e)
-            }
-        })?;
+fn extract_license_header(code: &str) -> Option<String> {
+    let lines: Vec<&str> = code.lines().collect();
+    let is_spdx = |line: &str| line.trim_start().starts_with("// SPDX-License-Identifier");
 
-    if output.status.success() {
-        Ok(())
+    let start = lines
+        .iter()
+        .position(|line| !line.trim().is_empty() && !is_spdx(line))?;
+    if start >= LICENSE_HEADER_MAX_LINE {
+        return None;
+    }
+
+    let first = lines[start].trim_start();
+    let end = if first.starts_with("/*") {
+        let offset = lines[start..].iter().position(|line| line.contains("*/"))?;
+        start + offset
+    } else if first.starts_with("//") {
+        let run = lines[start..]
+            .iter()
+            .take_while(|line| line.trim_start().starts_with("//") && !is_spdx(line))
+            .count();
+        start + run - 1
     } else {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("Git clone failed: {}", stderr.trim()))
+        return None;
+    };
+
+    if first.starts_with("/*")
+        && lines[end]
+            .split_once("*/")
+            .is_some_and(|(_, rest)| !rest.trim().is_empty())
+    {
+        // Code follows the comment on its closing line; keeping the whole
+        // line would duplicate code that the cleaner already emits.
+        return None;
     }
+
+    let searched = lines[start..=end.min(LICENSE_HEADER_MAX_LINE - 1)]
+        .join("\n")
+        .to_lowercase();
+    if !LICENSE_HEADER_KEYWORDS.iter().any(|k| searched.contains(k)) {
+        return None;
+    }
+
+    Some(
+        lines[start..=end]
+            .iter()
+            .map(|line| line.trim_end())
+            .collect::<Vec<&str>>()
+            .join("\n"),
+    )
 }
 
-/// Extracts the repository name from a git URL.
+/// Line length (in chars) above which a file is treated as minified or generated.
+const LONG_LINE_THRESHOLD: usize = 10_000;
+
+/// Returns the length in chars of the longest line in `code`.
+fn max_line_length(code: &str) -> usize {
+    code.lines()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Re-wraps lines longer than `threshold` chars at statement boundaries.
+///
+/// Use this on minified or generated single-line sources so that downstream
+/// line-oriented consumers see one statement per line. Lines are broken after
+/// `;`, `{`, and `}` outside of string literals; shorter lines pass through
+/// unchanged.
+///
+/// # Behavioral Contract
 ///
-/// Parses the final path component from a git URL, stripping trailing slashes
-/// and the `.git` extension. Use this to derive a default output filename when
-/// none is specified.
+/// - Only lines exceeding `threshold` are touched
+/// - String literals (with escapes) are never split
+/// - Resulting lines are trimmed and empty lines are dropped
 ///
 /// # Examples
 ///
 /// ```rust,ignore
-/// assert_eq!(extract_repo_name("https://github.com/user/repo.git"), "repo");
-/// assert_eq!(extract_repo_name("https://github.com/user/repo"), "repo");
-/// assert_eq!(extract_repo_name("https://github.com/user/my-project.git/"), "my-project");
+/// let code = "contract A { uint x; function f() public { x = 1; } }";
+/// let wrapped = rewrap_long_lines(code, 10);
+/// assert_eq!(wrapped, "contract A {\nuint x;\nfunction f() public {\nx = 1;\n}\n}");
 /// ```
-fn extract_repo_name(url: &str) -> String {
-    let url = url.trim_end_matches('/');
-    let url = url.trim_end_matches(".git");
-    url.rsplit('/').next().unwrap_or("repository").to_string()
+fn rewrap_long_lines(code: &str, threshold: usize) -> String {
+    let mut lines: Vec<String> = Vec::new();
+
+    for line in code.lines() {
+        if line.chars().count() <= threshold {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        let mut current = String::new();
+        let mut quote: Option<char> = None;
+        let mut escaped = false;
+
+        for c in line.chars() {
+            current.push(c);
+            match quote {
+                Some(q) => {
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == q {
+                        quote = None;
+                    }
+                }
+                None => match c {
+                    '"' | '\'' => quote = Some(c),
+                    ';' | '{' | '}' => {
+                        let statement = current.trim();
+                        if !statement.is_empty() {
+                            lines.push(statement.to_string());
+                        }
+                        current.clear();
+                    }
+                    _ => {}
+                },
+            }
+        }
+
+        let rest = current.trim();
+        if !rest.is_empty() {
+            lines.push(rest.to_string());
+        }
+    }
+
+    lines.join("\n")
 }
 
 // ============================================================================
-// File Discovery
+// Git Operations
 // ============================================================================
 
-/// Recursively discovers all Solidity files in a directory tree.
+/// Environment variable holding the access token of private repositories.
+const TOKEN_ENV: &str = "SOLSCRAPE_GIT_TOKEN";
+
+/// Environment variable holding a GitHub token, used for GitHub URLs only
+/// when [`TOKEN_ENV`] is not set.
+const GITHUB_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// The access token to clone `url` with: `--token`, else [`TOKEN_ENV`],
+/// else [`GITHUB_TOKEN_ENV`] for a GitHub URL. Empty variables are ignored.
 ///
-/// Walks the directory tree starting from `dir`, collecting paths to all `.sol`
-/// files while respecting the exclusion set. Results are sorted alphabetically
-/// for deterministic output ordering.
+/// `GITHUB_TOKEN` is set in every GitHub Actions job, and is never sent to
+/// another forge.
+fn git_token(url: &str, args: &Args) -> Option<forge_host::Token> {
+    let from_env = |name: &str| {
+        env::var(name)
+            .ok()
+            .filter(|token| !token.trim().is_empty())
+            .map(forge_host::Token)
+    };
+    args.token
+        .clone()
+        .or_else(|| from_env(TOKEN_ENV))
+        .or_else(|| {
+            (forge_host::parse(url).forge == forge_host::Forge::GitHub)
+                .then(|| from_env(GITHUB_TOKEN_ENV))
+                .flatten()
+        })
+}
+
+/// Clones a git repository to the specified directory.
 ///
-/// # Arguments
+/// Scrapes pass `--depth 1` in `options` for minimal bandwidth and disk
+/// usage; `--compare` needs the full history and passes `--no-checkout`. The
+/// target directory is created if it doesn't exist.
 ///
-/// * `dir` — The root directory to search
-/// * `excluded` — Directory names to skip (e.g., `"node_modules"`, `"lib"`)
+/// An HTTPS GitHub or GitLab `url` is cloned with the access token of
+/// [`git_token`] in it (see [`forge_host::with_token`]). The token is never
+/// displayed: [`Exec`] redacts it from the command line it logs, and it is
+/// scrubbed from git's stderr before the error is formatted.
 ///
-/// # Returns
+/// # Arguments
 ///
-/// A sorted vector of absolute paths to Solidity files.
+/// * `url` — The git repository URL (HTTPS or SSH format)
+/// * `target_dir` — The filesystem path where the repository will be cloned
+/// * `options` — Extra `git clone` options placed before the URL
+/// * `exec` — The policy git is run under
+/// * `log` — Destination for the raw stderr of a failed clone
 ///
 /// # Errors
 ///
-/// Returns an I/O error if the directory cannot be read.
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Git is not installed..."` | `git` command not found in PATH |
+/// | `"Failed to execute git: {e}"` | System error spawning the git process |
+/// | `"--no-subprocess forbids running git..."` | `exec` does not allow git |
+/// | `"Git clone failed: {stderr}"` | Git returned non-zero exit code |
+/// | `"Git clone failed after {n} attempts: {stderr}"` | Every retry failed too |
+/// | `"Git clone timed out after {s}s"` | An attempt outlasted `--timeout` |
+/// | `"Git clone did not finish before the --deadline"` | The run's `--deadline` passed |
+///
+/// On failure the message shows only the last meaningful lines of git's
+/// stderr (see [`summarize_git_stderr`]); the full raw output is logged at
+/// [`logger::Level::Detail`], visible with `--verbose` or in `--log-file`.
+///
+/// A failure that looks transient (see [`is_transient_clone_failure`]) is
+/// retried up to `--retries` times ([`DEFAULT_RETRIES`] by default), after
+/// a wait that starts at [`RETRY_DELAY`] and doubles each time, with a
+/// warning each time. No retry starts when the wait would outlast the
+/// `--deadline`. Each attempt is killed once `--timeout` has elapsed,
+/// which counts as transient too; the caller's [`tempfile::TempDir`]
+/// still removes whatever it left.
 ///
 /// # Examples
 ///
 /// ```rust,ignore
-/// let excluded: HashSet<&str> = [".git", "node_modules"].into_iter().collect();
-/// let files = find_solidity_files(Path::new("./contracts"), &excluded)?;
+/// let temp = tempfile::tempdir_in(&tempfile::base_dir(None, None))?;
+/// clone_repository(url, temp.path(), &["--depth", "1"], &exec, &log)?;
 /// ```
-fn find_solidity_files(dir: &Path, excluded: &HashSet<&str>) -> io::Result<Vec<PathBuf>> {
-    let mut sol_files = Vec::new();
-    find_solidity_files_recursive(dir, excluded, &mut sol_files)?;
-    sol_files.sort();
-    Ok(sol_files)
+fn clone_repository(
+    url: &str,
+    target_dir: &Path,
+    options: &[&str],
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<(), String> {
+    let token = git_token(url, args);
+    let authenticated = token
+        .as_ref()
+        .and_then(|token| forge_host::with_token(url, &token.0));
+    if authenticated.is_some() {
+        log.detail("Cloning with an access token");
+    }
+    let clone_url = authenticated.as_deref().unwrap_or(url);
+    // The token is scrubbed even where git prints it outside a URL
+    let scrub = |stderr: &str| {
+        let stderr = forge_host::redact(stderr);
+        match token.as_ref().filter(|_| authenticated.is_some()) {
+            Some(token) => stderr.replace(&token.0, forge_host::REDACTED),
+            None => stderr,
+        }
+    };
+
+    let attempts = args.retries.unwrap_or(DEFAULT_RETRIES) + 1;
+    let mut attempt = 1;
+    loop {
+        let left = time_left(args, "cloning")?;
+        // The attempt's own limit, unless the deadline comes first
+        let attempt_limit = args
+            .clone_timeout
+            .filter(|limit| left.is_none_or(|left| *limit < left));
+        let clone_args = std::iter::once("clone".as_ref())
+            .chain(options.iter().map(|o| o.as_ref()))
+            .chain([clone_url.as_ref(), target_dir.as_os_str()]);
+        // What went wrong, whether a retry may help, and for the final
+        // error, whether it was the attempt's time limit
+        let (failure, transient, timed_out) =
+            match exec.output_within("git", clone_args, None, attempt_limit.or(left), log) {
+                Ok(output) if output.status.success() => return Ok(()),
+                Ok(output) => {
+                    let stderr = scrub(&String::from_utf8_lossy(&output.stderr));
+                    log.detail(&format!(
+                        "Raw git clone stderr:\n{}",
+                        stderr.replace('\r', "\n")
+                    ));
+                    let summary = summarize_git_stderr(&stderr);
+                    let failure = if summary.is_empty() {
+                        format!("git exited with {}", output.status)
+                    } else {
+                        summary
+                    };
+                    (failure, is_transient_clone_failure(&stderr), false)
+                }
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => match attempt_limit {
+                    Some(limit) => (format!("timed out after {}s", limit.as_secs()), true, true),
+                    None => {
+                        return Err("Git clone did not finish before the --deadline".to_string());
+                    }
+                },
+                Err(e) if e.kind() == io::ErrorKind::PermissionDenied => return Err(e.to_string()),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    return Err(
+                        "Git is not installed or not in PATH. Please install Git first."
+                            .to_string(),
+                    );
+                }
+                Err(e) => return Err(format!("Failed to execute git: {}", e)),
+            };
+
+        let delay = RETRY_DELAY * 2u32.pow((attempt - 1).min(10) as u32);
+        let in_time = time_left(args, "cloning")?.is_none_or(|left| left > delay);
+        if attempt == attempts || !transient || !in_time {
+            return Err(match (timed_out, attempt) {
+                (true, 1) => format!("Git clone {}", failure),
+                (true, _) => format!("Git clone {} ({} attempts)", failure, attempt),
+                (false, 1) => format!("Git clone failed: {}", failure),
+                (false, _) => format!("Git clone failed after {} attempts: {}", attempt, failure),
+            });
+        }
+        log.warn(&format!(
+            "Clone attempt {} of {} failed ({}); retrying in {}s",
+            attempt,
+            attempts,
+            failure.replace("\n  ", " "),
+            delay.as_secs()
+        ));
+        std::thread::sleep(delay);
+        clear_directory(target_dir)?;
+        attempt += 1;
+    }
 }
 
-/// Recursive helper for [`find_solidity_files`].
+/// Retries of a clone that failed for what looks like a transient reason,
+/// when `--retries` is not given.
+const DEFAULT_RETRIES: usize = 2;
+
+/// The wait before the first retry of a clone; it doubles for each retry
+/// after that.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Lowercased phrases of git's stderr that mark a failed clone as worth
+/// retrying: network trouble, timeouts, rate limits, and server errors.
+const TRANSIENT_CLONE_ERRORS: [&str; 16] = [
+    "could not resolve host",
+    "temporary failure in name resolution",
+    "failed to connect",
+    "connection refused",
+    "connection reset",
+    "connection timed out",
+    "operation timed out",
+    "network is unreachable",
+    "the remote end hung up unexpectedly",
+    "early eof",
+    "rpc failed",
+    "gnutls",
+    "ssl_read",
+    "rate limit",
+    "returned error: 429",
+    "returned error: 5",
+];
+
+/// Lowercased phrases of git's stderr that mark a failed clone as one a
+/// retry cannot fix, even next to a transient-looking one.
+const PERMANENT_CLONE_ERRORS: [&str; 6] = [
+    "not found",
+    "does not exist",
+    "authentication failed",
+    "permission denied",
+    "could not read username",
+    "invalid username or password",
+];
+
+/// Returns true if a clone that failed with `stderr` is worth retrying: git
+/// names a transient cause (see [`TRANSIENT_CLONE_ERRORS`]) or gives none
+/// at all, and nothing that no retry can fix.
 ///
-/// Traverses subdirectories depth-first, appending found `.sol` file paths to
-/// the accumulator. Directories matching names in `excluded` are skipped.
-fn find_solidity_files_recursive(
-    dir: &Path,
-    excluded: &HashSet<&str>,
-    files: &mut Vec<PathBuf>,
-) -> io::Result<()> {
-    if !dir.is_dir() {
-        return Ok(());
+/// # Examples
+///
+/// ```rust,ignore
+/// assert!(is_transient_clone_failure("fatal: unable to access '...': Could not resolve host: github.com"));
+/// assert!(!is_transient_clone_failure("remote: Repository not found.\nfatal: repository '...' not found"));
+/// ```
+fn is_transient_clone_failure(stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    if PERMANENT_CLONE_ERRORS.iter().any(|p| stderr.contains(p)) {
+        return false;
     }
+    summarize_git_stderr(&stderr).is_empty()
+        || TRANSIENT_CLONE_ERRORS.iter().any(|p| stderr.contains(p))
+}
 
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        let file_name = entry.file_name();
-        let name_str = file_name.to_string_lossy();
-
-        if path.is_dir() {
-            if !excluded.contains(name_str.as_ref()) {
-                find_solidity_files_recursive(&path, excluded, files)?;
-            }
-        } else if path.is_file() {
-            if let Some(ext) = path.extension() {
-                if ext == "sol" {
-                    files.push(path);
-                }
-            }
+/// Removes everything inside `dir`, keeping `dir` itself, so that a failed
+/// clone's leftovers cannot fail the next attempt.
+///
+/// # Errors
+///
+/// Returns `"Failed to clear {dir} for another clone attempt: {e}"`.
+fn clear_directory(dir: &Path) -> Result<(), String> {
+    let failed = |e: io::Error| {
+        format!(
+            "Failed to clear {} for another clone attempt: {}",
+            dir.display(),
+            e
+        )
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(failed(e)),
+    };
+    for entry in entries {
+        let path = entry.map_err(failed)?.path();
+        if path.is_dir() && !path.is_symlink() {
+            fs::remove_dir_all(&path)
+        } else {
+            fs::remove_file(&path)
         }
+        .map_err(failed)?;
     }
-
     Ok(())
 }
 
-/// Builds the set of directory names to exclude from scraping.
+/// The time left before `--deadline`, or `None` without one.
 ///
-/// Creates a [`HashSet`] of directory names that should be skipped during
-/// file discovery. Some directories are always excluded (e.g., `.git`,
-/// `node_modules`), while others depend on the [`Args`] configuration.
+/// # Errors
 ///
-/// # Arguments
+/// Returns `"Deadline reached before {step}"` once the deadline has passed.
+fn time_left(args: &Args, step: &str) -> Result<Option<Duration>, String> {
+    match args.deadline {
+        None => Ok(None),
+        Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(Some(left)),
+            _ => Err(format!("Deadline reached before {}", step)),
+        },
+    }
+}
+
+/// Returns true once `--deadline` has passed.
+fn deadline_passed(args: &Args) -> bool {
+    args.deadline
+        .is_some_and(|deadline| Instant::now() >= deadline)
+}
+
+/// Renders the trailer of output cut short after `done` of `total` files.
+fn render_truncated_trailer(done: usize, total: usize) -> String {
+    format!(
+        "{}deadline reached after {} of {} files",
+        TRUNCATED_PREFIX, done, total
+    )
+}
+
+/// Maximum number of stderr lines shown in a git error message.
+const GIT_STDERR_TAIL_LINES: usize = 5;
+
+/// Line prefixes of git's transfer progress and status chatter.
+const GIT_PROGRESS_PREFIXES: [&str; 9] = [
+    "Cloning into",
+    "Enumerating objects",
+    "Counting objects",
+    "Compressing objects",
+    "Receiving objects",
+    "Resolving deltas",
+    "Updating files",
+    "Unpacking objects",
+    "Total ",
+];
+
+/// Reduces captured git stderr to the lines worth showing a user.
 ///
-/// * `args` — The parsed CLI arguments containing inclusion flags
+/// Git rewrites progress in place using `\r` and may color output with ANSI
+/// escapes, which turns a lossily-decoded stderr into one mangled line. This
+/// strips escape sequences, splits on both `\n` and `\r`, drops progress and
+/// blank lines, and keeps the last [`GIT_STDERR_TAIL_LINES`] lines.
 ///
-/// # Always Excluded
+/// # Returns
 ///
-/// - `.git`, `node_modules`, `out`, `cache`, `artifacts`
-/// - `build`, `coverage`, `.deps`, `dependencies`
+/// The remaining lines joined with newlines (continuation lines indented
+/// for display after a prefix), or an empty string if nothing meaningful
+/// was left.
 ///
-/// # Conditionally Excluded
+/// # Examples
 ///
-/// | Directory | Included When |
-/// |-----------|---------------|
-/// | `lib/` | `args.include_lib` is `true` |
-/// | `test/`, `tests/` | `args.include_test` is `true` |
-/// | `script/`, `scripts/` | `args.include_script` is `true` |
-fn build_excluded_dirs(args: &Args) -> HashSet<&'static str> {
-    let mut excluded: HashSet<&str> = HashSet::new();
+/// ```rust,ignore
+/// let raw = "Cloning into 'x'...\rReceiving objects:  50% (1/2)\r\nfatal: early EOF\n";
+/// assert_eq!(summarize_git_stderr(raw), "fatal: early EOF");
+/// ```
+fn summarize_git_stderr(raw: &str) -> String {
+    let cleaned = strip_ansi_escapes(raw);
+    let meaningful: Vec<&str> = cleaned
+        .split(['\n', '\r'])
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !is_git_progress_line(line))
+        .collect();
 
-    // Always exclude these
-    excluded.insert(".git");
-    excluded.insert("node_modules");
-    excluded.insert("out");
-    excluded.insert("cache");
-    excluded.insert("artifacts");
-    excluded.insert("build");
-    excluded.insert("coverage");
-    excluded.insert(".deps");
-    excluded.insert("dependencies");
+    let start = meaningful.len().saturating_sub(GIT_STDERR_TAIL_LINES);
+    meaningful[start..].join("\n  ")
+}
 
-    // Conditionally exclude based on flags
-    if !args.include_lib {
-        excluded.insert("lib");
+/// Returns true for git progress lines, with or without a `remote: ` prefix.
+fn is_git_progress_line(line: &str) -> bool {
+    let line = line.strip_prefix("remote:").map_or(line, str::trim_start);
+    if line.is_empty() {
+        // A bare "remote:" carries nothing
+        return true;
     }
-    if !args.include_test {
-        excluded.insert("test");
-        excluded.insert("tests");
-        excluded.insert("Test");
-        excluded.insert("Tests");
-    }
-    if !args.include_script {
-        excluded.insert("script");
-        excluded.insert("scripts");
-        excluded.insert("Script");
-        excluded.insert("Scripts");
+    if GIT_PROGRESS_PREFIXES.iter().any(|p| line.starts_with(p)) {
+        return true;
     }
 
-    excluded
+    // Bare percentage lines such as " 42% (420/1000)" from partial redraws
+    let digits = line.trim_start_matches(|c: char| c.is_ascii_digit());
+    digits.len() < line.len() && digits.starts_with('%')
 }
 
-// ============================================================================
-// File Processing
-// ============================================================================
+/// Removes ANSI escape sequences (CSI such as colors, and two-byte escapes).
+fn strip_ansi_escapes(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
 
-/// Processes a single Solidity file and returns its cleaned content.
-///
-/// Reads the file, applies [`clean_solidity`] to remove comments and empty lines,
-/// and optionally prepends a decorative header showing the file's relative path.
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        // A two-byte escape is dropped along with its following character;
+        // a CSI sequence runs until a final byte in `@..=~`
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Returns true if `path` looks like a bare git repository.
 ///
-/// # Arguments
+/// A bare repository has `HEAD`, `objects/`, and `refs/` at its top level and
+/// no working tree, so it must be cloned rather than scanned.
+fn is_bare_repository(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// Returns true if the source should be cloned with git rather than scanned.
 ///
-/// * `path` — Absolute path to the Solidity file
-/// * `base_dir` — Base directory for computing relative paths in headers
-/// * `add_header` — Whether to include a file separator header in the output
+/// Sources are cloned unless `--local` or `--reuse-clone` is given, except
+/// that under `--local`, `file://` URLs and bare repositories are still
+/// cloned: neither has files to scan in place, but git clones both fine.
+fn clones_source(args: &Args) -> bool {
+    if args.reuse_clone.is_some() {
+        return false;
+    }
+    !args.is_local
+        || args.source.starts_with("file://")
+        || is_bare_repository(Path::new(&args.source))
+}
+
+/// Returns the programs `--no-subprocess` still allows for `args`.
 ///
-/// # Returns
+/// Cloning, `--reuse-clone`, and `--since` need `git`, and a clone under
+/// `--max-clone-size` also needs `curl` for the size probe; `--no-git`
+/// needs the downloaders and `tar` instead (see [`tarball`]); everything else
+/// (revision detection, the free-space probe) is best effort and is skipped
+/// rather than allowed.
+fn allowed_programs(args: &Args) -> Vec<&'static str> {
+    let needs_git = clones_source(args)
+        || args.reuse_clone.is_some()
+        || args.compare.is_some()
+        || args.since.is_some();
+    let subcommand = args
+        .subcommand
+        .as_ref()
+        .is_some_and(|s| !matches!(s, Subcommand::Check { .. }));
+    if !needs_git || subcommand {
+        return Vec::new();
+    }
+    if args.no_git {
+        return tarball::DOWNLOADERS
+            .into_iter()
+            .chain([tarball::EXTRACTOR])
+            .collect();
+    }
+    if args.max_clone_size.is_some() && clones_source(args) {
+        vec!["git", "curl"]
+    } else {
+        vec!["git"]
+    }
+}
+
+/// Builds the [`Exec`] every subprocess of this invocation goes through.
+fn build_exec(args: &Args) -> Exec {
+    if args.no_subprocess {
+        Exec::restricted(&allowed_programs(args))
+    } else {
+        Exec::unrestricted()
+    }
+}
+
+/// Extracts the repository name from a git URL.
 ///
-/// - `Ok(Some(content))` — The cleaned file content (with optional header)
-/// - `Ok(None)` — The file was empty after cleaning
-/// - `Err(e)` — The file could not be read
+/// Takes the final path component of the repository (see
+/// [`forge_host::parse`]), without trailing slashes or the `.git` extension;
+/// a GitLab subgroup path ends in the project's name, like any other. Use
+/// this to derive a default output filename when none is specified.
 ///
 /// # Examples
 ///
 /// ```rust,ignore
-/// let content = process_file(
-///     Path::new("/project/src/Token.sol"),
-///     Path::new("/project"),
-///     true
-/// )?;
+/// assert_eq!(extract_repo_name("https://github.com/user/repo.git"), "repo");
+/// assert_eq!(extract_repo_name("https://github.com/user/repo"), "repo");
+/// assert_eq!(extract_repo_name("https://github.com/user/my-project.git/"), "my-project");
+/// assert_eq!(extract_repo_name("file:///srv/mirrors/openzeppelin.git"), "openzeppelin");
+/// assert_eq!(extract_repo_name("https://gitlab.com/group/sub/repo/"), "repo");
 /// ```
-fn process_file(path: &Path, base_dir: &Path, add_header: bool) -> io::Result<Option<String>> {
-    let content = fs::read_to_string(path)?;
-    let cleaned = clean_solidity(&content);
+fn extract_repo_name(url: &str) -> String {
+    forge_host::parse(url).name()
+}
 
-    if cleaned.trim().is_empty() {
-        return Ok(None);
-    }
+/// The branch and commit a scraped working tree was checked out at.
+///
+/// Recorded in [`ScraperResult`] so the summary can state exactly what was
+/// scraped. Only available for git working trees.
+#[derive(Debug, Clone, PartialEq)]
+struct GitRevision {
+    /// The checked-out branch, or `detached@<sha>` for a detached `HEAD`.
+    branch: String,
+    /// The abbreviated commit hash of `HEAD`.
+    commit: String,
+    /// The full hash of the commit `--rev` named, when one was checked out.
+    pinned: Option<String>,
+}
+
+/// Runs `git -C <dir> <args>` and returns its trimmed stdout on success.
+///
+/// Returns `None` when git is unavailable or not allowed, exits non-zero, or
+/// prints nothing.
+fn git_output(dir: &Path, args: &[&str], exec: &Exec, log: &Logger) -> Option<String> {
+    let git_args = ["-C".as_ref(), dir.as_os_str()]
+        .into_iter()
+        .chain(args.iter().map(|a| a.as_ref()));
+    let output = exec.output("git", git_args, None, log).ok()?;
 
-    let relative_path = path
-        .strip_prefix(base_dir)
-        .unwrap_or(path)
-        .to_string_lossy();
+    if !output.status.success() {
+        return None;
+    }
 
-    if add_header {
-        let separator = "// ══════════════════════════════════════════════════════════════════════";
-        Ok(Some(format!(
-            "{}\n// File: {}\n{}\n{}",
-            separator, relative_path, separator, cleaned
-        )))
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if stdout.is_empty() {
+        None
     } else {
-        Ok(Some(cleaned))
+        Some(stdout)
     }
 }
 
-// ============================================================================
-// Main Scraper
-// ============================================================================
-
-/// The result of a successful scraping operation.
+/// Detects the branch and commit checked out in a git working tree.
 ///
-/// Contains statistics about the scraping process and the location of the
-/// output file. Use this to report results to the user or for programmatic
-/// access to the output.
-struct ScraperResult {
-    /// The absolute path to the generated consolidated Solidity file.
-    output_path: PathBuf,
-    /// The number of Solidity files that were processed.
-    file_count: usize,
-    /// The total number of lines in the consolidated output.
-    line_count: usize,
-    /// Relative paths of all files that were included in the output.
-    files_processed: Vec<String>,
-}
-
-/// Scrapes Solidity files from a directory and consolidates them into a single file.
+/// Tries `git symbolic-ref --short HEAD` first, then `git branch --show-current`,
+/// and describes a detached `HEAD` as `detached@<sha>`.
 ///
-/// This is the core scraping logic used by both [`scrape_from_url`] and
-/// [`scrape_from_local`]. It discovers Solidity files, processes each one,
-/// and writes the combined output to the destination directory.
+/// # Returns
 ///
-/// # Arguments
+/// `None` if `dir` is not inside a git working tree (or git is unavailable).
+/// Git is only run when `dir` or one of its ancestors contains `.git`, so
+/// scanning a plain directory spawns nothing.
 ///
-/// * `source_dir` — The directory containing Solidity files to scrape
-/// * `destination` — The output directory for the consolidated file
-/// * `output_name` — Base name for the output file (produces `{name}_scraped.sol`)
-/// * `args` — Configuration affecting which files to include
+/// # Examples
 ///
-/// # Returns
+/// ```rust,ignore
+/// if let Some(rev) = detect_git_revision(temp.path(), &exec, &log) {
+///     println!("branch: {}, commit: {}", rev.branch, rev.commit);
+/// }
+/// ```
+fn detect_git_revision(dir: &Path, exec: &Exec, log: &Logger) -> Option<GitRevision> {
+    let absolute = fs::canonicalize(dir).ok()?;
+    if !absolute.ancestors().any(|p| p.join(".git").exists()) {
+        return None;
+    }
+    if !exec.allows("git") {
+        log.detail("Skipping revision detection: git is not allowed");
+        return None;
+    }
+
+    let git = |args: &[&str]| git_output(dir, args, exec, log);
+    let commit = git(&["rev-parse", "--short", "HEAD"])?;
+    let branch = git(&["symbolic-ref", "--short", "HEAD"])
+        .or_else(|| git(&["branch", "--show-current"]))
+        .unwrap_or_else(|| format!("detached@{}", commit));
+
+    Some(GitRevision {
+        branch,
+        commit,
+        pinned: None,
+    })
+}
+
+/// The history fetched per step while deepening a shallow clone for
+/// `--since`; it doubles each step.
+const SINCE_DEEPEN_START: usize = 64;
+
+/// From this many commits on, `--since` fetches the remaining history at once.
+const SINCE_DEEPEN_MAX: usize = 4096;
+
+/// The `.sol` files changed between a ref and `HEAD`, for `--since`.
+#[derive(Debug, Clone, PartialEq)]
+struct ChangedFiles {
+    /// The ref as given on the command line.
+    base: String,
+    /// Added or modified files, relative to the scraped directory.
+    changed: std::collections::BTreeSet<String>,
+    /// Files that existed at the merge base but no longer do.
+    removed: Vec<String>,
+}
+
+/// Lists the `.sol` files changed in `repo` since `git_ref`.
 ///
-/// A [`ScraperResult`] containing statistics and the output path.
+/// Equivalent to `git diff --name-status REF...HEAD -- '*.sol'`, so changes
+/// are counted from the merge base and uncommitted edits are ignored.
+/// Renames count as a removal plus an addition. Paths are relative to
+/// `repo`, which may be a subdirectory of the working tree.
+///
+/// A shallow clone usually lacks both the ref and the history connecting
+/// it to `HEAD`. The ref is then fetched by name, and history is deepened
+/// in doubling steps until a merge base appears, or fetched completely once
+/// [`SINCE_DEEPEN_MAX`] commits were not enough.
 ///
 /// # Errors
 ///
 /// | Error | Condition |
 /// |-------|-----------|
-/// | `"Failed to scan directory: {e}"` | I/O error during file discovery |
-/// | `"No Solidity files found..."` | No `.sol` files in the source tree |
-/// | `"All Solidity files were empty..."` | All files were empty after cleaning |
-/// | `"Failed to create destination: {e}"` | Cannot create output directory |
-/// | `"Failed to create output file: {e}"` | Cannot create the output file |
-/// | `"Failed to write output: {e}"` | Error writing to the output file |
-fn scrape_directory(
-    source_dir: &Path,
-    destination: &str,
-    output_name: &str,
+/// | `"--since needs a git repository ..."` | `repo` is not inside a git working tree |
+/// | `"Unknown ref {ref}: ..."` | Neither the repository nor its remote has the ref |
+/// | `"{ref} and HEAD share no history"` | The complete history has no merge base |
+/// | `"Failed to fetch history ..."` | A deepening fetch failed |
+/// | `"Deadline reached before ..."` | `--deadline` passed while fetching |
+fn changed_since(
+    repo: &Path,
+    git_ref: &str,
     args: &Args,
-) -> Result<ScraperResult, String> {
-    let excluded = build_excluded_dirs(args);
-
-    // Find all Solidity files
-    let sol_files = find_solidity_files(source_dir, &excluded)
-        .map_err(|e| format!("Failed to scan directory: {}", e))?;
-
-    if sol_files.is_empty() {
-        return Err("No Solidity files found in the source".to_string());
+    exec: &Exec,
+    log: &Logger,
+) -> Result<ChangedFiles, String> {
+    let git = |git_args: &[&str]| git_output(repo, git_args, exec, log);
+    if git(&["rev-parse", "--is-inside-work-tree"]).as_deref() != Some("true") {
+        return Err(format!(
+            "--since needs a git repository, but {} is not one",
+            repo.display()
+        ));
     }
+    let fetch = |fetch_args: &[&str]| -> Result<bool, String> {
+        let timeout = time_left(args, "fetching history")?;
+        let git_args = ["-C".as_ref(), repo.as_os_str(), "fetch".as_ref()]
+            .into_iter()
+            .chain(fetch_args.iter().map(|a| a.as_ref()));
+        let output = exec
+            .output_within("git", git_args, None, timeout, log)
+            .map_err(|e| format!("Failed to fetch history: {}", e))?;
+        if !output.status.success() {
+            log.detail(&format!(
+                "Raw git fetch stderr:\n{}",
+                forge_host::redact(&String::from_utf8_lossy(&output.stderr))
+            ));
+        }
+        Ok(output.status.success())
+    };
 
-    // Process all files
-    let mut all_parts: Vec<String> = Vec::new();
-    let mut files_processed: Vec<String> = Vec::new();
-
-    for file_path in &sol_files {
-        let relative = file_path
-            .strip_prefix(source_dir)
-            .unwrap_or(file_path)
-            .to_string_lossy()
-            .to_string();
-
-        match process_file(file_path, source_dir, !args.no_headers) {
-            Ok(Some(content)) => {
-                all_parts.push(content);
-                files_processed.push(relative);
-            }
-            Ok(None) => {
-                // Empty file, skip
-            }
-            Err(e) => {
-                if !args.quiet {
-                    eprintln!("Warning: Could not read {}: {}", relative, e);
-                }
+    let commit_of = |name: &str| {
+        git(&[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", name),
+        ])
+    };
+    let base = match commit_of(git_ref).or_else(|| commit_of(&format!("origin/{}", git_ref))) {
+        Some(base) => base,
+        None => {
+            log.detail(&format!("{} is not in the clone; fetching it", git_ref));
+            if !fetch(&["--depth", "1", "origin", git_ref])? {
+                return Err(format!(
+                    "Unknown ref {}: not found in the repository or on its remote",
+                    git_ref
+                ));
             }
+            commit_of("FETCH_HEAD").ok_or_else(|| format!("Unknown ref {}", git_ref))?
         }
-    }
+    };
 
-    if all_parts.is_empty() {
-        return Err("All Solidity files were empty after processing".to_string());
+    let mut depth = SINCE_DEEPEN_START;
+    while git(&["merge-base", &base, "HEAD"]).is_none() {
+        if git(&["rev-parse", "--is-shallow-repository"]).as_deref() != Some("true") {
+            return Err(format!("{} and HEAD share no history", git_ref));
+        }
+        let deepened = if depth > SINCE_DEEPEN_MAX {
+            log.info(&format!("Fetching full history to find {}", git_ref));
+            fetch(&["--unshallow", "origin"])?
+        } else {
+            log.detail(&format!("Deepening history by {} commits", depth));
+            fetch(&[&format!("--deepen={}", depth), "origin"])?
+        };
+        if !deepened {
+            return Err(format!(
+                "Failed to fetch history: could not deepen the clone to reach {}",
+                git_ref
+            ));
+        }
+        depth *= 2;
     }
 
-    // Combine all code
-    let final_code = all_parts.join("\n");
-    let line_count = final_code.lines().count();
-
-    // Prepare output path
-    let dest_path = Path::new(destination);
-    fs::create_dir_all(dest_path).map_err(|e| format!("Failed to create destination: {}", e))?;
-
-    let output_filename = format!("{}_scraped.sol", output_name);
-    let output_path = dest_path.join(&output_filename);
-
-    // Write output
-    let mut file =
-        File::create(&output_path).map_err(|e| format!("Failed to create output file: {}", e))?;
+    let range = format!("{}...HEAD", base);
+    let listing = git(&[
+        "diff",
+        "--name-status",
+        "--no-renames",
+        "--relative",
+        "-z",
+        &range,
+        "--",
+        "*.sol",
+    ])
+    .unwrap_or_default();
+    let mut changes = ChangedFiles {
+        base: git_ref.to_string(),
+        changed: std::collections::BTreeSet::new(),
+        removed: Vec::new(),
+    };
+    let mut fields = listing.split('\0').filter(|f| !f.is_empty());
+    while let (Some(status), Some(path)) = (fields.next(), fields.next()) {
+        if status == "D" {
+            changes.removed.push(path.to_string());
+        } else {
+            changes.changed.insert(path.to_string());
+        }
+    }
+    log.detail(&format!(
+        "Since {}: {} changed, {} removed .sol files",
+        git_ref,
+        changes.changed.len(),
+        changes.removed.len()
+    ));
+    Ok(changes)
+}
 
-    file.write_all(final_code.as_bytes())
-        .map_err(|e| format!("Failed to write output: {}", e))?;
+/// The files related to the `--related` target through imports.
+#[derive(Debug, Clone, PartialEq)]
+struct RelatedFiles {
+    /// The target, relative to the scraped directory.
+    target: String,
+    /// Each related file's relation, by relative path; the target included.
+    relations: std::collections::BTreeMap<String, graph::Relation>,
+}
 
-    Ok(ScraperResult {
-        output_path,
-        file_count: files_processed.len(),
-        line_count,
-        files_processed,
-    })
+impl RelatedFiles {
+    /// The relations of the files of `included` only, for the summary.
+    fn within(&self, included: &[String]) -> Self {
+        Self {
+            target: self.target.clone(),
+            relations: self
+                .relations
+                .iter()
+                .filter(|(path, _)| included.contains(path))
+                .map(|(path, relation)| (path.clone(), *relation))
+                .collect(),
+        }
+    }
 }
 
-/// Scrapes Solidity files from a remote git repository.
+/// Finds the files related to `target` in `source_dir` (`--related`).
 ///
-/// Clones the repository to a temporary directory, processes all Solidity files,
-/// and writes the consolidated output. The temporary directory is automatically
-/// cleaned up when the function returns.
+/// The import graph covers every `.sol` file under `source_dir`, excluded
+/// or not, and resolves bare imports through the source root's
+/// [`graph::REMAPPINGS_FILE`] (see [`graph::resolve`]). Files that cannot be
+/// read as text have no imports.
 ///
-/// # Arguments
+/// # Errors
 ///
-/// * `url` — The git repository URL to clone
-/// * `destination` — The output directory for the consolidated file
-/// * `output_name` — Optional custom output name; defaults to repository name
-/// * `args` — Configuration affecting scraping behavior
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Failed to scan directory: {e}"` | I/O error while walking the tree |
+/// | `"--related {path} is not a .sol file in {source}"` | The target is missing or not Solidity |
+fn related_files(source_dir: &Path, target: &str, log: &Logger) -> Result<RelatedFiles, String> {
+    let mut files = Vec::new();
+    for path in SolFileWalker::new(source_dir, &ExclusionPolicy::unfiltered()) {
+        let path = path.map_err(|e| format!("Failed to scan directory: {}", e))?;
+        let code = fs::read_to_string(&path).unwrap_or_default();
+        files.push((naming::relative_path(&path, source_dir), code));
+    }
+    let remappings = fs::read_to_string(source_dir.join(graph::REMAPPINGS_FILE))
+        .map(|text| graph::parse_remappings(&text))
+        .unwrap_or_default();
+    let graph = graph::ImportGraph::build(&files, &remappings);
+    if !graph.contains(target) {
+        return Err(format!(
+            "--related {} is not a .sol file in {}",
+            naming::display_path(target),
+            source_dir.display()
+        ));
+    }
+    let relations = graph.related(target);
+    log.detail(&format!(
+        "Import graph: {} files, {} related to {}",
+        files.len(),
+        relations.len(),
+        naming::display_path(target)
+    ));
+    Ok(RelatedFiles {
+        target: target.to_string(),
+        relations,
+    })
+}
+
+/// The warning for a `--related` target the usual rules leave out, naming
+/// the flag that adds it when there is one.
 ///
-/// # Returns
+/// # Examples
 ///
-/// A [`ScraperResult`] on success.
+/// ```rust,ignore
+/// let reason = exclusion::Reason::Suffix(".s.sol");
+/// assert_eq!(
+///     excluded_target_warning("script/Deploy.s.sol", &reason),
+///     "--related target script/Deploy.s.sol is excluded (*.s.sol file); \
+///      add --include-script to scrape it with the files related to it"
+/// );
+/// ```
+fn excluded_target_warning(target: &str, reason: &exclusion::Reason) -> String {
+    let target = naming::display_path(target);
+    match reason.lifted_by() {
+        Some(flag) => format!(
+            "--related target {} is excluded ({}); add --{} to scrape it with the files \
+             related to it",
+            target, reason, flag
+        ),
+        None => format!(
+            "--related target {} is excluded ({}); only the files related to it are scraped",
+            target, reason
+        ),
+    }
+}
+
+/// The refspecs fetched when `--rev` is looked up in the whole history.
+const REV_REFSPECS: [&str; 2] = [
+    "+refs/heads/*:refs/remotes/origin/*",
+    "+refs/tags/*:refs/tags/*",
+];
+
+/// Checks out the commit `rev` names in the git working tree at `dir`, for
+/// `--rev`, leaving `HEAD` detached.
 ///
-/// # Errors
+/// `rev` is first fetched from `remote` on its own, shallowly when the
+/// clone is shallow; that works for a branch, a tag, or a full commit hash.
+/// Servers refuse to send an abbreviated hash, so when the fetch fails
+/// every branch and tag is fetched with their whole history and `rev` is
+/// looked up among them.
 ///
-/// Returns an error if cloning fails or if the scraping process encounters errors.
-/// See [`clone_repository`] and [`scrape_directory`] for specific error conditions.
+/// # Returns
 ///
-/// # Examples
+/// The full hash of the commit checked out.
 ///
-/// ```rust,ignore
-/// let result = scrape_from_url(
-///     "https://github.com/OpenZeppelin/openzeppelin-contracts.git",
-///     "./output",
-///     Some("openzeppelin"),
-///     &args
-/// )?;
-/// println!("Output: {}", result.output_path.display());
-/// ```
-fn scrape_from_url(
-    url: &str,
-    destination: &str,
-    output_name: Option<&str>,
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Unknown revision {rev}: ..."` | Neither fetch finds a commit named `rev` |
+/// | `"git ... failed ..."` | Fetching the whole history or the checkout failed |
+/// | `"Deadline reached before ..."` | `--deadline` passed while fetching |
+fn checkout_revision(
+    dir: &Path,
+    remote: &str,
+    rev: &str,
     args: &Args,
-) -> Result<ScraperResult, String> {
-    // Create temporary directory
-    let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
-
-    let temp_path = temp_dir.path();
+    exec: &Exec,
+    log: &Logger,
+) -> Result<String, String> {
+    let git = |git_args: &[&str]| git_step(dir, git_args, args, exec, log);
+    let commit_of = |name: &str| {
+        git(&[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", name),
+        ])
+        .ok()
+        .filter(|commit| !commit.is_empty())
+    };
+    let shallow = git(&["rev-parse", "--is-shallow-repository"])
+        .ok()
+        .as_deref()
+        == Some("true");
 
-    if !args.quiet {
-        println!("Cloning repository...");
+    let mut fetch = vec!["fetch", "--quiet"];
+    if shallow {
+        fetch.extend(["--depth", "1"]);
     }
-
-    clone_repository(url, temp_path)?;
-
-    if !args.quiet {
-        println!("Processing files...");
+    fetch.extend([remote, rev]);
+    let commit = match git(&fetch) {
+        Ok(_) => commit_of("FETCH_HEAD"),
+        Err(e) => {
+            log.detail(&format!("Fetching {} alone failed: {}", rev, e));
+            log.info(&format!("Fetching full history to find {}", rev));
+            let mut fetch = vec!["fetch", "--quiet"];
+            if shallow {
+                fetch.push("--unshallow");
+            }
+            fetch.push(remote);
+            fetch.extend(REV_REFSPECS);
+            git(&fetch)?;
+            commit_of(rev)
+        }
     }
-
-    let name = output_name
-        .map(|s| s.to_string())
-        .unwrap_or_else(|| extract_repo_name(url));
-
-    scrape_directory(temp_path, destination, &name, args)
+    .ok_or_else(|| {
+        format!(
+            "Unknown revision {}: no commit, tag, or branch of {} has that name",
+            rev, remote
+        )
+    })?;
+    git(&["checkout", "--quiet", "--detach", &commit])?;
+    Ok(commit)
 }
 
-/// Scrapes Solidity files from a local directory.
+/// Runs `git -C <dir> <git_args>` for a `--reuse-clone` step, within the
+/// time left before `--deadline`.
 ///
-/// Processes all Solidity files in the specified local path and writes the
-/// consolidated output. Unlike [`scrape_from_url`], this operates directly on
-/// the filesystem without cloning.
+/// # Returns
 ///
-/// # Arguments
+/// The trimmed stdout, which may be empty.
 ///
-/// * `path` — The local directory path containing Solidity files
-/// * `destination` — The output directory for the consolidated file
-/// * `output_name` — Optional custom output name; defaults to directory name
-/// * `args` — Configuration affecting scraping behavior
+/// # Errors
+///
+/// Returns `"git {args} failed in {dir}: {stderr}"` when git exits non-zero,
+/// or describes why it could not be run.
+fn git_step(
+    dir: &Path,
+    git_args: &[&str],
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<String, String> {
+    let command = git_args.join(" ");
+    let timeout = time_left(args, &format!("git {}", command))?;
+    let full_args = ["-C".as_ref(), dir.as_os_str()]
+        .into_iter()
+        .chain(git_args.iter().map(|a| a.as_ref()));
+    let output = exec
+        .output_within("git", full_args, None, timeout, log)
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::PermissionDenied => e.to_string(),
+            io::ErrorKind::TimedOut => {
+                format!("git {} did not finish before the --deadline", command)
+            }
+            io::ErrorKind::NotFound => {
+                "Git is not installed or not in PATH. Please install Git first.".to_string()
+            }
+            _ => format!("Failed to execute git: {}", e),
+        })?;
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+    // A clone's origin may carry an access token, which git can echo
+    let stderr = forge_host::redact(&String::from_utf8_lossy(&output.stderr));
+    log.detail(&format!(
+        "Raw git {} stderr:\n{}",
+        command,
+        stderr.replace('\r', "\n")
+    ));
+    let summary = summarize_git_stderr(&stderr);
+    Err(format!(
+        "git {} failed in {}: {}",
+        command,
+        dir.display(),
+        if summary.is_empty() {
+            format!("git exited with {}", output.status)
+        } else {
+            summary
+        }
+    ))
+}
+
+/// Brings the existing clone at `clone` up to date with `url` for
+/// `--reuse-clone`.
+///
+/// Unless `--trust-clone` is given, the clone's `origin` must be `url` (as
+/// compared by [`forge_host::normalize_remote`]). A working tree with uncommitted
+/// changes or untracked files is refused unless `--allow-dirty` is given.
+/// The `--branch` given, or else the checked-out branch (or the remote's
+/// `HEAD`, when detached), is then fetched from `url` and checked out: a
+/// branch is moved to the fetched commit, a detached `HEAD` stays detached. The fetch is `--depth 1` only
+/// when the clone is already shallow, so a full clone keeps its history.
 ///
 /// # Returns
 ///
-/// A [`ScraperResult`] on success.
+/// The revision checked out afterwards.
 ///
 /// # Errors
 ///
 /// | Error | Condition |
 /// |-------|-----------|
-/// | `"Source path does not exist: {path}"` | The specified path doesn't exist |
-/// | `"Source path is not a directory: {path}"` | The path is a file, not a directory |
-///
-/// Additional errors may come from [`scrape_directory`].
+/// | `"Source path does not exist: ..."` | `clone` is missing or not a directory |
+/// | `"--reuse-clone needs a git working tree, ..."` | `clone` is not inside one |
+/// | `"{clone} has no origin remote ..."` | No `origin`, without `--trust-clone` |
+/// | `"{clone} is a clone of {origin}, not {url} ..."` | `origin` differs, without `--trust-clone` |
+/// | `"{clone} has uncommitted changes ..."` | Dirty working tree, without `--allow-dirty` |
+/// | `"git fetch ... failed ..."` | The fetch or checkout failed |
 ///
-/// # Examples
-///
-/// ```rust,ignore
-/// let result = scrape_from_local(
-///     "./my-project",
-///     "./output",
-///     Some("my_contracts"),
-///     &args
-/// )?;
-/// ```
-fn scrape_from_local(
-    path: &str,
-    destination: &str,
-    output_name: Option<&str>,
+/// With `--rev`, the revision is checked out instead, detached, as
+/// [`checkout_revision`] does.
+fn refresh_clone(
+    clone: &Path,
+    url: &str,
     args: &Args,
-) -> Result<ScraperResult, String> {
-    let source_path = Path::new(path);
+    exec: &Exec,
+    log: &Logger,
+) -> Result<Option<GitRevision>, String> {
+    let display = clone.display();
+    check_local_source(&clone.to_string_lossy())?;
+    let git = |git_args: &[&str]| git_step(clone, git_args, args, exec, log);
+    if git(&["rev-parse", "--is-inside-work-tree"]).ok().as_deref() != Some("true") {
+        return Err(format!(
+            "--reuse-clone needs a git working tree, but {} is not one",
+            display
+        ));
+    }
 
-    if !source_path.exists() {
-        return Err(format!("Source path does not exist: {}", path));
+    if args.trust_clone {
+        log.detail("Not checking the clone's origin (--trust-clone)");
+    } else {
+        let origin = git(&["remote", "get-url", "origin"]).map_err(|_| {
+            format!(
+                "{} has no origin remote; pass --trust-clone to use it anyway",
+                display
+            )
+        })?;
+        if forge_host::normalize_remote(&origin) != forge_host::normalize_remote(url) {
+            return Err(format!(
+                "{} is a clone of {}, not {}; pass --trust-clone to use it anyway",
+                display, origin, url
+            ));
+        }
     }
 
-    if !source_path.is_dir() {
-        return Err(format!("Source path is not a directory: {}", path));
+    let status = git(&["status", "--porcelain"])?;
+    if !status.is_empty() {
+        let paths = status.lines().count();
+        let summary = format!(
+            "{} has uncommitted changes ({} path{})",
+            display,
+            paths,
+            if paths == 1 { "" } else { "s" }
+        );
+        if !args.allow_dirty {
+            return Err(format!(
+                "{}; commit or stash them, or pass --allow-dirty",
+                summary
+            ));
+        }
+        log.warn(&format!("{}; scraping them (--allow-dirty)", summary));
     }
 
-    if !args.quiet {
-        println!("Scanning local directory...");
+    if let Some(rev) = &args.rev {
+        log.info(&format!("Checking out {} in {} from {}", rev, display, url));
+        let pinned = checkout_revision(clone, url, rev, args, exec, log)?;
+        let revision = detect_git_revision(clone, exec, log).map(|revision| GitRevision {
+            pinned: Some(pinned),
+            ..revision
+        });
+        log.detail(&format!("Refreshed clone is at revision: {:?}", revision));
+        return Ok(revision);
     }
 
-    let name = output_name.map(|s| s.to_string()).unwrap_or_else(|| {
-        source_path
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| "local".to_string())
-    });
+    let branch = match &args.url_ref {
+        Some(git_ref) => Some(git_ref.clone()),
+        None => git(&["symbolic-ref", "--quiet", "--short", "HEAD"]).ok(),
+    };
+    let shallow = git(&["rev-parse", "--is-shallow-repository"])
+        .ok()
+        .as_deref()
+        == Some("true");
+    let wanted = branch.as_deref().unwrap_or("HEAD");
+    log.info(&format!("Refreshing {} ({}) from {}", display, wanted, url));
+    let mut fetch = vec!["fetch", "--quiet"];
+    if shallow {
+        fetch.extend(["--depth", "1"]);
+    }
+    fetch.extend([url, wanted]);
+    git(&fetch)?;
+    match &branch {
+        Some(branch) => git(&["checkout", "--quiet", "-B", branch, "FETCH_HEAD"])?,
+        None => git(&["checkout", "--quiet", "--detach", "FETCH_HEAD"])?,
+    };
 
-    scrape_directory(source_path, destination, &name, args)
+    let revision = detect_git_revision(clone, exec, log);
+    log.detail(&format!("Refreshed clone is at revision: {:?}", revision));
+    Ok(revision)
 }
 
-// ============================================================================
-// Temporary Directory (simple implementation)
-// ============================================================================
+/// The installed git's version, read once per process.
+static GIT_VERSION: OnceLock<Option<GitVersion>> = OnceLock::new();
 
-/// A minimal temporary directory implementation with automatic cleanup.
+/// Returns the version of the installed git, running `git --version` on the
+/// first call only.
 ///
-/// This module provides `TempDir` and `tempdir()` as a zero-dependency
-/// alternative to the `tempfile` crate. Temporary directories are automatically
-/// removed when the `TempDir` is dropped.
+/// `None` when git cannot be run under `exec` or its output is not
+/// understood; the git commands that follow then report the problem.
+fn installed_git_version(exec: &Exec, log: &Logger) -> Option<GitVersion> {
+    *GIT_VERSION.get_or_init(|| {
+        let output = exec.output("git", ["--version"], None, log).ok()?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let version = GitVersion::parse(&text);
+        if version.is_none() {
+            log.detail(&format!("Unrecognized git version: {}", text.trim()));
+        }
+        version
+    })
+}
+
+/// Fails before any git command runs when a flag needs a newer git than
+/// the installed one (see [`git_version`]).
 ///
-/// # Design Notes
+/// Only an explicit `--precheck` is checked here; the default pre-check is
+/// skipped instead (see [`precheck_supported`]).
 ///
-/// Directory names are generated using nanosecond timestamps to ensure uniqueness.
-/// The cleanup on drop uses best-effort semantics—errors are silently ignored.
-mod tempfile {
-    use std::fs;
-    use std::path::PathBuf;
-    use std::time::{SystemTime, UNIX_EPOCH};
-
-    /// A temporary directory that is automatically removed on drop.
-    ///
-    /// Created via [`tempdir`], this struct owns a directory in the system's
-    /// temporary directory. The directory and all its contents are deleted
-    /// when this struct is dropped.
-    ///
-    /// # Lifecycle
-    ///
-    /// - **Construction** ([`tempdir`]): Creates a new directory with a unique name
-    /// - **Clone**: Not implemented; temporary directories are single-owner
-    /// - **Drop**: Recursively deletes the directory and all contents
-    ///
-    /// # Examples
-    ///
-    /// ```rust,ignore
-    /// let temp = tempdir()?;
-    /// let file_path = temp.path().join("data.txt");
-    /// std::fs::write(&file_path, "hello")?;
-    /// // Directory is deleted when `temp` goes out of scope
-    /// ```
-    pub struct TempDir {
-        /// The absolute path to the temporary directory.
-        path: PathBuf,
+/// # Errors
+///
+/// Returns `"requires git >= {minimum} for {flag}, found {found}"`.
+fn check_git_version(args: &Args, exec: &Exec, log: &Logger) -> Result<(), String> {
+    let mut features = Vec::new();
+    if args.precheck == Some(true) && wants_precheck(args) {
+        features.push(&git_version::PRECHECK);
     }
-
-    impl TempDir {
-        /// The path to this temporary directory.
-        pub fn path(&self) -> &std::path::Path {
-            &self.path
-        }
+    if args.since.is_some() {
+        features.push(&git_version::SINCE);
     }
-
-    impl Drop for TempDir {
-        fn drop(&mut self) {
-            let _ = fs::remove_dir_all(&self.path);
-        }
+    if args.reuse_clone.is_some() {
+        features.push(&git_version::REUSE_CLONE);
     }
+    if args.rev.is_some() && clones_source(args) && !args.no_git {
+        features.push(&git_version::REV);
+    }
+    if features.is_empty() {
+        return Ok(());
+    }
+    let found = installed_git_version(exec, log);
+    features
+        .into_iter()
+        .try_for_each(|feature| git_version::check(found, feature))
+}
 
-    /// Creates a new temporary directory with a unique name.
-    ///
-    /// The directory is created in the system's temporary directory (e.g., `/tmp`
-    /// on Unix) with a name in the format `solscrape_{timestamp}`.
-    ///
-    /// # Returns
-    ///
-    /// A [`TempDir`] that will be automatically cleaned up on drop.
-    ///
-    /// # Errors
-    ///
-    /// Returns an I/O error if the directory cannot be created.
-    ///
-    /// # Examples
-    ///
-    /// ```rust,ignore
-    /// let temp = tempdir()?;
-    /// println!("Using temp dir: {}", temp.path().display());
-    /// ```
-    pub fn tempdir() -> std::io::Result<TempDir> {
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_nanos();
-
-        let temp_base = std::env::temp_dir();
-        let dir_name = format!("solscrape_{}", timestamp);
-        let path = temp_base.join(dir_name);
-
-        fs::create_dir_all(&path)?;
-
-        Ok(TempDir { path })
+/// Returns false, noting why, when the pre-check was not asked for and the
+/// installed git is too old for it.
+fn precheck_supported(args: &Args, exec: &Exec, log: &Logger) -> bool {
+    if args.precheck.is_some() {
+        return true;
+    }
+    if git_missing() {
+        log.detail("Skipping the pre-check: git is not installed");
+        return false;
+    }
+    match git_version::check(installed_git_version(exec, log), &git_version::PRECHECK) {
+        Ok(()) => true,
+        Err(e) => {
+            log.detail(&format!("Skipping the pre-check, which {}", e));
+            false
+        }
     }
 }
 
-// ============================================================================
-// Entry Point
-// ============================================================================
+/// Returns true if the remote should be pre-checked before cloning.
+///
+/// `--precheck` and `--no-precheck` decide; otherwise only GitHub HTTPS URLs
+/// are checked, and not in quiet mode or with `--no-git`.
+fn wants_precheck(args: &Args) -> bool {
+    clones_source(args)
+        && !args.no_git
+        && args
+            .precheck
+            .unwrap_or_else(|| !args.quiet && precheck::is_github_https(&args.source))
+}
 
-/// Executes the main scraping workflow based on command-line arguments.
+/// Assesses the remote's default branch without downloading file contents.
 ///
-/// This is the core application logic, separated from `main` to enable proper
-/// error handling with the `?` operator. It parses arguments, performs the
-/// scraping operation, and prints results.
+/// Runs `git ls-remote` to confirm the remote is reachable, then makes a
+/// shallow, blob-less clone into a throwaway temporary directory and lists
+/// its tree with `git ls-tree`. An empty remote yields an empty assessment.
 ///
-/// # Returns
+/// # Errors
 ///
-/// - `Ok(())` — Scraping completed successfully (or help/version was shown)
-/// - `Err(message)` — An error occurred; message is suitable for display
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Precheck failed: cannot reach {url}: ..."` | `git ls-remote` failed |
+/// | `"Precheck failed: Git clone failed: ..."` | The blob-less clone failed |
+/// | `"Failed to create temp dir: {e}"` | The throwaway directory could not be created |
+fn precheck_remote(
+    url: &str,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<precheck::Assessment, String> {
+    let output = exec
+        .output("git", ["ls-remote", "--symref", url, "HEAD"], None, log)
+        .map_err(|e| format!("Precheck failed: {}", e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log.detail(&format!("Raw git ls-remote stderr:\n{}", stderr));
+        return Err(format!(
+            "Precheck failed: cannot reach {}: {}",
+            url,
+            summarize_git_stderr(&stderr)
+        ));
+    }
+    if String::from_utf8_lossy(&output.stdout).trim().is_empty() {
+        return Ok(precheck::assess::<&str>(&[]));
+    }
+
+    let temp_dir = tempfile::tempdir_in(&temp_base_dir(args))
+        .map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    clone_repository(
+        url,
+        temp_dir.path(),
+        &[
+            "--depth",
+            "1",
+            "--filter=blob:none",
+            "--no-checkout",
+            "--quiet",
+        ],
+        args,
+        exec,
+        log,
+    )
+    .map_err(|e| format!("Precheck failed: {}", e))?;
+    let listing = git_output(
+        temp_dir.path(),
+        &["ls-tree", "-r", "-z", "--name-only", "HEAD"],
+        exec,
+        log,
+    )
+    .unwrap_or_default();
+    let paths: Vec<&str> = listing.split('\0').filter(|p| !p.is_empty()).collect();
+    Ok(precheck::assess(&paths))
+}
+
+/// Pre-checks `url` and asks whether to go on when it looks unlikely to
+/// contain Solidity.
 ///
-/// # Behavioral Contract
+/// Under `--yes` or `--quiet` nothing is asked and the scrape continues with
+/// a warning. Without a terminal to ask on, the scrape stops instead.
 ///
-/// - If `--help` or `--version` is passed, prints the requested info and returns `Ok`
-/// - In quiet mode, only the output path is printed to stdout
-/// - In normal mode, a banner, progress messages, and summary are printed
-fn run() -> Result<(), String> {
-    let args = parse_args()?;
-
-    if args.show_help {
-        print_help();
+/// # Errors
+///
+/// Returns the pre-check's error, `"Cancelled: ..."` when the user declines,
+/// or a message suggesting `--yes` when stdin is not a terminal.
+fn confirm_remote(url: &str, args: &Args, exec: &Exec, log: &Logger) -> Result<(), String> {
+    let assessment = log.phase("precheck", || precheck_remote(url, args, exec, log))?;
+    log.detail(&format!("Precheck: {:?}", assessment));
+    let Some(concern) = assessment.concern(url) else {
         return Ok(());
-    }
+    };
 
-    if args.show_version {
-        print_version();
+    if args.assume_yes || args.quiet {
+        log.warn(&format!("{}; cloning anyway", concern));
         return Ok(());
     }
-
-    if !args.quiet {
-        print_banner();
-        println!("Source:      {}", args.source);
-        println!("Destination: {}", args.destination);
-        println!();
+    let stdin = io::stdin();
+    if !stdin.is_terminal() {
+        return Err(format!(
+            "{}. Pass --yes to scrape it anyway or --no-precheck to skip this check",
+            concern
+        ));
     }
-
-    let output_name = args.output_name.as_deref();
-
-    let result = if args.is_local {
-        scrape_from_local(&args.source, &args.destination, output_name, &args)?
+    let confirmed = precheck::confirm(
+        &format!("{}. Clone anyway?", concern),
+        &mut stdin.lock(),
+        &mut io::stderr(),
+    )
+    .map_err(|e| format!("Failed to read confirmation: {}", e))?;
+    if confirmed {
+        Ok(())
     } else {
-        scrape_from_url(&args.source, &args.destination, output_name, &args)?
-    };
+        Err(format!("Cancelled: {}", concern))
+    }
+}
 
-    if args.quiet {
-        println!("{}", result.output_path.display());
-    } else {
-        println!();
-        println!("════════════════════════════════════════════════════════════════");
-        println!("✅ Success!");
-        println!("   Files processed: {}", result.file_count);
-        println!("   Total lines:     {}", result.line_count);
-        println!("   Output:          {}", result.output_path.display());
-        println!("════════════════════════════════════════════════════════════════");
+/// Returns true if the size of the remote should be probed before cloning.
+///
+/// Always with `--max-clone-size`; otherwise only for the large-clone
+/// warning, which `--quiet` leaves out.
+fn wants_size_probe(args: &Args) -> bool {
+    clones_source(args) && (args.max_clone_size.is_some() || !args.quiet)
+}
 
-        if result.file_count <= 25 {
-            println!("\nFiles included:");
-            for f in &result.files_processed {
-                println!("  • {}", f);
+/// Probes the size of `url` and asks whether to go on when it is over
+/// `--max-clone-size` (see [`precheck::decide_size`]).
+///
+/// # Errors
+///
+/// Returns the refusal when stdin is not a terminal, `"Cancelled: ..."` when
+/// the user declines, or an error reading the answer.
+fn confirm_clone_size(url: &str, args: &Args, exec: &Exec, log: &Logger) -> Result<(), String> {
+    let size = log.phase("size-probe", || precheck::probe_size(url, exec, log));
+    log.detail(&format!("Reported clone size: {:?}", size));
+    let decision = precheck::decide_size(
+        url,
+        size,
+        args.max_clone_size,
+        args.assume_yes,
+        io::stdin().is_terminal(),
+    );
+    match decision {
+        precheck::SizeDecision::Proceed => Ok(()),
+        precheck::SizeDecision::Warn(message) => {
+            log.warn(&message);
+            Ok(())
+        }
+        precheck::SizeDecision::Refuse(message) => Err(message),
+        precheck::SizeDecision::Ask(concern) => {
+            let confirmed = precheck::confirm(
+                &format!("{}. Clone anyway?", concern),
+                &mut io::stdin().lock(),
+                &mut io::stderr(),
+            )
+            .map_err(|e| format!("Failed to read confirmation: {}", e))?;
+            if confirmed {
+                Ok(())
+            } else {
+                Err(format!("Cancelled: {}", concern))
             }
         }
     }
+}
 
-    Ok(())
+/// Formats the summary's source description, e.g. `url (branch: main, commit: abc1234)`.
+///
+/// Sources without git information are shown unchanged.
+fn describe_source(source: &str, revision: Option<&GitRevision>) -> String {
+    match revision {
+        Some(rev) => format!(
+            "{} (branch: {}, commit: {})",
+            source, rev.branch, rev.commit
+        ),
+        None => source.to_string(),
+    }
 }
 
-/// The program entry point.
+/// Renders the summary note for a scrape that included a single file, or
+/// `None` when several files were consolidated.
 ///
-/// Delegates to [`run`] for the main logic and converts the result into an
-/// appropriate [`ExitCode`]. Errors are printed to stderr with a visual indicator.
+/// The note names what the output adds to the file: its header, the pragma
+/// `--inject-pragma` gave it, and the project context `--with-context`
+/// appended.
 ///
-/// # Exit Codes
+/// # Examples
 ///
-/// - `0` — Success
-/// - `1` — Any error occurred
-fn main() -> ExitCode {
-    match run() {
-        Ok(()) => ExitCode::SUCCESS,
-        Err(e) => {
-            eprintln!("❌ Error: {}", e);
-            ExitCode::FAILURE
+/// ```rust,ignore
+/// let note = render_single_file_note(&result, &Args::default());
+/// // Some("1 file — output is a cleaned copy of src/Token.sol with a header")
+/// ```
+fn render_single_file_note(result: &ScraperResult, args: &Args) -> Option<String> {
+    let path = naming::display_path(result.single_source.as_deref()?);
+    let copy = if args.no_clean {
+        "copy"
+    } else {
+        "cleaned copy"
+    };
+    let mut added = Vec::new();
+    if !args.no_headers {
+        added.push("a header");
+    }
+    if result.pragmas_injected > 0 {
+        added.push("an injected pragma");
+    }
+    if !result.context.is_empty() {
+        added.push("the project context appended");
+    }
+    let added = match added.as_slice() {
+        [] => String::new(),
+        [only] => format!(" with {}", only),
+        [first, second] => format!(" with {} and {}", first, second),
+        [rest @ .., last] => format!(" with {}, and {}", rest.join(", "), last),
+    };
+    Some(format!(
+        "1 file {} output is a {} of {}{}",
+        args.charset.symbols().dash,
+        copy,
+        path,
+        added
+    ))
+}
+
+// ============================================================================
+// File Discovery
+// ============================================================================
+
+/// Recursively discovers all Solidity files in a directory tree.
+///
+/// Walks the directory tree starting from `dir`, collecting paths to all `.sol`
+/// files in directories the policy lets it enter. File rules (such as
+/// Foundry's `*.t.sol`) are applied afterwards by [`Selection`]. Results are
+/// sorted alphabetically for deterministic output ordering.
+///
+/// # Arguments
+///
+/// * `dir` — The root directory to search
+/// * `policy` — Decides which directories are entered
+///
+/// # Returns
+///
+/// A sorted vector of absolute paths to Solidity files.
+///
+/// # Errors
+///
+/// Returns an I/O error if the directory cannot be read.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let policy = exclusion_policy(&project, &args);
+/// let files = find_solidity_files(Path::new("./contracts"), &policy)?;
+/// ```
+fn find_solidity_files(dir: &Path, policy: &ExclusionPolicy) -> io::Result<Vec<PathBuf>> {
+    let mut sol_files = Vec::new();
+    find_solidity_files_recursive(dir, dir, policy, &mut sol_files)?;
+    sol_files.sort();
+    Ok(sol_files)
+}
+
+/// Recursive helper for [`find_solidity_files`].
+///
+/// Traverses subdirectories depth-first, appending found `.sol` file paths to
+/// the accumulator. Directories the policy excludes are skipped.
+fn find_solidity_files_recursive(
+    root: &Path,
+    dir: &Path,
+    policy: &ExclusionPolicy,
+    files: &mut Vec<PathBuf>,
+) -> io::Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.is_dir() {
+            if enters_dir(root, &path, policy) {
+                find_solidity_files_recursive(root, &path, policy, files)?;
+            }
+        } else if path.is_file() && is_sol_file(&path) {
+            files.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns true if `policy` lets discovery enter `dir`, found under `root`.
+fn enters_dir(root: &Path, dir: &Path, policy: &ExclusionPolicy) -> bool {
+    let relative = dir.strip_prefix(root).unwrap_or(dir);
+    policy.decide(relative, true) == Decision::Include
+}
+
+/// Returns true if `path` has the `.sol` extension.
+fn is_sol_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "sol")
+}
+
+/// A lazy, depth-first walk over a directory tree yielding `.sol` files.
+///
+/// Unlike [`find_solidity_files`], which collects and sorts every path before
+/// returning, the walker yields each file as soon as it is found, in
+/// filesystem order. Use it where discovery, cleaning, and writing should be
+/// pipelined (`--stream`); both yield the same file set.
+///
+/// # Behavioral Contract
+///
+/// - Directories the policy excludes are never entered
+/// - Only open directory handles along the current path are held, not the
+///   full list of discovered paths
+/// - I/O errors are yielded as `Err` items; iteration may continue afterwards
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// for path in SolFileWalker::new(Path::new("./contracts"), &policy) {
+///     println!("{}", path?.display());
+/// }
+/// ```
+struct SolFileWalker<'a> {
+    /// The directory the walk started at.
+    root: PathBuf,
+    /// Open directory iterators from the root down to the current directory.
+    stack: Vec<fs::ReadDir>,
+    /// Decides which directories are entered.
+    policy: &'a ExclusionPolicy,
+    /// An error opening the root, reported by the first call to `next`.
+    pending_error: Option<io::Error>,
+    /// How many more entries the walk looks at before it stops early.
+    budget: usize,
+}
+
+impl<'a> SolFileWalker<'a> {
+    /// Starts a walk at `dir`; a non-directory `dir` yields nothing.
+    fn new(dir: &Path, policy: &'a ExclusionPolicy) -> Self {
+        let mut walker = Self {
+            root: dir.to_path_buf(),
+            stack: Vec::new(),
+            policy,
+            pending_error: None,
+            budget: usize::MAX,
+        };
+        if dir.is_dir() {
+            match fs::read_dir(dir) {
+                Ok(entries) => walker.stack.push(entries),
+                Err(e) => walker.pending_error = Some(e),
+            }
+        }
+        walker
+    }
+
+    /// Stops the walk once it has looked at `entries` directory entries,
+    /// files and directories alike.
+    fn with_budget(mut self, entries: usize) -> Self {
+        self.budget = entries;
+        self
+    }
+
+    /// Returns true if the walk stopped at its budget with entries left.
+    fn cut_short(&self) -> bool {
+        self.budget == 0 && !self.stack.is_empty()
+    }
+}
+
+impl Iterator for SolFileWalker<'_> {
+    type Item = io::Result<PathBuf>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.pending_error.take() {
+            return Some(Err(e));
+        }
+
+        loop {
+            if self.budget == 0 {
+                return None;
+            }
+            let entry = match self.stack.last_mut()?.next() {
+                None => {
+                    self.stack.pop();
+                    continue;
+                }
+                Some(Err(e)) => return Some(Err(e)),
+                Some(Ok(entry)) => entry,
+            };
+            self.budget -= 1;
+
+            let path = entry.path();
+            if path.is_dir() {
+                if enters_dir(&self.root, &path, self.policy) {
+                    match fs::read_dir(&path) {
+                        Ok(entries) => self.stack.push(entries),
+                        Err(e) => return Some(Err(e)),
+                    }
+                }
+            } else if path.is_file() && is_sol_file(&path) {
+                return Some(Ok(path));
+            }
+        }
+    }
+}
+
+/// Builds the [`ExclusionPolicy`] for a project of kind `project` under `args`.
+///
+/// `--include-lib`, `--include-test`, and `--include-script` lift the
+/// matching default exclusions; the rules and their precedence are described
+/// in the [`exclusion`] module.
+fn exclusion_policy(project: &project::ProjectKind, args: &Args) -> ExclusionPolicy {
+    ExclusionPolicy::new(
+        project,
+        exclusion::Inclusions {
+            lib: args.include_lib,
+            test: args.include_test,
+            script: args.include_script,
+        },
+    )
+}
+
+/// Stop counting files in an excluded directory after this many.
+const EXCLUDED_COUNT_CAP: usize = 10_000;
+
+/// Stop walking an excluded directory after looking at this many entries.
+const EXCLUDED_VISIT_CAP: usize = 50_000;
+
+/// The `.sol` files found in one excluded directory by
+/// [`count_excluded_files`].
+#[derive(Debug, PartialEq)]
+struct ExcludedCount {
+    /// The directory's name, directly under the source.
+    name: String,
+    /// The files counted.
+    files: usize,
+    /// Whether counting stopped early, so there may be more.
+    at_least: bool,
+}
+
+/// Counts `.sol` files in each excluded directory directly under `source_dir`
+/// that an `--include-*` flag would add.
+///
+/// Directories excluded whatever the flags (`.git`, `node_modules`, ...)
+/// are not walked. Counting walks each directory with the same exclusions
+/// applied inside it, and stops at [`EXCLUDED_COUNT_CAP`] files or
+/// [`EXCLUDED_VISIT_CAP`] entries, so enormous dependency trees stay cheap.
+/// Directories with no Solidity files are omitted.
+///
+/// # Returns
+///
+/// The counts sorted by directory name.
+fn count_excluded_files(source_dir: &Path, policy: &ExclusionPolicy) -> Vec<ExcludedCount> {
+    let Ok(entries) = fs::read_dir(source_dir) else {
+        return Vec::new();
+    };
+
+    let mut counts: Vec<ExcludedCount> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| {
+            policy.excludes_dir_name(name)
+                && exclusion::Reason::Directory(name.clone())
+                    .lifted_by()
+                    .is_some()
+        })
+        .map(|name| {
+            let mut walker =
+                SolFileWalker::new(&source_dir.join(&name), policy).with_budget(EXCLUDED_VISIT_CAP);
+            let files = walker
+                .by_ref()
+                .filter(Result::is_ok)
+                .take(EXCLUDED_COUNT_CAP)
+                .count();
+            let at_least = files == EXCLUDED_COUNT_CAP || walker.cut_short();
+            ExcludedCount {
+                name,
+                files,
+                at_least,
+            }
+        })
+        .filter(|count| count.files > 0)
+        .collect();
+
+    counts.sort_by(|a, b| a.name.cmp(&b.name));
+    counts
+}
+
+/// Renders the one-line summary of excluded directories that contain files.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let counts = count_excluded_files(source_dir, policy);
+/// let line = render_excluded_summary(&counts, &symbols::UNICODE);
+/// // "Excluded: lib (1,204 files), test (312 files) — use --include-* to add"
+/// ```
+fn render_excluded_summary(counts: &[ExcludedCount], symbols: &Symbols) -> Option<String> {
+    if counts.is_empty() {
+        return None;
+    }
+
+    let parts: Vec<String> = counts
+        .iter()
+        .map(|count| {
+            let cap = if count.at_least { "+" } else { "" };
+            let noun = if count.files == 1 { "file" } else { "files" };
+            format!(
+                "{} ({}{} {})",
+                count.name,
+                group_thousands(count.files),
+                cap,
+                noun
+            )
+        })
+        .collect();
+    Some(format!(
+        "Excluded: {} {} use --include-* to add",
+        parts.join(", "),
+        symbols.dash
+    ))
+}
+
+/// Formats `n` with comma thousands separators (`1204` → `"1,204"`).
+fn group_thousands(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(c);
+    }
+    grouped
+}
+
+/// What a source tree holds, used to explain why no file was selected.
+#[derive(Debug, Default, PartialEq)]
+struct SourceSurvey {
+    /// Files anywhere outside `.git`.
+    files: usize,
+    /// `.sol` files among them, excluded or not.
+    solidity_files: usize,
+    /// File counts by extension, such as `.ts`; `""` for no extension.
+    extensions: std::collections::BTreeMap<String, usize>,
+}
+
+impl SourceSurvey {
+    /// Walks all of `source_dir` except `.git`, without following symlinks.
+    fn of(source_dir: &Path) -> Self {
+        let mut survey = Self::default();
+        let mut stack = vec![source_dir.to_path_buf()];
+        while let Some(dir) = stack.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for entry in entries.filter_map(Result::ok) {
+                let Ok(file_type) = entry.file_type() else {
+                    continue;
+                };
+                let name = entry.file_name().to_string_lossy().to_string();
+                if file_type.is_dir() {
+                    if name != ".git" {
+                        stack.push(entry.path());
+                    }
+                    continue;
+                }
+                if name == ".git" {
+                    // A submodule's pointer file, not one of the repository's files
+                    continue;
+                }
+                survey.files += 1;
+                if name.ends_with(".sol") {
+                    survey.solidity_files += 1;
+                }
+                let extension = match name.rfind('.') {
+                    Some(dot) if dot > 0 => name[dot..].to_ascii_lowercase(),
+                    _ => String::new(),
+                };
+                *survey.extensions.entry(extension).or_default() += 1;
+            }
+        }
+        survey
+    }
+
+    /// The most common extensions, most frequent first, as `.ts 312, .js 120`.
+    fn top_file_types(&self, limit: usize) -> String {
+        let mut counts: Vec<(&String, &usize)> = self.extensions.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        counts
+            .iter()
+            .take(limit)
+            .map(|(extension, count)| {
+                let extension = if extension.is_empty() {
+                    "no extension"
+                } else {
+                    extension.as_str()
+                };
+                format!("{} {}", extension, group_thousands(**count))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Explains why no Solidity file was selected from `source_dir`.
+///
+/// An empty tree, a tree without any `.sol` file, and Solidity that was all
+/// excluded need different remedies, so each gets its own message and exit
+/// code.
+///
+/// | Situation | Message | Exit code |
+/// |-----------|---------|-----------|
+/// | No files outside `.git` | `"Repository is empty ..."` | [`EXIT_EMPTY_SOURCE`] |
+/// | Files, but no `.sol` anywhere | `"Repository contains no Solidity (top file types: ...)"` | [`EXIT_NO_SOLIDITY`] |
+/// | Every `.sol` file excluded | `"No Solidity files found in the source: ..."` | [`EXIT_NO_FILES`] |
+fn no_files_error(source_dir: &Path, policy: &ExclusionPolicy, symbols: &Symbols) -> RunError {
+    let survey = SourceSurvey::of(source_dir);
+    if survey.files == 0 {
+        return RunError {
+            message: "Repository is empty: no files outside .git".to_string(),
+            code: EXIT_EMPTY_SOURCE,
+        };
+    }
+    if survey.solidity_files == 0 {
+        return RunError {
+            message: format!(
+                "Repository contains no Solidity (top file types: {})",
+                survey.top_file_types(3)
+            ),
+            code: EXIT_NO_SOLIDITY,
+        };
+    }
+
+    let noun = if survey.solidity_files == 1 {
+        "file was"
+    } else {
+        "files were"
+    };
+    let attribution = render_excluded_summary(&count_excluded_files(source_dir, policy), symbols)
+        .unwrap_or_else(|| "run with --verbose to see why".to_string());
+    RunError {
+        message: format!(
+            "No Solidity files found in the source: all {} .sol {} excluded. {}",
+            group_thousands(survey.solidity_files),
+            noun,
+            attribution
+        ),
+        code: EXIT_NO_FILES,
+    }
+}
+
+/// Logs the summary of excluded directories that contain Solidity files,
+/// counted under the directory the selection scans.
+fn report_excluded(selection: &Selection, log: &Logger) {
+    let counts = count_excluded_files(&selection.scan_root, &selection.policy);
+    if let Some(line) = render_excluded_summary(&counts, selection.charset.symbols()) {
+        log.info(&line);
+    }
+}
+
+/// The error for a `--subdir` directory that the source at `source_dir`
+/// lacks, naming the top-level directories it has instead.
+fn missing_subdir(source_dir: &Path, source: &str, subdir: &str) -> String {
+    let mut names: Vec<String> = fs::read_dir(source_dir)
+        .into_iter()
+        .flatten()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .map(|name| naming::display_path(&name))
+        .collect();
+    names.sort();
+    let available = if names.is_empty() {
+        "it has no top-level directories".to_string()
+    } else {
+        format!("top-level directories: {}", names.join(", "))
+    };
+    format!(
+        "{} has no directory {} ({})",
+        source,
+        naming::display_path(subdir),
+        available
+    )
+}
+
+/// Picks the directory to discover files in for a detected project.
+///
+/// For Hardhat projects whose config sets `paths.sources`, that directory is
+/// scanned instead of the whole tree; output paths stay relative to
+/// `source_dir`. Configs that cannot be read, or name a missing directory or
+/// one outside the tree, fall back to `source_dir` with a detail-level note.
+fn source_root(source_dir: &Path, project: &project::ProjectKind, log: &Logger) -> PathBuf {
+    if !project.frameworks.contains(&project::Framework::Hardhat) {
+        return source_dir.to_path_buf();
+    }
+    let Some(configured) = project::hardhat_sources(source_dir) else {
+        log.detail("No paths.sources override found in the Hardhat config");
+        return source_dir.to_path_buf();
+    };
+
+    let relative = Path::new(&configured);
+    let escapes = relative.is_absolute()
+        || relative
+            .components()
+            .any(|c| matches!(c, std::path::Component::ParentDir));
+    let dir = source_dir.join(relative);
+    if escapes || !dir.is_dir() {
+        log.detail(&format!(
+            "Ignoring Hardhat paths.sources \"{}\": not a directory inside the source",
+            configured
+        ));
+        return source_dir.to_path_buf();
+    }
+
+    log.info(&format!("Using Hardhat sources directory: {}", configured));
+    dir
+}
+
+// ============================================================================
+// File Processing
+// ============================================================================
+
+/// The result of processing a single Solidity file.
+#[derive(Debug)]
+enum FileOutcome {
+    /// The cleaned content (with optional header), ready for consolidation.
+    Content {
+        /// The text to append to the consolidated output.
+        text: String,
+        /// What cleaning removed from the file.
+        stats: CleanStats,
+        /// Structural warnings about the cleaned code (see [`structure`]).
+        findings: Vec<String>,
+        /// Markers left in the text, with `--flag-todos` (see [`todo`]).
+        todos: Vec<todo::Marker>,
+        /// Custom errors and revert reasons, with `--errors-report` (see
+        /// [`error_report`]).
+        errors: Box<error_report::FileErrors>,
+        /// The license and imports, for `--gate` (see [`gate`]).
+        facts: Box<gate::FileFacts>,
+        /// Whether `--inject-pragma` added a `pragma solidity` line.
+        pragma_injected: bool,
+        /// The [`checksum::content_digest`] of the cleaned code, or `None`
+        /// for consolidated output kept as is.
+        digest: Option<String>,
+    },
+    /// The file was empty after cleaning.
+    Empty,
+    /// The file was left out; see [`skip`].
+    Skipped(Skip),
+}
+
+/// A file on its way through the [`Pipeline`].
+#[derive(Debug, Clone, PartialEq)]
+struct FileContent {
+    /// The file as read from disk.
+    original: String,
+    /// The text as transformed by the stages so far.
+    text: String,
+    /// The file's path relative to the base directory.
+    relative_path: String,
+    /// What the stages so far removed from the file.
+    stats: CleanStats,
+    /// Structural warnings about the cleaned code (see [`structure`]).
+    findings: Vec<String>,
+    /// Markers left in the text, with `--flag-todos` (see [`todo`]).
+    todos: Vec<todo::Marker>,
+    /// Custom errors and revert reasons, with `--errors-report` (see
+    /// [`error_report`]).
+    errors: error_report::FileErrors,
+    /// The license and imports, for `--gate` (see [`gate`]).
+    facts: gate::FileFacts,
+    /// Whether `--inject-pragma` added a `pragma solidity` line.
+    pragma_injected: bool,
+    /// The digest of the cleaned code, once taken.
+    digest: Option<String>,
+}
+
+impl FileContent {
+    /// Starts processing `original`, read from `relative_path`.
+    fn new(original: String, relative_path: String) -> Self {
+        Self {
+            text: original.clone(),
+            original,
+            relative_path,
+            stats: CleanStats::default(),
+            findings: Vec::new(),
+            todos: Vec::new(),
+            errors: error_report::FileErrors::default(),
+            facts: gate::FileFacts::default(),
+            pragma_injected: false,
+            digest: None,
+        }
+    }
+}
+
+/// One named step of per-file processing.
+///
+/// A stage either transforms the [`FileContent`] in place or ends
+/// processing with the file's [`FileOutcome`].
+#[derive(Debug, Clone, PartialEq)]
+enum Stage {
+    /// Skips a file whose first line is [`exclusion::IGNORE_MARKER`], before
+    /// cleaning would remove the marker.
+    OptOutMarker,
+    /// Ends with the text unchanged when the file is itself consolidated
+    /// output, which [`split::parse_sections`] reads: cleaning would strip
+    /// its file headers, so scraping it again would not reproduce it.
+    ConsolidatedInput,
+    /// Removes comments and empty lines with [`clean_solidity`].
+    Clean,
+    /// Keeps the text as-is and only counts its lines (`--no-clean`).
+    Verbatim,
+    /// Applies [`vcs::normalize_whitespace`] (`--vcs-friendly`).
+    NormalizeWhitespace,
+    /// Ends with [`FileOutcome::Empty`] when no code is left.
+    RequireCode,
+    /// Skips a file whose braces nest deeper than the limit, checked on the
+    /// original so the line named is the file's own (see
+    /// [`structure::check_nesting`]).
+    BraceDepth(usize),
+    /// Takes the [`checksum::content_digest`] of the cleaned code, before
+    /// later stages add anything, to find copies of one file.
+    ContentDigest,
+    /// Skips a file whose `pragma solidity` misses `wanted`, explained by
+    /// `unmet` (`--filter-pragma`, `--min-solidity`).
+    PragmaFilter {
+        wanted: pragma::Constraint,
+        policy: PragmaUnknown,
+        unmet: String,
+    },
+    /// Skips a file with no top-level declaration of the wanted kinds
+    /// (`--kinds`); a file that also declares other kinds is kept with a note.
+    KindFilter(Vec<structure::Kind>),
+    /// Skips or, with `rewrap`, re-wraps a file with a line longer than
+    /// [`LONG_LINE_THRESHOLD`] chars.
+    LongLines { rewrap: bool },
+    /// Records the custom errors and revert reasons of the cleaned code with
+    /// [`error_report::scan`] (`--errors-report`), before any reason is
+    /// shortened.
+    ErrorInventory,
+    /// Records the SPDX license and imports of the original file with
+    /// [`gate::FileFacts::scan`], for the `--gate` conditions that need them.
+    GateFacts,
+    /// Shortens `require`/`revert` reasons with
+    /// [`revert::strip_revert_strings`].
+    StripRevertStrings,
+    /// Re-wraps lines wider than the width with [`wrap::wrap`]
+    /// (`--max-line-width`), warning about those it cannot bring within it.
+    MaxLineWidth(usize),
+    /// Records [`structure::structural_findings`] and the declaration counts
+    /// for the cleaned code.
+    Findings,
+    /// Adds `pragma solidity <version>;` to code without one, marked with
+    /// [`INJECTED_PRAGMA_MARKER`].
+    InjectPragma(String),
+    /// Prepends the original file's legal header block (see
+    /// [`extract_license_header`]).
+    LicenseHeader,
+    /// Records the [`todo::MARKERS`] left in the text (`--flag-todos`).
+    TodoMarkers,
+    /// Prepends the header showing the file's relative path, framed by
+    /// `separator`, and its `--related` relation when `relations` has one.
+    /// A file matching one of the `compact` patterns gets the path line
+    /// alone (`--no-headers-for`).
+    FileHeader {
+        separator: &'static str,
+        relations: std::collections::BTreeMap<String, graph::Relation>,
+        compact: Vec<String>,
+    },
+}
+
+impl Stage {
+    /// The stage's name, as listed in verbose output.
+    fn name(&self) -> &'static str {
+        match self {
+            Stage::OptOutMarker => "opt-out-marker",
+            Stage::ConsolidatedInput => "consolidated-input",
+            Stage::Clean => "clean",
+            Stage::Verbatim => "verbatim",
+            Stage::NormalizeWhitespace => "normalize-whitespace",
+            Stage::RequireCode => "require-code",
+            Stage::BraceDepth(_) => "brace-depth",
+            Stage::ContentDigest => "content-digest",
+            Stage::PragmaFilter { .. } => "pragma-filter",
+            Stage::KindFilter(_) => "kind-filter",
+            Stage::LongLines { .. } => "long-lines",
+            Stage::ErrorInventory => "error-inventory",
+            Stage::GateFacts => "gate-facts",
+            Stage::StripRevertStrings => "strip-revert-strings",
+            Stage::MaxLineWidth(_) => "max-line-width",
+            Stage::Findings => "findings",
+            Stage::InjectPragma(_) => "inject-pragma",
+            Stage::LicenseHeader => "license-header",
+            Stage::TodoMarkers => "todo-markers",
+            Stage::FileHeader { .. } => "file-header",
+        }
+    }
+
+    /// Runs the stage on `file`.
+    ///
+    /// # Returns
+    ///
+    /// - `ControlFlow::Continue(())` — Processing goes on with the next stage
+    /// - `ControlFlow::Break(outcome)` — The file is done, as `outcome`
+    fn apply(&self, file: &mut FileContent, log: &Logger) -> ControlFlow<FileOutcome> {
+        match self {
+            Stage::OptOutMarker => {
+                let first = file.text.lines().next();
+                if first.and_then(exclusion::marker) == Some(exclusion::Marker::Ignore) {
+                    return ControlFlow::Break(FileOutcome::Skipped(Skip::new(
+                        SkipReason::OptOutMarker,
+                        format!("opt-out marker (`{}`)", exclusion::IGNORE_MARKER),
+                    )));
+                }
+            }
+            Stage::ConsolidatedInput => {
+                if split::parse_sections(&file.text).is_ok() {
+                    log.info(&format!(
+                        "Keeping {} as is: it is already consolidated output",
+                        naming::display_path(&file.relative_path)
+                    ));
+                    let lines = file.text.lines().count();
+                    return ControlFlow::Break(FileOutcome::Content {
+                        stats: CleanStats {
+                            original_lines: lines,
+                            original_bytes: file.text.len(),
+                            cleaned_lines: lines,
+                            ..CleanStats::default()
+                        },
+                        text: std::mem::take(&mut file.text),
+                        findings: Vec::new(),
+                        todos: Vec::new(),
+                        errors: Box::default(),
+                        facts: Box::default(),
+                        pragma_injected: false,
+                        digest: None,
+                    });
+                }
+            }
+            Stage::Clean => {
+                (file.text, file.stats) = clean_solidity(&file.text);
+            }
+            Stage::Verbatim => {
+                let lines = file.text.lines().count();
+                file.stats = CleanStats {
+                    original_lines: lines,
+                    original_bytes: file.text.len(),
+                    cleaned_lines: lines,
+                    ..CleanStats::default()
+                };
+            }
+            Stage::NormalizeWhitespace => {
+                file.text = vcs::normalize_whitespace(&file.text);
+            }
+            Stage::RequireCode => {
+                if file.text.trim().is_empty() {
+                    return ControlFlow::Break(FileOutcome::Empty);
+                }
+            }
+            Stage::PragmaFilter {
+                wanted,
+                policy,
+                unmet,
+            } => {
+                if let Some(detail) = pragma_mismatch(&file.text, wanted, *policy, unmet) {
+                    return ControlFlow::Break(FileOutcome::Skipped(Skip::new(
+                        SkipReason::Pragma,
+                        detail,
+                    )));
+                }
+            }
+            Stage::KindFilter(wanted) => {
+                let selected: Vec<&str> = wanted.iter().map(|kind| kind.name()).collect();
+                match structure::match_kinds(&file.text, wanted) {
+                    structure::KindMatch::All => {}
+                    structure::KindMatch::Mixed(others) => log.info(&format!(
+                        "Including {} (mixed file): it also declares {}, outside --kinds {}",
+                        naming::display_path(&file.relative_path),
+                        others.join(", "),
+                        selected.join(",")
+                    )),
+                    structure::KindMatch::None(found) => {
+                        let declared = if found.is_empty() {
+                            "declares no contract, interface, or library".to_string()
+                        } else {
+                            format!("declares only {}", found.join(", "))
+                        };
+                        return ControlFlow::Break(FileOutcome::Skipped(Skip::new(
+                            SkipReason::Kind,
+                            format!("{}; --kinds selects {}", declared, selected.join(",")),
+                        )));
+                    }
+                }
+            }
+            Stage::BraceDepth(limit) => {
+                if let Err(too_deep) = structure::check_nesting(&file.original, *limit) {
+                    return ControlFlow::Break(FileOutcome::Skipped(Skip::new(
+                        SkipReason::DeepNesting,
+                        format!("{}; use --max-brace-depth to raise the limit", too_deep),
+                    )));
+                }
+            }
+            Stage::ContentDigest => {
+                file.digest = Some(checksum::content_digest(&file.text));
+            }
+            Stage::LongLines { rewrap } => {
+                let longest = max_line_length(&file.text);
+                if longest <= LONG_LINE_THRESHOLD {
+                    return ControlFlow::Continue(());
+                }
+                let size = format!(
+                    "{} line(s), {} bytes, longest line {} chars",
+                    file.text.lines().count(),
+                    file.text.len(),
+                    longest
+                );
+                if !rewrap {
+                    return ControlFlow::Break(FileOutcome::Skipped(Skip::new(
+                        SkipReason::LongLines,
+                        format!("{}; use --rewrap-long-lines to include it", size),
+                    )));
+                }
+                file.text = rewrap_long_lines(&file.text, LONG_LINE_THRESHOLD);
+                file.stats.cleaned_lines = file.text.lines().count();
+                log.info(&format!(
+                    "Re-wrapped {} ({}) into {} lines",
+                    naming::display_path(&file.relative_path),
+                    size,
+                    file.stats.cleaned_lines
+                ));
+            }
+            Stage::StripRevertStrings => {
+                let (stripped, saved) = revert::strip_revert_strings(&file.text);
+                file.text = stripped;
+                file.stats.revert_chars_saved = saved;
+            }
+            Stage::MaxLineWidth(width) => {
+                let wrapped = wrap::wrap(&file.text, *width);
+                if wrapped.too_wide > 0 {
+                    log.warn(&format!(
+                        "{} line(s) of {} are still wider than --max-line-width {}, \
+                         with no safe place to break them (such as a long string literal)",
+                        wrapped.too_wide,
+                        naming::display_path(&file.relative_path),
+                        width
+                    ));
+                }
+                file.text = wrapped.text;
+                file.stats.cleaned_lines = file.text.lines().count();
+            }
+            Stage::Findings => {
+                file.findings = structure::structural_findings(&file.text);
+                file.stats.declarations = structure::KindCounts::of(&file.text);
+            }
+            Stage::InjectPragma(version) => {
+                if !structure::has_pragma_solidity(&file.text) {
+                    file.text = format!(
+                        "pragma solidity {}; {}\n{}",
+                        version, INJECTED_PRAGMA_MARKER, file.text
+                    );
+                    file.stats.cleaned_lines += 1;
+                    file.pragma_injected = true;
+                }
+            }
+            Stage::LicenseHeader => {
+                if let Some(license) = extract_license_header(&file.original) {
+                    file.text = format!("{}\n{}", license, file.text);
+                }
+            }
+            Stage::TodoMarkers => {
+                file.todos = todo::scan(&file.text, &file.original);
+            }
+            Stage::ErrorInventory => {
+                file.errors = error_report::scan(&file.text);
+            }
+            Stage::GateFacts => {
+                file.facts = gate::FileFacts::scan(&file.original);
+            }
+            Stage::FileHeader {
+                separator,
+                relations,
+                compact,
+            } => {
+                let shown = match relations.get(&file.relative_path) {
+                    Some(relation) => naming::related_header_path(&file.relative_path, *relation),
+                    None => naming::header_path(&file.relative_path),
+                };
+                let path_line = format!("{}{}", HEADER_FILE_PREFIX, shown);
+                file.text = if compact
+                    .iter()
+                    .any(|pattern| glob::matches(pattern, &file.relative_path))
+                {
+                    format!("{}\n{}", path_line, file.text)
+                } else {
+                    format!("{}\n{}\n{}\n{}", separator, path_line, separator, file.text)
+                };
+            }
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+/// The ordered [`Stage`]s every file goes through, built once per scrape.
+#[derive(Debug, Clone, PartialEq)]
+struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    /// Builds the stages `args` asks for, in processing order.
+    ///
+    /// Consolidated output fed back in is kept as it is, before any stage
+    /// could change it, so scraping a scraped file reproduces it.
+    /// Pragma filters see the cleaned code, so a pragma inside a comment
+    /// does not count; the structural findings are taken before a pragma is
+    /// injected, markers are looked for in everything but the file header,
+    /// and the headers go on last.
+    fn from_args(args: &Args) -> Self {
+        let mut stages = vec![Stage::OptOutMarker, Stage::ConsolidatedInput];
+        stages.push(if args.no_clean {
+            Stage::Verbatim
+        } else {
+            Stage::Clean
+        });
+        if args.vcs_friendly {
+            stages.push(Stage::NormalizeWhitespace);
+        }
+        stages.push(Stage::RequireCode);
+        stages.push(Stage::BraceDepth(
+            args.max_brace_depth.unwrap_or(structure::MAX_BRACE_DEPTH),
+        ));
+        stages.push(Stage::ContentDigest);
+
+        let policy = args.pragma_unknown.unwrap_or(PragmaUnknown::Include);
+        if let Some(wanted) = &args.filter_pragma {
+            stages.push(Stage::PragmaFilter {
+                wanted: wanted.clone(),
+                policy,
+                unmet: format!("is outside --filter-pragma {}", wanted),
+            });
+        }
+        if let Some(min) = &args.min_solidity {
+            if let Ok(wanted) = pragma::Constraint::at_least(min) {
+                stages.push(Stage::PragmaFilter {
+                    wanted,
+                    policy,
+                    unmet: format!("only allows compilers below --min-solidity {}", min),
+                });
+            }
+        }
+
+        if let Some(kinds) = &args.kinds {
+            stages.push(Stage::KindFilter(kinds.clone()));
+        }
+        stages.push(Stage::LongLines {
+            rewrap: args.rewrap_long_lines,
+        });
+        if args.errors_report.is_some() {
+            stages.push(Stage::ErrorInventory);
+        }
+        if args.gates.iter().any(gate::Gate::needs_facts) {
+            stages.push(Stage::GateFacts);
+        }
+        if args.strip_revert_strings {
+            stages.push(Stage::StripRevertStrings);
+        }
+        if let Some(width) = args.max_line_width {
+            stages.push(Stage::MaxLineWidth(width));
+        }
+        stages.push(Stage::Findings);
+        if let Some(version) = &args.inject_pragma {
+            stages.push(Stage::InjectPragma(version.clone()));
+        }
+        // Verbatim content already starts with its license block
+        if args.keep_license_headers && !args.no_clean {
+            stages.push(Stage::LicenseHeader);
+        }
+        if args.flag_todos {
+            stages.push(Stage::TodoMarkers);
+        }
+        if !args.no_headers {
+            stages.push(Stage::FileHeader {
+                separator: args.output_charset.symbols().header_separator,
+                relations: std::collections::BTreeMap::new(),
+                compact: args.no_headers_for.clone(),
+            });
+        }
+        Self { stages }
+    }
+
+    /// Notes each file's relation to the `--related` target in its header.
+    fn with_relations(
+        mut self,
+        relations: std::collections::BTreeMap<String, graph::Relation>,
+    ) -> Self {
+        for stage in &mut self.stages {
+            if let Stage::FileHeader {
+                relations: noted, ..
+            } = stage
+            {
+                *noted = relations.clone();
+            }
+        }
+        self
+    }
+
+    /// The names of the stages, in order.
+    fn names(&self) -> Vec<&'static str> {
+        self.stages.iter().map(Stage::name).collect()
+    }
+
+    /// Reads `path` and runs it through every stage.
+    ///
+    /// Git LFS pointers, binary files, and files that are not UTF-8 are
+    /// skipped before any stage sees them (see [`non_text_reason`]).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` — Absolute path to the Solidity file
+    /// * `base_dir` — Base directory for computing relative paths in headers
+    /// * `log` — Destination for notes about re-wrapped files
+    ///
+    /// # Errors
+    ///
+    /// Returns the I/O error when the file cannot be read.
+    fn run(&self, path: &Path, base_dir: &Path, log: &Logger) -> io::Result<FileOutcome> {
+        let bytes = fs::read(path)?;
+        if let Some(skip) = non_text_reason(&bytes) {
+            return Ok(FileOutcome::Skipped(skip));
+        }
+        let original = match String::from_utf8(bytes) {
+            Ok(original) => original,
+            Err(e) => {
+                return Ok(FileOutcome::Skipped(Skip::new(
+                    SkipReason::NotUtf8,
+                    format!(
+                        "not valid UTF-8 (invalid byte at offset {}); re-encode the file as UTF-8",
+                        e.utf8_error().valid_up_to()
+                    ),
+                )));
+            }
+        };
+        let mut file = FileContent::new(original, naming::relative_path(path, base_dir));
+        for stage in &self.stages {
+            if let ControlFlow::Break(outcome) = stage.apply(&mut file, log) {
+                return Ok(outcome);
+            }
+        }
+        Ok(FileOutcome::Content {
+            text: file.text,
+            stats: file.stats,
+            findings: file.findings,
+            todos: file.todos,
+            errors: Box::new(file.errors),
+            facts: Box::new(file.facts),
+            pragma_injected: file.pragma_injected,
+            digest: file.digest,
+        })
+    }
+}
+
+/// Processes a single Solidity file and returns its cleaned content.
+///
+/// Builds the [`Pipeline`] for `args` and runs the file through it; callers
+/// processing many files build the pipeline once and call
+/// [`Pipeline::run`] instead. See [`Stage`] for what each step does.
+///
+/// # Arguments
+///
+/// * `path` — Absolute path to the Solidity file
+/// * `base_dir` — Base directory for computing relative paths in headers
+/// * `args` — Configuration selecting the stages
+/// * `log` — Destination for notes about re-wrapped files
+///
+/// # Returns
+///
+/// - `Ok(FileOutcome::Content { .. })` — The cleaned file content (with
+///   optional header), its [`CleanStats`], structural warnings, and whether a
+///   pragma was injected
+/// - `Ok(FileOutcome::Empty)` — The file was empty after cleaning
+/// - `Ok(FileOutcome::Skipped(skip))` — The file was left out, with its [`SkipReason`]
+/// - `Err(e)` — The file could not be read
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let outcome = process_file(
+///     Path::new("/project/src/Token.sol"),
+///     Path::new("/project"),
+///     &args,
+///     &log
+/// )?;
+/// ```
+fn process_file(
+    path: &Path,
+    base_dir: &Path,
+    args: &Args,
+    log: &Logger,
+) -> io::Result<FileOutcome> {
+    Pipeline::from_args(args).run(path, base_dir, log)
+}
+
+/// The first line of a Git LFS pointer file.
+const LFS_POINTER_HEADER: &str = "version https://git-lfs.github.com/spec/v1";
+
+/// How many leading bytes of a file are looked at to tell binary from text.
+const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// The share of control bytes, in percent of the sniffed bytes, above which
+/// a file is treated as binary.
+///
+/// Solidity source has practically none, and UTF-8 text in strings or
+/// comments never produces them, so a few percent is already far outside
+/// anything a compiler accepts.
+const BINARY_CONTROL_PERCENT: usize = 5;
+
+/// Explains why the bytes of a `.sol` file are not Solidity source, or
+/// returns `None` for text.
+///
+/// Catches Git LFS pointers (a clone without `git lfs pull`) and binary
+/// content under a `.sol` name, such as a corrupted download. Control bytes
+/// other than tab, line feed, form feed, and carriage return are counted in
+/// the first [`BINARY_SNIFF_BYTES`]; bytes of multi-byte UTF-8 characters
+/// are not.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let pointer = b"version https://git-lfs.github.com/spec/v1\noid sha256:4d7a\nsize 5120\n";
+/// // Some(Skip { reason: SkipReason::LfsPointer, detail: "Git LFS pointer to 5120 bytes; ..." })
+/// let skip = non_text_reason(pointer);
+/// assert_eq!(non_text_reason("string s = \"ünïcödé 🚀\";".as_bytes()), None);
+/// ```
+fn non_text_reason(bytes: &[u8]) -> Option<Skip> {
+    if let Some(rest) = bytes
+        .strip_prefix(LFS_POINTER_HEADER.as_bytes())
+        .filter(|rest| rest.starts_with(b"\n") || rest.starts_with(b"\r\n"))
+    {
+        let pointer = String::from_utf8_lossy(rest);
+        let size = pointer
+            .lines()
+            .find_map(|line| line.strip_prefix("size "))
+            .map(|size| format!(" to {} bytes", size.trim()))
+            .unwrap_or_default();
+        return Some(Skip::new(
+            SkipReason::LfsPointer,
+            format!(
+                "Git LFS pointer{}; run `git lfs pull` in the source to fetch the file",
+                size
+            ),
+        ));
+    }
+
+    let sniffed = &bytes[..bytes.len().min(BINARY_SNIFF_BYTES)];
+    let control = sniffed
+        .iter()
+        .filter(|&&b| (b < 0x20 && !matches!(b, b'\t' | b'\n' | 0x0C | b'\r')) || b == 0x7F)
+        .count();
+    (control * 100 > sniffed.len() * BINARY_CONTROL_PERCENT).then(|| {
+        Skip::new(
+            SkipReason::Binary,
+            format!(
+                "{} of the first {} bytes are control characters; not a text file",
+                control,
+                sniffed.len()
+            ),
+        )
+    })
+}
+
+/// Explains why a pragma filter excludes `code`, or returns `None` to keep it.
+///
+/// All of a file's `pragma solidity` constraints must hold, so they are
+/// combined before checking for overlap with `wanted`; `unmet` describes a
+/// miss. Files with no pragma, or only pragmas that cannot be parsed, follow
+/// `policy`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let wanted = pragma::Constraint::parse("0.8")?;
+/// let unmet = "is outside --filter-pragma 0.8";
+/// let reason = pragma_mismatch("pragma solidity ^0.7.6;", &wanted, PragmaUnknown::Include, unmet);
+/// // Some("`pragma solidity ^0.7.6` is outside --filter-pragma 0.8")
+/// ```
+fn pragma_mismatch(
+    code: &str,
+    wanted: &pragma::Constraint,
+    policy: PragmaUnknown,
+    unmet: &str,
+) -> Option<String> {
+    let pragmas = structure::solidity_pragmas(code);
+    let combined = pragmas
+        .iter()
+        .filter_map(|p| pragma::Constraint::parse(p).ok())
+        .reduce(|all, next| all.and(&next));
+
+    let Some(combined) = combined else {
+        let found = if pragmas.is_empty() {
+            "no pragma solidity".to_string()
+        } else {
+            format!("unrecognized `pragma solidity {}`", pragmas.join("; "))
+        };
+        return match policy {
+            PragmaUnknown::Include => None,
+            PragmaUnknown::Exclude => {
+                Some(format!("{}; excluded by --pragma-unknown exclude", found))
+            }
+        };
+    };
+
+    if combined.intersects(wanted) {
+        None
+    } else {
+        Some(format!(
+            "`pragma solidity {}` {}",
+            pragmas.join("; "),
+            unmet
+        ))
+    }
+}
+
+// ============================================================================
+// Main Scraper
+// ============================================================================
+
+/// The result of a successful scraping operation.
+///
+/// Contains statistics about the scraping process and the location of the
+/// output file. Use this to report results to the user or for programmatic
+/// access to the output.
+struct ScraperResult {
+    /// The absolute path to the generated consolidated Solidity file.
+    output_path: PathBuf,
+    /// The number of Solidity files that were processed.
+    file_count: usize,
+    /// The total number of lines in the consolidated output.
+    line_count: usize,
+    /// Relative paths of all files that were included in the output.
+    files_processed: Vec<String>,
+    /// Cleaning statistics for each included file, in output order.
+    file_stats: Vec<(String, CleanStats)>,
+    /// The branch and commit scraped, when the source was a git working tree.
+    revision: Option<GitRevision>,
+    /// The timestamped run directory outputs were written to, with `--run-dir`.
+    run_dir: Option<PathBuf>,
+    /// The frameworks detected at the source root.
+    project: project::ProjectKind,
+    /// Structural warnings about included files as `(displayed path, message)`.
+    warnings: Vec<(String, String)>,
+    /// Markers found with `--flag-todos` as `(displayed path, marker)`.
+    todos: Vec<(String, todo::Marker)>,
+    /// The custom errors and revert reasons found with `--errors-report`.
+    errors: error_report::Inventory,
+    /// The SPDX expression of each included file that has one, for
+    /// `--gate`, as `(displayed path, expression)`.
+    licenses: Vec<(String, String)>,
+    /// The imports of included files that are not in the output, for
+    /// `--gate no-missing-imports`, as `(displayed path, import path)`.
+    missing_imports: Vec<(String, String)>,
+    /// Candidates left out while processing as `(displayed path, reason,
+    /// redundant)`, in processing order, `redundant` saying what makes the
+    /// file redundant: the copy `--dedupe-content` kept in place of a
+    /// duplicate, or the implementers of an interface
+    /// `--prune-implemented-interfaces` left out (see
+    /// [`implemented_interfaces`]).
+    skipped: Vec<(String, SkipReason, Option<String>)>,
+    /// Groups of displayed paths whose cleaned code is the same, the first
+    /// path of each being the one kept under `--dedupe-content`.
+    duplicates: Vec<Vec<String>>,
+    /// The number of included files given a pragma by `--inject-pragma`.
+    pragmas_injected: usize,
+    /// Where each included file's part lies in the output, in output order.
+    parts: Vec<PartSpan>,
+    /// The relative path of the only included file, when there was just one.
+    single_source: Option<String>,
+    /// Why each `.sol` file in the tree was included or not, with `--explain`.
+    decisions: Vec<FileDecision>,
+    /// `(processed, candidates)` when `--deadline` cut processing short and
+    /// the output is partial.
+    truncated: Option<(usize, usize)>,
+    /// The files changed since the `--since` ref, when given.
+    since: Option<ChangedFiles>,
+    /// The included files related to the `--related` target, when given.
+    related: Option<RelatedFiles>,
+    /// The project files appended after the Solidity code, with `--with-context`.
+    context: Vec<context::ContextFile>,
+    /// The time spent cloning or refreshing the clone, when the source was
+    /// cloned.
+    clone_time: Option<Duration>,
+    /// The files written by `--split-by-dir`, in output order; empty for a
+    /// single output, which `output_path` and `parts` describe.
+    outputs: Vec<DirOutput>,
+    /// The `--stats` rollup of `file_stats` by directory; empty without
+    /// `--stats`.
+    directories: Vec<DirRollup>,
+    /// Each source scraped with several `--source` as `(name, files)`, in
+    /// the order given; empty for a single source.
+    sources: Vec<(String, usize)>,
+    /// Each of several `--source` that failed, with its error.
+    failed_sources: Vec<(String, RunError)>,
+}
+
+impl ScraperResult {
+    /// Every file written, with the parts it holds: the `--split-by-dir`
+    /// outputs, or the one output.
+    fn written_files(&self) -> Vec<(&Path, &[PartSpan])> {
+        if self.outputs.is_empty() {
+            return vec![(self.output_path.as_path(), self.parts.as_slice())];
+        }
+        self.outputs
+            .iter()
+            .map(|output| (output.path.as_path(), output.parts.as_slice()))
+            .collect()
+    }
+}
+
+/// One file written by `--split-by-dir`: the parts under one top-level
+/// source directory.
+#[derive(Debug, Clone, PartialEq)]
+struct DirOutput {
+    /// The top-level directory, or [`ROOT_GROUP`] for files at the root.
+    dir: String,
+    /// The file written.
+    path: PathBuf,
+    /// Where each file's part lies in it, in output order.
+    parts: Vec<PartSpan>,
+    /// The total lines of its parts.
+    line_count: usize,
+}
+
+/// Why one `.sol` file was or was not scraped, as reported by `--explain`.
+#[derive(Debug, Clone, PartialEq)]
+struct FileDecision {
+    /// The path relative to the source root.
+    path: String,
+    /// Whether the file is in the output.
+    included: bool,
+    /// The responsible rule; see [`explain_decisions`].
+    rule: String,
+}
+
+/// Scrapes Solidity files from a directory and consolidates them into a single file.
+///
+/// This is the core scraping logic used by both [`scrape_from_url`] and
+/// [`scrape_from_local`]. It builds the output with
+/// [`scrape_directory_to_memory`] and writes it to the destination
+/// directory, so the written file is exactly [`ScrapeOutput::render`].
+///
+/// By default every path is collected and sorted before processing so the
+/// output order is deterministic. With `--stream`, a [`SolFileWalker`] feeds
+/// files to cleaning and writing as they are discovered instead.
+///
+/// # Arguments
+///
+/// * `source_dir` — The directory containing Solidity files to scrape
+/// * `destination` — The output directory for the consolidated file
+/// * `output_name` — Base name for the output file (produces `{name}_scraped.sol`)
+/// * `args` — Configuration affecting which files to include
+/// * `log` — Destination for progress messages and warnings
+///
+/// # Returns
+///
+/// A [`ScraperResult`] containing statistics and the output path.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Failed to scan directory: {e}"` | I/O error during file discovery |
+/// | `"Repository is empty ..."` / `"Repository contains no Solidity ..."` / `"No Solidity files found ..."` | Nothing was selected; see [`no_files_error`] |
+/// | `"All Solidity files were empty..."` | All files were empty after cleaning |
+/// | `"Failed to create destination: {e}"` | Cannot create output directory |
+/// | `"Failed to create run directory ..."` | `--run-dir` directory cannot be created |
+/// | `"Failed to create output file: {e}"` | Cannot create the output file |
+/// | `"Failed to write output: {e} ..."` | Error writing to the output file (see [`output::write_atomic`]) |
+fn scrape_directory(
+    source_dir: &Path,
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let mut result = if args.stream {
+        let selection = Selection::new(source_dir, args, log)?;
+        stream_selection(source_dir, selection, destination, output_name, args, log)?
+    } else {
+        let scraped = scrape_directory_to_memory(source_dir, args, log)?;
+        write_scrape_output(scraped, destination, output_name, args, log)?
+    };
+    add_directory_rollup(&mut result, args);
+    Ok(result)
+}
+
+/// Scrapes the files of `selection` under `source_dir`, as
+/// [`scrape_directory`] does for a fresh selection.
+fn scrape_selection(
+    source_dir: &Path,
+    selection: Selection,
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let mut result = if args.stream {
+        stream_selection(source_dir, selection, destination, output_name, args, log)?
+    } else {
+        let scraped = scrape_selection_to_memory(source_dir, &selection, args, log)?;
+        write_scrape_output(scraped, destination, output_name, args, log)?
+    };
+    add_directory_rollup(&mut result, args);
+    Ok(result)
+}
+
+/// Rolls the per-file statistics of `result` up by directory, with `--stats`.
+fn add_directory_rollup(result: &mut ScraperResult, args: &Args) {
+    if args.stats {
+        let depth = args.stats_depth.unwrap_or(DEFAULT_STATS_DEPTH);
+        result.directories = rollup_directories(&result.file_stats, depth);
+    }
+}
+
+/// One included file's part of a [`ScrapeOutput`].
+#[derive(Debug, Clone, PartialEq)]
+struct OutputPart {
+    /// The file's relative path, unescaped.
+    path: String,
+    /// The cleaned text, with its header unless `--no-headers`.
+    text: String,
+}
+
+/// A consolidated scrape held in memory, before anything is written.
+#[derive(Debug)]
+struct ScrapeOutput {
+    /// The included files, in output order.
+    parts: Vec<OutputPart>,
+    /// Cleaning statistics of included files, in output order.
+    file_stats: Vec<(String, CleanStats)>,
+    /// Structural warnings as `(displayed path, message)`, in output order.
+    warnings: Vec<(String, String)>,
+    /// Markers found with `--flag-todos` as `(displayed path, marker)`.
+    todos: Vec<(String, todo::Marker)>,
+    /// The custom errors and revert reasons found with `--errors-report`.
+    errors: error_report::Inventory,
+    /// The SPDX expressions of included files, for `--gate`.
+    licenses: Vec<(String, String)>,
+    /// The imports not in the output, for `--gate`.
+    missing_imports: Vec<(String, String)>,
+    /// Candidates left out while processing (see [`ScraperResult::skipped`]).
+    skipped: Vec<(String, SkipReason, Option<String>)>,
+    /// Files sharing their cleaned code (see [`Consolidation::duplicate_groups`]).
+    duplicates: Vec<Vec<String>>,
+    /// Total lines of all parts.
+    line_count: usize,
+    /// The number of parts given a pragma by `--inject-pragma`.
+    pragmas_injected: usize,
+    /// `(processed, candidates)` when `--deadline` cut processing short.
+    truncated: Option<(usize, usize)>,
+    /// The project files to append, with `--with-context`.
+    context: Vec<context::ContextFile>,
+    /// Why each `.sol` file was included or not, with `--explain`.
+    decisions: Vec<FileDecision>,
+    /// The frameworks detected at the source root.
+    project: project::ProjectKind,
+}
+
+impl ScrapeOutput {
+    /// Renders the consolidated file and where each part lies in it.
+    ///
+    /// Parts are joined by newlines, followed by the `--deadline` trailer and
+    /// the `--with-context` section when there is one.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let scraped = scrape_directory_to_memory(&source, &args, &log)?;
+    /// let (code, spans) = scraped.render();
+    /// assert_eq!(&code[spans[0].offset..][..spans[0].len], scraped.parts[0].text);
+    /// ```
+    fn render(&self) -> (String, Vec<PartSpan>) {
+        self.render_parts(&self.parts.iter().collect::<Vec<_>>())
+    }
+
+    /// Renders `parts`, a selection of this output's parts, as
+    /// [`ScrapeOutput::render`] renders them all; `--split-by-dir` gives
+    /// each of its files the same trailer and context section.
+    fn render_parts(&self, parts: &[&OutputPart]) -> (String, Vec<PartSpan>) {
+        let mut code = String::new();
+        let mut spans = Vec::with_capacity(parts.len());
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                code.push('\n');
+            }
+            spans.push(PartSpan {
+                path: part.path.clone(),
+                offset: code.len(),
+                len: part.text.len(),
+            });
+            code.push_str(&part.text);
+        }
+        if let Some((done, total)) = self.truncated {
+            if !code.is_empty() {
+                code.push('\n');
+            }
+            code.push_str(&render_truncated_trailer(done, total));
+            code.push('\n');
+        }
+        if !self.context.is_empty() {
+            if !code.is_empty() && !code.ends_with('\n') {
+                code.push('\n');
+            }
+            code.push_str(&context::render(&self.context));
+        }
+        (code, spans)
+    }
+}
+
+/// Scrapes Solidity files from a directory without writing anything.
+///
+/// Selects and processes files exactly as [`scrape_directory`] does, for
+/// callers that keep the output themselves; the file-writing path calls this
+/// and writes [`ScrapeOutput::render`]. `--stream` is ignored, since there is
+/// no output file to stream to.
+///
+/// # Errors
+///
+/// The selection and cleaning errors of [`scrape_directory`]; nothing here
+/// touches the destination.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let scraped = scrape_directory_to_memory(Path::new("./vault"), &args, &log)?;
+/// for part in &scraped.parts {
+///     println!("{}: {} bytes", part.path, part.text.len());
+/// }
+/// ```
+fn scrape_directory_to_memory(
+    source_dir: &Path,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScrapeOutput, RunError> {
+    let selection = Selection::new(source_dir, args, log)?;
+    scrape_selection_to_memory(source_dir, &selection, args, log)
+}
+
+/// Scrapes the files of `selection` under `source_dir` into memory, as
+/// [`scrape_directory_to_memory`] does for a fresh selection.
+fn scrape_selection_to_memory(
+    source_dir: &Path,
+    selection: &Selection,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScrapeOutput, RunError> {
+    // Find all Solidity files
+    let sol_files = selection.discover(source_dir, log)?;
+
+    if sol_files.is_empty() {
+        return Err(selection.nothing_selected(source_dir));
+    }
+
+    // Process all files
+    let pruned = if args.prune_implemented_interfaces {
+        log.phase("interfaces", || {
+            implemented_interfaces(&sol_files, source_dir, log)
+        })
+    } else {
+        std::collections::HashMap::new()
+    };
+    let mut consolidation = Consolidation {
+        total: Some(sol_files.len()),
+        relations: selection.relations(),
+        pruned,
+        ..Consolidation::default()
+    };
+    log.progress(ProgressEvent::ProcessingStarted {
+        total: consolidation.total,
+    });
+    let mut texts: Vec<String> = Vec::new();
+    log.phase("processing", || {
+        for file_path in &sol_files {
+            if deadline_passed(args) {
+                break;
+            }
+            if let Some(text) = consolidation.process(file_path, source_dir, args, log) {
+                texts.push(text);
+            }
+        }
+    });
+
+    let mut truncated = None;
+    if consolidation.seen < sol_files.len() {
+        truncated = Some((consolidation.seen, sol_files.len()));
+    } else if texts.is_empty() {
+        return Err("All Solidity files were empty after processing"
+            .to_string()
+            .into());
+    }
+
+    let decisions = if args.explain {
+        log.phase("explain", || {
+            explain_decisions(source_dir, selection, &consolidation.processing_rules)
+        })
+    } else {
+        Vec::new()
+    };
+
+    let duplicates = consolidation.duplicate_groups();
+    let missing_imports = consolidation.missing_imports(source_dir, args);
+    Ok(ScrapeOutput {
+        parts: consolidation
+            .files_processed
+            .into_iter()
+            .zip(texts)
+            .map(|(path, text)| OutputPart { path, text })
+            .collect(),
+        file_stats: consolidation.file_stats,
+        warnings: consolidation.warnings,
+        todos: consolidation.todos,
+        errors: consolidation.errors,
+        licenses: consolidation.licenses,
+        missing_imports,
+        skipped: consolidation.skipped,
+        duplicates,
+        line_count: consolidation.line_count,
+        pragmas_injected: consolidation.pragmas_injected,
+        truncated,
+        context: collect_context(source_dir, args),
+        decisions,
+        project: selection.project.clone(),
+    })
+}
+
+/// Writes `code` to the output file at `output_path`.
+///
+/// With `--vcs-friendly`, the changes versus the file previously at the same
+/// path are reported before it is replaced.
+fn write_output_file(
+    output_path: &Path,
+    code: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<(), String> {
+    let previous = args
+        .vcs_friendly
+        .then(|| fs::read_to_string(output_path).ok());
+
+    log.progress(ProgressEvent::WritingOutput {
+        path: output_path.to_path_buf(),
+    });
+    log.phase("write", || {
+        output::write_atomic(output_path, code.as_bytes())
+    })?;
+    match previous {
+        Some(Some(old)) => {
+            for line in vcs::render_report(&vcs::compare(&old, code)) {
+                log.report(&line);
+            }
+        }
+        Some(None) => log.report(&format!(
+            "Changes vs previous output: no previous file at {}",
+            output_path.display()
+        )),
+        None => {}
+    }
+    Ok(())
+}
+
+/// The `--split-by-dir` group of files directly at the source root.
+const ROOT_GROUP: &str = "root";
+
+/// The top-level directory of the relative path `path`, or [`ROOT_GROUP`]
+/// for a file at the source root.
+fn top_level_dir(path: &str) -> &str {
+    match path.split_once('/') {
+        Some((dir, _)) => dir,
+        None => ROOT_GROUP,
+    }
+}
+
+/// Writes `scraped` as one file per top-level source directory
+/// (`--split-by-dir`), or per source with `--split-by-source`, whose
+/// sources are the top-level directories (see [`scrape_sources`]).
+///
+/// Each file holds the parts under one directory, in output order, with
+/// the `--deadline` trailer and `--with-context` section a single output
+/// would have. Files are named `{dir}_scraped.sol`, or
+/// `{name}_{dir}_scraped.sol` with `-o`, and only directories with included
+/// files get one.
+fn write_split_by_dir(
+    scraped: ScrapeOutput,
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let mut groups: Vec<(&str, Vec<&OutputPart>)> = Vec::new();
+    for part in &scraped.parts {
+        let dir = top_level_dir(&part.path);
+        match groups.iter_mut().find(|(group, _)| *group == dir) {
+            Some((_, parts)) => parts.push(part),
+            None => groups.push((dir, vec![part])),
+        }
+    }
+    let stem = |dir: &str| match &args.output_name {
+        Some(_) => format!("{}_{}", output_name, dir),
+        None => dir.to_string(),
+    };
+
+    let mut run_dir = None;
+    let mut outputs: Vec<DirOutput> = Vec::with_capacity(groups.len());
+    for (dir, parts) in &groups {
+        let (code, spans) = scraped.render_parts(parts);
+        let path = match outputs.first() {
+            None => {
+                let (path, created) = prepare_output(destination, &stem(dir), args, log)?;
+                run_dir = created;
+                path
+            }
+            Some(first) => {
+                first
+                    .path
+                    .with_file_name(fit_output_name(&stem(dir), "_scraped.sol", log))
+            }
+        };
+        write_output_file(&path, &code, args, log)?;
+        outputs.push(DirOutput {
+            dir: dir.to_string(),
+            path,
+            parts: spans,
+            line_count: parts.iter().map(|part| part.text.lines().count()).sum(),
+        });
+    }
+
+    let files_processed: Vec<String> = scraped.parts.into_iter().map(|part| part.path).collect();
+    let output_path = outputs
+        .first()
+        .map(|output| output.path.clone())
+        .unwrap_or_default();
+    log.progress(ProgressEvent::Finished {
+        files: files_processed.len(),
+        lines: scraped.line_count,
+        output: output_path.clone(),
+    });
+
+    Ok(ScraperResult {
+        output_path,
+        single_source: match files_processed.as_slice() {
+            [only] => Some(only.clone()),
+            _ => None,
+        },
+        file_count: files_processed.len(),
+        line_count: scraped.line_count,
+        files_processed,
+        file_stats: scraped.file_stats,
+        warnings: scraped.warnings,
+        todos: scraped.todos,
+        errors: scraped.errors,
+        licenses: scraped.licenses,
+        missing_imports: scraped.missing_imports,
+        skipped: scraped.skipped,
+        duplicates: scraped.duplicates,
+        pragmas_injected: scraped.pragmas_injected,
+        parts: Vec::new(),
+        revision: None,
+        run_dir,
+        decisions: scraped.decisions,
+        truncated: scraped.truncated,
+        since: None,
+        related: None,
+        context: scraped.context,
+        project: scraped.project,
+        clone_time: None,
+        outputs,
+        directories: Vec::new(),
+        sources: Vec::new(),
+        failed_sources: Vec::new(),
+    })
+}
+
+/// Reads the `--with-context` files at `source_dir`, or none without the flag.
+fn collect_context(source_dir: &Path, args: &Args) -> Vec<context::ContextFile> {
+    if !args.with_context {
+        return Vec::new();
+    }
+    let limit = args
+        .context_readme_limit
+        .unwrap_or(context::DEFAULT_README_LIMIT);
+    context::collect(source_dir, limit)
+}
+
+/// Writes `scraped` to the output file and reports it.
+///
+/// With `--vcs-friendly`, the changes versus the file previously at the same
+/// path are reported before it is replaced.
+fn write_scrape_output(
+    scraped: ScrapeOutput,
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    if args.split_by_dir {
+        return write_split_by_dir(scraped, destination, output_name, args, log);
+    }
+    let (final_code, parts) = scraped.render();
+
+    let (output_path, run_dir) = prepare_output(destination, output_name, args, log)?;
+    write_output_file(&output_path, &final_code, args, log)?;
+
+    let files_processed: Vec<String> = scraped.parts.into_iter().map(|part| part.path).collect();
+    log.progress(ProgressEvent::Finished {
+        files: files_processed.len(),
+        lines: scraped.line_count,
+        output: output_path.clone(),
+    });
+
+    // With one part nothing is consolidated; the summary says so
+    let single_source = match parts.as_slice() {
+        [only] => Some(only.path.clone()),
+        _ => None,
+    };
+
+    Ok(ScraperResult {
+        output_path,
+        single_source,
+        file_count: files_processed.len(),
+        line_count: scraped.line_count,
+        files_processed,
+        file_stats: scraped.file_stats,
+        warnings: scraped.warnings,
+        todos: scraped.todos,
+        errors: scraped.errors,
+        licenses: scraped.licenses,
+        missing_imports: scraped.missing_imports,
+        skipped: scraped.skipped,
+        duplicates: scraped.duplicates,
+        pragmas_injected: scraped.pragmas_injected,
+        parts,
+        revision: None,
+        run_dir,
+        decisions: scraped.decisions,
+        truncated: scraped.truncated,
+        since: None,
+        related: None,
+        context: scraped.context,
+        project: scraped.project,
+        clone_time: None,
+        outputs: Vec::new(),
+        directories: Vec::new(),
+        sources: Vec::new(),
+        failed_sources: Vec::new(),
+    })
+}
+
+/// Scrapes the files of `selection` with `--stream`: discovery, cleaning,
+/// and writing are pipelined file by file, so the output is in walk order.
+fn stream_selection(
+    source_dir: &Path,
+    selection: Selection,
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let mut consolidation = Consolidation {
+        relations: selection.relations(),
+        ..Consolidation::default()
+    };
+    let context = collect_context(source_dir, args);
+
+    let mut truncated = None;
+    report_excluded(&selection, log);
+    selection.check_forced(source_dir)?;
+    let unwalked = selection.unwalked_forced();
+
+    let mut out: Option<(output::AtomicFile, PathBuf, Option<PathBuf>)> = None;
+    let mut discovered = 0usize;
+    log.progress(ProgressEvent::ProcessingStarted { total: None });
+
+    log.phase("streaming", || -> Result<(), String> {
+        let walk = SolFileWalker::new(&selection.scan_root, &selection.walk);
+        for entry in walk.chain(unwalked.into_iter().map(Ok)) {
+            let file_path = entry.map_err(|e| format!("Failed to scan directory: {}", e))?;
+            if !selection.includes_file(&file_path, log) {
+                continue;
+            }
+            discovered += 1;
+            if deadline_passed(args) {
+                // Keep walking, only to count what was left out
+                continue;
+            }
+
+            let Some(text) = consolidation.process(&file_path, source_dir, args, log) else {
+                continue;
+            };
+            let file = match &mut out {
+                Some((file, _, _)) => {
+                    file.write_all(b"\n")?;
+                    file
+                }
+                None => {
+                    let (path, run_dir) = prepare_output(destination, output_name, args, log)?;
+                    log.progress(ProgressEvent::WritingOutput { path: path.clone() });
+                    let file = output::AtomicFile::create(&path)?;
+                    &mut out.insert((file, path, run_dir)).0
+                }
+            };
+            consolidation.record_part(file.bytes_written(), text.len());
+            file.write_all(text.as_bytes())?;
+        }
+        Ok(())
+    })?;
+    log.detail(&format!(
+        "Streamed {} discovered Solidity files under {}",
+        discovered,
+        source_dir.display()
+    ));
+
+    if discovered == 0 {
+        return Err(selection.nothing_selected(source_dir));
+    }
+    if consolidation.seen < discovered {
+        truncated = Some((consolidation.seen, discovered));
+        if out.is_none() {
+            let (path, run_dir) = prepare_output(destination, output_name, args, log)?;
+            out = Some((output::AtomicFile::create(&path)?, path, run_dir));
+        }
+    }
+    let Some((mut file, output_path, run_dir)) = out else {
+        return Err("All Solidity files were empty after processing"
+            .to_string()
+            .into());
+    };
+    if let Some((done, total)) = truncated {
+        if file.bytes_written() > 0 {
+            file.write_all(b"\n")?;
+        }
+        file.write_all(format!("{}\n", render_truncated_trailer(done, total)).as_bytes())?;
+    }
+    if !context.is_empty() {
+        if truncated.is_none() {
+            file.write_all(b"\n")?;
+        }
+        file.write_all(context::render(&context).as_bytes())?;
+    }
+    log.detail(&format!(
+        "Streamed {} bytes to {}",
+        file.bytes_written(),
+        output_path.display()
+    ));
+    file.commit()?;
+
+    log.progress(ProgressEvent::Finished {
+        files: consolidation.files_processed.len(),
+        lines: consolidation.line_count,
+        output: output_path.clone(),
+    });
+
+    let decisions = if args.explain {
+        log.phase("explain", || {
+            explain_decisions(source_dir, &selection, &consolidation.processing_rules)
+        })
+    } else {
+        Vec::new()
+    };
+
+    // With one part nothing is consolidated; the summary says so
+    let single_source = match consolidation.parts.as_slice() {
+        [only] => Some(only.path.clone()),
+        _ => None,
+    };
+
+    Ok(ScraperResult {
+        output_path,
+        single_source,
+        duplicates: consolidation.duplicate_groups(),
+        missing_imports: consolidation.missing_imports(source_dir, args),
+        file_count: consolidation.files_processed.len(),
+        line_count: consolidation.line_count,
+        files_processed: consolidation.files_processed,
+        file_stats: consolidation.file_stats,
+        warnings: consolidation.warnings,
+        todos: consolidation.todos,
+        errors: consolidation.errors,
+        licenses: consolidation.licenses,
+        skipped: consolidation.skipped,
+        pragmas_injected: consolidation.pragmas_injected,
+        parts: consolidation.parts,
+        revision: None,
+        run_dir,
+        decisions,
+        truncated,
+        since: None,
+        related: None,
+        context,
+        project: selection.project,
+        clone_time: None,
+        outputs: Vec::new(),
+        directories: Vec::new(),
+        sources: Vec::new(),
+        failed_sources: Vec::new(),
+    })
+}
+
+/// Which files of a source tree are candidates for scraping.
+///
+/// Combines project detection, the source root override, and the directory
+/// and suffix exclusions derived from [`Args`], so that scraping and
+/// `--list-files` select exactly the same files.
+struct Selection {
+    /// The frameworks detected at the source root.
+    project: project::ProjectKind,
+    /// The directory discovery starts from (see [`source_root`]).
+    scan_root: PathBuf,
+    /// The inclusion rules (see [`exclusion_policy`]).
+    policy: ExclusionPolicy,
+    /// The rules directories are walked with, so marked files inside
+    /// excluded directories are seen (see [`ExclusionPolicy::always`]).
+    walk: ExclusionPolicy,
+    /// The `--force-include` files, as paths under the source directory.
+    forced: Vec<PathBuf>,
+    /// With `--since`, the ref and the changed files, as paths under the
+    /// source directory; other files are not selected.
+    only: Option<(String, std::collections::BTreeSet<PathBuf>)>,
+    /// With `--related`, the related files and their paths under the source
+    /// directory; other files are not selected.
+    related: Option<(RelatedFiles, std::collections::BTreeSet<PathBuf>)>,
+    /// The charset of the excluded-files summary.
+    charset: Charset,
+}
+
+impl Selection {
+    /// Detects the project at `source_dir` and derives the selection for `args`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"{source} has no directory {subdir} ..."` when the `--subdir`
+    /// directory is missing.
+    fn new(source_dir: &Path, args: &Args, log: &Logger) -> Result<Self, String> {
+        let project = project::detect_project_kind(source_dir);
+        if project.is_unknown() {
+            log.detail("No Foundry, Hardhat, or Truffle markers found");
+        } else {
+            log.info(&format!("Detected: {}", project));
+        }
+
+        let scan_root = match &args.subdir {
+            Some(subdir) => {
+                let dir = source_dir.join(subdir);
+                if !dir.is_dir() {
+                    return Err(missing_subdir(source_dir, &args.source, subdir));
+                }
+                log.info(&format!("Scanning only {}", naming::display_path(subdir)));
+                dir
+            }
+            None => source_root(source_dir, &project, log),
+        };
+        Ok(Self {
+            scan_root,
+            policy: exclusion_policy(&project, args),
+            walk: ExclusionPolicy::always(),
+            forced: args
+                .force_include
+                .iter()
+                .map(|relative| source_dir.join(relative))
+                .collect(),
+            only: None,
+            related: None,
+            charset: args.charset,
+            project,
+        })
+    }
+
+    /// Limits the selection to the files changed since a ref (`--since`).
+    ///
+    /// The usual rules still apply to the changed files.
+    fn restrict_to(&mut self, source_dir: &Path, changes: &ChangedFiles) {
+        let changed = changes
+            .changed
+            .iter()
+            .map(|relative| source_dir.join(relative))
+            .collect();
+        self.only = Some((changes.base.clone(), changed));
+    }
+
+    /// Limits the selection to the files related to a target (`--related`).
+    ///
+    /// The usual rules still apply to the related files.
+    fn relate(&mut self, source_dir: &Path, related: RelatedFiles) {
+        let paths = related
+            .relations
+            .keys()
+            .map(|relative| source_dir.join(relative))
+            .collect();
+        self.related = Some((related, paths));
+    }
+
+    /// The relation of each related file, by relative path; empty without
+    /// `--related`.
+    fn relations(&self) -> std::collections::BTreeMap<String, graph::Relation> {
+        self.related
+            .as_ref()
+            .map(|(related, _)| related.relations.clone())
+            .unwrap_or_default()
+    }
+
+    /// The error for a selection that matched nothing.
+    ///
+    /// With `--since`, names the changed files instead of surveying the
+    /// whole tree (see [`no_files_error`]); with `--related`, counts the
+    /// related files.
+    fn nothing_selected(&self, source_dir: &Path) -> RunError {
+        if let Some((related, _)) = &self.related {
+            return RunError {
+                message: format!(
+                    "None of the {} Solidity files related to {} are selected",
+                    related.relations.len(),
+                    naming::display_path(&related.target)
+                ),
+                code: EXIT_NO_FILES,
+            };
+        }
+        let Some((base, changed)) = &self.only else {
+            return no_files_error(source_dir, &self.policy, self.charset.symbols());
+        };
+        let message = if changed.is_empty() {
+            format!("No Solidity files changed since {}", base)
+        } else {
+            let mut shown: Vec<String> = changed
+                .iter()
+                .take(5)
+                .map(|path| naming::display_path(&naming::relative_path(path, source_dir)))
+                .collect();
+            if changed.len() > shown.len() {
+                shown.push(format!("and {} more", changed.len() - shown.len()));
+            }
+            format!(
+                "None of the {} Solidity files changed since {} are selected: {}",
+                changed.len(),
+                base,
+                shown.join(", ")
+            )
+        };
+        RunError {
+            message,
+            code: EXIT_NO_FILES,
+        }
+    }
+
+    /// Checks that every `--force-include` path is a `.sol` file.
+    ///
+    /// # Errors
+    ///
+    /// For a missing path, names the closest ancestor that exists, which
+    /// usually shows where the path was mistyped.
+    fn check_forced(&self, source_dir: &Path) -> Result<(), String> {
+        for path in &self.forced {
+            let shown = naming::display_path(&naming::relative_path(path, source_dir));
+            if path.is_file() && is_sol_file(path) {
+                continue;
+            }
+            if path.exists() {
+                return Err(format!("--force-include {} is not a .sol file", shown));
+            }
+            let ancestor = path
+                .ancestors()
+                .skip(1)
+                .take_while(|dir| dir.starts_with(source_dir) && *dir != source_dir)
+                .find(|dir| dir.is_dir())
+                .map_or_else(
+                    || "the source root".to_string(),
+                    |dir| naming::display_path(&naming::relative_path(dir, source_dir)),
+                );
+            return Err(format!(
+                "--force-include {} does not exist (closest existing directory: {})",
+                shown, ancestor
+            ));
+        }
+        Ok(())
+    }
+
+    /// The `--force-include` files that a walk of the scan root under
+    /// [`Selection::walk`] does not reach, so a stream must visit them
+    /// separately.
+    fn unwalked_forced(&self) -> Vec<PathBuf> {
+        self.forced
+            .iter()
+            .filter(|path| match path.strip_prefix(&self.scan_root) {
+                Ok(relative) => self.walk.decide(relative, false) != Decision::Include,
+                Err(_) => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Collects the selected `.sol` files in sorted order.
+    ///
+    /// Also reports excluded directories that contain Solidity files.
+    fn discover(&self, source_dir: &Path, log: &Logger) -> Result<Vec<PathBuf>, String> {
+        let mut sol_files = log
+            .phase("discovery", || {
+                find_solidity_files(&self.scan_root, &self.walk)
+            })
+            .map_err(|e| format!("Failed to scan directory: {}", e))?;
+        sol_files.retain(|path| self.includes_file(path, log));
+        self.check_forced(source_dir)?;
+        sol_files.extend(self.forced.iter().cloned());
+        sol_files.sort();
+        sol_files.dedup();
+        log.detail(&format!(
+            "Discovered {} Solidity files under {}",
+            sol_files.len(),
+            source_dir.display()
+        ));
+        report_excluded(self, log);
+        Ok(sol_files)
+    }
+
+    /// Applies the policy to a discovered file, honoring `--force-include`
+    /// and an include marker, and noting exclusions at detail level.
+    fn includes_file(&self, path: &Path, log: &Logger) -> bool {
+        let relative = path.strip_prefix(&self.scan_root).unwrap_or(path);
+        if self.forced.iter().any(|forced| forced == path) {
+            log.detail(&format!(
+                "Included {}: --force-include",
+                naming::display_path(&relative.to_string_lossy())
+            ));
+            return true;
+        }
+        if let Some((base, changed)) = &self.only {
+            if !changed.contains(path) {
+                log.detail(&format!(
+                    "Excluded {}: unchanged since {}",
+                    naming::display_path(&relative.to_string_lossy()),
+                    base
+                ));
+                return false;
+            }
+        }
+        if let Some((related, paths)) = &self.related {
+            if !paths.contains(path) {
+                log.detail(&format!(
+                    "Excluded {}: not related to {}",
+                    naming::display_path(&relative.to_string_lossy()),
+                    naming::display_path(&related.target)
+                ));
+                return false;
+            }
+        }
+        let mut marked = false;
+        let decision = self.policy.decide_file(relative, || {
+            marked = true;
+            exclusion::read_marker(path)
+        });
+        match decision {
+            Decision::Include => {
+                if marked {
+                    log.detail(&format!(
+                        "Included {}: {}",
+                        naming::display_path(&relative.to_string_lossy()),
+                        exclusion::INCLUDE_MARKER
+                    ));
+                }
+                true
+            }
+            Decision::Exclude(reason) => {
+                log.detail(&format!(
+                    "Excluded {}: {}",
+                    naming::display_path(&relative.to_string_lossy()),
+                    reason
+                ));
+                false
+            }
+        }
+    }
+}
+
+/// Attributes every `.sol` file under `source_dir` to the rule that decided
+/// it, for `--explain`.
+///
+/// Unlike discovery, the walk enters every directory, including
+/// always-excluded ones, so it is only done on request. Rule names are
+/// stable for external tools:
+///
+/// | Status | Rule | Meaning |
+/// |--------|------|---------|
+/// | included | `default` | No rule excludes the file |
+/// | included | `flag:include-lib` (`-test`, `-script`) | The flag lifted a default exclusion |
+/// | included | `include-marker` | [`exclusion::INCLUDE_MARKER`] overrode an exclusion |
+/// | included | `force-include` | Named by `--force-include` |
+/// | excluded | `default-dir:<name>`, `always-dir:<name>`, `suffix:*<suffix>` | See [`exclusion::Reason::rule`] |
+/// | excluded | `outside-sources` | Outside the Hardhat `paths.sources` directory |
+/// | excluded | `unchanged-since` | Not changed since the `--since` ref |
+/// | excluded | `unrelated` | Neither imports nor is imported by the `--related` target |
+/// | excluded | `opt-out-marker`, `pragma`, `kind`, `duplicate`, `long-lines`, `deep-nesting`, `lfs-pointer`, `binary`, `not-utf8` | Skipped while processing |
+/// | excluded | `empty-after-clean`, `unreadable` | Nothing left after cleaning, or unreadable |
+fn explain_decisions(
+    source_dir: &Path,
+    selection: &Selection,
+    processing_rules: &std::collections::HashMap<String, &'static str>,
+) -> Vec<FileDecision> {
+    let defaults = ExclusionPolicy::new(&selection.project, exclusion::Inclusions::default());
+    let mut decisions: Vec<FileDecision> =
+        SolFileWalker::new(source_dir, &ExclusionPolicy::unfiltered())
+            .filter_map(Result::ok)
+            .map(|path| {
+                let relative = naming::relative_path(&path, source_dir);
+                let decision = |included: bool, rule: String| FileDecision {
+                    path: relative.clone(),
+                    included,
+                    rule,
+                };
+                let unchanged = selection
+                    .only
+                    .as_ref()
+                    .is_some_and(|(_, changed)| !changed.contains(&path));
+                let unrelated = selection
+                    .related
+                    .as_ref()
+                    .is_some_and(|(_, paths)| !paths.contains(&path));
+                let scanned = match path.strip_prefix(&selection.scan_root) {
+                    _ if selection.forced.contains(&path) => None,
+                    _ if unchanged => return decision(false, "unchanged-since".to_string()),
+                    _ if unrelated => return decision(false, "unrelated".to_string()),
+                    Ok(scanned) => Some(scanned),
+                    Err(_) => return decision(false, "outside-sources".to_string()),
+                };
+                let rule = match scanned.map(|s| (s, selection.policy.decide(s, false))) {
+                    None => "force-include".to_string(),
+                    Some((scanned, Decision::Include)) => match defaults.decide(scanned, false) {
+                        Decision::Exclude(reason) => match reason.lifted_by() {
+                            Some(flag) => format!("flag:{}", flag),
+                            None => "default".to_string(),
+                        },
+                        Decision::Include => "default".to_string(),
+                    },
+                    Some((scanned, Decision::Exclude(reason))) => {
+                        let marked = || exclusion::read_marker(&path);
+                        if selection.policy.decide_file(scanned, marked) == Decision::Include {
+                            "include-marker".to_string()
+                        } else {
+                            return decision(false, reason.rule());
+                        }
+                    }
+                };
+                match processing_rules.get(&relative) {
+                    Some(skipped) => decision(false, skipped.to_string()),
+                    None => decision(true, rule),
+                }
+            })
+            .collect();
+    decisions.sort_by(|a, b| a.path.cmp(&b.path));
+    decisions
+}
+
+/// Finds the interface files among `sol_files` that
+/// `--prune-implemented-interfaces` leaves out (see
+/// [`structure::implemented_interfaces`]), by relative path, with the skip
+/// detail naming each interface's implementer.
+///
+/// Files are read as they are on disk; one that cannot be read implements
+/// nothing. Interface names declared by several files are warned about.
+fn implemented_interfaces(
+    sol_files: &[PathBuf],
+    source_dir: &Path,
+    log: &Logger,
+) -> std::collections::HashMap<String, String> {
+    let files: Vec<(String, String)> = sol_files
+        .iter()
+        .filter_map(|path| {
+            let code = fs::read_to_string(path).ok()?;
+            Some((naming::relative_path(path, source_dir), code))
+        })
+        .collect();
+    let found = structure::implemented_interfaces(&files);
+    for (name, paths) in &found.ambiguous {
+        let shown: Vec<String> = paths.iter().map(|p| naming::display_path(p)).collect();
+        log.warn(&format!(
+            "Interface {} is declared in {}; keeping them, as an `is {}` clause \
+             cannot tell which one it means",
+            name,
+            shown.join(" and "),
+            name
+        ));
+    }
+    found
+        .implemented
+        .into_iter()
+        .map(|file| {
+            let detail = file
+                .implementers
+                .iter()
+                .map(|(interface, contract, path)| {
+                    format!(
+                        "{} is implemented by {} in {}",
+                        interface,
+                        contract,
+                        naming::display_path(path)
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("; ");
+            (file.path, detail)
+        })
+        .collect()
+}
+
+/// The [`digest::Row`] of each file a scrape of `source_dir` would include,
+/// in output order.
+///
+/// Files are processed as for [`list_directory`], without headers, so each
+/// row describes the file's cleaned code.
+fn digest_directory(
+    source_dir: &Path,
+    args: &Args,
+    log: &Logger,
+) -> Result<Vec<digest::Row>, String> {
+    let args = &Args {
+        no_headers: true,
+        ..args.clone()
+    };
+    let selection = Selection::new(source_dir, args, log)?;
+    let sol_files = selection.discover(source_dir, log)?;
+    let mut consolidation = Consolidation {
+        total: Some(sol_files.len()),
+        ..Consolidation::default()
+    };
+    let mut rows = Vec::new();
+    for file_path in &sol_files {
+        if let Some(text) = consolidation.process(file_path, source_dir, args, log) {
+            let original = fs::read_to_string(file_path).unwrap_or_default();
+            let relative = naming::relative_path(file_path, source_dir);
+            rows.push(digest::Row::of(&relative, &text, &original));
+        }
+    }
+    Ok(rows)
+}
+
+/// Lists the relative paths a scrape of `source_dir` would include.
+///
+/// Files are processed exactly as for a scrape (so empty and skipped files
+/// are left out), but nothing is written.
+fn list_directory(source_dir: &Path, args: &Args, log: &Logger) -> Result<Vec<String>, String> {
+    let selection = Selection::new(source_dir, args, log)?;
+    let sol_files = selection.discover(source_dir, log)?;
+    let mut consolidation = Consolidation {
+        total: Some(sol_files.len()),
+        ..Consolidation::default()
+    };
+    for file_path in &sol_files {
+        consolidation.process(file_path, source_dir, args, log);
+    }
+    Ok(consolidation.files_processed)
+}
+
+/// Per-file bookkeeping shared by the sorted and streaming scrape paths.
+#[derive(Default)]
+struct Consolidation {
+    /// Relative paths of included files, in output order.
+    files_processed: Vec<String>,
+    /// Cleaning statistics of included files, in output order.
+    file_stats: Vec<(String, CleanStats)>,
+    /// Total lines of all included parts.
+    line_count: usize,
+    /// Structural warnings as `(displayed path, message)`, in output order.
+    warnings: Vec<(String, String)>,
+    /// Markers found with `--flag-todos` as `(displayed path, marker)`.
+    todos: Vec<(String, todo::Marker)>,
+    /// The custom errors and revert reasons found with `--errors-report`.
+    errors: error_report::Inventory,
+    /// The SPDX expression of included files that have one, for `--gate`,
+    /// as `(displayed path, expression)`.
+    licenses: Vec<(String, String)>,
+    /// The imports of every included file, for `--gate`, as
+    /// `(relative path, import paths)`.
+    imports: Vec<(String, Vec<String>)>,
+    /// Candidates left out while processing (see [`ScraperResult::skipped`]).
+    skipped: Vec<(String, SkipReason, Option<String>)>,
+    /// The number of candidate files, when known up front.
+    total: Option<usize>,
+    /// The number of candidate files processed so far.
+    seen: usize,
+    /// The number of included files given a pragma by `--inject-pragma`.
+    pragmas_injected: usize,
+    /// Output positions of included files, once written.
+    parts: Vec<PartSpan>,
+    /// Versions of the vendored libraries seen so far.
+    libraries: library::Libraries,
+    /// The library of the most recently included file, `None` for project files.
+    library: Option<String>,
+    /// The rule each candidate that was not included fell to while being
+    /// processed, by relative path.
+    processing_rules: std::collections::HashMap<String, &'static str>,
+    /// The processing stages, built from the arguments of the first call.
+    pipeline: Option<Pipeline>,
+    /// The `--related` relation of each related file, noted in its header.
+    relations: std::collections::BTreeMap<String, graph::Relation>,
+    /// The displayed paths of included files by the digest of their cleaned
+    /// code, in processing order; with `--dedupe-content` the skipped
+    /// copies too.
+    digests: std::collections::HashMap<String, Vec<String>>,
+    /// The interface files `--prune-implemented-interfaces` leaves out, by
+    /// relative path, with the skip detail naming their implementers.
+    pruned: std::collections::HashMap<String, String>,
+}
+
+impl Consolidation {
+    /// Records where the most recently included file was written.
+    fn record_part(&mut self, offset: usize, len: usize) {
+        if let Some(path) = self.files_processed.last() {
+            self.parts.push(PartSpan {
+                path: path.clone(),
+                offset,
+                len,
+            });
+        }
+    }
+
+    /// Returns the banner to put before `relative` when it starts another
+    /// library's files (or the project's, after a library), with
+    /// `--include-lib` and headers on.
+    fn library_banner(&mut self, relative: &str, source_dir: &Path, args: &Args) -> Option<String> {
+        if !args.include_lib || args.no_headers {
+            return None;
+        }
+        let library = library::library_dir(relative).map(str::to_string);
+        if library == self.library {
+            return None;
+        }
+        self.library = library;
+        let version = match &self.library {
+            Some(dir) => self.libraries.version(source_dir, dir),
+            None => None,
+        };
+        Some(library::render_banner(
+            self.library.as_deref(),
+            version,
+            args.output_charset.symbols(),
+        ))
+    }
+
+    /// Processes one file, recording it and returning its text if included.
+    ///
+    /// Files that are not included go to [`Consolidation::skip`]. Every call
+    /// emits [`ProgressEvent::FileProcessed`].
+    ///
+    /// `files_processed` and progress events keep the real relative path;
+    /// messages, warnings, and statistics use [`naming::display_path`].
+    fn process(
+        &mut self,
+        file_path: &Path,
+        source_dir: &Path,
+        args: &Args,
+        log: &Logger,
+    ) -> Option<String> {
+        let relative = naming::relative_path(file_path, source_dir);
+        let shown = naming::display_path(&relative);
+
+        self.seen += 1;
+        let index = self.seen;
+
+        let relations = &self.relations;
+        let pipeline = self.pipeline.get_or_insert_with(|| {
+            let pipeline = Pipeline::from_args(args).with_relations(relations.clone());
+            log.detail(&format!(
+                "Processing stages: {}",
+                pipeline.names().join(", ")
+            ));
+            pipeline
+        });
+        let outcome = match self.pruned.get(&relative) {
+            Some(detail) => Ok(FileOutcome::Skipped(Skip::new(
+                SkipReason::ImplementedInterface,
+                detail.clone(),
+            ))),
+            None => pipeline
+                .run(file_path, source_dir, log)
+                .map(|outcome| self.dedupe(outcome, &shown, args)),
+        };
+        let included = match outcome {
+            Ok(FileOutcome::Content {
+                text,
+                stats,
+                findings,
+                todos,
+                errors,
+                facts,
+                pragma_injected,
+                ..
+            }) => {
+                if pragma_injected {
+                    self.pragmas_injected += 1;
+                    log.detail(&format!("Injected pragma into {}", shown));
+                }
+                for finding in findings {
+                    log.warn(&format!("{}: {}", shown, finding));
+                    self.warnings.push((shown.clone(), finding));
+                }
+                for marker in todos {
+                    log.warn(&format!("{}: {}", shown, marker.describe()));
+                    self.todos.push((shown.clone(), marker));
+                }
+                self.errors.add(&shown, *errors);
+                let gate::FileFacts { license, imports } = *facts;
+                if let Some(license) = license {
+                    self.licenses.push((shown.clone(), license));
+                }
+                self.imports.push((relative.clone(), imports));
+                let text = match self.library_banner(&relative, source_dir, args) {
+                    Some(banner) => format!("{}\n{}", banner, text),
+                    None => text,
+                };
+                self.line_count += text.lines().count();
+                self.file_stats.push((shown, stats));
+                self.files_processed.push(relative.clone());
+                Some(text)
+            }
+            Ok(FileOutcome::Empty) => {
+                self.skip(&relative, file_path, Skip::empty(), log);
+                None
+            }
+            Ok(FileOutcome::Skipped(skip)) => {
+                self.skip(&relative, file_path, skip, log);
+                None
+            }
+            Err(e) => {
+                self.skip(&relative, file_path, Skip::unreadable(&e), log);
+                None
+            }
+        };
+
+        log.progress(ProgressEvent::FileProcessed {
+            index,
+            total: self.total,
+            path: relative,
+        });
+        included
+    }
+
+    /// Notes the digest of an included file's code, turning the file into a
+    /// [`SkipReason::Duplicate`] skip under `--dedupe-content` when a file
+    /// with the same code came first. Files are processed in path order, so
+    /// the path kept is the first of its group.
+    fn dedupe(&mut self, outcome: FileOutcome, shown: &str, args: &Args) -> FileOutcome {
+        let FileOutcome::Content {
+            digest: Some(digest),
+            ..
+        } = &outcome
+        else {
+            return outcome;
+        };
+        let paths = self.digests.entry(digest.clone()).or_default();
+        paths.push(shown.to_string());
+        match paths.as_slice() {
+            [kept, _, ..] if args.dedupe_content => FileOutcome::Skipped(Skip::new(
+                SkipReason::Duplicate,
+                format!("same content as {}", kept),
+            )),
+            _ => outcome,
+        }
+    }
+
+    /// The imports of included files that are not in the output, as
+    /// `(displayed path, import path)`, resolved with the remappings at
+    /// `source_dir`; none unless a `--gate` needs them.
+    fn missing_imports(&self, source_dir: &Path, args: &Args) -> Vec<(String, String)> {
+        if !args.gates.contains(&gate::Gate::NoMissingImports) {
+            return Vec::new();
+        }
+        let remappings = fs::read_to_string(source_dir.join(graph::REMAPPINGS_FILE))
+            .map(|text| graph::parse_remappings(&text))
+            .unwrap_or_default();
+        gate::missing_imports(&self.imports, &remappings)
+            .into_iter()
+            .map(|(path, import)| (naming::display_path(&path), import))
+            .collect()
+    }
+
+    /// The groups of paths sharing their cleaned code, the first path of
+    /// each being the one processed first, ordered by that path.
+    fn duplicate_groups(&self) -> Vec<Vec<String>> {
+        let mut groups: Vec<Vec<String>> = self
+            .digests
+            .values()
+            .filter(|paths| paths.len() > 1)
+            .cloned()
+            .collect();
+        groups.sort();
+        groups
+    }
+
+    /// Records a candidate that was not included: reports it (see
+    /// [`report_skip`]), attributes it to its rule for `--explain`, and
+    /// counts it for the summary, with what makes it redundant.
+    fn skip(&mut self, relative: &str, file_path: &Path, skip: Skip, log: &Logger) {
+        let shown = naming::display_path(relative);
+        report_skip(&shown, None, &skip, log);
+        log.detail(&format!(
+            "Skipped path: {}",
+            naming::display_path(&file_path.to_string_lossy())
+        ));
+        self.processing_rules
+            .insert(relative.to_string(), skip.reason.name());
+        let redundant = match skip.reason {
+            SkipReason::Duplicate => self
+                .digests
+                .values()
+                .find(|paths| paths[1..].contains(&shown))
+                .map(|paths| paths[0].clone()),
+            SkipReason::ImplementedInterface => Some(skip.detail),
+            _ => None,
+        };
+        self.skipped.push((shown, skip.reason, redundant));
+    }
+}
+
+/// Reports a file left out while processing, with the ref it belongs to
+/// under `--compare`.
+///
+/// Files with nothing left after cleaning are only a detail; every other
+/// reason is a warning.
+fn report_skip(shown: &str, git_ref: Option<&str>, skip: &Skip, log: &Logger) {
+    let message = match git_ref {
+        Some(git_ref) => format!("Skipped {} ({}): {}", shown, git_ref, skip),
+        None => format!("Skipped {}: {}", shown, skip),
+    };
+    if skip.reason == SkipReason::Empty {
+        log.detail(&message);
+    } else {
+        log.warn(&message);
+    }
+}
+
+/// Splits a destination naming an output file into its directory and file name.
+///
+/// A destination ending in `.sol` that is not an existing directory is taken
+/// as the exact output file path: the directory is its parent (or `.`) and no
+/// `_scraped` suffix is added.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     explicit_output_file("./audits/vault.sol"),
+///     Some((PathBuf::from("./audits"), "vault.sol".to_string()))
+/// );
+/// assert_eq!(explicit_output_file("./audits"), None);
+/// ```
+fn explicit_output_file(destination: &str) -> Option<(PathBuf, String)> {
+    let path = Path::new(destination);
+    if path.extension().is_none_or(|ext| ext != "sol") || path.is_dir() {
+        return None;
+    }
+
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    Some((dir, file_name))
+}
+
+/// Checks that `destination` can hold the output before any work is done.
+///
+/// # Errors
+///
+/// Returns `"Destination must be a directory, but {path} is a file"` when the
+/// destination is an existing file not ending in `.sol` (see
+/// [`explicit_output_file`]).
+fn check_destination(destination: &str) -> Result<(), String> {
+    if explicit_output_file(destination).is_none() && Path::new(destination).is_file() {
+        return Err(format!(
+            "Destination must be a directory, but {} is a file",
+            destination
+        ));
+    }
+    Ok(())
+}
+
+/// Creates the destination directory (and run directory with `--run-dir`).
+///
+/// A `.sol` destination names the output file itself (see
+/// [`explicit_output_file`]); otherwise the file is `{output_name}_scraped.sol`.
+/// A name reserved on Windows (`con`, `nul.sol`, ...) is prefixed with `_`,
+/// with a warning; see [`naming::avoid_reserved_name`]. A file name longer
+/// than filesystems allow is shortened, also with a warning naming the
+/// intended name; see [`naming::shorten_file_name`].
+///
+/// # Returns
+///
+/// The path of the consolidated output file and the run directory, if any.
+fn prepare_output(
+    destination: &str,
+    output_name: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let (mut dest_path, output_filename) = match explicit_output_file(destination) {
+        Some((dir, file_name)) => {
+            let stem = file_name.strip_suffix(".sol").unwrap_or(&file_name);
+            (dir, fit_output_name(stem, ".sol", log))
+        }
+        None => (
+            PathBuf::from(destination),
+            fit_output_name(output_name, "_scraped.sol", log),
+        ),
+    };
+    fs::create_dir_all(&dest_path).map_err(|e| format!("Failed to create destination: {}", e))?;
+
+    let run_dir = if args.run_dir {
+        let dir = rundir::create_run_dir(&dest_path, clock::DateTime::now())?;
+        log.detail(&format!("Created run directory {}", dir.display()));
+        dest_path = dir.clone();
+        Some(dir)
+    } else {
+        None
+    };
+
+    Ok((dest_path.join(output_filename), run_dir))
+}
+
+/// The output file name `{stem}{suffix}`, with a name reserved on Windows
+/// prefixed and an overlong one shortened, each with a warning; see
+/// [`prepare_output`].
+fn fit_output_name(stem: &str, suffix: &str, log: &Logger) -> String {
+    let stem = match naming::avoid_reserved_name(stem) {
+        Some(renamed) => {
+            log.warn(&format!(
+                "Output name {} is reserved on Windows; using {} instead",
+                stem, renamed
+            ));
+            renamed
+        }
+        None => stem.to_string(),
+    };
+    match naming::shorten_file_name(&stem, suffix) {
+        Some(short) => {
+            log.warn(&format!(
+                "Output file name {}{} is longer than {} bytes; using {} instead",
+                stem,
+                suffix,
+                naming::MAX_FILE_NAME_BYTES,
+                short
+            ));
+            short
+        }
+        None => format!("{}{}", stem, suffix),
+    }
+}
+
+/// Number of entries in the "most/least documented" rankings.
+const STATS_TOP_N: usize = 5;
+
+/// Renders the `--stats` report for the given per-file statistics.
+///
+/// Lists every file's original and kept line counts, pure-comment and blank
+/// lines, and comment bytes removed, followed by totals and the
+/// [`STATS_TOP_N`] most and least documented files by comment share.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// log.info(&render_stats_report(&result.file_stats));
+/// ```
+fn render_stats_report(file_stats: &[(String, CleanStats)]) -> String {
+    let width = file_stats
+        .iter()
+        .map(|(path, _)| path.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Total".len());
+
+    let row = |name: &str, s: &CleanStats| {
+        format!(
+            "  {:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}",
+            name,
+            s.original_lines,
+            s.cleaned_lines,
+            s.comment_lines,
+            s.blank_lines,
+            s.comment_bytes,
+        )
+    };
+
+    let mut lines = vec![
+        "Cleaning statistics:".to_string(),
+        format!(
+            "  {:<width$}  {:>8}  {:>8}  {:>8}  {:>8}  {:>10}",
+            "File", "Lines", "Kept", "Comment", "Blank", "Comment B"
+        ),
+    ];
+    let mut total = CleanStats::default();
+    for (path, stats) in file_stats {
+        lines.push(row(path, stats));
+        total.add(stats);
+    }
+    lines.push(row("Total", &total));
+
+    let mut ranked: Vec<&(String, CleanStats)> = file_stats.iter().collect();
+    ranked.sort_by(|a, b| {
+        b.1.documentation_ratio()
+            .total_cmp(&a.1.documentation_ratio())
+            .then_with(|| a.0.cmp(&b.0))
+    });
+    let describe = |(path, stats): &&(String, CleanStats)| {
+        format!(
+            "  {} ({:.1}% comments)",
+            path,
+            stats.documentation_ratio() * 100.0
+        )
+    };
+
+    lines.push(String::new());
+    lines.push("Most documented:".to_string());
+    lines.extend(ranked.iter().take(STATS_TOP_N).map(describe));
+    lines.push("Least documented:".to_string());
+    lines.extend(ranked.iter().rev().take(STATS_TOP_N).map(describe));
+
+    lines.push(String::new());
+    lines.extend(render_kind_rollup(file_stats));
+    lines.join("\n")
+}
+
+/// Directory levels of the `--stats` rollup without `--stats-depth`.
+const DEFAULT_STATS_DEPTH: usize = 2;
+
+/// The name the `--stats` rollup gives the source root, for the files that
+/// sit in no directory.
+const ROOT_DIR_NAME: &str = ".";
+
+/// One directory of the `--stats` rollup.
+#[derive(Debug, Clone, PartialEq)]
+struct DirRollup {
+    /// The directory relative to the source root, `/`-separated, or
+    /// [`ROOT_DIR_NAME`] for the files at the root.
+    dir: String,
+    /// Files under it, at any depth.
+    files: usize,
+    /// Kept lines of those files.
+    lines: usize,
+    /// Percentage of all kept lines that are under it.
+    share: f64,
+    /// The largest of those files by kept lines, as `(path, lines)`.
+    largest: (String, usize),
+    /// Its subdirectories, down to the rollup depth, largest first.
+    children: Vec<DirRollup>,
+}
+
+/// A file of the rollup: its directories, displayed path, and kept lines.
+type RollupFile<'a> = (Vec<&'a str>, &'a str, usize);
+
+/// Rolls per-file statistics up by directory, down to `depth` levels.
+///
+/// A directory counts every file under it, however deep; a file below
+/// `depth` levels counts toward its ancestors down to that level only, and
+/// a file directly in a directory toward none of its subdirectories. Files
+/// at the source root make up a [`ROOT_DIR_NAME`] entry. Each level is
+/// sorted by kept lines, largest first, then by path.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let rollup = rollup_directories(&result.file_stats, 2);
+/// // [DirRollup { dir: "src", children: [DirRollup { dir: "src/core", .. }], .. }]
+/// ```
+fn rollup_directories(file_stats: &[(String, CleanStats)], depth: usize) -> Vec<DirRollup> {
+    let files: Vec<RollupFile> = file_stats
+        .iter()
+        .map(|(path, stats)| {
+            let mut dirs: Vec<&str> = path.split('/').collect();
+            dirs.pop();
+            (dirs, path.as_str(), stats.cleaned_lines)
+        })
+        .collect();
+    let all: Vec<&RollupFile> = files.iter().collect();
+    let total = files.iter().map(|(_, _, lines)| lines).sum();
+
+    let mut rollups = rollup_level(&all, 0, depth, total);
+    let root: Vec<&RollupFile> = all
+        .iter()
+        .copied()
+        .filter(|(dirs, _, _)| dirs.is_empty())
+        .collect();
+    if !root.is_empty() {
+        rollups.push(rollup_of(
+            ROOT_DIR_NAME.to_string(),
+            &root,
+            Vec::new(),
+            total,
+        ));
+        sort_rollups(&mut rollups);
+    }
+    rollups
+}
+
+/// The directories `level` levels down among `files`, which share their
+/// directories above that level, with their own subdirectories.
+fn rollup_level(files: &[&RollupFile], level: usize, depth: usize, total: usize) -> Vec<DirRollup> {
+    if level >= depth {
+        return Vec::new();
+    }
+    let mut groups: std::collections::BTreeMap<&str, Vec<&RollupFile>> =
+        std::collections::BTreeMap::new();
+    for file in files {
+        if let Some(name) = file.0.get(level) {
+            groups.entry(name).or_default().push(file);
+        }
+    }
+    let mut rollups: Vec<DirRollup> = groups
+        .into_values()
+        .map(|members| {
+            let dir = members[0].0[..=level].join("/");
+            let children = rollup_level(&members, level + 1, depth, total);
+            rollup_of(dir, &members, children, total)
+        })
+        .collect();
+    sort_rollups(&mut rollups);
+    rollups
+}
+
+/// The rollup entry of `dir`, holding `files`.
+fn rollup_of(
+    dir: String,
+    files: &[&RollupFile],
+    children: Vec<DirRollup>,
+    total: usize,
+) -> DirRollup {
+    let lines: usize = files.iter().map(|(_, _, lines)| lines).sum();
+    let largest = files
+        .iter()
+        .max_by(|a, b| a.2.cmp(&b.2).then_with(|| b.1.cmp(a.1)))
+        .map(|(_, path, lines)| (path.to_string(), *lines))
+        .unwrap_or_default();
+    DirRollup {
+        dir,
+        files: files.len(),
+        lines,
+        share: if total == 0 {
+            0.0
+        } else {
+            lines as f64 * 100.0 / total as f64
+        },
+        largest,
+        children,
+    }
+}
+
+/// Sorts rollup entries by kept lines, largest first, then by path.
+fn sort_rollups(rollups: &mut [DirRollup]) {
+    rollups.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.dir.cmp(&b.dir)));
+}
+
+/// Renders the `--stats` rollup by directory as an indented tree, each
+/// subdirectory under its parent by its last component.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let lines = render_dir_rollup(&result.directories);
+/// // ["By directory:", "  Directory  Files     Lines   Share  Largest file",
+/// //  "  src/           3       120   80.0%  src/core/Vault.sol (60 lines)",
+/// //  "    core/        2        80   53.3%  src/core/Vault.sol (60 lines)", ...]
+/// ```
+fn render_dir_rollup(rollups: &[DirRollup]) -> Vec<String> {
+    fn flatten<'a>(
+        rollups: &'a [DirRollup],
+        level: usize,
+        rows: &mut Vec<(String, &'a DirRollup)>,
+    ) {
+        for rollup in rollups {
+            let name = rollup.dir.rsplit('/').next().unwrap_or(&rollup.dir);
+            rows.push((format!("{}{}/", "  ".repeat(level), name), rollup));
+            flatten(&rollup.children, level + 1, rows);
+        }
+    }
+    let mut rows = Vec::new();
+    flatten(rollups, 0, &mut rows);
+    let width = rows
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("Directory".len());
+
+    let mut lines = vec![
+        "By directory:".to_string(),
+        format!(
+            "  {:<width$}  {:>5}  {:>8}  {:>6}  Largest file",
+            "Directory", "Files", "Lines", "Share"
+        ),
+    ];
+    for (name, rollup) in rows {
+        lines.push(format!(
+            "  {:<width$}  {:>5}  {:>8}  {:>5.1}%  {} ({} lines)",
+            name, rollup.files, rollup.lines, rollup.share, rollup.largest.0, rollup.largest.1
+        ));
+    }
+    lines
+}
+
+/// Renders the `--stats` rollup for the JSON result.
+fn dir_rollup_json(rollups: &[DirRollup]) -> Vec<json::Object> {
+    rollups
+        .iter()
+        .map(|rollup| {
+            json::Object::new()
+                .string("dir", &rollup.dir)
+                .number("files", rollup.files)
+                .number("lines", rollup.lines)
+                .decimal("share", rollup.share, 1)
+                .string("largest", &rollup.largest.0)
+                .number("largest_lines", rollup.largest.1)
+                .objects("children", &dir_rollup_json(&rollup.children))
+        })
+        .collect()
+}
+
+/// Renders the per-kind rollup closing the `--stats` report.
+///
+/// A file counts toward a kind when all its top-level declarations are of
+/// that kind; files declaring several kinds are counted as `mixed`, and
+/// files declaring none (only free functions, constants, or types) as
+/// `none`. Declarations are counted by kind across all files, mixed ones
+/// included. The `mixed` and `none` rows are left out when empty.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let rollup = render_kind_rollup(&result.file_stats);
+/// // ["By kind:", "  Kind       Files  Declarations     Lines",
+/// //  "  contract       3             4       120", ...]
+/// ```
+fn render_kind_rollup(file_stats: &[(String, CleanStats)]) -> Vec<String> {
+    let row = |name: &str, files: usize, declarations: usize, lines: usize| {
+        format!(
+            "  {:<9}  {:>5}  {:>12}  {:>8}",
+            name, files, declarations, lines
+        )
+    };
+    let tally = |wanted: &dyn Fn(&CleanStats) -> bool| {
+        file_stats.iter().filter(|(_, stats)| wanted(stats)).fold(
+            (0, 0, 0),
+            |(files, declarations, lines), (_, stats)| {
+                (
+                    files + 1,
+                    declarations + stats.declarations.total(),
+                    lines + stats.cleaned_lines,
+                )
+            },
+        )
+    };
+
+    let mut lines = vec![
+        "By kind:".to_string(),
+        format!(
+            "  {:<9}  {:>5}  {:>12}  {:>8}",
+            "Kind", "Files", "Declarations", "Lines"
+        ),
+    ];
+    for kind in structure::Kind::ALL {
+        let (files, _, kept) = tally(&|s| s.declarations.single_kind() == Some(kind));
+        let declarations: usize = file_stats
+            .iter()
+            .map(|(_, stats)| stats.declarations.get(kind))
+            .sum();
+        lines.push(row(kind.name(), files, declarations, kept));
+    }
+    let (files, declarations, kept) =
+        tally(&|s| s.declarations.total() > 0 && s.declarations.single_kind().is_none());
+    if files > 0 {
+        lines.push(row("mixed", files, declarations, kept));
+    }
+    let (files, _, kept) = tally(&|s| s.declarations.total() == 0);
+    if files > 0 {
+        lines.push(row("none", files, 0, kept));
+    }
+    lines
+}
+
+/// Renders the `--stats` lines for the `--with-context` files, which are not
+/// Solidity and so are left out of the cleaning statistics.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// log.info(&render_context_report(&result.context));
+/// // Project context (not Solidity):
+/// //   README.md     120 lines  8192 bytes (of 20480)
+/// //   foundry.toml   12 lines   301 bytes
+/// ```
+fn render_context_report(files: &[context::ContextFile]) -> String {
+    let width = files.iter().map(|f| f.path.len()).max().unwrap_or(0);
+    let mut lines = vec!["Project context (not Solidity):".to_string()];
+    for file in files {
+        let mut line = format!(
+            "  {:<width$}  {:>5} lines  {:>8} bytes",
+            file.path,
+            file.line_count(),
+            file.text.len()
+        );
+        if let Some(size) = file.truncated_from {
+            line.push_str(&format!(" (of {})", size));
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Scrapes Solidity files from a remote git repository.
+///
+/// Clones the repository to a temporary directory, processes all Solidity files,
+/// and writes the consolidated output. The temporary directory is automatically
+/// cleaned up when the function returns.
+///
+/// # Arguments
+///
+/// * `url` — The git repository URL to clone
+/// * `destination` — The output directory for the consolidated file
+/// * `output_name` — Optional custom output name; defaults to repository name
+/// * `args` — Configuration affecting scraping behavior
+/// * `exec` — The policy subprocesses (git, df) are run under
+/// * `log` — Destination for progress messages and warnings
+///
+/// # Returns
+///
+/// A [`ScraperResult`] on success.
+///
+/// # Errors
+///
+/// Returns an error if cloning fails or if the scraping process encounters errors.
+/// See [`clone_repository`] and [`scrape_directory`] for specific error conditions.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let result = scrape_from_url(
+///     "https://github.com/OpenZeppelin/openzeppelin-contracts.git",
+///     "./output",
+///     Some("openzeppelin"),
+///     &args,
+///     &exec,
+///     &log
+/// )?;
+/// println!("Output: {}", result.output_path.display());
+/// ```
+fn scrape_from_url(
+    url: &str,
+    destination: &str,
+    output_name: Option<&str>,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let repo = forge_host::parse(url);
+    log.detail(&format!(
+        "Repository {} (owner {}, forge {:?})",
+        repo.name(),
+        repo.owner().as_deref().unwrap_or("none"),
+        repo.forge
+    ));
+    let started = Instant::now();
+    let (temp_dir, revision) = if args.no_git || git_missing() {
+        (fetch_archive(url, args, exec, log)?, None)
+    } else {
+        clone_to_temp(url, args, exec, log)?
+    };
+    let clone_time = started.elapsed();
+    if args.include_lib {
+        log.phase("submodules", || {
+            fill_submodules(temp_dir.path(), args, exec, log)
+        })?;
+    }
+    let root = checkout_root(temp_dir.path(), args)?;
+
+    let name = output_name
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| extract_repo_name(url));
+
+    let mut result = scrape_checkout(&root, destination, &name, revision, args, exec, log)?;
+    result.clone_time = Some(clone_time);
+    Ok(result)
+}
+
+/// Returns true if no directory of `PATH` has a `git` program, so that a
+/// GitHub source is downloaded instead of cloned (see [`fetch_archive`]).
+///
+/// PATH is searched rather than git run, so that the check leaves no
+/// invocation in the [`Exec`] audit.
+fn git_missing() -> bool {
+    let Some(path) = env::var_os("PATH") else {
+        return true;
+    };
+    !env::split_paths(&path).any(|dir| dir.join("git").is_file() || dir.join("git.exe").is_file())
+}
+
+/// Downloads the tarball of a GitHub `url` into a fresh temporary directory,
+/// for `--no-git` or when git is not installed, and says so.
+///
+/// The archive is of the `--rev` revision, or of the ref given with
+/// `--branch` or named by a web URL, or of the default branch; see
+/// [`tarball`] for the programs tried.
+///
+/// # Errors
+///
+/// Returns `"Git is not installed or not in PATH..."` (or, with `--no-git`,
+/// `"--no-git can only fetch GitHub repositories..."`) for any other URL,
+/// and the errors of [`tarball::fetch`].
+fn fetch_archive(
+    url: &str,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<tempfile::TempDir, String> {
+    let git_ref = args.rev.as_deref().or(args.url_ref.as_deref());
+    let Some(archive) = tarball::archive_url(&forge_host::parse(url), git_ref) else {
+        return Err(if args.no_git {
+            format!(
+                "--no-git can only fetch GitHub repositories, and {} is not one",
+                url
+            )
+        } else {
+            format!(
+                "Git is not installed or not in PATH, and {} is not a GitHub repository, \
+                 the only kind solscrape can download without it. Please install Git first.",
+                url
+            )
+        });
+    };
+    let temp_dir = create_clone_dir(args, exec, log)?;
+    log.progress(ProgressEvent::CloneStarted {
+        url: url.to_string(),
+    });
+    let timeout = time_left(args, "downloading")?;
+    let downloader = log.phase("download", || {
+        tarball::fetch(&archive, temp_dir.path(), exec, timeout, log)
+    })?;
+    log.progress(ProgressEvent::CloneFinished);
+    log.info(&format!(
+        "Downloaded {} with {} and {} ({}); it has no git history or submodules",
+        archive,
+        downloader,
+        tarball::EXTRACTOR,
+        if args.no_git {
+            "--no-git"
+        } else {
+            "git is not installed"
+        }
+    ));
+    Ok(temp_dir)
+}
+
+/// Checks out the submodules of the clone at `clone` for `--include-lib`.
+///
+/// Unless `--offline` is set or `clone` was downloaded as a tarball (it has
+/// no `.git`), each empty submodule is initialized on its own with
+/// `git submodule update --init --depth 1`, so that one that cannot be
+/// fetched is warned about without holding back the others, and the count
+/// initialized is reported as [`ProgressEvent::SubmodulesInitialized`].
+/// Each submodule still empty after that, or every one offline, is filled
+/// from its checkout under the cache root (see [`submodules`]); one that
+/// cannot be is warned about and left empty.
+///
+/// # Errors
+///
+/// Returns `"Deadline reached before initializing submodules"` once the
+/// `--deadline` has passed.
+fn fill_submodules(clone: &Path, args: &Args, exec: &Exec, log: &Logger) -> Result<(), String> {
+    let pending = submodules::unpopulated(clone);
+    if pending.is_empty() {
+        return Ok(());
+    }
+    if !args.offline && clone.join(".git").exists() {
+        let mut initialized = 0;
+        for submodule in &pending {
+            let timeout = time_left(args, "initializing submodules")?;
+            let git_args: [&std::ffi::OsStr; 9] = [
+                "-C".as_ref(),
+                clone.as_os_str(),
+                "submodule".as_ref(),
+                "update".as_ref(),
+                "--init".as_ref(),
+                "--depth".as_ref(),
+                "1".as_ref(),
+                "--".as_ref(),
+                submodule.path.as_ref(),
+            ];
+            match exec.output_within("git", git_args, None, timeout, log) {
+                Ok(output) if output.status.success() => initialized += 1,
+                Ok(output) => {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    log.warn(&format!(
+                        "Submodule init failed for {} ({}); looking for it in the Foundry cache",
+                        submodule.path,
+                        summarize_git_stderr(&stderr)
+                    ));
+                }
+                Err(e) => {
+                    log.warn(&format!(
+                        "Submodule init failed ({}); filling submodules from the Foundry cache",
+                        e
+                    ));
+                    break;
+                }
+            }
+        }
+        log.progress(ProgressEvent::SubmodulesInitialized {
+            initialized,
+            total: pending.len(),
+        });
+    }
+
+    let missing = submodules::unpopulated(clone);
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let Some(root) = submodules::cache_root(
+        args.foundry_cache.as_deref().map(Path::new),
+        env::var_os(submodules::HOME_ENV),
+    ) else {
+        log.warn("No Foundry cache to fill submodules from: HOME is not set; use --foundry-cache");
+        return Ok(());
+    };
+    let cache = submodules::Cache::index(&root);
+    log.detail(&format!(
+        "Foundry cache {}: {} checkout(s)",
+        root.display(),
+        cache.len()
+    ));
+    let outcome = submodules::fill_from_cache(clone, missing, &cache);
+    for (submodule, checkout) in outcome.filled {
+        log.info(&format!(
+            "Filled {} from {} (its commit may differ from the pinned one)",
+            submodule.path,
+            checkout.display()
+        ));
+    }
+    for (submodule, reason) in outcome.unsatisfied {
+        log.warn(&format!(
+            "Submodule {} ({}) left empty: {} in {}",
+            submodule.path,
+            submodule.url,
+            reason,
+            root.display()
+        ));
+    }
+    Ok(())
+}
+
+/// The directory of `checkout` to scrape: the one a web URL named, or the
+/// checkout itself.
+///
+/// # Errors
+///
+/// Returns `"{source} has no directory {subdir}"` when it is missing, and
+/// refuses a directory outside the checkout.
+fn checkout_root(checkout: &Path, args: &Args) -> Result<PathBuf, String> {
+    let Some(subdir) = &args.url_subdir else {
+        return Ok(checkout.to_path_buf());
+    };
+    let relative = Path::new(subdir);
+    let root = checkout.join(relative);
+    let inside = relative
+        .components()
+        .all(|c| matches!(c, std::path::Component::Normal(_)));
+    if !inside || !root.is_dir() {
+        return Err(format!("{} has no directory {}", args.source, subdir));
+    }
+    Ok(root)
+}
+
+/// Scrapes a clone or local source, restricted to the files changed since
+/// the `--since` ref and to the files related to the `--related` target
+/// when those are given, and records `revision`.
+fn scrape_checkout(
+    source_dir: &Path,
+    destination: &str,
+    output_name: &str,
+    revision: Option<GitRevision>,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    if args.since.is_none() && args.related.is_none() {
+        let mut result = scrape_directory(source_dir, destination, output_name, args, log)?;
+        result.revision = revision;
+        return Ok(result);
+    }
+    let changes = match &args.since {
+        Some(git_ref) => Some(log.phase("since", || {
+            changed_since(source_dir, git_ref, args, exec, log)
+        })?),
+        None => None,
+    };
+    let related = match &args.related {
+        Some(target) => Some(log.phase("imports", || related_files(source_dir, target, log))?),
+        None => None,
+    };
+    let mut selection = Selection::new(source_dir, args, log)?;
+    if let Some(changes) = &changes {
+        selection.restrict_to(source_dir, changes);
+    }
+    if let Some(related) = &related {
+        let target = source_dir.join(&related.target);
+        let decision = selection
+            .policy
+            .decide_file(Path::new(&related.target), || {
+                exclusion::read_marker(&target)
+            });
+        if let exclusion::Decision::Exclude(reason) = decision {
+            log.warn(&excluded_target_warning(&related.target, &reason));
+        }
+        selection.relate(source_dir, related.clone());
+    }
+    let mut result = scrape_selection(source_dir, selection, destination, output_name, args, log)?;
+    result.since = changes;
+    result.related = related.map(|related| related.within(&result.files_processed));
+    result.revision = revision;
+    Ok(result)
+}
+
+/// Refreshes the existing clone at `clone` from `url` and scrapes it in
+/// place (`--reuse-clone`).
+///
+/// No temporary directory is involved; the output is named after `url`
+/// unless `output_name` is given. See [`refresh_clone`] for the checks made
+/// before the clone is touched.
+fn scrape_reused_clone(
+    clone: &Path,
+    url: &str,
+    destination: &str,
+    output_name: Option<&str>,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let started = Instant::now();
+    let revision = log.phase("refresh", || refresh_clone(clone, url, args, exec, log))?;
+    let clone_time = started.elapsed();
+    let name = output_name
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| extract_repo_name(url));
+
+    let mut result = scrape_checkout(clone, destination, &name, revision, args, exec, log)?;
+    result.clone_time = Some(clone_time);
+    Ok(result)
+}
+
+/// Clones `url` into a fresh temporary directory.
+///
+/// Checks free space first (see [`create_clone_dir`]) and detects the
+/// checked-out revision afterwards. The ref given with `--branch`, or
+/// named by a web URL, is cloned instead of the default branch; a failed
+/// clone of one names it, since git's own message may not. With `--rev`,
+/// the default branch is cloned without a checkout and the revision is
+/// checked out afterwards (see [`checkout_revision`]).
+///
+/// # Returns
+///
+/// The temporary directory, removed when dropped, and the clone's revision.
+fn clone_to_temp(
+    url: &str,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<(tempfile::TempDir, Option<GitRevision>), String> {
+    let temp_dir = create_clone_dir(args, exec, log)?;
+    let temp_path = temp_dir.path();
+
+    log.progress(ProgressEvent::CloneStarted {
+        url: url.to_string(),
+    });
+
+    let mut options = vec!["--depth", "1"];
+    if let Some(git_ref) = &args.url_ref {
+        options.extend(["--branch", git_ref.as_str()]);
+    }
+    if args.rev.is_some() {
+        options.push("--no-checkout");
+    }
+    log.phase("clone", || {
+        clone_repository(url, temp_path, &options, args, exec, log).map_err(|e| {
+            match &args.url_ref {
+                Some(git_ref) => format!("{} (cloning branch {})", e, git_ref),
+                None => e,
+            }
+        })
+    })?;
+    let pinned = match &args.rev {
+        Some(rev) => Some(log.phase("checkout", || {
+            checkout_revision(temp_path, "origin", rev, args, exec, log)
+        })?),
+        None => None,
+    };
+    log.progress(ProgressEvent::CloneFinished);
+
+    let revision = detect_git_revision(temp_path, exec, log)
+        .map(|revision| GitRevision { pinned, ..revision });
+    log.detail(&format!("Checked out revision: {:?}", revision));
+    Ok((temp_dir, revision))
+}
+
+/// Creates the temporary directory a clone goes into.
+///
+/// Notes leftovers of interrupted runs (see `cleanup-temp`) and checks free
+/// space first (see [`space::check_available`]).
+fn create_clone_dir(args: &Args, exec: &Exec, log: &Logger) -> Result<tempfile::TempDir, String> {
+    let temp_base = temp_base_dir(args);
+    let (orphans, _) = tempfile::find_orphans(&temp_base, DEFAULT_CLEANUP_AGE);
+    if !orphans.is_empty() {
+        log.info(&format!(
+            "Note: {} temporary clone{} left by interrupted runs in {}; \
+             run `solscrape cleanup-temp` to remove them",
+            orphans.len(),
+            if orphans.len() == 1 { "" } else { "s" },
+            temp_base.display()
+        ));
+    }
+    let min_space = args.min_temp_space.unwrap_or(space::DEFAULT_MIN_TEMP_SPACE);
+    space::check_available(&temp_base, min_space, exec, log)?;
+    let temp_dir = tempfile::tempdir_in(&temp_base)
+        .map_err(|e| format!("Failed to create temp dir: {}", e))?;
+
+    log.detail(&format!(
+        "Temporary clone directory: {}",
+        temp_dir.path().display()
+    ));
+    Ok(temp_dir)
+}
+
+/// The directory temporary clones are created in, per [`tempfile::base_dir`].
+fn temp_base_dir(args: &Args) -> PathBuf {
+    tempfile::base_dir(
+        args.temp_dir.as_deref().map(Path::new),
+        env::var_os(tempfile::TMPDIR_ENV),
+    )
+}
+
+/// Checks that a `--local` source is an existing directory.
+///
+/// # Errors
+///
+/// Returns `"Source path does not exist: {path}"` or
+/// `"Source path is not a directory: {path}"`.
+fn check_local_source(path: &str) -> Result<&Path, String> {
+    let source_path = Path::new(path);
+
+    if !source_path.exists() {
+        return Err(format!("Source path does not exist: {}", path));
+    }
+
+    if !source_path.is_dir() {
+        return Err(format!("Source path is not a directory: {}", path));
+    }
+
+    Ok(source_path)
+}
+
+/// Scrapes Solidity files from a local directory.
+///
+/// Processes all Solidity files in the specified local path and writes the
+/// consolidated output. Unlike [`scrape_from_url`], this operates directly on
+/// the filesystem without cloning.
+///
+/// # Arguments
+///
+/// * `path` — The local directory path containing Solidity files
+/// * `destination` — The output directory for the consolidated file
+/// * `output_name` — Optional custom output name; defaults to directory name
+/// * `args` — Configuration affecting scraping behavior
+/// * `exec` — The policy subprocesses (git, df) are run under
+/// * `log` — Destination for progress messages and warnings
+///
+/// # Returns
+///
+/// A [`ScraperResult`] on success.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Source path does not exist: {path}"` | The specified path doesn't exist |
+/// | `"Source path is not a directory: {path}"` | The path is a file, not a directory |
+///
+/// Additional errors may come from [`scrape_directory`].
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let result = scrape_from_local(
+///     "./my-project",
+///     "./output",
+///     Some("my_contracts"),
+///     &args,
+///     &exec,
+///     &log
+/// )?;
+/// ```
+fn scrape_from_local(
+    path: &str,
+    destination: &str,
+    output_name: Option<&str>,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<ScraperResult, RunError> {
+    let source_path = check_local_source(path)?;
+
+    log.progress(ProgressEvent::ScanStarted {
+        root: source_path.to_path_buf(),
+    });
+
+    let name = output_name.map(|s| s.to_string()).unwrap_or_else(|| {
+        source_path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "local".to_string())
+    });
+
+    let revision = detect_git_revision(source_path, exec, log);
+    log.detail(&format!("Local revision: {:?}", revision));
+
+    scrape_checkout(source_path, destination, &name, revision, args, exec, log)
+}
+
+/// Scrapes each of several `--source` in turn, into one output or, with
+/// `--split-by-source`, one output per source.
+///
+/// Each source is acquired as it would be on its own (cloned, or scanned in
+/// place when it is a local directory) and scraped in memory. Its paths are
+/// then put under its name, so `solmate/src/Vault.sol` and
+/// `vault/src/Vault.sol` stay apart, and in a combined output its files
+/// start with a [`library::render_source_banner`] unless `--no-headers`.
+/// Names are those outputs would get, with `-2`, `-3`, ... after a repeat.
+///
+/// A source that fails is reported and left out, and recorded in
+/// [`ScraperResult::failed_sources`] for the caller to fail the run on once
+/// everything else is written; with `--strict`, the first failure ends the
+/// run at once instead.
+///
+/// # Errors
+///
+/// | Error | Code | Condition |
+/// |-------|------|-----------|
+/// | `"{source} failed: ..."` | That of the failure | A source failed under `--strict` |
+/// | `"All {n} sources failed"` | That of the first failure | No source was scraped |
+///
+/// Writing fails as in [`write_scrape_output`].
+fn scrape_sources(args: &Args, exec: &Exec, log: &Logger) -> Result<ScraperResult, RunError> {
+    let mut merged: Option<ScrapeOutput> = None;
+    let mut sources: Vec<(String, usize)> = Vec::new();
+    let mut failed: Vec<(String, RunError)> = Vec::new();
+    let mut clone_time: Option<Duration> = None;
+    for source in &args.sources {
+        let mut one = args.clone();
+        one.sources = Vec::new();
+        one.source = source.clone();
+        let notes: Vec<String> = autodetect_local(&mut one)
+            .into_iter()
+            .chain(resolve_web_url(&mut one))
+            .collect();
+        log.info(&format!("Scraping {}", source));
+        for note in &notes {
+            log.info(note);
+        }
+        let (mut scraped, elapsed) = match scrape_source_to_memory(&one, exec, log) {
+            Ok(scraped) => scraped,
+            Err(e) if args.strict => {
+                return Err(RunError {
+                    message: format!("{} failed: {}", source, e.message),
+                    code: e.code,
+                });
+            }
+            Err(e) => {
+                log.warn(&format!("{} failed: {}", source, e.message));
+                failed.push((source.clone(), e));
+                continue;
+            }
+        };
+        if let Some(elapsed) = elapsed {
+            clone_time = Some(clone_time.unwrap_or_default() + elapsed);
+        }
+
+        let base = source_name(&one);
+        let mut name = base.clone();
+        let mut repeat = 1;
+        while sources.iter().any(|(taken, _)| *taken == name) {
+            repeat += 1;
+            name = format!("{}-{}", base, repeat);
+        }
+        put_under(&mut scraped, &name);
+        if !args.split_by_source && !args.no_headers {
+            let banner = library::render_source_banner(&name, args.output_charset.symbols());
+            scraped.line_count += banner.lines().count();
+            scraped.parts[0].text = format!("{}\n{}", banner, scraped.parts[0].text);
+        }
+        sources.push((name, scraped.parts.len()));
+        merged = Some(match merged {
+            None => scraped,
+            Some(mut all) => {
+                all.parts.extend(scraped.parts);
+                all.file_stats.extend(scraped.file_stats);
+                all.warnings.extend(scraped.warnings);
+                all.todos.extend(scraped.todos);
+                all.errors.merge(scraped.errors);
+                all.licenses.extend(scraped.licenses);
+                all.missing_imports.extend(scraped.missing_imports);
+                all.skipped.extend(scraped.skipped);
+                all.duplicates.extend(scraped.duplicates);
+                all.decisions.extend(scraped.decisions);
+                all.line_count += scraped.line_count;
+                all.pragmas_injected += scraped.pragmas_injected;
+                all
+            }
+        });
+    }
+
+    let Some(mut merged) = merged else {
+        let code = failed.first().map_or(EXIT_FAILURE, |(_, e)| e.code);
+        return Err(RunError {
+            message: format!("All {} sources failed", failed.len()),
+            code,
+        });
+    };
+    merged.project = project::ProjectKind::default();
+    let output_name = match &args.output_name {
+        Some(name) => name.clone(),
+        None => sources
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join("_"),
+    };
+    let mut result = if args.split_by_source {
+        write_split_by_dir(merged, &args.destination, &output_name, args, log)?
+    } else {
+        write_scrape_output(merged, &args.destination, &output_name, args, log)?
+    };
+    add_directory_rollup(&mut result, args);
+    result.clone_time = clone_time;
+    result.sources = sources;
+    result.failed_sources = failed;
+    Ok(result)
+}
+
+/// Acquires the one source of `args` and scrapes it in memory, returning
+/// the time spent cloning when it was cloned.
+fn scrape_source_to_memory(
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<(ScrapeOutput, Option<Duration>), RunError> {
+    if !clones_source(args) {
+        let scraped = scrape_directory_to_memory(check_local_source(&args.source)?, args, log)?;
+        return Ok((scraped, None));
+    }
+    if wants_precheck(args) && precheck_supported(args, exec, log) {
+        confirm_remote(&args.source, args, exec, log)?;
+    }
+    if wants_size_probe(args) {
+        confirm_clone_size(&args.source, args, exec, log)?;
+    }
+    let started = Instant::now();
+    let temp_dir = if args.no_git || git_missing() {
+        fetch_archive(&args.source, args, exec, log)?
+    } else {
+        clone_to_temp(&args.source, args, exec, log)?.0
+    };
+    let clone_time = started.elapsed();
+    if args.include_lib {
+        log.phase("submodules", || {
+            fill_submodules(temp_dir.path(), args, exec, log)
+        })?;
+    }
+    let root = checkout_root(temp_dir.path(), args)?;
+    Ok((
+        scrape_directory_to_memory(&root, args, log)?,
+        Some(clone_time),
+    ))
+}
+
+/// The name the one source of `args` is shown and written under: the
+/// repository's for a clone, the directory's for a local path.
+fn source_name(args: &Args) -> String {
+    if clones_source(args) {
+        return extract_repo_name(&args.source);
+    }
+    fs::canonicalize(&args.source)
+        .ok()
+        .and_then(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_else(|| "local".to_string())
+}
+
+/// Puts every path of `scraped` under the directory `name`.
+fn put_under(scraped: &mut ScrapeOutput, name: &str) {
+    let under = |path: &mut String| *path = format!("{}/{}", name, path);
+    for part in &mut scraped.parts {
+        under(&mut part.path);
+    }
+    for (path, _) in &mut scraped.file_stats {
+        under(path);
+    }
+    for (path, _) in &mut scraped.warnings {
+        under(path);
+    }
+    for (path, _) in &mut scraped.todos {
+        under(path);
+    }
+    for (path, reason, redundant) in &mut scraped.skipped {
+        under(path);
+        if let (SkipReason::Duplicate, Some(kept)) = (reason, redundant) {
+            under(kept);
+        }
+    }
+    for (path, _) in scraped
+        .licenses
+        .iter_mut()
+        .chain(&mut scraped.missing_imports)
+    {
+        under(path);
+    }
+    for path in scraped.duplicates.iter_mut().flatten() {
+        under(path);
+    }
+    for decision in &mut scraped.decisions {
+        under(&mut decision.path);
+    }
+    scraped.errors.put_under(name);
+}
+
+// ============================================================================
+// Ref Comparison
+// ============================================================================
+
+/// The outcome of [`compare_refs`].
+struct Comparison {
+    /// The consolidated comparison file.
+    output_path: PathBuf,
+    /// Files present at both refs whose cleaned code differs.
+    changed: Vec<String>,
+    /// Files only present at the new ref.
+    added: Vec<String>,
+    /// Files only present at the old ref.
+    removed: Vec<String>,
+    /// Files whose cleaned code is identical at both refs.
+    unchanged: Vec<String>,
+    /// The number of lines in the output.
+    line_count: usize,
+}
+
+/// Writes both versions of the files that differ between two refs of `url`.
+///
+/// The repository is cloned once without a checkout, each ref is
+/// materialized as a detached worktree in the temporary directory, and both
+/// trees are selected and cleaned exactly as for a scrape. Files are compared
+/// after cleaning, so comment-only edits count as unchanged. Each changed file
+/// appears twice, under `// File (<old>): path` and `// File (<new>): path`
+/// headers; added and removed files appear once. A leading comment block
+/// lists every file by category, unchanged ones included.
+///
+/// Branch names that only exist on the remote are resolved as
+/// `origin/<name>`.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Git clone failed: ..."` | See [`clone_repository`] |
+/// | `"Unknown ref {ref}: ..."` | A ref names no commit in the repository |
+/// | `"No Solidity files differ between ..."` | Both refs have the same cleaned sources |
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let refs = ("v1.0.0".to_string(), "v2.0.0".to_string());
+/// let comparison = compare_refs(url, "./out", None, &refs, &args, &exec, &log)?;
+/// println!("{} files changed", comparison.changed.len());
+/// ```
+fn compare_refs(
+    url: &str,
+    destination: &str,
+    output_name: Option<&str>,
+    refs: &(String, String),
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<Comparison, String> {
+    let (old_ref, new_ref) = refs;
+    let temp_dir = create_clone_dir(args, exec, log)?;
+    let repo = temp_dir.path().join("repo");
+
+    log.progress(ProgressEvent::CloneStarted {
+        url: url.to_string(),
+    });
+    log.phase("clone", || {
+        clone_repository(url, &repo, &["--no-checkout"], args, exec, log)
+    })?;
+    log.progress(ProgressEvent::CloneFinished);
+
+    let body_args = Args {
+        no_headers: true,
+        ..args.clone()
+    };
+    let mut trees = Vec::new();
+    for (side, git_ref) in [("old", old_ref), ("new", new_ref)] {
+        let tree = temp_dir.path().join(side);
+        add_worktree(&repo, &tree, git_ref, exec, log)?;
+        log.info(&format!("Reading {} ({})", git_ref, side));
+        trees.push(log.phase("processing", || {
+            cleaned_sources(&tree, git_ref, &body_args, log)
+        })?);
+    }
+    let new_files = trees.pop().unwrap_or_default();
+    let old_files = trees.pop().unwrap_or_default();
+
+    let mut comparison = Comparison {
+        output_path: PathBuf::new(),
+        changed: Vec::new(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        unchanged: Vec::new(),
+        line_count: 0,
+    };
+    let mut sections = Vec::new();
+    let separator = args.output_charset.symbols().header_separator;
+    let section = |git_ref: &str, path: &str, code: &str| {
+        format!(
+            "{}\n// File ({}): {}\n{}\n{}",
+            separator,
+            git_ref,
+            naming::display_path(path),
+            separator,
+            code
+        )
+    };
+    let paths: std::collections::BTreeSet<&String> =
+        old_files.keys().chain(new_files.keys()).collect();
+    for path in paths {
+        match (old_files.get(path), new_files.get(path)) {
+            (Some(old), Some(new)) if old == new => comparison.unchanged.push(path.clone()),
+            (Some(old), Some(new)) => {
+                sections.push(section(old_ref, path, old));
+                sections.push(section(new_ref, path, new));
+                comparison.changed.push(path.clone());
+            }
+            (Some(old), None) => {
+                sections.push(section(old_ref, path, old));
+                comparison.removed.push(path.clone());
+            }
+            (None, Some(new)) => {
+                sections.push(section(new_ref, path, new));
+                comparison.added.push(path.clone());
+            }
+            (None, None) => {}
+        }
+    }
+
+    if sections.is_empty() {
+        return Err(format!(
+            "No Solidity files differ between {} and {} after cleaning",
+            old_ref, new_ref
+        ));
+    }
+
+    let mut index = vec![format!(
+        "// Comparison {}..{}: {} changed, {} added, {} removed, {} unchanged",
+        old_ref,
+        new_ref,
+        comparison.changed.len(),
+        comparison.added.len(),
+        comparison.removed.len(),
+        comparison.unchanged.len()
+    )];
+    for (label, files) in [
+        ("Changed", &comparison.changed),
+        ("Added", &comparison.added),
+        ("Removed", &comparison.removed),
+        ("Unchanged", &comparison.unchanged),
+    ] {
+        if !files.is_empty() {
+            index.push(format!("// {}:", label));
+            index.extend(
+                files
+                    .iter()
+                    .map(|f| format!("//   {}", naming::display_path(f))),
+            );
+        }
+    }
+    sections.insert(0, index.join("\n") + "\n");
+    let text = sections.join("\n");
+    comparison.line_count = text.lines().count();
+
+    let repo_name = output_name
+        .map(|s| s.to_string())
+        .unwrap_or_else(|| extract_repo_name(url));
+    let name = format!(
+        "{}_{}_vs_{}",
+        repo_name,
+        naming::file_name_safe(old_ref),
+        naming::file_name_safe(new_ref)
+    );
+    let (output_path, _) = prepare_output(destination, &name, args, log)?;
+    log.progress(ProgressEvent::WritingOutput {
+        path: output_path.clone(),
+    });
+    log.phase("write", || {
+        output::write_atomic(&output_path, text.as_bytes())
+    })?;
+    log.progress(ProgressEvent::Finished {
+        files: comparison.changed.len() + comparison.added.len() + comparison.removed.len(),
+        lines: comparison.line_count,
+        output: output_path.clone(),
+    });
+
+    comparison.output_path = output_path;
+    Ok(comparison)
+}
+
+/// Checks out `git_ref` of the clone at `repo` as a detached worktree at `tree`.
+///
+/// Falls back to `origin/<git_ref>` so remote branch names work as well as
+/// tags and commits.
+fn add_worktree(
+    repo: &Path,
+    tree: &Path,
+    git_ref: &str,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<(), String> {
+    let mut failure = String::new();
+    for candidate in [git_ref.to_string(), format!("origin/{}", git_ref)] {
+        let worktree_args = [
+            "-C".as_ref(),
+            repo.as_os_str(),
+            "worktree".as_ref(),
+            "add".as_ref(),
+            "--detach".as_ref(),
+            tree.as_os_str(),
+            candidate.as_ref(),
+        ];
+        let output = exec
+            .output("git", worktree_args, None, log)
+            .map_err(|e| format!("Failed to execute git: {}", e))?;
+        if output.status.success() {
+            log.detail(&format!("Checked out {} at {}", candidate, tree.display()));
+            return Ok(());
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log.detail(&format!("Raw git worktree stderr:\n{}", stderr));
+        if failure.is_empty() {
+            failure = summarize_git_stderr(&stderr);
+        }
+    }
+    Err(format!("Unknown ref {}: {}", git_ref, failure))
+}
+
+/// Cleans every selected file under `root`, keyed by relative path.
+///
+/// Files that are not included are reported with the ref they belong to
+/// (see [`report_skip`]) and left out, as in a scrape.
+fn cleaned_sources(
+    root: &Path,
+    git_ref: &str,
+    args: &Args,
+    log: &Logger,
+) -> Result<std::collections::BTreeMap<String, String>, String> {
+    let selection = Selection::new(root, args, log)?;
+    let mut sources = std::collections::BTreeMap::new();
+    for path in selection.discover(root, log)? {
+        let relative = naming::relative_path(&path, root);
+        let skip = match process_file(&path, root, args, log) {
+            Ok(FileOutcome::Content { text, .. }) => {
+                sources.insert(relative, text);
+                continue;
+            }
+            Ok(FileOutcome::Empty) => Skip::empty(),
+            Ok(FileOutcome::Skipped(skip)) => skip,
+            Err(e) => Skip::unreadable(&e),
+        };
+        report_skip(&naming::display_path(&relative), Some(git_ref), &skip, log);
+    }
+    Ok(sources)
+}
+
+/// Compares two refs of the source and prints the summary, for `--compare`,
+/// returning the path of the output.
+fn run_compare(
+    refs: &(String, String),
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+) -> Result<PathBuf, String> {
+    let comparison = compare_refs(
+        &args.source,
+        &args.destination,
+        args.output_name.as_deref(),
+        refs,
+        args,
+        exec,
+        log,
+    )?;
+
+    print_result(args, &comparison.output_path, None)?;
+
+    let symbols = args.charset.symbols();
+    log.info("");
+    log.info(symbols.summary_rule);
+    log.info(&format!("{} Success!", symbols.success));
+    log.info(&format!("   Source:          {}", args.source));
+    log.info(&format!("   Compared:        {}..{}", refs.0, refs.1));
+    log.info(&format!("   Changed:         {}", comparison.changed.len()));
+    log.info(&format!("   Added:           {}", comparison.added.len()));
+    log.info(&format!("   Removed:         {}", comparison.removed.len()));
+    log.info(&format!(
+        "   Unchanged:       {}",
+        comparison.unchanged.len()
+    ));
+    log.info(&format!("   Total lines:     {}", comparison.line_count));
+    log.info(&format!(
+        "   Output:          {}",
+        comparison.output_path.display()
+    ));
+    log.info(symbols.summary_rule);
+    Ok(comparison.output_path)
+}
+
+// ============================================================================
+// Temporary Directory (simple implementation)
+// ============================================================================
+
+/// A minimal temporary directory implementation with automatic cleanup.
+///
+/// This module provides `TempDir` and `tempdir_in()` as a zero-dependency
+/// alternative to the `tempfile` crate. Temporary directories are automatically
+/// removed when the `TempDir` is dropped.
+///
+/// # Design Notes
+///
+/// Directory names are generated using nanosecond timestamps to ensure uniqueness.
+/// The cleanup on drop uses best-effort semantics—errors are silently ignored.
+mod tempfile {
+    use std::ffi::OsString;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Environment variable overriding the base directory for temporary clones.
+    pub const TMPDIR_ENV: &str = "SOLSCRAPE_TMPDIR";
+
+    /// Name prefix of every directory created by [`tempdir_in`].
+    pub const PREFIX: &str = "solscrape_";
+
+    /// Suffix of the lock file written next to each temporary directory.
+    ///
+    /// The lock lives beside the directory rather than inside it because git
+    /// refuses to clone into a non-empty directory.
+    const LOCK_SUFFIX: &str = ".lock";
+
+    /// A temporary directory that is automatically removed on drop.
+    ///
+    /// Created via [`tempdir_in`], this struct owns a directory in the system's
+    /// temporary directory. The directory and all its contents are deleted
+    /// when this struct is dropped.
+    ///
+    /// # Lifecycle
+    ///
+    /// - **Construction** ([`tempdir_in`]): Creates a new directory with a unique name
+    ///   and a sibling lock file holding this process's id
+    /// - **Clone**: Not implemented; temporary directories are single-owner
+    /// - **Drop**: Recursively deletes the directory and all contents, then the lock
+    ///
+    /// A killed process never runs `Drop`; [`remove_orphans`] cleans up after it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let temp = tempdir_in(&base_dir(None, None))?;
+    /// let file_path = temp.path().join("data.txt");
+    /// std::fs::write(&file_path, "hello")?;
+    /// // Directory is deleted when `temp` goes out of scope
+    /// ```
+    pub struct TempDir {
+        /// The absolute path to the temporary directory.
+        path: PathBuf,
+        /// The lock file marking the directory as in use.
+        lock: PathBuf,
+    }
+
+    impl TempDir {
+        /// The path to this temporary directory.
+        pub fn path(&self) -> &std::path::Path {
+            &self.path
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+            let _ = fs::remove_file(&self.lock);
+        }
+    }
+
+    /// Resolves the base directory for temporary directories.
+    ///
+    /// Precedence: an explicit directory (`--temp-dir`), then a non-empty
+    /// `env_override` (the value of [`TMPDIR_ENV`]), then the platform default
+    /// from [`std::env::temp_dir`].
+    pub fn base_dir(explicit: Option<&Path>, env_override: Option<OsString>) -> PathBuf {
+        if let Some(dir) = explicit {
+            return dir.to_path_buf();
+        }
+        match env_override {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => std::env::temp_dir(),
+        }
+    }
+
+    /// Creates a new temporary directory with a unique name under `base`.
+    ///
+    /// The directory is named `solscrape_{timestamp}` and locked by a
+    /// `solscrape_{timestamp}.lock` file beside it; `base` is created if
+    /// needed. Use [`base_dir`] to pick `base`.
+    ///
+    /// # Returns
+    ///
+    /// A [`TempDir`] that will be automatically cleaned up on drop.
+    ///
+    /// # Errors
+    ///
+    /// Returns an I/O error if the directory cannot be created.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let temp = tempdir_in(&base_dir(None, None))?;
+    /// println!("Using temp dir: {}", temp.path().display());
+    /// ```
+    pub fn tempdir_in(base: &Path) -> std::io::Result<TempDir> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+
+        let dir_name = format!("{}{}", PREFIX, timestamp);
+        let path = base.join(&dir_name);
+        let lock = lock_path(&path);
+
+        fs::create_dir_all(&path)?;
+        fs::write(&lock, std::process::id().to_string())?;
+
+        Ok(TempDir { path, lock })
+    }
+
+    /// The lock file path for the temporary directory at `dir`.
+    fn lock_path(dir: &Path) -> PathBuf {
+        let mut name = dir.as_os_str().to_owned();
+        name.push(LOCK_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Returns true if `dir` is locked by a process that is still running.
+    ///
+    /// Liveness can only be checked through `/proc`; elsewhere any lock whose
+    /// content is a process id is assumed live.
+    fn is_locked(dir: &Path) -> bool {
+        let Ok(content) = fs::read_to_string(lock_path(dir)) else {
+            return false;
+        };
+        match content.trim().parse::<u32>() {
+            Ok(pid) if Path::new("/proc/self").exists() => {
+                Path::new("/proc").join(pid.to_string()).exists()
+            }
+            Ok(_) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// What [`remove_orphans`] did.
+    #[derive(Debug, Default, PartialEq)]
+    pub struct CleanupReport {
+        /// Directories removed.
+        pub removed: Vec<PathBuf>,
+        /// Bytes freed by removing them.
+        pub bytes_freed: u64,
+        /// Directories left alone because a live run holds their lock.
+        pub in_use: Vec<PathBuf>,
+        /// Directories that could not be removed, with the reason.
+        pub failed: Vec<(PathBuf, String)>,
+    }
+
+    /// Lists the `solscrape_*` directories under `base` left by dead runs.
+    ///
+    /// Only directories last modified at least `min_age` ago are returned;
+    /// locked directories of live runs are returned separately as the second
+    /// element, whatever their age.
+    pub fn find_orphans(base: &Path, min_age: Duration) -> (Vec<PathBuf>, Vec<PathBuf>) {
+        let Ok(entries) = fs::read_dir(base) else {
+            return (Vec::new(), Vec::new());
+        };
+        let now = SystemTime::now();
+        let mut orphans = Vec::new();
+        let mut in_use = Vec::new();
+
+        for entry in entries.filter_map(Result::ok) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+            if !name.starts_with(PREFIX) || !is_dir {
+                continue;
+            }
+            let path = entry.path();
+            if is_locked(&path) {
+                in_use.push(path);
+                continue;
+            }
+            let age = entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .unwrap_or_default();
+            if age >= min_age {
+                orphans.push(path);
+            }
+        }
+        orphans.sort();
+        in_use.sort();
+        (orphans, in_use)
+    }
+
+    /// Removes the orphaned temporary directories under `base` and their locks.
+    ///
+    /// See [`find_orphans`] for which directories qualify; a `min_age` of zero
+    /// removes every directory not held by a live run.
+    pub fn remove_orphans(base: &Path, min_age: Duration) -> CleanupReport {
+        let (orphans, in_use) = find_orphans(base, min_age);
+        remove(orphans, in_use)
+    }
+
+    /// Removes the temporary directories under `base` whose lock names a
+    /// run that has ended, for `--resume` after an interrupted batch.
+    ///
+    /// Unlike [`remove_orphans`], a directory without a lock is left alone,
+    /// however old: it may not be a clone at all.
+    pub fn remove_dead(base: &Path) -> CleanupReport {
+        let (orphans, in_use) = find_orphans(base, Duration::ZERO);
+        let dead = orphans
+            .into_iter()
+            .filter(|path| lock_path(path).exists())
+            .collect();
+        remove(dead, in_use)
+    }
+
+    /// Removes `orphans` and their locks, reporting `in_use` as left alone.
+    fn remove(orphans: Vec<PathBuf>, in_use: Vec<PathBuf>) -> CleanupReport {
+        let mut report = CleanupReport {
+            in_use,
+            ..CleanupReport::default()
+        };
+        for path in orphans {
+            let size = dir_size(&path);
+            match fs::remove_dir_all(&path) {
+                Ok(()) => {
+                    let _ = fs::remove_file(lock_path(&path));
+                    report.bytes_freed += size;
+                    report.removed.push(path);
+                }
+                Err(e) => report.failed.push((path, e.to_string())),
+            }
+        }
+        report
+    }
+
+    /// The total size of the files under `path`, not following symlinks.
+    fn dir_size(path: &Path) -> u64 {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return 0;
+        };
+        if !metadata.is_dir() {
+            return metadata.len();
+        }
+        fs::read_dir(path)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| dir_size(&entry.path()))
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+}
+
+// ============================================================================
+// Entry Point
+// ============================================================================
+
+/// Executes the main scraping workflow based on command-line arguments.
+///
+/// This is the core application logic, separated from `main` to enable proper
+/// error handling with the `?` operator. It parses arguments, sets up the
+/// [`Logger`], performs the scraping operation via [`run_scrape`], and records
+/// any resulting error in the log. `charset`, picked by `main`, replaces
+/// whatever the arguments asked for.
+///
+/// # Returns
+///
+/// - `Ok(())` — Scraping completed successfully (or help/version was shown)
+/// - `Err(error)` — An error occurred; [`RunError`] carries a displayable
+///   message and the exit code
+///
+/// # Behavioral Contract
+///
+/// - If `--help` or `--version` is passed, prints the requested info and returns `Ok`
+/// - In quiet mode, only the output path is printed to stdout
+/// - In normal mode, a banner, progress messages, and summary are printed
+/// - With `--log-file`, an unwritable log path fails before any work starts
+fn run(charset: Charset) -> Result<(), RunError> {
+    let mut args = parse_args()?;
+    args.charset = charset;
+
+    if args.show_help {
+        print_help(args.help_page, charset.symbols());
+        return Ok(());
+    }
+
+    if args.show_version {
+        print_version(args.version_full || args.verbose);
+        return Ok(());
+    }
+
+    let autodetected = autodetect_local(&mut args);
+    let web_url = resolve_web_url(&mut args);
+    let warnings = check_flag_combinations(&args)?;
+    let log = build_logger(&args)?;
+    log.detail(&format!("solscrape {} started", VERSION));
+    for note in autodetected.iter().chain(&web_url) {
+        log.info(note);
+    }
+    log.detail(&format!("Resolved arguments: {:?}", args));
+    for warning in &warnings {
+        log.warn(warning);
+    }
+    let exec = build_exec(&args);
+
+    let result = match &args.subcommand {
+        Some(Subcommand::Split {
+            input,
+            out_dir,
+            mtime_from,
+        }) => run_split(input, out_dir, mtime_from.as_deref(), &args, &log).map_err(RunError::from),
+        Some(Subcommand::Extract { input, path }) => {
+            run_extract(input, path).map_err(RunError::from)
+        }
+        Some(Subcommand::Verify { input }) => {
+            run_verify(input, &args, &log).map_err(RunError::from)
+        }
+        Some(Subcommand::Check { against }) => run_check(against, &args, &exec, &log),
+        Some(Subcommand::CleanupTemp { min_age }) => {
+            run_cleanup_temp(*min_age, &args, &log).map_err(RunError::from)
+        }
+        Some(Subcommand::ConfigPath) => load_settings()
+            .map(|settings| print!("{}", settings.report()))
+            .map_err(RunError::from),
+        None if args.list_files => run_list_files(&args, &exec, &log),
+        None if args.digest => run_digest(&args, &exec, &log),
+        None => {
+            let started = Instant::now();
+            let mut metrics = metrics::Metrics::default();
+            let result = match &args.from_file {
+                Some(path) => run_batch(path, &args, &exec, &log, &mut metrics),
+                None => run_scrape(&args, &exec, &log, &mut metrics).map(|_| ()),
+            };
+            metrics.total_time = started.elapsed();
+            metrics.success = result.is_ok();
+            match (write_metrics(&args, &metrics), result) {
+                (Err(e), Ok(())) => Err(e.into()),
+                (Err(e), Err(failure)) => {
+                    log.warn(&e);
+                    Err(failure)
+                }
+                (Ok(()), result) => result,
+            }
+        }
+    };
+    for invocation in exec.invocations() {
+        log.detail(&format!("Subprocess audit: {}", invocation.command_line()));
+    }
+    match &result {
+        Ok(()) => log.detail("Run completed successfully"),
+        Err(e) => log.detail(&format!("Run failed: {}", e.message)),
+    }
+    result
+}
+
+/// Exit code for failures without a more specific code.
+const EXIT_FAILURE: u8 = 1;
+
+/// Exit code when `--deadline` cut the scrape short and the output is partial.
+const EXIT_PARTIAL: u8 = 3;
+
+/// Exit code when no Solidity files matched the selection.
+const EXIT_NO_FILES: u8 = 4;
+
+/// Exit code when the source has no files at all.
+const EXIT_EMPTY_SOURCE: u8 = 5;
+
+/// Exit code when the source has files, but no Solidity.
+const EXIT_NO_SOLIDITY: u8 = 6;
+
+/// Exit code when `check` finds the committed output out of date.
+const EXIT_OUT_OF_DATE: u8 = 7;
+
+/// Exit code when the scrape succeeded but a `--gate` condition failed.
+const EXIT_GATE: u8 = 8;
+
+/// A failed run: the message to print and the process exit code.
+///
+/// Plain `String` errors convert with [`EXIT_FAILURE`], so `?` keeps working
+/// for everything that has no dedicated exit code.
+#[derive(Debug)]
+struct RunError {
+    /// The message printed after `❌ Error:`.
+    message: String,
+    /// The process exit code.
+    code: u8,
+}
+
+impl From<String> for RunError {
+    fn from(message: String) -> Self {
+        Self {
+            message,
+            code: EXIT_FAILURE,
+        }
+    }
+}
+
+/// Builds the [`Logger`] for this invocation from the parsed [`Args`].
+///
+/// Always includes a [`ConsoleSink`]; adds a [`FileSink`] when `--log-file`
+/// was given, failing fast if the log file cannot be opened.
+fn build_logger(args: &Args) -> Result<Logger, String> {
+    // `--list-files` and `--digest` own stdout, so progress is never
+    // printed there
+    let quiet = args.quiet || args.list_files || args.digest;
+    // An `--output-mode` owns stdout, so everything else goes to stderr
+    let stderr_only = args.output_mode.is_some();
+    let mut log = Logger::new()
+        .with_sink(
+            ConsoleSink::new(quiet)
+                .verbose(args.verbose)
+                .stderr_only(stderr_only),
+        )
+        .with_progress(progress::console_progress(quiet, args.verbose, stderr_only));
+    if let Some(path) = &args.log_file {
+        log = log.with_sink(FileSink::open(Path::new(path))?);
+    }
+    Ok(log)
+}
+
+/// Splits a consolidated file back into a source tree and reports the result.
+fn run_split(
+    input: &str,
+    out_dir: &str,
+    mtime_from: Option<&str>,
+    args: &Args,
+    log: &Logger,
+) -> Result<(), String> {
+    let outcome = split::split_file(Path::new(input), Path::new(out_dir), args.strict)?;
+    for warning in &outcome.warnings {
+        log.warn(warning);
+    }
+    if let Some(source_dir) = mtime_from {
+        for warning in split::copy_mtimes(&outcome, Path::new(source_dir)) {
+            log.warn(&warning);
+        }
+    }
+    let written = outcome.written;
+
+    if args.quiet {
+        println!("{}", out_dir);
+    }
+    log.info(&format!(
+        "{} Split {} into {} files under {}",
+        args.charset.symbols().success,
+        input,
+        written.len(),
+        out_dir
+    ));
+    for path in &written {
+        log.detail(&format!("Wrote {}", path.display()));
+    }
+    Ok(())
+}
+
+/// Prints the section of `input` for the file at `path` to stdout.
+///
+/// The section is read at the byte range the `--parts-file` sidecar gives
+/// (see [`parts`]); without a sidecar, sections are found by their headers.
+/// Either way the input must have been produced without `--no-headers`.
+fn run_extract(input: &str, path: &str) -> Result<(), String> {
+    let at = |e: String| format!("{}: {}", input, e);
+    let section = match parts::find(Path::new(input), path)? {
+        Some(span) => parts::read_section(Path::new(input), &span)?,
+        None => {
+            let text = fs::read_to_string(input)
+                .map_err(|e| format!("Failed to read {}: {}", input, e))?;
+            split::extract_section(&text, path).map_err(at)?
+        }
+    };
+    println!("{}", section);
+    Ok(())
+}
+
+/// Checks a consolidated file against its checksum sidecar (see [`checksum`]).
+///
+/// # Errors
+///
+/// Fails when the sidecar is missing or malformed, or the digests differ;
+/// see [`checksum::verify`].
+fn run_verify(input: &str, args: &Args, log: &Logger) -> Result<(), String> {
+    let digest = checksum::verify(Path::new(input))?;
+    log.info(&format!(
+        "{} {} matches its checksum ({})",
+        args.charset.symbols().success,
+        input,
+        digest
+    ));
+    Ok(())
+}
+
+/// The [`Args`] of one `--from-file` line.
+///
+/// The command-line options, the line's words, and the command-line
+/// destination are parsed together, in that order, so an option the line
+/// gives a value for overrides the command line's, and flags add up. The
+/// `--deadline` of the command line is shared rather than restarted.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"{flag} applies to the whole run; give it on the command line"` | The line uses one of [`batch::GLOBAL_FLAGS`] |
+/// | `"A line names one source and its options, not a subcommand"` | The line starts with `split`, `check`, ... |
+///
+/// Otherwise parsing fails as for the command line, e.g. with
+/// `"Unknown option: ..."` or `"Too many positional arguments"` for a line
+/// that gives a destination.
+fn batch_line_args(args: &Args, entry: &batch::Entry) -> Result<Args, String> {
+    if let Some(flag) = batch::global_flag(&entry.words) {
+        return Err(format!(
+            "{} applies to the whole run; give it on the command line",
+            flag
+        ));
+    }
+    let mut argv = vec!["solscrape".to_string()];
+    argv.extend(args.batch_options.iter().cloned());
+    argv.extend(entry.words.iter().cloned());
+    argv.push(args.destination.clone());
+    let mut line = parse_args_from(argv)?;
+    if line.subcommand.is_some() {
+        return Err("A line names one source and its options, not a subcommand".to_string());
+    }
+    line.deadline = args.deadline;
+    line.charset = args.charset;
+    line.batch_line = true;
+    Ok(line)
+}
+
+/// Scrapes every source listed in the file at `path` (`--from-file`), one
+/// after another, adding up their metrics.
+///
+/// Every line is parsed before anything is scraped, so a mistake on the last
+/// line fails the run straight away. A source that fails is reported and the
+/// rest are still scraped. Each source scraped is recorded in the
+/// destination's [`batch::RESUME_FILE`], which `--resume` skips the
+/// recorded sources of (after removing the temporary clones of dead runs)
+/// and which is removed once every source succeeded. A
+/// [`batch::STOP_FILE`] in the destination stops the batch before its next
+/// source. With `--run-dir`, every source is written into the one run
+/// directory of the invocation, which `latest` points at once the batch ends.
+/// Under `--quiet`, each source prints `{source}\t{status}\t{detail}` in
+/// place of its output paths (see [`print_batch_status`]).
+///
+/// # Errors
+///
+/// | Error | Code | Condition |
+/// |-------|------|-----------|
+/// | `"Failed to read {path}: {e}"` | [`EXIT_FAILURE`] | The list cannot be read |
+/// | `"{path}:{line}: ..."` | [`EXIT_FAILURE`] | A line is malformed (see [`batch::tokenize`], [`batch_line_args`]) |
+/// | `"{path} lists no sources"` | [`EXIT_NO_FILES`] | Every line is blank or a comment |
+/// | `"Stopped before {path}:{line}, ..."` | [`EXIT_PARTIAL`] | The stop file was found |
+/// | `"{n} of {total} sources in {path} failed"` | That of the first failure | Any source failed |
+fn run_batch(
+    path: &str,
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+    metrics: &mut metrics::Metrics,
+) -> Result<(), RunError> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let entries =
+        batch::parse_list(&text).map_err(|(line, e)| format!("{}:{}: {}", path, line, e))?;
+    if entries.is_empty() {
+        return Err(RunError {
+            message: format!("{} lists no sources", path),
+            code: EXIT_NO_FILES,
+        });
+    }
+
+    let mut sources = Vec::new();
+    for entry in &entries {
+        let at = |e: String| format!("{}:{}: {}", path, entry.line, e);
+        let mut line = batch_line_args(args, entry).map_err(at)?;
+        let notes: Vec<String> = autodetect_local(&mut line)
+            .into_iter()
+            .chain(resolve_web_url(&mut line))
+            .collect();
+        let warnings = check_flag_combinations(&line).map_err(at)?;
+        sources.push((entry, line, notes, warnings));
+    }
+
+    let dir = match explicit_output_file(&args.destination) {
+        Some((dir, _)) => dir,
+        None => PathBuf::from(&args.destination),
+    };
+    if args.resume {
+        let report = tempfile::remove_dead(&temp_base_dir(args));
+        if !report.removed.is_empty() {
+            log.info(&format!(
+                "Removed {} temporary clone{} left by an interrupted run",
+                report.removed.len(),
+                if report.removed.len() == 1 { "" } else { "s" }
+            ));
+        }
+    }
+    let (mut resume, note) = batch::Resume::open(&dir, &args.batch_options, args.resume)?;
+    if let Some(note) = note {
+        log.warn(&note);
+    }
+    let stop_file = dir.join(batch::STOP_FILE);
+    let summary = |scraped: usize, done: usize, failed: usize| {
+        format!(
+            "Batch: {} of {} sources scraped, {} already done (--resume), {} failed",
+            scraped,
+            sources.len(),
+            done,
+            failed
+        )
+    };
+
+    let mut run_dir = None;
+    let finish_run_dir = |run_dir: &Option<PathBuf>| {
+        if let Some(run) = run_dir {
+            if let Err(e) = rundir::update_latest(&dir, run) {
+                log.warn(&e);
+            }
+            log.info(&format!("Run directory: {}", run.display()));
+        }
+    };
+
+    let (mut scraped, mut done) = (0, 0);
+    let mut failures = Vec::new();
+    for (entry, line, notes, warnings) in &sources {
+        let number = entry.line;
+        if resume.is_done(entry) {
+            log.info(&format!(
+                "{}:{}: {} already scraped; skipping",
+                path, number, line.source
+            ));
+            print_batch_status(args, &line.source, "skipped", "already scraped (--resume)");
+            done += 1;
+            continue;
+        }
+        if stop_file.exists() {
+            let _ = fs::remove_file(&stop_file);
+            log.info(&summary(scraped, done, failures.len()));
+            finish_run_dir(&run_dir);
+            return Err(RunError {
+                message: format!(
+                    "Stopped before {}:{}, as {} asked; run again with --resume to continue",
+                    path,
+                    number,
+                    stop_file.display()
+                ),
+                code: EXIT_PARTIAL,
+            });
+        }
+        log.info(&format!("{}:{}: {}", path, number, line.source));
+        for note in notes {
+            log.info(note);
+        }
+        log.detail(&format!(
+            "Resolved arguments for {}:{}: {:?}",
+            path, number, line
+        ));
+        for warning in warnings {
+            log.warn(&format!("{}:{}: {}", path, number, warning));
+        }
+        let mut line = line.clone();
+        if line.run_dir {
+            let run = match &run_dir {
+                Some(run) => run.clone(),
+                None => {
+                    let run = rundir::create_run_dir(&dir, clock::DateTime::now())?;
+                    log.detail(&format!("Created run directory {}", run.display()));
+                    run_dir.insert(run).clone()
+                }
+            };
+            line.run_dir = false;
+            line.destination = match explicit_output_file(&args.destination) {
+                Some((_, file_name)) => run.join(file_name),
+                None => run,
+            }
+            .to_string_lossy()
+            .into_owned();
+        }
+        let mut measured = metrics::Metrics::default();
+        let result = run_scrape(&line, exec, log, &mut measured);
+        metrics.add(&measured);
+        match result {
+            Ok(written) => {
+                resume.record(entry, &line.source)?;
+                for output in written {
+                    print_batch_status(args, &line.source, "ok", &output.to_string_lossy());
+                }
+                scraped += 1;
+            }
+            Err(e) => {
+                log.warn(&format!(
+                    "{}:{}: {} failed: {}",
+                    path, number, line.source, e.message
+                ));
+                print_batch_status(args, &line.source, "failed", &e.message);
+                failures.push(e);
+            }
+        }
+    }
+    log.info(&summary(scraped, done, failures.len()));
+    finish_run_dir(&run_dir);
+    match failures.first() {
+        None => {
+            resume.finish();
+            Ok(())
+        }
+        Some(first) => Err(RunError {
+            message: format!(
+                "{} of {} sources in {} failed",
+                failures.len(),
+                sources.len(),
+                path
+            ),
+            code: first.code,
+        }),
+    }
+}
+
+/// Prints the `--quiet` line of one `--from-file` source: the source, a tab,
+/// `ok`, `failed`, or `skipped`, a tab, and the output path (one line per
+/// output), the error, or why it was skipped. Tabs and line breaks in
+/// `detail` become spaces, to keep one source to a line.
+fn print_batch_status(args: &Args, source: &str, status: &str, detail: &str) {
+    if args.quiet {
+        println!(
+            "{}\t{}\t{}",
+            source,
+            status,
+            detail.replace(['\t', '\n'], " ")
+        );
+    }
+}
+
+/// Scrapes the source in memory and compares the result with the committed
+/// consolidated file at `against` (`check`).
+///
+/// Nothing is written. Line endings of the committed file are normalized
+/// first, so a checkout converting them to CRLF still matches, and the scrape
+/// uses the header separators found in the committed file (see
+/// [`Charset::of_output`]), whatever `--ascii` and the locale say; the output
+/// carries no timestamps, so nothing else needs ignoring.
+///
+/// # Errors
+///
+/// | Error | Code | Condition |
+/// |-------|------|-----------|
+/// | `"Failed to read {against}: {e}"` | [`EXIT_FAILURE`] | The committed file cannot be read |
+/// | `"{against} is out of date: ..."` | [`EXIT_OUT_OF_DATE`] | The scrape differs; the message lists the sections that do (see [`vcs::compare`]) |
+///
+/// Cloning and selection fail as for a scrape.
+fn run_check(against: &str, args: &Args, exec: &Exec, log: &Logger) -> Result<(), RunError> {
+    let committed = fs::read_to_string(against)
+        .map_err(|e| format!("Failed to read {}: {}", against, e))?
+        .replace("\r\n", "\n");
+    // Render with the separators the committed file was written with, so
+    // a file scraped with or without --ascii is compared by its content
+    let mut args = args.clone();
+    if let Some(charset) = Charset::of_output(&committed) {
+        args.output_charset = charset;
+    }
+    let args = &args;
+    let scraped = if clones_source(args) {
+        let (temp_dir, _) = clone_to_temp(&args.source, args, exec, log)?;
+        scrape_directory_to_memory(&checkout_root(temp_dir.path(), args)?, args, log)?
+    } else {
+        scrape_directory_to_memory(check_local_source(&args.source)?, args, log)?
+    };
+    let (code, _) = scraped.render();
+    if code == committed {
+        log.info(&format!(
+            "{} {} is up to date ({} files)",
+            args.charset.symbols().success,
+            against,
+            scraped.parts.len()
+        ));
+        return Ok(());
+    }
+    let changes = vcs::compare(&committed, &code);
+    let message = if changes.is_empty() {
+        format!(
+            "{} is out of date: every file's code matches, but the files are \
+             in a different order or framed differently",
+            against
+        )
+    } else {
+        format!(
+            "{} is out of date: {}\n{}",
+            against,
+            vcs::summarize(&changes),
+            vcs::render_sections(&changes).join("\n")
+        )
+    };
+    Err(RunError {
+        message,
+        code: EXIT_OUT_OF_DATE,
+    })
+}
+
+/// Removes orphaned temporary clones and reports what was freed.
+///
+/// The directory scanned is the one clones are made in: `--temp-dir`, then
+/// `SOLSCRAPE_TMPDIR`, then the system default.
+///
+/// # Errors
+///
+/// Fails if any orphaned directory could not be removed.
+fn run_cleanup_temp(min_age: Duration, args: &Args, log: &Logger) -> Result<(), String> {
+    let base = temp_base_dir(args);
+    let report = tempfile::remove_orphans(&base, min_age);
+
+    for path in &report.removed {
+        log.detail(&format!("Removed {}", path.display()));
+    }
+    for path in &report.in_use {
+        log.info(&format!(
+            "Skipped {} (in use by a running solscrape)",
+            path.display()
+        ));
+    }
+    log.info(&format!(
+        "{} Removed {} temporary director{} from {}, freeing {}",
+        args.charset.symbols().success,
+        report.removed.len(),
+        if report.removed.len() == 1 {
+            "y"
+        } else {
+            "ies"
+        },
+        base.display(),
+        space::format_size(report.bytes_freed)
+    ));
+
+    if report.failed.is_empty() {
+        return Ok(());
+    }
+    for (path, e) in &report.failed {
+        log.warn(&format!("Could not remove {}: {}", path.display(), e));
+    }
+    Err(format!(
+        "Could not remove {} temporary director{}",
+        report.failed.len(),
+        if report.failed.len() == 1 { "y" } else { "ies" }
+    ))
+}
+
+/// Prints the files a scrape would include, for `--list-files`.
+///
+/// Paths are relative to the repository root, one per line (NUL-terminated
+/// with `-0`), and nothing else is written to stdout. The destination is
+/// never created.
+///
+/// # Errors
+///
+/// Fails with [`EXIT_NO_FILES`] when no files match.
+fn run_list_files(args: &Args, exec: &Exec, log: &Logger) -> Result<(), RunError> {
+    let files = if clones_source(args) {
+        let (temp_dir, _) = clone_to_temp(&args.source, args, exec, log)?;
+        list_directory(&checkout_root(temp_dir.path(), args)?, args, log)?
+    } else {
+        list_directory(check_local_source(&args.source)?, args, log)?
+    };
+
+    if files.is_empty() {
+        return Err(RunError {
+            message: "No Solidity files matched".to_string(),
+            code: EXIT_NO_FILES,
+        });
+    }
+
+    // NUL-terminated entries are exact; newline-terminated ones are escaped
+    let listing: String = files
+        .iter()
+        .map(|f| {
+            if args.null_terminated {
+                format!("{}\0", f)
+            } else {
+                format!("{}\n", naming::display_path(f))
+            }
+        })
+        .collect();
+    let mut stdout = io::stdout().lock();
+    io::Write::write_all(&mut stdout, listing.as_bytes())
+        .and_then(|()| io::Write::flush(&mut stdout))
+        .map_err(|e| format!("Failed to write file list: {}", e))?;
+    Ok(())
+}
+
+/// Prints the digest of the files a scrape would include, for `--digest`:
+/// a table, or tab-separated lines under `--quiet` (see [`digest`]).
+///
+/// Nothing else is written to stdout, and the destination is never created.
+///
+/// # Errors
+///
+/// Fails with [`EXIT_NO_FILES`] when no files match.
+fn run_digest(args: &Args, exec: &Exec, log: &Logger) -> Result<(), RunError> {
+    let rows = if clones_source(args) {
+        let (temp_dir, _) = clone_to_temp(&args.source, args, exec, log)?;
+        digest_directory(&checkout_root(temp_dir.path(), args)?, args, log)?
+    } else {
+        digest_directory(check_local_source(&args.source)?, args, log)?
+    };
+
+    if rows.is_empty() {
+        return Err(RunError {
+            message: "No Solidity files matched".to_string(),
+            code: EXIT_NO_FILES,
+        });
+    }
+
+    let mut stdout = io::stdout().lock();
+    io::Write::write_all(&mut stdout, digest::render(&rows, args.quiet).as_bytes())
+        .and_then(|()| io::Write::flush(&mut stdout))
+        .map_err(|e| format!("Failed to write digest: {}", e))?;
+    Ok(())
+}
+
+/// Prints what the stdout contract promises for a finished run.
+///
+/// Without `--output-mode`, only `--quiet` prints anything: the output path.
+/// A JSON result needs the scrape's `summary`; comparisons have none.
+///
+/// # Errors
+///
+/// Returns `"Failed to write to stdout: {e}"` if stdout is closed or the
+/// output file cannot be read back for `content`.
+fn print_result(
+    args: &Args,
+    output_path: &Path,
+    summary: Option<&ScraperResult>,
+) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    let written = match (args.output_mode, summary) {
+        (None, _) if !args.quiet || args.batch_line => return Ok(()),
+        (None | Some(OutputMode::Path), _) => {
+            let paths: String = match summary {
+                Some(result) => result
+                    .written_files()
+                    .iter()
+                    .map(|(path, _)| format!("{}\n", path.display()))
+                    .collect(),
+                None => format!("{}\n", output_path.display()),
+            };
+            io::Write::write_all(&mut stdout, paths.as_bytes())
+        }
+        (Some(OutputMode::Content), _) => fs::File::open(output_path)
+            .and_then(|mut file| io::copy(&mut file, &mut stdout).map(|_| ())),
+        (Some(OutputMode::Json), Some(result)) => io::Write::write_all(
+            &mut stdout,
+            format!("{}\n", render_json_result(&args.source, result)).as_bytes(),
+        ),
+        (Some(OutputMode::Json), None) => {
+            return Err("--output-mode json is only supported for scrapes".to_string());
+        }
+    };
+    written
+        .and_then(|()| io::Write::flush(&mut stdout))
+        .map_err(|e| format!("Failed to write to stdout: {}", e))
+}
+
+/// The `{"path","offset","len"}` objects of `parts` for
+/// [`render_json_result`]: the byte range each file's part takes in its
+/// output, without the newline joining it to the next.
+fn parts_json(parts: &[PartSpan]) -> Vec<json::Object> {
+    parts
+        .iter()
+        .map(|part| {
+            json::Object::new()
+                .string("path", &part.path)
+                .number("offset", part.offset)
+                .number("len", part.len)
+        })
+        .collect()
+}
+
+/// Renders the one-line JSON object printed by `--output-mode json`.
+///
+/// `parts` holds a `{"path","offset","len"}` object per included file, giving
+/// the bytes of its part in the output (see [`parts_json`]). `todos` holds a
+/// `{"path","marker","line","text"}` object per marker `--flag-todos` found,
+/// `line` being the approximate original line or `null`. `skipped` holds a
+/// `{"path","reason"}` object per file left out while processing, `reason`
+/// being a [`SkipReason::name`], with `kept` added for a duplicate and
+/// `implemented_by` for a pruned interface. With `--split-by-dir`, an
+/// `outputs` array of `{"dir","path","files","lines","parts"}` objects
+/// follows, `output` being the first, and the top-level `parts` is empty.
+/// With `--stats`, a `directories` array of [`DirRollup`] objects follows
+/// (see [`dir_rollup_json`]). With `--explain`, a `decisions` array follows,
+/// holding a `{"path","status","rule"}` object per `.sol` file (see
+/// [`explain_decisions`]).
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let json = render_json_result("./project", &result);
+/// // {"output":"/out/project_scraped.sol","source":"./project","files":2,...}
+/// ```
+fn render_json_result(source: &str, result: &ScraperResult) -> String {
+    let output = result.output_path.to_string_lossy();
+    let run_dir = result.run_dir.as_ref().map(|d| d.to_string_lossy());
+    let mut json = json::Object::new()
+        .string("output", &output)
+        .string("source", source)
+        .optional_string(
+            "branch",
+            result.revision.as_ref().map(|r| r.branch.as_str()),
+        )
+        .optional_string(
+            "commit",
+            result.revision.as_ref().map(|r| r.commit.as_str()),
+        )
+        .optional_string(
+            "pinned_commit",
+            result.revision.as_ref().and_then(|r| r.pinned.as_deref()),
+        )
+        .number("files", result.file_count)
+        .number("lines", result.line_count)
+        .strings("paths", &result.files_processed)
+        .objects("parts", &parts_json(&result.parts))
+        .optional_string("run_dir", run_dir.as_deref())
+        .boolean("partial", result.truncated.is_some())
+        .optional_string("since", result.since.as_ref().map(|s| s.base.as_str()))
+        .strings(
+            "removed",
+            result.since.as_ref().map_or(&[][..], |s| &s.removed),
+        )
+        .strings(
+            "context",
+            &result.context.iter().map(|f| f.path).collect::<Vec<_>>(),
+        )
+        .objects(
+            "todos",
+            &result
+                .todos
+                .iter()
+                .map(|(path, marker)| {
+                    json::Object::new()
+                        .string("path", path)
+                        .string("marker", marker.marker)
+                        .optional_number("line", marker.original_line)
+                        .string("text", &marker.excerpt)
+                })
+                .collect::<Vec<_>>(),
+        )
+        .objects(
+            "skipped",
+            &result
+                .skipped
+                .iter()
+                .map(|(path, reason, redundant)| {
+                    let skipped = json::Object::new()
+                        .string("path", path)
+                        .string("reason", reason.name());
+                    match (reason, redundant) {
+                        (SkipReason::Duplicate, Some(kept)) => skipped.string("kept", kept),
+                        (_, Some(implementers)) => skipped.string("implemented_by", implementers),
+                        (_, None) => skipped,
+                    }
+                })
+                .collect::<Vec<_>>(),
+        );
+    if !result.outputs.is_empty() {
+        let outputs: Vec<json::Object> = result
+            .outputs
+            .iter()
+            .map(|output| {
+                json::Object::new()
+                    .string("dir", &output.dir)
+                    .string("path", &output.path.to_string_lossy())
+                    .number("files", output.parts.len())
+                    .number("lines", output.line_count)
+                    .objects("parts", &parts_json(&output.parts))
+            })
+            .collect();
+        json = json.objects("outputs", &outputs);
+    }
+    if !result.directories.is_empty() {
+        json = json.objects("directories", &dir_rollup_json(&result.directories));
+    }
+    if result.decisions.is_empty() {
+        return json.render();
+    }
+    let decisions: Vec<json::Object> = result
+        .decisions
+        .iter()
+        .map(|decision| {
+            json::Object::new()
+                .string("path", &decision.path)
+                .string(
+                    "status",
+                    if decision.included {
+                        "included"
+                    } else {
+                        "excluded"
+                    },
+                )
+                .string("rule", &decision.rule)
+        })
+        .collect();
+    json.objects("decisions", &decisions).render()
+}
+
+/// Writes the run's metrics where `--metrics-file` or `--metrics-stdout`
+/// asked, if anywhere; see [`metrics`] for the format.
+///
+/// # Errors
+///
+/// Returns `"Failed to write metrics to {path}: ..."`.
+fn write_metrics(args: &Args, metrics: &metrics::Metrics) -> Result<(), String> {
+    if args.metrics_file.is_none() && !args.metrics_stdout {
+        return Ok(());
+    }
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or(0);
+    let block = metrics.render(timestamp);
+    match &args.metrics_file {
+        Some(path) => metrics::append(Path::new(path), &block),
+        None => {
+            let mut stdout = io::stdout().lock();
+            io::Write::write_all(&mut stdout, block.as_bytes())
+                .and_then(|()| io::Write::flush(&mut stdout))
+                .map_err(|e| format!("Failed to write metrics to stdout: {}", e))
+        }
+    }
+}
+
+/// Performs the scrape described by `args` and prints the summary, filling
+/// in the counters of `metrics` once the output is written.
+///
+/// # Returns
+///
+/// The paths of the files written.
+fn run_scrape(
+    args: &Args,
+    exec: &Exec,
+    log: &Logger,
+    metrics: &mut metrics::Metrics,
+) -> Result<Vec<PathBuf>, RunError> {
+    check_destination(&args.destination)?;
+    if !args.quiet {
+        print_banner(args.output_mode.is_some(), args.charset.symbols());
+    }
+    if args.sources.is_empty() {
+        log.info(&format!("Source:      {}", args.source));
+    } else {
+        log.info(&format!("Sources:     {}", args.sources.join(", ")));
+    }
+    log.info(&format!("Destination: {}", args.destination));
+    log.info("");
+
+    check_git_version(args, exec, log)?;
+    // Several sources are checked one by one as they are scraped
+    if args.sources.is_empty() && wants_precheck(args) && precheck_supported(args, exec, log) {
+        confirm_remote(&args.source, args, exec, log)?;
+    }
+    if args.sources.is_empty() && wants_size_probe(args) {
+        confirm_clone_size(&args.source, args, exec, log)?;
+    }
+
+    if let Some(refs) = &args.compare {
+        return Ok(vec![run_compare(refs, args, exec, log)?]);
+    }
+
+    let output_name = args.output_name.as_deref();
+
+    let result = if !args.sources.is_empty() {
+        scrape_sources(args, exec, log)?
+    } else if let Some(clone) = &args.reuse_clone {
+        scrape_reused_clone(
+            Path::new(clone),
+            &args.source,
+            &args.destination,
+            output_name,
+            args,
+            exec,
+            log,
+        )?
+    } else if clones_source(args) {
+        if args.is_local {
+            log.detail("Source is a bare repository or file:// URL; cloning instead of scanning");
+        }
+        scrape_from_url(
+            &args.source,
+            &args.destination,
+            output_name,
+            args,
+            exec,
+            log,
+        )?
+    } else {
+        scrape_from_local(
+            &args.source,
+            &args.destination,
+            output_name,
+            args,
+            exec,
+            log,
+        )?
+    };
+
+    metrics.clone_time = result.clone_time;
+    metrics.files_processed = result.file_count;
+    metrics.files_skipped = result.skipped.len();
+    metrics.bytes_written = result
+        .written_files()
+        .iter()
+        .map(|(path, _)| fs::metadata(path).map_or(0, |m| m.len()))
+        .sum();
+
+    let mut sidecars = Vec::new();
+    if args.checksum_file {
+        for (path, _) in result.written_files() {
+            let (sidecar, digest) = checksum::write_sidecar(path)?;
+            log.detail(&format!("SHA-256 of {}: {}", path.display(), digest));
+            sidecars.push(sidecar);
+        }
+    }
+    let mut parts_files = Vec::new();
+    if args.parts_file {
+        for (path, parts) in result.written_files() {
+            parts_files.push(parts::write_sidecar(path, parts)?);
+        }
+    }
+    let errors_report = match args.errors_report {
+        Some(format) => {
+            let path = format.sidecar_path(&result.output_path);
+            fs::write(&path, result.errors.render(format))
+                .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+            Some(path)
+        }
+        None => None,
+    };
+
+    print_result(args, &result.output_path, Some(&result))?;
+
+    let symbols = args.charset.symbols();
+    log.info("");
+    log.info(symbols.summary_rule);
+    if result.truncated.is_some() {
+        log.info(&format!("{} Partial output", symbols.partial));
+    } else {
+        log.info(&format!("{} Success!", symbols.success));
+    }
+    if args.sources.is_empty() {
+        log.info(&format!(
+            "   Source:          {}",
+            describe_source(&args.source, result.revision.as_ref())
+        ));
+    } else {
+        let counts: Vec<String> = result
+            .sources
+            .iter()
+            .map(|(name, files)| {
+                format!(
+                    "{}: {} file{}",
+                    name,
+                    files,
+                    if *files == 1 { "" } else { "s" }
+                )
+            })
+            .collect();
+        log.info(&format!("   Sources:         {}", counts.join(", ")));
+    }
+    if !result.failed_sources.is_empty() {
+        let failed: Vec<&str> = result
+            .failed_sources
+            .iter()
+            .map(|(source, _)| source.as_str())
+            .collect();
+        log.info(&format!("   Failed:          {}", failed.join(", ")));
+    }
+    if let (Some(rev), Some(pinned)) = (
+        &args.rev,
+        result.revision.as_ref().and_then(|r| r.pinned.as_ref()),
+    ) {
+        log.info(&format!("   Revision:        {} = {}", rev, pinned));
+    }
+    if !result.project.is_unknown() {
+        log.info(&format!("   Project:         {}", result.project));
+    }
+    log.info(&format!("   Files processed: {}", result.file_count));
+    if !result.skipped.is_empty() {
+        log.info(&format!(
+            "   Skipped:         {}",
+            skip::summarize(result.skipped.iter().map(|(_, reason, _)| *reason))
+        ));
+    }
+    if !result.duplicates.is_empty() {
+        let redundant: usize = result.duplicates.iter().map(|group| group.len() - 1).sum();
+        log.info(&format!(
+            "   Duplicates:      {} group{}, {} redundant file{}{}",
+            result.duplicates.len(),
+            if result.duplicates.len() == 1 {
+                ""
+            } else {
+                "s"
+            },
+            redundant,
+            if redundant == 1 { "" } else { "s" },
+            if args.dedupe_content {
+                " (skipped)"
+            } else {
+                "; see --dedupe-content"
+            }
+        ));
+        for group in &result.duplicates {
+            log.info(&format!("                    {}", group.join(" = ")));
+        }
+    }
+    if !result.context.is_empty() {
+        log.info(&format!(
+            "   Context files:   {} ({} lines, not counted above)",
+            result.context.len(),
+            result
+                .context
+                .iter()
+                .map(context::ContextFile::line_count)
+                .sum::<usize>()
+        ));
+    }
+    if let Some(since) = &result.since {
+        log.info(&format!(
+            "   Since:           {} ({} changed, {} removed)",
+            since.base,
+            since.changed.len(),
+            since.removed.len()
+        ));
+    }
+    if let Some(related) = &result.related {
+        let counts = graph::count(&related.relations);
+        log.info(&format!(
+            "   Related:         {} ({} target, {} depends-on, {} dependent)",
+            naming::display_path(&related.target),
+            counts[0],
+            counts[1],
+            counts[2]
+        ));
+    }
+    if args.flag_todos {
+        log.info(&format!(
+            "   TODO markers:    {}",
+            todo::summarize(result.todos.iter().map(|(_, marker)| marker))
+        ));
+    }
+    if args.errors_report.is_some() {
+        log.info(&format!("   Errors:          {}", result.errors.summary()));
+    }
+    if let Some(note) = render_single_file_note(&result, args) {
+        log.info(&format!("   Note:            {}", note));
+    }
+    log.info(&format!("   Total lines:     {}", result.line_count));
+    if args.inject_pragma.is_some() {
+        log.info(&format!("   Pragmas added:   {}", result.pragmas_injected));
+    }
+    if args.strip_revert_strings {
+        let saved: usize = result
+            .file_stats
+            .iter()
+            .map(|(_, stats)| stats.revert_chars_saved)
+            .sum();
+        log.info(&format!(
+            "   Revert strings:  {} chars saved",
+            group_thousands(saved)
+        ));
+    }
+    if result.outputs.is_empty() {
+        log.info(&format!(
+            "   Output:          {}",
+            result.output_path.display()
+        ));
+    }
+    for (i, output) in result.outputs.iter().enumerate() {
+        log.info(&format!(
+            "   {:<17}{} ({} files, {} lines)",
+            if i == 0 { "Outputs:" } else { "" },
+            output.path.display(),
+            output.parts.len(),
+            output.line_count
+        ));
+    }
+    for (i, sidecar) in sidecars.iter().enumerate() {
+        log.info(&format!(
+            "   {:<17}{}",
+            if i == 0 { "Checksum:" } else { "" },
+            sidecar.display()
+        ));
+    }
+    for (i, sidecar) in parts_files.iter().enumerate() {
+        log.info(&format!(
+            "   {:<17}{}",
+            if i == 0 { "Parts:" } else { "" },
+            sidecar.display()
+        ));
+    }
+    if let Some(path) = &errors_report {
+        log.info(&format!("   Errors report:   {}", path.display()));
+    }
+    if let Some((done, total)) = result.truncated {
+        log.info(&format!(
+            "   Partial:         deadline reached after {} of {} files",
+            done, total
+        ));
+    }
+    let failed_gates = gate::evaluate(
+        &args.gates,
+        &gate::Observed {
+            skipped: result
+                .skipped
+                .iter()
+                .map(|(file, reason, _)| (file.clone(), reason.name()))
+                .collect(),
+            licenses: result.licenses.clone(),
+            missing_imports: result.missing_imports.clone(),
+            line_count: result.line_count,
+        },
+    );
+    if !args.gates.is_empty() {
+        log.info(&format!(
+            "   Gates:           {} of {} passed",
+            args.gates.len() - failed_gates.len(),
+            args.gates.len()
+        ));
+    }
+    log.info(symbols.summary_rule);
+
+    if result.file_count <= 25 {
+        log.info("\nFiles included:");
+        for f in &result.files_processed {
+            log.info(&format!("  {} {}", symbols.bullet, naming::display_path(f)));
+        }
+    } else {
+        log.detail(&format!(
+            "Files included: {}",
+            result.files_processed.join(", ")
+        ));
+    }
+
+    if !result.skipped.is_empty() {
+        log.info("\nSkipped files:");
+        for reason in SkipReason::ALL {
+            for (file, _, redundant) in result.skipped.iter().filter(|(_, r, _)| *r == reason) {
+                let redundant = match (reason, redundant) {
+                    (SkipReason::Duplicate, Some(kept)) => format!(" of {}", kept),
+                    (_, Some(implementers)) => format!(": {}", implementers),
+                    (_, None) => String::new(),
+                };
+                log.info(&format!(
+                    "  {} {} ({}{})",
+                    symbols.bullet,
+                    file,
+                    reason.name(),
+                    redundant
+                ));
+            }
+        }
+    }
+
+    if let Some(since) = result.since.as_ref().filter(|s| !s.removed.is_empty()) {
+        log.info(&format!("\nRemoved since {} (not included):", since.base));
+        for path in &since.removed {
+            log.info(&format!(
+                "  {} {}",
+                symbols.bullet,
+                naming::display_path(path)
+            ));
+        }
+    }
+
+    for (path, parts) in result.written_files() {
+        let file = if result.outputs.is_empty() {
+            String::new()
+        } else {
+            format!(" in {}", path.display())
+        };
+        for part in parts {
+            log.detail(&format!(
+                "Part {}{}: bytes {}..{}",
+                naming::display_path(&part.path),
+                file,
+                part.offset,
+                part.offset + part.len
+            ));
+        }
+    }
+
+    if !result.warnings.is_empty() {
+        log.info("\nStructural warnings:");
+        for (file, warning) in &result.warnings {
+            log.info(&format!("  {} {}: {}", symbols.bullet, file, warning));
+        }
+    }
+
+    if !result.todos.is_empty() {
+        log.info("\nTODO markers:");
+        for (file, marker) in &result.todos {
+            log.info(&format!(
+                "  {} {}: {}",
+                symbols.bullet,
+                file,
+                marker.describe()
+            ));
+        }
+    }
+
+    if !failed_gates.is_empty() {
+        log.info("\nFailed gates:");
+        for failure in &failed_gates {
+            log.info(&format!(
+                "  {} {}: {}",
+                symbols.bullet, failure.gate, failure.reason
+            ));
+        }
+    }
+
+    if args.stats {
+        log.info("");
+        log.info(&render_stats_report(&result.file_stats));
+        if !result.directories.is_empty() {
+            log.info("");
+            log.info(&render_dir_rollup(&result.directories).join("\n"));
+        }
+        if !result.context.is_empty() {
+            log.info("");
+            log.info(&render_context_report(&result.context));
+        }
+    }
+
+    if let Some(run_dir) = &result.run_dir {
+        if let Err(e) = rundir::update_latest(Path::new(&args.destination), run_dir) {
+            log.warn(&e);
+        }
+        log.info("");
+        log.info(&format!("Run directory: {}", run_dir.display()));
+    }
+
+    let lost: Vec<SkipReason> = result
+        .skipped
+        .iter()
+        .map(|(_, reason, _)| *reason)
+        .filter(|reason| reason.loses_content())
+        .collect();
+    match result.truncated {
+        Some((done, total)) => Err(RunError {
+            message: format!(
+                "Deadline reached: the output holds {} of {} files",
+                done, total
+            ),
+            code: EXIT_PARTIAL,
+        }),
+        None if !result.failed_sources.is_empty() => Err(RunError {
+            message: format!(
+                "{} of {} sources failed; see the warnings above",
+                result.failed_sources.len(),
+                args.sources.len()
+            ),
+            code: result.failed_sources[0].1.code,
+        }),
+        None if args.strict && !lost.is_empty() => Err(format!(
+            "--strict: files skipped with their code lost: {}; see the skipped files above",
+            skip::summarize(lost.into_iter())
+        )
+        .into()),
+        None if args.strict && !result.todos.is_empty() => Err(format!(
+            "--strict: the output still contains {} TODO marker{} ({}); see the list above",
+            result.todos.len(),
+            if result.todos.len() == 1 { "" } else { "s" },
+            result.output_path.display()
+        )
+        .into()),
+        None if !failed_gates.is_empty() => Err(RunError {
+            message: format!(
+                "{} of {} gates failed ({}); see the list above",
+                failed_gates.len(),
+                args.gates.len(),
+                failed_gates
+                    .iter()
+                    .map(|failure| failure.gate.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            code: EXIT_GATE,
+        }),
+        None => Ok(result
+            .written_files()
+            .iter()
+            .map(|(path, _)| path.to_path_buf())
+            .collect()),
+    }
+}
+
+/// The program entry point.
+///
+/// Delegates to [`run`] for the main logic and converts the result into an
+/// appropriate [`ExitCode`]. Errors are printed to stderr with a visual indicator.
+///
+/// The [`Charset`] is picked here, before the arguments are parsed, so that
+/// even a parse error is printed with the right one.
+///
+/// # Exit Codes
+///
+/// - `0` — Success
+/// - `1` — Any error without a more specific code
+/// - `3` — `--deadline` was reached; the output is partial
+/// - `4` — No files matched the selection (`--list-files`), or every `.sol`
+///   file was excluded
+/// - `5` — The source is empty
+/// - `6` — The source has files, but no Solidity
+/// - `7` — `check` found the committed output out of date
+/// - `8` — The scrape succeeded, but a `--gate` condition failed
+fn main() -> ExitCode {
+    let charset = Charset::detect(env::args().skip(1).any(|arg| arg == "--ascii"), |name| {
+        env::var(name).ok()
+    });
+    match run(charset) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{} Error: {}", charset.symbols().failure, e.message);
+            ExitCode::from(e.code)
+        }
+    }
+}
+
+// ============================================================================
+// Tests
+// ============================================================================
+
+/// Unit tests for the Solidity scraper.
+///
+/// These tests verify the core parsing and utility functions. End-to-end tests
+/// that run the built binary live in `tests/`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_SEPARATOR: &str = symbols::UNICODE.header_separator;
+
+    /// Creates a fresh, empty fixture directory under the system temp dir.
+    fn fixture_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("solscrape_fixture_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes `(relative path, content)` pairs under `root`.
+    fn write_tree(root: &Path, files: &[(&str, &str)]) {
+        for (relative, content) in files {
+            let path = root.join(relative);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+    }
+
+    /// Parses `argv` and checks it against [`FLAG_COMBINATIONS`], as a run
+    /// does.
+    fn parse_checked(argv: Vec<String>) -> Result<Args, String> {
+        let args = parse_args_from(argv)?;
+        check_flag_combinations(&args)?;
+        Ok(args)
+    }
+
+    /// Golden fixture for the processing pipeline: default flags must keep
+    /// producing exactly this output.
+    const PIPELINE_GOLDEN_FILES: &[(&str, &str)] = &[
+        ("foundry.toml", "[profile.default]\n"),
+        (
+            "src/Token.sol",
+            "\u{feff}// SPDX-License-Identifier: MIT\r\n\
+             /*\r\n * Copyright (c) Example\r\n */\r\n\
+             pragma solidity ^0.8.20;\r\n\r\n\
+             import {IERC20 as IToken} from \"./IERC20.sol\";\r\n\
+             /// @notice A token\r\n\
+             contract Token is IToken {\r\n\
+             \x20   string public url = \"https://example.com/*x*/\"; // trailing\r\n\
+             \x20   /** @dev doc */\r\n\
+             \x20   function f(uint a) external pure returns (uint) {\r\n\
+             \x20       require(a > 0, \"Token: amount must be positive\");\r\n\r\n\
+             \x20       return a /* inline */ * 2;\r\n\
+             \x20   }\r\n\
+             }\r\n",
+        ),
+        (
+            "src/utils/Math.sol",
+            "pragma solidity >=0.7.0 <0.9.0;\n\n\nlibrary Math {\t\n\
+             \tfunction max(uint a, uint b) internal pure returns (uint) {   \n\
+             \t\treturn a > b ? a : b; // pick\n\t}\n\
+             \tstring constant S = 'it\\'s // not a comment';\n}\n",
+        ),
+        ("src/Only.sol", "// nothing but comments\n/* here */\n"),
+        ("src/Gone.sol", "// solscrape: ignore\ncontract Gone {}\n"),
+        ("lib/dep/src/Dep.sol", "contract Dep {}\n"),
+        ("test/Token.t.sol", "contract TokenTest {}\n"),
+    ];
+
+    /// Locks the default-flag output of the processing pipeline byte for byte,
+    /// including the quirks of cleaning (a lone BOM line, the double space
+    /// left by an inline comment), with Unicode and with ASCII headers.
+    #[test]
+    fn test_pipeline_golden_output() {
+        let root = fixture_dir("pipeline_golden");
+        let source = root.join("source");
+        write_tree(&source, PIPELINE_GOLDEN_FILES);
+        // The separator is the only difference between the charsets
+        for (output_charset, rule) in [(Charset::Unicode, '═'), (Charset::Ascii, '=')] {
+            let separator = format!("// {}", rule.to_string().repeat(70));
+            let out = root.join(format!("out_{:?}", output_charset));
+            let result = scrape_directory(
+                &source,
+                out.to_str().unwrap(),
+                "golden",
+                &Args {
+                    output_charset,
+                    ..Args::default()
+                },
+                &Logger::new(),
+            )
+            .unwrap();
+            let header = |path: &str| {
+                format!(
+                    "{}\n{}{}\n{}\n",
+                    separator, HEADER_FILE_PREFIX, path, separator
+                )
+            };
+            let expected = format!(
+                "{}\u{feff}\n\
+             pragma solidity ^0.8.20;\n\
+             import {{IERC20 as IToken}} from \"./IERC20.sol\";\n\
+             contract Token is IToken {{\n\
+             \x20   string public url = \"https://example.com/*x*/\";\n\
+             \x20   function f(uint a) external pure returns (uint) {{\n\
+             \x20       require(a > 0, \"Token: amount must be positive\");\n\
+             \x20       return a  * 2;\n\
+             \x20   }}\n\
+             }}\n\
+             {}\
+             pragma solidity >=0.7.0 <0.9.0;\n\
+             library Math {{\n\
+             \tfunction max(uint a, uint b) internal pure returns (uint) {{\n\
+             \t\treturn a > b ? a : b;\n\
+             \t}}\n\
+             \tstring constant S = 'it\\'s // not a comment';\n\
+             }}",
+                header("src/Token.sol"),
+                header("src/utils/Math.sol")
+            );
+            assert_eq!(fs::read_to_string(&result.output_path).unwrap(), expected);
+            assert_eq!(
+                result.files_processed,
+                ["src/Token.sol", "src/utils/Math.sol"]
+            );
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that the in-memory output rebuilds exactly the file the
+    /// writing path produces, and that building it writes nothing.
+    #[test]
+    fn test_scrape_directory_to_memory() {
+        let root = fixture_dir("to_memory");
+        let source = root.join("source");
+        write_tree(&source, PIPELINE_GOLDEN_FILES);
+        fs::write(source.join("README.md"), "# Golden\n").unwrap();
+        let log = Logger::new();
+
+        for args in [
+            Args::default(),
+            Args {
+                include_lib: true,
+                with_context: true,
+                ..Args::default()
+            },
+        ] {
+            let scraped = scrape_directory_to_memory(&source, &args, &log).unwrap();
+            assert!(!root.join("out").exists());
+
+            // A consumer only needs the parts and the joining rules
+            let mut rebuilt = scraped
+                .parts
+                .iter()
+                .map(|part| part.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+            if !scraped.context.is_empty() {
+                rebuilt = format!("{}\n{}", rebuilt, context::render(&scraped.context));
+            }
+            let (rendered, spans) = scraped.render();
+            assert_eq!(rebuilt, rendered);
+            for (span, part) in spans.iter().zip(&scraped.parts) {
+                assert_eq!(&rendered[span.offset..][..span.len], part.text);
+            }
+
+            let paths: Vec<String> = scraped.parts.iter().map(|p| p.path.clone()).collect();
+            let stats = scraped.file_stats.clone();
+            let out = root.join("out");
+            let written =
+                scrape_directory(&source, out.to_str().unwrap(), "mem", &args, &log).unwrap();
+            assert_eq!(fs::read_to_string(&written.output_path).unwrap(), rebuilt);
+            assert_eq!(written.files_processed, paths);
+            assert_eq!(written.file_stats, stats);
+            assert_eq!(written.parts, spans);
+            fs::remove_dir_all(&out).unwrap();
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies LFS pointer and binary detection, and that unusual Unicode
+    /// in legitimate Solidity does not trigger it.
+    #[test]
+    fn test_non_text_reason() {
+        let pointer = format!(
+            "{}\noid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\nsize 12345\n",
+            LFS_POINTER_HEADER
+        );
+        assert_eq!(
+            non_text_reason(pointer.as_bytes()).unwrap().to_string(),
+            "[lfs] Git LFS pointer to 12345 bytes; run `git lfs pull` in the source to fetch the file"
+        );
+        let crlf = format!("{}\r\noid sha256:00\r\n", LFS_POINTER_HEADER);
+        assert!(
+            non_text_reason(crlf.as_bytes())
+                .unwrap()
+                .to_string()
+                .starts_with("[lfs] Git LFS pointer;")
+        );
+        // Only as the first line, and only the exact spec line
+        let quoted = format!("// {}\ncontract A {{}}", LFS_POINTER_HEADER);
+        assert_eq!(non_text_reason(quoted.as_bytes()), None);
+        assert_eq!(
+            non_text_reason(format!("{}2\n", LFS_POINTER_HEADER).as_bytes()),
+            None
+        );
+
+        // Deterministic noise, like a compressed blob
+        let mut state = 0x2545_f491_u32;
+        let noise: Vec<u8> = (0..4096)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            })
+            .collect();
+        assert_eq!(non_text_reason(&noise).unwrap().reason, SkipReason::Binary);
+        let nuls = format!("contract A {{}}{}", "\0".repeat(64));
+        assert_eq!(
+            non_text_reason(nuls.as_bytes()).unwrap().to_string(),
+            "[binary] 64 of the first 77 bytes are control characters; not a text file"
+        );
+
+        for text in [
+            "",
+            "contract A {\r\n\tstring s = unicode\"ünïcödé 🚀 \u{202e}rtl\u{200b}\";\r\n}\n",
+            "// 中文注释 — ≥ ≤ ∑\ncontract B {}\u{c}\n",
+            &format!("{}\u{1b}[0m\n", "contract C {}\n".repeat(4)),
+        ] {
+            assert_eq!(non_text_reason(text.as_bytes()), None, "{:?}", text);
+        }
+    }
+
+    /// Verifies that LFS pointers and binaries are skipped with their own
+    /// --explain rules while Unicode-heavy files are kept.
+    #[test]
+    fn test_scrape_skips_non_text_files() {
+        let root = fixture_dir("non_text");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                (
+                    "src/Big.sol",
+                    &format!("{}\noid sha256:ab\nsize 9000000\n", LFS_POINTER_HEADER),
+                ),
+                (
+                    "src/Emoji.sol",
+                    "contract Emoji { string s = unicode\"🚀 ünï\"; }\n",
+                ),
+            ],
+        );
+        let mut blob = b"\x1f\x8b\x08\x00".to_vec();
+        blob.extend((0u8..=255).cycle().take(2048));
+        fs::write(source.join("src/Blob.sol"), blob).unwrap();
+
+        let args = Args {
+            explain: true,
+            ..Args::default()
+        };
+        let out = root.join("out");
+        let result =
+            scrape_directory(&source, out.to_str().unwrap(), "t", &args, &Logger::new()).unwrap();
+        assert_eq!(result.files_processed, ["src/Emoji.sol"]);
+        let rules: Vec<(&str, &str)> = result
+            .decisions
+            .iter()
+            .map(|d| (d.path.as_str(), d.rule.as_str()))
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                ("src/Big.sol", "lfs-pointer"),
+                ("src/Blob.sol", "binary"),
+                ("src/Emoji.sol", "default")
+            ]
+        );
+        let output = fs::read_to_string(&result.output_path).unwrap();
+        assert!(output.contains("unicode\"🚀 ünï\""));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies --kinds on a file of each kind, a mixed file, and a file
+    /// with no declarations, and the per-kind rollup of --stats.
+    #[test]
+    fn test_kinds_filter() {
+        let root = fixture_dir("kinds");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("src/Abstract.sol", "abstract contract Base {}\n"),
+                ("src/Consts.sol", "uint256 constant FEE = 5;\n"),
+                ("src/IVault.sol", "interface IVault {}\n"),
+                ("src/Math.sol", "library Math {\n}\n"),
+                (
+                    "src/Mixed.sol",
+                    "interface IPool {}\ncontract Pool is IPool {\n}\n",
+                ),
+                ("src/Vault.sol", "contract Vault {\n    uint x;\n}\n"),
+            ],
+        );
+        let parse = |list: &str| parse_kinds(list);
+        assert_eq!(
+            parse("libraries, contracts,contract"),
+            Ok(vec![structure::Kind::Contract, structure::Kind::Library])
+        );
+        assert_eq!(
+            parse("contract,structs"),
+            Err("Invalid --kinds entry \"structs\" (expected contract, abstract, interface, or library)".to_string())
+        );
+        assert!(parse("").is_err());
+
+        let out = root.join("out");
+        let log = Logger::new();
+        let args = Args {
+            kinds: Some(parse("contracts,libraries").unwrap()),
+            explain: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(&source, out.to_str().unwrap(), "k", &args, &log).unwrap();
+        assert_eq!(
+            result.files_processed,
+            ["src/Math.sol", "src/Mixed.sol", "src/Vault.sol"]
+        );
+        let kind_skips: Vec<&str> = result
+            .decisions
+            .iter()
+            .filter(|d| d.rule == "kind")
+            .map(|d| d.path.as_str())
+            .collect();
+        assert_eq!(
+            kind_skips,
+            ["src/Abstract.sol", "src/Consts.sol", "src/IVault.sol"]
+        );
+
+        let outcome = |name: &str, kinds: &str| {
+            let args = Args {
+                kinds: Some(parse(kinds).unwrap()),
+                ..Args::default()
+            };
+            process_file(&source.join(name), &source, &args, &log).unwrap()
+        };
+        let FileOutcome::Skipped(reason) = outcome("src/IVault.sol", "contract") else {
+            panic!("interface kept");
+        };
+        assert_eq!(
+            reason.to_string(),
+            "[kind] declares only interface IVault (line 1); --kinds selects contract"
+        );
+        let FileOutcome::Skipped(reason) = outcome("src/Consts.sol", "contract") else {
+            panic!("constants kept");
+        };
+        assert_eq!(reason.reason, SkipReason::Kind);
+        assert!(
+            reason
+                .detail
+                .starts_with("declares no contract, interface, or library")
+        );
+        assert!(matches!(
+            outcome("src/Abstract.sol", "abstract"),
+            FileOutcome::Content { .. }
+        ));
+        assert!(matches!(
+            outcome("src/IVault.sol", "interfaces"),
+            FileOutcome::Content { .. }
+        ));
+
+        // Every file, rolled up by kind
+        let all = scrape_directory(
+            &source,
+            out.to_str().unwrap(),
+            "all",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert_eq!(
+            render_kind_rollup(&all.file_stats),
+            [
+                "By kind:",
+                "  Kind       Files  Declarations     Lines",
+                "  contract       1             2         3",
+                "  abstract       1             1         1",
+                "  interface      1             2         1",
+                "  library        1             1         2",
+                "  mixed          1             2         3",
+                "  none           1             0         1",
+            ]
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies which stages flags add, and their order.
+    #[test]
+    fn test_pipeline_stages() {
+        let defaults = Pipeline::from_args(&Args::default());
+        assert_eq!(
+            defaults.names(),
+            [
+                "opt-out-marker",
+                "consolidated-input",
+                "clean",
+                "require-code",
+                "brace-depth",
+                "content-digest",
+                "long-lines",
+                "findings",
+                "file-header"
+            ]
+        );
+
+        let args = Args {
+            no_clean: true,
+            keep_license_headers: true,
+            no_headers: true,
+            vcs_friendly: true,
+            ..Args::default()
+        };
+        assert_eq!(
+            Pipeline::from_args(&args).names(),
+            [
+                "opt-out-marker",
+                "consolidated-input",
+                "verbatim",
+                "normalize-whitespace",
+                "require-code",
+                "brace-depth",
+                "content-digest",
+                "long-lines",
+                "findings"
+            ]
+        );
+
+        let args = Args {
+            filter_pragma: Some(pragma::Constraint::parse("0.8").unwrap()),
+            min_solidity: Some("0.8.4".to_string()),
+            kinds: Some(vec![structure::Kind::Library]),
+            strip_revert_strings: true,
+            errors_report: Some(error_report::Format::Text),
+            max_line_width: Some(100),
+            inject_pragma: Some("^0.8.0".to_string()),
+            keep_license_headers: true,
+            flag_todos: true,
+            gates: vec![gate::Gate::NoMissingImports],
+            ..Args::default()
+        };
+        assert_eq!(
+            Pipeline::from_args(&args).names(),
+            [
+                "opt-out-marker",
+                "consolidated-input",
+                "clean",
+                "require-code",
+                "brace-depth",
+                "content-digest",
+                "pragma-filter",
+                "pragma-filter",
+                "kind-filter",
+                "long-lines",
+                "error-inventory",
+                "gate-facts",
+                "strip-revert-strings",
+                "max-line-width",
+                "findings",
+                "inject-pragma",
+                "license-header",
+                "todo-markers",
+                "file-header"
+            ]
+        );
+    }
+
+    /// Verifies stages one at a time on hand-built file content.
+    #[test]
+    fn test_pipeline_stage_apply() {
+        let log = Logger::new();
+        let file = |text: &str| FileContent::new(text.to_string(), "src/A.sol".to_string());
+
+        let mut ignored = file(&format!("{}\ncontract A {{}}", exclusion::IGNORE_MARKER));
+        assert!(matches!(
+            Stage::OptOutMarker.apply(&mut ignored, &log),
+            ControlFlow::Break(FileOutcome::Skipped(skip)) if skip.reason == SkipReason::OptOutMarker
+        ));
+
+        let mut only_comments = file("// nothing\n");
+        assert!(Stage::Clean.apply(&mut only_comments, &log).is_continue());
+        assert_eq!(only_comments.stats.original_lines, 1);
+        assert!(matches!(
+            Stage::RequireCode.apply(&mut only_comments, &log),
+            ControlFlow::Break(FileOutcome::Empty)
+        ));
+
+        // Injection only counts code, and later stages build on it
+        let mut code = file("// Copyright 2024 Acme\ncontract A {}\n");
+        for stage in [
+            Stage::Clean,
+            Stage::InjectPragma("^0.8.0".to_string()),
+            Stage::LicenseHeader,
+        ] {
+            assert!(stage.apply(&mut code, &log).is_continue());
+        }
+        assert!(code.pragma_injected);
+        assert_eq!(code.stats.cleaned_lines, 2);
+        assert_eq!(
+            code.text,
+            format!(
+                "// Copyright 2024 Acme\npragma solidity ^0.8.0; {}\ncontract A {{}}",
+                INJECTED_PRAGMA_MARKER
+            )
+        );
+
+        let mut long = file(&format!(
+            "contract A {{ {} }}",
+            "uint a; ".repeat(LONG_LINE_THRESHOLD / 4)
+        ));
+        assert!(matches!(
+            Stage::LongLines { rewrap: false }.apply(&mut long, &log),
+            ControlFlow::Break(FileOutcome::Skipped(skip)) if skip.reason == SkipReason::LongLines
+        ));
+        assert!(
+            Stage::LongLines { rewrap: true }
+                .apply(&mut long, &log)
+                .is_continue()
+        );
+        assert!(max_line_length(&long.text) <= LONG_LINE_THRESHOLD);
+
+        let mut wide = file(
+            "function f(uint256 a, uint256 b) external {}
+bytes c = hex\"0011\";
+",
+        );
+        assert!(Stage::MaxLineWidth(20).apply(&mut wide, &log).is_continue());
+        assert_eq!(
+            wide.text,
+            "function f(uint256 a,\n    uint256 b) external {}\nbytes c = hex\"0011\";\n"
+        );
+        assert_eq!(wide.stats.cleaned_lines, 3);
+
+        // Depth is checked on the original, so the line is the file's own
+        let mut deep = file("// {{{{\ncontract A {\n{{{}}}\n}\n");
+        assert!(Stage::Clean.apply(&mut deep, &log).is_continue());
+        assert!(Stage::BraceDepth(4).apply(&mut deep, &log).is_continue());
+        assert!(matches!(
+            Stage::BraceDepth(3).apply(&mut deep, &log),
+            ControlFlow::Break(FileOutcome::Skipped(skip)) if skip.reason == SkipReason::DeepNesting
+                && skip.detail == "braces nested deeper than 3 (line 3); \
+                                   use --max-brace-depth to raise the limit"
+        ));
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            parse(&["solscrape", "u", "--max-brace-depth", "5000"])
+                .unwrap()
+                .max_brace_depth,
+            Some(5000)
+        );
+        for bad in ["0", "-1", "1k"] {
+            assert_eq!(
+                parse(&["solscrape", "u", "--max-brace-depth", bad]).unwrap_err(),
+                format!(
+                    "Invalid --max-brace-depth: {:?} (expected a positive whole number)",
+                    bad
+                )
+            );
+        }
+        assert!(parse(&["solscrape", "u", "--max-line-width", "0"]).is_err());
+        assert_eq!(
+            parse(&["solscrape", "u", "--max-line-width", "80", "--no-clean"]).unwrap_err(),
+            "--max-line-width cannot be used with --no-clean"
+        );
+    }
+
+    /// Verifies that single-line comments are removed from code.
+    #[test]
+    fn test_remove_single_line_comments() {
+        let code = "uint256 x; // this is a comment\nuint256 y;";
+        let (result, _) = remove_comments(code);
+        assert!(!result.contains("this is a comment"));
+        assert!(result.contains("uint256 x;"));
+        assert!(result.contains("uint256 y;"));
+    }
+
+    /// Verifies that multi-line comments are removed from code.
+    #[test]
+    fn test_remove_multi_line_comments() {
+        let code = "uint256 x; /* multi\nline\ncomment */ uint256 y;";
+        let (result, _) = remove_comments(code);
+        assert!(!result.contains("multi"));
+        assert!(!result.contains("comment"));
+        assert!(result.contains("uint256 x;"));
+        assert!(result.contains("uint256 y;"));
+    }
+
+    /// Verifies that comment-like syntax within double-quoted strings is preserved.
+    #[test]
+    fn test_preserve_strings() {
+        let code = r#"string s = "// not a comment";"#;
+        let (result, _) = remove_comments(code);
+        assert!(result.contains("// not a comment"));
+    }
+
+    /// Verifies that multi-line comment syntax within strings is preserved.
+    #[test]
+    fn test_preserve_strings_multiline() {
+        let code = r#"string s = "/* not a comment */";"#;
+        let (result, _) = remove_comments(code);
+        assert!(result.contains("/* not a comment */"));
+    }
+
+    // ------------------------------------------------------------------------
+    // Property tests for remove_comments
+    // ------------------------------------------------------------------------
+
+    /// Cases generated by [`test_remove_comments_properties`].
+    const COMMENT_FUZZ_CASES: u64 = 3_000;
+
+    /// A seeded SplitMix64 generator, so every case can be replayed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, items: &[&'a str]) -> &'a str {
+            items[self.below(items.len())]
+        }
+    }
+
+    /// A generated source: its text, the string literals it holds, and the
+    /// text left once its comments are removed.
+    struct GeneratedSource {
+        text: String,
+        strings: Vec<String>,
+        without_comments: String,
+    }
+
+    /// Generates a Solidity-ish token stream mixing strings with escapes,
+    /// both comment forms, and stray slashes and asterisks.
+    ///
+    /// A space is inserted wherever a `/` would run into a following `/` or
+    /// `*`, so the tokens keep the meaning they were generated with.
+    fn generate_source(rng: &mut Rng) -> GeneratedSource {
+        const CODE: [&str; 18] = [
+            "uint", "x", "_a1", "unicode", "return", "(", ")", "{", "}", ";", "=", " ", "\t", "*",
+            "**", "*/", "/", "\\",
+        ];
+        const IN_STRING: [&str; 14] = [
+            "a", " ", "/", "*", "//", "/*", "*/", "\\\"", "\\'", "\\\\", "\\n", "\"", "'", "é",
+        ];
+        const IN_COMMENT: [&str; 10] = ["a", " ", "/", "*", "//", "/*", "\"", "'", "\\", "é"];
+
+        let mut source = GeneratedSource {
+            text: String::new(),
+            strings: Vec::new(),
+            without_comments: String::new(),
+        };
+        for _ in 0..rng.below(40) {
+            let (token, is_comment) = match rng.below(10) {
+                0..=3 => (rng.pick(&CODE).to_string(), false),
+                4 => ("\n".to_string(), false),
+                5 | 6 => {
+                    let quote = rng.pick(&["\"", "'"]);
+                    let mut literal = quote.to_string();
+                    for _ in 0..rng.below(6) {
+                        let piece = rng.pick(&IN_STRING);
+                        // An unescaped quote of the literal's own kind would end it
+                        if piece == quote {
+                            literal.push('\\');
+                        }
+                        literal.push_str(piece);
+                    }
+                    literal.push_str(quote);
+                    source.strings.push(literal.clone());
+                    (literal, false)
+                }
+                7 => {
+                    let mut comment = "//".to_string();
+                    for _ in 0..rng.below(6) {
+                        comment.push_str(rng.pick(&IN_COMMENT));
+                    }
+                    (comment + "\n", true)
+                }
+                _ => {
+                    let mut body = String::new();
+                    for _ in 0..rng.below(6) {
+                        body.push_str(rng.pick(&IN_COMMENT));
+                        if rng.below(4) == 0 {
+                            body.push('\n');
+                        }
+                    }
+                    (format!("/*{}*/", body.replace("*/", "* /")), true)
+                }
+            };
+
+            if source.text.ends_with('/') && token.starts_with(['/', '*']) {
+                source.text.push(' ');
+                source.without_comments.push(' ');
+            }
+            source.text.push_str(&token);
+            if is_comment {
+                // A line comment's terminating newline is kept
+                if token.starts_with("//") {
+                    source.without_comments.push('\n');
+                }
+            } else {
+                source.without_comments.push_str(&token);
+            }
+        }
+        source
+    }
+
+    /// Independently scans comment-free code for string literals.
+    ///
+    /// # Errors
+    ///
+    /// Returns the byte offset of a `//` or `/*` outside a string literal.
+    fn scan_strings(code: &str) -> Result<Vec<String>, usize> {
+        let mut strings = Vec::new();
+        let mut literal: Option<(char, String)> = None;
+        let mut escaped = false;
+        let mut previous = None;
+        for (offset, c) in code.char_indices() {
+            match &mut literal {
+                Some((quote, text)) => {
+                    text.push(c);
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == *quote {
+                        strings.push(std::mem::take(text));
+                        literal = None;
+                    }
+                    previous = None;
+                }
+                None => {
+                    if previous == Some('/') && (c == '/' || c == '*') {
+                        return Err(offset - 1);
+                    }
+                    if c == '"' || c == '\'' {
+                        literal = Some((c, c.to_string()));
+                        previous = None;
+                    } else {
+                        previous = Some(c);
+                    }
+                }
+            }
+        }
+        Ok(strings)
+    }
+
+    /// The seeds of `cases` generated cases counting up from `base`, or just
+    /// the one in `SOLSCRAPE_FUZZ_SEED` when it is set.
+    fn fuzz_seeds(cases: u64, base: u64) -> Vec<u64> {
+        match env::var("SOLSCRAPE_FUZZ_SEED") {
+            Ok(seed) => {
+                let seed = seed.trim().trim_start_matches("0x");
+                vec![u64::from_str_radix(seed, 16).expect("SOLSCRAPE_FUZZ_SEED is hex")]
+            }
+            Err(_) => (0..cases).map(|case| base + case).collect(),
+        }
+    }
+
+    /// Checks invariants of [`remove_comments`] over generated sources.
+    ///
+    /// Every case has its own seed, printed on failure; set
+    /// `SOLSCRAPE_FUZZ_SEED` to that value to replay just that case.
+    #[test]
+    fn test_remove_comments_properties() {
+        for seed in fuzz_seeds(COMMENT_FUZZ_CASES, 0x5eed_0000) {
+            let source = generate_source(&mut Rng(seed));
+            let context = format!(
+                "seed {:#x} (replay with SOLSCRAPE_FUZZ_SEED={:x}), input {:?}",
+                seed, seed, source.text
+            );
+            let (output, stats) = remove_comments(&source.text);
+
+            assert!(output.len() <= source.text.len(), "{}", context);
+            assert_eq!(
+                stats.comment_bytes,
+                source.text.len() - output.len(),
+                "{}",
+                context
+            );
+            match scan_strings(&output) {
+                Ok(strings) => assert_eq!(strings, source.strings, "{}", context),
+                Err(offset) => panic!(
+                    "comment marker at byte {} of {:?}; {}",
+                    offset, output, context
+                ),
+            }
+            assert_eq!(output, source.without_comments, "{}", context);
+            assert_eq!(remove_comments(&output).0, output, "{}", context);
+        }
+    }
+
+    /// Checks that cleaning cleaned code changes nothing, over generated
+    /// sources.
+    #[test]
+    fn test_clean_solidity_idempotent() {
+        for seed in fuzz_seeds(COMMENT_FUZZ_CASES, 0x1de0_0000) {
+            let source = generate_source(&mut Rng(seed));
+            let (once, _) = clean_solidity(&source.text);
+            assert_eq!(
+                clean_solidity(&once).0,
+                once,
+                "seed {:#x} (replay with SOLSCRAPE_FUZZ_SEED={:x}), input {:?}",
+                seed,
+                seed,
+                source.text
+            );
+        }
+    }
+
+    /// The number of generated trees [`test_rescrape_is_identical`] scrapes
+    /// twice per flag set.
+    const RESCRAPE_CASES: u64 = 40;
+
+    /// Checks that scraping a directory holding only a previous output
+    /// reproduces it byte for byte, over generated trees and the flags that
+    /// change the output's shape.
+    #[test]
+    fn test_rescrape_is_identical() {
+        let root = fixture_dir("rescrape");
+        let log = Logger::new();
+        let flag_sets = [
+            Args::default(),
+            Args {
+                output_charset: Charset::Ascii,
+                ..Args::default()
+            },
+            Args {
+                no_headers: true,
+                ..Args::default()
+            },
+            Args {
+                include_lib: true,
+                keep_license_headers: true,
+                ..Args::default()
+            },
+        ];
+        for seed in fuzz_seeds(RESCRAPE_CASES, 0x5c2a_0000) {
+            let mut rng = Rng(seed);
+            let _ = fs::remove_dir_all(&root);
+            let source = root.join("source");
+            let files = 1 + rng.below(4);
+            for i in 0..files {
+                let dir = rng.pick(&["src", "src/utils", "lib/oz", "."]);
+                let text = format!(
+                    "// Copyright {} Acme\n{}\ncontract C{} {{}}\n",
+                    i,
+                    generate_source(&mut rng).text,
+                    i
+                );
+                write_tree(&source, &[(&format!("{}/F{}.sol", dir, i), &text)]);
+            }
+
+            for args in &flag_sets {
+                let scrape = |dir: &Path, out: &str| {
+                    scrape_directory(dir, root.join(out).to_str().unwrap(), "x", args, &log)
+                        .map(|result| fs::read(&result.output_path).unwrap())
+                };
+                let context = format!(
+                    "seed {:#x} (replay with SOLSCRAPE_FUZZ_SEED={:x}), flags {:?}",
+                    seed,
+                    seed,
+                    (args.output_charset, args.no_headers, args.include_lib)
+                );
+                let Ok(first) = scrape(&source, "out1") else {
+                    continue;
+                };
+                let again = root.join("again");
+                let _ = fs::remove_dir_all(&again);
+                fs::create_dir_all(&again).unwrap();
+                fs::write(again.join("x_scraped.sol"), &first).unwrap();
+                let second = scrape(&again, "out2").unwrap();
+                assert_eq!(
+                    String::from_utf8_lossy(&second),
+                    String::from_utf8_lossy(&first),
+                    "{}",
+                    context
+                );
+            }
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that empty and whitespace-only lines are removed.
+    #[test]
+    fn test_remove_empty_lines() {
+        let code = "line1\n\n\nline2\n  \nline3";
+        let (result, _) = remove_empty_lines(code);
+        assert_eq!(result, "line1\nline2\nline3");
+    }
+
+    /// Verifies that bare `\r` line endings end single-line comments instead
+    /// of letting them swallow the rest of the file.
+    #[test]
+    fn test_carriage_return_line_endings() {
+        let old_mac =
+            "// header\rpragma solidity ^0.8.0;\rcontract A { // trailing\r    uint x;\r}\r";
+        let (cleaned, stats) = clean_solidity(old_mac);
+        assert_eq!(
+            cleaned,
+            "pragma solidity ^0.8.0;\ncontract A {\n    uint x;\n}"
+        );
+        assert_eq!(stats.original_lines, 5);
+        assert_eq!(stats.comment_lines, 1);
+
+        let (without_comments, stats) = remove_comments(old_mac);
+        assert_eq!(stats.comment_bytes, old_mac.len() - without_comments.len());
+        assert!(!without_comments.contains('\r'));
+
+        let mixed = "// a\r\ncontract B {\r// b\r    uint y; /* c\r d */\r\n}\r// end";
+        let (cleaned, stats) = clean_solidity(mixed);
+        assert_eq!(cleaned, "contract B {\n    uint y;\n}");
+        assert_eq!(stats.original_lines, 7);
+
+        let (lines, stats) = remove_empty_lines("a\r\rb\r\n\r\nc\r");
+        assert_eq!(lines, "a\nb\nc");
+        assert_eq!(stats.empty_lines_removed, 2);
+    }
+
+    /// Verifies repository name extraction from various URL formats.
+    #[test]
+    fn test_extract_repo_name() {
+        assert_eq!(
+            extract_repo_name("https://github.com/user/repo.git"),
+            "repo"
+        );
+        assert_eq!(extract_repo_name("https://github.com/user/repo"), "repo");
+        assert_eq!(
+            extract_repo_name("https://github.com/user/my-project.git/"),
+            "my-project"
+        );
+        for url in [
+            "https://gitlab.com/group/sub/deeper/repo",
+            "https://gitlab.com/group/sub/repo.git",
+            "https://gitlab.com/group/sub/repo/",
+            "https://gitlab.com/group/sub/repo.git/",
+            "git@gitlab.com:group/sub/repo.git",
+            "https://gitlab.com/group/sub/repo/-/tree/main/src",
+            "https://bitbucket.org/team/repo/src/main/",
+        ] {
+            assert_eq!(extract_repo_name(url), "repo", "{url}");
+        }
+    }
+
+    /// Verifies that web URL sources are read as clone URL, ref, and directory.
+    #[test]
+    fn test_resolve_web_url() {
+        let parse = |list: &[&str]| {
+            let all = std::iter::once("solscrape").chain(list.iter().copied());
+            let mut args = parse_args_from(all.map(str::to_string).collect()).unwrap();
+            let note = resolve_web_url(&mut args);
+            (args, note)
+        };
+        let (args, note) = parse(&["https://gitlab.com/acme/sub/vault/-/tree/v2/src/core"]);
+        assert_eq!(args.source, "https://gitlab.com/acme/sub/vault");
+        assert_eq!(args.url_ref.as_deref(), Some("v2"));
+        assert_eq!(args.url_subdir.as_deref(), Some("src/core"));
+        assert_eq!(
+            note.unwrap(),
+            "Reading https://gitlab.com/acme/sub/vault/-/tree/v2/src/core as \
+             https://gitlab.com/acme/sub/vault, ref v2, directory src/core"
+        );
+
+        let (args, note) = parse(&["https://github.com/acme/vault.git"]);
+        assert_eq!(
+            (args.source.as_str(), args.url_ref, note),
+            ("https://github.com/acme/vault.git", None, None)
+        );
+        let (args, _) = parse(&["https://github.com/acme/vault/tree/main", "--local"]);
+        assert_eq!(args.url_ref, None);
+
+        let root = fixture_dir("checkout_root");
+        write_tree(&root, &[("src/core/A.sol", "contract A {}")]);
+        let with_subdir = |subdir: &str| Args {
+            source: "https://github.com/acme/vault".to_string(),
+            url_subdir: Some(subdir.to_string()),
+            ..Args::default()
+        };
+        assert_eq!(
+            checkout_root(&root, &with_subdir("src/core")).unwrap(),
+            root.join("src/core")
+        );
+        assert_eq!(
+            checkout_root(&root, &with_subdir("docs")).unwrap_err(),
+            "https://github.com/acme/vault has no directory docs"
+        );
+        assert!(checkout_root(&root, &with_subdir("../x")).is_err());
+        assert_eq!(checkout_root(&root, &Args::default()).unwrap(), root);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the complete cleaning pipeline with realistic Solidity code.
+    #[test]
+    fn test_clean_solidity() {
+        let code = r#"
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+/* This is a 
+   multi-line comment */
+contract Test {
+    // Single line comment
+    uint256 public value;
+    
+    string public name = "// not removed";
+}
+"#;
+        let (result, _) = clean_solidity(code);
+
+        assert!(!result.contains("SPDX-License-Identifier"));
+        assert!(!result.contains("multi-line comment"));
+        assert!(!result.contains("Single line comment"));
+        assert!(result.contains("pragma solidity"));
+        assert!(result.contains("contract Test"));
+        assert!(result.contains("uint256 public value"));
+        assert!(result.contains(r#""// not removed""#));
+    }
+
+    /// Verifies that SPDX-only files have no license header to preserve.
+    #[test]
+    fn test_license_header_spdx_only() {
+        let code = "// SPDX-License-Identifier: MIT\npragma solidity ^0.8.0;\ncontract A {}";
+        assert_eq!(extract_license_header(code), None);
+    }
+
+    /// Verifies that a legal block following the SPDX line is preserved verbatim.
+    #[test]
+    fn test_license_header_after_spdx() {
+        let code = "// SPDX-License-Identifier: BUSL-1.1\n/*\n * Copyright (c) 2024 Acme Ltd.\n * All rights reserved.\n */\npragma solidity ^0.8.0;\n/* Copyright again */\ncontract A {}";
+        assert_eq!(
+            extract_license_header(code).as_deref(),
+            Some("/*\n * Copyright (c) 2024 Acme Ltd.\n * All rights reserved.\n */")
+        );
+    }
+
+    /// Verifies that consecutive `//` lines form a block and only the first block is kept.
+    #[test]
+    fn test_license_header_line_comments_first_block_only() {
+        let code = "// Copyright 2024 Acme\n// Licensed under the Apache License\n\n// License: second block\ncontract A {}";
+        assert_eq!(
+            extract_license_header(code).as_deref(),
+            Some("// Copyright 2024 Acme\n// Licensed under the Apache License")
+        );
+    }
+
+    /// Verifies that copyright text appearing deep in the file is not preserved.
+    #[test]
+    fn test_license_header_deep_in_file_ignored() {
+        let mut code = String::from("pragma solidity ^0.8.0;\n");
+        code.push_str(&"uint256 constant X = 1;\n".repeat(30));
+        code.push_str("/* Copyright 2024 Acme */\n");
+        assert_eq!(extract_license_header(&code), None);
+
+        let mut late = "\n".repeat(LICENSE_HEADER_MAX_LINE);
+        late.push_str("/* Copyright 2024 Acme */\ncontract A {}");
+        assert_eq!(extract_license_header(&late), None);
+    }
+
+    /// Verifies that a leading comment without legal keywords is not preserved.
+    #[test]
+    fn test_license_header_requires_keyword() {
+        let code = "/* Token implementation */\ncontract A {}";
+        assert_eq!(extract_license_header(code), None);
+    }
+
+    /// Builds a single-line contract of roughly `statements * 30` bytes.
+    fn minified_contract(statements: usize) -> String {
+        let mut code = String::from("pragma solidity ^0.8.0; contract Big { ");
+        for i in 0..statements {
+            code.push_str(&format!("uint256 public v{i} = {i}; "));
+        }
+        code.push_str(r#"string public s = "a;b{c}d"; }"#);
+        code
+    }
+
+    /// Verifies re-wrapping splits at statement boundaries but never inside strings.
+    #[test]
+    fn test_rewrap_long_lines() {
+        let code = minified_contract(10_000);
+        assert!(code.len() > 200_000);
+        assert_eq!(code.lines().count(), 1);
+
+        let wrapped = rewrap_long_lines(&code, LONG_LINE_THRESHOLD);
+        assert!(max_line_length(&wrapped) < 100);
+        assert_eq!(wrapped.lines().count(), 10_004);
+        assert!(wrapped.contains(r#"string public s = "a;b{c}d";"#));
+        assert_eq!(wrapped.lines().last(), Some("}"));
+    }
+
+    /// Verifies that short lines are left untouched by re-wrapping.
+    #[test]
+    fn test_rewrap_long_lines_leaves_short_lines() {
+        let code = "contract A {\n    uint x; uint y;\n}";
+        assert_eq!(rewrap_long_lines(code, LONG_LINE_THRESHOLD), code);
+    }
+
+    /// Verifies minified files are skipped by default and re-wrapped on request.
+    #[test]
+    fn test_process_file_long_lines() {
+        let dir = std::env::temp_dir().join(format!("solscrape_longline_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("Big.sol");
+        fs::write(&path, minified_contract(10_000)).unwrap();
+        let log = Logger::new();
+
+        let outcome = process_file(&path, &dir, &Args::default(), &log).unwrap();
+        let FileOutcome::Skipped(reason) = outcome else {
+            panic!("expected skip, got {outcome:?}");
+        };
+        assert!(
+            reason.to_string().starts_with("[long-lines] 1 line(s), "),
+            "{reason}"
+        );
+        assert!(reason.detail.contains("bytes"));
+
+        let args = Args {
+            rewrap_long_lines: true,
+            ..Args::default()
+        };
+        let FileOutcome::Content { text: content, .. } =
+            process_file(&path, &dir, &args, &log).unwrap()
+        else {
+            panic!("expected content");
+        };
+        assert!(content.starts_with("// ═"));
+        assert!(content.lines().count() > 10_000);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies `--filter-pragma` selection and the `--pragma-unknown` policy.
+    #[test]
+    fn test_filter_pragma() {
+        let root = fixture_dir("filter_pragma");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                (
+                    "src/Eight.sol",
+                    "pragma solidity ^0.8.0;\ncontract Eight {}",
+                ),
+                (
+                    "src/Seven.sol",
+                    "pragma solidity >=0.6.0 <0.8.0;\ncontract Seven {}",
+                ),
+                (
+                    "src/Both.sol",
+                    "pragma solidity >=0.6.0;\npragma solidity <0.8.0;\ncontract Both {}",
+                ),
+                ("src/Bare.sol", "contract Bare {}"),
+            ],
+        );
+        let log = Logger::new();
+        let scrape = |args: &Args| {
+            scrape_directory(&source, root.join("out").to_str().unwrap(), "f", args, &log)
+                .unwrap()
+                .files_processed
+        };
+        let src = |name: &str| Path::new("src").join(name).to_string_lossy().to_string();
+
+        let parse = |list: &[&str]| {
+            let mut all = vec!["solscrape".to_string(), "x".to_string()];
+            all.extend(list.iter().map(|s| s.to_string()));
+            parse_args_from(all)
+        };
+        let args = parse(&["--filter-pragma", "0.8"]).unwrap();
+        assert_eq!(scrape(&args), vec![src("Bare.sol"), src("Eight.sol")]);
+        let args = parse(&["--filter-pragma", "0.8", "--pragma-unknown", "exclude"]).unwrap();
+        assert_eq!(scrape(&args), vec![src("Eight.sol")]);
+
+        let seven = source.join("src/Seven.sol");
+        let FileOutcome::Skipped(reason) = process_file(&seven, &source, &args, &log).unwrap()
+        else {
+            panic!("expected skip");
+        };
+        assert_eq!(
+            reason.to_string(),
+            "[pragma] `pragma solidity >=0.6.0 <0.8.0` is outside --filter-pragma 0.8"
+        );
+
+        let args = parse(&["--min-solidity", "0.7"]).unwrap();
+        assert_eq!(
+            scrape(&args),
+            vec![
+                src("Bare.sol"),
+                src("Both.sol"),
+                src("Eight.sol"),
+                src("Seven.sol")
+            ]
+        );
+        let args = parse(&["--min-solidity", "0.8"]).unwrap();
+        assert_eq!(scrape(&args), vec![src("Bare.sol"), src("Eight.sol")]);
+        let FileOutcome::Skipped(reason) = process_file(&seven, &source, &args, &log).unwrap()
+        else {
+            panic!("expected skip");
+        };
+        assert_eq!(
+            reason.to_string(),
+            "[pragma] `pragma solidity >=0.6.0 <0.8.0` only allows compilers below --min-solidity 0.8"
+        );
+        let args = parse(&["--min-solidity", "0.8", "--pragma-unknown", "exclude"]).unwrap();
+        assert_eq!(scrape(&args), vec![src("Eight.sol")]);
+        assert!(parse(&["--min-solidity", "^0.8"]).is_err());
+
+        assert!(parse(&["--filter-pragma", "eight"]).is_err());
+        assert!(parse(&["--pragma-unknown", "exclude"]).is_err());
+        assert!(parse(&["--filter-pragma", "0.8", "--pragma-unknown", "maybe"]).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that awkward file names cannot break the header format.
+    #[cfg(unix)]
+    #[test]
+    fn test_headers_escape_awkward_names() {
+        let root = fixture_dir("awkward_names");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("  Padded .sol", "contract Padded {}"),
+                ("Two\nLines.sol", "contract TwoLines {}"),
+            ],
+        );
+
+        let result = scrape_directory(
+            &source,
+            root.join("out").to_str().unwrap(),
+            "awkward",
+            &Args::default(),
+            &Logger::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.files_processed,
+            vec!["  Padded .sol".to_string(), "Two\nLines.sol".to_string()]
+        );
+
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        let headers: Vec<&str> = content
+            .lines()
+            .filter(|l| l.starts_with(HEADER_FILE_PREFIX))
+            .collect();
+        assert_eq!(
+            headers,
+            vec!["// File: \\x20\\x20Padded .sol", "// File: Two\\nLines.sol"]
+        );
+        let sections = split::parse_sections(&content).unwrap();
+        assert_eq!(sections.len(), 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that files matching `--no-headers-for` get the path line
+    /// alone, the others the framed header, and that `split` reads both.
+    #[test]
+    fn test_no_headers_for() {
+        let root = fixture_dir("no_headers_for");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("lib/oz/ERC20.sol", "contract ERC20 {}"),
+                ("src/Vault.sol", "contract Vault {}"),
+            ],
+        );
+        let args = Args {
+            include_lib: true,
+            no_headers_for: vec!["lib".to_string()],
+            ..Args::default()
+        };
+        let result = scrape_directory(
+            &source,
+            root.join("out").to_str().unwrap(),
+            "compact",
+            &args,
+            &Logger::new(),
+        )
+        .unwrap();
+
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        let separator = args.output_charset.symbols().header_separator;
+        assert!(
+            content.contains("\n// File: lib/oz/ERC20.sol\ncontract ERC20 {}"),
+            "{content}"
+        );
+        assert!(
+            !content.contains(&format!("{}\n// File: lib/oz/ERC20.sol", separator)),
+            "{content}"
+        );
+        assert!(
+            content.contains(&format!(
+                "{}\n// File: src/Vault.sol\n{}\ncontract Vault {{}}",
+                separator, separator
+            )),
+            "{content}"
+        );
+        let sections = split::parse_sections(&content).unwrap();
+        let paths: Vec<&str> = sections.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, ["lib/oz/ERC20.sol", "src/Vault.sol"]);
+        assert_eq!(sections[0].content.trim(), "contract ERC20 {}");
+
+        let parse = |pattern: &str| {
+            parse_args_from(
+                ["solscrape", "x", "--no-headers-for", pattern]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect(),
+            )
+        };
+        assert!(
+            parse("./")
+                .unwrap_err()
+                .starts_with("Invalid --no-headers-for")
+        );
+        assert_eq!(parse("**/mocks").unwrap().no_headers_for, ["**/mocks"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the options each profile sets, and that explicit flags win.
+    #[test]
+    fn test_profiles() {
+        let parse = |list: &[&str]| {
+            let mut all = vec!["solscrape".to_string(), "x".to_string()];
+            all.extend(list.iter().map(|s| s.to_string()));
+            parse_checked(all)
+        };
+        type Flags = (bool, bool, bool, bool, Option<String>);
+        let flags = |a: &Args| -> Flags {
+            (
+                a.no_headers,
+                a.keep_license_headers,
+                a.rewrap_long_lines,
+                a.no_clean,
+                a.inject_pragma.clone(),
+            )
+        };
+
+        let cases: [(&str, Flags); 3] = [
+            ("audit", (false, true, false, false, None)),
+            ("llm", (false, false, true, false, None)),
+            ("verify", (false, false, false, true, None)),
+        ];
+        for (name, expected) in cases {
+            let args = parse(&["--profile", name]).unwrap();
+            assert_eq!(flags(&args), expected, "{}", name);
+        }
+
+        // Explicit flags apply on top, wherever they appear
+        let args = parse(&["--no-headers", "--profile", "audit"]).unwrap();
+        assert_eq!(flags(&args), (true, true, false, false, None));
+        let args = parse(&["--inject-pragma", "^0.8.0", "--profile", "verify"]).unwrap();
+        assert_eq!(args.inject_pragma, Some("^0.8.0".to_string()));
+
+        assert!(
+            parse(&["--profile", "fast"])
+                .unwrap_err()
+                .starts_with("Unknown profile")
+        );
+        assert!(parse(&["--profile"]).is_err());
+
+        assert!(parse(&["--profile", "llm"]).unwrap().strip_revert_strings);
+        assert!(
+            parse(&["--strip-revert-strings", "--no-clean"])
+                .unwrap_err()
+                .contains("--no-clean")
+        );
+    }
+
+    /// Verifies that headers of files outside the base never show an
+    /// absolute local path.
+    #[test]
+    fn test_external_file_headers() {
+        let root = fixture_dir("external_headers");
+        write_tree(
+            &root,
+            &[
+                ("project/src/App.sol", "contract App {}"),
+                ("deps/oz/ERC20.sol", "contract ERC20 {}"),
+            ],
+        );
+        let header_of = |path: &Path, base: &Path| {
+            let Ok(FileOutcome::Content { text, .. }) =
+                process_file(path, base, &Args::default(), &Logger::new())
+            else {
+                panic!("expected content");
+            };
+            text.lines().nth(1).unwrap().to_string()
+        };
+
+        // An absolute remapping target next to the project
+        let external = root.join("deps/oz/ERC20.sol");
+        let header = header_of(&external, &root.join("project"));
+        assert_eq!(
+            header,
+            format!(
+                "{}{}",
+                HEADER_FILE_PREFIX,
+                Path::new("../deps/oz/ERC20.sol").display()
+            )
+        );
+        // Nothing in common with the base but the filesystem root
+        let header = header_of(&external, Path::new("/nonexistent-base"));
+        assert_eq!(
+            header,
+            format!("{}<external>/ERC20.sol", HEADER_FILE_PREFIX)
+        );
+        assert!(!header.contains(&root.to_string_lossy().to_string()));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that files reached through a symlinked directory are shown
+    /// under the link, not at their absolute target.
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_lib_headers() {
+        let root = fixture_dir("symlinked_lib");
+        write_tree(
+            &root,
+            &[
+                ("project/src/App.sol", "contract App {}"),
+                ("shared/oz/ERC20.sol", "contract ERC20 {}"),
+            ],
+        );
+        std::os::unix::fs::symlink(root.join("shared"), root.join("project/lib")).unwrap();
+        let args = Args {
+            include_lib: true,
+            ..Args::default()
+        };
+
+        let result = scrape_directory(
+            &root.join("project"),
+            root.join("out").to_str().unwrap(),
+            "linked",
+            &args,
+            &Logger::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.files_processed,
+            vec!["lib/oz/ERC20.sol", "src/App.sol"]
+        );
+        let written = fs::read_to_string(&result.output_path).unwrap();
+        assert!(written.contains(&format!("{}lib/oz/ERC20.sol\n", HEADER_FILE_PREFIX)));
+        assert!(!written.contains(&root.to_string_lossy().to_string()));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies one output per top-level directory, only for directories
+    /// with included files, and the `-o` prefix.
+    #[test]
+    fn test_split_by_dir() {
+        let root = fixture_dir("split_by_dir");
+        write_tree(
+            &root,
+            &[
+                ("src/Vault.sol", "contract Vault {}"),
+                ("src/utils/Math.sol", "library Math {}"),
+                ("lib/oz/ERC20.sol", "contract ERC20 {}"),
+                ("test/Vault.t.sol", "contract VaultTest {}"),
+                ("Root.sol", "contract Root {}"),
+            ],
+        );
+        let out = root.join("out");
+        let scrape = |args: &Args| {
+            scrape_directory(&root, out.to_str().unwrap(), "vault", args, &Logger::new()).unwrap()
+        };
+
+        let args = Args {
+            split_by_dir: true,
+            include_lib: true,
+            include_test: true,
+            ..Args::default()
+        };
+        let result = scrape(&args);
+        let outputs: Vec<(&str, PathBuf, usize)> = result
+            .outputs
+            .iter()
+            .map(|o| (o.dir.as_str(), o.path.clone(), o.parts.len()))
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                ("root", out.join("root_scraped.sol"), 1),
+                ("lib", out.join("lib_scraped.sol"), 1),
+                ("src", out.join("src_scraped.sol"), 2),
+                ("test", out.join("test_scraped.sol"), 1),
+            ]
+        );
+        assert_eq!(result.output_path, out.join("root_scraped.sol"));
+        assert_eq!(result.file_count, 5);
+        let src = fs::read_to_string(out.join("src_scraped.sol")).unwrap();
+        assert!(src.contains(&format!("{}src/utils/Math.sol\n", HEADER_FILE_PREFIX)));
+        assert!(!src.contains("ERC20"));
+        for output in &result.outputs {
+            let written = fs::read_to_string(&output.path).unwrap();
+            let last = output.parts.last().unwrap();
+            assert_eq!(written.len(), last.offset + last.len);
+        }
+
+        // Without --include-lib there is no lib output
+        let _ = fs::remove_dir_all(&out);
+        let args = Args {
+            split_by_dir: true,
+            output_name: Some("vault".to_string()),
+            ..Args::default()
+        };
+        let result = scrape(&args);
+        let paths: Vec<PathBuf> = result
+            .written_files()
+            .iter()
+            .map(|(p, _)| p.to_path_buf())
+            .collect();
+        assert_eq!(
+            paths,
+            [
+                out.join("vault_root_scraped.sol"),
+                out.join("vault_src_scraped.sol")
+            ]
+        );
+        assert!(!out.join("vault_lib_scraped.sol").exists());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies revert reasons are shortened after cleaning and the savings recorded.
+    #[test]
+    fn test_process_file_strips_revert_strings() {
+        let dir = fixture_dir("strip_revert");
+        write_tree(
+            &dir,
+            &[(
+                "Vault.sol",
+                "contract Vault {\n    function f(uint a, uint b) public {\n        \
+                 require(a > b, \"Vault: a must exceed b\"); // why\n        \
+                 emit Log(\"kept, (untouched)\");\n        revert Custom(\"also kept\");\n    }\n}\n",
+            )],
+        );
+        let args = Args {
+            strip_revert_strings: true,
+            no_headers: true,
+            ..Args::default()
+        };
+        let FileOutcome::Content { text, stats, .. } =
+            process_file(&dir.join("Vault.sol"), &dir, &args, &Logger::new()).unwrap()
+        else {
+            panic!("expected content");
+        };
+        assert!(text.contains("require(a > b, \"e\");\n"), "{}", text);
+        assert!(text.contains("emit Log(\"kept, (untouched)\");"));
+        assert!(text.contains("revert Custom(\"also kept\");"));
+        assert_eq!(
+            stats.revert_chars_saved,
+            "\"Vault: a must exceed b\"".len() - 3
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies `--no-clean` copies files verbatim.
+    #[test]
+    fn test_process_file_no_clean() {
+        let dir = fixture_dir("no_clean");
+        let path = dir.join("A.sol");
+        let source = "// SPDX-License-Identifier: MIT\n\n/// @notice A\ncontract A {}\n";
+        fs::write(&path, source).unwrap();
+        let args = Args {
+            no_clean: true,
+            no_headers: true,
+            keep_license_headers: true,
+            ..Args::default()
+        };
+
+        let FileOutcome::Content { text, stats, .. } =
+            process_file(&path, &dir, &args, &Logger::new()).unwrap()
+        else {
+            panic!("expected content");
+        };
+        assert_eq!(text, source);
+        assert_eq!(stats.cleaned_lines, 4);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies that scraping a tree and splitting the output recreates the cleaned tree.
+    #[test]
+    fn test_split_round_trip() {
+        let root = fixture_dir("split_round_trip");
+        let source = root.join("source");
+        let files = [
+            (
+                "src/Token.sol",
+                "// SPDX-License-Identifier: MIT\npragma solidity ^0.8.0;\n\n/* docs */\ncontract Token {\n    uint256 x; // value\n}\n",
+            ),
+            (
+                "src/interfaces/IToken.sol",
+                "pragma solidity ^0.8.0;\ninterface IToken {\n    function f() external;\n}\n",
+            ),
+            (
+                "src/utils/Math.sol",
+                "library Math {\n    string constant S = \"// kept\";\n}\n",
+            ),
+        ];
+        write_tree(&source, &files);
+
+        let result = scrape_directory(
+            &source,
+            root.join("out").to_str().unwrap(),
+            "fixture",
+            &Args::default(),
+            &Logger::new(),
+        )
+        .unwrap();
+
+        let split_dir = root.join("split");
+        let outcome = split::split_file(&result.output_path, &split_dir, false).unwrap();
+        assert_eq!(outcome.written.len(), files.len());
+        assert!(outcome.warnings.is_empty());
+
+        for (relative, content) in files {
+            let recreated = fs::read_to_string(split_dir.join(relative)).unwrap();
+            assert_eq!(
+                recreated,
+                format!("{}\n", clean_solidity(content).0),
+                "{relative}"
+            );
+        }
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies library banners with `--include-lib`, and that splitting
+    /// drops them again.
+    #[test]
+    fn test_library_banners() {
+        let root = fixture_dir("library_banners");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("lib/oz/package.json", "{\"version\": \"4.9.3\"}"),
+                ("lib/oz/token/ERC20.sol", "contract ERC20 {}"),
+                ("lib/oz/token/ERC721.sol", "contract ERC721 {}"),
+                ("lib/oz-upgradeable/token/ERC20.sol", "contract ERC20U {}"),
+                ("src/Vault.sol", "contract Vault {}"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+        let args = Args {
+            include_lib: true,
+            ..Args::default()
+        };
+
+        let result = scrape_directory(&source, out.to_str().unwrap(), "libs", &args, &log).unwrap();
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        let labels: Vec<&str> = content
+            .lines()
+            .filter(|l| library::is_banner_line(l) && *l != symbols::UNICODE.library_rule)
+            .collect();
+        assert_eq!(
+            labels,
+            [
+                "// Library: lib/oz (version 4.9.3)",
+                "// Library: lib/oz-upgradeable",
+                library::PROJECT_LABEL
+            ]
+        );
+        assert_eq!(result.line_count, content.lines().count());
+
+        let split_dir = root.join("split");
+        split::split_file(&result.output_path, &split_dir, false).unwrap();
+        for (path, code) in [
+            ("lib/oz/token/ERC721.sol", "contract ERC721 {}"),
+            ("lib/oz-upgradeable/token/ERC20.sol", "contract ERC20U {}"),
+        ] {
+            assert_eq!(
+                fs::read_to_string(split_dir.join(path)).unwrap().trim_end(),
+                code
+            );
+        }
+
+        for args in [
+            Args::default(),
+            Args {
+                include_lib: true,
+                no_headers: true,
+                ..Args::default()
+            },
+        ] {
+            let result =
+                scrape_directory(&source, out.to_str().unwrap(), "plain", &args, &log).unwrap();
+            let content = fs::read_to_string(&result.output_path).unwrap();
+            assert!(!content.lines().any(library::is_banner_line));
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that Soldeer packages follow `--include-lib` and show their
+    /// version in the header, and that splitting restores the real paths.
+    #[test]
+    fn test_soldeer_dependencies() {
+        let root = fixture_dir("soldeer");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("foundry.toml", "[dependencies]\nforge-std = \"1.9.1\"\n"),
+                ("src/Vault.sol", "contract Vault {}"),
+                (
+                    "dependencies/@openzeppelin-contracts-5.0.2/token/ERC20/ERC20.sol",
+                    "contract ERC20 {}",
+                ),
+                (
+                    "dependencies/forge-std-1.9.1/src/Test.sol",
+                    "contract Test {}",
+                ),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+
+        let default =
+            scrape_directory(&source, out.to_str().unwrap(), "a", &Args::default(), &log).unwrap();
+        assert_eq!(default.files_processed, vec!["src/Vault.sol"]);
+
+        let args = Args {
+            include_lib: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(&source, out.to_str().unwrap(), "b", &args, &log).unwrap();
+        assert_eq!(result.file_count, 3);
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        assert!(content.contains(
+            "// File: dependencies/@openzeppelin-contracts/token/ERC20/ERC20.sol (version 5.0.2)\n"
+        ));
+        assert!(content.contains("// File: dependencies/forge-std/src/Test.sol (version 1.9.1)\n"));
+        assert!(content.contains("// File: src/Vault.sol\n"));
+
+        let split_dir = root.join("split");
+        split::split_file(&result.output_path, &split_dir, false).unwrap();
+        assert!(
+            split_dir
+                .join("dependencies/forge-std-1.9.1/src/Test.sol")
+                .is_file()
+        );
+        assert_eq!(
+            split::extract_section(&content, "dependencies/forge-std-1.9.1/src/Test.sol").unwrap(),
+            "contract Test {}"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that `split` is recognized as a subcommand with two arguments.
+    #[test]
+    fn test_parse_split_subcommand() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let args = parse_args_from(argv(&["solscrape", "split", "a_scraped.sol", "out"])).unwrap();
+        assert_eq!(
+            args.subcommand,
+            Some(Subcommand::Split {
+                input: "a_scraped.sol".to_string(),
+                out_dir: "out".to_string(),
+                mtime_from: None,
+            })
+        );
+        let args = parse_args_from(argv(&[
+            "solscrape",
+            "split",
+            "a_scraped.sol",
+            "out",
+            "--mtime-from",
+            "./repo",
+        ]))
+        .unwrap();
+        assert!(matches!(
+            args.subcommand,
+            Some(Subcommand::Split { mtime_from: Some(dir), .. }) if dir == "./repo"
+        ));
+        assert_eq!(
+            parse_args_from(argv(&[
+                "solscrape",
+                "./repo",
+                "--local",
+                "--mtime-from",
+                "."
+            ]))
+            .unwrap_err(),
+            "--mtime-from can only be used with split"
+        );
+
+        assert!(parse_args_from(argv(&["solscrape", "split", "a_scraped.sol"])).is_err());
+
+        let local = parse_args_from(argv(&["solscrape", "split", "--local"])).unwrap();
+        assert_eq!(local.subcommand, None);
+        assert_eq!(local.source, "split");
+    }
+
+    /// Verifies `--output-mode` values and the combinations it rejects.
+    #[test]
+    fn test_parse_output_mode() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        for (value, mode) in [
+            ("path", OutputMode::Path),
+            ("content", OutputMode::Content),
+            ("json", OutputMode::Json),
+        ] {
+            let args =
+                parse_args_from(argv(&["solscrape", "url", "--output-mode", value])).unwrap();
+            assert_eq!(args.output_mode, Some(mode));
+        }
+
+        for (list, expected) in [
+            (
+                &["solscrape", "url", "--output-mode", "yaml"][..],
+                "Invalid --output-mode: yaml (expected path, content, or json)",
+            ),
+            (
+                &[
+                    "solscrape",
+                    "split",
+                    "a.sol",
+                    "out",
+                    "--output-mode",
+                    "content",
+                ][..],
+                "--output-mode cannot be used with split",
+            ),
+            (
+                &["solscrape", "--output-mode", "path", "cleanup-temp"][..],
+                "--output-mode cannot be used with cleanup-temp",
+            ),
+            (
+                &["solscrape", "url", "--list-files", "--output-mode", "path"][..],
+                "--output-mode cannot be used with --list-files",
+            ),
+            (
+                &[
+                    "solscrape",
+                    "url",
+                    "--compare",
+                    "a..b",
+                    "--output-mode",
+                    "json",
+                ][..],
+                "--output-mode json cannot be used with --compare",
+            ),
+        ] {
+            assert_eq!(parse_checked(argv(list)).unwrap_err(), expected);
+        }
+        assert!(
+            parse_args_from(argv(&[
+                "solscrape",
+                "url",
+                "--compare",
+                "a..b",
+                "--output-mode",
+                "content"
+            ]))
+            .is_ok()
+        );
+    }
+
+    /// Verifies that `extract` takes a consolidated file and one path.
+    #[test]
+    fn test_parse_extract_subcommand() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&[
+            "solscrape",
+            "extract",
+            "a_scraped.sol",
+            "src/A.sol",
+        ]))
+        .unwrap();
+        assert_eq!(
+            args.subcommand,
+            Some(Subcommand::Extract {
+                input: "a_scraped.sol".to_string(),
+                path: "src/A.sol".to_string(),
+            })
+        );
+        assert!(parse_args_from(argv(&["solscrape", "extract", "a_scraped.sol"])).is_err());
+    }
+
+    /// Verifies that `verify` takes exactly one file, and where
+    /// `--checksum-file` is refused.
+    #[test]
+    fn test_parse_verify_subcommand() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&["solscrape", "verify", "a_scraped.sol"])).unwrap();
+        assert_eq!(
+            args.subcommand,
+            Some(Subcommand::Verify {
+                input: "a_scraped.sol".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_args_from(argv(&["solscrape", "verify"])).unwrap_err(),
+            "Usage: solscrape verify <scraped.sol>"
+        );
+        assert!(
+            parse_args_from(argv(&["solscrape", "x", "--checksum-file"]))
+                .unwrap()
+                .checksum_file
+        );
+        assert_eq!(
+            parse_checked(argv(&["solscrape", "x", "--checksum-file", "--list-files"]))
+                .unwrap_err(),
+            "--checksum-file cannot be used with --list-files or --compare"
+        );
+    }
+
+    /// Verifies that every flag of FLAGS has a parser arm, and that nothing
+    /// else is taken for a flag.
+    #[test]
+    fn test_parse_flags() {
+        let parse = |list: &[&str]| parse_args_from(list.iter().map(|s| s.to_string()).collect());
+        for flag in FLAGS {
+            let unknown = format!("Unknown option: {}", flag);
+            for argv in [&["solscrape", flag, "1"][..], &["solscrape", "x", flag]] {
+                assert_ne!(parse(argv).err(), Some(unknown.clone()), "{:?}", argv);
+            }
+        }
+        for flag in ["--no-such-flag", "-", "--Local", "--output=x"] {
+            assert_eq!(
+                parse(&["solscrape", "x", flag]).unwrap_err(),
+                format!("Unknown option: {}", flag)
+            );
+        }
+    }
+
+    /// Verifies that every flag of FLAGS is documented on exactly one topic
+    /// page, or with its subcommand, and that each page lists its own
+    /// options only.
+    #[test]
+    fn test_help_topics() {
+        let parsed: std::collections::BTreeSet<&str> = FLAGS.into_iter().collect();
+        assert_eq!(parsed.len(), FLAGS.len(), "a flag is in FLAGS twice");
+
+        let flags = |option: &OptionHelp| -> Vec<&'static str> {
+            option
+                .usage
+                .split([',', ' '])
+                .filter(|word| word.starts_with('-'))
+                .collect()
+        };
+        for flag in &parsed {
+            let listed = OPTIONS_HELP
+                .iter()
+                .filter(|option| flags(option).contains(flag))
+                .count();
+            if listed == 0 {
+                assert!(HELP_USAGE.contains(flag), "{} is not documented", flag);
+            } else {
+                assert_eq!(listed, 1, "{} is listed more than once", flag);
+            }
+        }
+        for option in OPTIONS_HELP {
+            for flag in flags(option) {
+                assert!(parsed.contains(flag), "{} is not parsed", flag);
+            }
+        }
+
+        let symbols = Charset::Ascii.symbols();
+        for topic in HelpTopic::ALL {
+            let page = render_help(HelpPage::Topic(topic), symbols);
+            assert!(page.contains(topic.title()), "{}", page);
+            for option in OPTIONS_HELP {
+                let line = format!("\n    {}", option.usage);
+                assert_eq!(page.contains(&line), option.topic == topic, "{}", line);
+            }
+        }
+        let all = render_help(HelpPage::All, symbols);
+        assert!(OPTIONS_HELP.iter().all(|option| all.contains(option.usage)));
+        assert!(all.contains("\n    --foundry-cache <PATH> Where to look"));
+        assert!(
+            all.contains("\n    --context-readme-limit <SIZE>\n                           Bytes")
+        );
+        let overview = render_help(HelpPage::Overview, symbols);
+        assert!(overview.contains("solscrape --help filters"));
+        assert!(!overview.contains("OPTIONS:"));
+
+        let parse = |list: &[&str]| parse_args_from(list.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            parse(&["solscrape", "--help", "git"]).unwrap().help_page,
+            HelpPage::Topic(HelpTopic::Git)
+        );
+        let args = parse(&["solscrape", "-h", "--verbose"]).unwrap();
+        assert_eq!((args.help_page, args.verbose), (HelpPage::Overview, true));
+        assert_eq!(
+            parse(&["solscrape", "-h", "all"]).unwrap().help_page,
+            HelpPage::All
+        );
+        assert_eq!(
+            parse(&["solscrape", "--help", "colours"]).unwrap_err(),
+            "Unknown help topic: colours (expected one of: general, filters, formats, output, \
+             git, all)"
+        );
+    }
+
+    /// Verifies -b/--branch with and without a value, and that it wins over
+    /// the ref of a web URL.
+    #[test]
+    fn test_parse_branch() {
+        let parse = |list: &[&str]| parse_args_from(list.iter().map(|s| s.to_string()).collect());
+        for flag in ["-b", "--branch"] {
+            let args = parse(&["solscrape", "u", flag, "release-v4.9"]).unwrap();
+            assert_eq!(args.url_ref.as_deref(), Some("release-v4.9"));
+            assert_eq!(args.source, "u");
+            assert_eq!(
+                parse(&["solscrape", "u", flag]).unwrap_err(),
+                "--branch requires a value"
+            );
+        }
+        assert_eq!(
+            parse(&["solscrape", "u", "--branch", "--local"]).unwrap_err(),
+            "Invalid --branch: \"--local\" (expected a branch or tag)"
+        );
+        assert_eq!(parse(&["solscrape", "u"]).unwrap().url_ref, None);
+
+        let mut args = parse(&[
+            "solscrape",
+            "https://github.com/a/b/tree/main/src",
+            "-b",
+            "develop",
+        ])
+        .unwrap();
+        let note = resolve_web_url(&mut args).unwrap();
+        assert!(note.ends_with(", ref develop, directory src"), "{}", note);
+        assert_eq!(args.url_ref.as_deref(), Some("develop"));
+    }
+
+    /// Verifies that --rev takes a value, cannot be combined with --branch
+    /// or --compare, and wins over a web URL's ref.
+    #[test]
+    fn test_parse_rev() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        let args = parse(&["solscrape", "u", "--rev", "6f4b0c4"]).unwrap();
+        assert_eq!((args.rev.as_deref(), args.url_ref), (Some("6f4b0c4"), None));
+        assert_eq!(
+            parse(&["solscrape", "u", "--rev", "-b"]).unwrap_err(),
+            "Invalid --rev: \"-b\" (expected a commit, tag, or branch)"
+        );
+        assert_eq!(
+            parse(&["solscrape", "u", "--rev", "v1", "-b", "main"]).unwrap_err(),
+            "--rev cannot be used with --branch; give one or the other"
+        );
+        assert_eq!(
+            parse(&["solscrape", "u", "--rev", "v1", "--compare", "a..b"]).unwrap_err(),
+            "--rev cannot be used with --compare, which names its own refs"
+        );
+
+        let mut args = parse(&[
+            "solscrape",
+            "https://github.com/a/b/tree/main/src",
+            "--rev",
+            "v1.0",
+        ])
+        .unwrap();
+        let note = resolve_web_url(&mut args).unwrap();
+        assert!(note.ends_with(", ref v1.0, directory src"), "{}", note);
+        assert_eq!(
+            (args.url_ref, args.url_subdir.as_deref()),
+            (None, Some("src"))
+        );
+    }
+
+    /// Verifies that --no-git refuses the flags that need git, skips the
+    /// pre-check, and allows the downloaders under --no-subprocess.
+    #[test]
+    fn test_parse_no_git() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        let args = parse(&["solscrape", "https://github.com/a/b", "--no-git"]).unwrap();
+        assert!(args.no_git && !wants_precheck(&args));
+        for flags in [
+            &["--reuse-clone", "c"][..],
+            &["--compare", "a..b"],
+            &["--since", "main"],
+            &["--precheck"],
+        ] {
+            let mut argv = vec!["solscrape", "u", "--no-git"];
+            argv.extend(flags);
+            assert_eq!(
+                parse(&argv).unwrap_err(),
+                "--no-git cannot be used with --reuse-clone, --compare, --since, \
+                 or --precheck, which need git",
+                "{:?}",
+                flags
+            );
+        }
+
+        let args = Args {
+            no_subprocess: true,
+            ..args
+        };
+        assert_eq!(allowed_programs(&args), ["curl", "wget", "tar"]);
+    }
+
+    /// Verifies that `--digest` parses and refuses the other ways of
+    /// printing to stdout.
+    #[test]
+    fn test_parse_digest() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        assert!(parse(&["solscrape", "src", "--digest"]).unwrap().digest);
+        for flags in [
+            &["--list-files"][..],
+            &["--output-mode", "json"],
+            &["--compare", "a..b"],
+        ] {
+            let mut argv = vec!["solscrape", "src", "--digest"];
+            argv.extend(flags);
+            assert!(
+                parse(&argv)
+                    .unwrap_err()
+                    .starts_with("--digest cannot be used with"),
+                "{:?}",
+                flags
+            );
+        }
+    }
+
+    /// Verifies `--errors-report` formats and the modes it cannot be used in.
+    #[test]
+    fn test_parse_errors_report() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        let args = parse(&["solscrape", "src", "--errors-report", "json"]).unwrap();
+        assert_eq!(args.errors_report, Some(error_report::Format::Json));
+        assert_eq!(
+            parse(&["solscrape", "src", "--errors-report", "csv"]).unwrap_err(),
+            "Invalid --errors-report: csv (expected text or json)"
+        );
+        assert!(parse(&["solscrape", "src", "--errors-report"]).is_err());
+        for flag in ["--list-files", "--digest", "--split-by-dir"] {
+            assert!(
+                parse(&["solscrape", "src", "--errors-report", "text", flag])
+                    .unwrap_err()
+                    .starts_with("--errors-report cannot be used with"),
+                "{}",
+                flag
+            );
+        }
+    }
+
+    /// Verifies that --gate repeats in order, rejects a malformed condition,
+    /// and refuses the modes that write no scrape.
+    #[test]
+    fn test_parse_gates() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        let args = parse(&[
+            "solscrape",
+            ".",
+            "--gate",
+            "no-skips",
+            "--gate",
+            "max-lines:200000",
+        ])
+        .unwrap();
+        assert_eq!(
+            args.gates,
+            [gate::Gate::NoSkips, gate::Gate::MaxLines(200_000)]
+        );
+        assert!(
+            parse(&["solscrape", ".", "--gate", "no-lines"])
+                .unwrap_err()
+                .starts_with("Invalid --gate: no-lines")
+        );
+        for flag in ["--list-files", "--digest"] {
+            assert_eq!(
+                parse(&["solscrape", ".", "--gate", "no-skips", flag]).unwrap_err(),
+                "--gate cannot be used with --list-files, --compare, or --digest",
+                "{}",
+                flag
+            );
+        }
+    }
+
+    /// Verifies that --source repeats with the destination as the only
+    /// argument, that a single one is a plain source, and the flags several
+    /// refuse.
+    #[test]
+    fn test_parse_sources() {
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        let args = parse(&["solscrape", "--source", "a", "--source", "b", "out"]).unwrap();
+        assert_eq!(args.sources, ["a", "b"]);
+        assert_eq!(args.destination, "out");
+        let args = parse(&["solscrape", "--source", "a"]).unwrap();
+        assert!(args.sources.is_empty());
+        assert_eq!(args.source, "a");
+        assert_eq!(
+            parse(&["solscrape", "--source", "a", "b", "out"]).unwrap_err(),
+            "With --source, the only argument is the destination"
+        );
+        assert_eq!(
+            parse(&["solscrape", "--source", "a", "--split-by-source"]).unwrap_err(),
+            "--split-by-source requires more than one --source"
+        );
+        assert!(
+            parse(&[
+                "solscrape",
+                "--source",
+                "a",
+                "--source",
+                "b",
+                "--split-by-source"
+            ])
+            .is_ok()
+        );
+        for flag in ["--stream", "--list-files", "--with-context"] {
+            assert!(
+                parse(&["solscrape", "--source", "a", "--source", "b", flag])
+                    .unwrap_err()
+                    .starts_with("Several --source cannot be used with"),
+                "{}",
+                flag
+            );
+        }
+        assert_eq!(
+            parse(&["solscrape", "--source", "a", "--from-file", "s.txt"]).unwrap_err(),
+            "--source cannot be used with --from-file"
+        );
+        assert_eq!(
+            parse(&["solscrape", "verify", "out.sol", "--source", "a"]).unwrap_err(),
+            "--source cannot be used with verify"
+        );
+    }
+
+    /// Verifies that `check` takes one source and --against, keeps the
+    /// scrape options, and refuses the flags that change what is scraped
+    /// into something else.
+    #[test]
+    fn test_parse_check_subcommand() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&[
+            "solscrape",
+            "check",
+            "./repo",
+            "--against",
+            "repo_scraped.sol",
+            "--include-lib",
+        ]))
+        .unwrap();
+        assert_eq!(
+            args.subcommand,
+            Some(Subcommand::Check {
+                against: "repo_scraped.sol".to_string(),
+            })
+        );
+        assert_eq!(args.source, "./repo");
+        assert!(args.include_lib);
+        for missing in [
+            &["solscrape", "check", "./repo"][..],
+            &["solscrape", "check", "--against", "a.sol"],
+            &["solscrape", "check", "./repo", "out", "--against", "a.sol"],
+        ] {
+            assert_eq!(
+                parse_args_from(argv(missing)).unwrap_err(),
+                "Usage: solscrape check <source> --against <scraped.sol>"
+            );
+        }
+        assert!(
+            parse_args_from(argv(&[
+                "solscrape",
+                "check",
+                "./repo",
+                "--against",
+                "a.sol",
+                "--since",
+                "v1"
+            ]))
+            .unwrap_err()
+            .starts_with("check cannot be used with")
+        );
+        assert_eq!(
+            parse_args_from(argv(&["solscrape", "./repo", "--against", "a.sol"])).unwrap_err(),
+            "--against can only be used with check"
+        );
+        let mut args =
+            parse_args_from(argv(&["solscrape", "check", ".", "--against", "a.sol"])).unwrap();
+        assert!(autodetect_local(&mut args).is_some());
+        assert!(!clones_source(&args));
+
+        // --local is a scrape option of check, not a reason to read `check`
+        // as the source
+        let args = parse_args_from(argv(&[
+            "solscrape",
+            "check",
+            "./repo",
+            "--local",
+            "--against",
+            "a.sol",
+        ]))
+        .unwrap();
+        assert!(matches!(args.subcommand, Some(Subcommand::Check { .. })));
+        assert!(args.is_local);
+        assert_eq!(args.source, "./repo");
+        let args = parse_args_from(argv(&["solscrape", "./check", "out", "--local"])).unwrap();
+        assert_eq!((args.subcommand, args.source.as_str()), (None, "./check"));
+    }
+
+    /// Verifies that a --from-file line is parsed under the command-line
+    /// options, its own values winning, and that global flags, unknown
+    /// options, and subcommands are refused per line.
+    #[test]
+    fn test_batch_line_args() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&[
+            "solscrape",
+            "--from-file",
+            "sources.txt",
+            "out",
+            "-o",
+            "shared",
+            "--include-test",
+            "--quiet",
+            "--deadline",
+            "5m",
+        ]))
+        .unwrap();
+        assert_eq!(args.from_file.as_deref(), Some("sources.txt"));
+        assert_eq!(args.destination, "out");
+        assert_eq!(
+            args.batch_options,
+            [
+                "-o",
+                "shared",
+                "--include-test",
+                "--quiet",
+                "--deadline",
+                "5m"
+            ]
+        );
+        let entry = |line: &str| batch::Entry {
+            line: 3,
+            words: batch::tokenize(line).unwrap(),
+        };
+
+        let line = batch_line_args(
+            &args,
+            &entry("https://github.com/x/core.git --branch develop -o 'core v2' --include-lib"),
+        )
+        .unwrap();
+        assert_eq!(line.source, "https://github.com/x/core.git");
+        assert_eq!(line.destination, "out");
+        assert_eq!(line.url_ref.as_deref(), Some("develop"));
+        assert_eq!(line.output_name.as_deref(), Some("core v2"));
+        assert!(line.include_lib && line.include_test && line.quiet);
+        assert_eq!(line.deadline, args.deadline);
+        assert!(line.from_file.is_none());
+
+        let line = batch_line_args(&args, &entry("./b")).unwrap();
+        assert_eq!(line.output_name.as_deref(), Some("shared"));
+        assert!(!line.include_lib);
+
+        for (words, expected) in [
+            (
+                "./b --verbose",
+                "--verbose applies to the whole run; give it on the command line",
+            ),
+            ("./b --colour", "Unknown option: --colour"),
+            ("./b other", "Too many positional arguments"),
+            (
+                "split a.sol",
+                "A line names one source and its options, not a subcommand",
+            ),
+        ] {
+            assert_eq!(
+                batch_line_args(&args, &entry(words)).unwrap_err(),
+                expected,
+                "{}",
+                words
+            );
+        }
+        assert_eq!(
+            parse_args_from(argv(&["solscrape", "verify", "--from-file", "s.txt"])).unwrap_err(),
+            "--from-file cannot be used with verify"
+        );
+        assert_eq!(
+            parse_args_from(argv(&["solscrape", "./a", "--resume"])).unwrap_err(),
+            "--resume can only be used with --from-file"
+        );
+        assert!(
+            parse_args_from(argv(&["solscrape", "--from-file", "s.txt", "a", "b"]))
+                .unwrap_err()
+                .starts_with("With --from-file")
+        );
+    }
+
+    /// Verifies the metrics and --split-by-dir flags and their conflicts.
+    #[test]
+    fn test_parse_output_flags() {
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&["solscrape", "x", "--metrics-file", "m.txt"])).unwrap();
+        assert_eq!(args.metrics_file.as_deref(), Some("m.txt"));
+        for (extra, error) in [
+            (
+                &["--metrics-file", "m.txt", "--metrics-stdout"][..],
+                "--metrics-file cannot be used with --metrics-stdout",
+            ),
+            (
+                &["--metrics-stdout", "--list-files"],
+                "--metrics-file and --metrics-stdout cannot be used with --list-files or --compare",
+            ),
+            (
+                &["--metrics-stdout", "--output-mode", "path"],
+                "--metrics-stdout cannot be used with --output-mode",
+            ),
+            (
+                &["--split-by-dir", "--stream"],
+                "--split-by-dir cannot be used with --stream, --list-files, or --compare",
+            ),
+            (
+                &["--split-by-dir", "--output-mode", "content"],
+                "--split-by-dir cannot be used with --output-mode path or content, \
+                 which print a single output",
+            ),
+        ] {
+            let mut list = vec!["solscrape", "x"];
+            list.extend(extra);
+            assert_eq!(parse_checked(argv(&list)).unwrap_err(), error);
+        }
+    }
+
+    /// Verifies recorded part offsets against the written output, for both
+    /// the sorted and the streaming writer, and that `extract` reads the
+    /// same section through the `--parts-file` sidecar as by headers.
+    #[test]
+    fn test_part_offsets_round_trip() {
+        let root = fixture_dir("part_offsets");
+        let src = root.join("src");
+        write_tree(
+            &src,
+            &[
+                ("A.sol", "// a\ncontract A {}\n"),
+                ("b/Ünïcode.sol", "contract B {\n    string s = \"é\";\n}\n"),
+                ("Empty.sol", "// only a comment\n"),
+                ("C.sol", "library C {}"),
+            ],
+        );
+
+        for stream in [false, true] {
+            let out = root.join(format!("out_{}", stream));
+            let args = Args {
+                stream,
+                ..Args::default()
+            };
+            let result =
+                scrape_directory(&src, out.to_str().unwrap(), "parts", &args, &Logger::new())
+                    .unwrap();
+            let bytes = fs::read(&result.output_path).unwrap();
+            let text = String::from_utf8(bytes.clone()).unwrap();
+
+            let paths: Vec<&str> = result.parts.iter().map(|p| p.path.as_str()).collect();
+            assert_eq!(paths, result.files_processed);
+            assert_eq!(result.parts.len(), 3);
+            let last = result.parts.last().unwrap();
+            assert_eq!(last.offset + last.len, bytes.len());
+            parts::write_sidecar(&result.output_path, &result.parts).unwrap();
+
+            for part in &result.parts {
+                let slice =
+                    std::str::from_utf8(&bytes[part.offset..part.offset + part.len]).unwrap();
+                let (header, content) = slice.split_at(
+                    slice
+                        .match_indices('\n')
+                        .nth(2)
+                        .map(|(i, _)| i + 1)
+                        .unwrap(),
+                );
+                assert_eq!(
+                    header,
+                    format!(
+                        "{0}\n{1}{2}\n{0}\n",
+                        HEADER_SEPARATOR, HEADER_FILE_PREFIX, part.path
+                    )
+                );
+                assert_eq!(
+                    split::extract_section(&text, &part.path).unwrap(),
+                    content.trim_end_matches('\n')
+                );
+                let span = parts::find(&result.output_path, &part.path)
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(&span, part);
+                assert_eq!(
+                    parts::read_section(&result.output_path, &span).unwrap(),
+                    content.trim_end_matches('\n')
+                );
+            }
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies line classification and comment byte counting.
+    #[test]
+    fn test_remove_comments_stats() {
+        let code = "// header\n\npragma solidity ^0.8.0; // trailing\n/* a\n b */\n   \nuint x;";
+        let (cleaned, stats) = remove_comments(code);
+
+        assert_eq!(stats.original_lines, 7);
+        assert_eq!(stats.comment_lines, 3);
+        assert_eq!(stats.blank_lines, 2);
+        assert_eq!(stats.comment_bytes, code.len() - cleaned.len());
+    }
+
+    /// Verifies that comment-like text inside strings is never counted as comment.
+    #[test]
+    fn test_remove_comments_stats_ignore_strings() {
+        let code = "string s = \"// not /* a */ comment\";\nstring t = '//';";
+        let (cleaned, stats) = remove_comments(code);
+
+        assert_eq!(cleaned, code);
+        assert_eq!(stats.comment_bytes, 0);
+        assert_eq!(stats.comment_lines, 0);
+        assert_eq!(stats.original_lines, 2);
+    }
+
+    /// Verifies the combined pipeline's counters.
+    #[test]
+    fn test_clean_solidity_stats() {
+        let code = "// a\n\ncontract A {\n    // b\n    uint x;\n}\n";
+        let (cleaned, stats) = clean_solidity(code);
+
+        assert_eq!(cleaned, "contract A {\n    uint x;\n}");
+        assert_eq!(stats.cleaned_lines, 3);
+        assert_eq!(stats.comment_lines, 2);
+        assert_eq!(stats.blank_lines, 1);
+        assert_eq!(stats.empty_lines_removed, 3);
+    }
+
+    /// Verifies the stats report totals and documentation ranking.
+    #[test]
+    fn test_render_stats_report() {
+        let (_, dense) = clean_solidity("// lots of docs here\n// more docs\nuint x;");
+        let (_, sparse) = clean_solidity("uint x; // d\nuint y;");
+        let (_, none) = clean_solidity("uint x;");
+        let report = render_stats_report(&[
+            ("Sparse.sol".to_string(), sparse),
+            ("Dense.sol".to_string(), dense),
+            ("None.sol".to_string(), none),
+        ]);
+
+        let lines: Vec<&str> = report.lines().collect();
+        assert!(lines[5].trim_start().starts_with("Total"));
+        let most = lines.iter().position(|l| *l == "Most documented:").unwrap();
+        let least = lines
+            .iter()
+            .position(|l| *l == "Least documented:")
+            .unwrap();
+        assert!(lines[most + 1].contains("Dense.sol"));
+        assert!(lines[least + 1].contains("None.sol (0.0% comments)"));
+    }
+
+    /// Verifies the directory rollup over a nested tree at several depths,
+    /// with files at the root and directly in a rolled-up directory.
+    #[test]
+    fn test_rollup_directories() {
+        let stats = |lines: usize| CleanStats {
+            cleaned_lines: lines,
+            ..CleanStats::default()
+        };
+        let file_stats = [
+            ("Root.sol", 30),
+            ("src/Top.sol", 10),
+            ("src/core/Vault.sol", 60),
+            ("src/core/deep/Deep.sol", 20),
+            ("src/utils/Math.sol", 40),
+            ("lib/Lib.sol", 40),
+        ]
+        .map(|(path, lines)| (path.to_string(), stats(lines)));
+        let shape = |rollups: &[DirRollup]| -> Vec<(String, usize, usize, usize)> {
+            rollups
+                .iter()
+                .map(|r| (r.dir.clone(), r.files, r.lines, r.children.len()))
+                .collect()
+        };
+
+        let top = rollup_directories(&file_stats, 1);
+        assert_eq!(
+            shape(&top),
+            [
+                ("src".to_string(), 4, 130, 0),
+                ("lib".to_string(), 1, 40, 0),
+                (".".to_string(), 1, 30, 0),
+            ]
+        );
+        assert_eq!(top[0].largest, ("src/core/Vault.sol".to_string(), 60));
+        assert_eq!(top[2].largest, ("Root.sol".to_string(), 30));
+        let share: f64 = top.iter().map(|r| r.share).sum();
+        assert!((share - 100.0).abs() < 1e-9);
+
+        let nested = rollup_directories(&file_stats, DEFAULT_STATS_DEPTH);
+        assert_eq!(
+            shape(&nested[0].children),
+            [
+                ("src/core".to_string(), 2, 80, 0),
+                ("src/utils".to_string(), 1, 40, 0),
+            ]
+        );
+        let deeper = rollup_directories(&file_stats, 3);
+        assert_eq!(
+            shape(&deeper[0].children[0].children),
+            [("src/core/deep".to_string(), 1, 20, 0)]
+        );
+
+        let rendered = render_dir_rollup(&nested);
+        assert_eq!(
+            rendered,
+            [
+                "By directory:",
+                "  Directory  Files     Lines   Share  Largest file",
+                "  src/           4       130   65.0%  src/core/Vault.sol (60 lines)",
+                "    core/        2        80   40.0%  src/core/Vault.sol (60 lines)",
+                "    utils/       1        40   20.0%  src/utils/Math.sol (40 lines)",
+                "  lib/           1        40   20.0%  lib/Lib.sol (40 lines)",
+                "  ./             1        30   15.0%  Root.sol (30 lines)",
+            ]
+        );
+        let json = json::Object::new()
+            .objects("directories", &dir_rollup_json(&top[1..]))
+            .render();
+        assert_eq!(
+            json,
+            r#"{"directories":[{"dir":"lib","files":1,"lines":40,"share":20.0,"largest":"lib/Lib.sol","largest_lines":40,"children":[]},{"dir":".","files":1,"lines":30,"share":15.0,"largest":"Root.sol","largest_lines":30,"children":[]}]}"#
+        );
+        assert!(rollup_directories(&[], 2).is_empty());
+
+        let argv = ["solscrape", "x", "--stats-depth", "0"];
+        assert_eq!(
+            parse_args_from(argv.iter().map(|s| s.to_string()).collect()).unwrap_err(),
+            "Invalid --stats-depth: \"0\" (expected a positive whole number)"
+        );
+
+        let root = fixture_dir("stats_rollup");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("Root.sol", "contract R {}"),
+                ("src/a/b/A.sol", "contract A {}\ncontract B {}"),
+            ],
+        );
+        let scrape = |args: &Args| {
+            scrape_directory(
+                &root.join("source"),
+                root.join("out").to_str().unwrap(),
+                "rollup",
+                args,
+                &Logger::new(),
+            )
+            .unwrap()
+        };
+        assert!(scrape(&Args::default()).directories.is_empty());
+        let args = Args {
+            stats: true,
+            stats_depth: Some(3),
+            ..Args::default()
+        };
+        let result = scrape(&args);
+        assert_eq!(result.directories.len(), 2);
+        assert_eq!(result.directories[0].children[0].children[0].dir, "src/a/b");
+        assert!(render_json_result("s", &result).contains(r#""directories":[{"dir":"src","#));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the lazy walker and the collecting discovery find the same files.
+    #[test]
+    fn test_walker_matches_collecting_discovery() {
+        let root = fixture_dir("walker");
+        write_tree(
+            &root,
+            &[
+                ("src/A.sol", "contract A {}"),
+                ("src/deep/er/B.sol", "contract B {}"),
+                ("src/notes.txt", "not solidity"),
+                ("lib/dep/C.sol", "contract C {}"),
+                ("node_modules/pkg/D.sol", "contract D {}"),
+                ("test/T.sol", "contract T {}"),
+                ("Root.sol", "contract R {}"),
+            ],
+        );
+        let policy = exclusion_policy(&project::ProjectKind::default(), &Args::default());
+
+        let collected = find_solidity_files(&root, &policy).unwrap();
+        let mut walked: Vec<PathBuf> = SolFileWalker::new(&root, &policy)
+            .collect::<io::Result<_>>()
+            .unwrap();
+        walked.sort();
+
+        assert_eq!(walked, collected);
+        assert_eq!(collected.len(), 3);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that streaming mode writes the same content set as sorted mode.
+    #[test]
+    fn test_stream_mode_output_matches_sorted() {
+        let root = fixture_dir("stream_mode");
+        let files = [
+            ("src/A.sol", "contract A {} // a"),
+            ("src/b/B.sol", "contract B {}"),
+            ("src/Empty.sol", "// nothing"),
+        ];
+        write_tree(&root.join("source"), &files);
+        let out = root.join("out");
+        let log = Logger::new();
+
+        let sorted = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "sorted",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        let stream_args = Args {
+            stream: true,
+            ..Args::default()
+        };
+        let streamed = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "streamed",
+            &stream_args,
+            &log,
+        )
+        .unwrap();
+
+        assert_eq!(streamed.line_count, sorted.line_count);
+        let mut sorted_sections =
+            split::parse_sections(&fs::read_to_string(&sorted.output_path).unwrap()).unwrap();
+        let mut streamed_sections =
+            split::parse_sections(&fs::read_to_string(&streamed.output_path).unwrap()).unwrap();
+        sorted_sections.sort_by(|a, b| a.path.cmp(&b.path));
+        streamed_sections.sort_by(|a, b| a.path.cmp(&b.path));
+        let contents = |s: &[split::Section]| {
+            s.iter()
+                .map(|s| (s.path.clone(), s.content.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(contents(&streamed_sections), contents(&sorted_sections));
+        assert_eq!(streamed.file_count, 2);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies which clone failures are retried, that `--retries` takes a
+    /// whole number, and that `--timeout` takes a duration.
+    #[test]
+    fn test_is_transient_clone_failure() {
+        for stderr in [
+            "fatal: unable to access 'https://github.com/a/b/': Could not resolve host: github.com",
+            "error: RPC failed; curl 56 GnuTLS recv error (-54)\nfatal: early EOF",
+            "fatal: unable to access 'https://github.com/a/b/': The requested URL returned error: 502",
+            "remote: API rate limit exceeded",
+            " 42% (42/100)\r",
+            "",
+        ] {
+            assert!(is_transient_clone_failure(stderr), "{stderr}");
+        }
+        for stderr in [
+            "remote: Repository not found.\nfatal: repository 'https://github.com/a/b/' not found",
+            "fatal: repository '/tmp/missing' does not exist",
+            "fatal: Authentication failed for 'https://github.com/a/b/'",
+            "git@github.com: Permission denied (publickey).\nfatal: Could not read from remote repository.",
+            "fatal: Remote branch v9 not found in upstream origin",
+            "fatal: destination path 'x' already exists and is not an empty directory.",
+        ] {
+            assert!(!is_transient_clone_failure(stderr), "{stderr}");
+        }
+
+        let parse = |list: &[&str]| parse_args_from(list.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            parse(&["solscrape", "u", "--retries", "0"])
+                .unwrap()
+                .retries,
+            Some(0)
+        );
+        assert!(parse(&["solscrape", "u", "--retries", "-1"]).is_err());
+        assert_eq!(
+            parse(&["solscrape", "u", "--timeout", "2m"])
+                .unwrap()
+                .clone_timeout,
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(
+            parse(&["solscrape", "u", "--timeout", "0"]).unwrap_err(),
+            "--timeout must be at least 1s"
+        );
+    }
+
+    /// Verifies that progress redraws collapse to the final error lines.
+    #[test]
+    fn test_summarize_git_stderr_drops_progress() {
+        let raw = "Cloning into '/tmp/solscrape_1/repo'...\n\
+                   remote: Enumerating objects: 120, done.\n\
+                   remote: Counting objects:  50% (60/120)\rremote: Counting objects: 100% (120/120), done.\n\
+                   Receiving objects:  12% (15/120)\rReceiving objects:  99% (119/120)\r\
+                   error: RPC failed; curl 18 transfer closed\n\
+                   fatal: early EOF\n\
+                   fatal: fetch-pack: invalid index-pack output\n";
+
+        assert_eq!(
+            summarize_git_stderr(raw),
+            "error: RPC failed; curl 18 transfer closed\n  \
+             fatal: early EOF\n  \
+             fatal: fetch-pack: invalid index-pack output"
+        );
+    }
+
+    /// Verifies ANSI escapes are stripped and only the last lines are kept.
+    #[test]
+    fn test_summarize_git_stderr_strips_ansi_and_limits_lines() {
+        let raw = "\x1b[31mfatal:\x1b[0m repository 'https://x/y' not found\r\n";
+        assert_eq!(
+            summarize_git_stderr(raw),
+            "fatal: repository 'https://x/y' not found"
+        );
+
+        let many: String = (1..=8).map(|i| format!("hint: line {}\n", i)).collect();
+        let summary = summarize_git_stderr(&many);
+        assert_eq!(summary.lines().count(), GIT_STDERR_TAIL_LINES);
+        assert!(summary.starts_with("hint: line 4"));
+        assert!(summary.ends_with("hint: line 8"));
+
+        assert_eq!(summarize_git_stderr(" 73% (73/100)\r\n\n"), "");
+    }
+
+    /// Verifies that Foundry test and script files are skipped unless included.
+    #[test]
+    fn test_foundry_suffix_filtering() {
+        let root = fixture_dir("foundry_suffixes");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("foundry.toml", "[profile.default]"),
+                ("src/Vault.sol", "contract Vault {}"),
+                ("src/Vault.t.sol", "contract VaultTest {}"),
+                ("src/Deploy.s.sol", "contract Deploy {}"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "default",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert!(result.project.is_foundry());
+        assert_eq!(result.files_processed, vec!["src/Vault.sol".to_string()]);
+
+        let include_args = Args {
+            include_test: true,
+            include_script: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "included",
+            &include_args,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(result.file_count, 3);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that a repository with one non-empty file is reported as a
+    /// copy of it, in both header modes.
+    #[test]
+    fn test_single_file_output() {
+        let root = fixture_dir("single_file");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("src/Token.sol", "// Token\ncontract Token {}\n"),
+                ("src/Empty.sol", "// nothing but a comment\n"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "headers",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert_eq!(result.single_source.as_deref(), Some("src/Token.sol"));
+        assert_eq!(
+            render_single_file_note(&result, &Args::default()).unwrap(),
+            "1 file — output is a cleaned copy of src/Token.sol with a header"
+        );
+        let written = fs::read_to_string(&result.output_path).unwrap();
+        assert!(written.starts_with(HEADER_SEPARATOR));
+
+        let bare = Args {
+            no_headers: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "bare",
+            &bare,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(
+            render_single_file_note(&result, &bare).unwrap(),
+            "1 file — output is a cleaned copy of src/Token.sol"
+        );
+        let written = fs::read_to_string(&result.output_path).unwrap();
+        assert_eq!(written.trim_end(), "contract Token {}");
+
+        // What --inject-pragma and --with-context add is named too
+        write_tree(&root.join("source"), &[("README.md", "# Token\n")]);
+        let extended = Args {
+            inject_pragma: Some("^0.8.20".to_string()),
+            with_context: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "extended",
+            &extended,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(
+            render_single_file_note(&result, &extended).unwrap(),
+            "1 file — output is a cleaned copy of src/Token.sol with a header, an \
+             injected pragma, and the project context appended"
+        );
+        let no_headers = Args {
+            no_headers: true,
+            ..extended
+        };
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "extended_bare",
+            &no_headers,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(
+            render_single_file_note(&result, &no_headers).unwrap(),
+            "1 file — output is a cleaned copy of src/Token.sol with an injected pragma \
+             and the project context appended"
+        );
+
+        write_tree(
+            &root.join("source"),
+            &[("src/Vault.sol", "contract Vault {}")],
+        );
+        let result = scrape_directory(
+            &root.join("source"),
+            out.to_str().unwrap(),
+            "two",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert_eq!(result.single_source, None);
+        assert_eq!(render_single_file_note(&result, &Args::default()), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Pins the `--explain` rule attribution strings external tools rely on.
+    #[test]
+    fn test_explain_decisions() {
+        let root = fixture_dir("explain");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("foundry.toml", "[profile.default]"),
+                ("src/Vault.sol", "contract Vault {}"),
+                ("src/Empty.sol", "// nothing left"),
+                ("src/Old.sol", "// solscrape: ignore\ncontract Old {}"),
+                ("src/Legacy.sol", "pragma solidity 0.4.24;\ncontract L {}"),
+                ("src/Fuzz.t.sol", "contract Fuzz {}"),
+                ("lib/oz/Token.sol", "contract Token {}"),
+                ("test/Kept.sol", "// solscrape: include\ncontract Kept {}"),
+                ("test/Other.sol", "contract Other {}"),
+                ("node_modules/x/A.sol", "contract A {}"),
+                ("README.md", "not solidity"),
+            ],
+        );
+        let args = Args {
+            explain: true,
+            include_lib: true,
+            min_solidity: Some("0.8".to_string()),
+            ..Args::default()
+        };
+        let out = root.join("out");
+        let result =
+            scrape_directory(&source, out.to_str().unwrap(), "x", &args, &Logger::new()).unwrap();
+
+        let decisions: Vec<(&str, bool, &str)> = result
+            .decisions
+            .iter()
+            .map(|d| (d.path.as_str(), d.included, d.rule.as_str()))
+            .collect();
+        assert_eq!(
+            decisions,
+            [
+                ("lib/oz/Token.sol", true, "flag:include-lib"),
+                ("node_modules/x/A.sol", false, "always-dir:node_modules"),
+                ("src/Empty.sol", false, "empty-after-clean"),
+                ("src/Fuzz.t.sol", false, "suffix:*.t.sol"),
+                ("src/Legacy.sol", false, "pragma"),
+                ("src/Old.sol", false, "opt-out-marker"),
+                ("src/Vault.sol", true, "default"),
+                ("test/Kept.sol", true, "include-marker"),
+                ("test/Other.sol", false, "default-dir:test"),
+            ]
+        );
+        assert!(render_json_result("s", &result).ends_with(
+            r#""decisions":[{"path":"lib/oz/Token.sol","status":"included","rule":"flag:include-lib"},{"path":"node_modules/x/A.sol","status":"excluded","rule":"always-dir:node_modules"},{"path":"src/Empty.sol","status":"excluded","rule":"empty-after-clean"},{"path":"src/Fuzz.t.sol","status":"excluded","rule":"suffix:*.t.sol"},{"path":"src/Legacy.sol","status":"excluded","rule":"pragma"},{"path":"src/Old.sol","status":"excluded","rule":"opt-out-marker"},{"path":"src/Vault.sol","status":"included","rule":"default"},{"path":"test/Kept.sol","status":"included","rule":"include-marker"},{"path":"test/Other.sol","status":"excluded","rule":"default-dir:test"}]}"#
+        ));
+
+        let parse = |extra: &[&str]| {
+            let mut list = vec!["solscrape", "./x", "--local"];
+            list.extend_from_slice(extra);
+            parse_args_from(list.into_iter().map(String::from).collect())
+        };
+        assert!(
+            parse(&["--explain", "--output-mode", "json"])
+                .unwrap()
+                .explain
+        );
+        assert_eq!(
+            parse(&["--explain"]).unwrap_err(),
+            "--explain can only be used with --output-mode json"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies partial output and the trailer once `--deadline` has passed.
+    #[test]
+    fn test_deadline_partial_output() {
+        let root = fixture_dir("deadline");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("src/A.sol", "contract A {}"),
+                ("src/B.sol", "contract B {}"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+
+        for stream in [false, true] {
+            let args = Args {
+                stream,
+                deadline: Some(Instant::now()),
+                ..Args::default()
+            };
+            let result =
+                scrape_directory(&source, out.to_str().unwrap(), "late", &args, &log).unwrap();
+            assert_eq!(result.truncated, Some((0, 2)), "stream: {}", stream);
+            assert_eq!(
+                fs::read_to_string(&result.output_path).unwrap(),
+                "// TRUNCATED: deadline reached after 0 of 2 files\n"
+            );
+            assert!(render_json_result("s", &result).contains(r#""partial":true,"#));
+        }
+
+        let args = Args {
+            deadline: Some(Instant::now() + Duration::from_secs(3_600)),
+            ..Args::default()
+        };
+        let result =
+            scrape_directory(&source, out.to_str().unwrap(), "on_time", &args, &log).unwrap();
+        assert_eq!(result.truncated, None);
+        assert!(time_left(&args, "cloning").unwrap().is_some());
+        assert_eq!(time_left(&Args::default(), "cloning"), Ok(None));
+        let late = Args {
+            deadline: Some(Instant::now()),
+            ..Args::default()
+        };
+        assert_eq!(
+            time_left(&late, "cloning"),
+            Err("Deadline reached before cloning".to_string())
+        );
+
+        // The trailer never ends up in a split section
+        let partial = format!(
+            "{}\n// File: src/A.sol\n{}\ncontract A {{}}\n\n{}\n",
+            HEADER_SEPARATOR,
+            HEADER_SEPARATOR,
+            render_truncated_trailer(1, 2)
+        );
+        let sections = split::parse_sections(&partial).unwrap();
+        assert_eq!(sections[0].content, "contract A {}\n");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies per-file markers in sorted and streaming scrapes.
+    #[test]
+    fn test_file_markers() {
+        let root = fixture_dir("file_markers");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("foundry.toml", "[profile.default]"),
+                ("src/Vault.sol", "contract Vault {}"),
+                (
+                    "src/Old.sol",
+                    "// solscrape: ignore (deprecated)\ncontract Old {}",
+                ),
+                (
+                    "test/Important.sol",
+                    "// solscrape: include\ncontract Important {}",
+                ),
+                ("test/Other.sol", "contract Other {}"),
+                ("src/Fuzz.t.sol", "// solscrape: include\ncontract Fuzz {}"),
+                (
+                    "node_modules/x/Forced.sol",
+                    "// solscrape: include\ncontract F {}",
+                ),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+
+        for stream in [false, true] {
+            let args = Args {
+                stream,
+                ..Args::default()
+            };
+            let result =
+                scrape_directory(&source, out.to_str().unwrap(), "marked", &args, &log).unwrap();
+            let mut files = result.files_processed.clone();
+            files.sort();
+            assert_eq!(
+                files,
+                ["src/Fuzz.t.sol", "src/Vault.sol", "test/Important.sol"],
+                "stream: {}",
+                stream
+            );
+        }
+
+        let args = Args {
+            no_clean: true,
+            ..Args::default()
+        };
+        let outcome = process_file(&source.join("src/Old.sol"), &source, &args, &log).unwrap();
+        assert!(
+            matches!(&outcome, FileOutcome::Skipped(skip) if skip.detail.contains("opt-out marker")),
+            "{:?}",
+            outcome
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that --force-include files are scraped in sorted position,
+    /// including inside always-excluded directories, and bad paths fail.
+    #[test]
+    fn test_force_include() {
+        let root = fixture_dir("force_include");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("foundry.toml", "[profile.default]"),
+                ("src/Vault.sol", "contract Vault {}"),
+                (
+                    "lib/solmate/src/utils/FixedPointMathLib.sol",
+                    "library FixedPointMathLib {}",
+                ),
+                ("lib/solmate/src/tokens/ERC20.sol", "contract ERC20 {}"),
+                ("node_modules/pkg/Vendored.sol", "contract Vendored {}"),
+                ("test/Vault.t.sol", "contract VaultTest {}"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+        let forced: Vec<String> = [
+            "node_modules/pkg/Vendored.sol",
+            "./lib/solmate/src/utils/FixedPointMathLib.sol",
+            "src/Vault.sol",
+        ]
+        .iter()
+        .map(|path| source_relative_path("--force-include", path).unwrap())
+        .collect();
+
+        for stream in [false, true] {
+            let args = Args {
+                stream,
+                force_include: forced.clone(),
+                ..Args::default()
+            };
+            let result =
+                scrape_directory(&source, out.to_str().unwrap(), "forced", &args, &log).unwrap();
+            let mut files = result.files_processed.clone();
+            if !stream {
+                assert!(files.is_sorted(), "{:?}", files);
+            }
+            files.sort();
+            assert_eq!(
+                files,
+                [
+                    "lib/solmate/src/utils/FixedPointMathLib.sol",
+                    "node_modules/pkg/Vendored.sol",
+                    "src/Vault.sol",
+                ],
+                "stream: {}",
+                stream
+            );
+            let output = fs::read_to_string(&result.output_path).unwrap();
+            assert!(output.contains(&format!(
+                "{}lib/solmate/src/utils/FixedPointMathLib.sol",
+                HEADER_FILE_PREFIX
+            )));
+            assert_eq!(result.file_stats.len(), 3);
+        }
+
+        let args = Args {
+            force_include: vec!["lib/solmate/src/utils/Missing.sol".to_string()],
+            ..Args::default()
+        };
+        let error = scrape_directory(&source, out.to_str().unwrap(), "forced", &args, &log)
+            .err()
+            .unwrap()
+            .message;
+        assert_eq!(
+            error,
+            "--force-include lib/solmate/src/utils/Missing.sol does not exist \
+             (closest existing directory: lib/solmate/src/utils)"
+        );
+        let args = Args {
+            force_include: vec!["vendor/x/A.sol".to_string()],
+            ..Args::default()
+        };
+        let error = list_directory(&source, &args, &log).unwrap_err();
+        assert!(
+            error.ends_with("(closest existing directory: the source root)"),
+            "{}",
+            error
+        );
+        let args = Args {
+            force_include: vec!["lib/solmate".to_string()],
+            ..Args::default()
+        };
+        assert_eq!(
+            list_directory(&source, &args, &log).unwrap_err(),
+            "--force-include lib/solmate is not a .sol file"
+        );
+
+        assert_eq!(
+            source_relative_path("--force-include", "lib//a/./B.sol").unwrap(),
+            "lib/a/B.sol"
+        );
+        for bad in ["", ".", "/etc/A.sol", "lib/../../A.sol"] {
+            assert!(
+                source_relative_path("--force-include", bad)
+                    .unwrap_err()
+                    .starts_with("Invalid --force-include path"),
+                "{:?}",
+                bad
+            );
+        }
+        let argv = [
+            "solscrape",
+            "url",
+            "--force-include",
+            "lib/a/A.sol",
+            "--force-include",
+            "lib/b/B.sol",
+        ];
+        let args = parse_args_from(argv.iter().map(|s| s.to_string()).collect()).unwrap();
+        assert_eq!(args.force_include, ["lib/a/A.sol", "lib/b/B.sol"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies every entry of the flag combination table, and that the
+    /// table is exhaustively covered here.
+    #[test]
+    fn test_flag_combinations() {
+        let parse = |list: &[&str]| {
+            let mut all = vec!["solscrape".to_string()];
+            all.extend(list.iter().map(|s| s.to_string()));
+            check_flag_combinations(&parse_args_from(all).unwrap())
+        };
+        let cases: [(&[&str], Severity, &str); 45] = [
+            (
+                &["x", "--quiet", "--verbose"],
+                Severity::Error,
+                "--quiet cannot be used with --verbose",
+            ),
+            (
+                &["https://github.com/a/b", "--local"],
+                Severity::Warning,
+                "the source looks like a URL, but --local reads it as a directory path",
+            ),
+            (
+                &["x", "--keep-license-headers", "--no-clean"],
+                Severity::Warning,
+                "--keep-license-headers has no effect with --no-clean, \
+                 which copies files verbatim, license headers included",
+            ),
+            (
+                &["x", "--stats", "--quiet"],
+                Severity::Warning,
+                "--stats has no effect with --quiet, which prints no report",
+            ),
+            (
+                &["x", "--verbose", "--list-files"],
+                Severity::Warning,
+                "--verbose has no effect with --list-files, which prints only paths",
+            ),
+            (
+                &["./project", "--local", "--precheck"],
+                Severity::Warning,
+                "--precheck has no effect on a local directory, which is not cloned",
+            ),
+            (
+                &["x", "--yes", "--no-precheck"],
+                Severity::Warning,
+                "--yes has no effect with --no-precheck, which asks nothing",
+            ),
+            (
+                &["x", "--offline"],
+                Severity::Warning,
+                "--offline and --foundry-cache have no effect without --include-lib \
+                 on a repository that is cloned",
+            ),
+            (
+                &["x", "--context-readme-limit", "4K"],
+                Severity::Warning,
+                "--context-readme-limit has no effect without --with-context",
+            ),
+            (
+                &["x", "--trust-clone"],
+                Severity::Warning,
+                "--trust-clone has no effect without --reuse-clone",
+            ),
+            (
+                &["x", "--allow-dirty"],
+                Severity::Warning,
+                "--allow-dirty has no effect without --reuse-clone",
+            ),
+            (
+                &["./project", "--local", "--max-clone-size", "1G"],
+                Severity::Warning,
+                "--max-clone-size has no effect on a local directory, which is not cloned",
+            ),
+            (
+                &["./project", "--local", "--branch", "dev"],
+                Severity::Warning,
+                "--branch has no effect on a local directory, which is not cloned",
+            ),
+            (
+                &["x", "--no-headers", "--no-headers-for", "lib"],
+                Severity::Warning,
+                "--no-headers-for has no effect with --no-headers, which omits every header",
+            ),
+            (
+                &["x", "--stats-depth", "3"],
+                Severity::Warning,
+                "--stats-depth has no effect without --stats",
+            ),
+            (
+                &["./project", "--local", "--rev", "v1"],
+                Severity::Warning,
+                "--rev has no effect on a local directory, which is not cloned",
+            ),
+            (
+                &["./project", "--local", "--no-git"],
+                Severity::Warning,
+                "--no-git has no effect on a local directory, which is not cloned",
+            ),
+            (
+                &["x", "--compare", "a..b", "--stream"],
+                Severity::Error,
+                "--compare cannot be used with --list-files, --stream, or --no-headers",
+            ),
+            (
+                &["x", "--strip-revert-strings", "--no-clean"],
+                Severity::Error,
+                "--strip-revert-strings cannot be used with --no-clean",
+            ),
+            (
+                &["x", "--output-mode", "path", "--list-files"],
+                Severity::Error,
+                "--output-mode cannot be used with --list-files",
+            ),
+            (
+                &["x", "--output-mode", "json", "--compare", "a..b"],
+                Severity::Error,
+                "--output-mode json cannot be used with --compare",
+            ),
+            (
+                &["x", "--vcs-friendly", "--run-dir"],
+                Severity::Error,
+                "--vcs-friendly cannot be used with --stream, --run-dir, or --compare",
+            ),
+            (
+                &["x", "--since", "main", "--compare", "a..b"],
+                Severity::Error,
+                "--since cannot be used with --compare",
+            ),
+            (
+                &["x", "--reuse-clone", "y", "--compare", "a..b"],
+                Severity::Error,
+                "--reuse-clone cannot be used with --local or --compare",
+            ),
+            (
+                &["x", "--checksum-file", "--list-files"],
+                Severity::Error,
+                "--checksum-file cannot be used with --list-files or --compare",
+            ),
+            (
+                &["x", "--parts-file", "--no-headers"],
+                Severity::Error,
+                "--parts-file cannot be used with --no-headers, --list-files, or --compare",
+            ),
+            (
+                &["x", "--metrics-file", "m.txt", "--metrics-stdout"],
+                Severity::Error,
+                "--metrics-file cannot be used with --metrics-stdout",
+            ),
+            (
+                &["x", "--metrics-stdout", "--list-files"],
+                Severity::Error,
+                "--metrics-file and --metrics-stdout cannot be used with --list-files \
+                 or --compare",
+            ),
+            (
+                &["x", "--metrics-stdout", "--output-mode", "path"],
+                Severity::Error,
+                "--metrics-stdout cannot be used with --output-mode",
+            ),
+            (
+                &["x", "--split-by-dir", "--stream"],
+                Severity::Error,
+                "--split-by-dir cannot be used with --stream, --list-files, or --compare",
+            ),
+            (
+                &["x", "--split-by-dir", "--output-mode", "content"],
+                Severity::Error,
+                "--split-by-dir cannot be used with --output-mode path or content, \
+                 which print a single output",
+            ),
+            (
+                &["x", "out.sol", "--split-by-dir"],
+                Severity::Error,
+                "--split-by-dir cannot be used when the destination is a .sol file",
+            ),
+            (
+                &["--from-file", "s.txt", "--output-mode", "path"],
+                Severity::Error,
+                "--from-file cannot be used with --list-files or --output-mode",
+            ),
+            (
+                &["x", "--dedupe-content", "--stream"],
+                Severity::Error,
+                "--dedupe-content cannot be used with --stream, --list-files, or --compare",
+            ),
+            (
+                &["x", "--related", "src/A.sol", "--list-files"],
+                Severity::Error,
+                "--related cannot be used with --list-files or --compare",
+            ),
+            (
+                &["x", "--rev", "v1", "--branch", "main"],
+                Severity::Error,
+                "--rev cannot be used with --branch; give one or the other",
+            ),
+            (
+                &["x", "--rev", "v1", "--compare", "a..b"],
+                Severity::Error,
+                "--rev cannot be used with --compare, which names its own refs",
+            ),
+            (
+                &["x", "--no-git", "--since", "main"],
+                Severity::Error,
+                "--no-git cannot be used with --reuse-clone, --compare, --since, \
+                 or --precheck, which need git",
+            ),
+            (
+                &["x", "--prune-implemented-interfaces", "--stream"],
+                Severity::Error,
+                "--prune-implemented-interfaces cannot be used with --stream, --list-files, \
+                 or --compare",
+            ),
+            (
+                &["x", "--digest", "--list-files"],
+                Severity::Error,
+                "--digest cannot be used with --list-files, --output-mode, --compare, \
+                 or --from-file",
+            ),
+            (
+                &["x", "--max-line-width", "80", "--no-clean"],
+                Severity::Error,
+                "--max-line-width cannot be used with --no-clean",
+            ),
+            (
+                &["x", "--errors-report", "text", "--digest"],
+                Severity::Error,
+                "--errors-report cannot be used with --list-files, --compare, --digest, \
+                 or --split-by-dir",
+            ),
+            (
+                &["--source", "a", "--source", "b", "--stream"],
+                Severity::Error,
+                "Several --source cannot be used with --list-files, --digest, --compare, \
+                 --since, --related, --reuse-clone, --stream, --split-by-dir, \
+                 --with-context, --deadline, or --output-mode json",
+            ),
+            (
+                &[
+                    "--source",
+                    "a",
+                    "--source",
+                    "b",
+                    "--split-by-source",
+                    "--errors-report",
+                    "json",
+                ],
+                Severity::Error,
+                "--split-by-source cannot be used with --errors-report, --output-mode path \
+                 or content, or a .sol destination",
+            ),
+            (
+                &["x", "--gate", "no-skips", "--digest"],
+                Severity::Error,
+                "--gate cannot be used with --list-files, --compare, or --digest",
+            ),
+        ];
+        assert_eq!(cases.len(), FLAG_COMBINATIONS.len());
+        for ((argv, severity, message), entry) in cases.into_iter().zip(FLAG_COMBINATIONS) {
+            assert_eq!((severity, message), (entry.severity, entry.message));
+            let expected = match severity {
+                Severity::Error => Err(message.to_string()),
+                Severity::Warning => Ok(vec![message.to_string()]),
+            };
+            assert_eq!(parse(argv), expected, "{:?}", argv);
+        }
+
+        // Nearby combinations that are fine
+        for argv in [
+            &["x", "--quiet"][..],
+            &["file:///srv/repo.git", "--local"],
+            &["git@github.com:a/b.git"],
+            &["x", "--yes", "--precheck"],
+            &["x", "--precheck"],
+            &["--profile", "verify", "x"],
+            &["x", "--with-context", "--context-readme-limit", "4K"],
+            &["x", "--reuse-clone", "y", "--trust-clone", "--allow-dirty"],
+            &["x", "--reuse-clone", "y", "--branch", "rel"],
+            &["x", "--reuse-clone", "y", "--rev", "v1"],
+        ] {
+            assert_eq!(parse(argv), Ok(Vec::new()), "{:?}", argv);
+        }
+        let both = parse(&["x", "--stats", "--quiet", "--yes", "--no-precheck"]).unwrap();
+        assert_eq!(both.len(), 2);
+    }
+
+    /// Verifies when an existing source switches to --local.
+    #[test]
+    fn test_autodetect_local() {
+        let root = fixture_dir("autodetect");
+        write_tree(&root, &[("contracts/A.sol", "contract A {}")]);
+        let parse = |list: &[&str]| {
+            let all = std::iter::once("solscrape").chain(list.iter().copied());
+            let mut args = parse_args_from(all.map(str::to_string).collect()).unwrap();
+            let note = autodetect_local(&mut args);
+            (args.is_local, note)
+        };
+        let dir = root.join("contracts");
+        let dir = dir.to_str().unwrap();
+        let file = root.join("contracts/A.sol");
+
+        let (local, note) = parse(&[dir]);
+        assert!(local);
+        assert!(note.unwrap().contains("--no-autodetect"));
+        assert!(parse(&[file.to_str().unwrap()]).0);
+
+        // URLs, explicit choices, and missing paths are left alone
+        for argv in [
+            &["https://github.com/acme/vault.git"][..],
+            &[dir, "--no-autodetect"],
+            &[root.join("missing").to_str().unwrap()],
+        ] {
+            assert_eq!(parse(argv), (false, None), "{:?}", argv);
+        }
+        assert_eq!(parse(&[dir, "--local"]), (true, None));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that an existing non-.sol file is rejected as a destination.
+    #[test]
+    fn test_destination_existing_file_rejected() {
+        let root = fixture_dir("dest_file");
+        let notes = root.join("notes.txt");
+        fs::write(&notes, "keep me").unwrap();
+        let notes = notes.to_str().unwrap();
+
+        assert_eq!(
+            check_destination(notes),
+            Err(format!(
+                "Destination must be a directory, but {} is a file",
+                notes
+            ))
+        );
+        assert_eq!(check_destination(root.to_str().unwrap()), Ok(()));
+        assert_eq!(fs::read_to_string(notes).unwrap(), "keep me");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that a .sol destination is used as the exact output path.
+    #[test]
+    fn test_destination_sol_file_is_output_path() {
+        let root = fixture_dir("dest_sol");
+        write_tree(&root.join("source"), &[("src/A.sol", "contract A {}")]);
+        let target = root.join("audits").join("vault.sol");
+        let target_str = target.to_str().unwrap();
+        assert_eq!(check_destination(target_str), Ok(()));
+
+        let result = scrape_directory(
+            &root.join("source"),
+            target_str,
+            "ignored",
+            &Args::default(),
+            &Logger::new(),
+        )
+        .unwrap();
+
+        assert_eq!(result.output_path, target);
+        assert!(target.is_file());
+        assert_eq!(
+            explicit_output_file("vault.sol"),
+            Some((PathBuf::from("."), "vault.sol".to_string()))
+        );
+
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let err = parse_args_from(argv(&["solscrape", "url", "out.sol", "-o", "x"])).unwrap_err();
+        assert!(err.starts_with("--output cannot be used"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies how --output resolves: bare names join the destination, a
+    /// `.sol` name is the exact file, and a path (absolute, relative, or
+    /// under `~`) replaces the destination and conflicts with a destination
+    /// argument.
+    #[test]
+    fn test_resolve_output() {
+        let home = Some("/home/a".to_string());
+        let resolve = |given: Option<&str>, output: &str| {
+            resolve_output(given.unwrap_or("."), given, output, home.clone())
+        };
+        let ok = |dir: &str, name: Option<&str>| Ok((dir.to_string(), name.map(str::to_string)));
+        assert_eq!(resolve(None, "vault"), ok(".", Some("vault")));
+        assert_eq!(resolve(Some("out"), "vault"), ok("out", Some("vault")));
+        assert_eq!(resolve(Some("out"), "vault.sol"), ok("out/vault.sol", None));
+        assert_eq!(resolve(None, ".sol"), ok(".", Some(".sol")));
+        assert_eq!(
+            resolve(None, "/abs/path/name"),
+            ok("/abs/path", Some("name"))
+        );
+        assert_eq!(
+            resolve(None, "/abs/path/v3.sol"),
+            ok("/abs/path/v3.sol", None)
+        );
+        assert_eq!(resolve(None, "audits/vault"), ok("audits", Some("vault")));
+        assert_eq!(resolve(None, "./vault"), ok(".", Some("vault")));
+        assert_eq!(
+            resolve(None, "~/audits/vault"),
+            ok("/home/a/audits", Some("vault"))
+        );
+        assert_eq!(resolve(None, "~/v3.sol"), ok("/home/a/v3.sol", None));
+        assert_eq!(resolve(None, "~bob/vault"), ok("~bob", Some("vault")));
+        assert_eq!(
+            resolve(Some("./out"), "/abs/path/name").unwrap_err(),
+            "--output /abs/path/name is a path, so it cannot be used with the destination \
+             ./out; give a destination and a bare --output name, or --output alone"
+        );
+        for output in ["~", "audits/", "audits/.."] {
+            assert_eq!(
+                resolve(None, output).unwrap_err(),
+                format!(
+                    "--output {} names a directory; give it as the destination instead",
+                    output
+                )
+            );
+        }
+        assert!(resolve(Some("out.sol"), "vault").is_err());
+
+        // Parsing resolves it, whether the destination is an argument or
+        // configured
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let args = parse_args_from(argv(&["solscrape", "url", "-o", "/abs/v3.sol"])).unwrap();
+        assert_eq!(
+            (args.destination.as_str(), args.output_name),
+            ("/abs/v3.sol", None)
+        );
+        let configured = Args {
+            destination: "configured".to_string(),
+            ..Args::default()
+        };
+        let args = parse_args_onto(configured, argv(&["solscrape", "url", "-o", "/abs/v3"]));
+        let args = args.unwrap();
+        assert_eq!(args.destination, "/abs");
+        assert_eq!(args.output_name.as_deref(), Some("v3"));
+        assert!(
+            parse_args_from(argv(&["solscrape", "url", "out", "-o", "/abs/v3"]))
+                .unwrap_err()
+                .starts_with("--output /abs/v3 is a path")
+        );
+        assert!(
+            parse_args_from(argv(&["solscrape", "--from-file", "s.txt", "-o", "a/b"]))
+                .unwrap_err()
+                .starts_with("With --from-file, --output is a name")
+        );
+    }
+
+    /// Verifies reserved Windows names are prefixed in output paths.
+    #[test]
+    fn test_prepare_output_reserved_names() {
+        let root = fixture_dir("reserved_names");
+        let log = Logger::new();
+        let dest = root.to_str().unwrap();
+
+        let (path, _) = prepare_output(dest, "con", &Args::default(), &log).unwrap();
+        assert_eq!(path, root.join("_con_scraped.sol"));
+        let (path, _) = prepare_output(dest, "console", &Args::default(), &log).unwrap();
+        assert_eq!(path, root.join("console_scraped.sol"));
+
+        let explicit = root.join("NUL.sol");
+        let (path, _) = prepare_output(
+            explicit.to_str().unwrap(),
+            "ignored",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert_eq!(path, root.join("_NUL.sol"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies over-long output names are shortened to a creatable file.
+    #[test]
+    fn test_prepare_output_long_names() {
+        let root = fixture_dir("long_names");
+        let log = Logger::new();
+        let long = "package".repeat(50);
+
+        let (path, _) =
+            prepare_output(root.to_str().unwrap(), &long, &Args::default(), &log).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.len() <= naming::MAX_FILE_NAME_BYTES);
+        assert!(name.ends_with("_scraped.sol"));
+        output::write_atomic(&path, b"contract A {}").unwrap();
+        // The sidecars next to it have room too
+        let (sidecar, _) = checksum::write_sidecar(&path).unwrap();
+        assert!(sidecar.file_name().unwrap().len() <= 255);
+        parts::write_sidecar(&path, &[]).unwrap();
+        for format in [error_report::Format::Text, error_report::Format::Json] {
+            fs::write(format.sidecar_path(&path), "").unwrap();
+        }
+
+        let explicit = root.join(format!("{}.sol", long));
+        let (path, _) = prepare_output(
+            explicit.to_str().unwrap(),
+            "ignored",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.len() <= naming::MAX_FILE_NAME_BYTES);
+        assert!(name.ends_with(".sol") && !name.ends_with("_scraped.sol"));
+        output::write_atomic(&path, b"contract A {}").unwrap();
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies temp base precedence: --temp-dir, then SOLSCRAPE_TMPDIR, then default.
+    #[test]
+    fn test_temp_base_dir_precedence() {
+        let explicit = Path::new("/mnt/big");
+        assert_eq!(
+            tempfile::base_dir(Some(explicit), Some("/env".into())),
+            explicit
+        );
+        assert_eq!(
+            tempfile::base_dir(None, Some("/env".into())),
+            PathBuf::from("/env")
+        );
+        assert_eq!(tempfile::base_dir(None, Some("".into())), env::temp_dir());
+        assert_eq!(tempfile::base_dir(None, None), env::temp_dir());
+
+        let base = fixture_dir("temp_base");
+        let temp = tempfile::tempdir_in(&base.join("nested")).unwrap();
+        assert!(temp.path().starts_with(base.join("nested")));
+        drop(temp);
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    /// Verifies that only unlocked, old enough `solscrape_*` directories are removed.
+    #[test]
+    fn test_remove_orphaned_temp_dirs() {
+        let base = fixture_dir("cleanup_temp");
+        write_tree(
+            &base,
+            &[
+                ("solscrape_1/repo/A.sol", "contract A {}"),
+                ("solscrape_2/B.sol", "contract B {}"),
+                ("solscrape_2.lock", "4294967295"),
+                ("unrelated/C.sol", "contract C {}"),
+            ],
+        );
+        let live = tempfile::tempdir_in(&base).unwrap();
+
+        let report = tempfile::remove_orphans(&base, DEFAULT_CLEANUP_AGE);
+        assert!(report.removed.is_empty(), "{:?}", report);
+
+        if Path::new("/proc/self").exists() {
+            let report = tempfile::remove_dead(&base);
+            assert_eq!(report.removed, vec![base.join("solscrape_2")]);
+            write_tree(
+                &base,
+                &[
+                    ("solscrape_2/B.sol", "contract B {}"),
+                    ("solscrape_2.lock", "4294967295"),
+                ],
+            );
+        }
+
+        let report = tempfile::remove_orphans(&base, Duration::ZERO);
+        assert_eq!(
+            report.removed,
+            vec![base.join("solscrape_1"), base.join("solscrape_2")]
+        );
+        assert_eq!(report.bytes_freed, 26);
+        assert_eq!(report.in_use, vec![live.path().to_path_buf()]);
+        assert!(!base.join("solscrape_2.lock").exists());
+        assert!(base.join("unrelated").exists() && live.path().exists());
+
+        let lock = PathBuf::from(format!("{}.lock", live.path().display()));
+        assert!(lock.exists());
+        drop(live);
+        assert!(!lock.exists());
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    /// Verifies `cleanup-temp` argument parsing.
+    #[test]
+    fn test_parse_cleanup_temp() {
+        let parse = |list: &[&str]| {
+            let mut all = vec!["solscrape".to_string()];
+            all.extend(list.iter().map(|s| s.to_string()));
+            parse_args_from(all)
+        };
+        let min_age = |list: &[&str]| match parse(list).unwrap().subcommand {
+            Some(Subcommand::CleanupTemp { min_age }) => min_age,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(min_age(&["cleanup-temp"]), DEFAULT_CLEANUP_AGE);
+        assert_eq!(min_age(&["cleanup-temp", "--all"]), Duration::ZERO);
+        assert_eq!(
+            min_age(&["cleanup-temp", "--older-than", "30m"]),
+            Duration::from_secs(1_800)
+        );
+        assert!(parse(&["cleanup-temp", "--all", "--older-than", "1h"]).is_err());
+        assert!(parse(&["cleanup-temp", "extra"]).is_err());
+        assert!(parse(&["https://x/y.git", "--all"]).is_err());
+    }
+
+    /// Verifies that configured settings are defaults the command line
+    /// overrides, and `config path` parsing.
+    #[test]
+    fn test_parse_args_with_settings() {
+        let mut settings = config::Settings::default();
+        let project = config::Origin::File(PathBuf::from("/p/solscrape.toml"));
+        for (key, value) in [
+            ("destination", config::Value::Text("./scrapes".to_string())),
+            ("quiet", config::Value::Bool(true)),
+            ("headers", config::Value::Text("none".to_string())),
+            ("profile", config::Value::Text("audit".to_string())),
+        ] {
+            let origin = project.clone();
+            settings
+                .values
+                .insert(key, config::Setting { value, origin });
+        }
+        let parse = |settings: &config::Settings, list: &[&str]| {
+            let mut all = vec!["solscrape".to_string()];
+            all.extend(list.iter().map(|s| s.to_string()));
+            parse_args_with(settings, all)
+        };
+
+        let args = parse(&settings, &["./repo", "--local"]).unwrap();
+        assert_eq!(args.destination, "./scrapes");
+        assert!(args.quiet && args.no_headers && args.keep_license_headers);
+        let args = parse(&settings, &["./repo", "./here", "--local", "--verbose"]).unwrap();
+        assert_eq!(args.destination, "./here");
+        assert!(!args.quiet && args.verbose);
+        let args = parse(&settings, &["./repo", "--profile", "llm"]).unwrap();
+        assert!(!args.keep_license_headers && args.rewrap_long_lines);
+
+        settings.values.get_mut("profile").unwrap().value = config::Value::Text("fast".to_string());
+        assert_eq!(
+            parse(&settings, &["./repo"]).unwrap_err(),
+            "Unknown profile: fast (expected audit, llm, or verify) (from /p/solscrape.toml)"
+        );
+        assert!(parse(&settings, &["./repo", "--profile", "llm"]).is_ok());
+        settings.values.clear();
+        assert_eq!(parse(&settings, &["./repo"]).unwrap().destination, ".");
+
+        let args = parse(&config::Settings::default(), &["config", "path"]).unwrap();
+        assert_eq!(args.subcommand, Some(Subcommand::ConfigPath));
+        assert_eq!(
+            parse(&config::Settings::default(), &["config", "show"]).unwrap_err(),
+            "Usage: solscrape config path"
+        );
+    }
+
+    /// Verifies counting and rendering of excluded top-level directories.
+    #[test]
+    fn test_excluded_directory_summary() {
+        let root = fixture_dir("excluded_summary");
+        write_tree(
+            &root,
+            &[
+                ("src/A.sol", "contract A {}"),
+                ("lib/oz/Token.sol", "contract T {}"),
+                ("lib/oz/Math.sol", "library M {}"),
+                ("lib/oz/node_modules/X.sol", "contract X {}"),
+                ("test/A.t.sol", "contract AT {}"),
+                ("out/readme.txt", "not solidity"),
+                ("out/Build.sol", "contract B {}"),
+                ("node_modules/x/X.sol", "contract X {}"),
+            ],
+        );
+        let policy = exclusion_policy(&project::ProjectKind::default(), &Args::default());
+
+        // Only the directories a flag can add are counted
+        let count = |name: &str, files| ExcludedCount {
+            name: name.to_string(),
+            files,
+            at_least: false,
+        };
+        let counts = count_excluded_files(&root, &policy);
+        assert_eq!(counts, vec![count("lib", 2), count("test", 1)]);
+        assert_eq!(
+            render_excluded_summary(&counts, &symbols::UNICODE).unwrap(),
+            "Excluded: lib (2 files), test (1 file) — use --include-* to add"
+        );
+        let capped = ExcludedCount {
+            at_least: true,
+            ..count("lib", EXCLUDED_COUNT_CAP)
+        };
+        assert_eq!(
+            render_excluded_summary(&[capped], &symbols::ASCII).unwrap(),
+            "Excluded: lib (10,000+ files) - use --include-* to add"
+        );
+
+        // The walk stops at its entry budget, whether or not it found files
+        let mut walker = SolFileWalker::new(&root.join("lib"), &policy).with_budget(1);
+        assert_eq!(walker.by_ref().count(), 0);
+        assert!(walker.cut_short());
+        let mut walker = SolFileWalker::new(&root.join("lib"), &policy).with_budget(10);
+        assert_eq!(walker.by_ref().count(), 2);
+        assert!(!walker.cut_short());
+        assert_eq!(render_excluded_summary(&[], &symbols::UNICODE), None);
+        assert_eq!(group_thousands(1_234_567), "1,234,567");
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the survey behind the "nothing selected" errors.
+    #[test]
+    fn test_source_survey() {
+        let root = fixture_dir("source_survey");
+        write_tree(
+            &root,
+            &[
+                (".git/HEAD", "ref: refs/heads/main"),
+                ("lib/dep/.git", "gitdir: ../../.git/modules/dep"),
+                ("web/a.ts", ""),
+                ("web/b.TS", ""),
+                ("web/c.js", ""),
+                ("Makefile", ""),
+                (".env", ""),
+            ],
+        );
+        let survey = SourceSurvey::of(&root);
+        assert_eq!((survey.files, survey.solidity_files), (5, 0));
+        assert_eq!(survey.top_file_types(3), "no extension 2, .ts 2, .js 1");
+
+        let policy = exclusion_policy(&project::ProjectKind::default(), &Args::default());
+        assert_eq!(
+            no_files_error(&root, &policy, &symbols::UNICODE).code,
+            EXIT_NO_SOLIDITY
+        );
+        write_tree(&root, &[("test/A.t.sol", "contract AT {}")]);
+        let error = no_files_error(&root, &policy, &symbols::UNICODE);
+        assert_eq!(error.code, EXIT_NO_FILES);
+        assert_eq!(
+            error.message,
+            "No Solidity files found in the source: all 1 .sol file was excluded. \
+             Excluded: test (1 file) — use --include-* to add"
+        );
+
+        let empty = fixture_dir("source_survey_empty");
+        write_tree(&empty, &[(".git/HEAD", "ref: refs/heads/main")]);
+        assert_eq!(
+            no_files_error(&empty, &policy, &symbols::UNICODE).code,
+            EXIT_EMPTY_SOURCE
+        );
+        let _ = fs::remove_dir_all(&root);
+        let _ = fs::remove_dir_all(&empty);
+    }
+
+    /// Verifies that a Hardhat `paths.sources` override limits discovery.
+    #[test]
+    fn test_hardhat_sources_override() {
+        let root = fixture_dir("hardhat_override");
+        write_tree(
+            &root.join("source"),
+            &[
+                (
+                    "hardhat.config.ts",
+                    "export default { paths: { sources: \"./solidity\" } };",
+                ),
+                ("solidity/Vault.sol", "contract Vault {}"),
+                ("contracts/Old.sol", "contract Old {}"),
+            ],
+        );
+
+        let result = scrape_directory(
+            &root.join("source"),
+            root.join("out").to_str().unwrap(),
+            "hardhat",
+            &Args::default(),
+            &Logger::new(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            result.files_processed,
+            vec![
+                Path::new("solidity")
+                    .join("Vault.sol")
+                    .to_string_lossy()
+                    .to_string()
+            ]
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that --subdir scans one directory, keeping paths relative
+    /// to the source root, and names the top-level directories when it is
+    /// missing.
+    #[test]
+    fn test_subdir() {
+        let root = fixture_dir("subdir");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("contracts/Router.sol", "contract Router {}"),
+                ("contracts/test/Router.t.sol", "contract T {}"),
+                ("packages/huge/Other.sol", "contract Other {}"),
+                (".github/x.yml", ""),
+            ],
+        );
+        let source = root.join("source");
+        let argv = |subdir: &str| {
+            let argv = ["solscrape", "./source", "--local", "--subdir", subdir];
+            parse_args_from(argv.iter().map(|s| s.to_string()).collect())
+        };
+        let result = scrape_directory(
+            &source,
+            root.join("out").to_str().unwrap(),
+            "router",
+            &argv("./contracts/").unwrap(),
+            &Logger::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            result.files_processed,
+            [Path::new("contracts")
+                .join("Router.sol")
+                .to_string_lossy()
+                .to_string()]
+        );
+        let output = fs::read_to_string(&result.output_path).unwrap();
+        assert!(output.contains("// File: contracts/Router.sol"), "{output}");
+
+        assert_eq!(
+            list_directory(&source, &argv("src").unwrap(), &Logger::new()).unwrap_err(),
+            "./source has no directory src (top-level directories: contracts, packages)"
+        );
+        assert_eq!(
+            argv("../x").unwrap_err(),
+            "Invalid --subdir path: \"../x\" (expected a path relative to the source root)"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that structural findings are attached to the scrape result.
+    #[test]
+    fn test_structural_warnings_reach_result() {
+        let root = fixture_dir("structural");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("src/Ok.sol", "contract Ok {}"),
+                ("src/Bad.sol", "contract Outer {\ncontract Inner {}\n}"),
+            ],
+        );
+
+        let result = scrape_directory(
+            &root.join("source"),
+            root.join("out").to_str().unwrap(),
+            "structural",
+            &Args::default(),
+            &Logger::new(),
+        )
+        .unwrap();
+
+        assert_eq!(result.file_count, 2);
+        assert_eq!(result.warnings.len(), 1);
+        assert!(result.warnings[0].0.ends_with("Bad.sol"));
+        assert!(result.warnings[0].1.contains("contract Inner"));
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that --flag-todos finds markers surviving cleaning, with
+    /// their original lines, and leaves the file header out of the scan.
+    #[test]
+    fn test_flag_todos() {
+        let root = fixture_dir("flag_todos");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                (
+                    "src/todo/Vault.sol",
+                    "// TODO: comments are cleaned away\n\
+                     contract Vault {\n\
+                     \x20   function f() external {\n\
+                     \x20       revert(\"FIXME: fees\"); // XXX\n\
+                     \x20   }\n\
+                     }\n",
+                ),
+                ("src/Clean.sol", "contract Clean {}\n"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+        let args = Args {
+            flag_todos: true,
+            ..Args::default()
+        };
+        let result =
+            scrape_directory(&source, out.to_str().unwrap(), "todos", &args, &log).unwrap();
+        let found: Vec<(&str, &str, Option<usize>)> = result
+            .todos
+            .iter()
+            .map(|(path, m)| (path.as_str(), m.marker, m.original_line))
+            .collect();
+        assert_eq!(found, [("src/todo/Vault.sol", "FIXME", Some(4))]);
+        assert!(render_json_result("./source", &result).ends_with(
+            r#""todos":[{"path":"src/todo/Vault.sol","marker":"FIXME","line":4,"text":"revert(\"FIXME: fees\");"}],"skipped":[]}"#
+        ));
+
+        // Comments kept verbatim are scanned too, and nothing is without the flag
+        let verbatim = Args {
+            no_clean: true,
+            ..args.clone()
+        };
+        let result =
+            scrape_directory(&source, out.to_str().unwrap(), "todos", &verbatim, &log).unwrap();
+        let markers: Vec<&str> = result.todos.iter().map(|(_, m)| m.marker).collect();
+        assert_eq!(markers, ["TODO", "FIXME", "XXX"]);
+        let result = scrape_directory(
+            &source,
+            out.to_str().unwrap(),
+            "todos",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert!(result.todos.is_empty());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that every candidate is either included or skipped with one
+    /// reason, and that the skips are grouped and reported.
+    #[test]
+    fn test_skip_accounting() {
+        let root = fixture_dir("skip_accounting");
+        let source = root.join("source");
+        let pointer = format!("{}\noid sha256:4d7a\nsize 5120\n", LFS_POINTER_HEADER);
+        let opted_out = format!("{}\ncontract Old {{}}\n", exclusion::IGNORE_MARKER);
+        write_tree(
+            &source,
+            &[
+                ("src/Vault.sol", "contract Vault {}\n"),
+                ("src/Old.sol", &opted_out),
+                ("src/Empty.sol", "// nothing but a comment\n"),
+                ("src/Pointer.sol", &pointer),
+            ],
+        );
+        fs::write(source.join("src/Blob.sol"), [0u8; 64]).unwrap();
+        fs::write(
+            source.join("src/Latin1.sol"),
+            b"string constant S = \"caf\xe9\";\n",
+        )
+        .unwrap();
+
+        let out = root.join("out");
+        let log = Logger::new();
+        let result = scrape_directory(
+            &source,
+            out.to_str().unwrap(),
+            "skips",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        assert_eq!(result.files_processed, ["src/Vault.sol"]);
+        assert_eq!(
+            skip::summarize(result.skipped.iter().map(|(_, reason, _)| *reason)),
+            "5 (1 opt-out-marker, 1 empty-after-clean, 1 lfs-pointer, 1 binary, 1 not-utf8)"
+        );
+        assert_eq!(result.file_count + result.skipped.len(), 6);
+        assert!(render_json_result("./source", &result).ends_with(
+            r#""skipped":[{"path":"src/Blob.sol","reason":"binary"},{"path":"src/Empty.sol","reason":"empty-after-clean"},{"path":"src/Latin1.sol","reason":"not-utf8"},{"path":"src/Old.sol","reason":"opt-out-marker"},{"path":"src/Pointer.sol","reason":"lfs-pointer"}]}"#
+        ));
+        let lost: Vec<&str> = result
+            .skipped
+            .iter()
+            .filter(|(_, reason, _)| reason.loses_content())
+            .map(|(path, _, _)| path.as_str())
+            .collect();
+        assert_eq!(lost, ["src/Blob.sol", "src/Latin1.sol", "src/Pointer.sol"]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies plain version output is unchanged and full output adds build info.
+    #[test]
+    fn test_render_version() {
+        assert_eq!(
+            render_version(false),
+            format!("solscrape {}\n", env!("CARGO_PKG_VERSION"))
+        );
+
+        let full = render_version(true);
+        assert!(full.starts_with(&render_version(false)));
+        assert!(full.contains(&format!(
+            "target:         {}",
+            env!("SOLSCRAPE_BUILD_TARGET")
+        )));
+        assert!(full.contains("rustc:          rustc "));
+        assert!(full.contains("excluded dirs:  .deps, .git,"));
+    }
+
+    /// Runs git in `dir` with a fixed identity, panicking on failure.
+    fn git(dir: &Path, args: &[&str]) {
+        let status = std::process::Command::new("git")
+            .args([
+                "-c",
+                "user.name=Fixture",
+                "-c",
+                "user.email=fixture@example.com",
+            ])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap();
+        assert!(status.status.success(), "git {:?}: {:?}", args, status);
+    }
+
+    /// Verifies that bare repositories and file:// URLs are cloned, not scanned.
+    #[test]
+    fn test_bare_repository_and_file_url_sources() {
+        let root = fixture_dir("bare_repo");
+        let work = root.join("work");
+        let bare = root.join("mirror.git");
+        write_tree(&work, &[("src/Vault.sol", "contract Vault {} // note")]);
+        git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "init"]);
+        git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+        git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+        let bare_path = bare.to_str().unwrap().to_string();
+        assert!(is_bare_repository(&bare));
+        assert!(!is_bare_repository(&work));
+        let local_bare = Args {
+            source: bare_path.clone(),
+            is_local: true,
+            ..Args::default()
+        };
+        assert!(clones_source(&local_bare));
+        let local_work = Args {
+            source: work.to_str().unwrap().to_string(),
+            is_local: true,
+            ..Args::default()
+        };
+        assert!(!clones_source(&local_work));
+
+        let out = root.join("out");
+        let log = Logger::new();
+        let args = Args {
+            min_temp_space: Some(0),
+            ..Args::default()
+        };
+        for source in [bare_path.clone(), format!("file://{}", bare_path)] {
+            let exec = Exec::unrestricted();
+            let result =
+                scrape_from_url(&source, out.to_str().unwrap(), None, &args, &exec, &log).unwrap();
+            assert_eq!(result.output_path, out.join("mirror_scraped.sol"));
+            assert_eq!(result.revision.map(|r| r.branch), Some("main".to_string()));
+            let content = fs::read_to_string(&result.output_path).unwrap();
+            assert!(content.contains("contract Vault {}"));
         }
+        let _ = fs::remove_dir_all(&root);
     }
-}
 
-// ============================================================================
-// Tests
-// ============================================================================
+    /// Verifies that --reuse-clone refreshes an existing clone in place, and
+    /// the checks made before touching it.
+    #[test]
+    fn test_reuse_clone() {
+        let root = fixture_dir("reuse_clone");
+        let work = root.join("work");
+        let bare = root.join("vault.git");
+        let clone = root.join("clone");
+        write_tree(&work, &[("src/Vault.sol", "contract Vault {}")]);
+        git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "init"]);
+        git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+        git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+        git(
+            &root,
+            &[
+                "clone",
+                "-q",
+                bare.to_str().unwrap(),
+                clone.to_str().unwrap(),
+            ],
+        );
 
-/// Unit tests for the Solidity scraper.
-///
-/// These tests verify the core parsing and utility functions. Integration tests
-/// for the full scraping workflow require filesystem access and are not included.
-#[cfg(test)]
-mod tests {
-    use super::*;
+        // A new upstream commit the clone does not have yet
+        write_tree(&work, &[("src/Vault.sol", "contract VaultV2 {}")]);
+        git(&work, &["commit", "-q", "-am", "v2"]);
+        git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
 
-    /// Verifies that single-line comments are removed from code.
+        let url = bare.to_str().unwrap();
+        let out = root.join("out");
+        let out = out.to_str().unwrap();
+        let log = Logger::new();
+        let exec = Exec::unrestricted();
+        let args = Args::default();
+        let result = scrape_reused_clone(&clone, url, out, None, &args, &exec, &log).unwrap();
+        assert_eq!(result.output_path, Path::new(out).join("vault_scraped.sol"));
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        assert!(content.contains("contract VaultV2 {}"), "{content}");
+        let revision = result.revision.unwrap();
+        assert_eq!(revision.branch, "main");
+        assert_eq!(
+            Some(revision.commit),
+            git_output(&work, &["rev-parse", "--short", "HEAD"], &exec, &log)
+        );
+
+        let err =
+            |url: &str, args: &Args| refresh_clone(&clone, url, args, &exec, &log).unwrap_err();
+        let other = "https://github.com/acme/other.git";
+        assert!(err(other, &args).contains("pass --trust-clone"));
+        assert!(err(root.to_str().unwrap(), &args).contains(&format!(
+            "is a clone of {}, not {}",
+            url,
+            root.display()
+        )));
+        // --trust-clone fetches from the given URL, whatever origin says
+        let trusting = Args {
+            trust_clone: true,
+            ..Args::default()
+        };
+        assert!(err(other, &trusting).starts_with("git fetch --quiet"));
+
+        fs::write(clone.join("notes.txt"), "local").unwrap();
+        assert_eq!(
+            err(url, &args),
+            format!(
+                "{} has uncommitted changes (1 path); commit or stash them, or pass --allow-dirty",
+                clone.display()
+            )
+        );
+        let dirty = Args {
+            allow_dirty: true,
+            ..Args::default()
+        };
+        assert!(scrape_reused_clone(&clone, url, out, None, &dirty, &exec, &log).is_ok());
+        fs::remove_file(clone.join("notes.txt")).unwrap();
+
+        // --branch fetches and checks out that branch instead
+        git(&work, &["checkout", "-q", "-b", "rel"]);
+        write_tree(&work, &[("src/Vault.sol", "contract VaultRel {}")]);
+        git(&work, &["commit", "-q", "-am", "rel"]);
+        git(&work, &["push", "-q", bare.to_str().unwrap(), "rel"]);
+        let release = Args {
+            url_ref: Some("rel".to_string()),
+            ..Args::default()
+        };
+        let result = scrape_reused_clone(&clone, url, out, None, &release, &exec, &log).unwrap();
+        let content = fs::read_to_string(&result.output_path).unwrap();
+        assert!(content.contains("contract VaultRel {}"), "{content}");
+        assert_eq!(result.revision.unwrap().branch, "rel");
+
+        let plain = root.join("plain");
+        write_tree(&plain, &[("A.sol", "contract A {}")]);
+        let not_git = refresh_clone(&plain, url, &args, &exec, &log).unwrap_err();
+        // The fixture lives in the system temp dir, outside any repository
+        if !plain.ancestors().skip(1).any(|p| p.join(".git").exists()) {
+            assert!(not_git.contains("needs a git working tree"), "{not_git}");
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the --with-context section and that it is counted apart from
+    /// the Solidity files.
     #[test]
-    fn test_remove_single_line_comments() {
-        let code = "uint256 x; // this is a comment\nuint256 y;";
-        let result = remove_comments(code);
-        assert!(!result.contains("this is a comment"));
-        assert!(result.contains("uint256 x;"));
-        assert!(result.contains("uint256 y;"));
+    fn test_with_context() {
+        let root = fixture_dir("with_context");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                ("foundry.toml", "[profile.default]\nsrc = \"src\"\n"),
+                ("README.md", "# Vault\n\nLine two\nLine three\n"),
+                ("src/A.sol", "contract A {}\n"),
+            ],
+        );
+        let out = root.join("out");
+        let log = Logger::new();
+        let plain = scrape_directory(
+            &source,
+            out.to_str().unwrap(),
+            "plain",
+            &Args::default(),
+            &log,
+        )
+        .unwrap();
+        let args = Args {
+            with_context: true,
+            context_readme_limit: Some(20),
+            ..Args::default()
+        };
+        let result = scrape_directory(&source, out.to_str().unwrap(), "ctx", &args, &log).unwrap();
+
+        assert_eq!(result.files_processed, ["src/A.sol"]);
+        assert_eq!(result.line_count, plain.line_count);
+        let paths: Vec<&str> = result.context.iter().map(|f| f.path).collect();
+        assert_eq!(paths, ["README.md", "foundry.toml"]);
+
+        let output = fs::read_to_string(&result.output_path).unwrap();
+        let solidity = fs::read_to_string(&plain.output_path).unwrap();
+        let (code, section) = output
+            .split_once(&format!("\n{}\n", context::BANNER))
+            .unwrap();
+        assert_eq!(code, solidity);
+        assert!(section.lines().all(|line| line.starts_with("//")));
+        assert!(section.contains(
+            "// ----- README.md (first 17 of 29 bytes) -----\n// # Vault\n//\n// Line two\n"
+        ));
+        assert!(
+            section
+                .ends_with("// ----- foundry.toml -----\n// [profile.default]\n// src = \"src\"")
+        );
+        assert_eq!(
+            split::parse_sections(&output).unwrap(),
+            split::parse_sections(&solidity).unwrap()
+        );
+        assert!(
+            render_json_result("./source", &result)
+                .ends_with(r#""context":["README.md","foundry.toml"],"todos":[],"skipped":[]}"#)
+        );
+        assert_eq!(
+            render_context_report(&result.context),
+            "Project context (not Solidity):\n  \
+             README.md         4 lines        17 bytes (of 29)\n  \
+             foundry.toml      3 lines        30 bytes"
+        );
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies that multi-line comments are removed from code.
+    /// Verifies --since on a shallow clone that must fetch the ref and deepen
+    /// past [`SINCE_DEEPEN_START`] commits, and on a local working tree.
     #[test]
-    fn test_remove_multi_line_comments() {
-        let code = "uint256 x; /* multi\nline\ncomment */ uint256 y;";
-        let result = remove_comments(code);
-        assert!(!result.contains("multi"));
-        assert!(!result.contains("comment"));
-        assert!(result.contains("uint256 x;"));
-        assert!(result.contains("uint256 y;"));
+    fn test_since_ref() {
+        let root = fixture_dir("since");
+        let work = root.join("work");
+        let bare = root.join("mirror.git");
+        write_tree(
+            &work,
+            &[
+                ("src/A.sol", "contract A {}"),
+                ("src/Old.sol", "contract Old {}"),
+                ("src/Same.sol", "contract Same {}"),
+                ("lib/x/L.sol", "library L {}"),
+            ],
+        );
+        git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "audited"]);
+        git(&work, &["tag", "v1.4.0"]);
+        for n in 0..SINCE_DEEPEN_START + 10 {
+            git(
+                &work,
+                &["commit", "-q", "--allow-empty", "-m", &n.to_string()],
+            );
+        }
+        write_tree(
+            &work,
+            &[
+                ("src/A.sol", "contract A { uint x; }"),
+                ("src/New.sol", "contract New {}"),
+                ("lib/x/L.sol", "library L { }"),
+                ("docs/notes.md", "changed"),
+            ],
+        );
+        fs::remove_file(work.join("src/Old.sol")).unwrap();
+        git(&work, &["add", "-A"]);
+        git(&work, &["commit", "-q", "-m", "changes"]);
+        git(
+            &work,
+            &["push", "-q", "--tags", bare.to_str().unwrap(), "main"],
+        );
+        git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+        let out = root.join("out");
+        let log = Logger::new();
+        let exec = Exec::unrestricted();
+        let url = format!("file://{}", bare.display());
+        let args = Args {
+            since: Some("v1.4.0".to_string()),
+            min_temp_space: Some(0),
+            ..Args::default()
+        };
+        let from_url = scrape_from_url(&url, out.to_str().unwrap(), None, &args, &exec, &log)
+            .unwrap_or_else(|e| panic!("{}", e.message));
+        let from_local = scrape_from_local(
+            work.to_str().unwrap(),
+            out.to_str().unwrap(),
+            None,
+            &args,
+            &exec,
+            &log,
+        )
+        .unwrap_or_else(|e| panic!("{}", e.message));
+        for result in [&from_url, &from_local] {
+            assert_eq!(result.files_processed, ["src/A.sol", "src/New.sol"]);
+            let since = result.since.as_ref().unwrap();
+            assert_eq!(since.base, "v1.4.0");
+            assert_eq!(since.removed, ["src/Old.sol"]);
+            assert!(since.changed.contains("lib/x/L.sol"));
+        }
+        let json = render_json_result(&url, &from_url);
+        assert!(
+            json.contains(r#""since":"v1.4.0","removed":["src/Old.sol"]"#),
+            "{}",
+            json
+        );
+
+        // Normal exclusions still apply to changed files
+        let with_lib = Args {
+            include_lib: true,
+            ..args.clone()
+        };
+        let result =
+            scrape_from_url(&url, out.to_str().unwrap(), None, &with_lib, &exec, &log).unwrap();
+        assert_eq!(
+            result.files_processed,
+            ["lib/x/L.sol", "src/A.sol", "src/New.sol"]
+        );
+
+        let fail = |since: &str, source: &str| {
+            let args = Args {
+                since: Some(since.to_string()),
+                ..args.clone()
+            };
+            let result = if source.starts_with("file://") {
+                scrape_from_url(source, out.to_str().unwrap(), None, &args, &exec, &log)
+            } else {
+                scrape_from_local(source, out.to_str().unwrap(), None, &args, &exec, &log)
+            };
+            result.err().unwrap()
+        };
+        let unknown = fail("v9.9.9", &url);
+        assert!(
+            unknown.message.starts_with("Unknown ref v9.9.9"),
+            "{}",
+            unknown.message
+        );
+        let nothing = fail("main", work.to_str().unwrap());
+        assert_eq!(
+            (nothing.message.as_str(), nothing.code),
+            ("No Solidity files changed since main", EXIT_NO_FILES)
+        );
+        let plain = root.join("plain");
+        write_tree(&plain, &[("A.sol", "contract A {}")]);
+        assert!(
+            fail("v1", plain.to_str().unwrap())
+                .message
+                .starts_with("--since needs a git repository")
+        );
+
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        assert!(parse(&["solscrape", "u", "--since", "-x"]).is_err());
+        assert!(parse(&["solscrape", "u", "--since", "a..b"]).is_err());
+        assert_eq!(
+            parse(&["solscrape", "u", "--since", "v1", "--compare", "a..b"]).unwrap_err(),
+            "--since cannot be used with --compare"
+        );
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies that comment-like syntax within double-quoted strings is preserved.
+    /// Verifies --related on a diamond `Vault -> {Left, Right} -> Base`:
+    /// the union is selected and annotated, remapped imports count, and the
+    /// usual exclusions still apply to related files.
     #[test]
-    fn test_preserve_strings() {
-        let code = r#"string s = "// not a comment";"#;
-        let result = remove_comments(code);
-        assert!(result.contains("// not a comment"));
+    fn test_related_diamond() {
+        let root = fixture_dir("related");
+        write_tree(
+            &root,
+            &[
+                ("remappings.txt", "@base/=src/base/\n"),
+                (
+                    "src/Vault.sol",
+                    "import \"./Left.sol\";\nimport {R} from \"./Right.sol\";\ncontract Vault {}",
+                ),
+                (
+                    "src/Left.sol",
+                    "import \"@base/Base.sol\";\ncontract Left {}",
+                ),
+                (
+                    "src/Right.sol",
+                    "import \"./base/Base.sol\";\ncontract Right {}",
+                ),
+                ("src/base/Base.sol", "contract Base {}"),
+                (
+                    "src/Other.sol",
+                    "import \"./base/Base.sol\";\ncontract Other {}",
+                ),
+                (
+                    "test/Vault.t.sol",
+                    "import \"../src/Vault.sol\";\ncontract T {}",
+                ),
+            ],
+        );
+        let out = root.join("out");
+        let (log, exec) = (Logger::new(), Exec::unrestricted());
+        let args = Args {
+            related: Some("src/Vault.sol".to_string()),
+            include_test: true,
+            ..Args::default()
+        };
+        let scrape = |args: &Args| {
+            scrape_from_local(
+                root.to_str().unwrap(),
+                out.to_str().unwrap(),
+                None,
+                args,
+                &exec,
+                &log,
+            )
+        };
+        let result = scrape(&args).unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(
+            result.files_processed,
+            [
+                "src/Left.sol",
+                "src/Right.sol",
+                "src/Vault.sol",
+                "src/base/Base.sol",
+                "test/Vault.t.sol"
+            ]
+        );
+        let related = result.related.as_ref().unwrap();
+        assert_eq!(graph::count(&related.relations), [1, 3, 1]);
+        let written = fs::read_to_string(&result.output_path).unwrap();
+        for header in [
+            "src/Vault.sol (target)",
+            "src/base/Base.sol (depends-on)",
+            "test/Vault.t.sol (dependent)",
+        ] {
+            assert!(
+                written.contains(&format!("{}{}\n", HEADER_FILE_PREFIX, header)),
+                "{}",
+                written
+            );
+        }
+        let sections = split::parse_sections(&written).unwrap();
+        assert_eq!(sections[0].path, "src/Left.sol");
+
+        let without_tests = Args {
+            include_test: false,
+            ..args.clone()
+        };
+        let result = scrape(&without_tests).unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(graph::count(&result.related.unwrap().relations), [1, 3, 0]);
+
+        // An excluded target is not dropped silently
+        let log_path = root.join("run.log");
+        let excluded = Args {
+            related: Some("test/Vault.t.sol".to_string()),
+            ..without_tests.clone()
+        };
+        let result = scrape_from_local(
+            root.to_str().unwrap(),
+            out.to_str().unwrap(),
+            None,
+            &excluded,
+            &exec,
+            &Logger::new().with_sink(FileSink::open(&log_path).unwrap()),
+        )
+        .unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(
+            !result
+                .files_processed
+                .contains(&"test/Vault.t.sol".to_string())
+        );
+        let logged = fs::read_to_string(&log_path).unwrap();
+        assert!(
+            logged.contains(
+                "--related target test/Vault.t.sol is excluded (inside excluded directory \
+                 test/); add --include-test to scrape it with the files related to it"
+            ),
+            "{logged}"
+        );
+        assert_eq!(
+            excluded_target_warning("out/A.sol", &exclusion::Reason::Directory("out".into())),
+            "--related target out/A.sol is excluded (inside excluded directory out/); \
+             only the files related to it are scraped"
+        );
+
+        let missing = Args {
+            related: Some("src/Missing.sol".to_string()),
+            ..args.clone()
+        };
+        let error = scrape(&missing).err().unwrap();
+        assert!(
+            error
+                .message
+                .starts_with("--related src/Missing.sol is not a .sol file"),
+            "{}",
+            error.message
+        );
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            parse(&["solscrape", "u", "--related", "../A.sol"]).unwrap_err(),
+            "Invalid --related path: \"../A.sol\" (expected a path relative to the source root)"
+        );
+        assert_eq!(
+            parse(&["solscrape", "u", "--related", "A.sol", "--list-files"]).unwrap_err(),
+            "--related cannot be used with --list-files or --compare"
+        );
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies that multi-line comment syntax within strings is preserved.
+    /// Verifies that copies differing only in comments and whitespace are
+    /// grouped, that near-copies are not, and that --dedupe-content keeps
+    /// the first path of each group and skips the others.
     #[test]
-    fn test_preserve_strings_multiline() {
-        let code = r#"string s = "/* not a comment */";"#;
-        let result = remove_comments(code);
-        assert!(result.contains("/* not a comment */"));
+    fn test_duplicate_content() {
+        let root = fixture_dir("duplicates");
+        let token = "contract Token {\n    uint256 public supply = 1;\n}\n";
+        write_tree(
+            &root,
+            &[
+                ("contracts/Token.sol", token),
+                (
+                    "contracts-flat/Token.sol",
+                    "// flattened\ncontract Token{\n\tuint256 public supply=1;\n\n}",
+                ),
+                ("contracts-old/Token.sol", token),
+                (
+                    "contracts/Vault.sol",
+                    "contract Vault {\n    uint256 fee = 30;\n}\n",
+                ),
+                (
+                    "contracts-flat/Vault.sol",
+                    "contract Vault {\n    uint256 fee = 31;\n}\n",
+                ),
+            ],
+        );
+        let out = root.join("out");
+        let (log, exec) = (Logger::new(), Exec::unrestricted());
+        let scrape = |args: &Args| {
+            scrape_from_local(
+                root.to_str().unwrap(),
+                out.to_str().unwrap(),
+                None,
+                args,
+                &exec,
+                &log,
+            )
+            .unwrap_or_else(|e| panic!("{}", e.message))
+        };
+        // Paths compare by component, so contracts/ comes first
+        let group = [
+            "contracts/Token.sol",
+            "contracts-flat/Token.sol",
+            "contracts-old/Token.sol",
+        ];
+
+        let result = scrape(&Args::default());
+        assert_eq!(result.file_count, 5);
+        assert_eq!(result.duplicates, [group]);
+        assert!(result.skipped.is_empty());
+
+        let args = Args {
+            dedupe_content: true,
+            ..Args::default()
+        };
+        let result = scrape(&args);
+        assert_eq!(
+            result.files_processed,
+            [
+                "contracts/Token.sol",
+                "contracts/Vault.sol",
+                "contracts-flat/Vault.sol"
+            ]
+        );
+        assert_eq!(result.duplicates, [group]);
+        assert_eq!(
+            result.skipped,
+            [
+                (
+                    group[1].to_string(),
+                    SkipReason::Duplicate,
+                    Some(group[0].to_string())
+                ),
+                (
+                    group[2].to_string(),
+                    SkipReason::Duplicate,
+                    Some(group[0].to_string())
+                )
+            ]
+        );
+        assert!(render_json_result("./source", &result).contains(
+            r#"{"path":"contracts-flat/Token.sol","reason":"duplicate","kept":"contracts/Token.sol"}"#
+        ));
+        let written = fs::read_to_string(&result.output_path).unwrap();
+        assert_eq!(written.matches("contract Token").count(), 1);
+        assert_eq!(
+            parse_checked(
+                ["solscrape", "u", "--dedupe-content", "--stream"]
+                    .iter()
+                    .map(|s| s.to_string())
+                    .collect()
+            )
+            .unwrap_err(),
+            "--dedupe-content cannot be used with --stream, --list-files, or --compare"
+        );
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies that empty and whitespace-only lines are removed.
+    /// Verifies that --prune-implemented-interfaces skips an implemented
+    /// interface file naming its implementer, and keeps an unimplemented one
+    /// and both declarations of an ambiguous name, warning about those.
     #[test]
-    fn test_remove_empty_lines() {
-        let code = "line1\n\n\nline2\n  \nline3";
-        let result = remove_empty_lines(code);
-        assert_eq!(result, "line1\nline2\nline3");
+    fn test_prune_implemented_interfaces() {
+        let root = fixture_dir("prune_interfaces");
+        let source = root.join("source");
+        write_tree(
+            &source,
+            &[
+                (
+                    "src/IVault.sol",
+                    "interface IVault {\n    function f() external;\n}\n",
+                ),
+                (
+                    "src/Vault.sol",
+                    "contract Vault is IVault {\n    function f() external {}\n}\n",
+                ),
+                (
+                    "src/IOracle.sol",
+                    "interface IOracle {\n    function p() external;\n}\n",
+                ),
+                (
+                    "src/IPool.sol",
+                    "interface IPool {\n    function a() external;\n}\n",
+                ),
+                (
+                    "src/legacy/IPool.sol",
+                    "interface IPool {\n    function b() external;\n}\n",
+                ),
+                (
+                    "src/Pool.sol",
+                    "contract Pool is IPool {\n    function a() external {}\n}\n",
+                ),
+            ],
+        );
+        let log_path = root.join("run.log");
+        let log = Logger::new().with_sink(FileSink::open(&log_path).unwrap());
+        let args = Args {
+            prune_implemented_interfaces: true,
+            ..Args::default()
+        };
+        let result = scrape_directory(
+            &source,
+            root.join("out").to_str().unwrap(),
+            "pruned",
+            &args,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(
+            result.skipped,
+            [(
+                "src/IVault.sol".to_string(),
+                SkipReason::ImplementedInterface,
+                Some("IVault is implemented by Vault in src/Vault.sol".to_string())
+            )]
+        );
+        assert!(render_json_result("./source", &result).contains(
+            r#"{"path":"src/IVault.sol","reason":"implemented-interface","implemented_by":"IVault is implemented by Vault in src/Vault.sol"}"#
+        ));
+        assert_eq!(result.files_processed.len(), 5);
+
+        let logged = fs::read_to_string(&log_path).unwrap();
+        assert!(
+            logged.contains(
+                "Skipped src/IVault.sol: [implemented-interface] IVault is implemented by \
+                 Vault in src/Vault.sol"
+            ),
+            "{logged}"
+        );
+        assert!(
+            logged
+                .contains("Interface IPool is declared in src/IPool.sol and src/legacy/IPool.sol"),
+            "{logged}"
+        );
+        assert!(!logged.contains("Skipped src/IOracle.sol"), "{logged}");
+
+        let parse = |list: &[&str]| parse_checked(list.iter().map(|s| s.to_string()).collect());
+        assert!(
+            parse(&[
+                "solscrape",
+                "u",
+                "--prune-implemented-interfaces",
+                "--stream"
+            ])
+            .unwrap_err()
+            .starts_with("--prune-implemented-interfaces cannot be used with --stream")
+        );
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies repository name extraction from various URL formats.
+    /// Verifies the pre-check against repositories with and without Solidity.
     #[test]
-    fn test_extract_repo_name() {
+    fn test_precheck_remote() {
+        let root = fixture_dir("precheck");
+        let mut sources = Vec::new();
+        for (name, files) in [
+            (
+                "contracts",
+                &[("foundry.toml", ""), ("src/A.sol", "contract A {}")][..],
+            ),
+            (
+                "webapp",
+                &[("package.json", "{}"), ("src/index.ts", "")][..],
+            ),
+        ] {
+            let work = root.join(name);
+            let bare = root.join(format!("{}.git", name));
+            write_tree(&work, files);
+            git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+            git(&work, &["init", "-q", "-b", "main"]);
+            git(&work, &["add", "."]);
+            git(&work, &["commit", "-q", "-m", "init"]);
+            git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+            git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+            sources.push(format!("file://{}", bare.display()));
+        }
+        let log = Logger::new();
+        let args = Args::default();
+
+        let exec = Exec::unrestricted();
+        let contracts = precheck_remote(&sources[0], &args, &exec, &log).unwrap();
+        assert_eq!(contracts.solidity_files, 1);
+        assert!(contracts.project.is_foundry());
+        let audit = exec.invocations();
+        assert_eq!(audit[0].args[..2], ["ls-remote", "--symref"]);
+        assert!(audit[1].args.contains(&"--filter=blob:none".to_string()));
+
+        let webapp = precheck_remote(&sources[1], &args, &exec, &log).unwrap();
+        assert_eq!(webapp.paths, 2);
+        assert!(!webapp.looks_like_solidity());
+
+        // --yes and --quiet continue past the concern instead of asking
+        for args in [
+            Args {
+                assume_yes: true,
+                ..Args::default()
+            },
+            Args {
+                quiet: true,
+                ..Args::default()
+            },
+        ] {
+            confirm_remote(&sources[1], &args, &exec, &log).unwrap();
+        }
+        confirm_remote(&sources[0], &args, &exec, &log).unwrap();
+
+        let missing = format!("file://{}", root.join("missing.git").display());
+        let err = precheck_remote(&missing, &args, &exec, &log).unwrap_err();
+        assert!(err.starts_with("Precheck failed: cannot reach"), "{}", err);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies when the pre-check runs by default.
+    #[test]
+    fn test_wants_precheck() {
+        let github = |flags: &[&str]| {
+            let mut argv = vec!["solscrape", "https://github.com/a/b.git"];
+            argv.extend_from_slice(flags);
+            parse_args_from(argv.into_iter().map(String::from).collect()).unwrap()
+        };
+        assert!(wants_precheck(&github(&[])));
+        assert!(!wants_precheck(&github(&["--quiet"])));
+        assert!(!wants_precheck(&github(&["--no-precheck"])));
+        assert!(wants_precheck(&github(&["-q", "--precheck"])));
+        assert!(github(&["-y"]).assume_yes);
+
+        let gitlab = parse_args_from(
+            ["solscrape", "https://gitlab.com/a/b.git"]
+                .map(String::from)
+                .to_vec(),
+        )
+        .unwrap();
+        assert!(!wants_precheck(&gitlab));
+        let local = Args {
+            source: ".".to_string(),
+            is_local: true,
+            precheck: Some(true),
+            ..Args::default()
+        };
+        assert!(!wants_precheck(&local));
+    }
+
+    /// Verifies `--no-subprocess` allows only git for a URL scrape, and that
+    /// the audit log records exactly the expected invocations.
+    #[test]
+    fn test_no_subprocess_audit_for_url_scrape() {
+        let root = fixture_dir("no_subprocess");
+        let work = root.join("work");
+        let bare = root.join("audit.git");
+        write_tree(&work, &[("src/A.sol", "contract A {}")]);
+        git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "init"]);
+        git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+        git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+        let args = Args {
+            source: bare.to_str().unwrap().to_string(),
+            no_subprocess: true,
+            ..Args::default()
+        };
+        assert_eq!(allowed_programs(&args), vec!["git"]);
+        let limited = Args {
+            max_clone_size: Some(1 << 30),
+            ..args.clone()
+        };
+        assert_eq!(allowed_programs(&limited), vec!["git", "curl"]);
+        let local = Args {
+            source: work.to_str().unwrap().to_string(),
+            is_local: true,
+            no_subprocess: true,
+            ..Args::default()
+        };
+        assert!(allowed_programs(&local).is_empty());
+
+        let exec = build_exec(&args);
+        let log = Logger::new();
+        let out = root.join("out");
+        scrape_from_url(
+            &args.source,
+            out.to_str().unwrap(),
+            None,
+            &args,
+            &exec,
+            &log,
+        )
+        .unwrap();
+
+        let audit = exec.invocations();
+        let programs: Vec<&str> = audit.iter().map(|i| i.program).collect();
+        // The df free-space probe is skipped, not refused
+        assert!(programs.iter().all(|p| *p == "git"), "{:?}", audit);
+        assert!(audit.iter().all(|i| i.allowed));
         assert_eq!(
-            extract_repo_name("https://github.com/user/repo.git"),
-            "repo"
+            audit[0].args[..4],
+            ["clone", "--depth", "1", args.source.as_str()]
         );
-        assert_eq!(extract_repo_name("https://github.com/user/repo"), "repo");
+        assert_eq!(audit[1].args[2..], ["rev-parse", "--short", "HEAD"]);
+
+        // A local scan under --no-subprocess still works, without git
+        let exec = build_exec(&local);
+        let result = scrape_from_local(
+            &local.source,
+            out.to_str().unwrap(),
+            None,
+            &local,
+            &exec,
+            &log,
+        )
+        .unwrap();
+        assert_eq!(result.revision, None);
+        assert!(exec.invocations().is_empty());
+
+        // With default flags, a local directory outside any git working
+        // tree spawns nothing either; inside one, git is asked for the
+        // revision (see detect_git_revision)
+        let plain = root.join("plain");
+        write_tree(&plain, &[("src/A.sol", "contract A {}")]);
+        let defaults = Args {
+            source: plain.to_str().unwrap().to_string(),
+            is_local: true,
+            ..Args::default()
+        };
+        let exec = Exec::unrestricted();
+        scrape_from_local(
+            &defaults.source,
+            out.to_str().unwrap(),
+            None,
+            &defaults,
+            &exec,
+            &log,
+        )
+        .unwrap();
+        if !plain.ancestors().skip(1).any(|p| p.join(".git").exists()) {
+            assert_eq!(exec.invocations(), []);
+        }
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies `--compare` emits both versions of only the files that differ.
+    #[test]
+    fn test_compare_refs() {
+        let root = fixture_dir("compare_refs");
+        let work = root.join("work");
+        write_tree(
+            &work,
+            &[
+                ("src/Same.sol", "contract Same {}"),
+                ("src/Token.sol", "contract Token { uint a; }"),
+                ("src/Docs.sol", "// v1 docs\ncontract Docs {}"),
+                ("src/Old.sol", "contract Old {}"),
+            ],
+        );
+        git(&work, &["init", "-q", "-b", "main"]);
+        git(&work, &["add", "."]);
+        git(&work, &["commit", "-q", "-m", "v1"]);
+        git(&work, &["tag", "v1.0.0"]);
+        fs::remove_file(work.join("src/Old.sol")).unwrap();
+        write_tree(
+            &work,
+            &[
+                ("src/Token.sol", "contract Token { uint a; uint b; }"),
+                ("src/Docs.sol", "// reworded v2 docs\ncontract Docs {}"),
+                ("src/New.sol", "contract New {}"),
+            ],
+        );
+        git(&work, &["add", "-A"]);
+        git(&work, &["commit", "-q", "-m", "v2"]);
+        git(&work, &["tag", "v2.0.0"]);
+
+        let args = parse_args_from(vec![
+            "solscrape".to_string(),
+            work.to_str().unwrap().to_string(),
+            "--compare".to_string(),
+            "v1.0.0..v2.0.0".to_string(),
+            "--min-temp-space".to_string(),
+            "0".to_string(),
+        ])
+        .unwrap();
+        let refs = args.compare.clone().unwrap();
+        let out = root.join("out");
+        let (exec, log) = (Exec::unrestricted(), Logger::new());
+        let comparison = compare_refs(
+            &args.source,
+            out.to_str().unwrap(),
+            None,
+            &refs,
+            &args,
+            &exec,
+            &log,
+        )
+        .unwrap();
+
+        let src = |name: &str| Path::new("src").join(name).to_string_lossy().to_string();
+        assert_eq!(comparison.changed, vec![src("Token.sol")]);
+        assert_eq!(comparison.added, vec![src("New.sol")]);
+        assert_eq!(comparison.removed, vec![src("Old.sol")]);
+        assert_eq!(comparison.unchanged, vec![src("Docs.sol"), src("Same.sol")]);
         assert_eq!(
-            extract_repo_name("https://github.com/user/my-project.git/"),
-            "my-project"
+            comparison.output_path,
+            out.join("work_v1.0.0_vs_v2.0.0_scraped.sol")
         );
+
+        let content = fs::read_to_string(&comparison.output_path).unwrap();
+        assert!(content.starts_with(
+            "// Comparison v1.0.0..v2.0.0: 1 changed, 1 added, 1 removed, 2 unchanged\n"
+        ));
+        assert!(content.contains(&format!("//   {}\n", src("Same.sol"))));
+        let header = |r: &str, name: &str| format!("// File ({}): {}\n", r, src(name));
+        let old_token = content.find(&header("v1.0.0", "Token.sol")).unwrap();
+        let new_token = content.find(&header("v2.0.0", "Token.sol")).unwrap();
+        assert!(old_token < new_token);
+        assert!(content[old_token..new_token].contains("contract Token { uint a; }"));
+        assert!(content[new_token..].contains("uint b;"));
+        assert!(content.contains(&header("v2.0.0", "New.sol")));
+        assert!(content.contains(&header("v1.0.0", "Old.sol")));
+        assert!(!content.contains("contract Same"));
+        assert!(!content.contains("docs"));
+
+        let missing = ("v1.0.0".to_string(), "v9".to_string());
+        let err = compare_refs(
+            &args.source,
+            out.to_str().unwrap(),
+            None,
+            &missing,
+            &args,
+            &exec,
+            &log,
+        )
+        .err()
+        .unwrap();
+        assert!(err.starts_with("Unknown ref v9"), "{}", err);
+        assert!(parse_compare_range("v1").is_err());
+        assert!(parse_compare_range("..v2").is_err());
+        let _ = fs::remove_dir_all(&root);
     }
 
-    /// Verifies the complete cleaning pipeline with realistic Solidity code.
+    /// Verifies the shape of the progress event stream for a fixture scrape.
     #[test]
-    fn test_clean_solidity() {
-        let code = r#"
-// SPDX-License-Identifier: MIT
-pragma solidity ^0.8.0;
+    fn test_progress_event_stream() {
+        let root = fixture_dir("progress");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("src/A.sol", "contract A {}"),
+                ("src/B.sol", "contract B {}"),
+            ],
+        );
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = events.clone();
+        let log = Logger::new().with_progress(move |e| recorder.lock().unwrap().push(e.clone()));
+        let exec = Exec::unrestricted();
 
-/* This is a 
-   multi-line comment */
-contract Test {
-    // Single line comment
-    uint256 public value;
-    
-    string public name = "// not removed";
-}
-"#;
-        let result = clean_solidity(code);
+        let result = scrape_from_local(
+            root.join("source").to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+            Some("progress"),
+            &Args::default(),
+            &exec,
+            &log,
+        )
+        .unwrap();
+        // A plain directory needs no git, so default flags spawn nothing
+        assert!(exec.invocations().is_empty());
 
-        assert!(!result.contains("SPDX-License-Identifier"));
-        assert!(!result.contains("multi-line comment"));
-        assert!(!result.contains("Single line comment"));
-        assert!(result.contains("pragma solidity"));
-        assert!(result.contains("contract Test"));
-        assert!(result.contains("uint256 public value"));
-        assert!(result.contains(r#""// not removed""#));
+        let file = |index, name: &str| ProgressEvent::FileProcessed {
+            index,
+            total: Some(2),
+            path: Path::new("src").join(name).to_string_lossy().to_string(),
+        };
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                ProgressEvent::ScanStarted {
+                    root: root.join("source"),
+                },
+                ProgressEvent::ProcessingStarted { total: Some(2) },
+                file(1, "A.sol"),
+                file(2, "B.sol"),
+                ProgressEvent::WritingOutput {
+                    path: result.output_path.clone(),
+                },
+                ProgressEvent::Finished {
+                    files: 2,
+                    lines: result.line_count,
+                    output: result.output_path.clone(),
+                },
+            ]
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that pragmas are injected only into files lacking one.
+    #[test]
+    fn test_inject_pragma() {
+        let root = fixture_dir("inject_pragma");
+        write_tree(
+            &root.join("source"),
+            &[
+                ("src/Has.sol", "pragma solidity ^0.8.0;\ncontract Has {}"),
+                (
+                    "src/Lacks.sol",
+                    "// pragma solidity 0.4.0;\nlibrary Lacks { string constant s = \"pragma solidity\"; }",
+                ),
+            ],
+        );
+        let args = Args {
+            inject_pragma: Some("^0.8.20".to_string()),
+            ..Args::default()
+        };
+
+        let result = scrape_directory(
+            &root.join("source"),
+            root.join("out").to_str().unwrap(),
+            "pragma",
+            &args,
+            &Logger::new(),
+        )
+        .unwrap();
+
+        assert_eq!(result.pragmas_injected, 1);
+        let sections =
+            split::parse_sections(&fs::read_to_string(&result.output_path).unwrap()).unwrap();
+        assert_eq!(
+            sections[0].content,
+            "pragma solidity ^0.8.0;\ncontract Has {}"
+        );
+        assert!(
+            sections[1]
+                .content
+                .starts_with("pragma solidity ^0.8.20; // injected by solscrape\nlibrary Lacks")
+        );
+
+        let argv = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert!(parse_args_from(argv(&["solscrape", "url", "--inject-pragma", "0.8; x"])).is_err());
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies the summary source line with and without git information.
+    #[test]
+    fn test_describe_source() {
+        let rev = GitRevision {
+            branch: "main".to_string(),
+            commit: "abc1234".to_string(),
+            pinned: None,
+        };
+        assert_eq!(
+            describe_source("https://github.com/user/repo.git", Some(&rev)),
+            "https://github.com/user/repo.git (branch: main, commit: abc1234)"
+        );
+        assert_eq!(describe_source("./local", None), "./local");
+    }
+
+    /// Verifies that every banner line has the same width for varied version
+    /// lengths, in either charset.
+    #[test]
+    fn test_render_banner_alignment() {
+        for version in ["1.0.0", "1.10.0", "2.0.0-rc.10"] {
+            let banner = render_banner(version, &symbols::UNICODE);
+            let ascii = render_banner(version, &symbols::ASCII);
+            assert!(ascii.is_ascii());
+            assert_eq!(
+                ascii.lines().map(str::len).collect::<Vec<_>>(),
+                banner
+                    .lines()
+                    .map(|l| l.chars().count())
+                    .collect::<Vec<_>>()
+            );
+            let widths: Vec<usize> = banner.lines().map(|l| l.chars().count()).collect();
+            assert_eq!(widths.len(), 3, "banner for {version} should have 3 lines");
+            assert!(
+                widths.iter().all(|&w| w == widths[0]),
+                "misaligned banner for {version}: {widths:?}"
+            );
+            assert!(banner.contains(&format!("SOLSCRAPE v{version}")));
+        }
+    }
+
+    /// Verifies that content wider than the minimum grows the box instead of overflowing it.
+    #[test]
+    fn test_render_box_grows_for_long_content() {
+        let content = "x".repeat(BANNER_MIN_WIDTH + 10);
+        let rendered = render_box(&content, &symbols::UNICODE);
+        let widths: Vec<usize> = rendered.lines().map(|l| l.chars().count()).collect();
+        assert!(widths.iter().all(|&w| w == BANNER_MIN_WIDTH + 16));
     }
 }
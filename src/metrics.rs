@@ -0,0 +1,162 @@
+//! Run metrics in a flat line format (`--metrics-file`, `--metrics-stdout`).
+//!
+//! Each run appends one block of lines, one metric per line, in the
+//! Graphite plaintext format `{key} {value} {timestamp}`: the key is
+//! `solscrape.` plus a name from [`KEYS`], the value an unsigned integer,
+//! and the timestamp the Unix time in seconds the run ended at, shared by
+//! all lines of the block. Every run writes every key, in [`KEYS`] order, so
+//! a consumer can rely on the set; the keys are a stable interface and are
+//! only ever added to.
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+/// The prefix of every key.
+pub const PREFIX: &str = "solscrape.";
+
+/// The metric names, in the order they are written.
+///
+/// | Name | Value |
+/// |------|-------|
+/// | `clone_ms` | Milliseconds spent cloning or refreshing the clone; 0 for a local directory |
+/// | `files_processed` | Files in the output |
+/// | `files_skipped` | Candidates left out while processing |
+/// | `bytes_written` | Size of the consolidated output |
+/// | `total_ms` | Milliseconds of the whole run |
+/// | `success` | 1 if the run exited 0, otherwise 0 |
+pub const KEYS: [&str; 6] = [
+    "clone_ms",
+    "files_processed",
+    "files_skipped",
+    "bytes_written",
+    "total_ms",
+    "success",
+];
+
+/// The measurements of one run. A run that fails before scraping keeps the
+/// zero counters.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metrics {
+    /// Time spent cloning, or `None` when nothing was cloned.
+    pub clone_time: Option<Duration>,
+    /// Files in the output.
+    pub files_processed: usize,
+    /// Candidates left out while processing.
+    pub files_skipped: usize,
+    /// Size of the consolidated output in bytes.
+    pub bytes_written: u64,
+    /// Time of the whole run.
+    pub total_time: Duration,
+    /// Whether the run succeeded.
+    pub success: bool,
+}
+
+impl Metrics {
+    /// The values, in [`KEYS`] order.
+    fn values(&self) -> [u128; 6] {
+        [
+            self.clone_time.unwrap_or_default().as_millis(),
+            self.files_processed as u128,
+            self.files_skipped as u128,
+            u128::from(self.bytes_written),
+            self.total_time.as_millis(),
+            u128::from(self.success),
+        ]
+    }
+
+    /// Adds the counters of `other` to these, for a run that scrapes several
+    /// sources (`--from-file`) and reports them as one block.
+    ///
+    /// The total time and success are left alone; they describe the whole
+    /// run.
+    pub fn add(&mut self, other: &Metrics) {
+        if let Some(time) = other.clone_time {
+            self.clone_time = Some(self.clone_time.unwrap_or_default() + time);
+        }
+        self.files_processed += other.files_processed;
+        self.files_skipped += other.files_skipped;
+        self.bytes_written += other.bytes_written;
+    }
+
+    /// Renders the block of lines for a run ending at `timestamp`, each line
+    /// ending in a newline.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let block = Metrics { files_processed: 3, success: true, ..Metrics::default() }
+    ///     .render(1_760_400_000);
+    /// assert!(block.contains("solscrape.files_processed 3 1760400000\n"));
+    /// ```
+    pub fn render(&self, timestamp: u64) -> String {
+        KEYS.iter()
+            .zip(self.values())
+            .map(|(key, value)| format!("{}{} {} {}\n", PREFIX, key, value, timestamp))
+            .collect()
+    }
+}
+
+/// Appends `block` to the metrics file at `path`, creating it if needed.
+///
+/// # Errors
+///
+/// Returns `"Failed to write metrics to {path}: ..."`.
+pub fn append(path: &Path, block: &str) -> Result<(), String> {
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(block.as_bytes()))
+        .map_err(|e| format!("Failed to write metrics to {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies the exact lines of a block, adding up the counters of
+    /// several sources, and that appending keeps earlier runs.
+    #[test]
+    fn test_render_and_append() {
+        let metrics = Metrics {
+            clone_time: Some(Duration::from_micros(1_234_567)),
+            files_processed: 12,
+            files_skipped: 2,
+            bytes_written: 40_960,
+            total_time: Duration::from_millis(2_500),
+            success: true,
+        };
+        assert_eq!(
+            metrics.render(1_760_400_000),
+            "solscrape.clone_ms 1234 1760400000\n\
+             solscrape.files_processed 12 1760400000\n\
+             solscrape.files_skipped 2 1760400000\n\
+             solscrape.bytes_written 40960 1760400000\n\
+             solscrape.total_ms 2500 1760400000\n\
+             solscrape.success 1 1760400000\n"
+        );
+        let mut sum = Metrics::default();
+        sum.add(&metrics);
+        sum.add(&Metrics {
+            files_processed: 1,
+            ..Metrics::default()
+        });
+        assert_eq!(sum.clone_time, metrics.clone_time);
+        assert_eq!((sum.files_processed, sum.bytes_written), (13, 40_960));
+        assert!(!sum.success);
+
+        let failed = Metrics::default().render(7);
+        assert!(failed.starts_with("solscrape.clone_ms 0 7\n"));
+        assert!(failed.ends_with("solscrape.success 0 7\n"));
+
+        let path =
+            std::env::temp_dir().join(format!("solscrape_metrics_{}.txt", std::process::id()));
+        let _ = fs::remove_file(&path);
+        append(&path, &failed).unwrap();
+        append(&path, &failed).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), failed.repeat(2));
+        let _ = fs::remove_file(&path);
+    }
+}
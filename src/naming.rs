@@ -0,0 +1,465 @@
+//! Output file names that are safe on every platform.
+//!
+//! Windows reserves the device names `CON`, `PRN`, `AUX`, `NUL`, `COM1`–`COM9`,
+//! and `LPT1`–`LPT9` in every directory, with or without an extension, so an
+//! `-o con` or a repository named `nul` would produce an output path that
+//! cannot be created there. Such names are prefixed with `_` on all platforms,
+//! so the same invocation yields the same file name everywhere. Names too
+//! long for the filesystem are cut with a hash suffix by
+//! [`shorten_file_name`].
+//!
+//! Source file names, on the other hand, are never changed, but they can
+//! contain newlines or other control characters that would corrupt headers
+//! and line-oriented listings; [`display_path`] escapes them for display.
+//! Headers also move the version out of Soldeer package directories (see
+//! [`header_path`]) and, with `--related`, note how each file relates to the
+//! target (see [`related_header_path`]); [`parse_header_path`] undoes both.
+//! Paths are made
+//! relative to the scanned tree by [`relative_path`], which never yields an
+//! absolute local path.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::graph::Relation;
+
+/// Device names Windows reserves regardless of extension.
+const RESERVED: [&str; 4] = ["CON", "PRN", "AUX", "NUL"];
+
+/// Returns true if `name` is a reserved Windows device name.
+///
+/// The comparison is case-insensitive and looks only at the part before the
+/// first `.`, ignoring trailing spaces, as Windows does: `con`, `Nul.sol`, and
+/// `com1.txt` are reserved, `console` and `con_scraped.sol` are not.
+pub fn is_reserved_windows_name(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    let upper = stem.to_ascii_uppercase();
+    if RESERVED.contains(&upper.as_str()) {
+        return true;
+    }
+    match upper.as_bytes() {
+        [b'C', b'O', b'M', digit] | [b'L', b'P', b'T', digit] => (b'1'..=b'9').contains(digit),
+        _ => false,
+    }
+}
+
+/// Returns `name` prefixed with `_` if it is reserved (see
+/// [`is_reserved_windows_name`]), or `None` if it can be used as is.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(avoid_reserved_name("aux"), Some("_aux".to_string()));
+/// assert_eq!(avoid_reserved_name("vault"), None);
+/// ```
+pub fn avoid_reserved_name(name: &str) -> Option<String> {
+    is_reserved_windows_name(name).then(|| format!("_{}", name))
+}
+
+/// The most bytes a file written next to an output adds to the output's
+/// name: the `.errors.json` of `--errors-report json`, longer than the
+/// `.sha256` of `--checksum-file` and the `.parts` of `--parts-file` (see
+/// [`crate::checksum::sidecar_path`], [`crate::parts::sidecar_path`], and
+/// [`crate::error_report::Format::sidecar_path`]).
+pub const SIDECAR_SUFFIX_BYTES: usize = ".errors.json".len();
+
+/// The bytes the temporary file an output is written as adds to its name:
+/// a `.` before and `.tmp` after (see [`crate::output::temp_path_for`]).
+const TEMP_NAME_BYTES: usize = ".".len() + ".tmp".len();
+
+/// The longest output file name, in bytes: the 255 common filesystems
+/// accept, less what its temporary file or a sidecar adds, whichever is
+/// more.
+pub const MAX_FILE_NAME_BYTES: usize = 255
+    - if SIDECAR_SUFFIX_BYTES > TEMP_NAME_BYTES {
+        SIDECAR_SUFFIX_BYTES
+    } else {
+        TEMP_NAME_BYTES
+    };
+
+/// Marks where [`shorten_file_name`] cut a name.
+const TRUNCATION_MARK: &str = "…";
+
+/// Fits `stem` followed by `suffix` into [`MAX_FILE_NAME_BYTES`], or returns
+/// `None` if the name already fits.
+///
+/// An over-long stem is cut at a character boundary and followed by `…-`
+/// and six hex digits of a hash of the whole stem, so different long names
+/// sharing a prefix still get different files, and the same name always
+/// gets the same one.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let name = shorten_file_name(&"a".repeat(300), "_scraped.sol").unwrap();
+/// assert!(name.len() <= MAX_FILE_NAME_BYTES);
+/// assert!(name.ends_with("_scraped.sol"));
+/// ```
+pub fn shorten_file_name(stem: &str, suffix: &str) -> Option<String> {
+    if stem.len() + suffix.len() <= MAX_FILE_NAME_BYTES {
+        return None;
+    }
+    let hash = format!("{:06x}", fnv1a(stem.as_bytes()) & 0xff_ffff);
+    let budget =
+        MAX_FILE_NAME_BYTES.saturating_sub(suffix.len() + TRUNCATION_MARK.len() + 1 + hash.len());
+    let mut cut = budget.min(stem.len());
+    while !stem.is_char_boundary(cut) {
+        cut -= 1;
+    }
+    Some(format!(
+        "{}{}-{}{}",
+        &stem[..cut],
+        TRUNCATION_MARK,
+        hash,
+        suffix
+    ))
+}
+
+/// The 64-bit FNV-1a hash of `bytes`, stable across platforms and releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Replaces characters that are awkward in file names (such as the `/` of
+/// `release/v2`) with `-`.
+pub fn file_name_safe(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Escapes a path for line-oriented display such as headers and file lists.
+///
+/// Newlines, carriage returns, and tabs become `\n`, `\r`, and `\t`; other
+/// control characters become `\xNN` (or `\u{NNNN}` above `\xFF`); leading
+/// and trailing spaces, which trimming would lose, become `\x20`. Ordinary
+/// paths are returned unchanged. Backslashes are left alone, since they are
+/// Windows path separators.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(display_path("src/a\nb.sol"), "src/a\\nb.sol");
+/// assert_eq!(display_path(" pad.sol"), "\\x20pad.sol");
+/// ```
+pub fn display_path(path: &str) -> String {
+    let leading = path.len() - path.trim_start_matches(' ').len();
+    let trailing = path.len() - path.trim_end_matches(' ').len();
+    if leading == path.len() {
+        return "\\x20".repeat(path.len());
+    }
+
+    let mut shown = "\\x20".repeat(leading);
+    for c in path[leading..path.len() - trailing].chars() {
+        match c {
+            '\n' => shown.push_str("\\n"),
+            '\r' => shown.push_str("\\r"),
+            '\t' => shown.push_str("\\t"),
+            c if c.is_control() && (c as u32) <= 0xFF => {
+                shown.push_str(&format!("\\x{:02X}", c as u32))
+            }
+            c if c.is_control() => shown.push_str(&format!("\\u{{{:04X}}}", c as u32)),
+            c => shown.push(c),
+        }
+    }
+    shown.push_str(&"\\x20".repeat(trailing));
+    shown
+}
+
+/// Stands in for the directories of a file outside the scanned tree.
+pub const EXTERNAL_PREFIX: &str = "<external>";
+
+/// Expresses `path` relative to `base`, for headers and messages.
+///
+/// Files under `base` get their plain relative path. Others, such as a
+/// dependency reached through an absolute remapping, are expressed with `..`
+/// components when the two share a directory below the filesystem root;
+/// otherwise only the file name is kept, under [`EXTERNAL_PREFIX`]. The local
+/// directory layout, which may include a user name, never leaks into output
+/// that gets shared.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let base = Path::new("/work/project");
+/// assert_eq!(relative_path(Path::new("/work/project/src/A.sol"), base), "src/A.sol");
+/// assert_eq!(relative_path(Path::new("/work/deps/B.sol"), base), "../deps/B.sol");
+/// assert_eq!(relative_path(Path::new("/home/alice/C.sol"), base), "<external>/C.sol");
+/// ```
+pub fn relative_path(path: &Path, base: &Path) -> String {
+    if let Ok(relative) = path.strip_prefix(base) {
+        return relative.to_string_lossy().to_string();
+    }
+
+    let path_parts: Vec<Component> = path.components().collect();
+    let base_parts: Vec<Component> = base.components().collect();
+    let common = path_parts
+        .iter()
+        .zip(&base_parts)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let shares_directory = path_parts[..common]
+        .iter()
+        .any(|c| matches!(c, Component::Normal(_)));
+    let base_rest_is_plain = base_parts[common..]
+        .iter()
+        .all(|c| matches!(c, Component::Normal(_)));
+    let path_rest_is_plain = path_parts[common..]
+        .iter()
+        .all(|c| matches!(c, Component::Normal(_) | Component::ParentDir));
+
+    if shares_directory && base_rest_is_plain && path_rest_is_plain {
+        let mut relative = PathBuf::new();
+        for _ in common..base_parts.len() {
+            relative.push("..");
+        }
+        relative.extend(&path_parts[common..]);
+        return relative.to_string_lossy().to_string();
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    format!("{}/{}", EXTERNAL_PREFIX, name)
+}
+
+/// The directory Soldeer installs packages into, as `<name>-<version>/`.
+pub const SOLDEER_DIR: &str = "dependencies";
+
+/// Marks the version moved out of a Soldeer package directory in a header.
+const VERSION_ANNOTATION: &str = " (version ";
+
+/// Splits a Soldeer package directory name such as `forge-std-1.9.1` into
+/// its name and version.
+///
+/// The version starts at the first `-` followed by a dotted number, or by a
+/// number that ends the name, so `chainlink-v2-1.0.0` yields `chainlink-v2`.
+pub fn split_package_version(dir: &str) -> Option<(&str, &str)> {
+    dir.match_indices('-').find_map(|(i, _)| {
+        let version = &dir[i + 1..];
+        let number = version
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .map_or(version, |end| &version[..end]);
+        let versioned = number.starts_with(|c: char| c.is_ascii_digit())
+            && (number.contains('.') || number.len() == version.len());
+        (versioned && i > 0).then(|| (&dir[..i], version))
+    })
+}
+
+/// The escaped path shown in a file's header.
+///
+/// Files of a Soldeer package show the package without its version, which
+/// is appended instead: `dependencies/forge-std-1.9.1/src/Test.sol` becomes
+/// `dependencies/forge-std/src/Test.sol (version 1.9.1)`. Other paths are
+/// shown as by [`display_path`].
+pub fn header_path(relative: &str) -> String {
+    let mut parts = relative.splitn(3, '/');
+    if let (Some(SOLDEER_DIR), Some(package), Some(rest)) =
+        (parts.next(), parts.next(), parts.next())
+    {
+        if let Some((name, version)) = split_package_version(package) {
+            return format!(
+                "{}{}{})",
+                display_path(&format!("{}/{}/{}", SOLDEER_DIR, name, rest)),
+                VERSION_ANNOTATION,
+                display_path(version)
+            );
+        }
+    }
+    display_path(relative)
+}
+
+/// The header path of a `--related` scrape: [`header_path`] followed by
+/// the file's relation to the target, as in `src/Base.sol (depends-on)`.
+pub fn related_header_path(relative: &str, relation: Relation) -> String {
+    format!("{} ({})", header_path(relative), relation)
+}
+
+/// Reverses [`header_path`] and [`related_header_path`]: the escaped
+/// relative path a header stands for.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let shown = "dependencies/forge-std/src/Test.sol (version 1.9.1)";
+/// assert_eq!(parse_header_path(shown), "dependencies/forge-std-1.9.1/src/Test.sol");
+/// ```
+pub fn parse_header_path(shown: &str) -> String {
+    let shown = Relation::ALL
+        .iter()
+        .find_map(|relation| shown.strip_suffix(&format!(" ({})", relation)))
+        .unwrap_or(shown);
+    let annotated = shown
+        .strip_suffix(')')
+        .and_then(|s| s.rsplit_once(VERSION_ANNOTATION));
+    if let Some((path, version)) = annotated {
+        let mut parts = path.splitn(3, '/');
+        if let (Some(SOLDEER_DIR), Some(name), Some(rest)) =
+            (parts.next(), parts.next(), parts.next())
+        {
+            return format!("{}/{}-{}/{}", SOLDEER_DIR, name, version, rest);
+        }
+    }
+    shown.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies paths outside the base never come out absolute.
+    #[test]
+    fn test_relative_path() {
+        let base = Path::new("/work/project");
+        let cases = [
+            ("/work/project/src/A.sol", "src/A.sol"),
+            ("/work/deps/oz/B.sol", "../deps/oz/B.sol"),
+            ("/work/project/../shared/C.sol", "../shared/C.sol"),
+            ("/home/alice/lib/D.sol", "<external>/D.sol"),
+            ("relative/E.sol", "<external>/E.sol"),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(
+                relative_path(Path::new(path), base),
+                Path::new(expected).to_string_lossy(),
+                "{}",
+                path
+            );
+        }
+        assert_eq!(
+            relative_path(Path::new("src/A.sol"), Path::new("other/dir")),
+            "<external>/A.sol"
+        );
+        assert_eq!(
+            relative_path(Path::new("ws/deps/A.sol"), Path::new("ws/app")),
+            Path::new("../deps/A.sol").to_string_lossy()
+        );
+    }
+
+    /// Verifies reserved names are recognized in any case, with or without extension.
+    #[test]
+    fn test_reserved_windows_names() {
+        for name in [
+            "con", "CON", "Prn", "aux", "nul", "NUL.sol", "com1", "COM9.txt", "lpt5", "con .sol",
+        ] {
+            assert!(is_reserved_windows_name(name), "{}", name);
+        }
+        for name in [
+            "console",
+            "con_scraped.sol",
+            "com0",
+            "com10",
+            "lpt",
+            "nullable",
+            "_con",
+            "",
+        ] {
+            assert!(!is_reserved_windows_name(name), "{}", name);
+        }
+        assert_eq!(avoid_reserved_name("Nul.sol"), Some("_Nul.sol".to_string()));
+        assert_eq!(avoid_reserved_name("vault"), None);
+    }
+
+    /// Verifies over-long names are cut deterministically and stay distinct.
+    #[test]
+    fn test_shorten_file_name() {
+        assert_eq!(shorten_file_name("vault", "_scraped.sol"), None);
+        let fits = "a".repeat(MAX_FILE_NAME_BYTES - "_scraped.sol".len());
+        assert_eq!(shorten_file_name(&fits, "_scraped.sol"), None);
+
+        let long = format!("{}-package-one", "monorepo".repeat(40));
+        let other = format!("{}-package-two", "monorepo".repeat(40));
+        let short = shorten_file_name(&long, "_scraped.sol").unwrap();
+        assert_eq!(short.len(), MAX_FILE_NAME_BYTES);
+        assert!(short.starts_with("monorepomonorepo"));
+        assert!(short.ends_with("_scraped.sol"));
+        assert!(short.contains("…-"));
+        assert_eq!(shorten_file_name(&long, "_scraped.sol").unwrap(), short);
+        assert_ne!(shorten_file_name(&other, "_scraped.sol").unwrap(), short);
+
+        // Multi-byte characters are never split
+        let wide = "é".repeat(200);
+        let short = shorten_file_name(&wide, ".sol").unwrap();
+        assert!(short.len() <= MAX_FILE_NAME_BYTES);
+        assert!(short.ends_with(".sol"));
+    }
+
+    /// Verifies control characters and edge spaces are escaped, and nothing else.
+    #[test]
+    fn test_display_path() {
+        assert_eq!(display_path("src/Token.sol"), "src/Token.sol");
+        assert_eq!(display_path("src/my token.sol"), "src/my token.sol");
+        assert_eq!(display_path("src/a\nb\tc.sol"), "src/a\\nb\\tc.sol");
+        assert_eq!(display_path("src/\u{1b}[31m.sol"), "src/\\x1B[31m.sol");
+        assert_eq!(display_path("\u{85}x"), "\\x85x");
+        assert_eq!(display_path("  pad.sol "), "\\x20\\x20pad.sol\\x20");
+        assert_eq!(display_path(" "), "\\x20");
+        assert_eq!(display_path(r"src\win.sol"), r"src\win.sol");
+    }
+
+    /// Verifies Soldeer versions move into the header and back.
+    #[test]
+    fn test_header_path_soldeer_versions() {
+        let cases = [
+            (
+                "dependencies/@openzeppelin-contracts-5.0.2/token/ERC20/ERC20.sol",
+                "dependencies/@openzeppelin-contracts/token/ERC20/ERC20.sol (version 5.0.2)",
+            ),
+            (
+                "dependencies/forge-std-1.9.1/src/Test.sol",
+                "dependencies/forge-std/src/Test.sol (version 1.9.1)",
+            ),
+            (
+                "dependencies/chainlink-v2-2-1.0.0-rc.1/A.sol",
+                "dependencies/chainlink-v2-2/A.sol (version 1.0.0-rc.1)",
+            ),
+            (
+                "dependencies/solady-7/B.sol",
+                "dependencies/solady/B.sol (version 7)",
+            ),
+        ];
+        for (relative, shown) in cases {
+            assert_eq!(header_path(relative), shown);
+            assert_eq!(parse_header_path(shown), relative);
+        }
+        for (relative, relation, shown) in [
+            (
+                "src/Base.sol",
+                Relation::DependsOn,
+                "src/Base.sol (depends-on)",
+            ),
+            (
+                "dependencies/solady-7/B.sol",
+                Relation::Target,
+                "dependencies/solady/B.sol (version 7) (target)",
+            ),
+        ] {
+            assert_eq!(related_header_path(relative, relation), shown);
+            assert_eq!(parse_header_path(shown), relative);
+        }
+        for unchanged in [
+            "dependencies/unversioned/A.sol",
+            "dependencies/A-1.0.sol",
+            "lib/forge-std-1.9.1/src/Test.sol",
+            "src/Vault (version 2).sol",
+        ] {
+            assert_eq!(header_path(unchanged), unchanged);
+            assert_eq!(parse_header_path(unchanged), unchanged);
+        }
+    }
+
+    /// Verifies awkward characters are replaced.
+    #[test]
+    fn test_file_name_safe() {
+        assert_eq!(file_name_safe("release/v2.0"), "release-v2.0");
+        assert_eq!(file_name_safe("v1.0.0"), "v1.0.0");
+    }
+}
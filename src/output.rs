@@ -0,0 +1,562 @@
+//! Shared output-writer layer.
+//!
+//! Every output solscrape produces goes through [`AtomicFile`] (or the
+//! one-shot [`write_atomic`]), which writes to a hidden temporary file next to
+//! the destination and renames it into place only after the data has been
+//! flushed and synced. A failed write
+//! therefore never leaves a truncated-but-plausible output file behind.
+//! Multi-file output goes through [`StagedDir`], which does the same for a
+//! whole tree.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Size of the chunks written per call, so progress can be counted precisely.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns the temporary path used while writing `path`.
+///
+/// The temporary file lives in the same directory (so the final rename is
+/// atomic) and is hidden: `out/repo_scraped.sol` → `out/.repo_scraped.sol.tmp`.
+pub fn temp_path_for(path: &Path) -> PathBuf {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "output".to_string());
+    path.with_file_name(format!(".{}.tmp", file_name))
+}
+
+/// An output file that only appears at its final path once committed.
+///
+/// Data is written to [`temp_path_for`] the target; [`AtomicFile::commit`]
+/// flushes, syncs, and renames it into place. Dropping an uncommitted
+/// `AtomicFile` (including after a write error) removes the temporary file,
+/// so an existing file at the target path is never disturbed.
+///
+/// # Lifecycle
+///
+/// - **Construction** ([`AtomicFile::create`]): Creates the hidden temporary file
+/// - **Writing** ([`AtomicFile::write_all`]): Appends data, counting bytes written
+/// - **Commit** ([`AtomicFile::commit`]): Makes the output visible atomically
+/// - **Drop**: Removes the temporary file unless committed
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let mut out = AtomicFile::create(Path::new("out/repo_scraped.sol"))?;
+/// for part in parts {
+///     out.write_all(part.as_bytes())?;
+/// }
+/// out.commit()?;
+/// ```
+pub struct AtomicFile {
+    /// The final output path.
+    path: PathBuf,
+    /// The hidden temporary path being written.
+    temp_path: PathBuf,
+    /// The open temporary file; `None` once committed or failed.
+    file: Option<File>,
+    /// Bytes successfully written so far.
+    written: usize,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file for an eventual write to `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to create output file: {e}"` if the temporary file
+    /// cannot be created.
+    pub fn create(path: &Path) -> Result<Self, String> {
+        let temp_path = temp_path_for(path);
+        let file =
+            File::create(&temp_path).map_err(|e| format!("Failed to create output file: {}", e))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            temp_path,
+            file: Some(file),
+            written: 0,
+        })
+    }
+
+    /// Appends `data` to the output.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to write output: {e} ({n} bytes written; partial output
+    /// discarded)"`. After an error the temporary file is removed and every
+    /// further call fails.
+    pub fn write_all(&mut self, data: &[u8]) -> Result<(), String> {
+        self.write_with(data, |file| Box::new(file))
+    }
+
+    /// Implementation of [`AtomicFile::write_all`] with a hook to wrap the
+    /// file writer, letting tests inject a writer that fails part-way through.
+    fn write_with(
+        &mut self,
+        data: &[u8],
+        wrap: impl for<'f> FnOnce(&'f mut File) -> Box<dyn Write + 'f>,
+    ) -> Result<(), String> {
+        let Some(file) = self.file.as_mut() else {
+            return Err("Failed to write output: output file already closed".to_string());
+        };
+
+        let mut writer = wrap(file);
+        let mut result = Ok(());
+        for chunk in data.chunks(CHUNK_SIZE) {
+            if let Err(e) = writer.write_all(chunk) {
+                result = Err(e);
+                break;
+            }
+            self.written += chunk.len();
+        }
+        drop(writer);
+
+        result.map_err(|e| self.fail(e))
+    }
+
+    /// Bytes successfully written so far.
+    pub fn bytes_written(&self) -> usize {
+        self.written
+    }
+
+    /// Flushes, syncs, and renames the output into place.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to write output: ..."` if the flush or sync fails, or
+    /// `"Failed to finalize output file: {e}"` if the rename fails.
+    pub fn commit(mut self) -> Result<(), String> {
+        let Some(mut file) = self.file.take() else {
+            return Err("Failed to write output: output file already closed".to_string());
+        };
+
+        if let Err(e) = file.flush().and_then(|_| file.sync_all()) {
+            drop(file);
+            let _ = fs::remove_file(&self.temp_path);
+            return Err(format!(
+                "Failed to write output: {} ({} bytes written; partial output discarded)",
+                e, self.written
+            ));
+        }
+        drop(file);
+
+        fs::rename(&self.temp_path, &self.path).map_err(|e| {
+            let _ = fs::remove_file(&self.temp_path);
+            format!("Failed to finalize output file: {}", e)
+        })
+    }
+
+    /// Closes and removes the temporary file, describing the write error.
+    fn fail(&mut self, error: io::Error) -> String {
+        self.file = None;
+        let _ = fs::remove_file(&self.temp_path);
+        format!(
+            "Failed to write output: {} ({} bytes written; partial output discarded)",
+            error, self.written
+        )
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if self.file.take().is_some() {
+            let _ = fs::remove_file(&self.temp_path);
+        }
+    }
+}
+
+/// Atomically writes `data` to `path` in one call.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Failed to create output file: {e}"` | The temporary file cannot be created |
+/// | `"Failed to write output: {e} ..."` | A write, flush, or sync failed; includes bytes written |
+/// | `"Failed to finalize output file: {e}"` | The rename into place failed |
+///
+/// On any error the temporary file is removed and an existing file at `path`
+/// is left untouched.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// write_atomic(Path::new("out/repo_scraped.sol"), code.as_bytes())?;
+/// ```
+pub fn write_atomic(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut file = AtomicFile::create(path)?;
+    file.write_all(data)?;
+    file.commit()
+}
+
+/// The staging subdirectory [`StagedDir`] uses inside a non-empty destination.
+pub const STAGING_DIR: &str = ".solscrape-staging";
+
+/// A tree of output files that only appears under its destination once every
+/// file has been written.
+///
+/// Files are written to a staging directory first. When the destination is
+/// missing or empty, the staging directory is its hidden sibling
+/// ([`temp_path_for`]) and [`StagedDir::commit`] renames it into place in one
+/// step. Otherwise it is [`STAGING_DIR`] inside the destination, and commit
+/// renames the files into place one by one, in the order they were written,
+/// so anything written last (a manifest, say) appears last. Dropping an
+/// uncommitted `StagedDir` removes the staging directory, leaving the
+/// destination as it was.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let mut staged = StagedDir::create(Path::new("tree"))?;
+/// staged.write(Path::new("src/Vault.sol"), code.as_bytes())?;
+/// let written = staged.commit()?;
+/// // [PathBuf::from("tree/src/Vault.sol")]
+/// ```
+pub struct StagedDir {
+    /// The directory the files end up in.
+    destination: PathBuf,
+    /// Where the files are written until commit.
+    staging: PathBuf,
+    /// Whether commit renames the staging directory as a whole.
+    whole: bool,
+    /// The relative paths written so far, in order.
+    files: Vec<PathBuf>,
+    /// Set once the files have been moved into place.
+    committed: bool,
+}
+
+impl StagedDir {
+    /// Creates the staging directory for output to `destination`.
+    ///
+    /// A staging directory left behind by an interrupted run is removed first.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to create {path}: {e}"` if the destination cannot be
+    /// read or the staging directory cannot be created.
+    pub fn create(destination: &Path) -> Result<Self, String> {
+        let whole = match fs::read_dir(destination) {
+            Ok(entries) => entries
+                .filter_map(Result::ok)
+                .all(|entry| entry.file_name() == STAGING_DIR),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+            Err(e) => return Err(format!("Failed to create {}: {}", destination.display(), e)),
+        };
+        let staging = if whole {
+            temp_path_for(destination)
+        } else {
+            destination.join(STAGING_DIR)
+        };
+        let _ = fs::remove_dir_all(&staging);
+        fs::create_dir_all(&staging)
+            .map_err(|e| format!("Failed to create {}: {}", staging.display(), e))?;
+        Ok(Self {
+            destination: destination.to_path_buf(),
+            staging,
+            whole,
+            files: Vec::new(),
+            committed: false,
+        })
+    }
+
+    /// Writes `data` to `relative` in the staging directory, creating
+    /// intermediate directories as needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to create {dir}: {e}"`, or the errors of
+    /// [`write_atomic`].
+    pub fn write(&mut self, relative: &Path, data: &[u8]) -> Result<(), String> {
+        self.write_with(relative, data, |file| Box::new(file))
+    }
+
+    /// Implementation of [`StagedDir::write`] with the writer hook of
+    /// [`AtomicFile`], for tests.
+    fn write_with(
+        &mut self,
+        relative: &Path,
+        data: &[u8],
+        wrap: impl for<'f> FnOnce(&'f mut File) -> Box<dyn Write + 'f>,
+    ) -> Result<(), String> {
+        let target = self.staging.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let mut file = AtomicFile::create(&target)?;
+        file.write_with(data, wrap)?;
+        file.commit()?;
+        self.files.push(relative.to_path_buf());
+        Ok(())
+    }
+
+    /// Moves the written files into the destination.
+    ///
+    /// # Returns
+    ///
+    /// The final paths of the files, in the order they were written.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Failed to move {path} into place: {e}"`. When files are
+    /// renamed one by one, the ones before `path` have already been moved.
+    pub fn commit(mut self) -> Result<Vec<PathBuf>, String> {
+        let finals: Vec<PathBuf> = self
+            .files
+            .iter()
+            .map(|relative| self.destination.join(relative))
+            .collect();
+
+        if self.whole {
+            // An empty destination would block the rename
+            let _ = fs::remove_dir(&self.destination);
+            fs::rename(&self.staging, &self.destination).map_err(|e| {
+                format!(
+                    "Failed to move {} into place: {}",
+                    self.destination.display(),
+                    e
+                )
+            })?;
+        } else {
+            for (relative, target) in self.files.iter().zip(&finals) {
+                if let Some(parent) = target.parent() {
+                    fs::create_dir_all(parent)
+                        .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+                }
+                fs::rename(self.staging.join(relative), target).map_err(|e| {
+                    format!("Failed to move {} into place: {}", target.display(), e)
+                })?;
+            }
+            let _ = fs::remove_dir_all(&self.staging);
+        }
+        self.committed = true;
+        Ok(finals)
+    }
+}
+
+impl Drop for StagedDir {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = fs::remove_dir_all(&self.staging);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A writer that fails once more than `limit` bytes have been written.
+    struct FailAfter<W> {
+        inner: W,
+        limit: usize,
+        written: usize,
+    }
+
+    impl<W: Write> Write for FailAfter<W> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.written + buf.len() > self.limit {
+                return Err(io::Error::other("simulated disk full"));
+            }
+            self.written += buf.len();
+            self.inner.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.inner.flush()
+        }
+    }
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("solscrape_output_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Verifies a successful write leaves only the final file behind.
+    #[test]
+    fn test_write_atomic_success() {
+        let dir = test_dir("success");
+        let path = dir.join("repo_scraped.sol");
+
+        write_atomic(&path, b"contract A {}").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "contract A {}");
+        assert!(!temp_path_for(&path).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies a mid-write failure removes the temp file, preserves the old
+    /// output, and reports how many bytes were written.
+    #[test]
+    fn test_write_atomic_failure_discards_partial_output() {
+        let dir = test_dir("failure");
+        let path = dir.join("repo_scraped.sol");
+        fs::write(&path, "previous").unwrap();
+
+        let data = vec![b'x'; CHUNK_SIZE * 3];
+        let mut file = AtomicFile::create(&path).unwrap();
+        let err = file
+            .write_with(&data, |file| {
+                Box::new(FailAfter {
+                    inner: file,
+                    limit: CHUNK_SIZE * 2,
+                    written: 0,
+                })
+            })
+            .unwrap_err();
+
+        assert!(err.contains("simulated disk full"), "{err}");
+        assert!(err.contains(&format!("({} bytes written", CHUNK_SIZE * 2)));
+        assert!(file.write_all(b"more").is_err());
+        assert!(file.commit().is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "previous");
+        assert!(!temp_path_for(&path).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies incremental writes and that dropping without commit leaves nothing behind.
+    #[test]
+    fn test_atomic_file_streaming_and_drop() {
+        let dir = test_dir("streaming");
+        let path = dir.join("repo_scraped.sol");
+
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"contract A {}").unwrap();
+        file.write_all(b"\ncontract B {}").unwrap();
+        assert_eq!(file.bytes_written(), 27);
+        assert!(!path.exists());
+        file.commit().unwrap();
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "contract A {}\ncontract B {}"
+        );
+
+        let other = dir.join("abandoned_scraped.sol");
+        let mut abandoned = AtomicFile::create(&other).unwrap();
+        abandoned.write_all(b"partial").unwrap();
+        drop(abandoned);
+        assert!(!other.exists());
+        assert!(!temp_path_for(&other).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Returns the relative path and content of every file under `dir`,
+    /// sorted by path.
+    fn tree(dir: &Path) -> Vec<(String, String)> {
+        let mut found = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(current) = pending.pop() {
+            for entry in fs::read_dir(&current).unwrap() {
+                let path = entry.unwrap().path();
+                if path.is_dir() {
+                    pending.push(path);
+                } else {
+                    let relative = path
+                        .strip_prefix(dir)
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string();
+                    found.push((relative, fs::read_to_string(&path).unwrap()));
+                }
+            }
+        }
+        found.sort();
+        found
+    }
+
+    /// Verifies both commit strategies put every file in place.
+    #[test]
+    fn test_staged_dir_commit() {
+        let dir = test_dir("staged_commit");
+
+        // Missing destination: the staged directory is renamed as a whole
+        let fresh = dir.join("fresh");
+        let mut staged = StagedDir::create(&fresh).unwrap();
+        staged
+            .write(Path::new("src/A.sol"), b"contract A {}")
+            .unwrap();
+        staged.write(Path::new("B.sol"), b"contract B {}").unwrap();
+        assert!(!fresh.exists());
+        assert_eq!(
+            staged.commit().unwrap(),
+            [fresh.join("src/A.sol"), fresh.join("B.sol")]
+        );
+        assert_eq!(
+            tree(&fresh),
+            [
+                ("B.sol".to_string(), "contract B {}".to_string()),
+                ("src/A.sol".to_string(), "contract A {}".to_string()),
+            ]
+        );
+        assert!(!temp_path_for(&fresh).exists());
+
+        // Non-empty destination: files are renamed one by one, others kept
+        let mut staged = StagedDir::create(&fresh).unwrap();
+        staged
+            .write(Path::new("src/A.sol"), b"contract A2 {}")
+            .unwrap();
+        staged
+            .write(Path::new("lib/C.sol"), b"contract C {}")
+            .unwrap();
+        assert!(fresh.join(STAGING_DIR).is_dir());
+        staged.commit().unwrap();
+        assert_eq!(
+            tree(&fresh),
+            [
+                ("B.sol".to_string(), "contract B {}".to_string()),
+                ("lib/C.sol".to_string(), "contract C {}".to_string()),
+                ("src/A.sol".to_string(), "contract A2 {}".to_string()),
+            ]
+        );
+        assert!(!fresh.join(STAGING_DIR).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies a write failing part-way through a tree leaves the
+    /// destination untouched.
+    #[test]
+    fn test_staged_dir_failure_leaves_destination_untouched() {
+        let dir = test_dir("staged_failure");
+        let existing = dir.join("existing");
+        fs::create_dir_all(existing.join("src")).unwrap();
+        fs::write(existing.join("src/A.sol"), "previous").unwrap();
+
+        for destination in [existing.clone(), dir.join("missing")] {
+            let before = destination.exists().then(|| tree(&destination));
+            let mut staged = StagedDir::create(&destination).unwrap();
+            staged
+                .write(Path::new("src/A.sol"), b"contract A {}")
+                .unwrap();
+            let data = vec![b'x'; CHUNK_SIZE * 2];
+            let err = staged
+                .write_with(Path::new("src/B.sol"), &data, |file| {
+                    Box::new(FailAfter {
+                        inner: file,
+                        limit: CHUNK_SIZE,
+                        written: 0,
+                    })
+                })
+                .unwrap_err();
+            assert!(err.contains("simulated disk full"), "{err}");
+            drop(staged);
+
+            assert_eq!(destination.exists().then(|| tree(&destination)), before);
+            assert!(!temp_path_for(&destination).exists());
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies the temporary file is hidden and lives beside the target.
+    #[test]
+    fn test_temp_path_for() {
+        assert_eq!(
+            temp_path_for(Path::new("out/repo_scraped.sol")),
+            Path::new("out/.repo_scraped.sol.tmp")
+        );
+    }
+}
@@ -0,0 +1,234 @@
+//! Where each file's part lies in consolidated output (`--parts-file`,
+//! `extract`).
+//!
+//! The spans are recorded while the output is written (see [`PartSpan`])
+//! and, with `--parts-file`, kept in a sidecar next to it, `{output}.parts`,
+//! holding a line per part: the byte offset, a tab, the length in bytes, a
+//! tab, and the path as headers show it (see [`display_path`]).
+//! `solscrape extract` seeks to the part the sidecar names instead of
+//! scanning the output for headers, and checks the header it finds there,
+//! so a sidecar left behind by an older output is reported, not trusted.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::naming::display_path;
+use crate::split;
+
+/// The extension appended to the output's file name for its sidecar.
+pub const SIDECAR_EXTENSION: &str = "parts";
+
+/// The bytes of one file's part (header included) in the consolidated output.
+///
+/// Lets a caller seek straight to a file instead of scanning for headers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PartSpan {
+    /// The file's relative path, unescaped.
+    pub path: String,
+    /// The byte offset of the part's first byte.
+    pub offset: usize,
+    /// The part's length in bytes, excluding the newline joining it to the next.
+    pub len: usize,
+}
+
+/// Returns the sidecar path for `output`: its full name plus
+/// `.`[`SIDECAR_EXTENSION`].
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     sidecar_path(Path::new("out/repo_scraped.sol")),
+///     PathBuf::from("out/repo_scraped.sol.parts")
+/// );
+/// ```
+pub fn sidecar_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_os_string();
+    name.push(".");
+    name.push(SIDECAR_EXTENSION);
+    PathBuf::from(name)
+}
+
+/// Writes the sidecar of `output`, holding `parts`, and returns its path.
+///
+/// # Errors
+///
+/// Returns `"Failed to write {sidecar}: ..."`.
+pub fn write_sidecar(output: &Path, parts: &[PartSpan]) -> Result<PathBuf, String> {
+    let text: String = parts
+        .iter()
+        .map(|part| {
+            format!(
+                "{}\t{}\t{}\n",
+                part.offset,
+                part.len,
+                display_path(&part.path)
+            )
+        })
+        .collect();
+    let sidecar = sidecar_path(output);
+    fs::write(&sidecar, text)
+        .map_err(|e| format!("Failed to write {}: {}", sidecar.display(), e))?;
+    Ok(sidecar)
+}
+
+/// Looks up the part of `path` in the sidecar of `output`, or returns
+/// `None` when there is no sidecar.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"{sidecar}:{line}: expected offset, length, and path ..."` | A line is malformed |
+/// | `"{output}: No section for {path}"` | The sidecar lists no part for `path` |
+/// | `"Failed to read {sidecar}: ..."` | The sidecar cannot be read |
+pub fn find(output: &Path, path: &str) -> Result<Option<PartSpan>, String> {
+    let sidecar = sidecar_path(output);
+    let text = match fs::read_to_string(&sidecar) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("Failed to read {}: {}", sidecar.display(), e)),
+    };
+    let wanted = display_path(path);
+    for (i, line) in text.lines().enumerate() {
+        let mut fields = line.splitn(3, '\t');
+        let span = match (fields.next(), fields.next(), fields.next()) {
+            (Some(offset), Some(len), Some(shown)) => offset
+                .parse()
+                .ok()
+                .zip(len.parse().ok())
+                .map(|(offset, len)| (offset, len, shown)),
+            _ => None,
+        };
+        let Some((offset, len, shown)) = span else {
+            return Err(format!(
+                "{}:{}: expected offset, length, and path separated by tabs",
+                sidecar.display(),
+                i + 1
+            ));
+        };
+        if shown == wanted {
+            return Ok(Some(PartSpan {
+                path: path.to_string(),
+                offset,
+                len,
+            }));
+        }
+    }
+    Err(format!("{}: No section for {}", output.display(), wanted))
+}
+
+/// Reads the part at `span` of `output` and returns its content, as
+/// [`split::extract_section`] would.
+///
+/// The part must start with the header of its file and end where the output
+/// or the next part begins; anything else means the output changed since
+/// the sidecar was written.
+///
+/// # Errors
+///
+/// Returns `"{sidecar} does not match {output}; ..."` when the bytes at
+/// `span` are not the part of its file, or `"Failed to read {output}: ..."`.
+pub fn read_section(output: &Path, span: &PartSpan) -> Result<String, String> {
+    let failed = |e: io::Error| format!("Failed to read {}: {}", output.display(), e);
+    let mut file = File::open(output).map_err(failed)?;
+    file.seek(SeekFrom::Start(span.offset as u64))
+        .map_err(failed)?;
+    // One byte more, to check that the part ends there
+    let mut bytes = Vec::with_capacity(span.len + 1);
+    file.take(span.len as u64 + 1)
+        .read_to_end(&mut bytes)
+        .map_err(failed)?;
+    let ends = bytes.len() == span.len || bytes.pop() == Some(b'\n') && bytes.len() == span.len;
+    let wanted = display_path(&span.path);
+    ends.then(|| String::from_utf8(bytes).ok())
+        .flatten()
+        .filter(|part| !part.starts_with(char::is_whitespace))
+        .and_then(|part| split::parse_sections(&part).ok())
+        .and_then(|sections| sections.into_iter().next())
+        .filter(|section| section.path == wanted)
+        .map(|section| section.content)
+        .ok_or_else(|| {
+            format!(
+                "{} does not match {}; scrape again with --parts-file, or remove it to scan \
+                 the headers instead",
+                sidecar_path(output).display(),
+                output.display()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HEADER_FILE_PREFIX;
+    use crate::symbols;
+
+    /// Verifies writing a sidecar, seeking to a part through it, and that a
+    /// changed output is reported.
+    #[test]
+    fn test_sidecar_round_trip() {
+        let dir = std::env::temp_dir().join(format!("solscrape_parts_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("repo_scraped.sol");
+        let separator = symbols::UNICODE.header_separator;
+        let header =
+            |path: &str| format!("{0}\n{1}{2}\n{0}\n", separator, HEADER_FILE_PREFIX, path);
+        let first = format!("{}contract A {{}}", header("src/A.sol"));
+        let second = format!("{}contract B {{}}", header("src/a b\\tc.sol"));
+        fs::write(&output, format!("{}\n{}\n", first, second)).unwrap();
+
+        assert_eq!(find(&output, "src/A.sol").unwrap(), None);
+        let parts = [
+            PartSpan {
+                path: "src/A.sol".to_string(),
+                offset: 0,
+                len: first.len(),
+            },
+            PartSpan {
+                path: "src/a b\tc.sol".to_string(),
+                offset: first.len() + 1,
+                len: second.len(),
+            },
+        ];
+        let sidecar = write_sidecar(&output, &parts).unwrap();
+        assert_eq!(sidecar, dir.join("repo_scraped.sol.parts"));
+        assert_eq!(
+            fs::read_to_string(&sidecar).unwrap(),
+            format!(
+                "0\t{}\tsrc/A.sol\n{}\t{}\tsrc/a b\\tc.sol\n",
+                first.len(),
+                first.len() + 1,
+                second.len()
+            )
+        );
+        let span = find(&output, "src/a b\tc.sol").unwrap().unwrap();
+        assert_eq!(span, parts[1]);
+        assert_eq!(read_section(&output, &span).unwrap(), "contract B {}");
+        assert!(
+            find(&output, "src/C.sol")
+                .unwrap_err()
+                .ends_with("repo_scraped.sol: No section for src/C.sol")
+        );
+
+        // The output changed since, even by one byte: the sidecar is not
+        // trusted
+        for changed in [second.clone(), format!("\n{}\n{}\n", first, second)] {
+            fs::write(&output, changed).unwrap();
+            for part in &parts {
+                assert!(
+                    read_section(&output, part)
+                        .unwrap_err()
+                        .contains("does not match")
+                );
+            }
+        }
+        fs::write(&sidecar, "0 12 src/A.sol\n").unwrap();
+        assert!(find(&output, "src/A.sol").unwrap_err().ends_with(
+            "repo_scraped.sol.parts:1: expected offset, length, and path separated by tabs"
+        ));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,402 @@
+//! Version constraints as written in `pragma solidity` directives.
+//!
+//! Solidity pragmas use npm-style semver ranges: caret (`^0.8.0`), tilde
+//! (`~0.8.1`), comparators (`>=0.7 <0.9`), hyphen ranges (`0.6 - 0.8`),
+//! partial and wildcard versions (`0.8`, `0.8.x`), and `||` alternatives.
+//! A [`Constraint`] parses those forms into half-open version intervals, which
+//! makes "could any compiler satisfy both?" a cheap interval intersection.
+//! Pre-release tags are not part of pragmas in practice and are rejected.
+
+use std::fmt;
+
+/// A `major.minor.patch` compiler version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Version {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl Version {
+    const ZERO: Version = Version::new(0, 0, 0);
+
+    const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// The next version, which bounds an exact match from above.
+    fn next_patch(self) -> Self {
+        Self::new(self.major, self.minor, self.patch + 1)
+    }
+
+    fn next_minor(self) -> Self {
+        Self::new(self.major, self.minor + 1, 0)
+    }
+
+    fn next_major(self) -> Self {
+        Self::new(self.major + 1, 0, 0)
+    }
+}
+
+/// A version as written, with the missing or wildcard parts unset.
+#[derive(Debug, Clone, Copy)]
+struct Partial {
+    major: Option<u64>,
+    minor: Option<u64>,
+    patch: Option<u64>,
+}
+
+impl Partial {
+    /// The lowest version this partial stands for (missing parts are 0).
+    fn floor(self) -> Version {
+        Version::new(
+            self.major.unwrap_or(0),
+            self.minor.unwrap_or(0),
+            self.patch.unwrap_or(0),
+        )
+    }
+
+    /// The first version past everything this partial stands for, or `None`
+    /// for `*`.
+    fn ceiling(self) -> Option<Version> {
+        let floor = self.floor();
+        match (self.major, self.minor, self.patch) {
+            (None, _, _) => None,
+            (Some(_), None, _) => Some(floor.next_major()),
+            (Some(_), Some(_), None) => Some(floor.next_minor()),
+            (Some(_), Some(_), Some(_)) => Some(floor.next_patch()),
+        }
+    }
+}
+
+/// The half-open interval `[low, high)`; `high` of `None` is unbounded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Interval {
+    low: Version,
+    high: Option<Version>,
+}
+
+impl Interval {
+    const ANY: Interval = Interval {
+        low: Version::ZERO,
+        high: None,
+    };
+
+    fn is_empty(self) -> bool {
+        self.high.is_some_and(|high| high <= self.low)
+    }
+
+    fn intersect(self, other: Interval) -> Interval {
+        let high = match (self.high, other.high) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        Interval {
+            low: self.low.max(other.low),
+            high,
+        }
+    }
+}
+
+/// A parsed version constraint: the union of its `||` alternatives.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let wanted = Constraint::parse("0.8")?;
+/// assert!(wanted.intersects(&Constraint::parse(">=0.7.0 <0.9.0")?));
+/// assert!(!wanted.intersects(&Constraint::parse("^0.7.6")?));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Constraint {
+    /// The constraint as written, for messages.
+    text: String,
+    /// Non-empty alternatives.
+    alternatives: Vec<Interval>,
+}
+
+impl Constraint {
+    /// Parses a pragma-style constraint such as `^0.8.0` or `>=0.7 <0.9`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Invalid version constraint: {text} ({reason})"` for empty
+    /// input, unknown operators, and malformed or pre-release versions.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("Invalid version constraint: {} ({})", text, reason);
+        if text.trim().is_empty() {
+            return Err(invalid("empty"));
+        }
+
+        let mut alternatives = Vec::new();
+        for alternative in text.split("||") {
+            let interval = parse_alternative(alternative).map_err(|e| invalid(&e))?;
+            if !interval.is_empty() {
+                alternatives.push(interval);
+            }
+        }
+        Ok(Self {
+            text: text.trim().to_string(),
+            alternatives,
+        })
+    }
+
+    /// The constraint `>=version`, for a plain version such as `0.6` or
+    /// `0.8.20`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `"Invalid version: {version} ..."` for operators, wildcards,
+    /// and malformed versions.
+    pub fn at_least(version: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid version: {} (expected e.g. 0.6 or 0.8.20)", version);
+        let partial = parse_partial(version.trim()).map_err(|_| invalid())?;
+        if partial.major.is_none() || version.trim().ends_with(['x', 'X', '*']) {
+            return Err(invalid());
+        }
+        Ok(Self {
+            text: format!(">={}", version.trim()),
+            alternatives: vec![Interval {
+                low: partial.floor(),
+                high: None,
+            }],
+        })
+    }
+
+    /// Returns true if some version satisfies both constraints.
+    pub fn intersects(&self, other: &Constraint) -> bool {
+        self.alternatives.iter().any(|a| {
+            other
+                .alternatives
+                .iter()
+                .any(|b| !a.intersect(*b).is_empty())
+        })
+    }
+
+    /// The versions satisfying both constraints.
+    ///
+    /// Used to combine several pragmas in one file, which must all hold.
+    pub fn and(&self, other: &Constraint) -> Constraint {
+        let alternatives = self
+            .alternatives
+            .iter()
+            .flat_map(|a| other.alternatives.iter().map(|b| a.intersect(*b)))
+            .filter(|i| !i.is_empty())
+            .collect();
+        Constraint {
+            text: format!("{} {}", self.text, other.text),
+            alternatives,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// Parses one `||` alternative: a hyphen range or a list of comparators.
+fn parse_alternative(text: &str) -> Result<Interval, String> {
+    let tokens = join_operators(text);
+    if tokens.is_empty() {
+        return Err("empty alternative".to_string());
+    }
+
+    if let [from, dash, to] = tokens.as_slice() {
+        if dash == "-" {
+            return Ok(Interval {
+                low: parse_partial(from)?.floor(),
+                high: parse_partial(to)?.ceiling(),
+            });
+        }
+    }
+
+    tokens.iter().try_fold(Interval::ANY, |acc, token| {
+        Ok(acc.intersect(comparator(token)?))
+    })
+}
+
+/// Splits on whitespace, reattaching operators written apart from their
+/// version (`>= 0.7` becomes `>=0.7`).
+fn join_operators(text: &str) -> Vec<String> {
+    let mut tokens: Vec<String> = Vec::new();
+    let mut pending = String::new();
+    for word in text.split_whitespace() {
+        if matches!(word, "^" | "~" | ">" | ">=" | "<" | "<=" | "=") {
+            pending.push_str(word);
+        } else {
+            tokens.push(format!("{}{}", pending, word));
+            pending.clear();
+        }
+    }
+    if !pending.is_empty() {
+        tokens.push(pending);
+    }
+    tokens
+}
+
+/// Converts one comparator (`^0.8.0`, `<0.9`, `0.8.x`, ...) to an interval.
+fn comparator(token: &str) -> Result<Interval, String> {
+    let split = token
+        .find(|c: char| c.is_ascii_alphanumeric() || c == '*')
+        .ok_or_else(|| format!("missing version after `{}`", token))?;
+    let (operator, version) = token.split_at(split);
+    let partial = parse_partial(version)?;
+    let floor = partial.floor();
+
+    let (low, high) = match operator {
+        "" | "=" => (floor, partial.ceiling()),
+        ">=" => (floor, None),
+        ">" => match partial.ceiling() {
+            Some(ceiling) => (ceiling, None),
+            // `>*` matches nothing
+            None => (Version::ZERO, Some(Version::ZERO)),
+        },
+        "<" => (Version::ZERO, Some(floor)),
+        "<=" => (Version::ZERO, partial.ceiling()),
+        "~" => (
+            floor,
+            match partial.minor {
+                Some(_) => Some(floor.next_minor()),
+                None => partial.ceiling(),
+            },
+        ),
+        "^" => (floor, caret_ceiling(partial)),
+        _ => return Err(format!("unknown operator `{}`", operator)),
+    };
+    Ok(Interval { low, high })
+}
+
+/// The exclusive upper bound of `^partial`: the next change to the leftmost
+/// non-zero part that was written.
+fn caret_ceiling(partial: Partial) -> Option<Version> {
+    let floor = partial.floor();
+    match (partial.major, partial.minor, partial.patch) {
+        (None, _, _) => None,
+        (Some(major), _, _) if major > 0 => Some(floor.next_major()),
+        (Some(_), None, _) => Some(floor.next_major()),
+        (Some(_), Some(minor), _) if minor > 0 => Some(floor.next_minor()),
+        (Some(_), Some(_), None) => Some(floor.next_minor()),
+        (Some(_), Some(_), Some(_)) => Some(floor.next_patch()),
+    }
+}
+
+/// Parses `1`, `0.8`, `0.8.20`, with `x`, `X`, or `*` as wildcards.
+fn parse_partial(text: &str) -> Result<Partial, String> {
+    let text = text.strip_prefix(['v', 'V']).unwrap_or(text);
+    let parts: Vec<&str> = text.split('.').collect();
+    if parts.len() > 3 {
+        return Err(format!("too many version parts in `{}`", text));
+    }
+
+    let mut numbers = [None; 3];
+    for (slot, part) in numbers.iter_mut().zip(&parts) {
+        *slot = match *part {
+            "x" | "X" | "*" => break,
+            _ => Some(
+                part.parse::<u64>()
+                    .map_err(|_| format!("`{}` is not a version", text))?,
+            ),
+        };
+    }
+    Ok(Partial {
+        major: numbers[0],
+        minor: numbers[0].and(numbers[1]),
+        patch: numbers[0].and(numbers[1]).and(numbers[2]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies intersection across the forms used in real pragmas.
+    #[test]
+    fn test_constraint_intersections() {
+        let cases = [
+            ("0.8", "^0.8.0", true),
+            ("0.8", "^0.7.6", false),
+            ("0.8", ">=0.7.0 <0.9.0", true),
+            ("0.8", ">=0.6.0 <0.8.0", false),
+            ("0.8", ">=0.6.0 <=0.8.0", true),
+            ("0.8", "0.8.20", true),
+            ("0.8", "=0.7.6", false),
+            ("0.8", ">0.7.6", true),
+            ("0.8", ">0.8", false),
+            ("0.8", "~0.8.4", true),
+            ("0.8", "0.8.x", true),
+            ("0.8", "*", true),
+            ("0.8", ">= 0.5.0 < 0.7.0 || ^0.8.0", true),
+            ("0.8", "0.6.0 - 0.7", false),
+            ("^0.8.0", "0.6.0 - 0.8", true),
+            ("^0.5.0", "^0.6.0", false),
+            ("^0.0.3", "0.0.4", false),
+            (">=0.7 <0.9", "^0.7.6", true),
+            (">=0.7 <0.9", "^0.9.0", false),
+            ("^1.2", "1.9.9", true),
+            ("^1.2", "2.0.0", false),
+            ("~0.8", "0.8.99", true),
+            ("~0.8.1", "0.8.0", false),
+            (">=0.8.0", ">=0.4.22 <0.8.0", false),
+        ];
+
+        for (wanted, pragma, expected) in cases {
+            let a = Constraint::parse(wanted).unwrap();
+            let b = Constraint::parse(pragma).unwrap();
+            assert_eq!(a.intersects(&b), expected, "{} vs {}", wanted, pragma);
+            assert_eq!(b.intersects(&a), expected, "{} vs {}", pragma, wanted);
+        }
+    }
+
+    /// Verifies that combined pragmas must all hold.
+    #[test]
+    fn test_constraint_and() {
+        let file = Constraint::parse(">=0.6.0")
+            .unwrap()
+            .and(&Constraint::parse("<0.8.0").unwrap());
+        assert!(file.intersects(&Constraint::parse("0.7").unwrap()));
+        assert!(!file.intersects(&Constraint::parse("0.8").unwrap()));
+        assert!(
+            !Constraint::parse("^0.8.0")
+                .unwrap()
+                .and(&file)
+                .intersects(&file)
+        );
+    }
+
+    /// Verifies `at_least` against old and current pragmas.
+    #[test]
+    fn test_constraint_at_least() {
+        let min = Constraint::at_least("0.6").unwrap();
+        assert_eq!(min.to_string(), ">=0.6");
+        for (pragma, expected) in [
+            ("0.4.24", false),
+            ("^0.5.0", false),
+            (">=0.4.22 <0.6.0", false),
+            (">=0.4.22 <0.9.0", true),
+            ("^0.6.12", true),
+            ("0.6.0", true),
+        ] {
+            let pragma = Constraint::parse(pragma).unwrap();
+            assert_eq!(min.intersects(&pragma), expected, "{}", pragma);
+        }
+        for bad in ["", "^0.6", ">=0.6", "0.x", "*", "six"] {
+            assert!(Constraint::at_least(bad).is_err(), "{}", bad);
+        }
+    }
+
+    /// Verifies malformed constraints are rejected with the input quoted.
+    #[test]
+    fn test_constraint_parse_errors() {
+        for bad in ["", "abc", "0.8.0-beta", "!=0.8", ">=", "1.2.3.4"] {
+            let err = Constraint::parse(bad).unwrap_err();
+            assert!(err.starts_with("Invalid version constraint"), "{}", err);
+        }
+        assert_eq!(Constraint::parse(" ^0.8.0 ").unwrap().to_string(), "^0.8.0");
+    }
+}
@@ -0,0 +1,436 @@
+//! A cheap look at a remote repository before cloning it.
+//!
+//! Scraping a repository that holds no Solidity wastes a full clone. The
+//! pre-check fetches only the tree of the remote's default branch (a shallow,
+//! blob-less clone, so no file contents are downloaded) and [`assess`]es its
+//! paths. When nothing suggests Solidity, the user is asked via [`confirm`]
+//! before the real clone starts.
+//!
+//! Git cannot tell how big a repository is before cloning it, but GitHub
+//! and GitLab report a size through their APIs. [`probe_size`] asks for it
+//! with `curl` where possible, and [`decide_size`] weighs the answer against
+//! `--max-clone-size`.
+
+use std::io::{self, BufRead, Write};
+
+use crate::exec::Exec;
+use crate::forge_host::{self, Forge, RepoUrl};
+use crate::logger::Logger;
+use crate::project::{self, ProjectKind};
+use crate::space;
+
+/// The reported size above which a clone is warned about when no
+/// `--max-clone-size` is set.
+pub const LARGE_CLONE_WARNING: u64 = 1 << 30;
+
+/// How long the size probe may take, in seconds.
+const PROBE_TIMEOUT_SECS: &str = "10";
+
+/// What a remote tree listing suggests about a repository.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assessment {
+    /// The number of files in the listing.
+    pub paths: usize,
+    /// The number of `.sol` files among them.
+    pub solidity_files: usize,
+    /// Frameworks recognized from top-level marker files.
+    pub project: ProjectKind,
+}
+
+impl Assessment {
+    /// Returns true if the repository plausibly contains Solidity sources.
+    pub fn looks_like_solidity(&self) -> bool {
+        self.solidity_files > 0
+    }
+
+    /// Describes why the repository looks unlikely to contain Solidity, or
+    /// `None` if it looks fine.
+    pub fn concern(&self, url: &str) -> Option<String> {
+        if self.looks_like_solidity() {
+            return None;
+        }
+        if self.paths == 0 {
+            return Some(format!("{} has no files on its default branch", url));
+        }
+        let mut message = format!(
+            "{} has no .sol files among {} path{} on its default branch",
+            url,
+            self.paths,
+            if self.paths == 1 { "" } else { "s" }
+        );
+        if !self.project.is_unknown() {
+            message.push_str(&format!(" (despite {} markers)", self.project));
+        }
+        Some(message)
+    }
+}
+
+/// Assesses the file paths of a repository tree, as listed by
+/// `git ls-tree -r --name-only`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let assessment = assess(&["README.md", "src/Token.sol"]);
+/// assert!(assessment.looks_like_solidity());
+/// ```
+pub fn assess<S: AsRef<str>>(paths: &[S]) -> Assessment {
+    let top_level: Vec<&str> = paths
+        .iter()
+        .map(|p| p.as_ref())
+        .filter(|p| !p.contains('/'))
+        .collect();
+    Assessment {
+        paths: paths.len(),
+        solidity_files: paths
+            .iter()
+            .filter(|p| p.as_ref().ends_with(".sol"))
+            .count(),
+        project: project::project_kind_from_names(&top_level),
+    }
+}
+
+/// Returns true if `url` is an HTTPS URL on github.com, where the pre-check
+/// runs by default.
+pub fn is_github_https(url: &str) -> bool {
+    url.starts_with("https://") && forge_host::parse(url).forge == Forge::GitHub
+}
+
+/// Returns the API URL reporting the size of `repo`, or `None` for hosts
+/// without one.
+///
+/// GitHub answers for `owner/name` repositories; GitLab, self-hosted
+/// included, for any project path when its statistics are visible.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let api = size_api_url(&forge_host::parse("git@github.com:acme/vault.git"));
+/// assert_eq!(api.as_deref(), Some("https://api.github.com/repos/acme/vault"));
+/// ```
+pub fn size_api_url(repo: &RepoUrl) -> Option<String> {
+    match (repo.forge, repo.segments.as_slice()) {
+        (Forge::GitHub, [owner, name]) => {
+            Some(format!("https://api.github.com/repos/{}/{}", owner, name))
+        }
+        (Forge::GitLab, [_, _, ..]) => Some(format!(
+            "https://{}/api/v4/projects/{}?statistics=true",
+            repo.host,
+            repo.segments.join("%2F")
+        )),
+        _ => None,
+    }
+}
+
+/// Extracts a repository's size in bytes from the response of its
+/// [`size_api_url`].
+///
+/// GitHub reports `size` in KiB; GitLab reports `repository_size` in bytes.
+/// Only the first occurrence of the field counts, which in both responses
+/// is the repository's own.
+pub fn parse_size_response(forge: Forge, body: &str) -> Option<u64> {
+    let (field, multiplier) = match forge {
+        Forge::GitHub => ("\"size\"", 1024),
+        Forge::GitLab => ("\"repository_size\"", 1),
+        Forge::Bitbucket | Forge::Other => return None,
+    };
+    let rest = body[body.find(field)? + field.len()..].trim_start();
+    let value = rest.strip_prefix(':')?.trim_start();
+    let digits = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    value[..digits].parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Asks the host of `url` how big the repository is.
+///
+/// Returns `None`, so that the clone goes ahead as usual, when the host has
+/// no [`size_api_url`], `exec` does not allow `curl`, or the request or its
+/// response fails in any way, for example on a private repository.
+pub fn probe_size(url: &str, exec: &Exec, log: &Logger) -> Option<u64> {
+    let repo = forge_host::parse(url);
+    let api = size_api_url(&repo)?;
+    if !exec.allows("curl") {
+        log.detail("Skipping clone size probe: curl is not allowed");
+        return None;
+    }
+    let output = exec
+        .output(
+            "curl",
+            ["-fsSL", "--max-time", PROBE_TIMEOUT_SECS, api.as_str()],
+            None,
+            log,
+        )
+        .inspect_err(|e| log.detail(&format!("Clone size probe failed: {}", e)))
+        .ok()?;
+    if !output.status.success() {
+        log.detail(&format!(
+            "Clone size probe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+        return None;
+    }
+    parse_size_response(repo.forge, &String::from_utf8_lossy(&output.stdout))
+}
+
+/// What to do about a clone, given its probed size.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SizeDecision {
+    /// Clone without comment.
+    Proceed,
+    /// Clone, after printing the message.
+    Warn(String),
+    /// Ask whether to clone despite the concern, and clone only if the user
+    /// agrees.
+    Ask(String),
+    /// Do not clone; the message says how to.
+    Refuse(String),
+}
+
+/// Weighs the probed `size` of `url` against the `limit` set with
+/// `--max-clone-size`.
+///
+/// An unknown size always proceeds. Without a limit, only a size above
+/// [`LARGE_CLONE_WARNING`] is warned about. Over the limit, `assume_yes`
+/// (`--yes`) clones with a warning, an `interactive` run asks, and any
+/// other run is refused.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let decision = decide_size(url, Some(3 << 30), Some(1 << 30), false, false);
+/// assert!(matches!(decision, SizeDecision::Refuse(_)));
+/// ```
+pub fn decide_size(
+    url: &str,
+    size: Option<u64>,
+    limit: Option<u64>,
+    assume_yes: bool,
+    interactive: bool,
+) -> SizeDecision {
+    let Some(size) = size else {
+        return SizeDecision::Proceed;
+    };
+    let reported = format!(
+        "{} reports a repository size of {}",
+        url,
+        space::format_size(size)
+    );
+    let Some(limit) = limit else {
+        return if size > LARGE_CLONE_WARNING {
+            SizeDecision::Warn(format!(
+                "{}; pass --max-clone-size to be asked before cloning repositories this large",
+                reported
+            ))
+        } else {
+            SizeDecision::Proceed
+        };
+    };
+    if size <= limit {
+        return SizeDecision::Proceed;
+    }
+    let over = format!(
+        "{}, over --max-clone-size {}",
+        reported,
+        space::format_size(limit)
+    );
+    if assume_yes {
+        SizeDecision::Warn(format!("{}; cloning anyway", over))
+    } else if interactive {
+        SizeDecision::Ask(over)
+    } else {
+        SizeDecision::Refuse(format!(
+            "{}. Raise --max-clone-size, or pass --yes to clone anyway",
+            over
+        ))
+    }
+}
+
+/// Asks a yes/no `question` on `output` and reads the answer from `input`.
+///
+/// Only `y` or `yes` (in any case) confirms; an empty answer or end of input
+/// declines.
+///
+/// # Errors
+///
+/// Returns the underlying error if writing the question or reading the
+/// answer fails.
+pub fn confirm(
+    question: &str,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+) -> io::Result<bool> {
+    write!(output, "{} [y/N] ", question)?;
+    output.flush()?;
+    let mut answer = String::new();
+    input.read_line(&mut answer)?;
+    Ok(matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "y" | "yes"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies listings with and without Solidity sources.
+    #[test]
+    fn test_assess() {
+        let solidity = assess(&["foundry.toml", "src/Vault.sol", "README.md"]);
+        assert!(solidity.looks_like_solidity());
+        assert_eq!(solidity.solidity_files, 1);
+        assert!(solidity.project.is_foundry());
+        assert_eq!(solidity.concern("u"), None);
+
+        let web = assess(&["package.json", "src/index.ts", "README.md"]);
+        assert!(!web.looks_like_solidity());
+        assert_eq!(
+            web.concern("https://github.com/a/b").unwrap(),
+            "https://github.com/a/b has no .sol files among 3 paths on its default branch"
+        );
+
+        let marked = assess(&[
+            "hardhat.config.ts",
+            "contracts/solidity/nested/hardhat.config.js",
+        ]);
+        assert!(
+            marked
+                .concern("u")
+                .unwrap()
+                .ends_with("(despite Hardhat project markers)")
+        );
+
+        let empty: [&str; 0] = [];
+        assert_eq!(
+            assess(&empty).concern("u").unwrap(),
+            "u has no files on its default branch"
+        );
+    }
+
+    /// Verifies which URLs get the default pre-check.
+    #[test]
+    fn test_is_github_https() {
+        assert!(is_github_https(
+            "https://github.com/foundry-rs/forge-std.git"
+        ));
+        assert!(is_github_https("https://token@GitHub.com/a/b"));
+        assert!(!is_github_https("git@github.com:a/b.git"));
+        assert!(!is_github_https("https://gitlab.com/a/b"));
+        assert!(!is_github_https("https://github.com.evil.example/a/b"));
+        assert!(!is_github_https("file:///srv/repo.git"));
+    }
+
+    /// Verifies the API URLs asked for a repository's size.
+    #[test]
+    fn test_size_api_url() {
+        let api = |url: &str| size_api_url(&forge_host::parse(url));
+        assert_eq!(
+            api("https://github.com/acme/vault.git").as_deref(),
+            Some("https://api.github.com/repos/acme/vault")
+        );
+        assert_eq!(
+            api("git@github.com:acme/vault.git").as_deref(),
+            Some("https://api.github.com/repos/acme/vault")
+        );
+        assert_eq!(
+            api("https://gitlab.example.org/group/sub/vault").as_deref(),
+            Some("https://gitlab.example.org/api/v4/projects/group%2Fsub%2Fvault?statistics=true")
+        );
+        assert_eq!(api("https://github.com/acme"), None);
+        assert_eq!(api("https://bitbucket.org/acme/vault"), None);
+        assert_eq!(api("file:///srv/vault.git"), None);
+    }
+
+    /// Verifies reading the size out of API responses.
+    #[test]
+    fn test_parse_size_response() {
+        let github = r#"{"id":1,"owner":{"login":"acme"},"size": 2048,"parent":{"size":1}}"#;
+        assert_eq!(parse_size_response(Forge::GitHub, github), Some(2 << 20));
+        let gitlab = r#"{"statistics":{"commit_count":3,"repository_size":5000}}"#;
+        assert_eq!(parse_size_response(Forge::GitLab, gitlab), Some(5000));
+        assert_eq!(parse_size_response(Forge::GitLab, r#"{"id":1}"#), None);
+        assert_eq!(parse_size_response(Forge::GitHub, r#"{"size":null}"#), None);
+        assert_eq!(parse_size_response(Forge::Other, github), None);
+    }
+
+    /// Verifies every outcome of the size decision with stubbed probe results.
+    #[test]
+    fn test_decide_size() {
+        const GIB: u64 = 1 << 30;
+        let url = "https://github.com/acme/monorepo";
+        assert_eq!(
+            decide_size(url, None, Some(1), false, false),
+            SizeDecision::Proceed
+        );
+        assert_eq!(
+            decide_size(url, Some(GIB), None, false, false),
+            SizeDecision::Proceed
+        );
+        assert_eq!(
+            decide_size(url, Some(2 * GIB), None, false, false),
+            SizeDecision::Warn(
+                "https://github.com/acme/monorepo reports a repository size of 2.0 GiB; \
+                 pass --max-clone-size to be asked before cloning repositories this large"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            decide_size(url, Some(2 * GIB), Some(2 * GIB), false, false),
+            SizeDecision::Proceed
+        );
+
+        let over = "https://github.com/acme/monorepo reports a repository size of 3.0 GiB, \
+                    over --max-clone-size 512.0 MiB";
+        let limit = Some(GIB / 2);
+        assert_eq!(
+            decide_size(url, Some(3 * GIB), limit, true, true),
+            SizeDecision::Warn(format!("{}; cloning anyway", over))
+        );
+        assert_eq!(
+            decide_size(url, Some(3 * GIB), limit, false, true),
+            SizeDecision::Ask(over.to_string())
+        );
+        assert_eq!(
+            decide_size(url, Some(3 * GIB), limit, false, false),
+            SizeDecision::Refuse(format!(
+                "{}. Raise --max-clone-size, or pass --yes to clone anyway",
+                over
+            ))
+        );
+    }
+
+    /// Verifies that the probe is skipped where it cannot run.
+    #[test]
+    fn test_probe_size_skipped() {
+        let log = Logger::new();
+        let exec = Exec::restricted(&["git"]);
+        assert_eq!(
+            probe_size("https://github.com/acme/vault", &exec, &log),
+            None
+        );
+        assert!(exec.invocations().is_empty());
+        assert_eq!(
+            probe_size("file:///srv/vault.git", &Exec::unrestricted(), &log),
+            None
+        );
+    }
+
+    /// Verifies answers and the default of declining.
+    #[test]
+    fn test_confirm() {
+        for (answer, expected) in [
+            ("y\n", true),
+            ("YES\n", true),
+            ("n\n", false),
+            ("\n", false),
+            ("", false),
+            ("sure\n", false),
+        ] {
+            let mut output = Vec::new();
+            let confirmed = confirm("Clone anyway?", &mut answer.as_bytes(), &mut output).unwrap();
+            assert_eq!(confirmed, expected, "{:?}", answer);
+            assert_eq!(String::from_utf8(output).unwrap(), "Clone anyway? [y/N] ");
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Structured progress events for front ends.
+//!
+//! Scraping reports its progress as [`ProgressEvent`]s delivered to observers
+//! registered on the [`Logger`](crate::logger::Logger), so wrappers such as
+//! GUIs or TUIs can follow a run without parsing terminal output. The CLI's
+//! own progress lines are produced by [`console_progress`], an observer like
+//! any other.
+
+use std::path::PathBuf;
+
+use crate::naming::display_path;
+
+/// A step in a scrape, in the order events are emitted.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// A clone of `url` into a temporary directory is starting.
+    CloneStarted {
+        /// The repository being cloned.
+        url: String,
+    },
+    /// The clone finished successfully.
+    CloneFinished,
+    /// The submodules of a clone were initialized for `--include-lib`;
+    /// the rest are looked for in the Foundry cache.
+    SubmodulesInitialized {
+        /// The number initialized.
+        initialized: usize,
+        /// The number that were empty after the clone.
+        total: usize,
+    },
+    /// A local directory is about to be scanned in place.
+    ScanStarted {
+        /// The directory being scanned.
+        root: PathBuf,
+    },
+    /// Discovered files are about to be processed.
+    ProcessingStarted {
+        /// The number of candidate files, or `None` when streaming.
+        total: Option<usize>,
+    },
+    /// One candidate file was processed (included, empty, or skipped).
+    FileProcessed {
+        /// 1-based position among the candidate files.
+        index: usize,
+        /// The number of candidate files, or `None` when streaming.
+        total: Option<usize>,
+        /// The file's path relative to the source root, unescaped.
+        path: String,
+    },
+    /// The consolidated output is being written.
+    WritingOutput {
+        /// The output file.
+        path: PathBuf,
+    },
+    /// The scrape completed.
+    Finished {
+        /// The number of files included in the output.
+        files: usize,
+        /// The number of lines in the output.
+        lines: usize,
+        /// The output file.
+        output: PathBuf,
+    },
+}
+
+/// Returns the observer printing the CLI's progress lines to stdout, or to
+/// stderr with `stderr_only`.
+///
+/// Prints nothing in quiet mode. Per-file events are only shown with
+/// `verbose`, as a `[index/total] path` counter on stderr.
+pub fn console_progress(quiet: bool, verbose: bool, stderr_only: bool) -> impl Fn(&ProgressEvent) {
+    let say = move |line: &str| {
+        if stderr_only {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    };
+    move |event| {
+        if quiet {
+            return;
+        }
+        match event {
+            ProgressEvent::CloneStarted { .. } => say("Cloning repository..."),
+            ProgressEvent::SubmodulesInitialized { initialized, total } => say(&format!(
+                "Initialized {} of {} submodule{}",
+                initialized,
+                total,
+                if *total == 1 { "" } else { "s" }
+            )),
+            ProgressEvent::ScanStarted { .. } => say("Scanning local directory..."),
+            ProgressEvent::ProcessingStarted { .. } => say("Processing files..."),
+            ProgressEvent::FileProcessed { index, total, path } if verbose => match total {
+                Some(total) => eprintln!("[{}/{}] {}", index, total, display_path(path)),
+                None => eprintln!("[{}] {}", index, display_path(path)),
+            },
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! Detection of the Solidity framework a source tree is laid out for.
+//!
+//! Knowing whether a tree is a Foundry, Hardhat, or Truffle project lets the
+//! scraper pick sensible defaults (such as skipping Foundry's `*.t.sol` test
+//! files or honoring Hardhat's `paths.sources`) and lets the summary tell the
+//! user what it found.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// A Solidity development framework recognized by its marker files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framework {
+    /// `foundry.toml` or `remappings.txt`.
+    Foundry,
+    /// `hardhat.config.*` (`.js`, `.ts`, `.cjs`, ...).
+    Hardhat,
+    /// `truffle-config.js` or the legacy `truffle.js`.
+    Truffle,
+}
+
+impl Framework {
+    /// The framework's display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            Framework::Foundry => "Foundry",
+            Framework::Hardhat => "Hardhat",
+            Framework::Truffle => "Truffle",
+        }
+    }
+}
+
+/// The frameworks detected at a project root.
+///
+/// Repositories may carry markers for more than one framework (for example,
+/// Hardhat projects using the Foundry plugin); all of them are reported.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProjectKind {
+    /// Detected frameworks, in [`Framework`] declaration order.
+    pub frameworks: Vec<Framework>,
+}
+
+impl ProjectKind {
+    /// Returns true if no framework markers were found.
+    pub fn is_unknown(&self) -> bool {
+        self.frameworks.is_empty()
+    }
+
+    /// Returns true if Foundry markers were found.
+    pub fn is_foundry(&self) -> bool {
+        self.frameworks.contains(&Framework::Foundry)
+    }
+}
+
+impl fmt::Display for ProjectKind {
+    /// Formats as `"Foundry project"`, `"Foundry + Hardhat project"`, or
+    /// `"unrecognized project layout"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_unknown() {
+            return write!(f, "unrecognized project layout");
+        }
+        let names: Vec<&str> = self.frameworks.iter().map(|k| k.name()).collect();
+        write!(f, "{} project", names.join(" + "))
+    }
+}
+
+/// Detects which frameworks `root` is laid out for.
+///
+/// Only the top level of `root` is inspected; unreadable directories are
+/// treated as having no markers.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let kind = detect_project_kind(Path::new("./my-project"));
+/// println!("Detected: {}", kind); // Detected: Foundry project
+/// ```
+pub fn detect_project_kind(root: &Path) -> ProjectKind {
+    let names: Vec<String> = fs::read_dir(root)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    project_kind_from_names(&names)
+}
+
+/// Detects frameworks from the file names at a project root.
+///
+/// The filesystem-free core of [`detect_project_kind`], also used on remote
+/// tree listings before anything is checked out.
+pub fn project_kind_from_names<S: AsRef<str>>(names: &[S]) -> ProjectKind {
+    let has = |predicate: &dyn Fn(&str) -> bool| names.iter().any(|n| predicate(n.as_ref()));
+
+    let mut frameworks = Vec::new();
+    if has(&|n| n == "foundry.toml" || n == "remappings.txt") {
+        frameworks.push(Framework::Foundry);
+    }
+    if has(&|n| n.starts_with("hardhat.config.")) {
+        frameworks.push(Framework::Hardhat);
+    }
+    if has(&|n| n == "truffle-config.js" || n == "truffle.js") {
+        frameworks.push(Framework::Truffle);
+    }
+
+    ProjectKind { frameworks }
+}
+
+/// Hardhat config file names, in the order Hardhat itself prefers them.
+const HARDHAT_CONFIGS: [&str; 4] = [
+    "hardhat.config.ts",
+    "hardhat.config.cts",
+    "hardhat.config.js",
+    "hardhat.config.cjs",
+];
+
+/// Reads the `paths.sources` override from the Hardhat config at `root`.
+///
+/// Returns the configured path as written (e.g. `"./solidity"`), or `None`
+/// when there is no config file or it sets no sources path.
+pub fn hardhat_sources(root: &Path) -> Option<String> {
+    HARDHAT_CONFIGS
+        .iter()
+        .find_map(|name| fs::read_to_string(root.join(name)).ok())
+        .and_then(|text| extract_sources_path(&text))
+}
+
+/// Scans Hardhat config text for a `sources: "<path>"` assignment.
+///
+/// The config is JavaScript or TypeScript and is not evaluated; this is a
+/// tolerant text scan. The key may be bare or quoted, the value may use
+/// single, double, or backtick quotes, commented-out lines are ignored, and
+/// an occurrence after `paths` is preferred over any earlier one.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let config = "module.exports = { paths: { sources: './solidity' } };";
+/// assert_eq!(extract_sources_path(config), Some("./solidity".to_string()));
+/// ```
+fn extract_sources_path(text: &str) -> Option<String> {
+    let code: String = text
+        .lines()
+        .filter(|line| {
+            let trimmed = line.trim_start();
+            !trimmed.starts_with("//") && !trimmed.starts_with('*')
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let paths_at = code.find("paths").unwrap_or(0);
+
+    let mut found = None;
+    let mut search_from = 0;
+    while let Some(offset) = code[search_from..].find("sources") {
+        let start = search_from + offset;
+        let end = start + "sources".len();
+        search_from = end;
+
+        let before = code[..start].chars().next_back();
+        if before.is_some_and(|c| c.is_alphanumeric() || c == '_' || c == '$' || c == '.') {
+            continue;
+        }
+        if let Some(value) = assigned_string(&code[end..]) {
+            if start >= paths_at {
+                return Some(value);
+            }
+            found.get_or_insert(value);
+        }
+    }
+    found
+}
+
+/// Parses `[quote] : <quoted string>` at the start of `rest`.
+fn assigned_string(rest: &str) -> Option<String> {
+    let rest = rest.strip_prefix(['"', '\'']).unwrap_or(rest).trim_start();
+    let rest = rest.strip_prefix(':')?.trim_start();
+    let quote = rest
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '"' | '\'' | '`'))?;
+    let value = &rest[1..];
+    let close = value.find(quote)?;
+    let value = value[..close].trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn layout(name: &str, markers: &[&str]) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("solscrape_project_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("contracts")).unwrap();
+        for marker in markers {
+            fs::write(dir.join(marker), "").unwrap();
+        }
+        dir
+    }
+
+    /// Verifies each framework is recognized from its own markers.
+    #[test]
+    fn test_detect_single_frameworks() {
+        let cases: [(&str, &[&str], Framework); 5] = [
+            ("foundry", &["foundry.toml"], Framework::Foundry),
+            ("remappings", &["remappings.txt"], Framework::Foundry),
+            ("hardhat_js", &["hardhat.config.js"], Framework::Hardhat),
+            ("hardhat_ts", &["hardhat.config.ts"], Framework::Hardhat),
+            ("truffle", &["truffle-config.js"], Framework::Truffle),
+        ];
+
+        for (name, markers, expected) in cases {
+            let dir = layout(name, markers);
+            let kind = detect_project_kind(&dir);
+            assert_eq!(kind.frameworks, vec![expected], "{}", name);
+            assert_eq!(kind.to_string(), format!("{} project", expected.name()));
+            let _ = fs::remove_dir_all(&dir);
+        }
+    }
+
+    /// Verifies `paths.sources` extraction across config styles.
+    #[test]
+    fn test_extract_sources_path() {
+        let js = "module.exports = {\n  solidity: \"0.8.20\",\n  paths: {\n    sources: \"./solidity\",\n    tests: \"./test\"\n  }\n};\n";
+        assert_eq!(extract_sources_path(js), Some("./solidity".to_string()));
+
+        let ts = "import { HardhatUserConfig } from 'hardhat/config';\n\
+                  const config: HardhatUserConfig = {\n\
+                  \x20 paths: { 'sources': 'src/contracts', artifacts: './build' },\n\
+                  };\nexport default config;\n";
+        assert_eq!(extract_sources_path(ts), Some("src/contracts".to_string()));
+
+        let commented =
+            "module.exports = {\n  // sources: \"./old\",\n  paths: { sources: `./new` }\n};";
+        assert_eq!(extract_sources_path(commented), Some("./new".to_string()));
+
+        let absent = "module.exports = { solidity: \"0.8.20\", resources: \"x\" };";
+        assert_eq!(extract_sources_path(absent), None);
+        assert_eq!(extract_sources_path("paths: { sources: dir }"), None);
+    }
+
+    /// Verifies the override is read from whichever config file is present.
+    #[test]
+    fn test_hardhat_sources_reads_config() {
+        let dir = layout("hardhat_sources", &[]);
+        assert_eq!(hardhat_sources(&dir), None);
+        fs::write(
+            dir.join("hardhat.config.js"),
+            "module.exports = { paths: { sources: './solidity' } };",
+        )
+        .unwrap();
+        assert_eq!(hardhat_sources(&dir), Some("./solidity".to_string()));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    /// Verifies ambiguous and unmarked layouts.
+    #[test]
+    fn test_detect_mixed_and_unknown() {
+        let dir = layout("mixed", &["hardhat.config.ts", "foundry.toml"]);
+        let kind = detect_project_kind(&dir);
+        assert_eq!(kind.to_string(), "Foundry + Hardhat project");
+        assert!(kind.is_foundry());
+        let _ = fs::remove_dir_all(&dir);
+
+        let dir = layout("unknown", &["package.json"]);
+        let kind = detect_project_kind(&dir);
+        assert!(kind.is_unknown());
+        assert_eq!(kind.to_string(), "unrecognized project layout");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
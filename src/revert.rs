@@ -0,0 +1,315 @@
+//! Shortening of revert reason strings.
+//!
+//! Messages such as `require(x, "Very long explanatory message")` cost many
+//! tokens and add little when the output is fed to a language model.
+//! [`strip_revert_strings`] replaces the string literal reason of
+//! `require(...)` and `revert(...)` calls with [`PLACEHOLDER`]. The scan is
+//! aware of parentheses, comments, and string literals, so commas or
+//! parentheses inside a message are not mistaken for argument boundaries,
+//! and no other string is touched. Reasons that are not plain literals (a
+//! variable, `string.concat(...)`) and custom errors (`revert Err()`) are
+//! left alone.
+//!
+//! [`revert_reasons`] finds the same literals without changing anything,
+//! for `--errors-report`.
+
+/// The literal that replaces each stripped reason.
+pub const PLACEHOLDER: &str = "\"e\"";
+
+/// Replaces revert reason literals in `code` with [`PLACEHOLDER`].
+///
+/// # Returns
+///
+/// The rewritten code and the number of characters saved.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let (code, saved) = strip_revert_strings("require(a > b, \"a must exceed b\");");
+/// assert_eq!(code, "require(a > b, \"e\");");
+/// assert_eq!(saved, 14);
+/// ```
+pub fn strip_revert_strings(code: &str) -> (String, usize) {
+    let chars: Vec<char> = code.chars().collect();
+    let reasons: Vec<(usize, usize)> = reason_ranges(&chars)
+        .into_iter()
+        .filter(|&(s, e)| e - s > PLACEHOLDER.len())
+        .collect();
+
+    let mut stripped = String::with_capacity(code.len());
+    let mut saved = 0;
+    let mut copied = 0;
+    for (start, end) in reasons {
+        stripped.extend(&chars[copied..start]);
+        stripped.push_str(PLACEHOLDER);
+        saved += end - start - PLACEHOLDER.len();
+        copied = end;
+    }
+    stripped.extend(&chars[copied..]);
+    (stripped, saved)
+}
+
+/// Returns each revert reason literal in `code`, as written (quotes,
+/// `unicode` prefix, and adjacent literals included), with the char index
+/// it starts at.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let reasons = revert_reasons("require(ok, \"no\");\nrevert(unicode\"nö\");");
+/// assert_eq!(reasons, [(12, "\"no\"".to_string()), (26, "unicode\"nö\"".to_string())]);
+/// ```
+pub fn revert_reasons(code: &str) -> Vec<(usize, String)> {
+    let chars: Vec<char> = code.chars().collect();
+    reason_ranges(&chars)
+        .into_iter()
+        .map(|(start, end)| (start, chars[start..end].iter().collect()))
+        .collect()
+}
+
+/// Returns the char range of every reason literal in `chars`.
+fn reason_ranges(chars: &[char]) -> Vec<(usize, usize)> {
+    let mut reasons: Vec<(usize, usize)> = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = skip_comment(chars, i) {
+            i = end;
+        } else if c == '"' || c == '\'' {
+            i = skip_string(chars, i);
+        } else if c.is_ascii_alphabetic() || c == '_' || c == '$' {
+            let start = i;
+            while i < chars.len() && is_ident_char(chars[i]) {
+                i += 1;
+            }
+            let word: String = chars[start..i].iter().collect();
+            let reason_index = match word.as_str() {
+                "require" => 1,
+                "revert" => 0,
+                _ => continue,
+            };
+            if previous_non_space(chars, start) == Some('.') {
+                continue;
+            }
+            let open = (i..chars.len()).find(|&j| !chars[j].is_whitespace());
+            let Some(open) = open.filter(|&j| chars[j] == '(') else {
+                continue;
+            };
+            if let Some((start, end)) = call_arguments(chars, open)
+                .and_then(|args| args.get(reason_index).copied())
+                .filter(|&(s, e)| is_string_literals(&chars[s..e]))
+            {
+                reasons.push((start, end));
+            }
+        } else {
+            i += 1;
+        }
+    }
+    reasons
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '$'
+}
+
+fn previous_non_space(chars: &[char], before: usize) -> Option<char> {
+    chars[..before]
+        .iter()
+        .rev()
+        .find(|c| !c.is_whitespace())
+        .copied()
+}
+
+/// Returns the index just past a comment starting at `chars[i]`, if one does.
+fn skip_comment(chars: &[char], i: usize) -> Option<usize> {
+    if chars[i] != '/' {
+        return None;
+    }
+    match chars.get(i + 1) {
+        Some('/') => Some(
+            (i..chars.len())
+                .find(|&j| chars[j] == '\n')
+                .unwrap_or(chars.len()),
+        ),
+        Some('*') => Some(
+            (i + 2..chars.len())
+                .find(|&j| chars[j] == '*' && chars.get(j + 1) == Some(&'/'))
+                .map_or(chars.len(), |j| j + 2),
+        ),
+        _ => None,
+    }
+}
+
+/// Returns the index just past the string literal opening at `chars[i]`.
+///
+/// An unterminated literal ends at the line break, as Solidity does not
+/// allow newlines in string literals.
+fn skip_string(chars: &[char], i: usize) -> usize {
+    let quote = chars[i];
+    let mut j = i + 1;
+    while j < chars.len() && chars[j] != quote && chars[j] != '\n' {
+        if chars[j] == '\\' {
+            j += 1;
+        }
+        j += 1;
+    }
+    (j + 1).min(chars.len())
+}
+
+/// Returns the trimmed char ranges of the arguments of the call whose `(`
+/// is at `chars[open]`, or `None` if the call is not closed.
+fn call_arguments(chars: &[char], open: usize) -> Option<Vec<(usize, usize)>> {
+    let mut args = Vec::new();
+    let mut depth = 0usize;
+    let mut arg_start = open + 1;
+    let mut i = open + 1;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(end) = skip_comment(chars, i) {
+            i = end;
+            continue;
+        }
+        match c {
+            '"' | '\'' => {
+                i = skip_string(chars, i);
+                continue;
+            }
+            '(' | '[' | '{' => depth += 1,
+            ')' if depth == 0 => {
+                args.push(trimmed(chars, arg_start, i));
+                return Some(args);
+            }
+            ')' | ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                args.push(trimmed(chars, arg_start, i));
+                arg_start = i + 1;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn trimmed(chars: &[char], mut start: usize, mut end: usize) -> (usize, usize) {
+    while start < end && chars[start].is_whitespace() {
+        start += 1;
+    }
+    while end > start && chars[end - 1].is_whitespace() {
+        end -= 1;
+    }
+    (start, end)
+}
+
+/// Returns true if `chars` is one or more adjacent string literals, each
+/// optionally prefixed with `unicode`.
+fn is_string_literals(chars: &[char]) -> bool {
+    let mut i = 0;
+    let mut literals = 0;
+    loop {
+        while i < chars.len() && chars[i].is_whitespace() {
+            i += 1;
+        }
+        if i == chars.len() {
+            return literals > 0;
+        }
+        if chars[i..].starts_with(&['u', 'n', 'i', 'c', 'o', 'd', 'e']) {
+            i += "unicode".len();
+        }
+        if !matches!(chars.get(i), Some('"' | '\'')) {
+            return false;
+        }
+        let end = skip_string(chars, i);
+        if chars[end - 1] != chars[i] {
+            return false;
+        }
+        i = end;
+        literals += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stripped(code: &str) -> String {
+        strip_revert_strings(code).0
+    }
+
+    /// Verifies reasons are replaced and the savings counted.
+    #[test]
+    fn test_strip_require_and_revert() {
+        let (code, saved) = strip_revert_strings("require(a > b, \"msg too long\");");
+        assert_eq!(code, "require(a > b, \"e\");");
+        assert_eq!(saved, "\"msg too long\"".len() - 3);
+
+        assert_eq!(
+            stripped("if (x) { revert(\"Vault: insufficient balance\"); }"),
+            "if (x) { revert(\"e\"); }"
+        );
+        assert_eq!(
+            stripped("require(\n    ok,\n    \"first part \"\n    'second part'\n);"),
+            "require(\n    ok,\n    \"e\"\n);"
+        );
+        assert_eq!(
+            stripped("require(ok, unicode\"ünïcode reason\");"),
+            "require(ok, \"e\");"
+        );
+    }
+
+    /// Verifies commas and parentheses inside messages and conditions.
+    #[test]
+    fn test_strip_with_nested_syntax() {
+        assert_eq!(
+            stripped("require(f(a, (b)), \"bad (a, b), see docs\");"),
+            "require(f(a, (b)), \"e\");"
+        );
+        assert_eq!(
+            stripped("require(keccak256(\"x,y\") == h, \"hash mismatch (x, y)\");"),
+            "require(keccak256(\"x,y\") == h, \"e\");"
+        );
+        assert_eq!(
+            stripped("require(m[\"k\"] /* ) */ > 0, 'it\\'s, (odd)');"),
+            "require(m[\"k\"] /* ) */ > 0, \"e\");"
+        );
+    }
+
+    /// Verifies that reasons are found as written, short ones included, and
+    /// nothing else.
+    #[test]
+    fn test_revert_reasons() {
+        let code = "require(ok, \"e\");\nrevert(\"a, (b)\" 'c');\n\
+                    revert Custom(\"x\");\n// require(ok, \"hidden\");\nrequire(ok, reason);";
+        let reasons: Vec<String> = revert_reasons(code).into_iter().map(|(_, r)| r).collect();
+        assert_eq!(reasons, ["\"e\"", "\"a, (b)\" 'c'"]);
+        assert_eq!(revert_reasons(code)[1].0, code.find("\"a,").unwrap());
+    }
+
+    /// Verifies that nothing else is touched.
+    #[test]
+    fn test_strip_leaves_other_code() {
+        for code in [
+            "revert CustomError(\"not a reason literal\");",
+            "revert Unauthorized();",
+            "revert();",
+            "require(ok);",
+            "require(ok, \"e\");",
+            "require(ok, string.concat(\"a long \", name));",
+            "require(ok, reason);",
+            "string s = \"require(ok, \\\"long message\\\")\";",
+            "// require(ok, \"commented out message\");",
+            "lib.require(ok, \"member call message\");",
+            "function requireOwner(string memory m) {}",
+            "require(ok, \"unterminated",
+        ] {
+            assert_eq!(
+                strip_revert_strings(code),
+                (code.to_string(), 0),
+                "{}",
+                code
+            );
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Timestamped run directories for `--run-dir`.
+//!
+//! Each invocation with `--run-dir` writes its outputs into a fresh
+//! `destination/<YYYY-MM-DD_HHMMSS>/` directory so repeated scrapes never
+//! overwrite one another, and a `latest` pointer in the destination always
+//! names the newest run.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::clock::DateTime;
+
+/// The name of the pointer to the newest run inside the destination.
+///
+/// A symlink on Unix; elsewhere a `latest.txt` file containing the run
+/// directory's name.
+#[cfg(unix)]
+pub const LATEST_NAME: &str = "latest";
+#[cfg(not(unix))]
+pub const LATEST_NAME: &str = "latest.txt";
+
+/// Creates a new run directory under `destination` named after `now`.
+///
+/// If a directory for the same second already exists, a counter is appended
+/// (`2024-01-31_130509-1`, `-2`, ...). Creation uses [`fs::create_dir`], so two
+/// concurrent runs can never claim the same directory.
+///
+/// # Errors
+///
+/// Returns `"Failed to create run directory {path}: {e}"` for any error other
+/// than a name collision.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let run = create_run_dir(Path::new("./out"), DateTime::now())?;
+/// // ./out/2024-01-31_130509
+/// ```
+pub fn create_run_dir(destination: &Path, now: DateTime) -> Result<PathBuf, String> {
+    let stamp = now.compact();
+    let mut counter = 0u32;
+
+    loop {
+        let name = if counter == 0 {
+            stamp.clone()
+        } else {
+            format!("{}-{}", stamp, counter)
+        };
+        let candidate = destination.join(name);
+
+        match fs::create_dir(&candidate) {
+            Ok(()) => return Ok(candidate),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => counter += 1,
+            Err(e) => {
+                return Err(format!(
+                    "Failed to create run directory {}: {}",
+                    candidate.display(),
+                    e
+                ));
+            }
+        }
+    }
+}
+
+/// Points `destination/latest` at `run_dir`.
+///
+/// An existing pointer is replaced; a real file or directory occupying the
+/// pointer's name is left alone and reported as an error.
+///
+/// # Errors
+///
+/// Returns a message suitable for a warning; failing to update the pointer
+/// never invalidates the run itself.
+pub fn update_latest(destination: &Path, run_dir: &Path) -> Result<(), String> {
+    let pointer = destination.join(LATEST_NAME);
+    let target = run_dir
+        .file_name()
+        .map(PathBuf::from)
+        .ok_or_else(|| format!("Run directory has no name: {}", run_dir.display()))?;
+
+    write_pointer(&pointer, &target)
+        .map_err(|e| format!("Could not update {}: {}", pointer.display(), e))
+}
+
+#[cfg(unix)]
+fn write_pointer(pointer: &Path, target: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(pointer) {
+        Ok(meta) if meta.file_type().is_symlink() => fs::remove_file(pointer)?,
+        Ok(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "a file or directory with that name already exists",
+            ));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    std::os::unix::fs::symlink(target, pointer)
+}
+
+#[cfg(not(unix))]
+fn write_pointer(pointer: &Path, target: &Path) -> io::Result<()> {
+    fs::write(pointer, format!("{}\n", target.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("solscrape_rundir_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Verifies that runs within the same second get distinct, counted names.
+    #[test]
+    fn test_create_run_dir_collisions() {
+        let dest = test_dir("collisions");
+        let now = DateTime::from_unix(1_706_706_309);
+
+        let first = create_run_dir(&dest, now).unwrap();
+        let second = create_run_dir(&dest, now).unwrap();
+        let third = create_run_dir(&dest, now).unwrap();
+
+        assert_eq!(first, dest.join("2024-01-31_130509"));
+        assert_eq!(second, dest.join("2024-01-31_130509-1"));
+        assert_eq!(third, dest.join("2024-01-31_130509-2"));
+        assert!(third.is_dir());
+        let _ = fs::remove_dir_all(&dest);
+    }
+
+    /// Verifies that the latest pointer follows the newest run.
+    #[test]
+    fn test_update_latest() {
+        let dest = test_dir("latest");
+        let first = create_run_dir(&dest, DateTime::from_unix(0)).unwrap();
+        let second = create_run_dir(&dest, DateTime::from_unix(60)).unwrap();
+        fs::write(second.join("marker"), "x").unwrap();
+
+        update_latest(&dest, &first).unwrap();
+        update_latest(&dest, &second).unwrap();
+
+        #[cfg(unix)]
+        assert!(dest.join(LATEST_NAME).join("marker").exists());
+        #[cfg(not(unix))]
+        assert_eq!(
+            fs::read_to_string(dest.join(LATEST_NAME)).unwrap().trim(),
+            "1970-01-01_000100"
+        );
+        let _ = fs::remove_dir_all(&dest);
+    }
+
+    /// Verifies that a real directory named like the pointer is not clobbered.
+    #[cfg(unix)]
+    #[test]
+    fn test_update_latest_refuses_real_directory() {
+        let dest = test_dir("occupied");
+        fs::create_dir(dest.join(LATEST_NAME)).unwrap();
+        let run = create_run_dir(&dest, DateTime::from_unix(0)).unwrap();
+
+        assert!(update_latest(&dest, &run).is_err());
+        assert!(dest.join(LATEST_NAME).is_dir());
+        let _ = fs::remove_dir_all(&dest);
+    }
+}
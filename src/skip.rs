@@ -0,0 +1,242 @@
+//! Why candidate files were left out while processing.
+//!
+//! Every file that reaches the processing pipeline is either included or
+//! skipped with one [`SkipReason`], so "Files processed" plus "Skipped" in the
+//! summary always adds up to the candidates seen. Reasons come in two sorts:
+//! explicit filters the user asked for (an opt-out marker, `--filter-pragma`,
+//! `--kinds`, `--dedupe-content`, `--prune-implemented-interfaces`) and files whose content was lost (binary, a Git LFS pointer,
+//! unreadable). `--strict` fails on the second sort only; see
+//! [`SkipReason::loses_content`].
+
+use std::fmt;
+use std::io;
+
+/// Why one file was left out while processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The file's first line is the opt-out marker.
+    OptOutMarker,
+    /// `--filter-pragma` or `--min-solidity` rejected its pragma.
+    Pragma,
+    /// It declares none of the `--kinds`.
+    Kind,
+    /// Its cleaned code is that of a file included before it
+    /// (`--dedupe-content`).
+    Duplicate,
+    /// It only declares interfaces that included contracts implement
+    /// (`--prune-implemented-interfaces`).
+    ImplementedInterface,
+    /// Nothing was left after cleaning.
+    Empty,
+    /// Its lines are too long (minified) and `--rewrap-long-lines` is off.
+    LongLines,
+    /// Its braces nest deeper than `--max-brace-depth`.
+    DeepNesting,
+    /// It is a Git LFS pointer rather than the file.
+    LfsPointer,
+    /// It is binary.
+    Binary,
+    /// It is text but not valid UTF-8.
+    NotUtf8,
+    /// It could not be read.
+    Unreadable,
+}
+
+impl SkipReason {
+    /// Every reason, in the order summaries list them.
+    pub const ALL: [SkipReason; 12] = [
+        SkipReason::OptOutMarker,
+        SkipReason::Pragma,
+        SkipReason::Kind,
+        SkipReason::Duplicate,
+        SkipReason::ImplementedInterface,
+        SkipReason::Empty,
+        SkipReason::LongLines,
+        SkipReason::DeepNesting,
+        SkipReason::LfsPointer,
+        SkipReason::Binary,
+        SkipReason::NotUtf8,
+        SkipReason::Unreadable,
+    ];
+
+    /// The stable name used by `--explain`, JSON output, and summaries.
+    pub fn name(self) -> &'static str {
+        match self {
+            SkipReason::OptOutMarker => "opt-out-marker",
+            SkipReason::Pragma => "pragma",
+            SkipReason::Kind => "kind",
+            SkipReason::Duplicate => "duplicate",
+            SkipReason::ImplementedInterface => "implemented-interface",
+            SkipReason::Empty => "empty-after-clean",
+            SkipReason::LongLines => "long-lines",
+            SkipReason::DeepNesting => "deep-nesting",
+            SkipReason::LfsPointer => "lfs-pointer",
+            SkipReason::Binary => "binary",
+            SkipReason::NotUtf8 => "not-utf8",
+            SkipReason::Unreadable => "unreadable",
+        }
+    }
+
+    /// The short category starting a skip message in the log, as in
+    /// `[lfs] Git LFS pointer ...`.
+    pub fn tag(self) -> &'static str {
+        match self {
+            SkipReason::OptOutMarker => "marker",
+            SkipReason::LfsPointer => "lfs",
+            SkipReason::Empty => "empty",
+            other => other.name(),
+        }
+    }
+
+    /// Returns true if skipping the file loses code that should have been in
+    /// the output, which `--strict` turns into a failure.
+    ///
+    /// Filters the user chose (the opt-out marker, `--filter-pragma`,
+    /// `--kinds`, `--dedupe-content`, `--prune-implemented-interfaces`) and
+    /// files with nothing left after cleaning lose nothing.
+    pub fn loses_content(self) -> bool {
+        !matches!(
+            self,
+            SkipReason::OptOutMarker
+                | SkipReason::Pragma
+                | SkipReason::Kind
+                | SkipReason::Duplicate
+                | SkipReason::ImplementedInterface
+                | SkipReason::Empty
+        )
+    }
+}
+
+/// A file left out while processing: the reason and what caused it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skip {
+    /// The category.
+    pub reason: SkipReason,
+    /// The explanation for the log, without the category.
+    pub detail: String,
+}
+
+impl Skip {
+    /// Creates a skip for `reason`.
+    pub fn new(reason: SkipReason, detail: impl Into<String>) -> Self {
+        Self {
+            reason,
+            detail: detail.into(),
+        }
+    }
+
+    /// The skip of a file with nothing left after cleaning.
+    pub fn empty() -> Self {
+        Self::new(SkipReason::Empty, "nothing left after cleaning")
+    }
+
+    /// The skip of a file that could not be read.
+    pub fn unreadable(error: &io::Error) -> Self {
+        Self::new(SkipReason::Unreadable, format!("could not read: {}", error))
+    }
+}
+
+impl fmt::Display for Skip {
+    /// Writes the skip as `[tag] detail`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.reason.tag(), self.detail)
+    }
+}
+
+/// Counts skipped files by reason, in [`SkipReason::ALL`] order.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(summarize(skipped.iter().map(|(_, r)| *r)), "3 (1 kind, 2 binary)");
+/// ```
+pub fn summarize(reasons: impl Iterator<Item = SkipReason>) -> String {
+    let mut counts = [0usize; SkipReason::ALL.len()];
+    for reason in reasons {
+        if let Some(index) = SkipReason::ALL.iter().position(|r| *r == reason) {
+            counts[index] += 1;
+        }
+    }
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return "none".to_string();
+    }
+    let groups: Vec<String> = SkipReason::ALL
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(reason, count)| format!("{} {}", count, reason.name()))
+        .collect();
+    format!("{} ({})", total, groups.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies names, tags, and which reasons `--strict` escalates.
+    #[test]
+    fn test_reasons() {
+        let names: Vec<&str> = SkipReason::ALL.iter().map(|r| r.name()).collect();
+        assert_eq!(
+            names,
+            [
+                "opt-out-marker",
+                "pragma",
+                "kind",
+                "duplicate",
+                "implemented-interface",
+                "empty-after-clean",
+                "long-lines",
+                "deep-nesting",
+                "lfs-pointer",
+                "binary",
+                "not-utf8",
+                "unreadable"
+            ]
+        );
+        let escalated: Vec<SkipReason> = SkipReason::ALL
+            .into_iter()
+            .filter(|r| r.loses_content())
+            .collect();
+        assert_eq!(
+            escalated,
+            [
+                SkipReason::LongLines,
+                SkipReason::DeepNesting,
+                SkipReason::LfsPointer,
+                SkipReason::Binary,
+                SkipReason::NotUtf8,
+                SkipReason::Unreadable
+            ]
+        );
+        let skip = Skip::new(SkipReason::LfsPointer, "Git LFS pointer");
+        assert_eq!(skip.to_string(), "[lfs] Git LFS pointer");
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(Skip::unreadable(&denied).reason, SkipReason::Unreadable);
+        assert_eq!(
+            Skip::empty().to_string(),
+            "[empty] nothing left after cleaning"
+        );
+        assert_eq!(
+            Skip::new(SkipReason::LongLines, "1 line(s)").to_string(),
+            "[long-lines] 1 line(s)"
+        );
+    }
+
+    /// Verifies grouping and order in the summary.
+    #[test]
+    fn test_summarize() {
+        let reasons = [
+            SkipReason::Binary,
+            SkipReason::Kind,
+            SkipReason::Binary,
+            SkipReason::OptOutMarker,
+        ];
+        assert_eq!(
+            summarize(reasons.into_iter()),
+            "4 (1 opt-out-marker, 1 kind, 2 binary)"
+        );
+        assert_eq!(summarize([].into_iter()), "none");
+    }
+}
@@ -0,0 +1,185 @@
+//! Free-space checks for the temporary clone directory.
+//!
+//! Clones of large repositories into a small `/tmp` tmpfs fail halfway with
+//! git write errors that do not mention disk space. Checking the available
+//! space up front turns that into an actionable message. The standard library
+//! offers no `statvfs`, so the probe runs `df` and is skipped wherever that is
+//! unavailable.
+
+use std::path::Path;
+
+use crate::exec::Exec;
+use crate::logger::Logger;
+
+/// Free space required in the temp directory when `--min-temp-space` is not given.
+pub const DEFAULT_MIN_TEMP_SPACE: u64 = 256 * 1024 * 1024;
+
+/// Parses a human-readable size such as `512M`, `2G`, `1.5G`, or `1048576`.
+///
+/// Suffixes `K`, `M`, `G`, and `T` (optionally followed by `B` or `iB`) are
+/// binary multiples; a bare number is bytes.
+///
+/// # Errors
+///
+/// Returns `"Invalid size: {text}"` for anything else.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(parse_size("512M"), Ok(512 * 1024 * 1024));
+/// ```
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let invalid = || format!("Invalid size: {} (expected e.g. 512M or 2G)", text);
+    let trimmed = text.trim();
+    let split = trimmed
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(trimmed.len());
+    let (number, unit) = trimmed.split_at(split);
+
+    let multiplier: u64 = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(invalid());
+    }
+    Ok((value * multiplier as f64) as u64)
+}
+
+/// Formats a byte count with a binary unit (`"1.5 GiB"`, `"512 B"`).
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Returns the bytes available to unprivileged users on the filesystem of `dir`.
+///
+/// The nearest existing ancestor of `dir` is probed, so a `--temp-dir` that
+/// has yet to be created still works. Returns `None` when the probe is not
+/// possible on this platform, `exec` does not allow running `df`, or the
+/// probe fails for any reason.
+pub fn available_space(dir: &Path, exec: &Exec, log: &Logger) -> Option<u64> {
+    let existing = dir.ancestors().find(|p| p.exists())?;
+    if !exec.allows("df") {
+        log.detail("Skipping free-space check: df is not allowed");
+        return None;
+    }
+    probe(existing, exec, log)
+}
+
+#[cfg(unix)]
+fn probe(dir: &Path, exec: &Exec, log: &Logger) -> Option<u64> {
+    let output = exec
+        .output(
+            "df",
+            [std::ffi::OsStr::new("-Pk"), dir.as_os_str()],
+            None,
+            log,
+        )
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    parse_df_available(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(unix))]
+fn probe(_dir: &Path, _exec: &Exec, _log: &Logger) -> Option<u64> {
+    None
+}
+
+/// Extracts the available bytes from POSIX `df -Pk` output.
+///
+/// The second line's fourth column is the available space in 1024-byte
+/// blocks; the mount point may contain spaces, so columns are counted from
+/// the left.
+fn parse_df_available(output: &str) -> Option<u64> {
+    let line = output.lines().nth(1)?;
+    let blocks: u64 = line.split_whitespace().nth(3)?.parse().ok()?;
+    blocks.checked_mul(1024)
+}
+
+/// Fails early if `dir` has less than `required` bytes available.
+///
+/// # Errors
+///
+/// Returns a message naming the available and required space and suggesting
+/// `--temp-dir` on a larger volume. No error is returned when the space
+/// cannot be determined.
+pub fn check_available(dir: &Path, required: u64, exec: &Exec, log: &Logger) -> Result<(), String> {
+    if required == 0 {
+        return Ok(());
+    }
+    match available_space(dir, exec, log) {
+        Some(available) if available < required => Err(format!(
+            "Only {} free in temp directory {} (need at least {}). \
+             Use --temp-dir <DIR> or SOLSCRAPE_TMPDIR to clone on a larger volume, \
+             or lower the threshold with --min-temp-space <SIZE>",
+            format_size(available),
+            dir.display(),
+            format_size(required)
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies size parsing across units and rejection of malformed input.
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1048576"), Ok(1 << 20));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("2g"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert!(parse_size("lots").is_err());
+        assert!(parse_size("12Q").is_err());
+        assert!(parse_size("").is_err());
+    }
+
+    /// Verifies `df -Pk` parsing, including mount points with spaces.
+    #[test]
+    fn test_parse_df_available() {
+        let output = "Filesystem     1024-blocks     Used Available Capacity Mounted on\n\
+                      tmpfs              65536    60000      5536      92% /tmp/build dir\n";
+        assert_eq!(parse_df_available(output), Some(5536 * 1024));
+        assert_eq!(parse_df_available("Filesystem\n"), None);
+        assert_eq!(format_size(5536 * 1024), "5.4 MiB");
+        assert_eq!(format_size(100), "100 B");
+    }
+
+    /// Verifies that an impossible requirement fails with a --temp-dir hint.
+    #[cfg(unix)]
+    #[test]
+    fn test_check_available_reports_shortfall() {
+        let dir = std::env::temp_dir().join("solscrape_space_not_yet_created");
+        let (exec, log) = (Exec::unrestricted(), Logger::new());
+        assert_eq!(check_available(&dir, 0, &exec, &log), Ok(()));
+        if available_space(&dir, &exec, &log).is_some() {
+            let err = check_available(&dir, u64::MAX, &exec, &log).unwrap_err();
+            assert!(err.contains("--temp-dir"), "{}", err);
+        }
+
+        // Without df there is nothing to measure, so the check passes
+        let no_df = Exec::restricted(&["git"]);
+        assert_eq!(check_available(&dir, u64::MAX, &no_df, &log), Ok(()));
+        assert!(no_df.invocations().is_empty());
+    }
+}
@@ -0,0 +1,657 @@
+//! The `split` and `extract` subcommands: reading files back out of
+//! consolidated output.
+//!
+//! A consolidated file produced with headers is a sequence of sections, each
+//! introduced by a three-line header (separator, `// File: <path>`,
+//! separator), or by the `// File: <path>` line alone for the files
+//! `--no-headers-for` matched. This module parses those sections and writes each one back to
+//! its relative path under an output directory, or returns a single one.
+//! Library banners between sections (see [`crate::library`]) and the trailer
+//! of output cut short by `--deadline` are dropped, and so is the project
+//! context section `--with-context` appends (see [`crate::context`]).
+//! With `--mtime-from`, split files take the modification time of the file
+//! they came from in the scraped tree (see [`copy_mtimes`]).
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+
+use crate::context;
+use crate::library;
+use crate::naming::{self, display_path};
+use crate::output;
+use crate::symbols;
+use crate::{HEADER_FILE_PREFIX, TRUNCATED_PREFIX};
+
+/// One file's section of a consolidated output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The relative path named in the section's `// File:` header, with any
+    /// Soldeer version restored (see [`naming::parse_header_path`]).
+    pub path: String,
+    /// The cleaned content following the header.
+    pub content: String,
+    /// The 1-based line number of the `// File:` header line.
+    pub line: usize,
+}
+
+/// Parses consolidated output into its per-file sections.
+///
+/// # Errors
+///
+/// Returns an error naming the offending line when:
+///
+/// - content appears before the first header (e.g. output made with `--no-headers`)
+/// - a separator is not followed by a `// File: <path>` line and a closing separator
+///   (headers written with either charset are read, but not mixed within one)
+/// - a path is absolute, escapes the output directory, or is repeated
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let sections = parse_sections(&fs::read_to_string("repo_scraped.sol")?)?;
+/// for section in &sections {
+///     println!("{} ({} lines)", section.path, section.content.lines().count());
+/// }
+/// ```
+pub fn parse_sections(text: &str) -> Result<Vec<Section>, String> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut sections: Vec<Section> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if line == context::BANNER {
+            break;
+        }
+
+        if let Some(separator) = symbols::ALL
+            .iter()
+            .map(|s| s.header_separator)
+            .find(|s| line == *s)
+        {
+            let path = lines
+                .get(i + 1)
+                .and_then(|l| l.strip_prefix(HEADER_FILE_PREFIX))
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .map(naming::parse_header_path)
+                .ok_or_else(|| {
+                    format!(
+                        "line {}: expected `{}<path>` after header separator",
+                        i + 2,
+                        HEADER_FILE_PREFIX
+                    )
+                })?;
+            if lines.get(i + 2) != Some(&separator) {
+                return Err(format!("line {}: expected closing header separator", i + 3));
+            }
+            validate_path(&path).map_err(|e| format!("line {}: {}", i + 2, e))?;
+            if !seen.insert(path.clone()) {
+                return Err(format!("line {}: duplicate section for {}", i + 2, path));
+            }
+
+            sections.push(Section {
+                path,
+                content: String::new(),
+                line: i + 2,
+            });
+            i += 3;
+            continue;
+        }
+
+        if library::is_banner_line(line) || line.starts_with(TRUNCATED_PREFIX) {
+            i += 1;
+            continue;
+        }
+
+        if let Some(path) = line
+            .strip_prefix(HEADER_FILE_PREFIX)
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+        {
+            let path = naming::parse_header_path(path);
+            validate_path(&path).map_err(|e| format!("line {}: {}", i + 1, e))?;
+            if !seen.insert(path.clone()) {
+                return Err(format!("line {}: duplicate section for {}", i + 1, path));
+            }
+            sections.push(Section {
+                path,
+                content: String::new(),
+                line: i + 1,
+            });
+            i += 1;
+            continue;
+        }
+
+        match sections.last_mut() {
+            Some(section) => {
+                if !section.content.is_empty() {
+                    section.content.push('\n');
+                }
+                section.content.push_str(line);
+            }
+            None if line.trim().is_empty() => {}
+            None => {
+                return Err(format!(
+                    "line {}: content before the first `{}` header (was the file produced with --no-headers?)",
+                    i + 1,
+                    HEADER_FILE_PREFIX.trim_end()
+                ));
+            }
+        }
+        i += 1;
+    }
+
+    if sections.is_empty() {
+        return Err("line 1: no file headers found".to_string());
+    }
+
+    Ok(sections)
+}
+
+/// Ensures a header path is relative and stays inside the output directory.
+fn validate_path(path: &str) -> Result<(), String> {
+    let escapes = Path::new(path).components().any(|c| {
+        matches!(
+            c,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        Err(format!("unsafe path in header: {}", path))
+    } else {
+        Ok(())
+    }
+}
+
+/// Where each section will be written, relative to the output directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    /// One relative target path per section, in section order.
+    pub targets: Vec<String>,
+    /// Notes about sections written somewhere other than their header path.
+    pub warnings: Vec<String>,
+}
+
+/// Plans target paths so no two differ only by letter case.
+///
+/// `Contracts/Token.sol` and `contracts/token.sol` are distinct on Linux but
+/// the same file on case-insensitive filesystems (macOS, Windows), where the
+/// second would silently overwrite the first. Later colliding sections get a
+/// numeric suffix (`token-1.sol`) and a warning naming both originals; with
+/// `strict`, any collision is an error instead.
+///
+/// # Errors
+///
+/// Under `strict`, returns `"line N: {path} collides with {other} ..."` for
+/// the first colliding section.
+pub fn plan_targets(sections: &[Section], strict: bool) -> Result<Plan, String> {
+    let mut claimed: HashMap<String, &Section> = HashMap::new();
+    let mut plan = Plan {
+        targets: Vec::with_capacity(sections.len()),
+        warnings: Vec::new(),
+    };
+
+    for section in sections {
+        let Some(original) = claimed.get(&section.path.to_lowercase()).copied() else {
+            claimed.insert(section.path.to_lowercase(), section);
+            plan.targets.push(section.path.clone());
+            continue;
+        };
+
+        if strict {
+            return Err(format!(
+                "line {}: {} collides with {} (line {}) on case-insensitive filesystems",
+                section.line, section.path, original.path, original.line
+            ));
+        }
+
+        let target = (1..)
+            .map(|n| with_suffix(&section.path, n))
+            .find(|candidate| !claimed.contains_key(&candidate.to_lowercase()))
+            .expect("an unused suffix always exists");
+        plan.warnings.push(format!(
+            "{} and {} differ only by case; writing {} as {}",
+            original.path, section.path, section.path, target
+        ));
+        claimed.insert(target.to_lowercase(), section);
+        plan.targets.push(target);
+    }
+
+    Ok(plan)
+}
+
+/// Appends `-{n}` to the file stem of `path` (`a/Token.sol` → `a/Token-1.sol`).
+fn with_suffix(path: &str, n: usize) -> String {
+    let (dir, file) = match path.rfind('/') {
+        Some(slash) => path.split_at(slash + 1),
+        None => ("", path),
+    };
+    match file.rfind('.').filter(|&dot| dot > 0) {
+        Some(dot) => format!("{}{}-{}{}", dir, &file[..dot], n, &file[dot..]),
+        None => format!("{}{}-{}", dir, file, n),
+    }
+}
+
+/// The result of [`split_file`].
+#[derive(Debug)]
+pub struct SplitOutcome {
+    /// The paths of the written files, in section order.
+    pub written: Vec<PathBuf>,
+    /// The relative paths named by the sections, parallel to `written`.
+    pub sources: Vec<String>,
+    /// Notes about disambiguated paths (see [`plan_targets`]).
+    pub warnings: Vec<String>,
+}
+
+/// Splits the consolidated file at `input` into a tree under `out_dir`.
+///
+/// Each section is written (with a trailing newline) to `out_dir/<path>`,
+/// creating intermediate directories as needed. Paths that differ only by
+/// case are disambiguated, or rejected with `strict` (see [`plan_targets`]).
+/// Nothing is written unless the whole input parses and plans, and the tree
+/// is staged (see [`output::StagedDir`]) so that `out_dir` only changes once
+/// every file has been written.
+///
+/// # Errors
+///
+/// Returns an error if the input cannot be read or parsed (see
+/// [`parse_sections`]), on a case collision under `strict`, or if any file
+/// cannot be written; the error names the file, and `out_dir` is left as it
+/// was.
+pub fn split_file(input: &Path, out_dir: &Path, strict: bool) -> Result<SplitOutcome, String> {
+    let text = fs::read_to_string(input)
+        .map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let sections = parse_sections(&text)?;
+    let plan = plan_targets(&sections, strict)?;
+
+    let files: Vec<(&str, String)> = plan
+        .targets
+        .iter()
+        .zip(&sections)
+        .map(|(relative, section)| (relative.as_str(), format!("{}\n", section.content)))
+        .collect();
+    let written = write_tree(out_dir, &files, |staged, relative, data| {
+        staged.write(relative, data)
+    })?;
+
+    Ok(SplitOutcome {
+        written,
+        sources: sections.into_iter().map(|section| section.path).collect(),
+        warnings: plan.warnings,
+    })
+}
+
+/// Gives each file written by [`split_file`] the modification time of the
+/// file at its section's path under `source_dir`, the tree that was scraped.
+///
+/// Recency is what reviewers sort a split tree by, and every file would
+/// otherwise be dated by the split. Returns one warning per file whose time
+/// could not be read or set; such a file keeps the time it was written at.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let outcome = split_file(Path::new("repo_scraped.sol"), Path::new("tree"), false)?;
+/// for warning in copy_mtimes(&outcome, Path::new("./repo")) {
+///     eprintln!("{}", warning);
+/// }
+/// ```
+pub fn copy_mtimes(outcome: &SplitOutcome, source_dir: &Path) -> Vec<String> {
+    outcome
+        .written
+        .iter()
+        .zip(&outcome.sources)
+        .filter_map(|(written, relative)| {
+            let source = source_dir.join(relative);
+            fs::metadata(&source)
+                .and_then(|metadata| metadata.modified())
+                .and_then(|mtime| {
+                    File::options()
+                        .write(true)
+                        .open(written)?
+                        .set_modified(mtime)
+                })
+                .err()
+                .map(|e| {
+                    format!(
+                        "Could not copy the modification time of {} to {}: {}",
+                        source.display(),
+                        written.display(),
+                        e
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Writes `files` under `out_dir` through a [`output::StagedDir`], calling
+/// `write` for each, and moves them into place once all succeeded.
+///
+/// `write` is a hook so tests can make a write fail part-way through.
+fn write_tree(
+    out_dir: &Path,
+    files: &[(&str, String)],
+    mut write: impl FnMut(&mut output::StagedDir, &Path, &[u8]) -> Result<(), String>,
+) -> Result<Vec<PathBuf>, String> {
+    let mut staged = output::StagedDir::create(out_dir)?;
+    for (relative, content) in files {
+        write(&mut staged, Path::new(relative), content.as_bytes()).map_err(|e| {
+            format!(
+                "{}: {}; {} was left unchanged",
+                out_dir.join(relative).display(),
+                e,
+                out_dir.display()
+            )
+        })?;
+    }
+    staged.commit()
+}
+
+/// Returns the content of the section for `path`, as [`split_file`] would
+/// write it (without the trailing newline).
+///
+/// `path` is the file's real relative path; headers show paths escaped, so
+/// it is compared in its [`display_path`] form.
+///
+/// # Errors
+///
+/// Returns the error of [`parse_sections`], or `"No section for {path}"`
+/// when no header names `path`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let text = fs::read_to_string("repo_scraped.sol")?;
+/// print!("{}", extract_section(&text, "src/Vault.sol")?);
+/// ```
+pub fn extract_section(text: &str, path: &str) -> Result<String, String> {
+    let wanted = display_path(path);
+    parse_sections(text)?
+        .into_iter()
+        .find(|section| section.path == wanted)
+        .map(|section| section.content)
+        .ok_or_else(|| format!("No section for {}", wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER_SEPARATOR: &str = symbols::UNICODE.header_separator;
+
+    fn header(path: &str) -> String {
+        format!(
+            "{}\n{}{}\n{}",
+            HEADER_SEPARATOR, HEADER_FILE_PREFIX, path, HEADER_SEPARATOR
+        )
+    }
+
+    /// Verifies that well-formed output parses into ordered sections.
+    #[test]
+    fn test_parse_sections() {
+        let text = format!(
+            "{}\ncontract A {{}}\n{}\ninterface IB {{\n}}",
+            header("src/A.sol"),
+            header("src/interfaces/IB.sol")
+        );
+        let sections = parse_sections(&text).unwrap();
+        assert_eq!(sections.len(), 2);
+        assert_eq!(sections[0].path, "src/A.sol");
+        assert_eq!(sections[0].content, "contract A {}");
+        assert_eq!(sections[1].line, 6);
+        assert_eq!(sections[1].content, "interface IB {\n}");
+
+        // ASCII headers (`--ascii`) read the same
+        let ascii = text.replace(HEADER_SEPARATOR, symbols::ASCII.header_separator);
+        assert_eq!(parse_sections(&ascii).unwrap(), sections);
+
+        // The project context section is not part of the last file
+        let with_context = format!(
+            "{}\n{}\n// ----- README.md -----\n// File: notes.md",
+            text,
+            context::BANNER
+        );
+        assert_eq!(parse_sections(&with_context).unwrap(), sections);
+    }
+
+    /// Verifies that headerless output is rejected at its first line.
+    #[test]
+    fn test_parse_sections_without_headers() {
+        let err = parse_sections("pragma solidity ^0.8.0;\ncontract A {}").unwrap_err();
+        assert!(err.starts_with("line 1:"), "{err}");
+        assert!(err.contains("--no-headers"));
+    }
+
+    /// Verifies that malformed headers point at the offending line.
+    #[test]
+    fn test_parse_sections_malformed_headers() {
+        let missing_path = format!("{}\ncontract A {{}}", HEADER_SEPARATOR);
+        assert!(
+            parse_sections(&missing_path)
+                .unwrap_err()
+                .starts_with("line 2:")
+        );
+
+        let missing_close = format!("{}\n// File: A.sol\ncontract A {{}}", HEADER_SEPARATOR);
+        assert!(
+            parse_sections(&missing_close)
+                .unwrap_err()
+                .starts_with("line 3:")
+        );
+
+        let mixed = format!(
+            "{}\n// File: A.sol\n{}\ncontract A {{}}",
+            HEADER_SEPARATOR,
+            symbols::ASCII.header_separator
+        );
+        assert!(parse_sections(&mixed).unwrap_err().starts_with("line 3:"));
+
+        // A lone path line starts a compact section (`--no-headers-for`)
+        let compact = format!(
+            "{}\ncontract A {{}}\n// File: B.sol\ncontract B {{}}",
+            header("A.sol")
+        );
+        let sections = parse_sections(&compact).unwrap();
+        assert_eq!((sections[1].path.as_str(), sections[1].line), ("B.sol", 5));
+        assert_eq!(sections[1].content, "contract B {}");
+        let duplicate = format!("{}\n// File: A.sol", header("A.sol"));
+        assert!(
+            parse_sections(&duplicate)
+                .unwrap_err()
+                .starts_with("line 4: duplicate")
+        );
+    }
+
+    /// Verifies that paths escaping the output directory are refused.
+    #[test]
+    fn test_parse_sections_rejects_unsafe_paths() {
+        for path in ["../evil.sol", "/etc/evil.sol", "src/../../evil.sol"] {
+            let text = format!("{}\ncontract A {{}}", header(path));
+            let err = parse_sections(&text).unwrap_err();
+            assert!(err.contains("unsafe path"), "{path}: {err}");
+        }
+    }
+
+    fn section(path: &str, line: usize) -> Section {
+        Section {
+            path: path.to_string(),
+            content: String::new(),
+            line,
+        }
+    }
+
+    /// Verifies that case-only collisions get numeric suffixes and warnings.
+    #[test]
+    fn test_plan_targets_disambiguates_case_collisions() {
+        let sections = [
+            section("Contracts/Token.sol", 2),
+            section("contracts/token.sol", 9),
+            section("CONTRACTS/TOKEN.SOL", 15),
+            section("contracts/Other.sol", 20),
+        ];
+
+        let plan = plan_targets(&sections, false).unwrap();
+
+        assert_eq!(
+            plan.targets,
+            vec![
+                "Contracts/Token.sol",
+                "contracts/token-1.sol",
+                "CONTRACTS/TOKEN-2.SOL",
+                "contracts/Other.sol",
+            ]
+        );
+        assert_eq!(plan.warnings.len(), 2);
+        assert_eq!(
+            plan.warnings[0],
+            "Contracts/Token.sol and contracts/token.sol differ only by case; \
+             writing contracts/token.sol as contracts/token-1.sol"
+        );
+    }
+
+    /// Verifies that strict mode refuses case-only collisions.
+    #[test]
+    fn test_plan_targets_strict() {
+        let sections = [section("A/Token.sol", 2), section("a/token.sol", 9)];
+        let err = plan_targets(&sections, true).unwrap_err();
+        assert_eq!(
+            err,
+            "line 9: a/token.sol collides with A/Token.sol (line 2) on case-insensitive filesystems"
+        );
+        assert_eq!(
+            plan_targets(&sections[..1], true).unwrap().warnings.len(),
+            0
+        );
+        assert_eq!(with_suffix("Makefile", 3), "Makefile-3");
+    }
+
+    /// Verifies that a single section is found by its unescaped path.
+    #[test]
+    fn test_extract_section() {
+        let text = format!(
+            "{}\ncontract A {{}}\n{}\ncontract\n  B {{}}",
+            header("src/A.sol"),
+            header("src/odd\\nname.sol")
+        );
+        assert_eq!(
+            extract_section(&text, "src/A.sol").unwrap(),
+            "contract A {}"
+        );
+        assert_eq!(
+            extract_section(&text, "src/odd\nname.sol").unwrap(),
+            "contract\n  B {}"
+        );
+        assert_eq!(
+            extract_section(&text, "src/C.sol").unwrap_err(),
+            "No section for src/C.sol"
+        );
+    }
+
+    /// Verifies a write failing mid-way through the tree leaves the output
+    /// directory untouched and names the file.
+    #[test]
+    fn test_write_tree_failure_leaves_out_dir_untouched() {
+        let root =
+            std::env::temp_dir().join(format!("solscrape_split_tree_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let out_dir = root.join("tree");
+        fs::create_dir_all(out_dir.join("src")).unwrap();
+        fs::write(out_dir.join("src/A.sol"), "previous\n").unwrap();
+
+        let files = [
+            ("src/A.sol", "contract A {}\n".to_string()),
+            ("src/B.sol", "contract B {}\n".to_string()),
+            ("src/C.sol", "contract C {}\n".to_string()),
+        ];
+        let mut calls = 0;
+        let err = write_tree(&out_dir, &files, |staged, relative, data| {
+            calls += 1;
+            if calls == 2 {
+                return Err("Failed to write output: simulated disk full".to_string());
+            }
+            staged.write(relative, data)
+        })
+        .unwrap_err();
+        assert_eq!(
+            err,
+            format!(
+                "{}: Failed to write output: simulated disk full; {} was left unchanged",
+                out_dir.join("src/B.sol").display(),
+                out_dir.display()
+            )
+        );
+        assert_eq!(calls, 2);
+        let entries: Vec<_> = fs::read_dir(out_dir.join("src")).unwrap().collect();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/A.sol")).unwrap(),
+            "previous\n"
+        );
+        assert!(!out_dir.join(output::STAGING_DIR).exists());
+
+        let written = write_tree(&out_dir, &files, |staged, relative, data| {
+            staged.write(relative, data)
+        })
+        .unwrap();
+        assert_eq!(written.len(), 3);
+        assert_eq!(
+            fs::read_to_string(out_dir.join("src/A.sol")).unwrap(),
+            "contract A {}\n"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that split files take their source's modification time, and
+    /// that a missing source only warns.
+    #[test]
+    fn test_copy_mtimes() {
+        let root =
+            std::env::temp_dir().join(format!("solscrape_split_mtime_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let source = root.join("source");
+        fs::create_dir_all(source.join("src")).unwrap();
+        fs::write(source.join("src/A.sol"), "contract A {}\n").unwrap();
+        let mtime =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        File::options()
+            .write(true)
+            .open(source.join("src/A.sol"))
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        let input = root.join("scraped.sol");
+        let text = format!("{}\nA\n{}\nB", header("src/A.sol"), header("src/Gone.sol"));
+        fs::write(&input, text).unwrap();
+
+        let outcome = split_file(&input, &root.join("tree"), false).unwrap();
+        assert_eq!(outcome.sources, ["src/A.sol", "src/Gone.sol"]);
+        let warnings = copy_mtimes(&outcome, &source);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Gone.sol"), "{}", warnings[0]);
+
+        let copied = fs::metadata(root.join("tree/src/A.sol"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        let drift = copied
+            .duration_since(mtime)
+            .unwrap_or_else(|e| e.duration());
+        assert!(drift < std::time::Duration::from_secs(2), "{:?}", drift);
+        let kept = fs::metadata(root.join("tree/src/Gone.sol"))
+            .unwrap()
+            .modified()
+            .unwrap();
+        assert!(kept > mtime);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    /// Verifies that a path appearing twice is reported.
+    #[test]
+    fn test_parse_sections_rejects_duplicates() {
+        let text = format!("{}\nA\n{}\nB", header("A.sol"), header("A.sol"));
+        assert!(parse_sections(&text).unwrap_err().contains("duplicate"));
+    }
+}
@@ -0,0 +1,1108 @@
+//! Structural checks on cleaned Solidity sources.
+//!
+//! A small lexer yields the identifiers of a source outside comments and
+//! string literals, with their line and brace depth. On top of it, a cheap
+//! declaration scanner finds `contract`, `interface`, and `library`
+//! definitions, and [`has_pragma_solidity`] finds version pragmas. Two shapes are
+//! worth flagging: a file with many top-level contracts (usually a flattened
+//! artifact committed by accident) and definitions nested inside braces,
+//! which Solidity does not allow and which typically come from a previous
+//! flatten gone wrong. Imports binding one name twice, found with
+//! [`imports::parse_imports`], are flagged as well.
+//!
+//! The top-level declarations also classify a file by [`Kind`], which
+//! `--kinds` filters on and `--stats` rolls up, and their `is` clauses tell
+//! [`implemented_interfaces`] which interface files
+//! `--prune-implemented-interfaces` can leave out.
+//!
+//! All brace tracking goes through the same lexer, so braces inside strings
+//! and comments never count. It keeps a depth counter rather than
+//! recursing, and [`check_nesting`] rejects code nested deeper than a limit
+//! (machine-generated code with thousands of nested blocks) before anything
+//! else relies on its depths.
+
+use std::collections::BTreeMap;
+
+use crate::imports;
+
+/// More top-level definitions than this in one file are reported.
+pub const MAX_TOP_LEVEL_CONTRACTS: usize = 10;
+
+/// The deepest brace nesting accepted by default (`--max-brace-depth`).
+pub const MAX_BRACE_DEPTH: usize = 1000;
+
+/// Code whose braces nest deeper than a limit, found by [`check_nesting`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct TooDeep {
+    /// The limit that was passed.
+    pub limit: usize,
+    /// The 1-based line of the first `{` past the limit.
+    pub line: usize,
+}
+
+impl std::fmt::Display for TooDeep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "braces nested deeper than {} (line {})",
+            self.limit, self.line
+        )
+    }
+}
+
+/// A contract-like definition found by [`scan_declarations`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Declaration {
+    /// `"contract"`, `"interface"`, or `"library"`.
+    pub kind: &'static str,
+    /// The declared name, empty if none followed the keyword.
+    pub name: String,
+    /// The 1-based line of the keyword.
+    pub line: usize,
+    /// The number of unclosed `{` before the keyword; 0 is top level.
+    pub depth: usize,
+    /// Whether a contract is declared `abstract`.
+    pub is_abstract: bool,
+    /// The names in the `is` clause, without constructor arguments and
+    /// qualifiers (`is Lib.IVault, Base(1)` gives `IVault`, `Base`).
+    pub bases: Vec<String>,
+}
+
+impl Declaration {
+    /// The declaration's [`Kind`].
+    pub fn kind(&self) -> Kind {
+        match self.kind {
+            "interface" => Kind::Interface,
+            "library" => Kind::Library,
+            _ if self.is_abstract => Kind::Abstract,
+            _ => Kind::Contract,
+        }
+    }
+}
+
+/// What a top-level definition is, as selected by `--kinds`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    Contract,
+    Abstract,
+    Interface,
+    Library,
+}
+
+impl Kind {
+    /// Every kind, in report order.
+    pub const ALL: [Kind; 4] = [
+        Kind::Contract,
+        Kind::Abstract,
+        Kind::Interface,
+        Kind::Library,
+    ];
+
+    /// Parses a kind name, singular or plural, case-insensitively.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// assert_eq!(Kind::parse("libraries"), Some(Kind::Library));
+    /// assert_eq!(Kind::parse("struct"), None);
+    /// ```
+    pub fn parse(name: &str) -> Option<Kind> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "contract" | "contracts" => Some(Kind::Contract),
+            "abstract" | "abstracts" => Some(Kind::Abstract),
+            "interface" | "interfaces" => Some(Kind::Interface),
+            "library" | "libraries" => Some(Kind::Library),
+            _ => None,
+        }
+    }
+
+    /// The kind's name, as accepted by [`Kind::parse`].
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Contract => "contract",
+            Kind::Abstract => "abstract",
+            Kind::Interface => "interface",
+            Kind::Library => "library",
+        }
+    }
+
+    /// How a declaration of this kind reads in source.
+    fn keyword(self) -> &'static str {
+        match self {
+            Kind::Abstract => "abstract contract",
+            kind => kind.name(),
+        }
+    }
+}
+
+/// The number of top-level declarations of each [`Kind`] in a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KindCounts([usize; 4]);
+
+impl KindCounts {
+    /// Counts the top-level declarations of `code`.
+    pub fn of(code: &str) -> Self {
+        let mut counts = Self::default();
+        for declaration in scan_declarations(code).iter().filter(|d| d.depth == 0) {
+            counts.0[declaration.kind() as usize] += 1;
+        }
+        counts
+    }
+
+    /// The number of declarations of `kind`.
+    pub fn get(&self, kind: Kind) -> usize {
+        self.0[kind as usize]
+    }
+
+    /// The number of declarations of any kind.
+    pub fn total(&self) -> usize {
+        self.0.iter().sum()
+    }
+
+    /// The only kind declared, or `None` for files with several kinds or
+    /// no declarations.
+    pub fn single_kind(&self) -> Option<Kind> {
+        let mut present = Kind::ALL.into_iter().filter(|&kind| self.get(kind) > 0);
+        match (present.next(), present.next()) {
+            (Some(kind), None) => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// Adds the counts of `other`.
+    pub fn add(&mut self, other: &KindCounts) {
+        for (mine, theirs) in self.0.iter_mut().zip(other.0) {
+            *mine += theirs;
+        }
+    }
+}
+
+/// How a file's top-level declarations match a `--kinds` selection.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KindMatch {
+    /// Every declaration is of a wanted kind.
+    All,
+    /// Some declarations are; the others are described, e.g.
+    /// `interface IVault (line 3)`.
+    Mixed(Vec<String>),
+    /// None are; the declarations found are described, or the list is empty
+    /// when the file declares nothing.
+    None(Vec<String>),
+}
+
+/// Checks the top-level declarations of `code` against the `wanted` kinds.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let code = "interface IVault {}\ncontract Vault is IVault {}";
+/// assert_eq!(
+///     match_kinds(code, &[Kind::Contract]),
+///     KindMatch::Mixed(vec!["interface IVault (line 1)".to_string()])
+/// );
+/// ```
+pub fn match_kinds(code: &str, wanted: &[Kind]) -> KindMatch {
+    let (matching, other): (Vec<Declaration>, Vec<Declaration>) = scan_declarations(code)
+        .into_iter()
+        .filter(|d| d.depth == 0)
+        .partition(|d| wanted.contains(&d.kind()));
+    let other: Vec<String> = other
+        .iter()
+        .map(|d| format!("{} {} (line {})", d.kind().keyword(), d.name, d.line))
+        .collect();
+    match (matching.is_empty(), other.is_empty()) {
+        (false, true) => KindMatch::All,
+        (false, false) => KindMatch::Mixed(other),
+        (true, _) => KindMatch::None(other),
+    }
+}
+
+/// An identifier or keyword found by [`words`].
+struct Word {
+    text: String,
+    line: usize,
+    depth: usize,
+    /// The char index just past the word.
+    end: usize,
+}
+
+/// Splits `code` into identifiers outside comments and string literals.
+///
+/// Braces are counted without a limit; unbalanced input yields
+/// best-effort depths rather than an error.
+fn words(code: &str) -> Vec<Word> {
+    lex(code, usize::MAX).map_or_else(|_| Vec::new(), |lexed| lexed.words)
+}
+
+/// Checks that the braces of `code` nest no deeper than `limit`.
+///
+/// Only braces outside comments and string literals count. A `}` without a
+/// matching `{` is ignored.
+///
+/// # Returns
+///
+/// The deepest nesting found.
+///
+/// # Errors
+///
+/// Returns [`TooDeep`] at the first `{` past the limit.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(check_nesting("contract A { string s = \"{{{\"; }", 1), Ok(1));
+/// assert_eq!(check_nesting("{{}}", 1).unwrap_err().line, 1);
+/// ```
+pub fn check_nesting(code: &str, limit: usize) -> Result<usize, TooDeep> {
+    lex(code, limit).map(|lexed| lexed.deepest)
+}
+
+/// What [`lex`] finds in code.
+struct Lexed {
+    /// The identifiers outside comments and string literals.
+    words: Vec<Word>,
+    /// The deepest brace nesting.
+    deepest: usize,
+    /// The char index of each `}` that closes a top-level `{`.
+    top_level_closes: Vec<usize>,
+}
+
+/// The lexer behind [`words`], [`check_nesting`], and [`scopes`], stopping
+/// at a `{` past `limit`.
+fn lex(code: &str, limit: usize) -> Result<Lexed, TooDeep> {
+    let chars: Vec<char> = code.chars().collect();
+    let mut words = Vec::new();
+    let mut top_level_closes = Vec::new();
+    let mut depth = 0usize;
+    let mut deepest = 0usize;
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => line += 1,
+            '{' => {
+                if depth == limit {
+                    return Err(TooDeep { limit, line });
+                }
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            '}' => {
+                if depth == 1 {
+                    top_level_closes.push(i);
+                }
+                depth = depth.saturating_sub(1);
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    if chars[i] == '\n' {
+                        line += 1;
+                    }
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '"' | '\'' => {
+                i += 1;
+                while i < chars.len() && chars[i] != c && chars[i] != '\n' {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            _ if is_ident_start(c) => {
+                let text = read_ident(&chars, &mut i);
+                words.push(Word {
+                    text,
+                    line,
+                    depth,
+                    end: i,
+                });
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Ok(Lexed {
+        words,
+        deepest,
+        top_level_closes,
+    })
+}
+
+/// The top-level contract, interface, and library bodies of some code,
+/// found by [`scopes`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Scopes(Vec<(String, std::ops::Range<usize>)>);
+
+impl Scopes {
+    /// The name of the definition whose keyword or body holds the char at
+    /// `index`, or `None` at file scope.
+    pub fn at(&self, index: usize) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, range)| range.contains(&index))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Finds the char range of each top-level definition in `code`, from its
+/// keyword to the `}` closing its body, to tell which one a position is in.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let scopes = scopes("error E();\ncontract A { error F(); }");
+/// assert_eq!(scopes.at(0), None);
+/// assert_eq!(scopes.at(20), Some("A"));
+/// ```
+pub fn scopes(code: &str) -> Scopes {
+    let Ok(lexed) = lex(code, usize::MAX) else {
+        return Scopes::default();
+    };
+    let mut spans = Vec::new();
+    for (i, word) in lexed.words.iter().enumerate() {
+        if word.depth != 0 || !["contract", "interface", "library"].contains(&word.text.as_str()) {
+            continue;
+        }
+        let start = word.end - word.text.chars().count();
+        let end = lexed
+            .top_level_closes
+            .iter()
+            .find(|&&close| close > word.end)
+            .map_or(usize::MAX, |close| close + 1);
+        let name = lexed.words.get(i + 1).map(|next| next.text.clone());
+        spans.push((name.unwrap_or_default(), start..end));
+    }
+    Scopes(spans)
+}
+
+/// A custom error definition found by [`scan_errors`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorDeclaration {
+    /// The error's name.
+    pub name: String,
+    /// The parameter list between the parentheses, with whitespace
+    /// collapsed, e.g. `"address caller, uint256 amount"`.
+    pub parameters: String,
+    /// The top-level definition it is declared in, `None` at file scope.
+    pub contract: Option<String>,
+}
+
+/// Scans `code` for `error Name(...);` definitions, outside comments and
+/// string literals.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let errors = scan_errors("error Zero();\ncontract A { error Late(uint256  at); }");
+/// assert_eq!(errors[1].contract.as_deref(), Some("A"));
+/// assert_eq!(errors[1].parameters, "uint256 at");
+/// ```
+pub fn scan_errors(code: &str) -> Vec<ErrorDeclaration> {
+    let words = words(code);
+    let chars: Vec<char> = code.chars().collect();
+    let scopes = scopes(code);
+    let mut errors = Vec::new();
+    for pair in words.windows(2) {
+        let (keyword, name) = (&pair[0], &pair[1]);
+        let start = keyword.end - keyword.text.chars().count();
+        if keyword.text != "error"
+            || name.depth != keyword.depth
+            || chars[..start].iter().rev().find(|c| !c.is_whitespace()) == Some(&'.')
+        {
+            continue;
+        }
+        let Some(open) = (name.end..chars.len()).find(|&j| !chars[j].is_whitespace()) else {
+            continue;
+        };
+        if chars[open] != '(' {
+            continue;
+        }
+        let mut parens = 0usize;
+        let Some(close) = (open..chars.len()).find(|&j| {
+            match chars[j] {
+                '(' => parens += 1,
+                ')' => parens -= 1,
+                _ => {}
+            }
+            parens == 0
+        }) else {
+            continue;
+        };
+        let parameters: String = chars[open + 1..close].iter().collect();
+        errors.push(ErrorDeclaration {
+            name: name.text.clone(),
+            parameters: parameters.split_whitespace().collect::<Vec<_>>().join(" "),
+            contract: scopes
+                .at(start)
+                .filter(|_| keyword.depth > 0)
+                .map(str::to_string),
+        });
+    }
+    errors
+}
+
+/// Scans `code` for contract, interface, and library definitions.
+///
+/// Comments and string literals are skipped, so keywords inside them are not
+/// reported.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let decls = scan_declarations("contract A { }\nlibrary B { }");
+/// assert_eq!(decls[1].name, "B");
+/// ```
+pub fn scan_declarations(code: &str) -> Vec<Declaration> {
+    let words = words(code);
+    let chars: Vec<char> = code.chars().collect();
+    let mut declarations = Vec::new();
+
+    for (i, word) in words.iter().enumerate() {
+        let Some(kind) = ["contract", "interface", "library"]
+            .into_iter()
+            .find(|k| *k == word.text)
+        else {
+            continue;
+        };
+        // The name is the next identifier; `contract {` leaves it empty
+        let name = words
+            .get(i + 1)
+            .filter(|next| next.depth == word.depth)
+            .map(|next| next.text.clone())
+            .unwrap_or_default();
+        let is_abstract =
+            i > 0 && words[i - 1].text == "abstract" && words[i - 1].depth == word.depth;
+        let bases = match words.get(i + 2) {
+            Some(next) if next.text == "is" && next.depth == word.depth => {
+                read_bases(&chars, next.end)
+            }
+            _ => Vec::new(),
+        };
+        declarations.push(Declaration {
+            kind,
+            name,
+            line: word.line,
+            depth: word.depth,
+            is_abstract,
+            bases,
+        });
+    }
+
+    declarations
+}
+
+/// Reads the base names of an `is` clause starting at `chars[from]`, up to
+/// the `{` opening the body.
+///
+/// Each comma-separated base contributes its last dotted segment; anything
+/// in parentheses (constructor arguments) and comments are passed over.
+fn read_bases(chars: &[char], from: usize) -> Vec<String> {
+    let mut bases = Vec::new();
+    let mut parens = 0usize;
+    let mut expecting = true;
+    let mut i = from;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '{' | ';' if parens == 0 => break,
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+                continue;
+            }
+            '(' => parens += 1,
+            ')' => parens = parens.saturating_sub(1),
+            ',' if parens == 0 => expecting = true,
+            _ if parens == 0 && is_ident_start(c) => {
+                let mut name = read_ident(chars, &mut i);
+                while chars.get(i) == Some(&'.')
+                    && chars.get(i + 1).is_some_and(|c| is_ident_start(*c))
+                {
+                    i += 1;
+                    name = read_ident(chars, &mut i);
+                }
+                if expecting {
+                    bases.push(name);
+                    expecting = false;
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    bases
+}
+
+/// An interface-only file whose interfaces are all implemented by contracts
+/// in other files, found by [`implemented_interfaces`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Implemented {
+    /// The interface file.
+    pub path: String,
+    /// Each of its interfaces, with the first contract naming it in an `is`
+    /// clause and that contract's file: `(interface, contract, path)`.
+    pub implementers: Vec<(String, String, String)>,
+}
+
+/// What [`implemented_interfaces`] found.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InterfacePruning {
+    /// The interface files that can be left out, in input order.
+    pub implemented: Vec<Implemented>,
+    /// Interface names declared by more than one file, each with those
+    /// files; a file declaring one is kept, since which of the declarations
+    /// a contract implements cannot be told by name.
+    pub ambiguous: Vec<(String, Vec<String>)>,
+}
+
+/// Finds the files of `files`, given as `(path, source)`, whose top-level
+/// declarations are all interfaces implemented by a contract (abstract or
+/// not) in another of the files, matching `is` clauses by name.
+///
+/// An interface nothing implements keeps its file, as it may be the only
+/// description of an external dependency.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let files = [
+///     ("src/IVault.sol".to_string(), "interface IVault {}".to_string()),
+///     ("src/Vault.sol".to_string(), "contract Vault is IVault {}".to_string()),
+/// ];
+/// let found = implemented_interfaces(&files);
+/// assert_eq!(found.implemented[0].implementers[0].1, "Vault");
+/// ```
+pub fn implemented_interfaces(files: &[(String, String)]) -> InterfacePruning {
+    let top_level: Vec<(&str, Vec<Declaration>)> = files
+        .iter()
+        .map(|(path, code)| {
+            let declarations = scan_declarations(code)
+                .into_iter()
+                .filter(|d| d.depth == 0 && !d.name.is_empty())
+                .collect();
+            (path.as_str(), declarations)
+        })
+        .collect();
+
+    let mut declared: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut implementers: BTreeMap<&str, (&str, &str)> = BTreeMap::new();
+    for (path, declarations) in &top_level {
+        for declaration in declarations {
+            let files = declared.entry(declaration.name.as_str()).or_default();
+            if !files.contains(path) {
+                files.push(path);
+            }
+            if declaration.kind == "contract" {
+                for base in &declaration.bases {
+                    implementers
+                        .entry(base.as_str())
+                        .or_insert((declaration.name.as_str(), path));
+                }
+            }
+        }
+    }
+
+    let mut found = InterfacePruning::default();
+    for (path, declarations) in &top_level {
+        if declarations.is_empty() || declarations.iter().any(|d| d.kind != "interface") {
+            continue;
+        }
+        let mut implemented = Vec::new();
+        for declaration in declarations {
+            let name = declaration.name.as_str();
+            if declared[name].len() > 1 {
+                if !found.ambiguous.iter().any(|(n, _)| n == name) {
+                    let files = declared[name].iter().map(|p| p.to_string()).collect();
+                    found.ambiguous.push((name.to_string(), files));
+                }
+                break;
+            }
+            match implementers.get(name) {
+                Some((contract, file)) if file != path => {
+                    implemented.push((name.to_string(), contract.to_string(), file.to_string()));
+                }
+                _ => break,
+            }
+        }
+        if implemented.len() == declarations.len() {
+            found.implemented.push(Implemented {
+                path: path.to_string(),
+                implementers: implemented,
+            });
+        }
+    }
+    found
+}
+
+/// Returns true if `code` contains a `pragma solidity` directive.
+///
+/// The words must appear as code: occurrences inside comments or string
+/// literals do not count.
+pub fn has_pragma_solidity(code: &str) -> bool {
+    words(code)
+        .windows(2)
+        .any(|pair| pair[0].text == "pragma" && pair[1].text == "solidity")
+}
+
+/// Returns true if `code` has an `assembly` block, outside comments and
+/// string literals.
+pub fn uses_assembly(code: &str) -> bool {
+    words(code).iter().any(|word| word.text == "assembly")
+}
+
+/// Returns the version constraint of each `pragma solidity` directive in `code`.
+///
+/// A constraint runs from after `solidity` to the next `;` and is returned
+/// trimmed, without the semicolon, e.g. `"^0.8.0"` or `">=0.7 <0.9"`.
+pub fn solidity_pragmas(code: &str) -> Vec<String> {
+    let chars: Vec<char> = code.chars().collect();
+    words(code)
+        .windows(2)
+        .filter(|pair| pair[0].text == "pragma" && pair[1].text == "solidity")
+        .map(|pair| {
+            chars[pair[1].end..]
+                .iter()
+                .take_while(|c| **c != ';')
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .collect()
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '$'
+}
+
+/// Reads an identifier starting at `chars[*i]`, leaving `i` just past it.
+fn read_ident(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while *i < chars.len()
+        && (chars[*i].is_ascii_alphanumeric() || chars[*i] == '_' || chars[*i] == '$')
+    {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+/// Returns human-readable warnings about suspicious structure in `code`.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let findings = structural_findings("contract A { contract B {} }");
+/// // ["nested definition inside braces: contract B (line 1); possibly a corrupted flatten"]
+/// ```
+pub fn structural_findings(code: &str) -> Vec<String> {
+    let declarations = scan_declarations(code);
+    let mut findings = Vec::new();
+
+    let top_level = declarations.iter().filter(|d| d.depth == 0).count();
+    if top_level > MAX_TOP_LEVEL_CONTRACTS {
+        findings.push(format!(
+            "{} top-level contract/interface/library definitions (more than {}); \
+             possibly a committed flattened artifact",
+            top_level, MAX_TOP_LEVEL_CONTRACTS
+        ));
+    }
+
+    let nested: Vec<String> = declarations
+        .iter()
+        .filter(|d| d.depth > 0)
+        .map(|d| format!("{} {} (line {})", d.kind, d.name, d.line))
+        .collect();
+    if !nested.is_empty() {
+        let noun = if nested.len() == 1 {
+            "definition"
+        } else {
+            "definitions"
+        };
+        findings.push(format!(
+            "nested {} inside braces: {}; possibly a corrupted flatten",
+            noun,
+            nested.join(", ")
+        ));
+    }
+
+    for (name, lines) in imports::conflicting_names(&imports::parse_imports(code)) {
+        let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+        findings.push(format!(
+            "`{}` is imported more than once (lines {}); solc rejects the redeclaration",
+            name,
+            lines.join(", ")
+        ));
+    }
+
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies declarations are found with depths, skipping comments and strings.
+    #[test]
+    fn test_scan_declarations() {
+        let code = "// contract InComment {}\n\
+                    abstract contract Base {\n    string s = \"contract InString {\";\n}\n\
+                    interface IToken {}\n\
+                    library Math { }\n";
+        let decls = scan_declarations(code);
+
+        let names: Vec<(&str, &str, usize, usize)> = decls
+            .iter()
+            .map(|d| (d.kind, d.name.as_str(), d.line, d.depth))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("contract", "Base", 2, 0),
+                ("interface", "IToken", 5, 0),
+                ("library", "Math", 6, 0),
+            ]
+        );
+        let kinds: Vec<Kind> = decls.iter().map(Declaration::kind).collect();
+        assert_eq!(kinds, [Kind::Abstract, Kind::Interface, Kind::Library]);
+
+        let code = "contract Vault is Lib.IVault, Base(1, owner), /* c */ IOwnable {}\n\
+                    interface IVault is IERC20{}\ncontract Plain {}";
+        let bases: Vec<Vec<String>> = scan_declarations(code)
+            .into_iter()
+            .map(|d| d.bases)
+            .collect();
+        assert_eq!(
+            bases,
+            [vec!["IVault", "Base", "IOwnable"], vec!["IERC20"], vec![]]
+        );
+    }
+
+    /// Verifies errors at file scope and inside contracts, the scope of each
+    /// position, and that `error` in other roles is not a definition.
+    #[test]
+    fn test_scan_errors() {
+        let code = "error Zero();\n\
+                    contract Vault {\n    error Unauthorized(address  caller,\n        uint256 amount);\n    \
+                    event Failed(string error);\n    string s = \"error Fake()\";\n}\n\
+                    library Math { error Overflow(); }\nerror Late(uint256 at);\n";
+        let errors: Vec<(String, String, Option<String>)> = scan_errors(code)
+            .into_iter()
+            .map(|e| (e.name, e.parameters, e.contract))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                ("Zero".into(), "".into(), None),
+                (
+                    "Unauthorized".into(),
+                    "address caller, uint256 amount".into(),
+                    Some("Vault".into())
+                ),
+                ("Overflow".into(), "".into(), Some("Math".into())),
+                ("Late".into(), "uint256 at".into(), None),
+            ]
+        );
+
+        let scopes = scopes(code);
+        let at = |needle: &str| scopes.at(code.find(needle).unwrap());
+        assert_eq!(at("Zero"), None);
+        assert_eq!(at("contract Vault"), Some("Vault"));
+        assert_eq!(at("Fake"), Some("Vault"));
+        assert_eq!(at("Overflow"), Some("Math"));
+        assert_eq!(at("Late"), None);
+    }
+
+    /// Verifies that implemented interface files are found with their
+    /// implementers, and that unimplemented, mixed, and ambiguous ones are
+    /// kept.
+    #[test]
+    fn test_implemented_interfaces() {
+        let files: Vec<(String, String)> = [
+            (
+                "src/IVault.sol",
+                "interface IVault {}\ninterface IVaultEvents {}",
+            ),
+            ("src/Vault.sol", "contract Vault is IVault, IVaultEvents {}"),
+            ("src/IOracle.sol", "interface IOracle {}"),
+            ("src/IToken.sol", "interface IToken {}\nlibrary TokenLib {}"),
+            ("src/Token.sol", "abstract contract Token is IToken {}"),
+            ("src/IPool.sol", "interface IPool {}"),
+            ("lib/IPool.sol", "interface IPool {}"),
+            ("src/Pool.sol", "contract Pool is IPool {}"),
+            ("src/IExtended.sol", "interface IExtended {}"),
+            ("src/IChild.sol", "interface IChild is IExtended {}"),
+        ]
+        .iter()
+        .map(|(path, code)| (path.to_string(), code.to_string()))
+        .collect();
+
+        let found = implemented_interfaces(&files);
+        assert_eq!(
+            found.implemented,
+            [Implemented {
+                path: "src/IVault.sol".to_string(),
+                implementers: vec![
+                    (
+                        "IVault".to_string(),
+                        "Vault".to_string(),
+                        "src/Vault.sol".to_string()
+                    ),
+                    (
+                        "IVaultEvents".to_string(),
+                        "Vault".to_string(),
+                        "src/Vault.sol".to_string()
+                    ),
+                ],
+            }]
+        );
+        assert_eq!(
+            found.ambiguous,
+            [(
+                "IPool".to_string(),
+                vec!["src/IPool.sol".to_string(), "lib/IPool.sol".to_string()]
+            )]
+        );
+    }
+
+    /// Verifies kind names and per-file counts.
+    #[test]
+    fn test_kind_counts() {
+        assert_eq!(Kind::parse("Contracts"), Some(Kind::Contract));
+        assert_eq!(Kind::parse(" abstract"), Some(Kind::Abstract));
+        assert_eq!(Kind::parse("interface"), Some(Kind::Interface));
+        assert_eq!(Kind::parse("libraries"), Some(Kind::Library));
+        assert_eq!(Kind::parse("struct"), None);
+        for kind in Kind::ALL {
+            assert_eq!(Kind::parse(kind.name()), Some(kind));
+        }
+
+        let counts = KindCounts::of(
+            "contract A { function f() {} }\ncontract B {}\nlibrary L {}\n\
+             abstract contract C { }",
+        );
+        assert_eq!(counts.get(Kind::Contract), 2);
+        assert_eq!(counts.get(Kind::Abstract), 1);
+        assert_eq!(counts.get(Kind::Library), 1);
+        assert_eq!(counts.total(), 4);
+        assert_eq!(counts.single_kind(), None);
+        assert_eq!(
+            KindCounts::of("interface I {}").single_kind(),
+            Some(Kind::Interface)
+        );
+        assert_eq!(
+            KindCounts::of("function free() pure {}").single_kind(),
+            None
+        );
+
+        let mut sum = counts;
+        sum.add(&KindCounts::of("interface I {}"));
+        assert_eq!(sum.total(), 5);
+    }
+
+    /// Verifies matching each kind on its own, mixed files, and files
+    /// without declarations.
+    #[test]
+    fn test_match_kinds() {
+        let files = [
+            ("contract A {}", Kind::Contract),
+            ("abstract contract A {}", Kind::Abstract),
+            ("interface IA {}", Kind::Interface),
+            ("library A {}", Kind::Library),
+        ];
+        for (code, kind) in files {
+            assert_eq!(match_kinds(code, &[kind]), KindMatch::All, "{}", code);
+            let others: Vec<Kind> = Kind::ALL.into_iter().filter(|k| *k != kind).collect();
+            assert!(
+                matches!(match_kinds(code, &others), KindMatch::None(found) if found.len() == 1)
+            );
+        }
+
+        let mixed = "interface IVault {}\ncontract Vault is IVault { }\n";
+        assert_eq!(
+            match_kinds(mixed, &[Kind::Contract]),
+            KindMatch::Mixed(vec!["interface IVault (line 1)".to_string()])
+        );
+        assert_eq!(
+            match_kinds(mixed, &[Kind::Library]),
+            KindMatch::None(vec![
+                "interface IVault (line 1)".to_string(),
+                "contract Vault (line 2)".to_string()
+            ])
+        );
+        assert_eq!(
+            match_kinds("abstract contract B {}", &[Kind::Contract]),
+            KindMatch::None(vec!["abstract contract B (line 1)".to_string()])
+        );
+        assert_eq!(
+            match_kinds("uint constant X = 1;", &Kind::ALL),
+            KindMatch::None(vec![])
+        );
+    }
+
+    /// Verifies pragma detection ignores comments and strings.
+    #[test]
+    fn test_has_pragma_solidity() {
+        assert!(has_pragma_solidity(
+            "pragma solidity ^0.8.0;\ncontract A {}"
+        ));
+        assert!(has_pragma_solidity("// SPDX\npragma   solidity >=0.7;"));
+        assert!(!has_pragma_solidity("pragma abicoder v2;\ncontract A {}"));
+        assert!(!has_pragma_solidity(
+            "// pragma solidity ^0.8.0;\ncontract A {}"
+        ));
+        assert!(!has_pragma_solidity(
+            "/* pragma solidity 0.8 */ contract A {}"
+        ));
+        assert!(!has_pragma_solidity(
+            "contract A { string s = \"pragma solidity 0.8\"; }"
+        ));
+
+        assert!(uses_assembly("function f() { assembly { } }"));
+        assert!(!uses_assembly("// assembly\nstring s = \"assembly\";"));
+    }
+
+    /// Verifies pragma constraints are extracted as written.
+    #[test]
+    fn test_solidity_pragmas() {
+        let code = "pragma solidity >=0.7.0 <0.9.0;\npragma abicoder v2;\n\
+                    pragma solidity ^0.8.0 ;\ncontract A { string s = \"pragma solidity 0.4;\"; }";
+        assert_eq!(solidity_pragmas(code), vec![">=0.7.0 <0.9.0", "^0.8.0"]);
+        assert!(solidity_pragmas("contract A {}").is_empty());
+    }
+
+    /// Verifies flagging of files with too many top-level contracts.
+    #[test]
+    fn test_findings_flattened_artifact() {
+        let many: String = (0..=MAX_TOP_LEVEL_CONTRACTS)
+            .map(|n| format!("contract C{} {{}}\n", n))
+            .collect();
+        let findings = structural_findings(&many);
+        assert_eq!(findings.len(), 1);
+        assert!(findings[0].starts_with("11 top-level"), "{}", findings[0]);
+
+        let few: String = (0..MAX_TOP_LEVEL_CONTRACTS)
+            .map(|n| format!("contract C{} {{}}\n", n))
+            .collect();
+        assert!(structural_findings(&few).is_empty());
+    }
+
+    /// Verifies flagging of definitions nested inside braces.
+    #[test]
+    fn test_findings_nested_definitions() {
+        let code = "contract Outer {\n    function f() public {}\ncontract Inner {\n}\n}\n";
+        assert_eq!(
+            structural_findings(code),
+            vec![
+                "nested definition inside braces: contract Inner (line 3); \
+                 possibly a corrupted flatten"
+                    .to_string()
+            ]
+        );
+    }
+
+    /// Verifies flagging of a name bound by two aliased imports.
+    #[test]
+    fn test_findings_conflicting_imports() {
+        let code = "import {IERC20 as IToken} from \"lib/a/IERC20.sol\";\n\
+                    import \"lib/b/IERC20.sol\" as IToken;\ncontract A {}\n";
+        assert_eq!(
+            structural_findings(code),
+            vec![
+                "`IToken` is imported more than once (lines 1, 2); \
+                 solc rejects the redeclaration"
+                    .to_string()
+            ]
+        );
+        assert!(structural_findings("import {A} from \"a\";\nimport {B} from \"b\";").is_empty());
+    }
+
+    /// `depth` nested blocks, one brace per line, around `contract Deep {}`,
+    /// whose own braces are one level deeper.
+    fn nested(depth: usize) -> String {
+        format!(
+            "{}contract Deep {{}}\n{}",
+            "{\n".repeat(depth),
+            "}\n".repeat(depth)
+        )
+    }
+
+    /// Verifies the limit at and past its edge, and that generated nesting
+    /// far deeper than any real file is scanned without recursion.
+    #[test]
+    fn test_check_nesting_depth() {
+        assert_eq!(check_nesting("", MAX_BRACE_DEPTH), Ok(0));
+        assert_eq!(
+            check_nesting(&nested(MAX_BRACE_DEPTH - 1), MAX_BRACE_DEPTH),
+            Ok(MAX_BRACE_DEPTH)
+        );
+        let too_deep = check_nesting(&nested(MAX_BRACE_DEPTH), MAX_BRACE_DEPTH).unwrap_err();
+        assert_eq!(
+            too_deep,
+            TooDeep {
+                limit: MAX_BRACE_DEPTH,
+                line: MAX_BRACE_DEPTH + 1
+            }
+        );
+        assert_eq!(
+            too_deep.to_string(),
+            "braces nested deeper than 1000 (line 1001)"
+        );
+
+        let deep = nested(200_000);
+        assert_eq!(check_nesting(&deep, usize::MAX), Ok(200_001));
+        let decls = scan_declarations(&deep);
+        assert_eq!((decls.len(), decls[0].depth), (1, 200_000));
+        assert_eq!(structural_findings(&deep).len(), 1);
+        // Closing braces past the top level are ignored, not counted down
+        assert_eq!(check_nesting(&"}".repeat(50_000), 1), Ok(0));
+    }
+
+    /// Verifies braces inside strings and comments never count, including
+    /// unbalanced ones, escaped quotes, and a string cut off by a newline.
+    #[test]
+    fn test_check_nesting_adversarial_strings() {
+        let cases = [
+            ("contract A { string s = \"}}}}{\"; }", 1),
+            ("contract A { bytes1 c = '{'; string t = '}'; }", 1),
+            ("contract A { string s = \"\\\"{{\"; }", 1),
+            (
+                "contract A { string s = unicode\"{{{ ☃\"; bytes h = hex\"7b7b\"; }",
+                1,
+            ),
+            ("// {{{{\n/* }}}}{{{{ */\ncontract A { /* { */ }", 1),
+            ("contract A { string s = \"{{{\n{ }", 2),
+            ("contract A { function f() { if (x) { y(\"}\"); } } }", 3),
+        ];
+        for (code, deepest) in cases {
+            assert_eq!(check_nesting(code, 3), Ok(deepest), "{}", code);
+        }
+        let decls = scan_declarations("contract A { string s = \"}\"; }\ncontract B {}");
+        assert!(decls.iter().all(|d| d.depth == 0), "{:?}", decls);
+        let hidden = format!("contract A {{ string s = \"{}\"; }}", "{".repeat(5_000));
+        assert_eq!(check_nesting(&hidden, 1), Ok(1));
+    }
+}
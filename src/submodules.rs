@@ -0,0 +1,531 @@
+//! Filling the `lib/` submodules of a clone, from the network or a local
+//! cache of checkouts (`--include-lib`, `--offline`, `--foundry-cache`).
+//!
+//! A shallow clone leaves each submodule as an empty directory. With
+//! `--include-lib` solscrape runs `git submodule update --init`; when that
+//! fails, or under `--offline`, each submodule still empty is matched by the
+//! URL `.gitmodules` records against the git checkouts under a cache root
+//! (by default `~/.foundry`), and the matching checkout is copied in. The
+//! cached checkout is used as it is, so its commit may differ from the one
+//! the repository pins.
+
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// The variable holding the home directory the default cache is under.
+pub const HOME_ENV: &str = "HOME";
+
+/// The default cache root, relative to the home directory.
+pub const DEFAULT_CACHE: &str = ".foundry";
+
+/// How many directories below the cache root checkouts are looked for, deep
+/// enough for `{project}/lib/{dependency}/lib/{nested}`.
+const CACHE_DEPTH: usize = 4;
+
+/// One `[submodule "name"]` entry of `.gitmodules`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Submodule {
+    /// The name in the section header.
+    pub name: String,
+    /// The checkout directory, relative to the repository root.
+    pub path: String,
+    /// The URL it is cloned from.
+    pub url: String,
+}
+
+/// One `key = value` line of a git config file, with its section.
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    /// The section name, lowercased, as `submodule` in `[submodule "x"]`.
+    section: String,
+    /// The quoted subsection, as `x` in `[submodule "x"]`.
+    subsection: Option<String>,
+    /// The key, lowercased.
+    key: String,
+    /// The value, unquoted and unescaped.
+    value: String,
+}
+
+/// Parses the entries of a git config file such as `.gitmodules`.
+///
+/// Handles quoted subsections and values, backslash escapes, and `#` or `;`
+/// comments outside quotes. Lines that are not understood are skipped.
+fn parse_config(text: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut section = String::new();
+    let mut subsection = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if let Some(header) = line.strip_prefix('[') {
+            let Some(end) = header.rfind(']') else {
+                continue;
+            };
+            let header = header[..end].trim();
+            match header.split_once(char::is_whitespace) {
+                Some((name, rest)) => {
+                    section = name.to_ascii_lowercase();
+                    subsection = Some(unquote(rest.trim()));
+                }
+                None => {
+                    section = header.to_ascii_lowercase();
+                    subsection = None;
+                }
+            }
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let key = key.trim();
+        if section.is_empty() || key.is_empty() || key.starts_with(['#', ';']) {
+            continue;
+        }
+        entries.push(Entry {
+            section: section.clone(),
+            subsection: subsection.clone(),
+            key: key.to_ascii_lowercase(),
+            value: unquote(value.trim()),
+        });
+    }
+    entries
+}
+
+/// Removes quotes and escapes from a config value, and a trailing comment
+/// outside quotes.
+fn unquote(raw: &str) -> String {
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' => match chars.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some(other) => value.push(other),
+                None => {}
+            },
+            '#' | ';' if !quoted => break,
+            _ => value.push(c),
+        }
+    }
+    if raw.ends_with('"') {
+        value
+    } else {
+        value.trim_end().to_string()
+    }
+}
+
+/// Parses the submodules of a `.gitmodules` file, in file order.
+///
+/// Entries without both a `path` and a `url` are dropped.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let submodules = parse_gitmodules(
+///     "[submodule \"lib/forge-std\"]\n\
+///      \tpath = lib/forge-std\n\
+///      \turl = https://github.com/foundry-rs/forge-std\n",
+/// );
+/// assert_eq!(submodules[0].path, "lib/forge-std");
+/// ```
+pub fn parse_gitmodules(text: &str) -> Vec<Submodule> {
+    let mut submodules: Vec<(String, Option<String>, Option<String>)> = Vec::new();
+    for entry in parse_config(text) {
+        let Some(name) = entry.subsection.filter(|_| entry.section == "submodule") else {
+            continue;
+        };
+        let index = match submodules.iter().position(|(n, _, _)| *n == name) {
+            Some(index) => index,
+            None => {
+                submodules.push((name, None, None));
+                submodules.len() - 1
+            }
+        };
+        match entry.key.as_str() {
+            "path" => submodules[index].1 = Some(entry.value),
+            "url" => submodules[index].2 = Some(entry.value),
+            _ => {}
+        }
+    }
+    submodules
+        .into_iter()
+        .filter_map(|(name, path, url)| {
+            Some(Submodule {
+                name,
+                path: path?,
+                url: url?,
+            })
+        })
+        .collect()
+}
+
+/// Reduces a git URL to `host/owner/repo`, so the HTTPS and SSH forms of
+/// one repository compare equal.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(
+///     normalize_url("git@github.com:OpenZeppelin/openzeppelin-contracts.git"),
+///     normalize_url("https://github.com/openzeppelin/openzeppelin-contracts")
+/// );
+/// ```
+pub fn normalize_url(url: &str) -> String {
+    let url = url.trim();
+    let rest = match url.split_once("://") {
+        Some((_, rest)) => rest.to_string(),
+        // scp-like `git@host:owner/repo`
+        None => url.replacen(':', "/", 1),
+    };
+    let rest = match rest.split_once('/') {
+        Some((authority, path)) => {
+            let host = authority.rsplit('@').next().unwrap_or(authority);
+            format!("{}/{}", host, path)
+        }
+        None => rest,
+    };
+    let rest = rest.trim_end_matches('/');
+    rest.strip_suffix(".git")
+        .unwrap_or(rest)
+        .to_ascii_lowercase()
+}
+
+/// Resolves the cache root: `--foundry-cache` when given, otherwise
+/// [`DEFAULT_CACHE`] under a non-empty `home` (the value of [`HOME_ENV`]).
+pub fn cache_root(explicit: Option<&Path>, home: Option<OsString>) -> Option<PathBuf> {
+    if let Some(dir) = explicit {
+        return Some(dir.to_path_buf());
+    }
+    match home {
+        Some(home) if !home.is_empty() => Some(PathBuf::from(home).join(DEFAULT_CACHE)),
+        _ => None,
+    }
+}
+
+/// The git checkouts under a cache root, keyed by their normalized
+/// `origin` URL.
+#[derive(Debug, Default)]
+pub struct Cache {
+    /// Normalized URL and checkout directory, in walk order.
+    checkouts: Vec<(String, PathBuf)>,
+}
+
+impl Cache {
+    /// Finds the checkouts up to a few directories below `root`.
+    ///
+    /// A checkout is a directory with a `.git` directory, or a `.git` file
+    /// pointing at one, whose config has a `[remote "origin"]` URL. Symlinks
+    /// are not followed.
+    pub fn index(root: &Path) -> Self {
+        let mut cache = Self::default();
+        let mut stack = vec![(root.to_path_buf(), 0)];
+        while let Some((dir, depth)) = stack.pop() {
+            if let Some(url) = origin_url(&dir) {
+                cache.checkouts.push((normalize_url(&url), dir.clone()));
+            }
+            if depth == CACHE_DEPTH {
+                continue;
+            }
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+            let mut children: Vec<PathBuf> = entries
+                .filter_map(Result::ok)
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()) && e.file_name() != ".git")
+                .map(|e| e.path())
+                .collect();
+            children.sort();
+            stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+        cache
+    }
+
+    /// The number of checkouts found.
+    pub fn len(&self) -> usize {
+        self.checkouts.len()
+    }
+
+    /// The first checkout cloned from `url`.
+    pub fn find(&self, url: &str) -> Option<&Path> {
+        let wanted = normalize_url(url);
+        self.checkouts
+            .iter()
+            .find(|(url, _)| *url == wanted)
+            .map(|(_, dir)| dir.as_path())
+    }
+}
+
+/// The `origin` URL of the checkout at `dir`, read from its git config.
+fn origin_url(dir: &Path) -> Option<String> {
+    let dot_git = dir.join(".git");
+    let git_dir = if dot_git.is_file() {
+        let pointer = fs::read_to_string(&dot_git).ok()?;
+        dir.join(pointer.strip_prefix("gitdir:")?.trim())
+    } else if dot_git.is_dir() {
+        dot_git
+    } else {
+        return None;
+    };
+    let config = fs::read_to_string(git_dir.join("config")).ok()?;
+    parse_config(&config)
+        .into_iter()
+        .find(|e| {
+            e.section == "remote" && e.subsection.as_deref() == Some("origin") && e.key == "url"
+        })
+        .map(|e| e.value)
+}
+
+/// Returns true if a submodule's `path` stays inside the repository.
+fn is_inside(path: &str) -> bool {
+    let path = Path::new(path);
+    path.components().next().is_some()
+        && path.components().all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Returns true if the submodule directory at `dir` has nothing checked out.
+fn is_unpopulated(dir: &Path) -> bool {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => entries.next().is_none(),
+        Err(_) => !dir.exists(),
+    }
+}
+
+/// The submodules of the clone at `root` that have nothing checked out,
+/// in `.gitmodules` order; none without a `.gitmodules`.
+///
+/// Entries whose path leaves the repository are dropped.
+pub fn unpopulated(root: &Path) -> Vec<Submodule> {
+    let Ok(text) = fs::read_to_string(root.join(".gitmodules")) else {
+        return Vec::new();
+    };
+    parse_gitmodules(&text)
+        .into_iter()
+        .filter(|s| is_inside(&s.path) && is_unpopulated(&root.join(&s.path)))
+        .collect()
+}
+
+/// Copies the tree at `from` into `to`, leaving out `.git` and symlinks.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        let target = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_tree(&entry.path(), &target)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// What [`fill_from_cache`] did with each submodule.
+#[derive(Debug, Default)]
+pub struct Filled {
+    /// The submodules filled, with the checkout each came from.
+    pub filled: Vec<(Submodule, PathBuf)>,
+    /// The submodules that could not be filled, with the reason.
+    pub unsatisfied: Vec<(Submodule, String)>,
+}
+
+/// Fills each submodule of `submodules` from its checkout in `cache`,
+/// copying it to the submodule's path under `root`.
+pub fn fill_from_cache(root: &Path, submodules: Vec<Submodule>, cache: &Cache) -> Filled {
+    let mut outcome = Filled::default();
+    for submodule in submodules {
+        let Some(checkout) = cache.find(&submodule.url) else {
+            outcome
+                .unsatisfied
+                .push((submodule, "no cached checkout".to_string()));
+            continue;
+        };
+        match copy_tree(checkout, &root.join(&submodule.path)) {
+            Ok(()) => outcome.filled.push((submodule, checkout.to_path_buf())),
+            Err(e) => {
+                let reason = format!("failed to copy {}: {}", checkout.display(), e);
+                outcome.unsatisfied.push((submodule, reason));
+            }
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!(
+            "solscrape_submodules_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for (relative, content) in files {
+            let path = root.join(relative);
+            if let Some(dir) = relative.strip_suffix('/') {
+                fs::create_dir_all(root.join(dir)).unwrap();
+                continue;
+            }
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, content).unwrap();
+        }
+        root
+    }
+
+    /// Verifies several submodules, quoted values, comments, and entries
+    /// missing a URL.
+    #[test]
+    fn test_parse_gitmodules() {
+        let text = "# generated by forge install\n\
+                    [submodule \"lib/forge-std\"]\n\
+                    \tpath = lib/forge-std\n\
+                    \turl = https://github.com/foundry-rs/forge-std\n\
+                    [submodule \"lib/my lib\"]\n\
+                    \tpath = \"lib/my lib\" ; spaces need quotes\n\
+                    \tURL = git@github.com:acme/my-lib.git\n\
+                    \tbranch = v1\n\
+                    [submodule \"lib/odd\\\"name\"]\n\
+                    \tpath = \"lib/odd#1\"\n\
+                    \turl = https://example.com/odd.git # trailing\n\
+                    [submodule \"lib/no-url\"]\n\
+                    \tpath = lib/no-url\n\
+                    [core]\n\
+                    \tpath = not-a-submodule\n";
+        assert_eq!(
+            parse_gitmodules(text),
+            [
+                Submodule {
+                    name: "lib/forge-std".to_string(),
+                    path: "lib/forge-std".to_string(),
+                    url: "https://github.com/foundry-rs/forge-std".to_string(),
+                },
+                Submodule {
+                    name: "lib/my lib".to_string(),
+                    path: "lib/my lib".to_string(),
+                    url: "git@github.com:acme/my-lib.git".to_string(),
+                },
+                Submodule {
+                    name: "lib/odd\"name".to_string(),
+                    path: "lib/odd#1".to_string(),
+                    url: "https://example.com/odd.git".to_string(),
+                },
+            ]
+        );
+        assert!(parse_gitmodules("").is_empty());
+    }
+
+    /// Verifies that the URL forms of one repository compare equal.
+    #[test]
+    fn test_normalize_url() {
+        let expected = "github.com/openzeppelin/openzeppelin-contracts";
+        for url in [
+            "https://github.com/OpenZeppelin/openzeppelin-contracts",
+            "https://github.com/OpenZeppelin/openzeppelin-contracts.git",
+            "https://github.com/OpenZeppelin/openzeppelin-contracts/",
+            "https://token@github.com/OpenZeppelin/openzeppelin-contracts",
+            "git@github.com:OpenZeppelin/openzeppelin-contracts.git",
+            "ssh://git@github.com/OpenZeppelin/openzeppelin-contracts",
+        ] {
+            assert_eq!(normalize_url(url), expected, "{url}");
+        }
+        assert_ne!(
+            normalize_url("https://github.com/acme/forge-std"),
+            normalize_url("https://github.com/foundry-rs/forge-std")
+        );
+    }
+
+    /// Verifies the explicit root, the home default, and an empty home.
+    #[test]
+    fn test_cache_root() {
+        assert_eq!(
+            cache_root(Some(Path::new("/cache")), Some("/home/a".into())),
+            Some(PathBuf::from("/cache"))
+        );
+        assert_eq!(
+            cache_root(None, Some("/home/a".into())),
+            Some(PathBuf::from("/home/a/.foundry"))
+        );
+        assert_eq!(cache_root(None, Some(OsString::new())), None);
+        assert_eq!(cache_root(None, None), None);
+    }
+
+    /// Verifies indexing checkouts (including a submodule pointer), finding
+    /// unpopulated submodules, and filling them without `.git`.
+    #[test]
+    fn test_fill_from_cache() {
+        let origin = |url: &str| format!("[remote \"origin\"]\n\turl = {}\n", url);
+        let cache_dir = tree(
+            "cache",
+            &[
+                (
+                    "project/.git/config",
+                    &origin("https://github.com/acme/project"),
+                ),
+                (
+                    "project/.git/modules/lib/forge-std/config",
+                    &origin("https://github.com/foundry-rs/forge-std.git"),
+                ),
+                (
+                    "project/lib/forge-std/.git",
+                    "gitdir: ../../.git/modules/lib/forge-std\n",
+                ),
+                ("project/lib/forge-std/src/Test.sol", "contract Test {}"),
+                ("loose/src/A.sol", "contract A {}"),
+            ],
+        );
+        let cache = Cache::index(&cache_dir);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(
+            cache.find("git@github.com:foundry-rs/forge-std"),
+            Some(cache_dir.join("project/lib/forge-std").as_path())
+        );
+
+        let clone = tree(
+            "clone",
+            &[
+                (
+                    ".gitmodules",
+                    "[submodule \"lib/forge-std\"]\n\tpath = lib/forge-std\n\
+                     \turl = https://github.com/foundry-rs/forge-std\n\
+                     [submodule \"lib/solmate\"]\n\tpath = lib/solmate\n\
+                     \turl = https://github.com/transmissions11/solmate\n\
+                     [submodule \"lib/vendored\"]\n\tpath = lib/vendored\n\
+                     \turl = https://github.com/acme/vendored\n\
+                     [submodule \"escape\"]\n\tpath = ../outside\n\
+                     \turl = https://github.com/foundry-rs/forge-std\n",
+                ),
+                ("lib/forge-std/", ""),
+                ("lib/vendored/src/V.sol", "contract V {}"),
+            ],
+        );
+        let empty = unpopulated(&clone);
+        let paths: Vec<&str> = empty.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, ["lib/forge-std", "lib/solmate"]);
+
+        let Filled {
+            filled,
+            unsatisfied: missing,
+        } = fill_from_cache(&clone, empty, &cache);
+        assert_eq!(filled.len(), 1);
+        assert_eq!(filled[0].0.path, "lib/forge-std");
+        assert!(clone.join("lib/forge-std/src/Test.sol").is_file());
+        assert!(!clone.join("lib/forge-std/.git").exists());
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].0.path, "lib/solmate");
+        assert_eq!(missing[0].1, "no cached checkout");
+        assert!(unpopulated(&clone).iter().all(|s| s.path == "lib/solmate"));
+
+        let _ = fs::remove_dir_all(&cache_dir);
+        let _ = fs::remove_dir_all(&clone);
+    }
+}
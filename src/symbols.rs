@@ -0,0 +1,221 @@
+//! Decorative characters, in Unicode or plain ASCII (`--ascii`).
+//!
+//! The banner, summary rules, bullets, and status markers, as well as the
+//! separators framing file headers and library banners in the output, all
+//! come from one [`Symbols`] table. [`Charset::detect`] picks the table for
+//! messages once at startup: ASCII when asked for, or when the locale on Unix
+//! does not name UTF-8 (a C locale in a minimal container, say), where
+//! Unicode would come out garbled. The output file only switches on an
+//! explicit `--ascii`, so the same source scrapes to the same bytes under
+//! any locale.
+
+/// The decorative characters for one [`Charset`].
+#[derive(Debug, PartialEq)]
+pub struct Symbols {
+    /// The line framing each file header in consolidated output.
+    pub header_separator: &'static str,
+    /// The line framing each library banner in the output.
+    pub library_rule: &'static str,
+    /// The line framing the summary printed after a run.
+    pub summary_rule: &'static str,
+    /// The corners of the startup banner: top left, top right, bottom left,
+    /// bottom right.
+    pub box_corners: [char; 4],
+    /// The top and bottom edge of the startup banner.
+    pub box_horizontal: char,
+    /// The left and right edge of the startup banner.
+    pub box_vertical: char,
+    /// Starts each item of a list in the summary.
+    pub bullet: &'static str,
+    /// Sets off the advice at the end of a summary line.
+    pub dash: &'static str,
+    /// Marks a successful run.
+    pub success: &'static str,
+    /// Marks a run whose output is partial.
+    pub partial: &'static str,
+    /// Marks the error a run failed with.
+    pub failure: &'static str,
+}
+
+/// Box-drawing characters and emoji.
+pub const UNICODE: Symbols = Symbols {
+    header_separator: "// ══════════════════════════════════════════════════════════════════════",
+    library_rule: "// ──────────────────────────────────────────────────────────────────────",
+    summary_rule: "════════════════════════════════════════════════════════════════",
+    box_corners: ['╔', '╗', '╚', '╝'],
+    box_horizontal: '═',
+    box_vertical: '║',
+    bullet: "•",
+    dash: "—",
+    success: "✅",
+    partial: "⚠️ ",
+    failure: "❌",
+};
+
+/// ASCII stand-ins of the same widths.
+pub const ASCII: Symbols = Symbols {
+    header_separator: "// ======================================================================",
+    library_rule: "// ----------------------------------------------------------------------",
+    summary_rule: "================================================================",
+    box_corners: ['+', '+', '+', '+'],
+    box_horizontal: '=',
+    box_vertical: '|',
+    bullet: "*",
+    dash: "-",
+    success: "[OK]",
+    partial: "[!]",
+    failure: "[X]",
+};
+
+/// Both tables, for reading output written with either.
+pub const ALL: [&Symbols; 2] = [&UNICODE, &ASCII];
+
+/// The locale variables deciding the character encoding, in the order
+/// POSIX gives them precedence.
+const LOCALE_VARS: [&str; 3] = ["LC_ALL", "LC_CTYPE", "LANG"];
+
+/// Which [`Symbols`] a run uses.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Charset {
+    #[default]
+    Unicode,
+    Ascii,
+}
+
+impl Charset {
+    /// Picks the charset for this run.
+    ///
+    /// ASCII is used when `ascii_requested` (`--ascii`) or, on Unix, when
+    /// the first of [`LOCALE_VARS`] that `var` finds set does not name UTF-8;
+    /// with none set the locale is C, which is ASCII too. Elsewhere the
+    /// locale is not consulted.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let charset = Charset::detect(false, |name| std::env::var(name).ok());
+    /// assert_eq!(Charset::detect(false, |_| Some("C".to_string())), Charset::Ascii);
+    /// ```
+    pub fn detect(ascii_requested: bool, var: impl Fn(&str) -> Option<String>) -> Self {
+        if ascii_requested || (cfg!(unix) && !locale_is_utf8(var)) {
+            Charset::Ascii
+        } else {
+            Charset::Unicode
+        }
+    }
+
+    /// The charset an output was written with: that of the first header
+    /// separator or library banner rule in `text`, or `None` when it has
+    /// neither (as with `--no-headers`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// let text = format!("{}\n// File: A.sol\n", ASCII.header_separator);
+    /// assert_eq!(Charset::of_output(&text), Some(Charset::Ascii));
+    /// ```
+    pub fn of_output(text: &str) -> Option<Self> {
+        text.lines().find_map(|line| {
+            [Charset::Unicode, Charset::Ascii]
+                .into_iter()
+                .find(|charset| {
+                    let symbols = charset.symbols();
+                    line == symbols.header_separator || line == symbols.library_rule
+                })
+        })
+    }
+
+    /// The table of characters for the charset.
+    pub fn symbols(self) -> &'static Symbols {
+        match self {
+            Charset::Unicode => &UNICODE,
+            Charset::Ascii => &ASCII,
+        }
+    }
+}
+
+/// Returns true if the locale named by [`LOCALE_VARS`] uses UTF-8, as in
+/// `en_US.UTF-8` or `C.utf8`.
+fn locale_is_utf8(var: impl Fn(&str) -> Option<String>) -> bool {
+    LOCALE_VARS
+        .iter()
+        .find_map(|name| var(name).filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_ascii_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    /// Verifies that both tables line up, so switching never changes widths
+    /// in the output.
+    #[test]
+    fn test_tables_match() {
+        for (unicode, ascii) in [
+            (UNICODE.header_separator, ASCII.header_separator),
+            (UNICODE.library_rule, ASCII.library_rule),
+            (UNICODE.summary_rule, ASCII.summary_rule),
+        ] {
+            assert_eq!(unicode.chars().count(), ascii.chars().count());
+            assert!(ascii.is_ascii());
+        }
+        assert!(ASCII.header_separator.starts_with("// "));
+        assert_ne!(ASCII.header_separator, ASCII.library_rule);
+        assert!(
+            [
+                ASCII.bullet,
+                ASCII.dash,
+                ASCII.success,
+                ASCII.partial,
+                ASCII.failure
+            ]
+            .iter()
+            .all(|s| s.is_ascii())
+        );
+    }
+
+    /// Verifies that an output's charset is read from its first rule.
+    #[test]
+    fn test_of_output() {
+        let ascii = format!("{}\n// File: A.sol\n", ASCII.header_separator);
+        assert_eq!(Charset::of_output(&ascii), Some(Charset::Ascii));
+        let unicode = format!(
+            "pragma x;\n{}\n{}\n",
+            UNICODE.library_rule, ASCII.library_rule
+        );
+        assert_eq!(Charset::of_output(&unicode), Some(Charset::Unicode));
+        assert_eq!(Charset::of_output("contract A {}\n// ==\n"), None);
+    }
+
+    /// Verifies the flag and locale precedence.
+    #[test]
+    fn test_detect() {
+        let utf8 = [("LANG", "en_US.UTF-8")];
+        assert_eq!(Charset::detect(true, env(&utf8)), Charset::Ascii);
+        assert_eq!(Charset::detect(false, env(&utf8)).symbols(), &UNICODE);
+        assert_eq!(Charset::Ascii.symbols(), &ASCII);
+        if cfg!(unix) {
+            assert_eq!(Charset::detect(false, env(&[])), Charset::Ascii);
+            assert_eq!(
+                Charset::detect(false, env(&[("LANG", "C.utf8")])),
+                Charset::Unicode
+            );
+            // LC_ALL wins over LANG, and an empty one is skipped
+            let c_locale = [("LC_ALL", "C"), ("LANG", "en_US.UTF-8")];
+            assert_eq!(Charset::detect(false, env(&c_locale)), Charset::Ascii);
+            let empty = [("LC_ALL", ""), ("LC_CTYPE", "de_DE.UTF-8")];
+            assert_eq!(Charset::detect(false, env(&empty)), Charset::Unicode);
+        }
+    }
+}
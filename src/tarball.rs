@@ -0,0 +1,237 @@
+//! Fetching a GitHub repository without git, for `--no-git` and for when
+//! git is not installed.
+//!
+//! GitHub serves any branch, tag, or commit of a repository as a gzipped
+//! tarball. [`archive_url`] names it, and [`fetch`] downloads it with the
+//! first of [`DOWNLOADERS`] that is installed and unpacks it with `tar`,
+//! dropping the archive's top-level `{repo}-{ref}/` directory. The result is
+//! a plain directory: no history, no submodules, and no revision to report.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::exec::Exec;
+use crate::forge_host::{Forge, RepoUrl};
+use crate::logger::Logger;
+
+/// The programs tried, in order, to download an archive.
+pub const DOWNLOADERS: [&str; 2] = ["curl", "wget"];
+
+/// The program that unpacks an archive.
+pub const EXTRACTOR: &str = "tar";
+
+/// The name the archive is downloaded under, inside the target directory,
+/// until it is unpacked.
+const ARCHIVE_FILE: &str = ".solscrape-archive.tar.gz";
+
+/// The tarball of `git_ref` of a GitHub repository, or of its default
+/// branch without one; `None` for any other forge.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let repo = forge_host::parse("https://github.com/foundry-rs/forge-std.git");
+/// assert_eq!(
+///     archive_url(&repo, Some("v1.9.1")).as_deref(),
+///     Some("https://github.com/foundry-rs/forge-std/archive/v1.9.1.tar.gz")
+/// );
+/// ```
+pub fn archive_url(repo: &RepoUrl, git_ref: Option<&str>) -> Option<String> {
+    match (repo.forge, repo.segments.as_slice()) {
+        (Forge::GitHub, [owner, name]) => Some(format!(
+            "https://github.com/{}/{}/archive/{}.tar.gz",
+            owner,
+            name,
+            git_ref.unwrap_or("HEAD")
+        )),
+        _ => None,
+    }
+}
+
+/// The arguments that make `downloader` save `url` to `path`.
+fn download_args<'a>(downloader: &str, url: &'a str, path: &'a Path) -> Vec<&'a std::ffi::OsStr> {
+    let flags: &[&str] = match downloader {
+        "curl" => &["-fsSL", "-o"],
+        _ => &["-q", "-O"],
+    };
+    flags
+        .iter()
+        .map(|f| f.as_ref())
+        .chain([path.as_os_str(), url.as_ref()])
+        .collect()
+}
+
+/// The last non-empty line of a program's stderr, or its exit status.
+fn failure(output: &std::process::Output) -> String {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .map_or_else(|| format!("exited with {}", output.status), str::to_string)
+}
+
+/// Downloads the archive at `url` and unpacks it into the existing
+/// directory `target`, each program stopped once `timeout` has elapsed.
+///
+/// A downloader that is not installed, or that `exec` does not allow, is
+/// skipped for the next one.
+///
+/// # Returns
+///
+/// The downloader used.
+///
+/// # Errors
+///
+/// | Error | Condition |
+/// |-------|-----------|
+/// | `"Neither curl nor wget is available to download {url}"` | No downloader could run |
+/// | `"Downloading {url} with {program} failed: ..."` | The download failed or timed out |
+/// | `"tar is not installed..."` | `tar` could not be found |
+/// | `"Unpacking {url} failed: ..."` | `tar` failed |
+pub fn fetch(
+    url: &str,
+    target: &Path,
+    exec: &Exec,
+    timeout: Option<Duration>,
+    log: &Logger,
+) -> Result<&'static str, String> {
+    let archive = target.join(ARCHIVE_FILE);
+    let mut used = None;
+    for downloader in DOWNLOADERS {
+        if !exec.allows(downloader) {
+            log.detail(&format!(
+                "Not downloading with {}: it is not allowed",
+                downloader
+            ));
+            continue;
+        }
+        let output = match exec.output_within(
+            downloader,
+            download_args(downloader, url, &archive),
+            None,
+            timeout,
+            log,
+        ) {
+            Ok(output) => output,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                log.detail(&format!("{} is not installed", downloader));
+                continue;
+            }
+            Err(e) => {
+                return Err(format!(
+                    "Downloading {} with {} failed: {}",
+                    url, downloader, e
+                ));
+            }
+        };
+        if !output.status.success() {
+            let _ = fs::remove_file(&archive);
+            return Err(format!(
+                "Downloading {} with {} failed: {}",
+                url,
+                downloader,
+                failure(&output)
+            ));
+        }
+        used = Some(downloader);
+        break;
+    }
+    let Some(downloader) = used else {
+        return Err(format!(
+            "Neither {} is available to download {}",
+            DOWNLOADERS.join(" nor "),
+            url
+        ));
+    };
+
+    let unpack = [
+        "-xzf".as_ref(),
+        archive.as_os_str(),
+        "-C".as_ref(),
+        target.as_os_str(),
+        "--strip-components=1".as_ref(),
+    ];
+    let unpacked = exec.output_within(EXTRACTOR, unpack, None, timeout, log);
+    let _ = fs::remove_file(&archive);
+    match unpacked {
+        Ok(output) if output.status.success() => Ok(downloader),
+        Ok(output) => Err(format!("Unpacking {} failed: {}", url, failure(&output))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(format!(
+            "{} is not installed or not in PATH, and is needed to unpack {}",
+            EXTRACTOR, url
+        )),
+        Err(e) => Err(format!("Unpacking {} failed: {}", url, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::forge_host;
+    use std::process::Command;
+
+    /// Verifies the archive of the default branch and of a ref, and that
+    /// only GitHub repositories have one.
+    #[test]
+    fn test_archive_url() {
+        let repo = forge_host::parse("https://github.com/foundry-rs/forge-std.git");
+        assert_eq!(
+            archive_url(&repo, None).as_deref(),
+            Some("https://github.com/foundry-rs/forge-std/archive/HEAD.tar.gz")
+        );
+        let web = forge_host::parse("https://github.com/o/r/tree/v2/src");
+        assert_eq!(
+            archive_url(&web, web.git_ref.as_deref()).as_deref(),
+            Some("https://github.com/o/r/archive/v2.tar.gz")
+        );
+        for url in [
+            "https://gitlab.com/o/r",
+            "https://github.com/o",
+            "/tmp/repo",
+        ] {
+            assert_eq!(archive_url(&forge_host::parse(url), None), None, "{url}");
+        }
+    }
+
+    /// Verifies a download through a `file://` URL: the top-level directory
+    /// is dropped and the archive removed, and a failed download names the
+    /// downloader.
+    #[test]
+    fn test_fetch() {
+        let root = std::env::temp_dir().join(format!("solscrape_tarball_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("repo-main/src")).unwrap();
+        fs::write(root.join("repo-main/src/A.sol"), "contract A {}\n").unwrap();
+        let status = Command::new("tar")
+            .args(["-czf", "repo.tar.gz", "repo-main"])
+            .current_dir(&root)
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let target = root.join("target");
+        fs::create_dir(&target).unwrap();
+        let url = format!("file://{}", root.join("repo.tar.gz").display());
+        let (exec, log) = (Exec::unrestricted(), Logger::new());
+        assert_eq!(fetch(&url, &target, &exec, None, &log), Ok("curl"));
+        assert_eq!(
+            fs::read_to_string(target.join("src/A.sol")).unwrap(),
+            "contract A {}\n"
+        );
+        assert!(!target.join(ARCHIVE_FILE).exists());
+
+        let missing = format!("file://{}", root.join("missing.tar.gz").display());
+        let err = fetch(&missing, &target, &exec, None, &log).unwrap_err();
+        assert!(err.starts_with("Downloading file://"), "{err}");
+        assert!(err.contains("with curl failed"), "{err}");
+
+        let err = fetch(&url, &target, &Exec::restricted(&["tar"]), None, &log).unwrap_err();
+        assert_eq!(
+            err,
+            format!("Neither curl nor wget is available to download {}", url)
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+}
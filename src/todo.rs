@@ -0,0 +1,189 @@
+//! Work-in-progress markers left in the cleaned output (`--flag-todos`).
+//!
+//! Cleaning removes comments, but `TODO` and its relatives survive inside
+//! string literals (revert reasons, most often), in license headers kept
+//! with `--keep-license-headers`, and everywhere with `--no-clean`. [`scan`]
+//! finds them in a file's final text in a single pass and points each one
+//! back at the line of the original file it most likely came from.
+
+/// The marker words looked for, matched as whole words in any letter case.
+pub const MARKERS: [&str; 4] = ["TODO", "FIXME", "XXX", "HACK"];
+
+/// The longest excerpt of a marker's line kept for the report, in chars.
+const EXCERPT_CHARS: usize = 80;
+
+/// One marker found in a file's output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    /// The marker, as listed in [`MARKERS`].
+    pub marker: &'static str,
+    /// The 1-based line in the file's output text.
+    pub line: usize,
+    /// The 1-based line of the original file holding the same text, when
+    /// one was found.
+    pub original_line: Option<usize>,
+    /// The trimmed output line, cut to [`EXCERPT_CHARS`].
+    pub excerpt: String,
+}
+
+impl Marker {
+    /// Describes the marker for a report line, without the file's path.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,ignore
+    /// assert_eq!(marker.describe(), "FIXME near line 12: require(ok, \"FIXME: cap\");");
+    /// ```
+    pub fn describe(&self) -> String {
+        match self.original_line {
+            Some(line) => format!("{} near line {}: {}", self.marker, line, self.excerpt),
+            None => format!("{} (original line unknown): {}", self.marker, self.excerpt),
+        }
+    }
+}
+
+/// Finds the [`MARKERS`] in `text`, the output of a file read as `original`.
+///
+/// Words are runs of ASCII letters, digits, and `_`, so `TODO:` and
+/// `"todo"` match while `TODO_LIST` and `Todos` do not. Each line of `text`
+/// is looked up in `original` as a substring, searching forward from the
+/// previous match since processing keeps lines in order.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let markers = scan("revert(\"TODO\");", "// note\nrevert(\"TODO\");\n");
+/// assert_eq!((markers[0].line, markers[0].original_line), (1, Some(2)));
+/// ```
+pub fn scan(text: &str, original: &str) -> Vec<Marker> {
+    let original_lines: Vec<&str> = original.lines().collect();
+    let mut search_from = 0;
+    let mut found = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let words = line.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'));
+        let mut located = None;
+        for word in words {
+            let Some(&marker) = MARKERS.iter().find(|m| word.eq_ignore_ascii_case(m)) else {
+                continue;
+            };
+            let original_line = *located.get_or_insert_with(|| {
+                let trimmed = line.trim();
+                let offset = original_lines[search_from.min(original_lines.len())..]
+                    .iter()
+                    .position(|candidate| candidate.contains(trimmed))?;
+                search_from += offset;
+                Some(search_from + 1)
+            });
+            found.push(Marker {
+                marker,
+                line: index + 1,
+                original_line,
+                excerpt: excerpt(line.trim()),
+            });
+        }
+    }
+    found
+}
+
+/// Counts markers by kind, in [`MARKERS`] order.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(summarize(markers.iter()), "3 (2 TODO, 1 FIXME)");
+/// ```
+pub fn summarize<'a>(markers: impl Iterator<Item = &'a Marker>) -> String {
+    let mut counts = [0usize; MARKERS.len()];
+    for marker in markers {
+        if let Some(index) = MARKERS.iter().position(|m| *m == marker.marker) {
+            counts[index] += 1;
+        }
+    }
+    let total: usize = counts.iter().sum();
+    if total == 0 {
+        return "none".to_string();
+    }
+    let kinds: Vec<String> = MARKERS
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| *count > 0)
+        .map(|(marker, count)| format!("{} {}", count, marker))
+        .collect();
+    format!("{} ({})", total, kinds.join(", "))
+}
+
+/// Cuts `line` to [`EXCERPT_CHARS`], marking the cut with `…`.
+fn excerpt(line: &str) -> String {
+    match line.char_indices().nth(EXCERPT_CHARS) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies whole-word, case-insensitive matching, including inside a
+    /// revert string.
+    #[test]
+    fn test_scan() {
+        let original = "\
+// SPDX-License-Identifier: MIT
+contract Vault {
+    // TODO: this comment is cleaned away
+    function f() external {
+        revert(\"todo: handle fees\"); // XXX
+        uint TODO_LIST = 1;
+    }
+    string constant NOTE = \"FIXME HACK\";
+}
+";
+        let cleaned = "\
+contract Vault {
+    function f() external {
+        revert(\"todo: handle fees\");
+        uint TODO_LIST = 1;
+    }
+    string constant NOTE = \"FIXME HACK\";
+}";
+        let markers = scan(cleaned, original);
+        let found: Vec<(&str, usize, Option<usize>)> = markers
+            .iter()
+            .map(|m| (m.marker, m.line, m.original_line))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("TODO", 3, Some(5)),
+                ("FIXME", 6, Some(8)),
+                ("HACK", 6, Some(8))
+            ]
+        );
+        assert_eq!(
+            markers[0].describe(),
+            "TODO near line 5: revert(\"todo: handle fees\");"
+        );
+        assert_eq!(summarize(markers.iter()), "3 (1 TODO, 1 FIXME, 1 HACK)");
+        assert_eq!(summarize([].iter()), "none");
+
+        // A line no longer in the original (re-indented, say) has no line
+        let moved = scan("\tx = 1; // todo", "x  =  1;");
+        assert_eq!(moved[0].original_line, None);
+        assert_eq!(
+            moved[0].describe(),
+            "TODO (original line unknown): x = 1; // todo"
+        );
+        assert!(scan("Todos TODOs _TODO", "").is_empty());
+    }
+
+    /// Verifies that long lines are cut for the report.
+    #[test]
+    fn test_excerpt() {
+        let long = format!("revert(\"TODO {}\");", "é".repeat(100));
+        let markers = scan(&long, &long);
+        assert_eq!(markers[0].excerpt.chars().count(), EXCERPT_CHARS + 1);
+        assert!(markers[0].excerpt.ends_with('…'));
+        assert_eq!(excerpt("short"), "short");
+    }
+}
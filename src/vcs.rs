@@ -0,0 +1,271 @@
+//! Output meant to be checked into a review repository (`--vcs-friendly`).
+//!
+//! Regenerating a consolidated file should only produce diffs for code that
+//! actually changed. Files are already emitted in sorted path order and the
+//! output carries no timestamps; [`normalize_whitespace`] additionally
+//! removes whitespace churn, and [`compare`] summarizes what changed versus
+//! the file previously at the same path, section by section, so the user can
+//! see the size of the diff before committing it.
+
+use std::collections::HashMap;
+
+use crate::split;
+
+/// The number of spaces a leading tab is expanded to, per the Solidity style
+/// guide.
+pub const TAB_WIDTH: usize = 4;
+
+/// The path reported for output without file headers, compared as a whole.
+pub const WHOLE_FILE: &str = "(whole file)";
+
+/// Normalizes whitespace so that editor settings do not show up as changes.
+///
+/// Line endings become `\n`, leading tabs become [`TAB_WIDTH`] spaces, and
+/// trailing whitespace is removed. Other whitespace, including tabs after
+/// the indentation, is left alone.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// assert_eq!(normalize_whitespace("\tuint x;  \r\n"), "    uint x;\n");
+/// ```
+pub fn normalize_whitespace(code: &str) -> String {
+    let mut normalized = String::with_capacity(code.len());
+    let mut lines = code.split('\n').peekable();
+    while let Some(line) = lines.next() {
+        let line = line.trim_end();
+        let body = line.trim_start_matches('\t');
+        let tabs = line.len() - body.len();
+        normalized.extend(std::iter::repeat_n(' ', tabs * TAB_WIDTH));
+        normalized.push_str(body);
+        if lines.peek().is_some() {
+            normalized.push('\n');
+        }
+    }
+    normalized
+}
+
+/// How one file's section differs from the previous output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    /// The path in the section header, or [`WHOLE_FILE`].
+    pub path: String,
+    /// Lines present now but not before.
+    pub added: usize,
+    /// Lines present before but not now.
+    pub removed: usize,
+    /// Whether the section is new, gone, or changed.
+    pub kind: ChangeKind,
+}
+
+/// Whether a section was added, removed, or modified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified,
+}
+
+/// Compares two consolidated outputs section by section.
+///
+/// Sections are matched by the path in their header; output without headers
+/// is compared as a single [`WHOLE_FILE`] section. Lines are counted as a
+/// multiset, so a line that only moved within its section is not counted.
+///
+/// # Returns
+///
+/// The changed sections, sorted by path; unchanged sections are left out.
+pub fn compare(old: &str, new: &str) -> Vec<Change> {
+    let old_sections = sections(old);
+    let new_sections = sections(new);
+
+    let mut paths: Vec<&String> = old_sections.keys().chain(new_sections.keys()).collect();
+    paths.sort();
+    paths.dedup();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let before = old_sections.get(path).map(String::as_str);
+            let after = new_sections.get(path).map(String::as_str);
+            let (added, removed) = line_delta(before.unwrap_or(""), after.unwrap_or(""));
+            let kind = match (before, after) {
+                (None, _) => ChangeKind::Added,
+                (_, None) => ChangeKind::Removed,
+                _ if added == 0 && removed == 0 => return None,
+                _ => ChangeKind::Modified,
+            };
+            Some(Change {
+                path: path.clone(),
+                added,
+                removed,
+                kind,
+            })
+        })
+        .collect()
+}
+
+/// Renders the report for `changes`, one line per changed section after a
+/// summary line.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let report = render_report(&compare(old, new));
+/// // ["Changes vs previous output: 2 files, +4 -1",
+/// //  "  src/Vault.sol  +3 -1",
+/// //  "  src/New.sol    added, +1"]
+/// ```
+pub fn render_report(changes: &[Change]) -> Vec<String> {
+    let mut report = vec![format!(
+        "Changes vs previous output: {}",
+        summarize(changes)
+    )];
+    report.extend(render_sections(changes));
+    report
+}
+
+/// Totals `changes` as `"2 files, +4 -1"`, or `"none"`.
+pub fn summarize(changes: &[Change]) -> String {
+    if changes.is_empty() {
+        return "none".to_string();
+    }
+    let added: usize = changes.iter().map(|c| c.added).sum();
+    let removed: usize = changes.iter().map(|c| c.removed).sum();
+    format!(
+        "{} file{}, +{} -{}",
+        changes.len(),
+        if changes.len() == 1 { "" } else { "s" },
+        added,
+        removed
+    )
+}
+
+/// Renders one indented line per changed section, paths aligned.
+pub fn render_sections(changes: &[Change]) -> Vec<String> {
+    let width = changes.iter().map(|c| c.path.len()).max().unwrap_or(0);
+    let mut report = Vec::with_capacity(changes.len());
+    for change in changes {
+        let delta = match change.kind {
+            ChangeKind::Added => format!("added, +{}", change.added),
+            ChangeKind::Removed => format!("removed, -{}", change.removed),
+            ChangeKind::Modified => format!("+{} -{}", change.added, change.removed),
+        };
+        report.push(format!(
+            "  {:width$}  {}",
+            change.path,
+            delta,
+            width = width
+        ));
+    }
+    report
+}
+
+/// Maps section paths to their content, or the whole text to [`WHOLE_FILE`].
+fn sections(text: &str) -> HashMap<String, String> {
+    match split::parse_sections(text) {
+        Ok(sections) => sections
+            .into_iter()
+            .map(|section| (section.path, section.content))
+            .collect(),
+        Err(_) => HashMap::from([(WHOLE_FILE.to_string(), text.to_string())]),
+    }
+}
+
+/// Counts the lines only in `after` and only in `before`, as multisets.
+fn line_delta(before: &str, after: &str) -> (usize, usize) {
+    let mut balance: HashMap<&str, isize> = HashMap::new();
+    for line in after.lines() {
+        *balance.entry(line).or_default() += 1;
+    }
+    for line in before.lines() {
+        *balance.entry(line).or_default() -= 1;
+    }
+    let added = balance.values().filter(|&&n| n > 0).sum::<isize>();
+    let removed = -balance.values().filter(|&&n| n < 0).sum::<isize>();
+    (added as usize, removed as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::UNICODE;
+
+    fn output(files: &[(&str, &str)]) -> String {
+        files
+            .iter()
+            .map(|(path, code)| {
+                format!(
+                    "{}\n// File: {}\n{}\n{}\n",
+                    UNICODE.header_separator, path, UNICODE.header_separator, code
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Verifies line endings, indentation, and trailing whitespace.
+    #[test]
+    fn test_normalize_whitespace() {
+        assert_eq!(
+            normalize_whitespace("contract A {\r\n\tuint x; \t\r\n\t\tf(a,\tb);\n}"),
+            "contract A {\n    uint x;\n        f(a,\tb);\n}"
+        );
+        assert_eq!(normalize_whitespace("a\n\n"), "a\n\n");
+        assert_eq!(normalize_whitespace(""), "");
+    }
+
+    /// Verifies per-section counts for added, removed, and changed files.
+    #[test]
+    fn test_compare() {
+        let old = output(&[
+            ("src/A.sol", "contract A {\n    uint x;\n}"),
+            ("src/Gone.sol", "contract Gone {}"),
+            ("src/Same.sol", "contract Same {}"),
+        ]);
+        let new = output(&[
+            ("src/A.sol", "contract A {\n    uint y;\n    uint z;\n}"),
+            ("src/New.sol", "contract New {}"),
+            ("src/Same.sol", "contract Same {}"),
+        ]);
+        let changes = compare(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change {
+                    path: "src/A.sol".to_string(),
+                    added: 2,
+                    removed: 1,
+                    kind: ChangeKind::Modified,
+                },
+                Change {
+                    path: "src/Gone.sol".to_string(),
+                    added: 0,
+                    removed: 1,
+                    kind: ChangeKind::Removed,
+                },
+                Change {
+                    path: "src/New.sol".to_string(),
+                    added: 1,
+                    removed: 0,
+                    kind: ChangeKind::Added,
+                },
+            ]
+        );
+        assert_eq!(
+            render_report(&changes),
+            vec![
+                "Changes vs previous output: 3 files, +3 -2",
+                "  src/A.sol     +2 -1",
+                "  src/Gone.sol  removed, -1",
+                "  src/New.sol   added, +1",
+            ]
+        );
+
+        assert!(compare(&old, &old).is_empty());
+        assert_eq!(render_report(&[]), vec!["Changes vs previous output: none"]);
+        // Moving a line within a section is not a change
+        assert!(compare("a\nb\n", "b\na\n").is_empty());
+        assert_eq!(compare("a\nb\n", "a\nc\n")[0].path, WHOLE_FILE);
+    }
+}
@@ -0,0 +1,258 @@
+//! Re-wrapping lines wider than `--max-line-width` at safe break points.
+//!
+//! Generated contracts often put a whole function signature on one line,
+//! hundreds of characters wide. A line over the width is broken after a
+//! `,` inside parentheses or brackets, before `returns`, and after a `{`,
+//! filling each line as far as the width allows; continuation lines are
+//! indented one level deeper than the line they came from. The scanner
+//! skips string literals (`hex"..."` and `unicode"..."` included) and
+//! comments, and tracks the parenthesis depth itself, so nothing inside a
+//! string or a comment is ever broken.
+//!
+//! A line with nowhere to break, such as one long string literal, is left
+//! as it is and counted in [`Wrapped::too_wide`], as is a wrapped line with
+//! a piece still over the width.
+
+/// The indentation added to continuation lines.
+pub const INDENT: &str = "    ";
+
+/// The result of [`wrap`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Wrapped {
+    /// The re-wrapped code.
+    pub text: String,
+    /// The lines that were broken up.
+    pub wrapped: usize,
+    /// The lines still wider than the width afterwards.
+    pub too_wide: usize,
+}
+
+/// Breaks every line of `code` wider than `width` chars; see the module
+/// documentation for where.
+///
+/// # Examples
+///
+/// ```rust,ignore
+/// let code = "function f(uint256 a, uint256 b) external returns (uint256) {}";
+/// let wrapped = wrap(code, 40);
+/// assert_eq!(
+///     wrapped.text,
+///     "function f(uint256 a,\n    uint256 b) external\n    returns (uint256) {}"
+/// );
+/// ```
+pub fn wrap(code: &str, width: usize) -> Wrapped {
+    let mut lines = Vec::new();
+    let (mut wrapped, mut too_wide) = (0, 0);
+    for line in code.lines() {
+        if line.chars().count() <= width {
+            lines.push(line.to_string());
+            continue;
+        }
+        let pieces = wrap_line(line, width);
+        if pieces.len() > 1 {
+            wrapped += 1;
+        }
+        if pieces.iter().any(|piece| piece.chars().count() > width) {
+            too_wide += 1;
+        }
+        lines.extend(pieces);
+    }
+    let mut text = lines.join("\n");
+    if code.ends_with('\n') {
+        text.push('\n');
+    }
+    Wrapped {
+        text,
+        wrapped,
+        too_wide,
+    }
+}
+
+/// The pieces `line` is broken into, or `line` alone when it has no break
+/// point.
+///
+/// Each piece takes the furthest break point that keeps it within `width`,
+/// or, when none does, the nearest one.
+fn wrap_line(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    let breaks = break_points(&chars);
+    if breaks.is_empty() {
+        return vec![line.to_string()];
+    }
+    let indent: String = chars.iter().take_while(|c| c.is_whitespace()).collect();
+    let continuation = format!("{}{}", indent, INDENT);
+
+    let mut pieces = Vec::new();
+    let mut start = indent.chars().count();
+    let mut prefix = indent.as_str();
+    loop {
+        let room = width.saturating_sub(prefix.chars().count());
+        let piece_len = |end: usize| {
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim_end()
+                .chars()
+                .count()
+        };
+        let rest = piece_len(chars.len());
+        let candidates: Vec<usize> = breaks
+            .iter()
+            .copied()
+            .filter(|&b| b > start && piece_len(b) > 0)
+            .collect();
+        let end = if rest <= room {
+            None
+        } else {
+            candidates
+                .iter()
+                .copied()
+                .rfind(|&b| piece_len(b) <= room)
+                .or_else(|| candidates.first().copied())
+        };
+        let Some(end) = end else {
+            let piece: String = chars[start..].iter().collect();
+            pieces.push(format!("{}{}", prefix, piece.trim_end()));
+            break;
+        };
+        let piece: String = chars[start..end].iter().collect();
+        pieces.push(format!("{}{}", prefix, piece.trim_end()));
+        start = end;
+        while start < chars.len() && chars[start].is_whitespace() {
+            start += 1;
+        }
+        prefix = &continuation;
+    }
+    pieces
+}
+
+/// The char indices `chars` may be broken at: a new line would start there.
+///
+/// They are just after a `,` at parenthesis or bracket depth 1 or more,
+/// just before the keyword `returns`, and just after a `{` with code other
+/// than `}` after it. Nothing inside a string literal, and nothing from a
+/// `//` comment on, counts.
+fn break_points(chars: &[char]) -> Vec<usize> {
+    let mut breaks = Vec::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '"' | '\'' => {
+                let quote = chars[i];
+                i += 1;
+                while i < chars.len() && chars[i] != quote {
+                    if chars[i] == '\\' {
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => break,
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            ',' if depth > 0 => breaks.push(i + 1),
+            '{' => {
+                let next = chars[i + 1..].iter().find(|c| !c.is_whitespace());
+                if next.is_some_and(|&c| c != '}') {
+                    breaks.push(i + 1);
+                }
+            }
+            c if is_ident_char(c) => {
+                let start = i;
+                while i < chars.len() && is_ident_char(chars[i]) {
+                    i += 1;
+                }
+                if chars[start..i].iter().copied().eq("returns".chars()) {
+                    breaks.push(start);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    breaks
+}
+
+/// Returns true for chars that can be part of an identifier.
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Verifies a long signature broken after commas and before `returns`,
+    /// with continuations one level in, and that short lines are untouched.
+    #[test]
+    fn test_wrap_signature() {
+        let code = "contract A {\n    function swap(address tokenIn, address tokenOut, \
+                    uint256 amountIn, uint256 minOut) external returns (uint256 amountOut) {\n    \
+                    }\n}\n";
+        let wrapped = wrap(code, 60);
+        assert_eq!(
+            wrapped.text,
+            "contract A {\n\
+             \x20   function swap(address tokenIn, address tokenOut,\n\
+             \x20       uint256 amountIn, uint256 minOut) external\n\
+             \x20       returns (uint256 amountOut) {\n\
+             \x20   }\n\
+             }\n"
+        );
+        assert_eq!((wrapped.wrapped, wrapped.too_wide), (1, 0));
+        assert!(wrapped.text.lines().all(|line| line.chars().count() <= 60));
+        assert_eq!(wrap(code, 200).text, code);
+    }
+
+    /// Verifies that a long `require` message is moved to its own line but
+    /// never split, and is counted when it is still too wide.
+    #[test]
+    fn test_wrap_require_message() {
+        let message = "x".repeat(50);
+        let code = format!("        require(amount > 0, \"{}, really\");", message);
+        let wrapped = wrap(&code, 40);
+        assert_eq!(
+            wrapped.text,
+            format!(
+                "        require(amount > 0,\n            \"{}, really\");",
+                message
+            )
+        );
+        assert_eq!((wrapped.wrapped, wrapped.too_wide), (1, 1));
+    }
+
+    /// Verifies that a long hex literal, with nowhere to break, is left
+    /// alone and counted, as is a comma in a comment.
+    #[test]
+    fn test_wrap_leaves_literals() {
+        let code = format!(
+            "    bytes constant CODE = hex\"{}\"; // a, b, c",
+            "60".repeat(40)
+        );
+        let wrapped = wrap(&code, 40);
+        assert_eq!(wrapped.text, code);
+        assert_eq!((wrapped.wrapped, wrapped.too_wide), (0, 1));
+    }
+
+    /// Verifies a break after `{`, not before a `}` that closes it, and that
+    /// commas at depth 0 and in nested calls are told apart.
+    #[test]
+    fn test_break_points() {
+        let points = |line: &str| break_points(&line.chars().collect::<Vec<_>>());
+        assert_eq!(points("f() { x = 1; }"), [5]);
+        assert_eq!(points("f() { }"), Vec::<usize>::new());
+        assert_eq!(points("uint a, b;"), Vec::<usize>::new());
+        assert_eq!(points("f(a, g(b, c))"), [4, 9]);
+        assert_eq!(points("f(\"a, b\", [c, d])"), [9, 13]);
+        assert_eq!(points("f() returns (uint) myreturns"), [4]);
+    }
+}
@@ -0,0 +1,111 @@
+//! End-to-end tests for the ASCII fallback (`--ascii`, or a locale without
+//! UTF-8, which changes only the messages), run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[("source/src/Vault.sol", "// Vault\ncontract Vault {}\n")],
+    );
+    root
+}
+
+/// Scrapes the fixture into `out/` with `LC_ALL` set to `locale`, returning
+/// the run and the output file's content.
+fn scrape(root: &Path, locale: &str, extra: &[&str]) -> (Output, String) {
+    let out = root.join("out");
+    let output = common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            out.to_str().unwrap(),
+            "--local",
+            "-o",
+            "vault",
+        ])
+        .args(extra)
+        .env("LC_ALL", locale)
+        .output()
+        .unwrap();
+    let content = fs::read_to_string(out.join("vault_scraped.sol")).unwrap_or_default();
+    (output, content)
+}
+
+/// A UTF-8 locale keeps the Unicode banner, summary, and headers.
+#[test]
+fn test_utf8_locale_uses_unicode() {
+    let root = fixture("utf8");
+    let (output, content) = scrape(&root, "en_US.UTF-8", &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        content,
+        format!(
+            "// {rule}\n// File: src/Vault.sol\n// {rule}\ncontract Vault {{}}",
+            rule = "═".repeat(70)
+        )
+    );
+    assert!(stdout.contains("╔═"), "{}", stdout);
+    assert!(stdout.contains("✅ Success!"), "{}", stdout);
+    assert!(stdout.contains("  • src/Vault.sol"), "{}", stdout);
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A C locale switches the messages to ASCII and `--ascii` under any
+/// locale the output too, and `split` reads the result.
+#[test]
+fn test_ascii_fallback() {
+    let output_of = |rule: &str| {
+        format!(
+            "// {rule}\n// File: src/Vault.sol\n// {rule}\ncontract Vault {{}}",
+            rule = rule.repeat(70)
+        )
+    };
+    for (locale, extra, expected) in [
+        ("C", &[][..], output_of("═")),
+        ("C", &["--ascii"][..], output_of("=")),
+        ("en_US.UTF-8", &["--ascii"][..], output_of("=")),
+    ] {
+        let root = fixture("fallback");
+        let (output, content) = scrape(&root, locale, extra);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(output.status.success(), "{:?}", output);
+        assert!(stdout.is_ascii(), "{}", stdout);
+        assert!(stdout.contains("+===="), "{}", stdout);
+        assert!(stdout.contains("[OK] Success!"), "{}", stdout);
+        assert!(stdout.contains("  * src/Vault.sol"), "{}", stdout);
+        assert_eq!(content, expected);
+
+        let split = common::solscrape()
+            .args(["split", "--ascii"])
+            .arg(root.join("out/vault_scraped.sol"))
+            .arg(root.join("split"))
+            .env("LC_ALL", locale)
+            .output()
+            .unwrap();
+        assert!(split.status.success(), "{:?}", split);
+        assert_eq!(
+            fs::read_to_string(root.join("split/src/Vault.sol")).unwrap(),
+            "contract Vault {}\n"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+}
+
+/// Errors are marked in ASCII too, even when parsing the arguments fails.
+#[test]
+fn test_ascii_error_marker() {
+    let output = common::solscrape()
+        .args(["--ascii", "--no-such-flag"])
+        .env("LC_ALL", "en_US.UTF-8")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.starts_with("[X] Error: "), "{}", stderr);
+}
@@ -0,0 +1,75 @@
+//! End-to-end tests for `--branch`, run against the built binary on a bare
+//! repository with a second branch.
+
+mod common;
+
+use common::git;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Creates a bare repository whose `main` has `Main.sol` and whose
+/// `release-v1` has `Release.sol` instead.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let work = root.join("work");
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/Main.sol"), "contract Main {}\n").unwrap();
+    git(&work, &["init", "-q", "-b", "main"]);
+    git(&work, &["add", "."]);
+    git(&work, &["commit", "-q", "-m", "main"]);
+    git(&work, &["checkout", "-q", "-b", "release-v1"]);
+    git(&work, &["rm", "-q", "src/Main.sol"]);
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/Release.sol"), "contract Release {}\n").unwrap();
+    git(&work, &["add", "."]);
+    git(&work, &["commit", "-q", "-m", "release"]);
+    let bare = root.join("repo.git");
+    git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+    git(
+        &work,
+        &["push", "-q", bare.to_str().unwrap(), "main", "release-v1"],
+    );
+    git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    root
+}
+
+/// Scrapes the fixture's bare repository into `out/` with `extra`.
+fn scrape(root: &Path, extra: &[&str]) -> Output {
+    let url = format!("file://{}", root.join("repo.git").display());
+    common::solscrape()
+        .args([
+            url.as_str(),
+            root.join("out").to_str().unwrap(),
+            "--min-temp-space",
+            "0",
+        ])
+        .args(extra)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .unwrap()
+}
+
+/// The named branch is cloned instead of the default one.
+#[test]
+fn test_branch_is_cloned() {
+    let root = fixture("clone");
+    let output = scrape(&root, &["-b", "release-v1"]);
+    assert!(output.status.success(), "{:?}", output);
+    let scraped = fs::read_to_string(root.join("out/repo_scraped.sol")).unwrap();
+    assert!(scraped.contains("contract Release {}"), "{scraped}");
+    assert!(!scraped.contains("contract Main {}"), "{scraped}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A branch that does not exist fails the clone with its name.
+#[test]
+fn test_missing_branch_is_named() {
+    let root = fixture("missing");
+    let output = scrape(&root, &["--branch", "release-v9"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("Git clone failed: "), "{stderr}");
+    assert!(stderr.contains("(cloning branch release-v9)"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
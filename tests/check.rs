@@ -0,0 +1,155 @@
+//! End-to-end tests for `solscrape check`, run against the built binary on
+//! a local source and the output a plain scrape of it committed.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// The exit code of a committed output that is out of date.
+const EXIT_OUT_OF_DATE: i32 = 7;
+
+/// Creates a source with two contracts and scrapes it into
+/// `committed/source_scraped.sol`, returning the fixture root.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            ("source/src/A.sol", "// a\ncontract A {\n    uint x;\n}\n"),
+            ("source/src/B.sol", "contract B {}\n"),
+        ],
+    );
+    let output = common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            root.join("committed").to_str().unwrap(),
+            "--quiet",
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    root
+}
+
+/// Runs `solscrape check` on the fixture's source against its committed
+/// output.
+fn check(root: &Path) -> Output {
+    common::solscrape()
+        .args([
+            "check",
+            root.join("source").to_str().unwrap(),
+            "--against",
+            root.join("committed/source_scraped.sol").to_str().unwrap(),
+        ])
+        .output()
+        .unwrap()
+}
+
+/// An unchanged source passes and nothing is written.
+#[test]
+fn test_check_identical() {
+    let root = fixture("identical");
+    let committed = root.join("committed/source_scraped.sol");
+    let before = fs::metadata(&committed).unwrap().modified().unwrap();
+    let output = check(&root);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).contains("is up to date (2 files)"),
+        "{:?}",
+        output
+    );
+    assert_eq!(
+        fs::metadata(&committed).unwrap().modified().unwrap(),
+        before
+    );
+    assert_eq!(fs::read_dir(root.join("committed")).unwrap().count(), 1);
+
+    // A checkout with CRLF line endings still matches
+    let crlf = fs::read_to_string(&committed)
+        .unwrap()
+        .replace('\n', "\r\n");
+    fs::write(&committed, crlf).unwrap();
+    assert!(check(&root).status.success());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A file committed under a UTF-8 locale passes on a C-locale runner, and
+/// one committed with `--ascii` passes without it.
+#[test]
+fn test_check_ignores_locale() {
+    let root = fixture("locale");
+    let source = root.join("source");
+    let committed = root.join("committed");
+    let run = |args: &[&str], locale: &str| {
+        common::solscrape()
+            .args(args)
+            .env("LC_ALL", locale)
+            .output()
+            .unwrap()
+    };
+    let against = committed.join("source_scraped.sol");
+    let check = [
+        "check",
+        source.to_str().unwrap(),
+        "--against",
+        against.to_str().unwrap(),
+        "--vcs-friendly",
+    ];
+    for (locale, extra) in [("en_US.UTF-8", None), ("C", Some("--ascii"))] {
+        let mut scrape = vec![
+            source.to_str().unwrap(),
+            committed.to_str().unwrap(),
+            "--quiet",
+            "--vcs-friendly",
+        ];
+        scrape.extend(extra);
+        assert!(run(&scrape, locale).status.success());
+        for checking in ["C", "en_US.UTF-8"] {
+            let output = run(&check, checking);
+            assert!(
+                output.status.success(),
+                "{} then {}: {:?}",
+                locale,
+                checking,
+                output
+            );
+        }
+    }
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A changed contract fails with the section and its line counts.
+#[test]
+fn test_check_modified() {
+    let root = fixture("modified");
+    fs::write(
+        root.join("source/src/A.sol"),
+        "contract A {\n    uint x;\n    uint y;\n}\n",
+    )
+    .unwrap();
+    let output = check(&root);
+    assert_eq!(output.status.code(), Some(EXIT_OUT_OF_DATE), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("source_scraped.sol is out of date: 1 file, +1 -0"),
+        "{stderr}"
+    );
+    assert!(stderr.contains("  src/A.sol  +1 -0"), "{stderr}");
+    assert!(!stderr.contains("src/B.sol"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A new contract fails as an added section.
+#[test]
+fn test_check_file_added() {
+    let root = fixture("added");
+    fs::write(root.join("source/src/C.sol"), "contract C {}\n").unwrap();
+    let output = check(&root);
+    assert_eq!(output.status.code(), Some(EXIT_OUT_OF_DATE), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("out of date: 1 file, +1 -0"), "{stderr}");
+    assert!(stderr.contains("  src/C.sol  added, +1"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,71 @@
+//! End-to-end tests for `--checksum-file` and `verify`, run against the
+//! built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[("source/src/Vault.sol", "// the vault\ncontract Vault {}\n")],
+    );
+    root
+}
+
+fn solscrape(args: &[&Path], extra: &[&str]) -> Output {
+    common::solscrape().args(args).args(extra).output().unwrap()
+}
+
+/// The sidecar is written next to the output and verifies until a single
+/// byte of the output changes.
+#[test]
+fn test_checksum_file_and_verify() {
+    let root = fixture("verify");
+    let output = solscrape(
+        &[&root.join("source"), &root.join("out")],
+        &["--local", "--checksum-file"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let scraped = root.join("out/source_scraped.sol");
+    let sidecar = fs::read_to_string(root.join("out/source_scraped.sol.sha256")).unwrap();
+    let (digest, name) = sidecar.trim_end().split_once("  ").unwrap();
+    assert_eq!((digest.len(), name), (64, "source_scraped.sol"));
+
+    let verified = solscrape(&[Path::new("verify"), &scraped], &[]);
+    let stdout = String::from_utf8_lossy(&verified.stdout);
+    assert!(verified.status.success(), "{:?}", verified);
+    assert!(
+        stdout.contains(&format!("matches its checksum ({})", digest)),
+        "{stdout}"
+    );
+
+    let mut bytes = fs::read(&scraped).unwrap();
+    let last = bytes.len() - 2;
+    bytes[last] ^= 1;
+    fs::write(&scraped, bytes).unwrap();
+    let tampered = solscrape(&[Path::new("verify"), &scraped], &[]);
+    let stderr = String::from_utf8_lossy(&tampered.stderr);
+    assert_eq!(tampered.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("Checksum mismatch for "), "{stderr}");
+    assert!(stderr.contains(&format!("expected {}", digest)), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifying without a sidecar says how to get one.
+#[test]
+fn test_verify_without_sidecar() {
+    let root = fixture("missing");
+    let scraped = root.join("source/src/Vault.sol");
+    let output = solscrape(&[Path::new("verify"), &scraped], &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("scrape with --checksum-file to write one"),
+        "{stderr}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,77 @@
+//! Helpers shared by the end-to-end tests: fresh fixture directories, the
+//! built binary, git with a fixed identity, and stand-ins for the programs
+//! solscrape runs.
+
+// Every test crate compiles this module and uses only some of it.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Creates a fresh, empty fixture directory under the system temp dir.
+pub fn fixture_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("solscrape_test_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `(relative path, content)` pairs under `root`.
+pub fn write_tree(root: &Path, files: &[(&str, &str)]) {
+    for (relative, content) in files {
+        let path = root.join(relative);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+}
+
+/// A command running the built binary.
+pub fn solscrape() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_solscrape"))
+}
+
+/// Runs git in `dir` with a fixed identity, and with the file transport
+/// allowed for local submodules, returning its trimmed stdout; panics on
+/// failure.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .args([
+            "-c",
+            "user.name=Fixture",
+            "-c",
+            "user.email=fixture@example.com",
+            "-c",
+            "protocol.file.allow=always",
+        ])
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {:?}: {:?}", args, output);
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Writes `root/bin/{program}`, a shell script running `script`, next to
+/// links to the system's `sh` and `tools`, and returns `bin/` to put on
+/// `PATH` in place of the real program.
+#[cfg(unix)]
+pub fn stub_program(root: &Path, program: &str, script: &str, tools: &[&str]) -> PathBuf {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let bin = root.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    for tool in std::iter::once(&"sh").chain(tools) {
+        symlink(Path::new("/bin").join(tool), bin.join(tool)).unwrap();
+    }
+    let path = bin.join(program);
+    fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    bin
+}
+
+/// [`stub_program`] for git.
+#[cfg(unix)]
+pub fn stub_git(root: &Path, script: &str, tools: &[&str]) -> PathBuf {
+    stub_program(root, "git", script, tools)
+}
@@ -0,0 +1,130 @@
+//! End-to-end tests for configuration files and environment variables, run
+//! against the built binary with its own `XDG_CONFIG_HOME` and working
+//! directory.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A directory with a user configuration under `xdg/solscrape/`, and a
+/// `project/` holding a project configuration and a contract.
+fn fixture(name: &str, user: &str, project: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            ("xdg/solscrape/config.toml", user),
+            ("project/solscrape.toml", project),
+            ("project/src/A.sol", "// A vault.\ncontract A {}\n"),
+        ],
+    );
+    root
+}
+
+/// Runs solscrape in `project/` with the fixture's configuration and the
+/// variables given.
+fn solscrape(root: &Path, args: &[&str], env: &[(&str, &str)]) -> Output {
+    let mut command = common::solscrape();
+    command
+        .args(args)
+        .current_dir(root.join("project"))
+        .env("HOME", root)
+        .env("XDG_CONFIG_HOME", root.join("xdg"));
+    for name in [
+        "SOLSCRAPE_DESTINATION",
+        "SOLSCRAPE_QUIET",
+        "SOLSCRAPE_HEADERS",
+        "SOLSCRAPE_PROFILE",
+    ] {
+        command.env_remove(name);
+    }
+    for (name, value) in env {
+        command.env(name, value);
+    }
+    command.output().unwrap()
+}
+
+/// `config path` lists the files in order and where each setting came
+/// from, the environment overriding both files.
+#[test]
+fn test_config_path_reports_layers() {
+    let root = fixture(
+        "report",
+        "destination = \"user-out\"\nheaders = \"none\"\n",
+        "destination = \"project-out\"\n",
+    );
+    let output = solscrape(&root, &["config", "path"], &[("SOLSCRAPE_QUIET", "yes")]);
+    assert!(output.status.success(), "{:?}", output);
+    let project = root.join("project").canonicalize().unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "Configuration, lowest precedence first:\n  \
+             {user}\n  \
+             {project}/solscrape.toml\n  \
+             $SOLSCRAPE_DESTINATION, $SOLSCRAPE_QUIET, $SOLSCRAPE_HEADERS, $SOLSCRAPE_PROFILE\n  \
+             command-line options\n\nSettings:\n  \
+             destination = \"project-out\"  (from {project}/solscrape.toml)\n  \
+             headers = \"none\"  (from {user})\n  \
+             quiet = true  (from $SOLSCRAPE_QUIET)\n",
+            user = root.join("xdg/solscrape/config.toml").display(),
+            project = project.display(),
+        )
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A scrape uses the configured destination and header style, and a
+/// destination or `--verbose` on the command line, and a header style in
+/// the environment, win.
+#[test]
+fn test_scrape_uses_configuration() {
+    let root = fixture(
+        "scrape",
+        "headers = \"none\"\nquiet = true\n",
+        "destination = \"project-out\"\n",
+    );
+    let output = solscrape(&root, &[".", "--local", "-o", "all"], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(output.stderr.is_empty(), "{:?}", output);
+    let scraped = fs::read_to_string(root.join("project/project-out/all_scraped.sol")).unwrap();
+    assert!(!scraped.contains("src/A.sol"), "{scraped}");
+
+    let output = solscrape(
+        &root,
+        &[".", "cli-out", "--local", "-o", "all", "--verbose"],
+        &[("SOLSCRAPE_HEADERS", "full")],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert!(!output.stderr.is_empty());
+    let scraped = fs::read_to_string(root.join("project/cli-out/all_scraped.sol")).unwrap();
+    assert!(scraped.contains("src/A.sol"), "{scraped}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A malformed file or variable stops the run, naming the file and line or
+/// the variable.
+#[test]
+fn test_invalid_configuration_is_an_error() {
+    let root = fixture("invalid", "quiet = true\nheader = \"none\"\n", "");
+    let output = solscrape(&root, &[".", "--local"], &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("solscrape/config.toml:2: unknown key \"header\""),
+        "{stderr}"
+    );
+
+    fs::write(root.join("xdg/solscrape/config.toml"), "").unwrap();
+    let output = solscrape(&root, &[".", "--local"], &[("SOLSCRAPE_PROFILE", "fast")]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "Unknown profile: fast (expected audit, llm, or verify) (from $SOLSCRAPE_PROFILE)"
+        ),
+        "{stderr}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
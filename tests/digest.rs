@@ -0,0 +1,84 @@
+//! End-to-end tests for `solscrape --digest`, run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+/// A source covering every digest column: declarations of each kind, two
+/// pragmas, assembly, a marker in a comment, a file with none of them, and
+/// a test that is left out.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            (
+                "src/Vault.sol",
+                "// SPDX-License-Identifier: MIT\npragma solidity ^0.8.20;\n\
+                 pragma solidity <0.9.0;\n\ninterface IVault {}\n\nlibrary Math {}\n\n\
+                 // TODO: fees\nabstract contract Base {}\n\ncontract Vault is Base {\n    \
+                 function f() external {\n        assembly { let x := 1 }\n    }\n}\n",
+            ),
+            (
+                "src/tokens/Token.sol",
+                "/* The token. */\ncontract Token {}\n",
+            ),
+            ("test/Vault.t.sol", "contract VaultTest {}\n"),
+        ],
+    );
+    root
+}
+
+fn solscrape(args: &[&str]) -> Output {
+    common::solscrape().args(args).output().unwrap()
+}
+
+/// Verifies the exact table, and that nothing is written.
+#[test]
+fn test_digest_prints_table() {
+    let root = fixture("table");
+    let dest = root.join("never-created");
+
+    let output = solscrape(&[
+        root.to_str().unwrap(),
+        dest.to_str().unwrap(),
+        "--local",
+        "--digest",
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "\
+PATH                  LINES  CONTRACTS  INTERFACES  LIBRARIES  PRAGMA          FLAGS
+src/Vault.sol            10          2           1          1  ^0.8.20 <0.9.0  assembly,todo
+src/tokens/Token.sol      1          1           0          0  -               -
+"
+    );
+    assert!(!dest.exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies the exact tab-separated lines under `--quiet`, and exit code 4
+/// when nothing matches.
+#[test]
+fn test_digest_quiet_is_tab_separated() {
+    let root = fixture("quiet");
+    let source = root.to_str().unwrap();
+
+    let output = solscrape(&[source, "--local", "--digest", "-q"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "src/Vault.sol\t10\t2\t1\t1\t^0.8.20 <0.9.0\tassembly,todo\n\
+         src/tokens/Token.sol\t1\t1\t0\t0\t-\t-\n"
+    );
+
+    fs::remove_dir_all(root.join("src")).unwrap();
+    let output = solscrape(&[source, "--local", "--digest"]);
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(output.stdout.is_empty());
+    let _ = fs::remove_dir_all(&root);
+}
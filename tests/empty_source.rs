@@ -0,0 +1,113 @@
+//! End-to-end tests for the three "nothing to scrape" failures, run against
+//! the built binary on bare repositories cloned through file:// URLs.
+
+mod common;
+
+use common::git;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Creates a bare repository holding `files` on `main`, or no commit at all
+/// when `files` is empty.
+fn repository(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let bare = root.join("repo.git");
+    git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+    if files.is_empty() {
+        return root;
+    }
+
+    let work = root.join("work");
+    common::write_tree(&work, files);
+    git(&work, &["init", "-q", "-b", "main"]);
+    git(&work, &["add", "."]);
+    git(&work, &["commit", "-q", "-m", "init"]);
+    git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+    git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    root
+}
+
+/// Scrapes the fixture's bare repository into `out/`.
+fn scrape(root: &Path) -> Output {
+    let url = format!("file://{}", root.join("repo.git").display());
+    common::solscrape()
+        .args([
+            url.as_str(),
+            root.join("out").to_str().unwrap(),
+            "--min-temp-space",
+            "0",
+        ])
+        // The messages below are checked with their Unicode punctuation
+        .env("LC_ALL", "C.UTF-8")
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+/// Verifies an empty repository is reported as such.
+#[test]
+fn test_empty_repository() {
+    let root = repository("empty", &[]);
+    let output = scrape(&root);
+
+    assert_eq!(output.status.code(), Some(5), "{:?}", output);
+    assert!(
+        stderr(&output).contains("Repository is empty"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!root.join("out").exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies a repository without Solidity names its most common file types.
+#[test]
+fn test_repository_without_solidity() {
+    let root = repository(
+        "no_sol",
+        &[
+            ("src/index.ts", "export {}"),
+            ("src/app.ts", "export {}"),
+            ("src/util.js", "module.exports = {}"),
+            ("README.md", "# web"),
+        ],
+    );
+    let output = scrape(&root);
+
+    assert_eq!(output.status.code(), Some(6), "{:?}", output);
+    assert!(
+        stderr(&output)
+            .contains("Repository contains no Solidity (top file types: .ts 2, .js 1, .md 1)"),
+        "{}",
+        stderr(&output)
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies Solidity that is all excluded is attributed to its directories.
+#[test]
+fn test_repository_with_only_excluded_solidity() {
+    let root = repository(
+        "excluded",
+        &[
+            ("lib/dep/Dep.sol", "contract Dep {}"),
+            ("test/Vault.t.sol", "contract VaultTest {}"),
+            ("package.json", "{}"),
+        ],
+    );
+    let output = scrape(&root);
+
+    assert_eq!(output.status.code(), Some(4), "{:?}", output);
+    assert!(
+        stderr(&output).contains(
+            "all 2 .sol files were excluded. Excluded: lib (1 file), test (1 file) — use --include-* to add"
+        ),
+        "{}",
+        stderr(&output)
+    );
+    let _ = fs::remove_dir_all(&root);
+}
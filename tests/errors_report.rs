@@ -0,0 +1,113 @@
+//! End-to-end tests for `solscrape --errors-report`, run against the built
+//! binary.
+
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Output;
+
+/// Two contracts sharing a reason, an error at file scope and one inside a
+/// contract, and a reason that `--strip-revert-strings` would shorten.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            ("src/Errors.sol", "// Shared errors.\nerror ZeroAmount();\n"),
+            (
+                "src/Vault.sol",
+                "contract Vault {\n    error Unauthorized(address caller);\n    \
+                 function deposit(uint256 amount) external {\n        \
+                 require(amount > 0, \"Vault: zero amount\");\n        \
+                 // require(false, \"commented out\");\n    }\n}\n",
+            ),
+            (
+                "src/Router.sol",
+                "contract Router {\n    function swap(uint256 amount) external {\n        \
+                 require(amount > 0, \"Vault: zero amount\");\n        \
+                 revert(\"Router: not implemented\");\n    }\n}\n",
+            ),
+        ],
+    );
+    root
+}
+
+fn solscrape(args: &[&str]) -> Output {
+    common::solscrape().args(args).output().unwrap()
+}
+
+/// Verifies the exact text sidecar and the summary line, with the reasons
+/// as written even when the output shortens them.
+#[test]
+fn test_errors_report_writes_text_sidecar() {
+    let root = fixture("text");
+    let out = root.join("out");
+    let output = solscrape(&[
+        root.to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--local",
+        "-o",
+        "all",
+        "--errors-report",
+        "text",
+        "--strip-revert-strings",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("   Errors:          2 custom errors, 2 revert strings (3 uses)\n"),
+        "{stdout}"
+    );
+    let sidecar = out.join("all_scraped.sol.errors.txt");
+    assert!(
+        stdout.contains(&format!("   Errors report:   {}\n", sidecar.display())),
+        "{stdout}"
+    );
+    assert_eq!(
+        fs::read_to_string(&sidecar).unwrap(),
+        "\
+Custom errors (2):
+  ZeroAmount()                  -      src/Errors.sol
+  Unauthorized(address caller)  Vault  src/Vault.sol
+
+Revert strings (2, 3 uses):
+  2  \"Vault: zero amount\"       src/Router.sol (Router), src/Vault.sol (Vault)
+  1  \"Router: not implemented\"  src/Router.sol (Router)
+"
+    );
+    let scraped = fs::read_to_string(out.join("all_scraped.sol")).unwrap();
+    assert!(!scraped.contains("Vault: zero amount"));
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies the JSON sidecar, `null` standing for file scope.
+#[test]
+fn test_errors_report_writes_json_sidecar() {
+    let root = fixture("json");
+    let out = root.join("out");
+    let output = solscrape(&[
+        root.to_str().unwrap(),
+        out.to_str().unwrap(),
+        "--local",
+        "-o",
+        "all",
+        "--errors-report",
+        "json",
+        "-q",
+    ]);
+    assert!(output.status.success(), "{:?}", output);
+    let json = fs::read_to_string(out.join("all_scraped.sol.errors.json")).unwrap();
+    assert!(
+        json.starts_with(
+            r#"{"errors":[{"name":"ZeroAmount","parameters":"","contract":null,"path":"src/Errors.sol"},"#
+        ),
+        "{json}"
+    );
+    assert!(
+        json.contains(r#"{"text":"\"Vault: zero amount\"","count":2,"#),
+        "{json}"
+    );
+    assert!(json.ends_with("}\n"));
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,65 @@
+//! End-to-end tests for `--flag-todos`, run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[(
+            "source/src/Vault.sol",
+            "contract Vault {\n    function f() external {\n        revert(\"TODO: fees\");\n    }\n}\n",
+        )],
+    );
+    root
+}
+
+/// Scrapes the fixture with `--flag-todos` and `extra` into `out/`.
+fn solscrape(root: &Path, extra: &[&str]) -> Output {
+    common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+            "--local",
+            "--flag-todos",
+        ])
+        .args(extra)
+        // The summary bullets below are the Unicode ones
+        .env("LC_ALL", "C.UTF-8")
+        .output()
+        .unwrap()
+}
+
+/// Markers are reported in the summary without failing the run.
+#[test]
+fn test_flag_todos_reports_markers() {
+    let root = fixture("report");
+    let output = solscrape(&root, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.contains("TODO markers:    1 (1 TODO)"), "{stdout}");
+    assert!(
+        stdout.contains("• src/Vault.sol: TODO near line 3: revert(\"TODO: fees\");"),
+        "{stdout}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Under --strict a marker fails the run, after the output was written.
+#[test]
+fn test_flag_todos_strict_fails() {
+    let root = fixture("strict");
+    let output = solscrape(&root, &["--strict"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("--strict: the output still contains 1 TODO marker"),
+        "{stderr}"
+    );
+    assert!(root.join("out/source_scraped.sol").is_file());
+    let _ = fs::remove_dir_all(&root);
+}
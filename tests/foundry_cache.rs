@@ -0,0 +1,121 @@
+//! End-to-end tests for filling `--include-lib` submodules from a Foundry
+//! cache, run against the built binary on a bare repository whose `lib/`
+//! submodules cannot be fetched.
+
+mod common;
+
+use common::git;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// The URL the fixture's cached dependency was cloned from; `.invalid`
+/// never resolves, so fetching it fails without a network.
+const DEP_URL: &str = "https://example.invalid/acme/dep.git";
+
+/// Creates a bare repository with `src/App.sol` and two submodules,
+/// `lib/dep` and `lib/gone`, and a cache holding a checkout of `lib/dep`
+/// only.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let work = root.join("work");
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/App.sol"), "contract App {}\n").unwrap();
+    fs::write(
+        work.join(".gitmodules"),
+        format!(
+            "[submodule \"lib/dep\"]\n\tpath = lib/dep\n\turl = {}\n\
+             [submodule \"lib/gone\"]\n\tpath = \"lib/gone\"\n\
+             \turl = https://example.invalid/acme/gone\n",
+            DEP_URL
+        ),
+    )
+    .unwrap();
+    git(&work, &["init", "-q", "-b", "main"]);
+    git(&work, &["add", "."]);
+    let gitlink = "0123456789abcdef0123456789abcdef01234567";
+    for path in ["lib/dep", "lib/gone"] {
+        let cacheinfo = format!("160000,{},{}", gitlink, path);
+        git(&work, &["update-index", "--add", "--cacheinfo", &cacheinfo]);
+    }
+    git(&work, &["commit", "-q", "-m", "init"]);
+    let bare = root.join("repo.git");
+    git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+    git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+    git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+
+    let checkout = root.join("cache/acme/dep");
+    fs::create_dir_all(checkout.join(".git")).unwrap();
+    fs::create_dir_all(checkout.join("src")).unwrap();
+    fs::write(
+        checkout.join(".git/config"),
+        "[remote \"origin\"]\n\turl = git@example.invalid:Acme/dep\n",
+    )
+    .unwrap();
+    fs::write(checkout.join("src/Dep.sol"), "contract Dep {}\n").unwrap();
+    root
+}
+
+/// Scrapes the fixture's bare repository into `out/` with `extra`.
+fn scrape(root: &Path, extra: &[&str]) -> Output {
+    let url = format!("file://{}", root.join("repo.git").display());
+    common::solscrape()
+        .args([
+            url.as_str(),
+            root.join("out").to_str().unwrap(),
+            "--min-temp-space",
+            "0",
+            "--include-lib",
+            "--foundry-cache",
+            root.join("cache").to_str().unwrap(),
+        ])
+        .args(extra)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .unwrap()
+}
+
+/// Under --offline the cached dependency is copied in and the other is
+/// warned about, without trying to fetch either.
+#[test]
+fn test_offline_fills_from_cache() {
+    let root = fixture("offline");
+    let output = scrape(&root, &["--offline"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(!stderr.contains("Submodule init failed"), "{stderr}");
+    assert!(
+        stderr.contains(
+            "Submodule lib/gone (https://example.invalid/acme/gone) left empty: \
+             no cached checkout"
+        ),
+        "{stderr}"
+    );
+
+    let scraped = fs::read_to_string(root.join("out/repo_scraped.sol")).unwrap();
+    assert!(scraped.contains("contract App {}"), "{scraped}");
+    assert!(scraped.contains("contract Dep {}"), "{scraped}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// When fetching the submodules fails, the cache is fallen back on.
+#[test]
+fn test_failed_init_falls_back_to_cache() {
+    let root = fixture("fallback");
+    let output = scrape(&root, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("Submodule init failed for lib/dep"),
+        "expected a fallback: {stderr}"
+    );
+    assert!(
+        stderr.contains("Submodule init failed for lib/gone"),
+        "{stderr}"
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Initialized 0 of 2 submodules"), "{stdout}");
+    let scraped = fs::read_to_string(root.join("out/repo_scraped.sol")).unwrap();
+    assert!(scraped.contains("contract Dep {}"), "{scraped}");
+    let _ = fs::remove_dir_all(&root);
+}
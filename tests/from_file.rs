@@ -0,0 +1,211 @@
+//! End-to-end tests for `--from-file`, run against the built binary on two
+//! local sources.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Two sources, `a` and `b`, each with a contract and a test, and an empty
+/// `out/`.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    for source in ["a", "b"] {
+        let dir = root.join(source);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::create_dir_all(dir.join("test")).unwrap();
+        fs::write(
+            dir.join("src/Main.sol"),
+            format!("contract {} {{}}\n", source),
+        )
+        .unwrap();
+        fs::write(dir.join("test/Main.t.sol"), "contract T {}\n").unwrap();
+    }
+    fs::create_dir_all(root.join("out")).unwrap();
+    root
+}
+
+/// Runs solscrape on the list `list`, written to `sources.txt`, with
+/// `extra`.
+fn solscrape(root: &Path, list: &str, extra: &[&str]) -> Output {
+    let path = root.join("sources.txt");
+    fs::write(&path, list).unwrap();
+    common::solscrape()
+        .args([
+            "--from-file",
+            path.to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+        ])
+        .args(extra)
+        .current_dir(root)
+        .output()
+        .unwrap()
+}
+
+/// Each line is scraped with its own options on top of the command line's,
+/// and the metrics add up.
+#[test]
+fn test_from_file_scrapes_each_line() {
+    let root = fixture("each");
+    let list = "# two sources\n\na --local -o 'first one'\n\"b\" --include-test\n";
+    let output = solscrape(&root, list, &["--metrics-stdout", "-o", "shared"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let first = fs::read_to_string(root.join("out/first one_scraped.sol")).unwrap();
+    assert!(first.contains("contract a {}"), "{first}");
+    assert!(!first.contains("contract T {}"), "{first}");
+    let second = fs::read_to_string(root.join("out/shared_scraped.sol")).unwrap();
+    assert!(second.contains("contract b {}"), "{second}");
+    assert!(second.contains("contract T {}"), "{second}");
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("solscrape.files_processed 3 "), "{stdout}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A malformed line fails the run with its file and line number before
+/// any source is scraped.
+#[test]
+fn test_from_file_rejects_bad_lines() {
+    let root = fixture("bad");
+    for (list, expected) in [
+        ("a --local\nb -o 'open\n", ":2: unterminated ' quote"),
+        ("a\n\nb --quiet\n", ":3: --quiet applies to the whole run"),
+        ("a --frobnicate\n", ":1: Unknown option: --frobnicate"),
+    ] {
+        let output = solscrape(&root, list, &[]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(output.status.code(), Some(1), "{stderr}");
+        assert!(
+            stderr.contains(&format!("sources.txt{}", expected)),
+            "{stderr}"
+        );
+        assert_eq!(fs::read_dir(root.join("out")).unwrap().count(), 0);
+    }
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A failed source leaves the record of the others, which `--resume` skips;
+/// other command-line options make it scrape everything again.
+#[test]
+fn test_from_file_resume() {
+    let root = fixture("resume");
+    let list = "a --local\nmissing --local\nb --local\n";
+    let output = solscrape(&root, list, &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let record = root.join("out/.solscrape-resume");
+    assert_eq!(
+        fs::read_to_string(&record)
+            .unwrap()
+            .matches("\ndone ")
+            .count(),
+        2
+    );
+
+    fs::remove_file(root.join("out/a_scraped.sol")).unwrap();
+    fs::remove_file(root.join("out/b_scraped.sol")).unwrap();
+    let list = "a --local\nb --local\n";
+    let output = solscrape(&root, list, &["--resume"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("a already scraped; skipping"), "{stdout}");
+    assert!(!root.join("out/a_scraped.sol").exists());
+    assert!(!root.join("out/b_scraped.sol").exists());
+    assert!(!record.exists());
+
+    let output = solscrape(&root, "missing --local\n", &[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let output = solscrape(&root, list, &["--resume", "--include-test"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("other options"), "{stderr}");
+    assert!(root.join("out/a_scraped.sol").exists());
+    assert!(root.join("out/b_scraped.sol").exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// The stop file stops the batch before its next source and is removed.
+#[test]
+fn test_from_file_stop_file() {
+    let root = fixture("stop");
+    let stop = root.join("out/.solscrape-stop");
+    fs::write(&stop, "").unwrap();
+    let output = solscrape(&root, "a --local\nb --local\n", &[]);
+    assert_eq!(output.status.code(), Some(3), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("sources.txt:1"), "{stderr}");
+    assert!(stderr.contains("--resume"), "{stderr}");
+    assert!(!stop.exists());
+    assert!(!root.join("out/a_scraped.sol").exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// With `--run-dir`, every source goes into the one run directory of the
+/// invocation, which `latest` points at.
+#[test]
+fn test_from_file_run_dir() {
+    let root = fixture("run_dir");
+    let output = solscrape(&root, "a --local\nb --local\n", &["--run-dir"]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let runs: Vec<PathBuf> = fs::read_dir(root.join("out"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| !path.is_symlink() && path.is_dir())
+        .collect();
+    assert_eq!(runs.len(), 1, "{runs:?}");
+    assert!(runs[0].join("a_scraped.sol").exists());
+    assert!(runs[0].join("b_scraped.sol").exists());
+    #[cfg(unix)]
+    assert_eq!(
+        fs::read_link(root.join("out/latest")).unwrap(),
+        runs[0].file_name().unwrap()
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.matches("Run directory: ").count(), 1, "{stdout}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Under `--quiet`, each source prints one tab-separated status line, in
+/// list order, and nothing else reaches stdout.
+#[cfg(unix)]
+#[test]
+fn test_from_file_quiet_status_lines() {
+    let root = fixture("quiet");
+    common::stub_git(
+        &root,
+        "echo 'fatal: repository not found' >&2\nexit 128",
+        &[],
+    );
+    let list = "a --local\nhttps://github.com/acme/vault --no-precheck\n";
+    fs::write(root.join("sources.txt"), list).unwrap();
+    let run = |extra: &[&str]| {
+        common::solscrape()
+            .args(["--from-file", "sources.txt", "out", "-q"])
+            .args(extra)
+            .current_dir(&root)
+            .env("PATH", root.join("bin"))
+            .output()
+            .unwrap()
+    };
+
+    let output = run(&[]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a\tok\tout/a_scraped.sol\n\
+         https://github.com/acme/vault\tfailed\tGit clone failed: fatal: repository not found\n"
+    );
+
+    let output = run(&["--resume"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(
+        String::from_utf8_lossy(&output.stdout).starts_with(
+            "a\tskipped\talready scraped (--resume)\nhttps://github.com/acme/vault\tfailed\t"
+        ),
+        "{:?}",
+        output
+    );
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,99 @@
+//! End-to-end tests for `--gate`, run against the built binary on a local
+//! source with one GPL contract importing a file that is not there, and one
+//! contract with nothing left after cleaning.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A source under `src/` and an empty `out/`.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            (
+                "src/Vault.sol",
+                "// SPDX-License-Identifier: GPL-3.0-or-later\n\
+                 import \"./Missing.sol\";\n\
+                 contract Vault {}\n",
+            ),
+            ("src/Empty.sol", "// Nothing here yet.\n"),
+        ],
+    );
+    fs::create_dir_all(root.join("out")).unwrap();
+    root
+}
+
+/// Runs solscrape on the fixture with `args`.
+fn solscrape(root: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .args([".", "out", "--local"])
+        .args(args)
+        .current_dir(root)
+        .output()
+        .unwrap()
+}
+
+/// Gates that pass leave the exit code at 0 and are counted in the summary.
+#[test]
+fn test_passing_gates() {
+    let root = fixture("pass");
+    let output = solscrape(
+        &root,
+        &["--gate", "max-lines:1000", "--gate", "no-license:MIT"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("   Gates:           2 of 2 passed"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("Failed gates:"), "{stdout}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Failing gates exit 8 after writing the output, listing each gate that
+/// failed and why.
+#[test]
+fn test_failing_gates() {
+    let root = fixture("fail");
+    let output = solscrape(
+        &root,
+        &[
+            "--gate",
+            "no-skips",
+            "--gate",
+            "no-license:GPL-3.0",
+            "--gate",
+            "no-missing-imports",
+            "--gate",
+            "max-lines:1000",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(8), "{:?}", output);
+    assert!(root.join("out/local_scraped.sol").exists());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("   Gates:           1 of 4 passed"),
+        "{stdout}"
+    );
+    for line in [
+        "no-skips: 1 file skipped: src/Empty.sol (empty-after-clean)",
+        "no-license:GPL-3.0: 1 file under GPL-3.0: src/Vault.sol (GPL-3.0-or-later)",
+        "no-missing-imports: 1 import not in the output: src/Vault.sol imports ./Missing.sol",
+    ] {
+        assert!(stdout.contains(line), "{line}\n{stdout}");
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(
+            "3 of 4 gates failed (no-skips, no-license:GPL-3.0, no-missing-imports); \
+             see the list above"
+        ),
+        "{stderr}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,67 @@
+//! End-to-end tests for the git version check, run against the built binary
+//! with a stand-in `git` that reports an old release.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A directory holding a `git` script that prints `version` and fails on
+/// anything but `--version`, so a test notices any other git command.
+fn fake_git(name: &str, version: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let script = format!(
+        "if [ \"$1\" = --version ]; then echo '{}'; exit 0; fi\n\
+         echo \"unexpected git $*\" >&2\nexit 1",
+        version
+    );
+    common::stub_git(&root, &script, &[]);
+    root
+}
+
+/// Runs solscrape with only the fake git (and `sh`) on `PATH`.
+fn solscrape(root: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .args(args)
+        .arg(root.join("out"))
+        .env(
+            "PATH",
+            format!("{}:/bin:/usr/bin", root.join("bin").display()),
+        )
+        .output()
+        .unwrap()
+}
+
+/// An explicit --precheck fails early, naming the versions.
+#[test]
+fn test_old_git_fails_early() {
+    let root = fake_git("old", "git version 2.17.1");
+    let output = solscrape(
+        &root,
+        &["https://github.com/example/repo.git", "--precheck"],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("requires git >= 2.19 for --precheck, found 2.17.1"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("unexpected git"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A vendor suffix is read past, and a new enough git goes on to clone.
+#[test]
+fn test_vendor_version_passes() {
+    let root = fake_git("apple", "git version 2.39.3 (Apple Git-146)");
+    let output = solscrape(
+        &root,
+        &["https://github.com/example/repo.git", "--precheck"],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("requires git"), "{stderr}");
+    assert!(stderr.contains("unexpected git ls-remote"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,116 @@
+//! End-to-end tests for `solscrape --list-files`, run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(&root, files);
+    root
+}
+
+fn solscrape(args: &[&str]) -> Output {
+    common::solscrape().args(args).output().unwrap()
+}
+
+fn tree() -> Vec<(&'static str, &'static str)> {
+    vec![
+        ("src/Vault.sol", "contract Vault {}"),
+        ("src/tokens/Token.sol", "contract Token {}"),
+        ("src/Empty.sol", "// nothing but a comment"),
+        ("lib/dep/Dep.sol", "contract Dep {}"),
+        ("test/Vault.t.sol", "contract VaultTest {}"),
+    ]
+}
+
+/// Verifies exact stdout for the default selection and that nothing is written.
+#[test]
+fn test_list_files_prints_only_included_paths() {
+    let root = fixture("list_default", &tree());
+    let dest = root.join("never-created");
+
+    let output = solscrape(&[
+        root.to_str().unwrap(),
+        dest.to_str().unwrap(),
+        "--local",
+        "--list-files",
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).replace('\\', "/"),
+        "src/Vault.sol\nsrc/tokens/Token.sol\n"
+    );
+    assert!(!dest.exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies inclusion flags and NUL termination.
+#[test]
+fn test_list_files_respects_flags_and_nul() {
+    let root = fixture("list_flags", &tree());
+
+    let output = solscrape(&[
+        root.to_str().unwrap(),
+        "--local",
+        "--list-files",
+        "--include-lib",
+        "-0",
+    ]);
+
+    assert!(output.status.success(), "{:?}", output);
+    let entries: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .split_terminator('\0')
+        .map(|entry| entry.replace('\\', "/"))
+        .collect();
+    assert_eq!(
+        entries,
+        vec!["lib/dep/Dep.sol", "src/Vault.sol", "src/tokens/Token.sol"]
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies exit code 4 and empty stdout when nothing matches.
+#[test]
+fn test_list_files_exits_4_when_nothing_matches() {
+    let root = fixture("list_none", &[("test/Only.t.sol", "contract T {}")]);
+
+    let output = solscrape(&[root.to_str().unwrap(), "--local", "--list-files"]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    assert!(Path::new(&root).exists());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies newline-terminated listings escape control characters while
+/// NUL-terminated ones stay byte-exact.
+#[cfg(unix)]
+#[test]
+fn test_list_files_escapes_awkward_names() {
+    let root = fixture(
+        "list_awkward",
+        &[
+            (" Padded.sol", "contract Padded {}"),
+            ("src/Two\nLines.sol", "contract TwoLines {}"),
+        ],
+    );
+    let source = root.to_str().unwrap();
+
+    let output = solscrape(&[source, "--local", "--list-files"]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "\\x20Padded.sol\nsrc/Two\\nLines.sol\n"
+    );
+
+    let output = solscrape(&[source, "--local", "--list-files", "-0"]);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        " Padded.sol\0src/Two\nLines.sol\0"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,88 @@
+//! End-to-end tests for treating an existing source path as `--local`, run
+//! against the built binary from inside a fixture directory so that sources
+//! can be written as relative paths.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Creates a fixture directory holding `files`.
+fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(&root, files);
+    root
+}
+
+/// Runs solscrape in `dir` with `args`, writing into `out/`.
+fn solscrape(dir: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .args(args)
+        .args(["out", "--min-temp-space", "0", "--no-precheck"])
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// A relative directory is scanned in place, with a note saying so.
+#[test]
+fn test_relative_directory_is_local() {
+    let root = fixture("dir", &[("contracts/src/A.sol", "contract A {}\n")]);
+    let output = solscrape(&root, &["./contracts"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("./contracts exists on disk; treating it as a local path"));
+    assert!(root.join("out/contracts_scraped.sol").is_file());
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A name that could also be an owner/repo shorthand is taken as the local
+/// directory; --no-autodetect hands it to git instead.
+#[test]
+fn test_owner_repo_name_that_exists_locally() {
+    let root = fixture(
+        "owner_repo",
+        &[("acme/vault/src/Vault.sol", "contract Vault {}\n")],
+    );
+    let output = solscrape(&root, &["acme/vault"]);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(root.join("out/vault_scraped.sol").is_file());
+
+    let output = solscrape(&root, &["acme/vault", "--no-autodetect"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Git clone failed"), "{}", stderr);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("treating it as a local path"));
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A source written like a URL is never switched, even when a directory of
+/// that name exists.
+#[test]
+fn test_url_is_not_local() {
+    // The path `https://127.0.0.1:1/acme` names a directory too; port 1
+    // refuses the clone at once
+    let root = fixture(
+        "url",
+        &[("https:/127.0.0.1:1/acme/src/A.sol", "contract A {}\n")],
+    );
+    let output = solscrape(&root, &["https://127.0.0.1:1/acme"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success());
+    assert!(
+        !stdout.contains("treating it as a local path"),
+        "{}",
+        stdout
+    );
+    assert!(!root.join("out").join("acme_scraped.sol").exists());
+    let _ = fs::remove_dir_all(&root);
+}
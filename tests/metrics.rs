@@ -0,0 +1,105 @@
+//! End-to-end tests for `--metrics-file` and `--metrics-stdout`, run against
+//! the built binary on a local fixture.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A source with two included files and one binary file that is skipped.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let src = root.join("source/src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("A.sol"), "contract A {}\n").unwrap();
+    fs::write(src.join("B.sol"), "// b\ncontract B {}\n").unwrap();
+    fs::write(src.join("Blob.sol"), [0u8; 64]).unwrap();
+    root
+}
+
+/// Scrapes the fixture with `extra` into `out/`.
+fn solscrape(root: &Path, extra: &[&str]) -> Output {
+    common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+            "--local",
+        ])
+        .args(extra)
+        .output()
+        .unwrap()
+}
+
+/// Splits a block into `(key, value, timestamp)`, checking that every line
+/// has the three fields and that the timestamps agree.
+fn parse(block: &str) -> Vec<(String, u64)> {
+    let lines: Vec<Vec<&str>> = block
+        .lines()
+        .map(|line| line.split(' ').collect())
+        .collect();
+    assert!(lines.iter().all(|fields| fields.len() == 3), "{block}");
+    assert!(
+        lines.iter().all(|fields| fields[2] == lines[0][2]),
+        "{block}"
+    );
+    lines
+        .iter()
+        .map(|fields| (fields[0].to_string(), fields[1].parse().unwrap()))
+        .collect()
+}
+
+/// A run appends exactly the documented keys, in order, with the counters
+/// of the fixture; a second run appends a second block.
+#[test]
+fn test_metrics_file_lines() {
+    let root = fixture("file");
+    let metrics = root.join("metrics.txt");
+    let output = solscrape(&root, &["--metrics-file", metrics.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+
+    let block = fs::read_to_string(&metrics).unwrap();
+    let lines = parse(&block);
+    let keys: Vec<&str> = lines.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(
+        keys,
+        [
+            "solscrape.clone_ms",
+            "solscrape.files_processed",
+            "solscrape.files_skipped",
+            "solscrape.bytes_written",
+            "solscrape.total_ms",
+            "solscrape.success",
+        ]
+    );
+    let written = fs::metadata(root.join("out/source_scraped.sol"))
+        .unwrap()
+        .len();
+    assert_eq!(lines[0].1, 0);
+    assert_eq!(lines[1].1, 2);
+    assert_eq!(lines[2].1, 1);
+    assert_eq!(lines[3].1, written);
+    assert_eq!(lines[5].1, 1);
+
+    let output = solscrape(&root, &["--metrics-file", metrics.to_str().unwrap()]);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(fs::read_to_string(&metrics).unwrap().lines().count(), 12);
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A failed run still reports, with success 0; --metrics-stdout prints the
+/// block after whatever else goes to stdout (here the --quiet output path).
+#[test]
+fn test_metrics_on_failure_and_stdout() {
+    let root = fixture("failure");
+    let output = solscrape(&root, &["--strict", "--quiet", "--metrics-stdout"]);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (path, block) = stdout.split_once('\n').unwrap();
+    assert!(path.ends_with("source_scraped.sol"), "{stdout}");
+    let lines = parse(block);
+    assert_eq!(lines.len(), 6, "{stdout}");
+    assert_eq!(lines[1], ("solscrape.files_processed".to_string(), 2));
+    assert_eq!(lines[5], ("solscrape.success".to_string(), 0));
+    let _ = fs::remove_dir_all(&root);
+}
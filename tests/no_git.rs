@@ -0,0 +1,95 @@
+//! End-to-end tests for fetching a GitHub source without git, run against
+//! the built binary with a `PATH` that has no git and a stand-in `curl`
+//! that serves a local tarball.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A directory holding `repo.tar.gz` (a contract and a test under
+/// `vault-HEAD/`), and a `bin/` with `sh`, `tar`, `gzip`, `cp`, and a
+/// `curl` script that logs its arguments to `curl.log` and serves the
+/// tarball for any `-o` download.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root.join("vault-HEAD"),
+        &[
+            ("src/Vault.sol", "contract Vault {}\n"),
+            ("test/Vault.t.sol", "contract VaultTest {}\n"),
+        ],
+    );
+    let status = Command::new("tar")
+        .args(["-czf", "repo.tar.gz", "vault-HEAD"])
+        .current_dir(&root)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let script = format!(
+        "echo \"$*\" >> '{root}/curl.log'\n\
+         if [ \"$2\" = -o ]; then cp '{root}/repo.tar.gz' \"$3\"; exit $?; fi\nexit 22",
+        root = root.display()
+    );
+    common::stub_program(&root, "curl", &script, &["tar", "gzip", "cp"]);
+    root
+}
+
+/// Runs solscrape with only the fixture's `bin/` on `PATH`.
+fn solscrape(root: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .args(args)
+        .arg(root.join("out"))
+        .env("PATH", root.join("bin"))
+        .output()
+        .unwrap()
+}
+
+/// Without git, a GitHub source is downloaded as the tarball of its default
+/// branch, or of the branch asked for, and the output says how.
+#[test]
+fn test_missing_git_downloads_tarball() {
+    let root = fixture("download");
+    let output = solscrape(&root, &["https://github.com/acme/vault"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(
+            "Downloaded https://github.com/acme/vault/archive/HEAD.tar.gz with curl and tar \
+             (git is not installed)"
+        ),
+        "{stdout}"
+    );
+    let content = fs::read_to_string(root.join("out/vault_scraped.sol")).unwrap();
+    assert!(content.contains("contract Vault {}"), "{content}");
+    assert!(!content.contains("contract VaultTest {}"), "{content}");
+
+    let output = solscrape(
+        &root,
+        &["https://github.com/acme/vault", "-b", "dev", "-o", "dev"],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    let log = fs::read_to_string(root.join("curl.log")).unwrap();
+    assert!(
+        log.contains("https://github.com/acme/vault/archive/dev.tar.gz"),
+        "{log}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Without git, any other source fails, saying why nothing was tried.
+#[test]
+fn test_missing_git_other_forge() {
+    let root = fixture("other");
+    let output = solscrape(&root, &["https://gitlab.com/acme/vault"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("Git is not installed or not in PATH, and https://gitlab.com/acme/vault is not a GitHub repository"),
+        "{stderr}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
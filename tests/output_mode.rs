@@ -0,0 +1,113 @@
+//! End-to-end tests for the `--output-mode` stdout contracts, run against the
+//! built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            ("source/src/Vault.sol", "// Vault\ncontract Vault {}\n"),
+            ("source/src/Token.sol", "contract Token {}\n"),
+            ("source/lib/dep/Dep.sol", "contract Dep {}\n"),
+        ],
+    );
+    root
+}
+
+/// Runs a local scrape of the fixture into `out/contracts_scraped.sol`.
+fn scrape(root: &Path, mode: &str) -> (Output, PathBuf) {
+    let out = root.join("out");
+    let output = common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            out.to_str().unwrap(),
+            "--local",
+            "-o",
+            "contracts",
+            "--output-mode",
+            mode,
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    (output, out.join("contracts_scraped.sol"))
+}
+
+/// Verifies that `path` prints exactly one line and the rest goes to stderr.
+#[test]
+fn test_output_mode_path_prints_one_line() {
+    let root = fixture("path");
+    let (output, written) = scrape(&root, "path");
+
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\n", written.display())
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Success!"), "{}", stderr);
+    assert!(stderr.contains("Processing files..."), "{}", stderr);
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies that `content` prints the output file byte for byte.
+#[test]
+fn test_output_mode_content_streams_the_output() {
+    let root = fixture("content");
+    let (output, written) = scrape(&root, "content");
+
+    assert_eq!(output.stdout, fs::read(&written).unwrap());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("contract Vault {}"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Success!"));
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies that `json` prints exactly one JSON object, with the byte range
+/// of each file in the output.
+#[test]
+fn test_output_mode_json_prints_one_object() {
+    let root = fixture("json");
+    let (output, written) = scrape(&root, "json");
+
+    let content = fs::read_to_string(&written).unwrap();
+    let lines = content.lines().count();
+    // The second part starts at the separator line above its header
+    let header = content.find("\n// File: src/Vault.sol").unwrap();
+    let second = content[..header].rfind('\n').unwrap() + 1;
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "{{\"output\":\"{}\",\"source\":\"{}\",\"branch\":null,\"commit\":null,\
+             \"pinned_commit\":null,\"files\":2,\"lines\":{},\"paths\":[\"src/Token.sol\",\"src/Vault.sol\"],\
+             \"parts\":[{{\"path\":\"src/Token.sol\",\"offset\":0,\"len\":{}}},\
+             {{\"path\":\"src/Vault.sol\",\"offset\":{},\"len\":{}}}],\
+             \"run_dir\":null,\"partial\":false,\"since\":null,\"removed\":[],\"context\":[],\"todos\":[],\"skipped\":[]}}\n",
+            written.display(),
+            root.join("source").display(),
+            lines,
+            second - 1,
+            second,
+            content.len() - second
+        )
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies that conflicting combinations are rejected before any work.
+#[test]
+fn test_output_mode_rejects_conflicts() {
+    let output = common::solscrape()
+        .args(["split", "a_scraped.sol", "out", "--output-mode", "content"])
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("--output-mode cannot be used with split")
+    );
+}
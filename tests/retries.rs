@@ -0,0 +1,144 @@
+//! End-to-end tests for retrying failed clones and for `--timeout`, run
+//! against the built binary with a stand-in `git` that always fails with a
+//! given message, or hangs.
+#![cfg(unix)]
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::{Duration, Instant};
+
+/// A directory with a `bin/` holding `sh`, `sleep`, and a `git` script
+/// that appends its arguments to `git.log`, prints `stderr`, and exits 128,
+/// or with no `stderr` sleeps for a minute.
+fn fixture(name: &str, stderr: Option<&str>) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let fail = match stderr {
+        Some(stderr) => format!("echo \"{}\" >&2\nexit 128", stderr),
+        None => "exec sleep 60".to_string(),
+    };
+    let script = format!("echo \"$*\" >> '{}/git.log'\n{}", root.display(), fail);
+    common::stub_git(&root, &script, &["sleep"]);
+    root
+}
+
+/// Runs solscrape on a GitHub URL with only the fixture's `bin/` on `PATH`.
+fn solscrape(root: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .arg("https://github.com/acme/vault")
+        .arg(root.join("out"))
+        .args(["--no-precheck"])
+        .args(args)
+        .env("PATH", root.join("bin"))
+        .env_remove("SOLSCRAPE_GIT_TOKEN")
+        .env_remove("GITHUB_TOKEN")
+        .output()
+        .unwrap()
+}
+
+/// The number of clones the stand-in git was asked for.
+fn clones(root: &Path) -> usize {
+    fs::read_to_string(root.join("git.log"))
+        .unwrap()
+        .lines()
+        .filter(|line| line.starts_with("clone "))
+        .count()
+}
+
+/// A network failure is retried with a warning each time, and the final
+/// error counts the attempts; `--quiet` drops the warnings.
+#[test]
+fn test_transient_failure_is_retried() {
+    let root = fixture(
+        "transient",
+        Some(
+            "fatal: unable to access 'https://github.com/acme/vault/': \
+             Could not resolve host: github.com",
+        ),
+    );
+    let output = solscrape(&root, &["--retries", "1"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains(
+            "Clone attempt 1 of 2 failed (fatal: unable to access \
+             'https://github.com/acme/vault/': Could not resolve host: github.com); \
+             retrying in 1s"
+        ),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Git clone failed after 2 attempts: fatal: unable to access"),
+        "{stderr}"
+    );
+    assert_eq!(clones(&root), 2);
+
+    let output = solscrape(&root, &["--retries", "1", "-q"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("Clone attempt"), "{stderr}");
+    assert_eq!(clones(&root), 4);
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A missing repository fails at once, as does any failure with
+/// `--retries 0`.
+#[test]
+fn test_permanent_failure_is_not_retried() {
+    let root = fixture(
+        "permanent",
+        Some(
+            "remote: Repository not found.\n\
+             fatal: repository 'https://github.com/acme/vault/' not found",
+        ),
+    );
+    let output = solscrape(&root, &[]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("Git clone failed: remote: Repository not found."),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("Clone attempt"), "{stderr}");
+    assert_eq!(clones(&root), 1);
+    let _ = fs::remove_dir_all(&root);
+
+    let root = fixture("none", Some("fatal: early EOF"));
+    let output = solscrape(&root, &["--retries", "0"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Git clone failed: fatal: early EOF"),
+        "{stderr}"
+    );
+    assert_eq!(clones(&root), 1);
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A hung clone is killed once `--timeout` has elapsed, and its temporary
+/// directory removed.
+#[test]
+fn test_hung_clone_times_out() {
+    let root = fixture("hung", None);
+    let temp = root.join("tmp");
+    fs::create_dir_all(&temp).unwrap();
+    let started = Instant::now();
+    let output = solscrape(
+        &root,
+        &[
+            "--timeout",
+            "1",
+            "--retries",
+            "0",
+            "--temp-dir",
+            temp.to_str().unwrap(),
+        ],
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "{stderr}");
+    assert!(stderr.contains("Git clone timed out after 1s"), "{stderr}");
+    assert!(started.elapsed() < Duration::from_secs(30));
+    assert_eq!(clones(&root), 1);
+    assert_eq!(fs::read_dir(&temp).unwrap().count(), 0);
+    let _ = fs::remove_dir_all(&root);
+}
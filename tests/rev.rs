@@ -0,0 +1,84 @@
+//! End-to-end tests for `--rev`, run against the built binary on a bare
+//! repository with two commits on `main`.
+
+mod common;
+
+use common::git;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Creates a bare repository whose first commit, tagged `v1`, has
+/// `Old.sol` and whose second replaces it with `New.sol`. Returns the root
+/// and the full hash of the first commit.
+fn fixture(name: &str) -> (PathBuf, String) {
+    let root = common::fixture_dir(name);
+    let work = root.join("work");
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/Old.sol"), "contract Old {}\n").unwrap();
+    git(&work, &["init", "-q", "-b", "main"]);
+    git(&work, &["add", "."]);
+    git(&work, &["commit", "-q", "-m", "old"]);
+    git(&work, &["tag", "v1"]);
+    let first = git(&work, &["rev-parse", "HEAD"]);
+    git(&work, &["rm", "-q", "src/Old.sol"]);
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/New.sol"), "contract New {}\n").unwrap();
+    git(&work, &["add", "."]);
+    git(&work, &["commit", "-q", "-m", "new"]);
+    let bare = root.join("repo.git");
+    git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+    git(
+        &work,
+        &["push", "-q", "--tags", bare.to_str().unwrap(), "main"],
+    );
+    git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    (root, first)
+}
+
+/// Scrapes the fixture's bare repository into `out/` with `extra`.
+fn scrape(root: &Path, extra: &[&str]) -> Output {
+    let url = format!("file://{}", root.join("repo.git").display());
+    common::solscrape()
+        .args([
+            url.as_str(),
+            root.join("out").to_str().unwrap(),
+            "--min-temp-space",
+            "0",
+        ])
+        .args(extra)
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .output()
+        .unwrap()
+}
+
+/// A full hash, an abbreviated one, and a tag all check out the first
+/// commit, and the summary records its full hash.
+#[test]
+fn test_rev_is_checked_out() {
+    let (root, first) = fixture("checkout");
+    for rev in [first.as_str(), &first[..7], "v1"] {
+        let output = scrape(&root, &["--rev", rev]);
+        assert!(output.status.success(), "{:?}", output);
+        let scraped = fs::read_to_string(root.join("out/repo_scraped.sol")).unwrap();
+        assert!(scraped.contains("contract Old {}"), "{rev}: {scraped}");
+        assert!(!scraped.contains("contract New {}"), "{rev}: {scraped}");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains(&format!("Revision:        {} = {}", rev, first)),
+            "{stdout}"
+        );
+    }
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A revision the repository does not have fails with its name.
+#[test]
+fn test_unknown_rev_is_named() {
+    let (root, _) = fixture("unknown");
+    let output = scrape(&root, &["--rev", "deadbeef"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(stderr.contains("Unknown revision deadbeef"), "{stderr}");
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,138 @@
+//! End-to-end tests for skip accounting in the summary and under
+//! `--strict`, run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A source with one included file and one skip per entry of `skipped`.
+fn fixture(name: &str, skipped: &[(&str, &[u8])]) -> PathBuf {
+    let root = common::fixture_dir(name);
+    let src = root.join("source/src");
+    fs::create_dir_all(&src).unwrap();
+    fs::write(src.join("Vault.sol"), "contract Vault {}\n").unwrap();
+    for (file, content) in skipped {
+        fs::write(src.join(file), content).unwrap();
+    }
+    root
+}
+
+/// Scrapes the fixture with `extra` into `out/`.
+fn solscrape(root: &Path, extra: &[&str]) -> Output {
+    common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+            "--local",
+        ])
+        .args(extra)
+        .env("LC_ALL", "C.UTF-8")
+        .output()
+        .unwrap()
+}
+
+/// Skips are counted by reason and listed, without failing the run.
+#[test]
+fn test_skips_are_summarized() {
+    let root = fixture(
+        "summary",
+        &[
+            ("Old.sol", b"// solscrape: ignore\ncontract Old {}\n"),
+            ("Blob.sol", &[0u8; 64]),
+            ("Latin1.sol", b"string constant S = \"caf\xe9\";\n"),
+            (
+                "Pointer.sol",
+                b"version https://git-lfs.github.com/spec/v1\noid sha256:00\nsize 9\n",
+            ),
+        ],
+    );
+    let output = solscrape(&root, &[]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(stdout.contains("Files processed: 1\n"), "{stdout}");
+    assert!(
+        stdout
+            .contains("Skipped:         4 (1 opt-out-marker, 1 lfs-pointer, 1 binary, 1 not-utf8)"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains(
+            "Skipped files:\n  • src/Old.sol (opt-out-marker)\n  • src/Pointer.sol (lfs-pointer)\n  \
+             • src/Blob.sol (binary)\n  • src/Latin1.sol (not-utf8)\n"
+        ),
+        "{stdout}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// A duplicate skipped by --dedupe-content is listed with the copy kept.
+#[test]
+fn test_duplicates_name_the_copy_kept() {
+    let root = fixture(
+        "duplicates",
+        &[(
+            "Copy.sol",
+            b"// a copy
+contract Vault {}
+",
+        )],
+    );
+    let output = solscrape(&root, &["--dedupe-content"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        stdout.contains("Skipped files:\n  • src/Vault.sol (duplicate of src/Copy.sol)\n"),
+        "{stdout}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// An interface left out by --prune-implemented-interfaces is listed with
+/// its implementer.
+#[test]
+fn test_pruned_interfaces_name_the_implementer() {
+    let root = fixture(
+        "pruned",
+        &[
+            ("IPool.sol", b"interface IPool {}\n"),
+            ("Pool.sol", b"contract Pool is IPool {}\n"),
+        ],
+    );
+    let output = solscrape(&root, &["--prune-implemented-interfaces"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert!(
+        stdout.contains(
+            "Skipped files:\n  • src/IPool.sol (implemented-interface: IPool is implemented by \
+             Pool in src/Pool.sol)\n"
+        ),
+        "{stdout}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Under --strict a skip that loses code fails the run; an explicit filter
+/// does not.
+#[test]
+fn test_strict_fails_on_lost_content_only() {
+    let root = fixture("strict", &[("Blob.sol", &[0u8; 64])]);
+    let output = solscrape(&root, &["--strict"]);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{stderr}");
+    assert!(
+        stderr.contains("--strict: files skipped with their code lost: 1 (1 binary)"),
+        "{stderr}"
+    );
+    assert!(root.join("out/source_scraped.sol").is_file());
+    let _ = fs::remove_dir_all(&root);
+
+    let root = fixture(
+        "strict_filter",
+        &[("Old.sol", b"// solscrape: ignore\ncontract Old {}\n")],
+    );
+    let output = solscrape(&root, &["--strict"]);
+    assert!(output.status.success(), "{:?}", output);
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,105 @@
+//! End-to-end tests for several `--source`, run against the built binary on
+//! two local sources.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Two sources, `vault` with two contracts and `solmate` with one, and an
+/// empty `out/`.
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(
+        &root,
+        &[
+            ("vault/src/Vault.sol", "contract Vault {}\n"),
+            ("vault/src/Router.sol", "contract Router {}\n"),
+            ("solmate/src/ERC20.sol", "contract ERC20 {}\n"),
+        ],
+    );
+    fs::create_dir_all(root.join("out")).unwrap();
+    root
+}
+
+/// Runs solscrape in `root` with `args` and the destination `out`.
+fn solscrape(root: &Path, args: &[&str]) -> Output {
+    common::solscrape()
+        .args(args)
+        .arg("out")
+        .current_dir(root)
+        .output()
+        .unwrap()
+}
+
+/// The sources go into one output, each under its name and banner, and the
+/// summary counts the files of each.
+#[test]
+fn test_sources_combined() {
+    let root = fixture("combined");
+    let output = solscrape(&root, &["--source", "vault", "--source", "solmate"]);
+    assert!(output.status.success(), "{:?}", output);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("   Sources:         vault: 2 files, solmate: 1 file\n"),
+        "{stdout}"
+    );
+    assert!(stdout.contains("vault/src/Router.sol"), "{stdout}");
+
+    let scraped = fs::read_to_string(root.join("out/vault_solmate_scraped.sol")).unwrap();
+    let vault = scraped.find("// Source: vault").unwrap();
+    let router = scraped.find("contract Router {}").unwrap();
+    let solmate = scraped.find("// Source: solmate").unwrap();
+    let erc20 = scraped.find("contract ERC20 {}").unwrap();
+    assert!(
+        vault < router && router < solmate && solmate < erc20,
+        "{scraped}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// With `--split-by-source` each source gets its own file; a source that
+/// fails is reported and fails the run once the others are written, and
+/// with `--strict` ends it before anything is written.
+#[test]
+fn test_sources_split_with_failure() {
+    let root = fixture("split");
+    let args = [
+        "--local",
+        "--split-by-source",
+        "--source",
+        "vault",
+        "--source",
+        "missing",
+        "--source",
+        "solmate",
+    ];
+    let output = solscrape(&root, &args);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("missing failed: Source path does not exist: missing"),
+        "{stderr}"
+    );
+    assert!(
+        stderr.contains("Error: 1 of 3 sources failed; see the warnings above"),
+        "{stderr}"
+    );
+    let vault = fs::read_to_string(root.join("out/vault_scraped.sol")).unwrap();
+    assert!(vault.contains("contract Vault {}"), "{vault}");
+    assert!(!vault.contains("// Source:"), "{vault}");
+    let solmate = fs::read_to_string(root.join("out/solmate_scraped.sol")).unwrap();
+    assert!(solmate.contains("contract ERC20 {}"), "{solmate}");
+
+    fs::remove_dir_all(root.join("out")).unwrap();
+    let output = solscrape(&root, &[&args[..], &["--strict"]].concat());
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Error: missing failed: Source path does not exist: missing"),
+        "{stderr}"
+    );
+    assert!(!root.join("out").exists());
+    let _ = fs::remove_dir_all(&root);
+}
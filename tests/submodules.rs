@@ -0,0 +1,107 @@
+//! End-to-end tests for initializing a clone's submodules with
+//! `--include-lib`, run against the built binary on a bare repository with
+//! one submodule that can be fetched and one that cannot.
+
+mod common;
+
+use common::git;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Creates `dep.git`, a bare repository with `src/Dep.sol`, and `repo.git`,
+/// whose `src/App.sol` sits next to the submodules `lib/dep` (pinned to
+/// `dep.git`'s tip) and `lib/gone` (at a host that never resolves).
+fn fixture(name: &str) -> PathBuf {
+    let root = common::fixture_dir(name);
+
+    let dep = root.join("dep");
+    fs::create_dir_all(dep.join("src")).unwrap();
+    fs::write(dep.join("src/Dep.sol"), "contract Dep {}\n").unwrap();
+    git(&dep, &["init", "-q", "-b", "main"]);
+    git(&dep, &["add", "."]);
+    git(&dep, &["commit", "-q", "-m", "dep"]);
+    let dep_commit = git(&dep, &["rev-parse", "HEAD"]);
+    let dep_bare = root.join("dep.git");
+    git(
+        &dep,
+        &["clone", "-q", "--bare", ".", dep_bare.to_str().unwrap()],
+    );
+
+    let work = root.join("work");
+    fs::create_dir_all(work.join("src")).unwrap();
+    fs::write(work.join("src/App.sol"), "contract App {}\n").unwrap();
+    fs::write(
+        work.join(".gitmodules"),
+        format!(
+            "[submodule \"lib/dep\"]\n\tpath = lib/dep\n\turl = file://{}\n\
+             [submodule \"lib/gone\"]\n\tpath = lib/gone\n\
+             \turl = https://example.invalid/acme/gone\n",
+            dep_bare.display()
+        ),
+    )
+    .unwrap();
+    git(&work, &["init", "-q", "-b", "main"]);
+    git(&work, &["add", "."]);
+    for (path, commit) in [
+        ("lib/dep", dep_commit.as_str()),
+        ("lib/gone", "0123456789abcdef0123456789abcdef01234567"),
+    ] {
+        let cacheinfo = format!("160000,{},{}", commit, path);
+        git(&work, &["update-index", "--add", "--cacheinfo", &cacheinfo]);
+    }
+    git(&work, &["commit", "-q", "-m", "init"]);
+    let bare = root.join("repo.git");
+    git(&root, &["init", "-q", "--bare", bare.to_str().unwrap()]);
+    git(&work, &["push", "-q", bare.to_str().unwrap(), "main"]);
+    git(&bare, &["symbolic-ref", "HEAD", "refs/heads/main"]);
+    fs::create_dir_all(root.join("cache")).unwrap();
+    root
+}
+
+/// Scrapes the fixture's `repo.git` into `out/` with `--include-lib`,
+/// allowing git's file transport for the local submodule.
+fn scrape(root: &Path) -> Output {
+    let url = format!("file://{}", root.join("repo.git").display());
+    common::solscrape()
+        .args([
+            url.as_str(),
+            root.join("out").to_str().unwrap(),
+            "--min-temp-space",
+            "0",
+            "--include-lib",
+            "--foundry-cache",
+            root.join("cache").to_str().unwrap(),
+        ])
+        .env("GIT_TERMINAL_PROMPT", "0")
+        .env("GIT_CONFIG_COUNT", "1")
+        .env("GIT_CONFIG_KEY_0", "protocol.file.allow")
+        .env("GIT_CONFIG_VALUE_0", "always")
+        .output()
+        .unwrap()
+}
+
+/// The submodule that can be fetched is scraped; the other is warned about
+/// without failing the run, and the count shows in the progress output.
+#[test]
+fn test_include_lib_initializes_submodules() {
+    let root = fixture("init");
+    let output = scrape(&root);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{stderr}");
+    assert!(
+        stderr.contains("Submodule init failed for lib/gone"),
+        "{stderr}"
+    );
+    assert!(!stderr.contains("for lib/dep"), "{stderr}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Initialized 1 of 2 submodules"), "{stdout}");
+
+    let scraped = fs::read_to_string(root.join("out/repo_scraped.sol")).unwrap();
+    assert!(scraped.contains("contract App {}"), "{scraped}");
+    assert!(
+        scraped.contains("// File: lib/dep/src/Dep.sol"),
+        "{scraped}"
+    );
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,107 @@
+//! End-to-end tests for `--vcs-friendly`, run against the built binary.
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+fn fixture(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let root = common::fixture_dir(name);
+    common::write_tree(&root.join("source"), files);
+    root
+}
+
+/// Scrapes the fixture into `out/vcs_scraped.sol` with `--vcs-friendly`.
+fn scrape(root: &Path) -> Output {
+    let output = common::solscrape()
+        .args([
+            root.join("source").to_str().unwrap(),
+            root.join("out").to_str().unwrap(),
+            "--local",
+            "-o",
+            "vcs",
+            "--vcs-friendly",
+            "--log-file",
+            root.join("run.log").to_str().unwrap(),
+        ])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    output
+}
+
+/// Verifies normalized output and the change report on stderr and in the log.
+#[test]
+fn test_vcs_friendly_reports_changes() {
+    let root = fixture(
+        "report",
+        &[
+            ("src/A.sol", "contract A {\r\n\tuint x;   \r\n}\r\n"),
+            ("src/B.sol", "contract B {}\n"),
+        ],
+    );
+
+    let first = scrape(&root);
+    let stderr = String::from_utf8_lossy(&first.stderr);
+    assert!(
+        stderr.contains("Changes vs previous output: no previous file at"),
+        "{}",
+        stderr
+    );
+    let output = fs::read_to_string(root.join("out/vcs_scraped.sol")).unwrap();
+    assert!(
+        output.contains("contract A {\n    uint x;\n}"),
+        "{}",
+        output
+    );
+    assert!(!output.contains('\r') && !output.contains('\t'));
+
+    fs::remove_file(root.join("source/src/B.sol")).unwrap();
+    common::write_tree(
+        &root.join("source"),
+        &[
+            ("src/A.sol", "contract A {\n    uint x;\n    uint y;\n}\n"),
+            ("src/C.sol", "contract C {}\n"),
+        ],
+    );
+    let second = scrape(&root);
+    let stderr = String::from_utf8_lossy(&second.stderr);
+    let stdout = String::from_utf8_lossy(&second.stdout);
+    for line in [
+        "Changes vs previous output: 3 files, +2 -1",
+        "  src/A.sol  +1 -0",
+        "  src/B.sol  removed, -1",
+        "  src/C.sol  added, +1",
+    ] {
+        assert!(stderr.contains(line), "{}", stderr);
+    }
+    assert!(!stdout.contains("Changes vs previous output"), "{}", stdout);
+    let log = fs::read_to_string(root.join("run.log")).unwrap();
+    assert!(
+        log.contains("REPORT") && log.contains("src/C.sol  added, +1"),
+        "{}",
+        log
+    );
+
+    // A third run over the same sources changes nothing
+    let third = scrape(&root);
+    assert!(String::from_utf8_lossy(&third.stderr).contains("Changes vs previous output: none"));
+    let _ = fs::remove_dir_all(&root);
+}
+
+/// Verifies that options producing unstable output are rejected.
+#[test]
+fn test_vcs_friendly_rejects_unstable_options() {
+    for flag in ["--stream", "--run-dir"] {
+        let output = common::solscrape()
+            .args(["./x", "--local", "--vcs-friendly", flag])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert!(
+            String::from_utf8_lossy(&output.stderr)
+                .contains("--vcs-friendly cannot be used with --stream, --run-dir, or --compare")
+        );
+    }
+}